@@ -1,10 +1,16 @@
-use crate::cli::index;
-use crate::shared::{self, CacheManager, DisplayOptions, SearchEngine, SearchQuery, SortOrder};
+use crate::cli::{import, index, snapshot};
+use crate::mcp::daemon::DaemonClient;
+use crate::shared::terminal::ColorChoice;
+use crate::shared::{
+    self, CacheManager, DisplayOptions, SearchEngine, SearchQuery, SessionSort, SortOrder,
+    WorklogPeriod, parse_date,
+};
 use anyhow::Result;
-use chrono::{NaiveDate, TimeZone, Utc};
-use clap::{Subcommand, ValueEnum};
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand, ValueEnum};
 use regex::Regex;
 use std::collections::HashMap;
+use std::io::IsTerminal;
 use std::path::Path;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
@@ -17,54 +23,63 @@ pub enum CliCommands {
         action: Option<IndexAction>,
     },
     /// Search conversations (auto-indexes if needed)
-    Search {
+    Search(Box<SearchArgs>),
+    /// Save a search under a name (replaces an existing one)
+    SaveSearch {
+        /// Name to save the search under
+        name: String,
         /// Search query
         query: String,
         /// Filter by project
         #[arg(long)]
         project: Option<String>,
-        /// Filter by session ID (prefix match)
+        /// Filter by source host ("local" for this machine)
         #[arg(long)]
-        session: Option<String>,
-        /// Results limit
-        #[arg(long, default_value = "10")]
-        limit: usize,
-        /// Context lines before and after match (like grep -C)
-        #[arg(short = 'C', default_value = "2")]
-        context: usize,
-        /// Context lines before match (like grep -B)
-        #[arg(short = 'B')]
-        ctx_before: Option<usize>,
-        /// Context lines after match (like grep -A)
-        #[arg(short = 'A')]
-        ctx_after: Option<usize>,
-        /// Exclude projects by name
+        host: Option<String>,
+        /// Filter by git branch
         #[arg(long)]
-        exclude_project: Vec<String>,
-        /// Exclude results matching regex patterns
+        branch: Option<String>,
+        /// Only the last N days, relative to when the search is run
         #[arg(long)]
-        exclude_pattern: Vec<String>,
-        /// Sort order
-        #[arg(long, value_enum, default_value = "relevance")]
-        sort: SortArg,
-        /// Results after date (YYYY-MM-DD or ISO 8601)
+        within_days: Option<u32>,
+        /// Fixed start date (YYYY-MM-DD or ISO 8601)
         #[arg(long)]
         after: Option<String>,
-        /// Results before date (YYYY-MM-DD or ISO 8601)
+        /// Fixed end date (YYYY-MM-DD or ISO 8601)
         #[arg(long)]
         before: Option<String>,
-        /// Include extra content types
+        /// Sort order
         #[arg(long, value_enum)]
-        include: Vec<IncludeArg>,
-        /// Characters shown per message (0 = full content)
-        #[arg(long, default_value = "300")]
-        truncate: usize,
+        sort: Option<SortArg>,
+        /// Default number of results
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Include flags or presets, as for search
+        #[arg(long, value_delimiter = ',')]
+        include: Vec<String>,
+    },
+    /// Run a saved search, or list saved searches when no name is given
+    RunSearch {
+        /// Saved search name
+        name: Option<String>,
+        /// Override the saved result limit
+        #[arg(long)]
+        limit: Option<usize>,
     },
     /// Show technology topics and their usage across conversations
     Topics {
         /// Filter by project
         #[arg(long)]
         project: Option<String>,
+        /// Only work in this directory or below it
+        #[arg(long)]
+        dir: Option<String>,
+        /// Messages after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Messages before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
         /// Results limit
         #[arg(long, default_value = "20")]
         limit: usize,
@@ -74,10 +89,13 @@ pub enum CliCommands {
         /// Filter by project
         #[arg(long)]
         project: Option<String>,
+        /// Only work in this directory or below it, broken down by subdirectory
+        #[arg(long)]
+        dir: Option<String>,
     },
     /// View specific session conversations
     Session {
-        /// Session ID to view
+        /// Session ID or unique prefix to view
         session_id: String,
         /// Show full content (not just snippets)
         #[arg(long)]
@@ -95,10 +113,258 @@ pub enum CliCommands {
         #[arg(short = 'A')]
         after: Option<usize>,
     },
-    /// Summarize a session using Claude (runs in jailed empty dir)
+    /// List sessions with message counts and last retrieval time
+    Sessions {
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Sort order
+        #[arg(long, value_enum, default_value = "last-active")]
+        sort: SessionSortArg,
+        /// List sessions continued after compaction as one, with the continuations after 🔗
+        #[arg(long)]
+        follow_continuations: bool,
+        /// Results limit
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+    /// List indexed projects with message and session counts
+    Projects {
+        /// Only projects matching this name, substring or glob
+        #[arg(long)]
+        project: Option<String>,
+        /// Results limit
+        #[arg(long, default_value = "50")]
+        limit: usize,
+    },
+    /// Manage how history is grouped into projects
+    Project {
+        #[command(subcommand)]
+        action: ProjectCommand,
+    },
+    /// Report API token usage and estimated cost per model, project and day
+    Usage {
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Usage after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Usage before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
+        /// Max projects listed
+        #[arg(long, default_value = "10")]
+        limit: usize,
+        /// Max days listed, most recent first
+        #[arg(long, default_value = "14")]
+        days: usize,
+    },
+    /// Report which Claude tools were called most and how often they failed
+    ToolUsage {
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Calls after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Calls before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
+        /// Max tools and projects listed
+        #[arg(long, default_value = "15")]
+        limit: usize,
+    },
+    /// Cluster recurring tool errors and show where they were resolved
+    Errors {
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Errors after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Errors before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
+        /// Only errors seen in at least this many sessions
+        #[arg(long, default_value = "1")]
+        min_sessions: usize,
+        /// Max error signatures listed
+        #[arg(long, default_value = "15")]
+        limit: usize,
+    },
+    /// Decisions stated in sessions ("we'll go with", "decided to") and done checklist items
+    Decisions {
+        /// Only this session (ID or prefix)
+        #[arg(long)]
+        session: Option<String>,
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Decisions after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Decisions before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
+        /// Max decisions listed
+        #[arg(long, default_value = "30")]
+        limit: usize,
+    },
+    /// Compare two sessions: prompts, tools, commands, files modified and outcome
+    DiffSessions {
+        /// First session ID or prefix
+        session_a: String,
+        /// Second session ID or prefix
+        session_b: String,
+    },
+    /// Print the full text of messages by id
+    Messages {
+        /// Message ids or unique prefixes (from 💬 in results)
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Replace `[result omitted: N chars]` with the tool output from the JSONL
+        #[arg(long)]
+        expand_tool_results: bool,
+    },
+    /// Bookmark a message with tags and a note, or remove its bookmark
+    Bookmark {
+        /// Message id or prefix (from 💬 in results)
+        id: String,
+        /// Tag to add (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Note to keep with the bookmark; empty clears it
+        #[arg(long)]
+        note: Option<String>,
+        /// Remove the bookmark instead
+        #[arg(long, conflicts_with_all = ["tags", "note"])]
+        remove: bool,
+    },
+    /// Attach a note to a message, shown under it in search and session views
+    Annotate {
+        /// Message id or prefix (from 💬 in results)
+        id: String,
+        /// Note to add after the message's existing notes
+        #[arg(required_unless_present = "clear")]
+        note: Option<String>,
+        /// Remove the message's notes instead
+        #[arg(long, conflicts_with = "note")]
+        clear: bool,
+    },
+    /// List bookmarked messages, newest first
+    Bookmarks {
+        /// Only bookmarks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Show recorded searches: latest, most frequent, or those that found nothing
+    QueryHistory {
+        /// Which searches to list
+        #[arg(long, value_enum, default_value = "recent")]
+        view: QueryHistoryViewArg,
+        /// Results limit
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+    /// Complete a word from the indexed vocabulary, most frequent terms first
+    SuggestTerms {
+        /// Start of the word
+        prefix: String,
+        /// Results limit
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+    /// Open a message in $VISUAL/$EDITOR at its JSONL line, or print its location
+    Open {
+        /// Message id or prefix (from 💬 in results)
+        id: String,
+        /// Print the file and line instead of launching the editor
+        #[arg(long)]
+        print: bool,
+    },
+    /// List sessions whose Read/Edit/Write tool calls touched a file
+    FileHistory {
+        /// File path, absolute or relative (matched by trailing components)
+        path: String,
+        /// Only sessions that edited or wrote the file
+        #[arg(long)]
+        modified_only: bool,
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Max sessions
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+    /// List Bash commands run in past sessions, deduplicated, with exit status
+    Commands {
+        /// Only commands containing this text (case-insensitive)
+        pattern: Option<String>,
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Filter by session ID (prefix match)
+        #[arg(long)]
+        session: Option<String>,
+        /// Max distinct commands (most recent kept)
+        #[arg(long, default_value = "30")]
+        limit: usize,
+    },
+    /// Extract fenced code blocks from a session or from search matches
+    CodeBlocks {
+        /// Search query selecting messages (ignored with --session)
+        query: Option<String>,
+        /// Session ID to extract from (prefix match)
+        #[arg(long)]
+        session: Option<String>,
+        /// Filter search matches by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Only blocks tagged with this language
+        #[arg(long)]
+        language: Option<String>,
+        /// Max code blocks
+        #[arg(long, default_value = "10")]
+        limit: usize,
+    },
+    /// Summarize a session: Claude Code's own summary when it wrote one,
+    /// otherwise generated by the configured summarizer, or with Claude
+    /// (runs in jailed empty dir)
     Summary {
-        /// Session ID to summarize
+        /// Session ID or unique prefix to summarize
+        session_id: String,
+        /// Generate a fresh summary even if Claude Code wrote one
+        #[arg(long)]
+        generate: bool,
+        /// Extractive summary built offline (key asks, decisions, final
+        /// answers, files changed) instead of running Claude
+        #[arg(long, conflicts_with = "generate")]
+        local: bool,
+    },
+    /// Keep a summary of a session so later searches match it (summary:
+    /// queries, session titles); storing again replaces it
+    StoreSummary {
+        /// Session ID or unique prefix the summary is of
         session_id: String,
+        /// Summary text, read from stdin when absent or `-`
+        summary: Option<String>,
+        /// How it was made, kept with it
+        #[arg(long, default_value = shared::AGENT_SUMMARY_SOURCE)]
+        source: String,
+    },
+    /// Import JSONL history from another machine (directory, .tar or .tar.zst)
+    Import {
+        /// Directory or tarball containing Claude `projects/` JSONL files
+        source: std::path::PathBuf,
+        /// Host name to tag imported history with (default: source file name)
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// Export reports built from the index
+    Export {
+        #[command(subcommand)]
+        what: ExportCommand,
     },
     /// Cache management
     Cache {
@@ -111,21 +377,254 @@ pub enum CliCommands {
         shell: clap_complete::Shell,
     },
     /// Run as MCP server
-    Mcp,
+    Mcp {
+        /// Transport to serve MCP over
+        #[arg(long, value_enum, default_value = "stdio")]
+        transport: TransportArg,
+        /// Port for the HTTP transport (binds to localhost)
+        #[arg(long, default_value = "8765")]
+        port: u16,
+    },
+    /// Run the shared index daemon; CLI and stdio MCP proxy to it while it runs
+    Daemon,
     /// Register with Claude MCP
     Install {
         /// Use project scope instead of user scope
         #[arg(long)]
         project: bool,
+        /// Print the `mcpServers` JSON entry instead of running `claude mcp add`
+        #[arg(long)]
+        print: bool,
+    },
+    /// Remove the Claude MCP registration made by install
+    Uninstall {
+        /// Remove the project scope registration instead of the user one
+        #[arg(long)]
+        project: bool,
     },
 }
 
+#[derive(Args)]
+pub struct SearchArgs {
+    /// Search query
+    pub query: String,
+    /// Filter by project: name, substring or glob (repeat to search several)
+    #[arg(long)]
+    pub project: Vec<String>,
+    /// Filter by session ID (prefix match)
+    #[arg(long)]
+    pub session: Option<String>,
+    /// Filter by source host of imported history ("local" for this machine)
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Filter by git branch checked out when the message was written
+    #[arg(long)]
+    pub branch: Option<String>,
+    /// Filter by the tool the history came from (claude-code, codex, gemini, claude-desktop, openai)
+    #[arg(long)]
+    pub source: Option<String>,
+    /// Results limit (0 = no limit)
+    #[arg(long, default_value = "10")]
+    pub limit: usize,
+    /// Output format
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+    /// With --format jsonl, print each result as soon as it is found, in
+    /// index order instead of --sort (pair with --limit 0 for every match)
+    #[arg(long)]
+    pub stream: bool,
+    /// Context lines before and after match (like grep -C)
+    #[arg(short = 'C', default_value = "2")]
+    pub context: usize,
+    /// Context lines before match (like grep -B)
+    #[arg(short = 'B')]
+    pub ctx_before: Option<usize>,
+    /// Context lines after match (like grep -A)
+    #[arg(short = 'A')]
+    pub ctx_after: Option<usize>,
+    /// Exclude projects by name
+    #[arg(long)]
+    pub exclude_project: Vec<String>,
+    /// Exclude results matching regex patterns
+    #[arg(long)]
+    pub exclude_pattern: Vec<String>,
+    /// Sort order
+    #[arg(long, value_enum, default_value = "relevance")]
+    pub sort: SortArg,
+    /// Halve relevance for every this many days of message age (0 = off)
+    #[arg(long, value_name = "DAYS")]
+    pub recency_boost: Option<f64>,
+    /// Results after date (YYYY-MM-DD or ISO 8601)
+    #[arg(long)]
+    pub after: Option<String>,
+    /// Results before date (YYYY-MM-DD or ISO 8601)
+    #[arg(long)]
+    pub before: Option<String>,
+    /// Only sessions still active after this date, searched whole
+    #[arg(long, value_name = "DATE")]
+    pub active_after: Option<String>,
+    /// Only sessions already active before this date, searched whole
+    #[arg(long, value_name = "DATE")]
+    pub active_before: Option<String>,
+    /// Include extra content: thinking, tools, current_session, or a preset
+    /// (everything, prose_only, debugging, or one from config)
+    #[arg(long, value_delimiter = ',')]
+    pub include: Vec<String>,
+    /// Characters shown per message (0 = full content)
+    #[arg(long, default_value = "300")]
+    pub truncate: usize,
+    /// Characters per snippet of the matched message (default: --truncate)
+    #[arg(long)]
+    pub snippet_length: Option<usize>,
+    /// Separate regions of matches shown from a long matched message
+    #[arg(long, default_value = "1")]
+    pub max_snippets: usize,
+    /// Show every match instead of one per session with the ids of the others
+    #[arg(long)]
+    pub no_dedupe_sessions: bool,
+    /// Show near-duplicate matches instead of collapsing them into the first
+    #[arg(long)]
+    pub show_duplicates: bool,
+}
+
 #[derive(Subcommand)]
 pub enum CacheAction {
     /// Show cache statistics
     Info,
     /// Clear cache and rebuild
     Clear,
+    /// Remove sessions with no activity or retrieval within N days from the index
+    Prune {
+        /// Age threshold in days (last message and last retrieval)
+        #[arg(long)]
+        older_than: u32,
+        /// Only prune sessions that were never retrieved
+        #[arg(long)]
+        never_accessed: bool,
+        /// List candidates without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Snapshot the index and cache metadata to a .tar.zst for another machine
+    Export {
+        /// Output archive path
+        file: std::path::PathBuf,
+    },
+    /// Replace the index with a snapshot made by `cache export`
+    Import {
+        /// Archive created by `cache export`
+        file: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProjectCommand {
+    /// Record that a repo moved, filing the old path's history under the new one
+    Merge {
+        /// Old project path, or the name of an indexed project
+        old: String,
+        /// New project path, or the name of an indexed project
+        new: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExportCommand {
+    /// Sessions, messages, time and topics per project and day, week or month
+    Worklog {
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Sessions started after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Sessions started before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
+        /// Period per row
+        #[arg(long, value_enum, default_value = "day")]
+        by: WorklogPeriodArg,
+        /// Table format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: WorklogFormat,
+    },
+    /// Static HTML site of the history with client-side search, for archiving
+    Site {
+        /// Directory to write the site to
+        dir: std::path::PathBuf,
+        /// Filter by project
+        #[arg(long)]
+        project: Option<String>,
+        /// Sessions started after date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        after: Option<String>,
+        /// Sessions started before date (YYYY-MM-DD or ISO 8601)
+        #[arg(long)]
+        before: Option<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum QueryHistoryViewArg {
+    #[default]
+    Recent,
+    Frequent,
+    Failed,
+}
+
+impl From<QueryHistoryViewArg> for shared::QueryHistoryView {
+    fn from(v: QueryHistoryViewArg) -> Self {
+        match v {
+            QueryHistoryViewArg::Recent => shared::QueryHistoryView::Recent,
+            QueryHistoryViewArg::Frequent => shared::QueryHistoryView::Frequent,
+            QueryHistoryViewArg::Failed => shared::QueryHistoryView::Failed,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum WorklogPeriodArg {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl From<WorklogPeriodArg> for WorklogPeriod {
+    fn from(p: WorklogPeriodArg) -> Self {
+        match p {
+            WorklogPeriodArg::Day => WorklogPeriod::Day,
+            WorklogPeriodArg::Week => WorklogPeriod::Week,
+            WorklogPeriodArg::Month => WorklogPeriod::Month,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum WorklogFormat {
+    #[default]
+    Markdown,
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum SessionSortArg {
+    #[default]
+    LastActive,
+    LastAccessed,
+    Oldest,
+    Messages,
+}
+
+impl From<SessionSortArg> for SessionSort {
+    fn from(s: SessionSortArg) -> Self {
+        match s {
+            SessionSortArg::LastActive => SessionSort::LastActive,
+            SessionSortArg::LastAccessed => SessionSort::LastAccessed,
+            SessionSortArg::Oldest => SessionSort::Oldest,
+            SessionSortArg::Messages => SessionSort::Messages,
+        }
+    }
 }
 
 #[derive(ValueEnum, Clone, Default)]
@@ -136,10 +635,62 @@ pub enum SortArg {
     DateAsc,
 }
 
-#[derive(ValueEnum, Clone, PartialEq)]
-pub enum IncludeArg {
-    Thinking,
-    Tools,
+#[derive(ValueEnum, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Compact human-readable results
+    #[default]
+    Text,
+    /// One JSON object per result
+    Jsonl,
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum ColorArg {
+    /// Follow HYPERLINKS, NO_COLOR and CLICOLOR, then detect the terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<ColorArg> for ColorChoice {
+    fn from(c: ColorArg) -> Self {
+        match c {
+            ColorArg::Auto => ColorChoice::Auto,
+            ColorArg::Always => ColorChoice::Always,
+            ColorArg::Never => ColorChoice::Never,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum TransportArg {
+    /// JSON-RPC over stdin/stdout (spawned per client)
+    #[default]
+    Stdio,
+    /// Streamable HTTP on localhost (shared by multiple clients)
+    Http,
+}
+
+impl SessionSortArg {
+    fn as_str(self) -> &'static str {
+        match self {
+            SessionSortArg::LastActive => "last_active",
+            SessionSortArg::LastAccessed => "last_accessed",
+            SessionSortArg::Oldest => "oldest",
+            SessionSortArg::Messages => "messages",
+        }
+    }
+}
+
+impl SortArg {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortArg::Relevance => "relevance",
+            SortArg::DateDesc => "date_desc",
+            SortArg::DateAsc => "date_asc",
+        }
+    }
 }
 
 impl From<SortArg> for SortOrder {
@@ -158,9 +709,20 @@ pub enum IndexAction {
     #[default]
     Status,
     /// Force full rebuild of the index
-    Rebuild,
+    Rebuild {
+        /// List the files a rebuild would index, with estimated entries and time
+        #[arg(long)]
+        dry_run: bool,
+        /// Rebuild without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
     /// Clean up deleted entries from index
-    Vacuum,
+    Vacuum {
+        /// Rebuild without asking for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
 }
 
 pub fn setup_logging(verbose: u8) {
@@ -188,66 +750,209 @@ pub fn run_cli(verbose: u8, command: CliCommands) -> Result<()> {
             let index_path = config.get_cache_dir()?;
             match action.unwrap_or_default() {
                 IndexAction::Status => index::show_status(&index_path)?,
-                IndexAction::Rebuild => index::rebuild(&index_path)?,
-                IndexAction::Vacuum => index::vacuum(&index_path)?,
+                IndexAction::Rebuild { dry_run: true, .. } => index::rebuild_plan(&index_path)?,
+                IndexAction::Rebuild { yes, .. } => index::rebuild(&index_path, yes)?,
+                IndexAction::Vacuum { yes } => index::vacuum(&index_path, yes)?,
             }
         }
         CliCommands::Completions { .. } => unreachable!("Completions handled in main"),
-        CliCommands::Mcp => unreachable!("MCP handled in main"),
-        CliCommands::Search {
-            query,
-            project,
-            session,
-            limit,
-            context,
-            ctx_before,
-            ctx_after,
-            exclude_project,
-            exclude_pattern,
-            sort,
-            after,
-            before,
-            include,
-            truncate,
-        } => {
+        CliCommands::Mcp { .. } => unreachable!("MCP handled in main"),
+        CliCommands::Daemon => unreachable!("Daemon handled in main"),
+        CliCommands::Search(args) => {
+            let SearchArgs {
+                query,
+                project,
+                session,
+                host,
+                branch,
+                source,
+                limit,
+                format,
+                stream,
+                context,
+                ctx_before,
+                ctx_after,
+                exclude_project,
+                exclude_pattern,
+                sort,
+                after,
+                before,
+                active_after,
+                active_before,
+                include,
+                truncate,
+                snippet_length,
+                max_snippets,
+                no_dedupe_sessions,
+                show_duplicates,
+                recency_boost,
+            } = *args;
+            if stream && format != OutputFormat::Jsonl {
+                anyhow::bail!("--stream needs --format jsonl");
+            }
+            let cb = ctx_before.unwrap_or(context);
+            let ca = ctx_after.unwrap_or(context);
+            let mut include = shared::get_config().search.expand_include(&include)?;
+            // The daemon only renders text pages
+            let daemon = if format == OutputFormat::Text && limit > 0 {
+                DaemonClient::connect_for("search_conversations")
+            } else {
+                None
+            };
+            if let Some(mut client) = daemon {
+                // CLI searches never hide the caller's own session
+                include.push("current_session".to_string());
+                let args = serde_json::json!({
+                    "query": query,
+                    "project": project,
+                    "session": session,
+                    "host": host,
+                    "branch": branch,
+                    "source": source,
+                    "limit": limit,
+                    "-B": cb,
+                    "-A": ca,
+                    "exclude_projects": exclude_project,
+                    "exclude_patterns": exclude_pattern,
+                    "sort_by": sort.as_str(),
+                    "after": after,
+                    "before": before,
+                    "session_active_between": (active_after.is_some() || active_before.is_some())
+                        .then(|| serde_json::json!({ "after": active_after, "before": active_before })),
+                    "include": include,
+                    "truncate_length": truncate,
+                    "snippet_length": snippet_length,
+                    "max_snippets_per_message": max_snippets,
+                    "dedupe_sessions": !no_dedupe_sessions,
+                    "collapse_duplicates": !show_duplicates,
+                    "recency_boost": recency_boost,
+                });
+                return print_tool_output(client.call_tool("search_conversations", args)?);
+            }
+
             let config = shared::get_config();
             let index_path = config.get_cache_dir()?;
             shared::auto_index(&index_path)?;
-            let cb = ctx_before.unwrap_or(context);
-            let ca = ctx_after.unwrap_or(context);
+            let (query, uuid_filter) = shared::bookmark_filter(&query)?;
             let opts = SearchOpts {
                 query,
-                project,
+                projects: project,
                 session,
+                host,
+                branch,
+                source,
+                uuid_filter,
                 limit,
                 context_before: cb,
                 context_after: ca,
                 exclude_projects: exclude_project,
                 exclude_patterns: exclude_pattern,
+                dedupe_sessions: !no_dedupe_sessions,
+                collapse_duplicates: !show_duplicates,
+                recency_boost,
                 sort: sort.into(),
                 after: after.as_deref().map(parse_date).transpose()?,
                 before: before.as_deref().map(parse_date).transpose()?,
+                active_after: active_after.as_deref().map(parse_date).transpose()?,
+                active_before: active_before.as_deref().map(parse_date).transpose()?,
                 display: DisplayOptions {
-                    include_thinking: include.contains(&IncludeArg::Thinking),
-                    include_tools: include.contains(&IncludeArg::Tools),
+                    include_thinking: include.iter().any(|i| i == "thinking"),
+                    include_tools: include.iter().any(|i| i == "tools"),
                     truncate_length: truncate,
+                    snippet_length: snippet_length.unwrap_or(0),
+                    max_snippets,
                 },
             };
-            search_conversations(&index_path, opts)?;
+            if stream {
+                stream_search(&index_path, opts)?;
+            } else {
+                search_conversations(&index_path, opts, format)?;
+            }
+        }
+        CliCommands::SaveSearch {
+            name,
+            query,
+            project,
+            host,
+            branch,
+            within_days,
+            after,
+            before,
+            sort,
+            limit,
+            include,
+        } => {
+            // Validate now rather than when the search is replayed
+            shared::rewrite_field_query(&shared::split_bookmarked(&query)?.0)?;
+            for date in after.iter().chain(before.iter()) {
+                parse_date(date)?;
+            }
+            shared::get_config().search.expand_include(&include)?;
+            let search = shared::SavedSearch {
+                query,
+                project,
+                host,
+                branch,
+                within_days,
+                after,
+                before,
+                sort_by: sort.map(|s| s.as_str().to_string()),
+                limit,
+                include,
+            };
+            let replaced = shared::save_search(&name, &search)?;
+            outln!(
+                "{} saved search '{}': {}",
+                if replaced { "Updated" } else { "Added" },
+                name,
+                search.describe()
+            );
         }
-        CliCommands::Topics { project, limit } => {
+        CliCommands::RunSearch { name, limit } => {
+            if let Some(mut client) = DaemonClient::connect_for("run_saved_search") {
+                let args = serde_json::json!({ "name": name, "limit": limit });
+                return print_tool_output(client.call_tool("run_saved_search", args)?);
+            }
+
+            let searches = shared::load_saved_searches()?;
+            let Some(name) = name else {
+                out!("{}", shared::format_saved_searches(&searches));
+                return Ok(());
+            };
+            let search = searches
+                .get(&name)
+                .ok_or_else(|| anyhow::anyhow!("No saved search named '{}'", name))?;
+
             let config = shared::get_config();
             let index_path = config.get_cache_dir()?;
             shared::auto_index(&index_path)?;
-            show_topics(&index_path, project, limit)?;
+            search_conversations(
+                &index_path,
+                saved_search_opts(search, limit)?,
+                OutputFormat::Text,
+            )?;
         }
-        CliCommands::Stats { project } => {
+        CliCommands::Topics {
+            project,
+            dir,
+            after,
+            before,
+            limit,
+        } => {
             let config = shared::get_config();
             let index_path = config.get_cache_dir()?;
             shared::auto_index(&index_path)?;
-            show_stats(&index_path, project)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            show_topics(&index_path, project, dir, after, before, limit)?;
         }
-        CliCommands::Session {
+        CliCommands::Stats { project, dir } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            show_stats(&index_path, project, dir)?;
+        }
+        CliCommands::Session {
             session_id,
             full,
             center,
@@ -262,11 +967,398 @@ pub fn run_cli(verbose: u8, command: CliCommands) -> Result<()> {
             let ctx_after = after.unwrap_or(context);
             view_session(&index_path, session_id, full, center, ctx_before, ctx_after)?;
         }
-        CliCommands::Summary { session_id } => {
+        CliCommands::Sessions {
+            project,
+            sort,
+            follow_continuations,
+            limit,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("list_sessions") {
+                let args = serde_json::json!({
+                    "project": project,
+                    "sort_by": sort.as_str(),
+                    "follow_continuations": follow_continuations,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("list_sessions", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            list_sessions(
+                &index_path,
+                project,
+                sort.into(),
+                follow_continuations,
+                limit,
+            )?;
+        }
+        CliCommands::Projects { project, limit } => {
+            if let Some(mut client) = DaemonClient::connect_for("list_projects") {
+                let args = serde_json::json!({
+                    "project": project,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("list_projects", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            list_projects(&index_path, project, limit)?;
+        }
+        CliCommands::Project {
+            action: ProjectCommand::Merge { old, new },
+        } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            merge_project(&index_path, &old, &new)?;
+        }
+        CliCommands::Usage {
+            project,
+            after,
+            before,
+            limit,
+            days,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("usage_report") {
+                let args = serde_json::json!({
+                    "project": project,
+                    "after": after,
+                    "before": before,
+                    "limit": limit,
+                    "days": days,
+                });
+                return print_tool_output(client.call_tool("usage_report", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            usage_report(&index_path, project, after, before, limit, days)?;
+        }
+        CliCommands::ToolUsage {
+            project,
+            after,
+            before,
+            limit,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("analyze_tool_usage") {
+                let args = serde_json::json!({
+                    "project": project,
+                    "after": after,
+                    "before": before,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("analyze_tool_usage", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            tool_usage(&index_path, project, after, before, limit)?;
+        }
+        CliCommands::Errors {
+            project,
+            after,
+            before,
+            min_sessions,
+            limit,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("analyze_errors") {
+                let args = serde_json::json!({
+                    "project": project,
+                    "after": after,
+                    "before": before,
+                    "min_sessions": min_sessions,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("analyze_errors", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            analyze_errors(&index_path, project, after, before, min_sessions, limit)?;
+        }
+        CliCommands::Decisions {
+            session,
+            project,
+            after,
+            before,
+            limit,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("find_decisions") {
+                let args = serde_json::json!({
+                    "session": session,
+                    "project": project,
+                    "after": after,
+                    "before": before,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("find_decisions", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            find_decisions(&index_path, session, project, after, before, limit)?;
+        }
+        CliCommands::DiffSessions {
+            session_a,
+            session_b,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("diff_sessions") {
+                let args = serde_json::json!({
+                    "session_a": session_a,
+                    "session_b": session_b,
+                });
+                return print_tool_output(client.call_tool("diff_sessions", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            diff_sessions(&index_path, &session_a, &session_b)?;
+        }
+        CliCommands::Messages {
+            ids,
+            expand_tool_results,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("get_messages") {
+                let args = serde_json::json!({
+                    "ids": ids,
+                    "expand_tool_results": expand_tool_results,
+                });
+                return print_tool_output(client.call_tool("get_messages", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            show_messages(&index_path, &ids, expand_tool_results)?;
+        }
+        CliCommands::Bookmark {
+            id,
+            tags,
+            note,
+            remove,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("bookmark_message") {
+                let args = serde_json::json!({
+                    "id": id,
+                    "tags": tags,
+                    "note": note,
+                    "remove": remove,
+                });
+                return print_tool_output(client.call_tool("bookmark_message", args)?);
+            }
+
             let config = shared::get_config();
             let index_path = config.get_cache_dir()?;
             shared::auto_index(&index_path)?;
-            summarize_session(&index_path, session_id)?;
+            bookmark_message(&index_path, &id, &tags, note.as_deref(), remove)?;
+        }
+        CliCommands::StoreSummary {
+            session_id,
+            summary,
+            source,
+        } => {
+            let summary = match summary.filter(|s| s != "-") {
+                Some(summary) => summary,
+                None => std::io::read_to_string(std::io::stdin())?,
+            };
+            if let Some(mut client) = DaemonClient::connect_for("store_session_summary") {
+                let args = serde_json::json!({
+                    "session_id": session_id,
+                    "summary": summary,
+                    "source": source,
+                });
+                return print_tool_output(client.call_tool("store_session_summary", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            store_summary(&index_path, &session_id, &summary, source)?;
+        }
+        CliCommands::Annotate { id, note, clear } => {
+            if let Some(mut client) = DaemonClient::connect_for("annotate_message") {
+                let args = serde_json::json!({
+                    "id": id,
+                    "note": note,
+                    "clear": clear,
+                });
+                return print_tool_output(client.call_tool("annotate_message", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            annotate_message(&index_path, &id, note.as_deref(), clear)?;
+        }
+        CliCommands::Bookmarks { tag } => {
+            if let Some(mut client) = DaemonClient::connect_for("list_bookmarks") {
+                let args = serde_json::json!({ "tag": tag });
+                return print_tool_output(client.call_tool("list_bookmarks", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            list_bookmarks(&index_path, tag.as_deref())?;
+        }
+        CliCommands::QueryHistory { view, limit } => {
+            let records = shared::QueryHistory::open_default()?.list()?;
+            out!(
+                "{}",
+                shared::format_query_history(&records, view.into(), limit)
+            );
+        }
+        CliCommands::SuggestTerms { prefix, limit } => {
+            if let Some(mut client) = DaemonClient::connect_for("suggest_terms") {
+                let args = serde_json::json!({
+                    "prefix": prefix,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("suggest_terms", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            suggest_terms(&index_path, &prefix, limit)?;
+        }
+        CliCommands::Open { id, print } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            open_message(&index_path, &id, print)?;
+        }
+        CliCommands::FileHistory {
+            path,
+            modified_only,
+            project,
+            limit,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("find_conversations_for_file") {
+                let args = serde_json::json!({
+                    "path": path,
+                    "modified_only": modified_only,
+                    "project": project,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("find_conversations_for_file", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            file_history(&index_path, &path, modified_only, project, limit)?;
+        }
+        CliCommands::Commands {
+            pattern,
+            project,
+            session,
+            limit,
+        } => {
+            if let Some(mut client) = DaemonClient::connect_for("extract_commands") {
+                let args = serde_json::json!({
+                    "pattern": pattern,
+                    "project": project,
+                    "session": session,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("extract_commands", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            extract_commands(&index_path, pattern, project, session, limit)?;
+        }
+        CliCommands::CodeBlocks {
+            query,
+            session,
+            project,
+            language,
+            limit,
+        } => {
+            if query.is_none() && session.is_none() {
+                anyhow::bail!("Provide a search query or --session");
+            }
+            if let Some(mut client) = DaemonClient::connect_for("get_code_blocks") {
+                let args = serde_json::json!({
+                    "query": query,
+                    "session_id": session,
+                    "project": project,
+                    "language": language,
+                    "limit": limit,
+                });
+                return print_tool_output(client.call_tool("get_code_blocks", args)?);
+            }
+
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            code_blocks(&index_path, query, session, project, language, limit)?;
+        }
+        CliCommands::Summary {
+            session_id,
+            generate,
+            local,
+        } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            if local {
+                local_summary(&index_path, &session_id)?;
+            } else {
+                summarize_session(&index_path, session_id, generate)?;
+            }
+        }
+        CliCommands::Export {
+            what:
+                ExportCommand::Worklog {
+                    project,
+                    after,
+                    before,
+                    by,
+                    format,
+                },
+        } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            export_worklog(&index_path, project, after, before, by.into(), format)?;
+        }
+        CliCommands::Export {
+            what:
+                ExportCommand::Site {
+                    dir,
+                    project,
+                    after,
+                    before,
+                },
+        } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            shared::auto_index(&index_path)?;
+            let after = after.as_deref().map(parse_date).transpose()?;
+            let before = before.as_deref().map(parse_date).transpose()?;
+            export_site(&index_path, &dir, project, after, before)?;
         }
         CliCommands::Cache { action } => {
             let config = shared::get_config();
@@ -274,304 +1366,1155 @@ pub fn run_cli(verbose: u8, command: CliCommands) -> Result<()> {
             match action {
                 CacheAction::Info => show_cache_info(&index_path)?,
                 CacheAction::Clear => clear_cache(&index_path)?,
+                CacheAction::Prune {
+                    older_than,
+                    never_accessed,
+                    dry_run,
+                } => prune_cache(&index_path, older_than, never_accessed, dry_run)?,
+                CacheAction::Export { file } => snapshot::export_index(&index_path, &file)?,
+                CacheAction::Import { file } => snapshot::import_index(&index_path, &file)?,
             }
         }
-        CliCommands::Install { project } => install(project)?,
+        CliCommands::Import { source, host } => {
+            let config = shared::get_config();
+            let index_path = config.get_cache_dir()?;
+            import::import_history(&index_path, &source, host)?;
+        }
+        CliCommands::Install { project, print } => install(project, print)?,
+        CliCommands::Uninstall { project } => uninstall(project)?,
+    }
+
+    Ok(())
+}
+
+/// Name the server is registered under with `claude mcp`
+const MCP_SERVER_NAME: &str = "claude-conversation-search";
+
+/// Registered name, suffixed with the profile so profiles can be added side by side
+fn mcp_server_name() -> String {
+    match shared::active_profile() {
+        Some(profile) => format!("{}-{}", MCP_SERVER_NAME, profile),
+        None => MCP_SERVER_NAME.to_string(),
+    }
+}
+
+fn mcp_scope(project_scope: bool) -> &'static str {
+    if project_scope { "project" } else { "user" }
+}
+
+fn install(project_scope: bool, print: bool) -> Result<()> {
+    use std::process::Command;
+
+    let exe = std::env::current_exe()?;
+    let exe_path = exe
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Invalid exe path"))?;
+
+    let name = mcp_server_name();
+    let env: HashMap<&str, String> = shared::active_profile()
+        .map(|profile| (shared::PROFILE_ENV, profile))
+        .into_iter()
+        .collect();
+
+    // For `.claude.json`, `.mcp.json` or another client's settings
+    if print {
+        let mut server = serde_json::json!({
+            "type": "stdio",
+            "command": exe_path,
+            "args": [],
+        });
+        if !env.is_empty() {
+            server["env"] = serde_json::json!(env);
+        }
+        let entry = serde_json::json!({ "mcpServers": { name: server } });
+        outln!("{}", serde_json::to_string_pretty(&entry)?);
+        return Ok(());
+    }
+
+    let scope = mcp_scope(project_scope);
+    let _ = Command::new("claude")
+        .args(["mcp", "remove", "-s", scope, &name])
+        .status();
+
+    let mut add = Command::new("claude");
+    add.args(["mcp", "add", "-s", scope]);
+    for (key, value) in &env {
+        add.args(["-e", &format!("{}={}", key, value)]);
+    }
+    let status = add.args([name.as_str(), exe_path]).status()?;
+
+    if !status.success() {
+        anyhow::bail!("claude mcp add failed");
+    }
+
+    outln!("{}", exe_path);
+    Ok(())
+}
+
+fn uninstall(project_scope: bool) -> Result<()> {
+    let name = mcp_server_name();
+    let status = std::process::Command::new("claude")
+        .args(["mcp", "remove", "-s", mcp_scope(project_scope), &name])
+        .status()?;
+
+    if !status.success() {
+        anyhow::bail!("claude mcp remove failed");
+    }
+
+    outln!("Removed {} from Claude MCP servers.", name);
+    Ok(())
+}
+
+fn show_cache_info(index_path: &Path) -> Result<()> {
+    let cache_manager = CacheManager::new(index_path)?;
+    let stats = cache_manager.get_stats();
+
+    outln!("Cache Statistics:");
+    outln!("  Total files indexed: {}", stats.total_files);
+    outln!("  Total entries: {}", stats.total_entries);
+    outln!("  Cache size: {:.2} MB", stats.cache_size_mb);
+    outln!("  JSONL size: {:.2} MB", stats.jsonl_size_mb);
+    if stats.jsonl_size_mb > 0.0 {
+        outln!(
+            "  Compression: segments are {:.2}x the JSONL size ({:.2} MB)",
+            stats.index_size_mb / stats.jsonl_size_mb,
+            stats.index_size_mb
+        );
+    }
+
+    if let Some(last_updated) = stats.last_updated {
+        outln!(
+            "  Last updated: {}",
+            shared::local_time(last_updated).format("%Y-%m-%d %H:%M:%S %:z")
+        );
+    }
+
+    if !stats.segments.is_empty() {
+        outln!("\nSegments:");
+        for segment in &stats.segments {
+            outln!(
+                "  {} - {} docs, {} deleted, {:.2} MB",
+                segment.id,
+                segment.docs,
+                segment.deleted_docs,
+                mib(segment.bytes)
+            );
+        }
+    }
+
+    if !stats.projects.is_empty() {
+        let mut projects = stats.projects.clone();
+        projects.sort_by_key(|p| std::cmp::Reverse(p.estimated_index_bytes));
+        outln!("\nProject breakdown (largest first, index size estimated from entries):");
+        for project in projects.iter().take(10) {
+            outln!(
+                "  {} - {} files, {} entries, ~{:.2} MB index from {:.2} MB JSONL (updated: {})",
+                project.name,
+                project.files,
+                project.entries,
+                mib(project.estimated_index_bytes),
+                mib(project.jsonl_bytes),
+                shared::local_time(project.last_updated).format("%Y-%m-%d")
+            );
+        }
+        if stats.projects.len() > 10 {
+            outln!("  ... and {} more projects", stats.projects.len() - 10);
+        }
+    }
+
+    Ok(())
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn clear_cache(index_path: &Path) -> Result<()> {
+    let mut cache_manager = CacheManager::new(index_path)?;
+    cache_manager.clear_cache()?;
+    outln!("Cache cleared successfully. Run 'claude-search index' to rebuild.");
+    Ok(())
+}
+
+fn prune_cache(
+    index_path: &Path,
+    older_than_days: u32,
+    never_accessed: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let _lock = shared::ExclusiveIndexAccess::acquire()?;
+
+    let mut cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let sessions = shared::prepare_session_list(
+        shared::indexed_sessions(&search_engine, index_path, None)?,
+        cache.get_session_access(),
+        SessionSort::Oldest,
+    );
+
+    let cutoff = Utc::now() - chrono::Duration::days(older_than_days as i64);
+    let candidates = shared::prune_candidates(&sessions, cutoff, never_accessed);
+
+    if candidates.is_empty() {
+        outln!("No sessions to prune.");
+        return Ok(());
+    }
+
+    for (i, session) in candidates.iter().enumerate() {
+        out!("{}", shared::format_session_line(i, session));
+    }
+
+    if dry_run {
+        outln!("\nWould prune {} sessions (dry run).", candidates.len());
+        return Ok(());
+    }
+
+    let ids: Vec<String> = candidates.iter().map(|s| s.session_id.clone()).collect();
+    drop(search_engine);
+    let mut indexer = shared::SearchIndexer::open(index_path)?;
+    cache.prune_sessions(&mut indexer, &ids)?;
+    outln!(
+        "\nPruned {} sessions. They stay out of the index until their file changes or a rebuild.",
+        ids.len()
+    );
+    Ok(())
+}
+
+/// Print a tool result proxied through the daemon
+fn print_tool_output((text, is_error): (String, bool)) -> Result<()> {
+    if is_error {
+        anyhow::bail!("{}", text.trim_end());
+    }
+    out!("{}", text);
+    if !text.ends_with('\n') {
+        outln!();
+    }
+    Ok(())
+}
+
+fn usage_report(
+    index_path: &Path,
+    project: Option<String>,
+    after: Option<chrono::DateTime<Utc>>,
+    before: Option<chrono::DateTime<Utc>>,
+    limit: usize,
+    days: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let report = search_engine.usage_report(
+        project.as_deref(),
+        after,
+        before,
+        &shared::get_config().usage,
+    )?;
+    out!("{}", shared::format_usage_report(&report, limit, days));
+    Ok(())
+}
+
+fn tool_usage(
+    index_path: &Path,
+    project: Option<String>,
+    after: Option<chrono::DateTime<Utc>>,
+    before: Option<chrono::DateTime<Utc>>,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let report = search_engine.tool_usage(project.as_deref(), after, before)?;
+    out!("{}", shared::format_tool_usage_report(&report, limit));
+    Ok(())
+}
+
+fn analyze_errors(
+    index_path: &Path,
+    project: Option<String>,
+    after: Option<chrono::DateTime<Utc>>,
+    before: Option<chrono::DateTime<Utc>>,
+    min_sessions: usize,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let clusters = search_engine.error_clusters(project.as_deref(), after, before, min_sessions)?;
+
+    if clusters.is_empty() {
+        outln!("No matching tool errors found.");
+        return Ok(());
+    }
+
+    out!("{}", shared::format_error_clusters(&clusters, limit));
+    Ok(())
+}
+
+fn find_decisions(
+    index_path: &Path,
+    session: Option<String>,
+    project: Option<String>,
+    after: Option<chrono::DateTime<Utc>>,
+    before: Option<chrono::DateTime<Utc>>,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let decisions =
+        search_engine.decisions(session.as_deref(), project.as_deref(), after, before)?;
+
+    if decisions.is_empty() {
+        outln!("No decisions found.");
+        return Ok(());
+    }
+
+    out!("{}", shared::format_decisions(&decisions, limit));
+    Ok(())
+}
+
+fn diff_sessions(index_path: &Path, session_a: &str, session_b: &str) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let mut cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let mut sessions = Vec::new();
+    for id in [session_a, session_b] {
+        let Some(activity) = search_engine.session_activity(id)? else {
+            anyhow::bail!("No session '{}' in the index", id);
+        };
+        sessions.push(activity);
+    }
+
+    cache.record_session_access(sessions.iter().map(|s| s.session_id.as_str()))?;
+    out!(
+        "{}",
+        shared::format_session_diff(&sessions[0], &sessions[1])
+    );
+    Ok(())
+}
+
+fn show_messages(index_path: &Path, ids: &[String], expand_tool_results: bool) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let mut cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let mut messages = search_engine.get_messages_by_uuid(ids)?;
+    if messages.is_empty() {
+        outln!("No messages found for provided IDs");
+        return Ok(());
+    }
+    shared::attach_notes(&mut messages)?;
+    if expand_tool_results {
+        shared::expand_tool_results(&search_engine, &mut messages)?;
+    }
+
+    cache.record_session_access(messages.iter().map(|m| m.session_id.as_str()))?;
+    out!("{}", shared::format_messages(&messages));
+    Ok(())
+}
+
+fn bookmark_message(
+    index_path: &Path,
+    id: &str,
+    tags: &[String],
+    note: Option<&str>,
+    remove: bool,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let store = shared::BookmarkStore::open_default()?;
+    if remove {
+        // Bookmarks of messages gone from the index can still be removed
+        let uuid = search_engine
+            .resolve_message_id(id)?
+            .unwrap_or_else(|| id.to_string());
+        if store.remove(&uuid)? {
+            outln!("Removed bookmark {}", shared::short_uuid(&uuid));
+        } else {
+            outln!("Message {} was not bookmarked", shared::short_uuid(&uuid));
+        }
+        return Ok(());
+    }
+
+    let Some(uuid) = search_engine.resolve_message_id(id)? else {
+        anyhow::bail!("No message '{}' in the index", id);
+    };
+    out!(
+        "{}",
+        shared::format_bookmark(&store.bookmark(&uuid, tags, note)?)
+    );
+    Ok(())
+}
+
+fn annotate_message(index_path: &Path, id: &str, note: Option<&str>, clear: bool) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let store = shared::BookmarkStore::open_default()?;
+    if clear {
+        // Notes on messages gone from the index can still be cleared
+        let uuid = search_engine
+            .resolve_message_id(id)?
+            .unwrap_or_else(|| id.to_string());
+        let removed = store.clear_annotations(&uuid)?;
+        outln!(
+            "Removed {} notes from {}",
+            removed,
+            shared::short_uuid(&uuid)
+        );
+        return Ok(());
+    }
+
+    let Some(uuid) = search_engine.resolve_message_id(id)? else {
+        anyhow::bail!("No message '{}' in the index", id);
+    };
+    let annotations = store.annotate(&uuid, note.unwrap_or_default())?;
+    out!("{}", shared::format_annotations(&uuid, &annotations));
+    Ok(())
+}
+
+fn list_bookmarks(index_path: &Path, tag: Option<&str>) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let bookmarks = shared::BookmarkStore::open_default()?.list(tag)?;
+    out!(
+        "{}",
+        shared::format_bookmark_list(&search_engine, &bookmarks)?
+    );
+    Ok(())
+}
+
+fn open_message(index_path: &Path, id: &str, print: bool) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let Some(location) = shared::locate_message(&search_engine, id)? else {
+        anyhow::bail!("No message '{}' found in the index or its session file", id);
+    };
+
+    // Only hand the terminal to an editor when someone is at it
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty() && std::io::stdout().is_terminal());
+    match editor {
+        Some(editor) if !print => open_in_editor(&editor, &location),
+        _ => {
+            out!("{}", shared::format_message_location(&location));
+            Ok(())
+        }
+    }
+}
+
+/// Run `$EDITOR +line file`; the editor value may carry its own arguments
+fn open_in_editor(editor: &str, location: &shared::MessageLocation) -> Result<()> {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or(editor);
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(format!("+{}", location.line))
+        .arg(&location.file)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Failed to launch editor '{}': {}", editor, e))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+fn file_history(
+    index_path: &Path,
+    path: &str,
+    modified_only: bool,
+    project: Option<String>,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let touches = search_engine.file_touches(path, project.as_deref(), modified_only)?;
+
+    if touches.is_empty() {
+        outln!("No sessions touched {path}");
+        return Ok(());
+    }
+
+    out!("{}", shared::format_file_history(&touches, limit));
+    Ok(())
+}
+
+fn extract_commands(
+    index_path: &Path,
+    pattern: Option<String>,
+    project: Option<String>,
+    session: Option<String>,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let runs =
+        search_engine.command_runs(project.as_deref(), session.as_deref(), pattern.as_deref())?;
+
+    if runs.is_empty() {
+        outln!("No matching Bash commands found.");
+        return Ok(());
+    }
+
+    out!("{}", shared::format_command_history(&runs, limit));
+    Ok(())
+}
+
+fn code_blocks(
+    index_path: &Path,
+    query: Option<String>,
+    session: Option<String>,
+    project: Option<String>,
+    language: Option<String>,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let snippets = match session {
+        Some(session) => {
+            shared::session_code_snippets(&search_engine, &session, language.as_deref())?
+        }
+        None => {
+            let query = SearchQuery {
+                text: query.unwrap_or_default(),
+                project_filters: project.into_iter().collect(),
+                limit,
+                ..Default::default()
+            };
+            shared::search_code_snippets(&search_engine, query, language.as_deref())?
+        }
+    };
+
+    if snippets.is_empty() {
+        outln!("No code blocks found.");
+        return Ok(());
+    }
+
+    out!("{}", shared::format_code_snippets(&snippets, limit));
+    Ok(())
+}
+
+fn list_sessions(
+    index_path: &Path,
+    project: Option<String>,
+    sort: SessionSort,
+    follow_continuations: bool,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let mut sessions = shared::indexed_sessions(&search_engine, index_path, project.as_deref())?;
+    if follow_continuations {
+        let links = search_engine.session_continuations()?;
+        sessions = shared::merge_continuations(sessions, &links);
+    }
+    let sessions = shared::prepare_session_list(sessions, cache.get_session_access(), sort);
+
+    if sessions.is_empty() {
+        outln!("No sessions found.");
+        return Ok(());
     }
 
+    out!("{}", shared::format_session_list(&sessions, limit));
     Ok(())
 }
 
-fn install(project_scope: bool) -> Result<()> {
-    use std::process::Command;
+fn export_worklog(
+    index_path: &Path,
+    project: Option<String>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+    period: WorklogPeriod,
+    format: WorklogFormat,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
 
-    let exe = std::env::current_exe()?;
-    let exe_path = exe
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("Invalid exe path"))?;
-    let scope = if project_scope { "project" } else { "user" };
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let mut sessions = shared::indexed_sessions(&search_engine, index_path, project.as_deref())?;
+    // Listed straight from the index, sessions come without titles
+    if !shared::SessionStore::new(index_path).is_current() {
+        let mut titles = search_engine.session_titles(None)?;
+        for session in sessions.iter_mut().filter(|s| s.title.is_none()) {
+            session.title = titles.remove(&session.session_id);
+        }
+    }
 
-    let _ = Command::new("claude")
-        .args(["mcp", "remove", "-s", scope, "claude-conversation-search"])
-        .status();
+    let rows = shared::build_worklog(&sessions, period, after, before);
+    match format {
+        WorklogFormat::Markdown => out!("{}", shared::format_worklog_markdown(&rows)),
+        WorklogFormat::Csv => out!("{}", shared::format_worklog_csv(&rows)),
+    }
+    Ok(())
+}
 
-    let status = Command::new("claude")
-        .args([
-            "mcp",
-            "add",
-            "-s",
-            scope,
-            "claude-conversation-search",
-            exe_path,
-        ])
-        .status()?;
+fn export_site(
+    index_path: &Path,
+    dir: &Path,
+    project: Option<String>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
 
-    if !status.success() {
-        anyhow::bail!("claude mcp add failed");
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let mut sessions = shared::indexed_sessions(&search_engine, index_path, project.as_deref())?;
+    sessions.retain(|s| {
+        after.is_none_or(|after| s.first_timestamp >= after)
+            && before.is_none_or(|before| s.first_timestamp < before)
+    });
+    if !shared::SessionStore::new(index_path).is_current() {
+        let mut titles = search_engine.session_titles(None)?;
+        for session in sessions.iter_mut().filter(|s| s.title.is_none()) {
+            session.title = titles.remove(&session.session_id);
+        }
     }
 
-    println!("{}", exe_path);
+    let mut site = Vec::with_capacity(sessions.len());
+    for info in sessions {
+        let messages = search_engine.get_session_messages(&info.session_id)?;
+        site.push(shared::SiteSession { info, messages });
+    }
+    let summary = shared::write_site(dir, &site)?;
+    outln!(
+        "Wrote {} projects, {} sessions and {} messages to {}",
+        summary.projects,
+        summary.sessions,
+        summary.messages,
+        dir.join("index.html").display()
+    );
     Ok(())
 }
 
-fn show_cache_info(index_path: &Path) -> Result<()> {
-    let cache_manager = CacheManager::new(index_path)?;
-    let stats = cache_manager.get_stats();
+fn list_projects(index_path: &Path, project: Option<String>, limit: usize) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
 
-    println!("Cache Statistics:");
-    println!("  Total files indexed: {}", stats.total_files);
-    println!("  Total entries: {}", stats.total_entries);
-    println!("  Cache size: {:.2} MB", stats.cache_size_mb);
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let projects = search_engine.list_projects(project.as_deref())?;
 
-    if let Some(last_updated) = stats.last_updated {
-        println!(
-            "  Last updated: {}",
-            last_updated.format("%Y-%m-%d %H:%M:%S UTC")
-        );
+    if projects.is_empty() {
+        outln!("No projects found.");
+        return Ok(());
     }
 
-    if !stats.projects.is_empty() {
-        println!("\nProject breakdown:");
-        for project in stats.projects.iter().take(10) {
-            println!(
-                "  {} - {} files, {} entries (updated: {})",
-                project.name,
-                project.files,
-                project.entries,
-                project.last_updated.format("%Y-%m-%d")
-            );
-        }
-        if stats.projects.len() > 10 {
-            println!("  ... and {} more projects", stats.projects.len() - 10);
-        }
+    out!("{}", shared::format_project_list(&projects, limit));
+    Ok(())
+}
+
+fn suggest_terms(index_path: &Path, prefix: &str, limit: usize) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
     }
 
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let terms = search_engine.suggest_terms(prefix, limit)?;
+    out!("{}", shared::format_term_suggestions(prefix, &terms));
     Ok(())
 }
 
-fn clear_cache(index_path: &Path) -> Result<()> {
-    let mut cache_manager = CacheManager::new(index_path)?;
-    cache_manager.clear_cache()?;
-    println!("Cache cleared successfully. Run 'claude-search index' to rebuild.");
+/// Full path for a `project merge` argument: paths as given, bare names
+/// looked up among indexed project directories
+fn resolve_project_path(sessions: &[shared::SessionInfo], project: &str) -> Result<String> {
+    if project.contains('/') {
+        return Ok(shared::expand_tilde(project.trim_end_matches('/')));
+    }
+    let mut matches: Vec<&str> = sessions
+        .iter()
+        .map(|s| s.project_path.as_str())
+        .filter(|path| Path::new(path).file_name().and_then(|n| n.to_str()) == Some(project))
+        .collect();
+    matches.sort();
+    matches.dedup();
+    match matches.as_slice() {
+        [path] => Ok(path.to_string()),
+        [] => anyhow::bail!("No indexed project named '{}'; give its full path", project),
+        paths => anyhow::bail!(
+            "Project name '{}' is ambiguous: {}; give the full path",
+            project,
+            paths.join(", ")
+        ),
+    }
+}
+
+fn merge_project(index_path: &Path, old: &str, new: &str) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+
+    let _lock = shared::ExclusiveIndexAccess::acquire()?;
+    let mut cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let sessions = search_engine.list_sessions(None)?;
+    let old = resolve_project_path(&sessions, old)?;
+    let new = resolve_project_path(&sessions, new)?;
+    if old == new {
+        anyhow::bail!("Old and new project paths are the same: {}", old);
+    }
+
+    shared::set_config_entry("project_aliases", &old, new.clone().into())?;
+    let alias = HashMap::from([(old.clone(), new.clone())]);
+    let moved: Vec<String> = sessions
+        .iter()
+        .filter(|s| shared::alias_project_path(&s.project_path, &alias).is_some())
+        .map(|s| s.session_id.clone())
+        .collect();
+    let files: Vec<_> = search_engine.source_files(&moved)?.into_iter().collect();
+    drop(search_engine);
+
+    cache.forget_files(&files);
+    let mut indexer = shared::SearchIndexer::open(index_path)?;
+    // The global config was read before the alias was saved
+    indexer.set_project_aliases(shared::Config::load()?.project_aliases);
+    cache.update_incremental(&mut indexer, files.clone())?;
+
+    // Stored summaries have no file to reparse; index them again too
+    let store = shared::SessionStore::new(index_path);
+    let mut search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    for session_id in &moved {
+        if let Some(summary) = store.summary(session_id)? {
+            let messages = search_engine.get_session_messages(session_id)?;
+            indexer.index_session_summary(&messages, &summary.text)?;
+        }
+    }
+    indexer.commit()?;
+    search_engine.reload(cache.get_session_counts().clone())?;
+    store.refresh(&search_engine, Some(&moved))?;
+
+    outln!(
+        "🔀 Merged {} into {}: {} sessions reindexed from {} files",
+        shared::home_to_tilde(&old),
+        shared::home_to_tilde(&new),
+        moved.len(),
+        files.len()
+    );
     Ok(())
 }
 
 struct SearchOpts {
     query: String,
-    project: Option<String>,
+    projects: Vec<String>,
     session: Option<String>,
+    host: Option<String>,
+    branch: Option<String>,
+    source: Option<String>,
+    /// Only these messages, from a `bookmarked:true` term
+    uuid_filter: Option<Vec<String>>,
     limit: usize,
     context_before: usize,
     context_after: usize,
     exclude_projects: Vec<String>,
     exclude_patterns: Vec<String>,
+    dedupe_sessions: bool,
+    collapse_duplicates: bool,
+    /// Requested half-life; the configured default applies when None
+    recency_boost: Option<f64>,
     sort: SortOrder,
     after: Option<chrono::DateTime<Utc>>,
     before: Option<chrono::DateTime<Utc>>,
+    /// Window the sessions searched must overlap
+    active_after: Option<chrono::DateTime<Utc>>,
+    active_before: Option<chrono::DateTime<Utc>>,
     display: DisplayOptions,
 }
 
-fn parse_date(s: &str) -> Result<chrono::DateTime<Utc>> {
-    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(s) {
-        return Ok(dt.with_timezone(&Utc));
-    }
-    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
-        return Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()));
-    }
-    anyhow::bail!("Invalid date '{}': use YYYY-MM-DD or ISO 8601", s)
+/// Search options for a saved search, with the CLI's default context and display
+fn saved_search_opts(search: &shared::SavedSearch, limit: Option<usize>) -> Result<SearchOpts> {
+    let args = search.to_search_args();
+    let date = |key: &str| args[key].as_str().map(parse_date).transpose();
+    let include = shared::get_config()
+        .search
+        .expand_include(&search.include)?;
+    let (query, uuid_filter) = shared::bookmark_filter(&search.query)?;
+    Ok(SearchOpts {
+        query,
+        projects: search.project.iter().cloned().collect(),
+        session: None,
+        host: search.host.clone(),
+        branch: search.branch.clone(),
+        source: None,
+        uuid_filter,
+        limit: limit.or(search.limit).unwrap_or(10),
+        context_before: 2,
+        context_after: 2,
+        exclude_projects: Vec::new(),
+        exclude_patterns: Vec::new(),
+        dedupe_sessions: true,
+        collapse_duplicates: true,
+        recency_boost: None,
+        sort: match search.sort_by.as_deref() {
+            Some("date_desc") => SortOrder::DateDesc,
+            Some("date_asc") => SortOrder::DateAsc,
+            _ => SortOrder::Relevance,
+        },
+        after: date("after")?,
+        before: date("before")?,
+        active_after: None,
+        active_before: None,
+        display: DisplayOptions {
+            include_thinking: include.iter().any(|i| i == "thinking"),
+            include_tools: include.iter().any(|i| i == "tools"),
+            ..Default::default()
+        },
+    })
 }
 
-fn search_conversations(index_path: &Path, opts: SearchOpts) -> Result<()> {
-    if !index_path.exists() {
-        println!("Index not found. Please run 'claude-search index' first.");
-        return Ok(());
-    }
+/// CLI-side result filter: excluded projects and patterns
+struct ResultFilter<'a> {
+    exclude_projects: &'a [String],
+    exclude_regexes: Vec<Regex>,
+}
 
-    let config = shared::get_config();
-    let mut all_exclude_patterns = config.search.exclude_patterns.clone();
-    all_exclude_patterns.extend(opts.exclude_patterns);
+impl<'a> ResultFilter<'a> {
+    fn new(opts: &'a SearchOpts) -> Self {
+        let mut all_exclude_patterns = shared::get_config().search.exclude_patterns.clone();
+        all_exclude_patterns.extend(opts.exclude_patterns.iter().cloned());
+        Self {
+            exclude_projects: &opts.exclude_projects,
+            exclude_regexes: all_exclude_patterns
+                .iter()
+                .filter_map(|p| Regex::new(p).ok())
+                .collect(),
+        }
+    }
 
-    let exclude_regexes: Vec<Regex> = all_exclude_patterns
-        .iter()
-        .filter_map(|p| Regex::new(p).ok())
-        .collect();
+    fn keep(&self, result: &shared::SearchResultWithContext) -> bool {
+        let proj = &result.matched_message.project;
+        let path = &result.matched_message.project_path;
 
-    let cache = CacheManager::new(index_path)?;
-    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+        if self.exclude_projects.contains(proj) {
+            return false;
+        }
+        !self
+            .exclude_regexes
+            .iter()
+            .any(|regex| regex.is_match(proj) || regex.is_match(path))
+    }
+}
 
-    let query = SearchQuery {
-        text: opts.query,
-        project_filter: opts.project,
-        session_filter: opts.session,
-        limit: opts.limit * 3,
+fn search_query(opts: &SearchOpts, limit: usize) -> SearchQuery {
+    SearchQuery {
+        text: opts.query.clone(),
+        project_filters: opts.projects.clone(),
+        session_filter: opts.session.clone(),
+        limit,
         sort_by: opts.sort,
         after: opts.after,
         before: opts.before,
-    };
-
-    let results =
-        search_engine.search_with_context(query, opts.context_before, opts.context_after)?;
-
-    let mut session_seen = std::collections::HashSet::new();
-    let filtered: Vec<_> = results
-        .into_iter()
-        .filter(|r| {
-            let proj = &r.matched_message.project;
-            let path = &r.matched_message.project_path;
-
-            if opts.exclude_projects.contains(proj) {
-                return false;
-            }
-            for regex in &exclude_regexes {
-                if regex.is_match(proj) || regex.is_match(path) {
-                    return false;
-                }
-            }
-            session_seen.insert(r.matched_message.session_id.clone())
-        })
-        .take(opts.limit)
-        .collect();
-
-    if filtered.is_empty() {
-        println!("No results found.");
-        return Ok(());
+        host_filter: opts.host.clone(),
+        branch_filter: opts.branch.clone(),
+        source_filter: opts.source.clone(),
+        uuid_filter: opts.uuid_filter.clone(),
+        dir_filter: None,
+        session_active_after: opts.active_after,
+        session_active_before: opts.active_before,
+        recency_half_life_days: shared::get_config()
+            .search
+            .recency_half_life(opts.recency_boost),
+        type_boosts: shared::get_config().search.type_boosts.clone(),
     }
+}
 
-    let ctx_display = if opts.context_before == opts.context_after {
-        format!("-C {}", opts.context_before)
-    } else {
-        format!("-B {} -A {}", opts.context_before, opts.context_after)
-    };
-    println!("Found {} results ({}):\n", filtered.len(), ctx_display);
-
-    for (i, result) in filtered.iter().enumerate() {
-        print!("{}", result.format_compact_with_options(i, &opts.display));
-        if i < filtered.len() - 1 {
-            println!();
+/// Filters kept with a query in its history, as `name=value`
+fn history_filters(opts: &SearchOpts) -> Vec<String> {
+    let mut filters: Vec<String> = opts
+        .projects
+        .iter()
+        .map(|project| format!("project={}", project))
+        .collect();
+    let named = [
+        ("session", opts.session.clone()),
+        ("host", opts.host.clone()),
+        ("branch", opts.branch.clone()),
+        ("source", opts.source.clone()),
+        (
+            "after",
+            opts.after.map(|d| d.format("%Y-%m-%d").to_string()),
+        ),
+        (
+            "before",
+            opts.before.map(|d| d.format("%Y-%m-%d").to_string()),
+        ),
+        (
+            "active_after",
+            opts.active_after.map(|d| d.format("%Y-%m-%d").to_string()),
+        ),
+        (
+            "active_before",
+            opts.active_before.map(|d| d.format("%Y-%m-%d").to_string()),
+        ),
+    ];
+    for (name, value) in named {
+        if let Some(value) = value {
+            filters.push(format!("{}={}", name, value));
         }
     }
-
-    Ok(())
+    filters
 }
 
-fn show_topics(index_path: &Path, project_filter: Option<String>, limit: usize) -> Result<()> {
+fn search_conversations(index_path: &Path, opts: SearchOpts, format: OutputFormat) -> Result<()> {
     if !index_path.exists() {
-        println!("Index not found. Please run 'claude-search index' first.");
+        outln!("Index not found. Please run 'claude-search index' first.");
         return Ok(());
     }
 
     let cache = CacheManager::new(index_path)?;
     let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
 
-    // Get all conversations to analyze topics
-    let query = SearchQuery {
-        text: "*".to_string(), // Match everything
-        project_filter: project_filter.clone(),
-        session_filter: None,
-        limit: 100_000,
-        sort_by: SortOrder::default(),
-        after: None,
-        before: None,
+    // Over-fetch to make up for filtered results; limit 0 fetches every match
+    let fetch_limit = match opts.limit {
+        0 => (search_engine.num_docs() as usize).max(1),
+        limit => limit * 3,
     };
-
-    let results = search_engine.search(query)?;
-
-    // Count technology mentions
-    let mut tech_counts = HashMap::new();
-    let mut lang_counts = HashMap::new();
-    let mut tool_counts = HashMap::new();
-    let mut project_counts = HashMap::new();
-
-    for result in &results {
-        project_counts
-            .entry(result.project.clone())
-            .and_modify(|count| *count += 1)
-            .or_insert(1);
-
-        for tech in &result.technologies {
-            tech_counts
-                .entry(tech.clone())
-                .and_modify(|count| *count += 1)
-                .or_insert(1);
+    let results = search_engine.search_with_context(
+        search_query(&opts, fetch_limit),
+        opts.context_before,
+        opts.context_after,
+    )?;
+
+    let filter = ResultFilter::new(&opts);
+    let filtered: Vec<_> = results.into_iter().filter(|r| filter.keep(r)).collect();
+    let filtered = if opts.dedupe_sessions {
+        shared::dedupe_by_session(filtered)
+    } else {
+        filtered
+    };
+    let mut filtered = if opts.collapse_duplicates {
+        shared::collapse_near_duplicates(filtered)
+    } else {
+        filtered
+    };
+    if opts.limit > 0 {
+        filtered.truncate(opts.limit);
+    }
+    shared::attach_notes(filtered.iter_mut().flat_map(|r| &mut r.context_messages))?;
+    let facets = search_engine.search_facets(&search_query(&opts, fetch_limit))?;
+    // Read before this search is added, so it isn't suggested to itself
+    let history = filtered
+        .is_empty()
+        .then(|| shared::QueryHistory::open_default().and_then(|h| h.list()));
+    shared::record_query(&shared::QueryRecord::new(
+        &opts.query,
+        history_filters(&opts),
+        facets.hits,
+        "cli",
+    ));
+
+    // JSON carries message content as data, so it bypasses the output style
+    if format == OutputFormat::Jsonl {
+        for result in &filtered {
+            println!("{}", result.to_json(&opts.display));
         }
+        return Ok(());
+    }
 
-        for lang in &result.code_languages {
-            lang_counts
-                .entry(lang.clone())
-                .and_modify(|count| *count += 1)
-                .or_insert(1);
+    if filtered.is_empty() {
+        outln!("No results found.");
+        // Matches dropped by post-filters aren't a misspelled query
+        if facets.hits == 0
+            && let Ok(suggestions) =
+                search_engine.suggest_queries(&search_query(&opts, fetch_limit))
+        {
+            out!("{}", shared::format_query_suggestions(&suggestions));
         }
-
-        for tool in &result.tools_mentioned {
-            tool_counts
-                .entry(tool.clone())
-                .and_modify(|count| *count += 1)
-                .or_insert(1);
+        if let Some(Ok(history)) = history {
+            out!(
+                "{}",
+                shared::format_suggestions(&shared::suggest_refinements(&history, &opts.query))
+            );
         }
+        return Ok(());
     }
 
-    println!(
-        "Topic Analysis - {} conversations analyzed\n",
-        results.len()
-    );
-
-    if let Some(ref project) = project_filter {
-        println!("Filtered by project: {project}\n");
+    let ctx_display = if opts.context_before == opts.context_after {
+        format!("-C {}", opts.context_before)
+    } else {
+        format!("-B {} -A {}", opts.context_before, opts.context_after)
+    };
+    if facets.hits >= shared::FACET_MIN_HITS {
+        out!("{}", shared::format_search_facets(&facets));
     }
+    outln!("Found {} results ({}):\n", filtered.len(), ctx_display);
 
-    // Top technologies
-    if !tech_counts.is_empty() {
-        println!("🔧 Top Technologies:");
-        let mut sorted_tech: Vec<_> = tech_counts.iter().collect();
-        sorted_tech.sort_by(|a, b| b.1.cmp(a.1));
+    out!(
+        "{}",
+        shared::format_results(&filtered, &opts.display, opts.sort)
+    );
 
-        for (tech, count) in sorted_tech.iter().take(limit) {
-            println!("   {tech} ({count})");
-        }
-        println!();
-    }
+    Ok(())
+}
 
-    // Top programming languages
-    if !lang_counts.is_empty() {
-        println!("💻 Top Programming Languages:");
-        let mut sorted_lang: Vec<_> = lang_counts.iter().collect();
-        sorted_lang.sort_by(|a, b| b.1.cmp(a.1));
+/// Print matches as JSON lines while the index is still being scanned
+fn stream_search(index_path: &Path, opts: SearchOpts) -> Result<()> {
+    use std::io::Write;
 
-        for (lang, count) in sorted_lang.iter().take(limit) {
-            println!("   {lang} ({count})");
-        }
-        println!();
+    if !index_path.exists() {
+        anyhow::bail!("Index not found. Please run 'claude-search index' first.");
     }
 
-    // Top tools mentioned
-    if !tool_counts.is_empty() {
-        println!("🔨 Top Tools Mentioned:");
-        let mut sorted_tools: Vec<_> = tool_counts.iter().collect();
-        sorted_tools.sort_by(|a, b| b.1.cmp(a.1));
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
 
-        for (tool, count) in sorted_tools.iter().take(limit) {
-            println!("   {tool} ({count})");
-        }
-        println!();
+    let filter = ResultFilter::new(&opts);
+    // Printed lines can't list later matches, so repeats are just skipped
+    let mut session_seen = std::collections::HashSet::new();
+    let mut printed_simhashes = Vec::new();
+    let mut remaining = if opts.limit == 0 {
+        usize::MAX
+    } else {
+        opts.limit
+    };
+    let mut stdout = std::io::stdout().lock();
+    search_engine.search_each_with_context(
+        search_query(&opts, opts.limit),
+        opts.context_before,
+        opts.context_after,
+        |mut result| {
+            let simhash = result.matched_message.simhash;
+            if !filter.keep(&result)
+                || (opts.dedupe_sessions
+                    && !session_seen.insert(result.matched_message.session_id.clone()))
+                || (opts.collapse_duplicates
+                    && printed_simhashes
+                        .iter()
+                        .any(|&printed| shared::is_near_duplicate(printed, simhash)))
+            {
+                return Ok(true);
+            }
+            printed_simhashes.push(simhash);
+            shared::attach_notes(&mut result.context_messages)?;
+            // A closed pipe (fzf accepted, head exited) ends the stream quietly
+            if writeln!(stdout, "{}", result.to_json(&opts.display))
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                return Ok(false);
+            }
+            remaining -= 1;
+            Ok(remaining > 0)
+        },
+    )
+}
+
+fn show_topics(
+    index_path: &Path,
+    project_filter: Option<String>,
+    dir_filter: Option<String>,
+    after: Option<chrono::DateTime<Utc>>,
+    before: Option<chrono::DateTime<Utc>>,
+    limit: usize,
+) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
     }
 
-    // Project breakdown (if not filtering by project)
-    if project_filter.is_none() && !project_counts.is_empty() {
-        println!("📂 Project Activity:");
-        let mut sorted_projects: Vec<_> = project_counts.iter().collect();
-        sorted_projects.sort_by(|a, b| b.1.cmp(a.1));
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
 
-        for (project, count) in sorted_projects.iter().take(limit) {
-            println!("   {project} ({count} conversations)");
-        }
-    }
+    let report = search_engine.topic_report(
+        project_filter.as_deref(),
+        dir_filter.as_deref(),
+        after,
+        before,
+    )?;
+    out!(
+        "{}",
+        shared::format_topic_report(
+            &report,
+            project_filter.as_deref(),
+            dir_filter.as_deref(),
+            limit
+        )
+    );
 
     Ok(())
 }
 
-fn show_stats(index_path: &Path, project_filter: Option<String>) -> Result<()> {
+fn show_stats(
+    index_path: &Path,
+    project_filter: Option<String>,
+    dir_filter: Option<String>,
+) -> Result<()> {
     if !index_path.exists() {
-        println!("Index not found. Please run 'claude-search index' first.");
+        outln!("Index not found. Please run 'claude-search index' first.");
         return Ok(());
     }
 
@@ -579,96 +2522,68 @@ fn show_stats(index_path: &Path, project_filter: Option<String>) -> Result<()> {
     let cache_stats = cache_manager.get_stats();
     let search_engine = SearchEngine::new(index_path, cache_manager.get_session_counts().clone())?;
 
-    // Get conversation stats
-    let query = SearchQuery {
-        text: "*".to_string(),
-        project_filter: project_filter.clone(),
-        session_filter: None,
-        limit: 1_000_000,
-        sort_by: SortOrder::default(),
-        after: None,
-        before: None,
-    };
-
-    let results = search_engine.search(query)?;
-
-    let mut code_conversations = 0;
-    let mut error_conversations = 0;
-    let mut total_interactions = 0;
-    let mut session_counts = HashMap::new();
-
-    for result in &results {
-        if result.has_code {
-            code_conversations += 1;
-        }
-        if result.has_error {
-            error_conversations += 1;
-        }
-        total_interactions += result.interaction_count;
-
-        session_counts
-            .entry(result.session_id.clone())
-            .and_modify(|count| *count += 1)
-            .or_insert(1);
-    }
+    let stats = search_engine.aggregate_stats(project_filter.as_deref(), dir_filter.as_deref())?;
+    let directories =
+        search_engine.directory_breakdown(project_filter.as_deref(), dir_filter.as_deref())?;
 
     if let Some(ref project) = project_filter {
-        println!("📊 Statistics for project: {project}\n");
+        outln!("📊 Statistics for project: {project}\n");
     } else {
-        println!("📊 Overall Statistics\n");
+        outln!("📊 Overall Statistics\n");
+    }
+    if let Some(ref dir) = dir_filter {
+        outln!("📂 Under directory: {dir}\n");
     }
 
-    println!("Cache Information:");
-    println!("  📁 Total files indexed: {}", cache_stats.total_files);
-    println!("  💾 Cache size: {:.2} MB", cache_stats.cache_size_mb);
+    outln!("Cache Information:");
+    outln!("  🗃️ Total files indexed: {}", cache_stats.total_files);
+    outln!("  💾 Cache size: {:.2} MB", cache_stats.cache_size_mb);
 
     if let Some(last_updated) = cache_stats.last_updated {
-        println!(
+        outln!(
             "  🕒 Last updated: {}",
-            last_updated.format("%Y-%m-%d %H:%M UTC")
+            shared::local_time(last_updated).format("%Y-%m-%d %H:%M %:z")
         );
     }
 
-    println!();
+    outln!();
 
-    let total_indexed = cache_stats.total_entries as usize;
-    let sampled = results.len();
+    let percent = |n: usize| {
+        if stats.messages > 0 {
+            n as f64 / stats.messages as f64 * 100.0
+        } else {
+            0.0
+        }
+    };
 
-    println!("Conversation Analysis:");
-    println!("  💬 Total messages indexed: {}", total_indexed);
-    println!("  🏗️ Unique sessions: {}", session_counts.len());
-    if sampled < total_indexed {
-        println!(
-            "  📊 Sampled for stats: {} ({:.1}%)",
-            sampled,
-            (sampled as f64 / total_indexed as f64) * 100.0
-        );
-    }
-    println!(
+    outln!("Conversation Analysis:");
+    outln!("  💬 Total messages indexed: {}", stats.messages);
+    outln!(
+        "  🏗️ Unique sessions: {} (avg: {} messages per session)",
+        stats.sessions.len(),
+        stats
+            .messages
+            .checked_div(stats.sessions.len())
+            .unwrap_or(0)
+    );
+    outln!(
         "  📝 Messages with code: {} ({:.1}%)",
-        code_conversations,
-        (code_conversations as f64 / sampled as f64) * 100.0
+        stats.with_code,
+        percent(stats.with_code)
     );
-    println!(
+    outln!(
         "  🚨 Messages with errors: {} ({:.1}%)",
-        error_conversations,
-        (error_conversations as f64 / sampled as f64) * 100.0
-    );
-    println!(
-        "  💬 Total interactions: {} (avg: {} per conversation)",
-        total_interactions,
-        if !results.is_empty() {
-            total_interactions / results.len()
-        } else {
-            0
-        }
+        stats.with_error,
+        percent(stats.with_error)
     );
+    outln!("  ✍️ Total words: {}", stats.words);
+    outln!("  🔢 Estimated tokens: {}", stats.tokens);
 
     // Show most active sessions
-    if !session_counts.is_empty() {
-        println!();
-        println!("Most Active Sessions:");
-        let mut sorted_sessions: Vec<_> = session_counts.iter().collect();
+    if !stats.sessions.is_empty() {
+        outln!();
+        outln!("Most Active Sessions:");
+        let mut sorted_sessions: Vec<_> = stats.sessions.iter().collect();
         sorted_sessions.sort_by(|a, b| b.1.cmp(a.1));
 
         for (session_id, count) in sorted_sessions.iter().take(5) {
@@ -677,7 +2592,42 @@ fn show_stats(index_path: &Path, project_filter: Option<String>) -> Result<()> {
             } else {
                 session_id.to_string()
             };
-            println!("  {short_id} ({count} messages)");
+            let tokens = stats.session_tokens.get(*session_id).copied().unwrap_or(0);
+            outln!("  {short_id} ({count} messages, ~{tokens} tokens)");
+        }
+    }
+
+    if !stats.branches.is_empty() {
+        outln!();
+        outln!("Git Branches:");
+        let mut sorted_branches: Vec<_> = stats.branches.iter().collect();
+        sorted_branches.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        for (branch, count) in sorted_branches.iter().take(10) {
+            outln!("  🌿 {branch} ({count} messages)");
+        }
+        if sorted_branches.len() > 10 {
+            outln!("  ... and {} more branches", sorted_branches.len() - 10);
+        }
+    }
+
+    if !directories.children.is_empty() {
+        outln!();
+        outln!(
+            "Directories under {} (drill down with --dir):",
+            shared::home_to_tilde(&directories.path)
+        );
+        for (name, count) in directories.children.iter().take(10) {
+            outln!("  📂 {name}/ ({count} messages)");
+        }
+        if directories.children.len() > 10 {
+            outln!(
+                "  ... and {} more directories",
+                directories.children.len() - 10
+            );
+        }
+        if directories.here > 0 {
+            outln!("  ({} messages in the directory itself)", directories.here);
         }
     }
 
@@ -693,7 +2643,7 @@ fn view_session(
     context_after: usize,
 ) -> Result<()> {
     if !index_path.exists() {
-        println!("Index not found. Please run 'claude-search index' first.");
+        outln!("Index not found. Please run 'claude-search index' first.");
         return Ok(());
     }
 
@@ -702,13 +2652,18 @@ fn view_session(
     let mut results = search_engine.get_session_messages(&session_id)?;
 
     if results.is_empty() {
-        println!("No messages found for session: {session_id}");
-        println!("Tip: Use 'claude-search stats' to see available session IDs");
+        outln!("No messages found for session: {session_id}");
+        outln!("Tip: Use 'claude-search stats' to see available session IDs");
         return Ok(());
     }
 
+    let session_id = results[0].session_id.clone();
+    let mut cache = cache;
+    cache.record_session_access([session_id.as_str()])?;
+
     // Sort by timestamp for chronological display
     results.sort_by_key(|r| r.timestamp);
+    shared::attach_notes(&mut results)?;
 
     // Filter displayable messages
     let displayable: Vec<_> = results.iter().filter(|r| r.is_displayable()).collect();
@@ -733,13 +2688,13 @@ fn view_session(
     let project_path = results[0].project_path_display();
     let time_range = format!(
         "{} - {}",
-        results[0].timestamp.format("%Y-%m-%d %H:%M"),
-        results.last().unwrap().timestamp.format("%H:%M")
+        shared::local_time(results[0].timestamp).format("%Y-%m-%d %H:%M"),
+        shared::local_time(results.last().unwrap().timestamp).format("%H:%M")
     );
 
     // Header line with all key info - full session UUID for `claude -r`
     if center_on.is_some() {
-        println!(
+        outln!(
             "📁 {} 🗒️ {} ({}/{} msgs) ⏱️ {}",
             project_path,
             session_id,
@@ -748,9 +2703,12 @@ fn view_session(
             time_range
         );
     } else {
-        println!(
+        outln!(
             "📁 {} 🗒️ {} ({} msgs) ⏱️ {}",
-            project_path, session_id, total, time_range
+            project_path,
+            session_id,
+            total,
+            time_range
         );
     }
 
@@ -784,15 +2742,15 @@ fn view_session(
             tags.push("error".to_string());
         }
         if !tags.is_empty() {
-            println!("tags: {}", tags.join(" "));
+            outln!("tags: {}", tags.join(" "));
         }
     }
-    println!();
+    outln!();
 
     // Messages in dense format
     let max_content = if show_full { 2000 } else { 200 };
     for result in window {
-        let time = result.timestamp.format("%H:%M:%S");
+        let time = shared::local_time(result.timestamp).format("%H:%M:%S");
         let marker = if center_idx.is_some()
             && Some(&result.uuid)
                 == center_on.as_ref().and_then(|u| {
@@ -813,25 +2771,77 @@ fn view_session(
         } else {
             ""
         };
-        println!(
+        outln!(
             "{marker} [{time}] {}: {content}{ellipsis}",
             result.role_display(),
         );
+        out!("{}", shared::format_notes(&result.notes, "   "));
     }
 
     if !show_full && window.iter().any(|r| r.content.chars().count() > 200) {
-        println!("\nUse --full for complete content");
+        outln!("\nUse --full for complete content");
+    }
+
+    Ok(())
+}
+
+fn store_summary(index_path: &Path, session_id: &str, text: &str, source: String) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
+    }
+    let text = text.trim();
+    if text.is_empty() {
+        anyhow::bail!("Summary is empty");
+    }
+
+    let _lock = shared::ExclusiveIndexAccess::acquire()?;
+    let cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let messages = search_engine.get_session_messages(session_id)?;
+    let Some(first) = messages.first() else {
+        outln!("No messages found for session: {session_id}");
+        return Ok(());
+    };
+    let summary = shared::StoredSummary {
+        text: text.to_string(),
+        source,
+        message_count: messages.len(),
+        created_at: chrono::Utc::now(),
+    };
+    shared::save_session_summary(index_path, &messages, &summary)?;
+    out!(
+        "{}",
+        shared::format_stored_summary(&first.session_id, &summary)
+    );
+    Ok(())
+}
+
+fn local_summary(index_path: &Path, session_id: &str) -> Result<()> {
+    if !index_path.exists() {
+        outln!("Index not found. Please run 'claude-search index' first.");
+        return Ok(());
     }
 
+    let mut cache = CacheManager::new(index_path)?;
+    let search_engine = SearchEngine::new(index_path, cache.get_session_counts().clone())?;
+    let Some(activity) = search_engine.session_activity(session_id)? else {
+        outln!("No messages found for session: {session_id}");
+        return Ok(());
+    };
+    cache.record_session_access([activity.session_id.as_str()])?;
+
+    let store = shared::SessionStore::new(index_path);
+    out!("{}", shared::cached_extractive_summary(&store, &activity)?);
     Ok(())
 }
 
-fn summarize_session(index_path: &Path, session_id: String) -> Result<()> {
+fn summarize_session(index_path: &Path, session_id: String, generate: bool) -> Result<()> {
     use std::io::Write;
     use std::process::{Command, Stdio};
 
     if !index_path.exists() {
-        println!("Index not found. Please run 'claude-search index' first.");
+        outln!("Index not found. Please run 'claude-search index' first.");
         return Ok(());
     }
 
@@ -840,7 +2850,20 @@ fn summarize_session(index_path: &Path, session_id: String) -> Result<()> {
     let mut results = search_engine.get_session_messages(&session_id)?;
 
     if results.is_empty() {
-        println!("No messages found for session: {session_id}");
+        outln!("No messages found for session: {session_id}");
+        return Ok(());
+    }
+
+    let mut cache = cache;
+    cache.record_session_access([results[0].session_id.as_str()])?;
+
+    if !generate && let Some(summary) = shared::latest_summary(&results) {
+        outln!("{}", summary.content);
+        if shared::is_stored_summary(summary) {
+            outln!("\n(Stored summary; --generate for a fresh one)");
+        } else {
+            outln!("\n(Summary written by Claude Code; --generate for a fresh one)");
+        }
         return Ok(());
     }
 
@@ -848,6 +2871,12 @@ fn summarize_session(index_path: &Path, session_id: String) -> Result<()> {
     results.sort_by_key(|r| r.sequence_num);
     let results: Vec<_> = results.into_iter().filter(|r| r.is_displayable()).collect();
 
+    let config = shared::get_config();
+    if let Some(summarizer) = &config.summarizer {
+        outln!("{}", shared::summarize_messages(summarizer, &results)?);
+        return Ok(());
+    }
+
     // Build conversation text
     let mut conversation = String::new();
     for r in &results {
@@ -0,0 +1,145 @@
+use crate::cli::index;
+use crate::shared::{LOCAL_HOST, get_imports_dir};
+use anyhow::{Result, bail};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Copy another machine's JSONL history into the imports dir under `host`, then index it.
+/// `source` is a directory (e.g. a copy of `~/.claude`) or a `.tar`/`.tar.zst` of one.
+pub fn import_history(index_path: &Path, source: &Path, host: Option<String>) -> Result<()> {
+    let host = host.unwrap_or_else(|| host_from_source(source));
+    if host.is_empty() || host == LOCAL_HOST || host.contains(['/', '\\']) || host.starts_with('.')
+    {
+        bail!("Invalid host name '{}': pass --host <name>", host);
+    }
+
+    let dest = get_imports_dir()?.join(&host);
+    let imported = if source.is_dir() {
+        copy_jsonl_tree(source, &dest)?
+    } else {
+        extract_jsonl_archive(source, &dest)?
+    };
+
+    if imported == 0 {
+        outln!("No JSONL files found in {}", source.display());
+        return Ok(());
+    }
+
+    outln!(
+        "Imported {} files from {} as host '{}'",
+        imported,
+        source.display(),
+        host
+    );
+    index::update(index_path)
+}
+
+/// Default host name: the source's file name without archive extensions
+fn host_from_source(source: &Path) -> String {
+    let name = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    [".tar.zst", ".tzst", ".tar"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// Destination-relative path for an archived/copied file: everything after a
+/// `projects` component (so both `~/.claude` and `~/.claude/projects` copies work).
+/// None for non-JSONL files and paths that would escape the destination.
+fn import_relative_path(path: &Path) -> Option<PathBuf> {
+    if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+        return None;
+    }
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) if c == "projects" => components.clear(),
+            Component::Normal(c) => components.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(components.iter().collect())
+}
+
+fn copy_jsonl_tree(source: &Path, dest: &Path) -> Result<usize> {
+    let pattern = source.join("**/*.jsonl");
+    let mut count = 0;
+    for file in glob::glob(&pattern.to_string_lossy())?.flatten() {
+        let Some(relative) = file
+            .strip_prefix(source)
+            .ok()
+            .and_then(import_relative_path)
+        else {
+            continue;
+        };
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&file, &target)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn extract_jsonl_archive(source: &Path, dest: &Path) -> Result<usize> {
+    let name = source.to_string_lossy();
+    let file = File::open(source)?;
+    let reader: Box<dyn Read> = if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        Box::new(zstd::Decoder::new(file)?)
+    } else if name.ends_with(".tar") {
+        Box::new(file)
+    } else {
+        bail!("Unsupported import source: expected a directory, .tar or .tar.zst");
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    let mut count = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(relative) = import_relative_path(&entry.path()?) else {
+            continue;
+        };
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&target)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_relative_path() {
+        assert_eq!(
+            import_relative_path(Path::new(".claude/projects/-home-me-app/abc.jsonl")),
+            Some(PathBuf::from("-home-me-app/abc.jsonl"))
+        );
+        assert_eq!(
+            import_relative_path(Path::new("./-home-me-app/abc.jsonl")),
+            Some(PathBuf::from("-home-me-app/abc.jsonl"))
+        );
+        assert_eq!(import_relative_path(Path::new("../evil/abc.jsonl")), None);
+        assert_eq!(import_relative_path(Path::new("projects/notes.txt")), None);
+    }
+
+    #[test]
+    fn test_host_from_source() {
+        assert_eq!(host_from_source(Path::new("/tmp/laptop.tar.zst")), "laptop");
+        assert_eq!(host_from_source(Path::new("desktop")), "desktop");
+    }
+}
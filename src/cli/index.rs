@@ -1,25 +1,33 @@
 use crate::shared::{
-    CacheManager, ExclusiveIndexAccess, SearchIndexer, SharedIndexAccess, discover_jsonl_files,
+    CacheManager, ExclusiveIndexAccess, IndexProgress, SearchIndexer, SharedIndexAccess,
+    discover_jsonl_files, local_time, rebuild_index,
 };
 use anyhow::Result;
-use std::path::Path;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use tracing::info;
 
+/// Rough JSONL indexing throughput, for rebuild estimates
+const REBUILD_BYTES_PER_SEC: f64 = 20.0 * 1024.0 * 1024.0;
+/// JSONL bytes per entry assumed when no indexed file gives a better ratio
+const DEFAULT_BYTES_PER_ENTRY: f64 = 2048.0;
+
 pub fn show_status(index_path: &Path) -> Result<()> {
-    println!("Index Status");
-    println!("============");
+    outln!("Index Status");
+    outln!("============");
 
     // Check lock status
     if ExclusiveIndexAccess::is_available() {
-        println!("Lock Status: Available");
+        outln!("Lock Status: Available");
     } else if SharedIndexAccess::is_available() {
-        println!("Lock Status: Read-only access available");
+        outln!("Lock Status: Read-only access available");
     } else {
-        println!("Lock Status: Locked by another process");
+        outln!("Lock Status: Locked by another process");
     }
 
     if !index_path.exists() {
-        println!("Index: Not found (will be created on next search)");
+        outln!("Index: Not found (will be created on next search)");
         return Ok(());
     }
 
@@ -27,7 +35,7 @@ pub fn show_status(index_path: &Path) -> Result<()> {
     let _lock = match SharedIndexAccess::acquire() {
         Ok(lock) => lock,
         Err(e) => {
-            println!("Index: Unable to read ({})", e);
+            outln!("Index: Unable to read ({})", e);
             return Ok(());
         }
     };
@@ -35,17 +43,17 @@ pub fn show_status(index_path: &Path) -> Result<()> {
     let cache_manager = CacheManager::new(index_path)?;
     let (total_files, total_entries, last_updated) = cache_manager.get_basic_stats();
 
-    println!("Index Path: {}", index_path.display());
-    println!("Total Files: {}", total_files);
-    println!("Total Entries: {}", total_entries);
+    outln!("Index Path: {}", index_path.display());
+    outln!("Total Files: {}", total_files);
+    outln!("Total Entries: {}", total_entries);
 
     if let Some(last_updated) = last_updated {
-        println!(
+        outln!(
             "Last Updated: {}",
-            last_updated.format("%Y-%m-%d %H:%M:%S UTC")
+            local_time(last_updated).format("%Y-%m-%d %H:%M:%S %:z")
         );
     } else {
-        println!("Last Updated: Never");
+        outln!("Last Updated: Never");
     }
 
     // Show disk usage
@@ -60,47 +68,165 @@ pub fn show_status(index_path: &Path) -> Result<()> {
         0.0
     };
 
-    println!("Index Size: {:.2} MB", cache_size_mb);
+    outln!("Index Size: {:.2} MB", cache_size_mb);
+
+    Ok(())
+}
+
+/// Print what `rebuild` would index: every file, marked new or changed
+/// against the current index, with estimated entries and time
+pub fn rebuild_plan(index_path: &Path) -> Result<()> {
+    let files = discover_jsonl_files()?;
+    let cache = CacheManager::new(index_path)?;
+
+    let sizes: Vec<u64> = files
+        .iter()
+        .map(|f| std::fs::metadata(f).map_or(0, |m| m.len()))
+        .collect();
+    let (known_bytes, known_entries) = files
+        .iter()
+        .zip(&sizes)
+        .filter_map(|(f, &size)| Some((size, cache.indexed_entry_count(f)?)))
+        .fold((0u64, 0usize), |(b, e), (size, entries)| {
+            (b + size, e + entries)
+        });
+    let bytes_per_entry = if known_entries > 0 {
+        known_bytes as f64 / known_entries as f64
+    } else {
+        DEFAULT_BYTES_PER_ENTRY
+    };
+
+    let (mut new, mut changed, mut entries) = (0, 0, 0.0);
+    for (file, &size) in files.iter().zip(&sizes) {
+        let indexed = cache.indexed_entry_count(file);
+        let status = match indexed {
+            None => "new",
+            Some(_) if cache.needs_indexing(file).unwrap_or(true) => "changed",
+            Some(_) => "",
+        };
+        match (status, indexed) {
+            ("", Some(count)) => entries += count as f64,
+            _ => entries += size as f64 / bytes_per_entry,
+        }
+        new += usize::from(status == "new");
+        changed += usize::from(status == "changed");
+        outln!("  {:>8} {}", status, file.display());
+    }
 
+    let total_bytes: u64 = sizes.iter().sum();
+    outln!(
+        "\nWould reindex {} files ({} new, {} changed since the last update): ~{} entries from {:.2} MB JSONL, about {} (dry run).",
+        files.len(),
+        new,
+        changed,
+        entries.round() as u64,
+        total_bytes as f64 / (1024.0 * 1024.0),
+        format_duration(total_bytes as f64 / REBUILD_BYTES_PER_SEC)
+    );
     Ok(())
 }
 
-pub fn rebuild(index_path: &Path) -> Result<()> {
+fn format_duration(seconds: f64) -> String {
+    match seconds.ceil() as u64 {
+        0..60 => format!("{}s", seconds.ceil().max(1.0)),
+        s => format!("{}m{:02}s", s / 60, s % 60),
+    }
+}
+
+/// Ask on the terminal before a full rebuild; elsewhere `--yes` is required
+fn confirm_rebuild(files: &[PathBuf]) -> Result<bool> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        anyhow::bail!("A full rebuild reindexes every file; pass --yes to confirm");
+    }
+    eprint!(
+        "Rebuild the index from {} files? Searches use the current index until it finishes. [y/N] ",
+        files.len()
+    );
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub fn rebuild(index_path: &Path, yes: bool) -> Result<()> {
+    let all_files = discover_jsonl_files()?;
+    if !yes && !confirm_rebuild(&all_files)? {
+        outln!("Rebuild cancelled.");
+        return Ok(());
+    }
+
     info!("Starting index rebuild...");
 
     // Acquire exclusive lock
     let _lock = ExclusiveIndexAccess::acquire()?;
 
+    info!("Found {} files to process", all_files.len());
+    // Built beside the current index, which stays searchable until the swap
+    let bar = progress_bar(all_files.len())?;
+    rebuild_index(index_path, all_files, report_to(&bar))?;
+    bar.finish_and_clear();
+
+    outln!("Index rebuild completed successfully.");
+    Ok(())
+}
+
+/// Index new and changed files, creating the index if needed
+pub fn update(index_path: &Path) -> Result<()> {
+    let _lock = ExclusiveIndexAccess::acquire()?;
+
+    let mut indexer = if index_path.join("meta.json").exists() {
+        SearchIndexer::open(index_path)?
+    } else {
+        SearchIndexer::new(index_path)?
+    };
     let mut cache_manager = CacheManager::new(index_path)?;
-    cache_manager.clear_cache()?;
+    index_with_progress(&mut cache_manager, &mut indexer, discover_jsonl_files()?)?;
 
-    let mut indexer = SearchIndexer::new(index_path)?;
-    let all_files = discover_jsonl_files()?;
+    outln!("Index updated.");
+    Ok(())
+}
 
-    info!("Found {} files to process", all_files.len());
-    cache_manager.update_incremental(&mut indexer, all_files)?;
+fn progress_bar(files: usize) -> Result<ProgressBar> {
+    Ok(
+        ProgressBar::new(files as u64).with_style(ProgressStyle::with_template(
+            "{bar:40} {pos}/{len} files, {msg} [{elapsed}]",
+        )?),
+    )
+}
+
+fn report_to(bar: &ProgressBar) -> impl FnMut(IndexProgress) + '_ {
+    |p| {
+        bar.set_position(p.files_done as u64);
+        bar.set_message(format!("{} entries", p.entries_indexed));
+    }
+}
 
-    println!("Index rebuild completed successfully.");
+/// Index files with a progress bar on stderr
+fn index_with_progress(
+    cache_manager: &mut CacheManager,
+    indexer: &mut SearchIndexer,
+    files: Vec<std::path::PathBuf>,
+) -> Result<()> {
+    let bar = progress_bar(files.len())?;
+    cache_manager.update_incremental_with_progress(indexer, files, report_to(&bar))?;
+    bar.finish_and_clear();
     Ok(())
 }
 
-pub fn vacuum(index_path: &Path) -> Result<()> {
+pub fn vacuum(index_path: &Path, yes: bool) -> Result<()> {
     info!("Starting index vacuum operation...");
 
-    // Acquire exclusive lock
-    let _lock = ExclusiveIndexAccess::acquire()?;
-
     if !index_path.exists() {
-        println!("No index found to vacuum.");
+        outln!("No index found to vacuum.");
         return Ok(());
     }
 
     // For now, vacuum is essentially a rebuild since Tantivy doesn't have
     // built-in vacuum. In the future, we could implement a more sophisticated
     // approach that only removes deleted entries.
-    println!("Vacuuming index by rebuilding...");
-    rebuild(index_path)?;
+    outln!("Vacuuming index by rebuilding...");
+    rebuild(index_path, yes)?;
 
-    println!("Index vacuum completed.");
+    outln!("Index vacuum completed.");
     Ok(())
 }
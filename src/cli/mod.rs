@@ -1,4 +1,23 @@
+/// `print!` through the output style, so `--no-emoji` covers all CLI output
+macro_rules! out {
+    ($($arg:tt)*) => {
+        print!("{}", $crate::shared::terminal::styled(&format!($($arg)*)))
+    };
+}
+
+/// `println!` through the output style
+macro_rules! outln {
+    () => {
+        println!()
+    };
+    ($($arg:tt)*) => {
+        println!("{}", $crate::shared::terminal::styled(&format!($($arg)*)))
+    };
+}
+
 pub mod commands;
+pub mod import;
 pub mod index;
+pub mod snapshot;
 
 pub use commands::*;
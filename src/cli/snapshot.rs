@@ -0,0 +1,184 @@
+use crate::shared::{self, CacheManager, ExclusiveIndexAccess, SCHEMA_VERSION, SearchIndexer};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_NAME: &str = "snapshot-manifest.json";
+
+/// Per-process files in the cache dir that never go into a snapshot
+const SKIPPED_FILES: &[&str] = &[
+    "index.lock",
+    "daemon.sock",
+    ".tantivy-writer.lock",
+    ".tantivy-meta.lock",
+];
+
+/// Written into the archive so imports can check compatibility and re-root paths
+#[derive(Serialize, Deserialize)]
+struct SnapshotManifest {
+    schema_version: u32,
+    #[serde(alias = "claude_dir", deserialize_with = "shared::one_or_many")]
+    claude_dirs: Vec<PathBuf>,
+    imports_dir: PathBuf,
+    exported_at: DateTime<Utc>,
+}
+
+/// Snapshot the index and cache metadata into a .tar.zst, holding the exclusive lock
+pub fn export_index(index_path: &Path, output: &Path) -> Result<()> {
+    if !index_path.join("meta.json").exists() {
+        bail!("No index to export. Please run 'claude-search index' first.");
+    }
+    let _lock = ExclusiveIndexAccess::acquire()?;
+
+    let config = shared::get_config();
+    let manifest = SnapshotManifest {
+        schema_version: SCHEMA_VERSION,
+        claude_dirs: config.get_claude_dirs()?,
+        imports_dir: config.get_imports_dir()?,
+        exported_at: Utc::now(),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+    // Write next to the target and rename, so a failed export leaves no partial file
+    let partial = output.with_extension("partial");
+    let encoder = zstd::Encoder::new(File::create(&partial)?, 3)?;
+    let mut archive = tar::Builder::new(encoder);
+    // redb files have holes; plain entries keep the archive a flat list of files
+    archive.sparse(false);
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.exported_at.timestamp() as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, MANIFEST_NAME, manifest_json.as_slice())?;
+
+    let mut count = 0;
+    for entry in fs::read_dir(index_path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if SKIPPED_FILES.contains(&name) || !entry.file_type()?.is_file() {
+            continue;
+        }
+        archive.append_path_with_name(entry.path(), name)?;
+        count += 1;
+    }
+
+    archive.into_inner()?.finish()?;
+    fs::rename(&partial, output)?;
+
+    let size_mb = fs::metadata(output)?.len() as f64 / (1024.0 * 1024.0);
+    outln!(
+        "Exported {} files ({:.1} MB) to {}",
+        count,
+        size_mb,
+        output.display()
+    );
+    Ok(())
+}
+
+/// Replace the index with a snapshot from `export_index`
+pub fn import_index(index_path: &Path, input: &Path) -> Result<()> {
+    let index_name = index_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("index");
+    let staging = index_path.with_file_name(format!(".{}.import", index_name));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let result = stage_snapshot(input, &staging).and_then(|manifest| {
+        let _lock = ExclusiveIndexAccess::acquire()?;
+        shared::swap_into_place(&staging, index_path)?;
+        rebase_snapshot_paths(index_path, &manifest)
+    });
+    let _ = fs::remove_dir_all(&staging);
+    result?;
+
+    outln!("Imported index snapshot from {}", input.display());
+    Ok(())
+}
+
+/// Unpack the archive into `staging` and validate it
+fn stage_snapshot(input: &Path, staging: &Path) -> Result<SnapshotManifest> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(File::open(input)?)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let entry_type = entry.header().entry_type();
+        // Snapshots are flat; anything else is not ours. Older exports hold
+        // sessions.redb as a GNU sparse entry.
+        if path.components().count() != 1 || !(entry_type.is_file() || entry_type.is_gnu_sparse()) {
+            bail!("Unexpected entry in snapshot: {}", path.display());
+        }
+        entry.unpack(staging.join(&path))?;
+    }
+
+    let manifest_path = staging.join(MANIFEST_NAME);
+    if !manifest_path.exists() || !staging.join("meta.json").exists() {
+        bail!("{} is not an index snapshot", input.display());
+    }
+    let manifest: SnapshotManifest = serde_json::from_slice(&fs::read(&manifest_path)?)?;
+    fs::remove_file(&manifest_path)?;
+
+    if manifest.schema_version != SCHEMA_VERSION {
+        bail!(
+            "Snapshot schema v{} does not match this build (v{}); run 'claude-search index rebuild' instead",
+            manifest.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(manifest)
+}
+
+/// Point cached file paths at this machine's Claude and imports dirs,
+/// pairing Claude dirs in the order they are configured. Documents name the
+/// file they were parsed from, for lazy content and for replacing them when
+/// it changes, so the moved files present here are reindexed.
+fn rebase_snapshot_paths(index_path: &Path, manifest: &SnapshotManifest) -> Result<()> {
+    let config = shared::get_config();
+    let mut cache = CacheManager::new(index_path)?;
+    let claude_dirs = config.get_claude_dirs()?;
+    let pairs = manifest
+        .claude_dirs
+        .iter()
+        .zip(claude_dirs)
+        .chain([(&manifest.imports_dir, config.get_imports_dir()?)]);
+    let mut moved = Vec::new();
+    for (from, to) in pairs {
+        if *from != to {
+            let rewritten = cache.rebase_paths(from, &to)?;
+            outln!(
+                "Re-rooted {} paths: {} -> {}",
+                rewritten.len(),
+                from.display(),
+                to.display()
+            );
+            moved.extend(rewritten);
+        }
+    }
+    // Files missing here keep their documents, which still search
+    moved.retain(|(_, new_path)| new_path.exists());
+    if moved.is_empty() {
+        return Ok(());
+    }
+
+    let mut indexer = SearchIndexer::open(index_path)?;
+    for (old_path, _) in &moved {
+        indexer.delete_file(old_path)?;
+    }
+    let files: Vec<PathBuf> = moved.into_iter().map(|(_, new_path)| new_path).collect();
+    cache.forget_files(&files);
+    let count = files.len();
+    cache.update_incremental(&mut indexer, files)?;
+    indexer.commit()?;
+    outln!("Reindexed {} moved files", count);
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use claude_conversation_search::shared::terminal::init_output_style;
+use claude_conversation_search::shared::{active_profile, set_profile};
 use claude_conversation_search::{cli, mcp};
 
 use anyhow::Result;
@@ -12,6 +14,18 @@ struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// When to emit terminal escapes such as hyperlinks
+    #[arg(long, value_enum, global = true, default_value = "auto")]
+    color: cli::ColorArg,
+
+    /// Replace emoji with plain ASCII labels
+    #[arg(long, global = true)]
+    no_emoji: bool,
+
+    /// Use a separate config, cache and data dir (default: $CLAUDE_SEARCH_PROFILE)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
     #[command(subcommand)]
     command: Option<cli::CliCommands>,
 }
@@ -30,12 +44,20 @@ async fn main() -> Result<()> {
     }));
 
     let args = Cli::parse();
+    // Checked up front, as profile names become directory names
+    if let Some(profile) = args.profile.clone().or_else(active_profile) {
+        set_profile(&profile)?;
+    }
+    init_output_style(args.color.into(), !args.no_emoji);
 
     match args.command {
-        Some(cli::CliCommands::Mcp) | None => {
-            // Default to MCP server mode when no subcommand provided
-            mcp::run_mcp_server().await
-        }
+        // Default to MCP server mode when no subcommand provided
+        None => mcp::run_mcp_server().await,
+        Some(cli::CliCommands::Mcp { transport, port }) => match transport {
+            cli::TransportArg::Stdio => mcp::run_mcp_server().await,
+            cli::TransportArg::Http => mcp::run_http_server(port).await,
+        },
+        Some(cli::CliCommands::Daemon) => mcp::run_daemon().await,
         Some(command) => cli::run_cli(args.verbose, command),
     }
 }
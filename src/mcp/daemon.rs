@@ -0,0 +1,219 @@
+//! Shared index daemon: one process owns the index and serves MCP JSON-RPC
+//! (newline-delimited, same as stdio) on a Unix socket in the cache dir.
+//! The stdio MCP entry point and CLI search commands proxy to it when running.
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::path::PathBuf;
+
+use super::output_budget::MAX_OUTPUT_TOKENS_ARG;
+use crate::shared::{get_cache_dir, get_config};
+
+/// Socket path inside the cache directory
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join("daemon.sock"))
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::io::{BufRead, BufReader as StdBufReader, Write};
+    use std::os::unix::net::UnixStream as StdUnixStream;
+    use std::sync::Arc;
+    use tokio::io::{AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::Mutex;
+    use tracing::debug;
+
+    use crate::mcp::server::{McpServer, init_server_logging, serve_connection};
+
+    async fn handle_connection(stream: UnixStream, server: Arc<Mutex<McpServer>>) -> Result<()> {
+        let (read_half, mut write_half) = stream.into_split();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let writer = tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                write_half.write_all(message.as_bytes()).await?;
+                write_half.write_all(b"\n").await?;
+                write_half.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+
+        serve_connection(BufReader::new(read_half), tx, &server).await?;
+        writer.await??;
+        Ok(())
+    }
+
+    /// Run the daemon until killed
+    pub async fn run_daemon() -> Result<()> {
+        init_server_logging();
+
+        let path = socket_path()?;
+        if path.exists() {
+            if StdUnixStream::connect(&path).is_ok() {
+                return Err(anyhow!("Daemon already running on {}", path.display()));
+            }
+            // Stale socket from a previous run
+            std::fs::remove_file(&path)?;
+        }
+
+        let server = Arc::new(Mutex::new(McpServer::new()?));
+        let listener = UnixListener::bind(&path)?;
+        eprintln!("Daemon listening on {}", path.display());
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, server).await {
+                    debug!("Daemon client disconnected: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Relay stdio MCP traffic to a running daemon. Returns false if none is running.
+    pub async fn proxy_stdio() -> Result<bool> {
+        let Ok(path) = socket_path() else {
+            return Ok(false);
+        };
+        let Ok(stream) = UnixStream::connect(&path).await else {
+            return Ok(false);
+        };
+
+        let (mut read_half, mut write_half) = stream.into_split();
+        let (mut stdin, mut stdout) = (tokio::io::stdin(), tokio::io::stdout());
+        let upstream = async {
+            tokio::io::copy(&mut stdin, &mut write_half).await?;
+            write_half.shutdown().await
+        };
+        let downstream = tokio::io::copy(&mut read_half, &mut stdout);
+        tokio::select! {
+            r = upstream => r?,
+            r = downstream => { r?; }
+        }
+        Ok(true)
+    }
+
+    /// Blocking client used by CLI commands
+    pub struct DaemonClient {
+        reader: StdBufReader<StdUnixStream>,
+        writer: StdUnixStream,
+        next_id: u64,
+    }
+
+    impl DaemonClient {
+        /// Connect if a daemon is running and exposes `tool`
+        pub fn connect_for(tool: &str) -> Option<Self> {
+            get_config().tools.advertised_name(tool)?;
+            Self::connect()
+        }
+
+        /// Connect if a daemon is running
+        pub fn connect() -> Option<Self> {
+            let stream = StdUnixStream::connect(socket_path().ok()?).ok()?;
+            let writer = stream.try_clone().ok()?;
+            Some(Self {
+                reader: StdBufReader::new(stream),
+                writer,
+                next_id: 1,
+            })
+        }
+
+        /// Call a built-in tool (by its unrenamed name) and return its text content and error flag
+        pub fn call_tool(&mut self, name: &str, mut arguments: Value) -> Result<(String, bool)> {
+            // A terminal has no context window to protect, so print it all
+            if let Some(args) = arguments.as_object_mut() {
+                args.entry(MAX_OUTPUT_TOKENS_ARG).or_insert(0.into());
+            }
+            let config = get_config();
+            let name = config
+                .tools
+                .advertised_name(name)
+                .ok_or_else(|| anyhow!("Tool {} is disabled in config", name))?;
+            let request = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": self.next_id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments },
+            });
+            self.next_id += 1;
+
+            writeln!(self.writer, "{}", request)?;
+            self.writer.flush()?;
+
+            // Skip notifications until the response arrives
+            let response = loop {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Err(anyhow!("Daemon closed connection"));
+                }
+                let message: Value = serde_json::from_str(&line)?;
+                if message.get("id").is_some() {
+                    break message;
+                }
+            };
+            if let Some(error) = response.get("error") {
+                let message = error.get("message").and_then(|m| m.as_str());
+                return Err(anyhow!("{}", message.unwrap_or("Daemon error")));
+            }
+
+            let result = response.get("result").cloned().unwrap_or_default();
+            let text = result
+                .get("content")
+                .and_then(|c| c.as_array())
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            let is_error = result
+                .get("isError")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            // Show the plain message, not the agent-facing recovery hint
+            let error_message = result
+                .pointer("/structuredContent/error/message")
+                .and_then(|m| m.as_str());
+            if let (true, Some(message)) = (is_error, error_message) {
+                return Ok((message.to_string(), true));
+            }
+            Ok((text, is_error))
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{DaemonClient, proxy_stdio, run_daemon};
+
+#[cfg(windows)]
+pub async fn run_daemon() -> Result<()> {
+    Err(anyhow!("daemon mode is not supported on Windows"))
+}
+
+#[cfg(windows)]
+pub async fn proxy_stdio() -> Result<bool> {
+    Ok(false)
+}
+
+#[cfg(windows)]
+pub struct DaemonClient;
+
+#[cfg(windows)]
+impl DaemonClient {
+    pub fn connect_for(_tool: &str) -> Option<Self> {
+        None
+    }
+
+    pub fn connect() -> Option<Self> {
+        None
+    }
+
+    pub fn call_tool(&mut self, _name: &str, _arguments: Value) -> Result<(String, bool)> {
+        Err(anyhow!("daemon mode is not supported on Windows"))
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use super::server::{McpServer, init_server_logging, serve_message};
+
+/// Path of the single MCP endpoint (streamable HTTP transport)
+const MCP_ENDPOINT: &str = "/mcp";
+
+/// Reject request bodies larger than this; tool arguments are tiny
+const MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// Minimal parsed HTTP/1.1 request
+#[derive(Debug)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    /// Header names lowercased
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|s| s.as_str())
+    }
+
+    fn wants_close(&self) -> bool {
+        self.header("connection")
+            .is_some_and(|v| v.eq_ignore_ascii_case("close"))
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    reason: &'static str,
+    content_type: Option<&'static str>,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn json(body: String) -> Self {
+        Self {
+            status: 200,
+            reason: "OK",
+            content_type: Some("application/json"),
+            body: body.into_bytes(),
+        }
+    }
+
+    fn empty(status: u16, reason: &'static str) -> Self {
+        Self {
+            status,
+            reason,
+            content_type: None,
+            body: Vec::new(),
+        }
+    }
+}
+
+/// Read one request from the connection. Returns None on clean EOF.
+async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<HttpRequest>> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts
+        .next()
+        .ok_or_else(|| anyhow!("Empty request line"))?
+        .to_string();
+    let path = parts
+        .next()
+        .ok_or_else(|| anyhow!("Missing request path"))?
+        .to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(anyhow!("Connection closed in headers"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let content_length = headers
+        .get("content-length")
+        .map(|v| v.parse::<usize>())
+        .transpose()?
+        .unwrap_or(0);
+    if content_length > MAX_BODY_BYTES {
+        return Err(anyhow!("Request body too large: {} bytes", content_length));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(HttpRequest {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+/// Only accept browser-originated requests from localhost (DNS rebinding guard)
+fn origin_allowed(origin: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let authority = origin
+        .split("://")
+        .nth(1)
+        .unwrap_or(origin)
+        .split('/')
+        .next()
+        .unwrap_or("");
+    // An IPv6 host is bracketed and holds colons, so take it before the port
+    let host = match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(""),
+        None => authority.split(':').next().unwrap_or(""),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+async fn route(request: &HttpRequest, server: &Mutex<McpServer>) -> HttpResponse {
+    if request.path.split('?').next() != Some(MCP_ENDPOINT) {
+        return HttpResponse::empty(404, "Not Found");
+    }
+    if !origin_allowed(request.header("origin")) {
+        return HttpResponse::empty(403, "Forbidden");
+    }
+
+    match request.method.as_str() {
+        "POST" => {
+            let Ok(line) = std::str::from_utf8(&request.body) else {
+                return HttpResponse::empty(400, "Bad Request");
+            };
+            // POST replies carry a single JSON body, so progress notifications are dropped
+            let reply = serve_message(server, line, None).await;
+            server.lock().await.start_background_refresh();
+            match reply {
+                Ok(Some(json)) => HttpResponse::json(json),
+                // Notifications and responses are accepted without a body
+                Ok(None) => HttpResponse::empty(202, "Accepted"),
+                Err(e) => {
+                    debug!("Failed to handle HTTP message: {}", e);
+                    HttpResponse::empty(500, "Internal Server Error")
+                }
+            }
+        }
+        // No server-initiated SSE stream; clients fall back to POST-only
+        _ => HttpResponse::empty(405, "Method Not Allowed"),
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: &HttpResponse) -> Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+        response.status,
+        response.reason,
+        response.body.len()
+    );
+    if let Some(content_type) = response.content_type {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    if response.status == 405 {
+        head.push_str("Allow: POST\r\n");
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn handle_connection(stream: TcpStream, server: Arc<Mutex<McpServer>>) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    while let Some(request) = read_request(&mut reader).await? {
+        debug!("HTTP {} {}", request.method, request.path);
+        let response = route(&request, &server).await;
+        write_response(reader.get_mut(), &response).await?;
+        if request.wants_close() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Serve MCP over streamable HTTP on localhost, sharing one index across clients
+pub async fn run_http_server(port: u16) -> Result<()> {
+    init_server_logging();
+
+    let server = Arc::new(Mutex::new(McpServer::new()?));
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    eprintln!(
+        "MCP HTTP transport listening on http://{}{}",
+        listener.local_addr()?,
+        MCP_ENDPOINT
+    );
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, server).await {
+                debug!("Connection from {} closed: {}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request_with_body() {
+        let raw = b"POST /mcp HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 13\r\n\r\n{\"jsonrpc\":1}";
+        let mut reader = BufReader::new(&raw[..]);
+        let request = read_request(&mut reader).await.unwrap().unwrap();
+
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/mcp");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, b"{\"jsonrpc\":1}");
+        assert!(read_request(&mut reader).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_origin_allowed() {
+        assert!(origin_allowed(None));
+        assert!(origin_allowed(Some("http://localhost:3000")));
+        assert!(origin_allowed(Some("http://127.0.0.1")));
+        assert!(!origin_allowed(Some("https://evil.example.com")));
+        assert!(!origin_allowed(Some("http://localhost.evil.com")));
+        assert!(origin_allowed(Some("http://[::1]:8080")));
+        assert!(origin_allowed(Some("http://[::1]")));
+        assert!(!origin_allowed(Some("http://[::2]:8080")));
+    }
+}
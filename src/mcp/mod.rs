@@ -1,4 +1,14 @@
+pub mod daemon;
+pub mod http;
+pub mod output_budget;
+pub mod refresh;
+pub mod sampling;
 pub mod server;
 pub mod stats_analyzer;
+pub mod summary_job;
+pub mod tool_error;
+pub mod topic_analyzer;
 
+pub use daemon::run_daemon;
+pub use http::run_http_server;
 pub use server::run_mcp_server;
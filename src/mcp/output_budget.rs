@@ -0,0 +1,261 @@
+use super::tool_error::ToolError;
+use crate::shared::{estimate_tokens, get_config};
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Argument every tool accepts to cap its output
+pub const MAX_OUTPUT_TOKENS_ARG: &str = "max_output_tokens";
+/// Tool returning the rest of output cut by the budget
+pub const CONTINUE_OUTPUT_TOOL: &str = "continue_output";
+pub const CONTINUATION_ARG: &str = "continuation";
+
+/// Cut outputs kept for `continue_output`, oldest dropped first
+const MAX_STORED_OUTPUTS: usize = 32;
+const STORED_OUTPUT_TTL: Duration = Duration::from_secs(3600);
+
+/// `max_output_tokens` of a call, defaulting to the configured one
+pub fn budget_args(args: Option<&Value>) -> Result<usize, ToolError> {
+    match args.and_then(|a| a.get(MAX_OUTPUT_TOKENS_ARG)) {
+        None | Some(Value::Null) => Ok(get_config().tools.max_output_tokens),
+        Some(value) => value.as_u64().map(|n| n as usize).ok_or_else(|| {
+            ToolError::invalid_argument(format!(
+                "'{}' must be a non-negative integer",
+                MAX_OUTPUT_TOKENS_ARG
+            ))
+        }),
+    }
+}
+
+/// Advertise the output budget argument in a tool's input schema
+pub fn add_budget_properties(input_schema: &mut Value) {
+    let Some(properties) = input_schema
+        .get_mut("properties")
+        .and_then(|p| p.as_object_mut())
+    else {
+        return;
+    };
+    properties.insert(
+        MAX_OUTPUT_TOKENS_ARG.to_string(),
+        json!({
+            "type": "integer",
+            "description": "Estimated tokens of output returned at most; longer output ends with a +more: continue_output(continuation=...) line to fetch the rest. 0 = no limit. Defaults to tools.max_output_tokens in config",
+            "optional": true
+        }),
+    );
+}
+
+fn to_string(chars: &[char]) -> String {
+    chars.iter().collect()
+}
+
+/// Characters of `rest` that fit in `max_tokens` estimated tokens: whole
+/// lines while they fit, else as much of the first line as fits
+fn page_end(rest: &[char], max_tokens: usize) -> usize {
+    let mut end = 0;
+    let mut tokens = 0;
+    for line in rest.split_inclusive(|&c| c == '\n') {
+        let line_tokens = estimate_tokens(&to_string(line));
+        if tokens + line_tokens > max_tokens {
+            break;
+        }
+        tokens += line_tokens;
+        end += line.len();
+    }
+    if end == 0 {
+        for word in rest.split_inclusive(|c| c.is_whitespace()) {
+            let word_tokens = estimate_tokens(&to_string(word));
+            if tokens + word_tokens > max_tokens {
+                break;
+            }
+            tokens += word_tokens;
+            end += word.len();
+        }
+    }
+    if end == 0 {
+        // One word over the budget: no character is more than a token
+        end = max_tokens.clamp(1, rest.len());
+    }
+    end
+}
+
+struct StoredOutput {
+    id: String,
+    stored_at: Instant,
+    chars: Vec<char>,
+}
+
+/// Tool outputs cut by the budget, so `continue_output` can return the rest
+/// without running the tool again
+#[derive(Default)]
+pub struct ContinuationStore {
+    outputs: VecDeque<StoredOutput>,
+}
+
+impl ContinuationStore {
+    fn insert(&mut self, id: String, chars: Vec<char>) {
+        self.outputs
+            .retain(|output| output.stored_at.elapsed() < STORED_OUTPUT_TTL);
+        while self.outputs.len() >= MAX_STORED_OUTPUTS {
+            self.outputs.pop_front();
+        }
+        self.outputs.push_back(StoredOutput {
+            id,
+            stored_at: Instant::now(),
+            chars,
+        });
+    }
+
+    fn get(&self, id: &str) -> Option<&[char]> {
+        self.outputs
+            .iter()
+            .find(|output| output.id == id && output.stored_at.elapsed() < STORED_OUTPUT_TTL)
+            .map(|output| output.chars.as_slice())
+    }
+}
+
+/// The part of stored output `id` from character `offset` that fits in
+/// `max_tokens` (0 = no limit), followed by a continuation to the rest
+fn render_page(id: &str, chars: &[char], offset: usize, max_tokens: usize, tool: &str) -> String {
+    let rest = &chars[offset.min(chars.len())..];
+    if max_tokens == 0 || estimate_tokens(&to_string(rest)) <= max_tokens {
+        return to_string(rest);
+    }
+    let end = page_end(rest, max_tokens);
+    let page = to_string(&rest[..end]);
+    let separator = if page.ends_with('\n') { "" } else { "\n" };
+    format!(
+        "{}{}\n+more: {}({}=\"{}:{}\") (~{} more tokens)\n",
+        page,
+        separator,
+        tool,
+        CONTINUATION_ARG,
+        id,
+        offset + end,
+        estimate_tokens(&to_string(&rest[end..]))
+    )
+}
+
+/// Apply the budget to a tool's text result in place: output over it is
+/// stored and cut to its first page. Errors are left whole, and so is
+/// everything when `continue_output` is disabled.
+pub fn apply_output_budget(result: &mut Value, max_tokens: usize, store: &mut ContinuationStore) {
+    if max_tokens == 0 || result.get("isError").and_then(|v| v.as_bool()) == Some(true) {
+        return;
+    }
+    let config = get_config();
+    let Some(tool) = config.tools.advertised_name(CONTINUE_OUTPUT_TOOL) else {
+        return;
+    };
+    let Some(text) = result
+        .get_mut("content")
+        .and_then(|c| c.get_mut(0))
+        .and_then(|item| item.get_mut("text"))
+    else {
+        return;
+    };
+    let Some(output) = text.as_str().filter(|t| estimate_tokens(t) > max_tokens) else {
+        return;
+    };
+    let chars: Vec<char> = output.chars().collect();
+    let id = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+    let page = render_page(&id, &chars, 0, max_tokens, tool);
+    store.insert(id, chars);
+    *text = Value::String(page);
+}
+
+/// Result of a `continue_output` call: the next page of a stored output
+pub fn continue_output(
+    args: Option<&Value>,
+    max_tokens: usize,
+    store: &ContinuationStore,
+) -> Result<Value, ToolError> {
+    let token = args
+        .and_then(|a| a.get(CONTINUATION_ARG))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| ToolError::missing_argument(CONTINUATION_ARG))?;
+    let (id, offset) = token
+        .rsplit_once(':')
+        .and_then(|(id, offset)| Some((id, offset.parse::<usize>().ok()?)))
+        .ok_or_else(|| {
+            ToolError::invalid_argument(format!(
+                "'{}' is not a continuation from a +more: line",
+                token
+            ))
+        })?;
+    let chars = store.get(id).ok_or_else(|| {
+        ToolError::not_found(format!(
+            "Output {} is no longer kept (outputs are kept for {} minutes, the last {})",
+            id,
+            STORED_OUTPUT_TTL.as_secs() / 60,
+            MAX_STORED_OUTPUTS
+        ))
+        .retry_with(json!({ "action": "repeat_original_call" }))
+    })?;
+    if offset >= chars.len() {
+        return Err(ToolError::invalid_argument(format!(
+            "No more output: {} is past its end ({} chars)",
+            token,
+            chars.len()
+        )));
+    }
+    let config = get_config();
+    let tool = config
+        .tools
+        .advertised_name(CONTINUE_OUTPUT_TOOL)
+        .unwrap_or(CONTINUE_OUTPUT_TOOL);
+    Ok(json!({
+        "content": [{ "type": "text", "text": render_page(id, chars, offset, max_tokens, tool) }],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_text(result: &Value) -> &str {
+        result["content"][0]["text"].as_str().unwrap()
+    }
+
+    fn continuation(page: &str) -> Value {
+        let token = page.split("continuation=\"").nth(1).unwrap();
+        json!({ CONTINUATION_ARG: token.split('"').next().unwrap() })
+    }
+
+    #[test]
+    fn test_cut_output_continues_from_the_store() {
+        let text = "first line of output\nsecond line of output\nthird line of output\n";
+        let mut store = ContinuationStore::default();
+        let mut result = json!({ "content": [{ "type": "text", "text": text }] });
+        apply_output_budget(&mut result, 1000, &mut store);
+        assert_eq!(page_text(&result), text);
+
+        apply_output_budget(&mut result, 12, &mut store);
+        let page = page_text(&result);
+        assert!(page.starts_with(
+            "first line of output\nsecond line of output\n\n+more: continue_output(continuation=\""
+        ));
+        let args = continuation(page);
+        let next = continue_output(Some(&args), 12, &store).unwrap();
+        assert_eq!(page_text(&next), "third line of output\n");
+        // Tokens stay valid, so a retried call gets the same page
+        let again = continue_output(Some(&args), 12, &store).unwrap();
+        assert_eq!(page_text(&again), "third line of output\n");
+
+        // A line over the budget on its own is cut inside it
+        let mut result = json!({ "content": [{ "type": "text", "text": text }] });
+        apply_output_budget(&mut result, 2, &mut store);
+        assert!(page_text(&result).starts_with("first"));
+        let rest = continue_output(Some(&continuation(page_text(&result))), 0, &store).unwrap();
+        assert!(page_text(&rest).ends_with("third line of output\n"));
+
+        let unknown = json!({ CONTINUATION_ARG: "000000000000:5" });
+        assert_eq!(
+            continue_output(Some(&unknown), 12, &store)
+                .unwrap_err()
+                .code,
+            "not_found"
+        );
+        assert!(continue_output(Some(&json!({ CONTINUATION_ARG: "junk" })), 12, &store).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Reindexing of history files changed since they were indexed, run in the
+//! background after a request is answered so the next one searches fresh
+//! results instead of reporting a stale index. The caller's own session is
+//! written to all along; it is only indexed once it has gone quiet, and only
+//! when `index.current_session_debounce_secs` asks for it.
+
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+use crate::shared::{
+    CacheManager, ExclusiveIndexAccess, SearchIndexer, SessionStore, current_session_file,
+    discover_jsonl_files, get_config,
+};
+
+/// Background refreshes of one server. Its lock is held by whatever writes
+/// the index in this process, so a refresh never races a reindex tool call,
+/// and records when the last refresh started.
+#[derive(Clone, Default)]
+pub(crate) struct BackgroundRefresh {
+    last_started: Arc<Mutex<Option<Instant>>>,
+    /// Set while a task waits for the caller's session to go quiet
+    session_waiting: Arc<AtomicBool>,
+    /// Set once a refresh changed the cache metadata, until taken
+    changed: Arc<AtomicBool>,
+}
+
+impl BackgroundRefresh {
+    /// Wait out a running refresh and keep others from starting while the
+    /// guard is held
+    pub(crate) fn hold(&self) -> Result<MutexGuard<'_, Option<Instant>>> {
+        self.last_started
+            .lock()
+            .map_err(|_| anyhow!("Background refresh lock poisoned"))
+    }
+
+    /// Whether a refresh changed the cache metadata since the last call, so
+    /// the server reloads what it searches with
+    pub(crate) fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+
+    /// True, and recorded as started, when no refresh is running or held
+    /// off and the last one started at least `interval` ago
+    fn claim(&self, interval: Duration) -> bool {
+        let Ok(mut last_started) = self.last_started.try_lock() else {
+            return false;
+        };
+        if last_started.is_some_and(|started| started.elapsed() < interval) {
+            return false;
+        }
+        *last_started = Some(Instant::now());
+        true
+    }
+
+    /// Reindex changed and new files of the index in `cache_dir`, unless
+    /// disabled, too soon after the last refresh or already running, and
+    /// have the caller's session indexed once quiet if so configured
+    pub(crate) fn start(
+        &self,
+        cache_dir: &Path,
+        cache: &Arc<Mutex<CacheManager>>,
+    ) -> Option<JoinHandle<()>> {
+        let config = get_config();
+        let debounce = config.index.current_session_debounce_secs;
+        let interval = config.index.background_refresh_secs;
+        let current_session = current_session_file();
+        if debounce > 0
+            && let Some(session_file) = &current_session
+        {
+            self.index_when_quiet(
+                cache_dir,
+                cache,
+                session_file,
+                Duration::from_secs(debounce),
+            );
+        }
+        if interval == 0 || !self.claim(Duration::from_secs(interval)) {
+            return None;
+        }
+        let refresh = self.clone();
+        let cache_dir = cache_dir.to_path_buf();
+        let cache = cache.clone();
+        Some(tokio::task::spawn_blocking(move || {
+            let result = refresh.hold().and_then(|_writing| {
+                let files = discover_jsonl_files()?
+                    .into_iter()
+                    .filter(|f| Some(f) != current_session.as_ref())
+                    .collect();
+                refresh.reindex_changed(&cache_dir, &cache, files)
+            });
+            match result {
+                Ok(0) => {}
+                Ok(count) => info!("Background refresh reindexed {} files", count),
+                Err(e) => debug!("Background refresh failed: {}", e),
+            }
+        }))
+    }
+
+    /// Index `session_file` once it has gone `debounce` without a write.
+    /// One task waits at a time; it is started again by the next request.
+    fn index_when_quiet(
+        &self,
+        cache_dir: &Path,
+        cache: &Arc<Mutex<CacheManager>>,
+        session_file: &Path,
+        debounce: Duration,
+    ) {
+        if self.session_waiting.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let refresh = self.clone();
+        let cache_dir = cache_dir.to_path_buf();
+        let cache = cache.clone();
+        let session_file = session_file.to_path_buf();
+        tokio::spawn(async move {
+            while let Some(wait) = modified_time(&session_file)
+                .and_then(|modified| wait_until_quiet(modified, debounce))
+            {
+                tokio::time::sleep(wait).await;
+            }
+            let index = tokio::task::spawn_blocking(move || {
+                refresh.session_waiting.store(false, Ordering::SeqCst);
+                refresh.hold().and_then(|_writing| {
+                    refresh.reindex_changed(&cache_dir, &cache, vec![session_file])
+                })
+            });
+            match index.await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(_)) => info!("Indexed the current session"),
+                Ok(Err(e)) => debug!("Current session indexing failed: {}", e),
+                Err(e) => debug!("Current session indexing failed: {}", e),
+            }
+        });
+    }
+
+    /// Reindex those of `files` changed since they were indexed; returns how
+    /// many there were. Nothing is done while another process holds the
+    /// index or the session store; the next refresh picks the files up.
+    /// Indexing works on a copy of the cache metadata, so tool calls aren't
+    /// held up meanwhile; the server's copy is reloaded before the index
+    /// lock is let go, so it never saves over the refresh.
+    fn reindex_changed(
+        &self,
+        cache_dir: &Path,
+        cache: &Mutex<CacheManager>,
+        files: Vec<PathBuf>,
+    ) -> Result<usize> {
+        let Ok(_lock) = ExclusiveIndexAccess::acquire() else {
+            debug!("Skipping background refresh: another process is indexing");
+            return Ok(0);
+        };
+        if SessionStore::new(cache_dir).is_busy() {
+            debug!("Skipping background refresh: the session store is in use");
+            return Ok(0);
+        }
+        let mut latest = CacheManager::new(cache_dir)?;
+        let changed: Vec<_> = files
+            .into_iter()
+            .filter(|f| latest.needs_indexing(f).unwrap_or(false))
+            .collect();
+        if changed.is_empty() {
+            return Ok(0);
+        }
+        let count = changed.len();
+        let mut indexer = SearchIndexer::open(cache_dir)?;
+        let result = latest.update_incremental(&mut indexer, changed);
+        let reloaded = cache
+            .lock()
+            .map_err(|_| anyhow!("Cache metadata lock poisoned"))?
+            .reload_if_changed()?;
+        if reloaded {
+            self.changed.store(true, Ordering::SeqCst);
+        }
+        result.map(|_| count)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+/// How much longer a file last written at `modified` must stay unwritten to
+/// have been quiet for `debounce`; None once it has
+fn wait_until_quiet(modified: SystemTime, debounce: Duration) -> Option<Duration> {
+    let quiet = modified.elapsed().unwrap_or_default();
+    debounce.checked_sub(quiet).filter(|wait| !wait.is_zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refreshes_are_rate_limited_and_held_off_by_writers() {
+        let refresh = BackgroundRefresh::default();
+        assert!(refresh.claim(Duration::from_secs(60)));
+        assert!(!refresh.claim(Duration::from_secs(60)), "Too soon");
+        assert!(refresh.claim(Duration::ZERO));
+
+        let writing = refresh.hold().unwrap();
+        assert!(
+            !refresh.clone().claim(Duration::ZERO),
+            "Index being written"
+        );
+        drop(writing);
+        assert!(refresh.claim(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_current_session_waits_until_quiet() {
+        let debounce = Duration::from_secs(30);
+        let just_written = SystemTime::now();
+        let wait = wait_until_quiet(just_written, debounce).unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= debounce);
+        let quiet = SystemTime::now() - Duration::from_secs(45);
+        assert_eq!(wait_until_quiet(quiet, debounce), None);
+    }
+}
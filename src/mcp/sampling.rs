@@ -0,0 +1,251 @@
+//! Requests from the server to the connected client, for MCP sampling
+//! (`sampling/createMessage`): the client's model summarizes sessions when
+//! no local summarizer is configured
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use super::server::Notifier;
+use crate::shared::{
+    SearchResult, chunk_prompt, combine_prompt, conversation_chunks, summary_prompt,
+};
+
+/// Clients may ask the user to approve each request, so allow for a human
+const SAMPLING_TIMEOUT: Duration = Duration::from_secs(300);
+/// Estimated tokens of conversation per sampling request
+const SAMPLING_CHUNK_TOKENS: usize = 30_000;
+/// Longest answer asked for per request
+const SAMPLING_MAX_TOKENS: u64 = 1024;
+/// Prefix of the ids of requests this server sends, apart from client ids
+const REQUEST_ID_PREFIX: &str = "server-";
+
+type Waiting = HashMap<String, oneshot::Sender<Result<Value>>>;
+
+/// One client connection: where messages to it go, what it said it supports
+/// and the requests sent to it that await a response
+#[derive(Clone)]
+pub(crate) struct Peer {
+    notifier: Notifier,
+    sampling: Arc<AtomicBool>,
+    next_id: Arc<AtomicU64>,
+    waiting: Arc<Mutex<Waiting>>,
+}
+
+impl Peer {
+    pub(crate) fn new(notifier: Notifier) -> Self {
+        Self {
+            notifier,
+            sampling: Arc::new(AtomicBool::new(false)),
+            next_id: Arc::new(AtomicU64::new(1)),
+            waiting: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub(crate) fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+
+    /// Record the client's `capabilities` from its initialize request
+    pub(crate) fn set_capabilities(&self, capabilities: Option<&Value>) {
+        let sampling = capabilities.is_some_and(|c| c.get("sampling").is_some());
+        self.sampling.store(sampling, Ordering::Relaxed);
+    }
+
+    pub(crate) fn supports_sampling(&self) -> bool {
+        self.sampling.load(Ordering::Relaxed)
+    }
+
+    /// Hand a response from the client to the request awaiting it. Returns
+    /// false for anything that isn't a response to one of ours.
+    pub(crate) fn deliver(&self, line: &str) -> bool {
+        let Ok(message) = serde_json::from_str::<Value>(line) else {
+            return false;
+        };
+        if message.get("method").is_some() {
+            return false;
+        }
+        let Some(id) = message.get("id").and_then(Value::as_str) else {
+            return false;
+        };
+        let Some(sender) = self.waiting.lock().ok().and_then(|mut w| w.remove(id)) else {
+            return false;
+        };
+        let result = match message.get("error") {
+            Some(error) => Err(anyhow!(
+                "{}",
+                error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("client returned an error")
+            )),
+            None => Ok(message.get("result").cloned().unwrap_or_default()),
+        };
+        let _ = sender.send(result);
+        true
+    }
+
+    /// Fail every request still waiting, once the connection is gone
+    pub(crate) fn close(&self) {
+        if let Ok(mut waiting) = self.waiting.lock() {
+            waiting.clear();
+        }
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = format!(
+            "{}{}",
+            REQUEST_ID_PREFIX,
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let (sender, receiver) = oneshot::channel();
+        self.waiting
+            .lock()
+            .map_err(|_| anyhow!("client request lock poisoned"))?
+            .insert(id.clone(), sender);
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        if self.notifier.send(request.to_string()).is_err() {
+            self.close();
+            bail!("client disconnected");
+        }
+        let result = tokio::time::timeout(SAMPLING_TIMEOUT, receiver).await;
+        if let Ok(mut waiting) = self.waiting.lock() {
+            waiting.remove(&id);
+        }
+        match result {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => bail!("client disconnected"),
+            Err(_) => bail!("no answer to {} within {:?}", method, SAMPLING_TIMEOUT),
+        }
+    }
+
+    /// One `sampling/createMessage` round: the answer's text and the model
+    /// the client used
+    async fn create_message(&self, prompt: String) -> Result<(String, String)> {
+        let result = self
+            .request(
+                "sampling/createMessage",
+                serde_json::json!({
+                    "messages": [
+                        { "role": "user", "content": { "type": "text", "text": prompt } }
+                    ],
+                    "modelPreferences": {
+                        "hints": [{ "name": "haiku" }],
+                        "costPriority": 0.8,
+                        "speedPriority": 0.8,
+                    },
+                    "includeContext": "none",
+                    "maxTokens": SAMPLING_MAX_TOKENS,
+                }),
+            )
+            .await?;
+        let text = result
+            .pointer("/content/text")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .ok_or_else(|| anyhow!("client answered with no text"))?;
+        let model = result
+            .get("model")
+            .and_then(Value::as_str)
+            .unwrap_or("sampling");
+        Ok((text.to_string(), model.to_string()))
+    }
+}
+
+/// Summary of a session's messages written by the client's model, chunked
+/// like the local summarizer's, with the model that wrote it. Reports the
+/// requests done and to do after each one.
+pub(crate) async fn sample_summary(
+    peer: &Peer,
+    messages: &[SearchResult],
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<(String, String)> {
+    let chunks = conversation_chunks(messages, SAMPLING_CHUNK_TOKENS);
+    match chunks.as_slice() {
+        [] => bail!("no messages to summarize"),
+        [conversation] => {
+            let summary = peer.create_message(summary_prompt(conversation)).await?;
+            on_progress(1, 1);
+            Ok(summary)
+        }
+        _ => {
+            let total = chunks.len() + 1;
+            let mut parts = Vec::with_capacity(chunks.len());
+            for (i, chunk) in chunks.iter().enumerate() {
+                let (part, _) = peer
+                    .create_message(chunk_prompt(i, chunks.len(), chunk))
+                    .await?;
+                parts.push(part);
+                on_progress(i + 1, total);
+            }
+            let summary = peer.create_message(combine_prompt(&parts)).await?;
+            on_progress(total, total);
+            Ok(summary)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sampling_requests_are_answered_through_deliver() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let peer = Peer::new(tx);
+        peer.set_capabilities(Some(&serde_json::json!({ "sampling": {} })));
+        assert!(peer.supports_sampling());
+
+        let client = peer.clone();
+        tokio::spawn(async move {
+            while let Some(line) = rx.recv().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                assert_eq!(request["method"], "sampling/createMessage");
+                let prompt = request.pointer("/params/messages/0/content/text").unwrap();
+                assert!(
+                    prompt
+                        .as_str()
+                        .unwrap()
+                        .contains("User: why is the build red")
+                );
+                let response = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": {
+                        "role": "assistant",
+                        "content": { "type": "text", "text": " Fixed a flaky test. " },
+                        "model": "claude-haiku",
+                    },
+                });
+                assert!(client.deliver(&response.to_string()));
+            }
+        });
+
+        let messages = [SearchResult {
+            content: "why is the build red".to_string(),
+            message_type: "User".to_string(),
+            ..Default::default()
+        }];
+        let mut progress = Vec::new();
+        let (text, model) =
+            sample_summary(&peer, &messages, |done, total| progress.push((done, total)))
+                .await
+                .unwrap();
+        assert_eq!(progress, [(1, 1)]);
+        assert_eq!(text, "Fixed a flaky test.");
+        assert_eq!(model, "claude-haiku");
+
+        assert!(!peer.deliver(r#"{"jsonrpc":"2.0","id":"server-99","result":{}}"#));
+        assert!(!peer.deliver(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#));
+    }
+}
@@ -1,19 +1,80 @@
 use anyhow::Result;
-use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
 use tracing::{debug, error, info};
 
+use super::output_budget::{
+    CONTINUE_OUTPUT_TOOL, ContinuationStore, add_budget_properties, apply_output_budget,
+    budget_args, continue_output,
+};
+use super::refresh::BackgroundRefresh;
+use super::sampling::Peer;
+use super::stats_analyzer::handle_get_stats;
+use super::summary_job::{PendingSummary, SummaryWriter};
+use super::tool_error::ToolError;
+use super::topic_analyzer::handle_analyze_topics;
 use crate::shared::{
-    CacheManager, DisplayOptions, SearchEngine, SearchQuery, SortOrder, auto_index,
-    discover_jsonl_files, get_cache_dir, get_config, short_uuid,
+    AGENT_SUMMARY_SOURCE, AmbiguousId, BookmarkStore, CacheManager, DisplayOptions,
+    ExclusiveIndexAccess, FACET_MIN_HITS, IndexProgress, QueryHistory, QueryHistoryView,
+    QueryRecord, SETTABLE_SEARCH_OPTIONS, SavedSearch, SearchEngine, SearchQuery, SearchResult,
+    SessionSort, SessionStore, SortOrder, StoredSummary, attach_notes, auto_index, bookmark_filter,
+    cached_extractive_summary, collapse_near_duplicates, current_session_file, dedupe_by_session,
+    discover_jsonl_files, expand_tool_results, folded_tool_result, format_annotations,
+    format_bookmark, format_bookmark_list, format_code_snippets, format_command_history,
+    format_config, format_decisions, format_error_clusters, format_file_history,
+    format_message_location, format_messages, format_notes, format_project_list,
+    format_query_history, format_query_suggestions, format_results, format_saved_searches,
+    format_search_facets, format_session_diff, format_session_list, format_stored_summary,
+    format_suggestions, format_term_suggestions, format_tool_usage_report, format_usage_report,
+    get_cache_dir, get_config, indexed_sessions, is_stored_summary, latest_summary,
+    load_saved_searches, local_time, locate_message, match_snippets, merge_continuations,
+    parse_date, prepare_session_list, record_query, reload_config_if_changed, rewrite_field_query,
+    save_search, save_session_summary, search_code_snippets, session_code_snippets,
+    set_search_option, short_uuid, split_bookmarked, suggest_refinements, truncate_content,
 };
 
 const HAIKU_CONTEXT_WINDOW: usize = 200_000;
 const CONTEXT_SAFETY_MARGIN: f64 = 0.75;
+/// Characters of each match shown by get_session_messages with a query
+const SESSION_HIT_CHARS: usize = 200;
+/// Tokens per page of session messages in summarize_session's Task
+const SUMMARY_PAGE_TOKENS: usize = 15_000;
+
+/// Search arguments kept with a query in its history, as `name=value`
+const HISTORY_FILTER_ARGS: &[&str] = &[
+    "project",
+    "include_projects",
+    "exclude_projects",
+    "session",
+    "host",
+    "branch",
+    "source",
+    "after",
+    "before",
+    "session_active_between",
+    "include",
+    "sort_by",
+];
+
+fn history_filters(args: &Value) -> Vec<String> {
+    HISTORY_FILTER_ARGS
+        .iter()
+        .filter_map(|name| {
+            let value = match args.get(*name)? {
+                Value::Null => return None,
+                Value::String(s) => s.clone(),
+                Value::Array(items) => json_strings(Some(&Value::Array(items.clone()))).join(","),
+                other => other.to_string(),
+            };
+            (!value.is_empty()).then(|| format!("{}={}", name, value))
+        })
+        .collect()
+}
 
 /// Extract Vec<String> from JSON array value
 fn json_strings(value: Option<&Value>) -> Vec<String> {
@@ -28,17 +89,63 @@ fn json_strings(value: Option<&Value>) -> Vec<String> {
         .unwrap_or_default()
 }
 
-/// Parse date string: YYYY-MM-DD (as start of day UTC) or full ISO 8601
-fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
-    // Try full ISO 8601 first
-    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
-        return Ok(dt.with_timezone(&Utc));
-    }
-    // Try YYYY-MM-DD
-    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
-        return Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()));
+/// A string argument that may also be given as an array of strings
+fn json_string_list(value: Option<&Value>) -> Vec<String> {
+    match value.and_then(|v| v.as_str()) {
+        Some(s) => vec![s.to_string()],
+        None => json_strings(value),
     }
-    Err(format!("Invalid date '{}': use YYYY-MM-DD or ISO 8601", s))
+}
+
+/// Optional date argument, with a fix_argument hint when it does not parse
+pub(super) fn date_arg(args: &Value, name: &str) -> Result<Option<DateTime<Utc>>, ToolError> {
+    args.get(name)
+        .and_then(|v| v.as_str())
+        .map(|s| {
+            parse_date(s).map_err(|e| {
+                ToolError::invalid_argument(e.to_string())
+                    .retry_with(serde_json::json!({ "action": "fix_argument", "argument": name }))
+            })
+        })
+        .transpose()
+}
+
+/// Index of the message a get_session_messages `position` names
+fn session_position(messages: &[SearchResult], position: &str) -> Result<usize, ToolError> {
+    let (found, what) = match position {
+        "first_user" => (messages.iter().position(|m| m.is_prompt()), "user prompt"),
+        "last_assistant" => (
+            messages
+                .iter()
+                .rposition(|m| m.message_type == "Assistant" && !m.content.starts_with('['))
+                .or_else(|| messages.iter().rposition(|m| m.message_type == "Assistant")),
+            "assistant message",
+        ),
+        "last_error" => (
+            messages.iter().rposition(|m| m.has_error),
+            "failed tool result",
+        ),
+        _ => {
+            return Err(ToolError::invalid_argument(format!(
+                "Unknown position '{}': use first_user, last_assistant or last_error",
+                position
+            ))
+            .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "position" })));
+        }
+    };
+    found.ok_or_else(|| {
+        ToolError::not_found(format!("No {} in this session", what))
+            .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "position" }))
+    })
+}
+
+/// Reject unknown `field:` prefixes in a query before it reaches the index
+fn check_query(query: &str) -> Result<(), ToolError> {
+    let checked = split_bookmarked(query).and_then(|(query, _)| rewrite_field_query(&query));
+    checked.map(|_| ()).map_err(|e| {
+        ToolError::invalid_argument(e.to_string())
+            .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "query" }))
+    })
 }
 
 // MCP Protocol Structures
@@ -129,9 +236,33 @@ pub struct ToolResult {
     pub text: String,
 }
 
+/// Outgoing message channel for a client connection (responses and notifications)
+pub(crate) type Notifier = tokio::sync::mpsc::UnboundedSender<String>;
+
+/// Minimum interval between progress notifications
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
 pub struct McpServer {
     search_engine: SearchEngine,
+    /// Cache metadata shared by all tool calls and background refreshes;
+    /// re-read only when another process (CLI indexing, another server)
+    /// rewrites it
+    cache: Arc<Mutex<CacheManager>>,
     cache_dir: std::path::PathBuf,
+    /// Set while handling a message from a client connection that accepts
+    /// notifications and requests from the server
+    peer: Option<Peer>,
+    /// Tool names from the last tools/list, to detect changes
+    advertised_tools: Option<Vec<String>>,
+    /// History id of the last search and the message and session ids it
+    /// showed, to record which of them are opened next
+    last_search: Mutex<Option<(u64, Vec<String>)>>,
+    /// Outputs cut by the budget, for `continue_output`
+    continuations: ContinuationStore,
+    /// Reindexing of changed files between requests
+    background_refresh: BackgroundRefresh,
+    /// Summary the call being handled left to be written, see `serve_message`
+    pending_summary: Option<PendingSummary>,
 }
 
 impl McpServer {
@@ -147,12 +278,70 @@ impl McpServer {
 
         Ok(Self {
             search_engine,
+            cache: Arc::new(Mutex::new(cache)),
             cache_dir,
+            peer: None,
+            advertised_tools: None,
+            last_search: Mutex::new(None),
+            continuations: ContinuationStore::default(),
+            background_refresh: BackgroundRefresh::default(),
+            pending_summary: None,
         })
     }
 
+    fn cache(&self) -> Result<MutexGuard<'_, CacheManager>> {
+        self.cache
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Cache metadata lock poisoned"))
+    }
+
+    /// Record ids opened from the last search's results in its history entry
+    fn note_chosen<'a>(&self, ids: impl IntoIterator<Item = &'a str>) {
+        let Ok(last_search) = self.last_search.lock() else {
+            return;
+        };
+        let Some((history_id, shown)) = last_search.as_ref() else {
+            return;
+        };
+        let chosen: Vec<String> = ids
+            .into_iter()
+            .filter(|id| !id.is_empty() && shown.iter().any(|s| s.starts_with(id)))
+            .map(str::to_string)
+            .collect();
+        if chosen.is_empty() {
+            return;
+        }
+        if let Err(e) =
+            QueryHistory::open_default().and_then(|h| h.add_chosen(*history_id, &chosen))
+        {
+            debug!("Opened results not recorded: {}", e);
+        }
+    }
+
+    /// Pick up config edits and indexing done by other processes since the
+    /// last call
+    fn refresh(&mut self) -> Result<()> {
+        reload_config_if_changed();
+        let counts = {
+            let mut cache = self.cache()?;
+            let refreshed = self.background_refresh.take_changed();
+            if !cache.reload_if_changed()? && !refreshed {
+                return Ok(());
+            }
+            cache.get_session_counts().clone()
+        };
+        self.search_engine.reload(counts)
+    }
+
+    /// Reindex files changed since they were indexed in the background, at
+    /// most every `index.background_refresh_secs`; the next call picks the
+    /// result up through `refresh`
+    pub(crate) fn start_background_refresh(&self) -> Option<tokio::task::JoinHandle<()>> {
+        self.background_refresh.start(&self.cache_dir, &self.cache)
+    }
+
     /// Check if a session's source JSONL is stale and reindex if needed.
-    /// Returns true if reindexing occurred.
+    /// Returns true if reindexing occurred, here or in another process.
     fn ensure_session_fresh(&mut self, session_id: &str, project_path: &str) -> Result<bool> {
         use crate::shared::path_utils::session_jsonl_path;
 
@@ -161,31 +350,50 @@ impl McpServer {
             _ => return Ok(false),
         };
 
-        let cache = CacheManager::new(&self.cache_dir)?;
-        if !cache.needs_indexing(&jsonl_path)? {
-            return Ok(false);
-        }
-
-        info!(
-            "Session {} is stale, reindexing {}",
-            session_id,
-            jsonl_path.display()
-        );
-
-        // Reindex just this file
-        let mut indexer = crate::shared::SearchIndexer::open(&self.cache_dir)?;
-        let mut cache = CacheManager::new(&self.cache_dir)?;
-        cache.update_incremental(&mut indexer, vec![jsonl_path])?;
-
-        // Reload search engine
-        let counts = cache.get_session_counts().clone();
-        self.search_engine = SearchEngine::new(&self.cache_dir, counts)?;
+        let counts = {
+            let _writing = self.background_refresh.hold()?;
+            let Ok(_lock) = ExclusiveIndexAccess::acquire() else {
+                debug!("Not reindexing {}: another process is indexing", session_id);
+                return Ok(false);
+            };
+            let mut cache = self.cache()?;
+            // Another process's indexing would be saved over otherwise
+            let reloaded = cache.reload_if_changed()?;
+            if cache.needs_indexing(&jsonl_path)? {
+                info!(
+                    "Session {} is stale, reindexing {}",
+                    session_id,
+                    jsonl_path.display()
+                );
+
+                // Reindex just this file
+                let mut indexer = crate::shared::SearchIndexer::open(&self.cache_dir)?;
+                cache.update_incremental(&mut indexer, vec![jsonl_path])?;
+            } else if !reloaded {
+                return Ok(false);
+            }
+            cache.get_session_counts().clone()
+        };
 
+        self.search_engine.reload(counts)?;
         Ok(true)
     }
 
+    /// Record retrieval of sessions for listing/prune policies (best effort)
+    fn record_session_access<'a>(&self, session_ids: impl IntoIterator<Item = &'a str>) {
+        let result = self
+            .cache()
+            .and_then(|mut cache| cache.record_session_access(session_ids));
+        if let Err(e) = result {
+            debug!("Failed to record session access: {}", e);
+        }
+    }
+
     async fn handle_initialize(&self, params: Option<Value>) -> Result<Value> {
         debug!("Handling initialize request: {:?}", params);
+        if let Some(peer) = &self.peer {
+            peer.set_capabilities(params.as_ref().and_then(|p| p.get("capabilities")));
+        }
 
         let response = InitializeResponse {
             protocol_version: "2024-11-05".to_string(),
@@ -209,10 +417,25 @@ impl McpServer {
         Ok(serde_json::to_value(response)?)
     }
 
-    async fn handle_list_tools(&self) -> Result<Value> {
+    async fn handle_list_tools(&mut self) -> Result<Value> {
         debug!("Handling list_tools request");
+        reload_config_if_changed();
+
+        let tools = self.advertised_tool_definitions();
+        self.advertised_tools = Some(tools.iter().map(|t| t.name.clone()).collect());
+
+        let response = ListToolsResponse { tools };
+        Ok(serde_json::to_value(response)?)
+    }
+
+    /// Analysis tools need an index with content
+    fn analysis_tools_enabled(&self) -> bool {
+        self.search_engine.num_docs() > 0
+    }
 
-        let tools = vec![
+    /// Tools currently offered; the set depends on config and index state
+    fn tool_definitions(&self) -> Vec<Tool> {
+        let mut tools = vec![
             Tool {
                 name: "search_conversations".to_string(),
                 description: "Search conversation history (Tantivy/BM25). Exact terms for functions (`_fix_ssh_agent`), natural language for concepts. Workflow: search → get_messages(ids)/truncate_length:0 for full text → summarize_session for AI summary.".to_string(),
@@ -221,11 +444,12 @@ impl McpServer {
                     "properties": {
                         "query": {
                             "type": "string",
-                            "description": "Search query. Field syntax: 'session_id:abc', 'project:name'"
+                            "description": "Search query. AND/OR/-term, \"phrases\" and fields: project:name, session_id:abc, has_error:true, has_code:true, has_attachments:true, type:compact, lang:rust, tech:docker, model:opus, path:infra/terraform (working directory), ticket:PROJ-2291 or ticket:#456, url:github.com/org/repo, file:src/main.rs (mentioned in the message), meta:jira=OPS-12 (configured metadata extractors), bookmarked:true. Other field: prefixes are rejected"
                         },
                         "project": {
-                            "type": "string",
-                            "description": "Filter by project name",
+                            "type": ["string", "array"],
+                            "items": { "type": "string" },
+                            "description": "Filter by project name, substring (`client`) or glob (`client-*`), or an array of them to search several projects; see list_projects",
                             "optional": true
                         },
                         "session": {
@@ -233,6 +457,21 @@ impl McpServer {
                             "description": "Filter by session ID (prefix match)",
                             "optional": true
                         },
+                        "host": {
+                            "type": "string",
+                            "description": "Filter by source host of imported history ('local' = this machine)",
+                            "optional": true
+                        },
+                        "branch": {
+                            "type": "string",
+                            "description": "Filter by git branch the conversation happened on (exact name)",
+                            "optional": true
+                        },
+                        "source": {
+                            "type": "string",
+                            "description": "Filter by the tool the history came from: claude-code, codex, gemini, claude-desktop or openai",
+                            "optional": true
+                        },
                         "-C": {
                             "type": "integer",
                             "description": "Messages before and after match (like grep -C)",
@@ -249,6 +488,12 @@ impl McpServer {
                             "description": "Messages after match (like grep -A)",
                             "optional": true
                         },
+                        "include_projects": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Project names, substrings or globs to search, added to `project`",
+                            "optional": true
+                        },
                         "exclude_projects": {
                             "type": "array",
                             "items": { "type": "string" },
@@ -273,6 +518,11 @@ impl McpServer {
                             "optional": true,
                             "default": "relevance"
                         },
+                        "recency_boost": {
+                            "type": "number",
+                            "description": "Half-life in days: a message's relevance halves for every this many days of age, so recent discussions of a topic rank first. 0 for pure BM25",
+                            "optional": true
+                        },
                         "after": {
                             "type": "string",
                             "description": "Results after date (YYYY-MM-DD or ISO 8601)",
@@ -283,10 +533,19 @@ impl McpServer {
                             "description": "Results before date (YYYY-MM-DD or ISO 8601)",
                             "optional": true
                         },
+                        "session_active_between": {
+                            "type": "object",
+                            "description": "Only sessions active during this window (e.g. last sprint), searched whole: a session's messages before or after the window still match, unlike after/before",
+                            "properties": {
+                                "after": { "type": "string", "description": "Window start (YYYY-MM-DD or ISO 8601)" },
+                                "before": { "type": "string", "description": "Window end (YYYY-MM-DD or ISO 8601)" }
+                            },
+                            "optional": true
+                        },
                         "include": {
                             "type": "array",
-                            "items": { "type": "string", "enum": ["thinking", "tools", "current_session"] },
-                            "description": "Include: thinking, tools, current_session",
+                            "items": { "type": "string" },
+                            "description": "Include: thinking, tools, current_session, or a preset (everything, prose_only, debugging)",
                             "optional": true
                         },
                         "truncate_length": {
@@ -295,6 +554,29 @@ impl McpServer {
                             "optional": true,
                             "default": 300
                         },
+                        "dedupe_sessions": {
+                            "type": "boolean",
+                            "description": "One result per session, listing the ids of its other matches. false shows every match",
+                            "optional": true,
+                            "default": true
+                        },
+                        "collapse_duplicates": {
+                            "type": "boolean",
+                            "description": "Fold matches with nearly the same content into the first, listed as +N near-duplicates. false shows them all",
+                            "optional": true,
+                            "default": true
+                        },
+                        "snippet_length": {
+                            "type": "integer",
+                            "description": "Chars per snippet of the matched message. Defaults to truncate_length",
+                            "optional": true
+                        },
+                        "max_snippets_per_message": {
+                            "type": "integer",
+                            "description": "Separate match regions shown from a long matched message",
+                            "optional": true,
+                            "default": 1
+                        },
                         "debug": {
                             "type": "boolean",
                             "optional": true
@@ -315,25 +597,47 @@ impl McpServer {
             },
             Tool {
                 name: "get_session_messages".to_string(),
-                description: "Paginate session messages. Use offset/limit for sequential reading, or center_on with -B/-A/-C to jump to a specific message.".to_string(),
+                description: "Read session messages from offset on (every message by default; long output continues through continue_output), or center_on with -B/-A/-C to jump to a specific message. With query, full-text search within the session instead: returns the matching messages' indices to jump to.".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
                         "session_id": {
                             "type": "string",
-                            "description": "Session ID to retrieve messages for"
+                            "description": "Session ID or unique prefix (from 🗒️ in search results)"
                         },
                         "offset": {
                             "type": "integer",
-                            "description": "Starting message index",
+                            "description": "Index of the first message to show",
                             "optional": true,
                             "default": 0
                         },
                         "limit": {
                             "type": "integer",
-                            "description": "Messages per page",
+                            "description": "Messages to show (default: the rest of the session), or matches with query (default 20)",
+                            "optional": true
+                        },
+                        "position": {
+                            "type": "string",
+                            "enum": ["first_user", "last_assistant", "last_error"],
+                            "description": "Center on the first prompt, the last answer (how the session ended) or the last failed tool result, like center_on",
+                            "optional": true
+                        },
+                        "query": {
+                            "type": "string",
+                            "description": "Search only this session (same syntax as search_conversations) and list matching message indices, limit of them by relevance",
+                            "optional": true
+                        },
+                        "expand_ids": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Message ids or prefixes (💬 of folded tool results) to show in full; tool output left out of the index is read back from the session file",
+                            "optional": true
+                        },
+                        "follow_continuations": {
+                            "type": "boolean",
+                            "description": "Read the whole logical conversation: sessions this one continues after compaction, and ones continuing it, in order",
                             "optional": true,
-                            "default": 50
+                            "default": false
                         },
                         "center_on": {
                             "type": "string",
@@ -361,543 +665,2217 @@ impl McpServer {
                 }),
             },
             Tool {
-                name: "summarize_session".to_string(),
-                description: "Get Task tool instructions to summarize a session with haiku. Use for long sessions when you need an AI-generated overview.".to_string(),
+                name: "list_sessions".to_string(),
+                description: "List sessions with message counts, time span and last retrieval time (👁️).".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "session_id": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "sort_by": {
                             "type": "string",
-                            "description": "Session ID to summarize"
+                            "enum": ["last_active", "last_accessed", "oldest", "messages"],
+                            "optional": true,
+                            "default": "last_active"
+                        },
+                        "follow_continuations": {
+                            "type": "boolean",
+                            "description": "List a conversation continued in new sessions after compaction as one row, with the continuations after 🔗",
+                            "optional": true,
+                            "default": false
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max sessions",
+                            "optional": true,
+                            "default": 20
                         }
-                    },
-                    "required": ["session_id"]
+                    }
                 }),
             },
             Tool {
-                name: "get_messages".to_string(),
-                description: "Get full content of specific messages by UUID. Use after search to read complete message text.".to_string(),
+                name: "list_projects".to_string(),
+                description: "List indexed projects with message and session counts and last activity, to find the names `project` filters accept.".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "ids": {
-                            "type": "array",
-                            "items": { "type": "string" },
-                            "description": "Message UUIDs (from 💬 in search results)"
+                        "project": {
+                            "type": "string",
+                            "description": "Only projects matching this name, substring or glob",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max projects",
+                            "optional": true,
+                            "default": 50
                         }
-                    },
-                    "required": ["ids"]
+                    }
                 }),
             },
             Tool {
-                name: "respawn_server".to_string(),
-                description: "Respawn the MCP server to reload with latest changes".to_string(),
+                name: "usage_report".to_string(),
+                description: "API token usage and estimated cost (list prices) per model, project and day, from usage recorded in the JSONL.".to_string(),
                 input_schema: serde_json::json!({
                     "type": "object",
-                    "properties": {}
+                    "properties": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "after": {
+                            "type": "string",
+                            "description": "Usage after date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "before": {
+                            "type": "string",
+                            "description": "Usage before date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max projects",
+                            "optional": true,
+                            "default": 10
+                        },
+                        "days": {
+                            "type": "integer",
+                            "description": "Max days, most recent first",
+                            "optional": true,
+                            "default": 14
+                        }
+                    }
                 }),
             },
-        ];
-
-        let response = ListToolsResponse { tools };
-        Ok(serde_json::to_value(response)?)
-    }
-
-    async fn handle_call_tool(&mut self, params: Value) -> Result<Value> {
-        let request: CallToolRequest = serde_json::from_value(params)?;
-        debug!("Handling tool call: {}", request.name);
-
-        let result = match request.name.as_str() {
-            "search_conversations" => self.tool_search_conversations(request.arguments).await?,
-            "respawn_server" => self.tool_respawn().await?,
-            "reindex" => self.tool_reindex(request.arguments).await?,
-            "get_session_messages" => self.tool_get_session_messages(request.arguments).await?,
-            "summarize_session" => self.tool_summarize_session(request.arguments).await?,
-            "get_messages" => self.tool_get_messages(request.arguments).await?,
-            _ => {
-                return Ok(serde_json::to_value(CallToolResponse {
-                    content: vec![ToolResult {
-                        result_type: "text".to_string(),
-                        text: format!("Unknown tool: {}", request.name),
-                    }],
-                    is_error: Some(true),
-                })?);
-            }
-        };
-
-        Ok(result)
-    }
-
-    async fn tool_search_conversations(&self, args: Option<Value>) -> Result<Value> {
-        let args = args.unwrap_or_default();
-        let query_text = args
-            .get("query")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing 'query' parameter"))?
-            .to_string();
-
-        let debug_mode = args
-            .get("debug")
-            .and_then(|v| v.as_str())
-            .map(|s| s == "true")
-            .unwrap_or(false);
-
-        let project_filter = args
-            .get("project")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        let session_filter = args
-            .get("session")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-
-        // Parse grep-style context: -C (both), -B (before), -A (after)
-        let context_c = args.get("-C").and_then(|v| v.as_u64()).unwrap_or(2);
-        let context_before = args.get("-B").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
-        let context_after = args.get("-A").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
-
-        let exclude_projects = json_strings(args.get("exclude_projects"));
-
-        let exclude_patterns: Vec<String> = args
-            .get("exclude_patterns")
-            .map(|v| {
-                if let Some(arr) = v.as_array() {
-                    arr.iter()
-                        .filter_map(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .collect()
-                } else if let Some(s) = v.as_str() {
-                    serde_json::from_str::<Vec<String>>(s).unwrap_or_default()
-                } else {
-                    Vec::new()
-                }
-            })
-            .unwrap_or_default();
-
-        let config = get_config();
-        let claude_dir = config.get_claude_dir()?;
-        let all_files = discover_jsonl_files()?;
-
-        // Detect current session early to exclude from stale check
-        let current_session_file: Option<std::path::PathBuf> =
-            std::env::current_dir().ok().and_then(|cwd| {
-                let cwd_str = cwd.to_string_lossy().replace(['/', '\\', '.'], "-");
-                let sess_pattern = claude_dir.join("projects").join(&cwd_str).join("*.jsonl");
-                glob::glob(&sess_pattern.to_string_lossy())
-                    .ok()?
-                    .flatten()
-                    .max_by_key(|p| p.metadata().and_then(|m| m.modified()).ok())
-            });
-
-        // Exclude current session from stale check (it's always being written to)
-        let current_session_name = current_session_file
-            .as_ref()
-            .and_then(|p| p.file_name())
-            .and_then(|n| n.to_str());
-
-        let files_for_stale_check: Vec<_> = all_files
-            .iter()
-            .filter(|f| {
-                let name = f.file_name().and_then(|n| n.to_str());
-                name != current_session_name
-            })
-            .cloned()
-            .collect();
-
-        let cache = CacheManager::new(&config.get_cache_dir()?)?;
-        let (stale_count, new_count) = cache.quick_health_check(&files_for_stale_check);
-
-        let mut all_exclude_patterns = config.search.exclude_patterns.clone();
-        all_exclude_patterns.extend(exclude_patterns.clone());
-
-        let exclude_regexes: Vec<Regex> = all_exclude_patterns
-            .iter()
-            .filter_map(|p| Regex::new(p).ok())
-            .collect();
-
-        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
-
-        let sort_by = match args
-            .get("sort_by")
-            .and_then(|v| v.as_str())
-            .unwrap_or("relevance")
-        {
-            "date_desc" => SortOrder::DateDesc,
-            "date_asc" => SortOrder::DateAsc,
-            _ => SortOrder::Relevance,
-        };
-
-        let after = if let Some(s) = args.get("after").and_then(|v| v.as_str()) {
-            match parse_date(s) {
-                Ok(dt) => Some(dt),
-                Err(e) => {
-                    return Ok(serde_json::to_value(CallToolResponse {
-                        content: vec![ToolResult {
-                            result_type: "text".to_string(),
-                            text: e,
-                        }],
-                        is_error: Some(true),
-                    })?);
-                }
-            }
-        } else {
-            None
-        };
+            Tool {
+                name: "analyze_tool_usage".to_string(),
+                description: "Which Claude tools (Bash, Edit, Read, Task, MCP tools...) were called most, with failure rates from tool_result errors, overall and per project.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "after": {
+                            "type": "string",
+                            "description": "Calls after date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "before": {
+                            "type": "string",
+                            "description": "Calls before date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max tools and projects listed",
+                            "optional": true,
+                            "default": 15
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "analyze_errors".to_string(),
+                description: "Recurring tool errors (failed tool_result outputs) clustered by normalized signature, most sessions first. Each cluster links the latest occurrence and the messages where the same tool later succeeded in that session (✅), or ❔ if it never did.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "after": {
+                            "type": "string",
+                            "description": "Errors after date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "before": {
+                            "type": "string",
+                            "description": "Errors before date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "min_sessions": {
+                            "type": "integer",
+                            "description": "Only errors seen in at least this many sessions (2 = recurring)",
+                            "optional": true,
+                            "default": 1
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max error signatures listed",
+                            "optional": true,
+                            "default": 15
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "find_decisions".to_string(),
+                description: "What was decided: assistant statements like \"we'll go with…\", \"decided to…\", \"settled on…\" and checklist items marked done ([x], ✅), grouped by session, most recent first. A quick way to recover outcomes without full summaries.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "session": {
+                            "type": "string",
+                            "description": "Only this session (ID or unique prefix)",
+                            "optional": true
+                        },
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "after": {
+                            "type": "string",
+                            "description": "Decisions after date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "before": {
+                            "type": "string",
+                            "description": "Decisions before date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max decisions listed",
+                            "optional": true,
+                            "default": 30
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "open_message".to_string(),
+                description: "Resolve a message id to its JSONL file and line number (permalink), e.g. to point the user at the raw transcript.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "string",
+                            "description": "Message id or prefix (from 💬 in results)"
+                        }
+                    },
+                    "required": ["id"]
+                }),
+            },
+            Tool {
+                name: "find_conversations_for_file".to_string(),
+                description: "Sessions whose Read/Edit/Write tool calls touched a file, newest first, with message ids (✏️ = modified). Relative paths match by trailing components.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "File path, absolute or relative (e.g. src/shared/search.rs)"
+                        },
+                        "modified_only": {
+                            "type": "boolean",
+                            "description": "Only sessions that edited or wrote the file",
+                            "optional": true,
+                            "default": false
+                        },
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max sessions",
+                            "optional": true,
+                            "default": 20
+                        }
+                    },
+                    "required": ["path"]
+                }),
+            },
+            Tool {
+                name: "extract_commands".to_string(),
+                description: "Bash commands Claude ran, deduplicated and ordered by latest run, with exit status (✅ ok, ❌ code, ❔ no result) and run count. Use to recover a long command from a past session.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "session": {
+                            "type": "string",
+                            "description": "Filter by session ID (prefix match)",
+                            "optional": true
+                        },
+                        "pattern": {
+                            "type": "string",
+                            "description": "Case-insensitive substring of the command (e.g. 'kubectl')",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max distinct commands, most recent kept",
+                            "optional": true,
+                            "default": 30
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "get_code_blocks".to_string(),
+                description: "Fenced code blocks from one session or from messages matching a search query, each with its language tag and the prompt that preceded it (❓). Use to recover a specific snippet without reading full messages.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "session_id": {
+                            "type": "string",
+                            "description": "Session to extract from (full or prefix)",
+                            "optional": true
+                        },
+                        "query": {
+                            "type": "string",
+                            "description": "Search query selecting messages, when no session_id is given",
+                            "optional": true
+                        },
+                        "project": {
+                            "type": "string",
+                            "description": "Filter search matches by project name, substring or glob",
+                            "optional": true
+                        },
+                        "language": {
+                            "type": "string",
+                            "description": "Only blocks tagged with this language (e.g. rust, bash)",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Max code blocks",
+                            "optional": true,
+                            "default": 10
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "save_search".to_string(),
+                description: "Save a search under a name so it can be replayed later with run_saved_search. Saving an existing name replaces it.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Name to save the search under"
+                        },
+                        "query": {
+                            "type": "string",
+                            "description": "Search query"
+                        },
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "host": {
+                            "type": "string",
+                            "description": "Filter by source host ('local' = this machine)",
+                            "optional": true
+                        },
+                        "branch": {
+                            "type": "string",
+                            "description": "Filter by git branch",
+                            "optional": true
+                        },
+                        "within_days": {
+                            "type": "integer",
+                            "description": "Only the last N days, relative to when the search is run",
+                            "optional": true
+                        },
+                        "after": {
+                            "type": "string",
+                            "description": "Fixed start date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "before": {
+                            "type": "string",
+                            "description": "Fixed end date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "sort_by": {
+                            "type": "string",
+                            "enum": ["relevance", "date_desc", "date_asc"],
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Default number of results",
+                            "optional": true
+                        },
+                        "include": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Include flags or presets, as for search_conversations",
+                            "optional": true
+                        }
+                    },
+                    "required": ["name", "query"]
+                }),
+            },
+            Tool {
+                name: "run_saved_search".to_string(),
+                description: "Run a search saved with save_search (or in config under saved_searches). Without a name, lists saved searches.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Saved search name",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Override the saved result limit",
+                            "optional": true
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "query_history".to_string(),
+                description: "Past searches with their filters, hit counts and the results opened after them. Use view=failed to find questions the history couldn't answer, view=frequent for recurring topics.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "view": {
+                            "type": "string",
+                            "enum": ["recent", "frequent", "failed"],
+                            "description": "recent: latest searches (default); frequent: distinct queries, most run first; failed: queries that last found nothing",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum entries (default: 20)",
+                            "optional": true
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "suggest_terms".to_string(),
+                description: "Complete a word from the indexed vocabulary: the most frequent content terms starting with a prefix, with how many messages use each. Use it to check how something was spelled or named before searching.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "prefix": {
+                            "type": "string",
+                            "description": "Start of the word, case-insensitive"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum terms (default: 20)",
+                            "optional": true
+                        }
+                    },
+                    "required": ["prefix"]
+                }),
+            },
+            Tool {
+                name: "get_config".to_string(),
+                description: "Show the settings in effect: the config file with CLAUDE_SEARCH_* environment overrides applied. Edits to the file are picked up without a restart.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "section": {
+                            "type": "string",
+                            "description": "Only this top-level section (index, search, tools, limits, display, ...)",
+                            "optional": true
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "set_config".to_string(),
+                description: format!("Change a search default in the config file and use it from the next call. Settable: {}. The value replaces the whole option, e.g. recency_boost: 30 or type_boosts: {{\"user\": 1.2, \"assistant\": 1.0}}.", SETTABLE_SEARCH_OPTIONS.join(", ")),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "key": {
+                            "type": "string",
+                            "enum": SETTABLE_SEARCH_OPTIONS,
+                            "description": "Search option to set"
+                        },
+                        "value": {
+                            "description": "New value; null restores the default"
+                        }
+                    },
+                    "required": ["key", "value"]
+                }),
+            },
+            Tool {
+                name: "diff_sessions".to_string(),
+                description: "Compare two sessions, such as two attempts at the same task: prompts aligned in order (= both, A/B only one), tool calls and failures, shell commands only one ran, files modified, and each one's last answer. Use it to learn which approach worked.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "session_a": {
+                            "type": "string",
+                            "description": "First session ID or prefix"
+                        },
+                        "session_b": {
+                            "type": "string",
+                            "description": "Second session ID or prefix"
+                        }
+                    },
+                    "required": ["session_a", "session_b"]
+                }),
+            },
+            Tool {
+                name: "bookmark_message".to_string(),
+                description: "Bookmark a message (e.g. the canonical answer to a question) with tags and a note, to find it again with list_bookmarks or a `bookmarked:true` search. Bookmarking it again adds tags and replaces the note.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "string",
+                            "description": "Message UUID or unique prefix (from 💬 in results)"
+                        },
+                        "tags": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Tags to add",
+                            "optional": true
+                        },
+                        "note": {
+                            "type": "string",
+                            "description": "Note to keep with the bookmark; empty clears it",
+                            "optional": true
+                        },
+                        "remove": {
+                            "type": "boolean",
+                            "description": "Remove the bookmark instead",
+                            "optional": true,
+                            "default": false
+                        }
+                    },
+                    "required": ["id"]
+                }),
+            },
+            Tool {
+                name: "annotate_message".to_string(),
+                description: "Attach a free-text note to a message, e.g. why it matters or what turned out wrong. Notes are shown under the message, marked 📝 Note:, in search context, get_session_messages and get_messages.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "string",
+                            "description": "Message UUID or unique prefix (from 💬 in results)"
+                        },
+                        "note": {
+                            "type": "string",
+                            "description": "Note to add after the message's existing notes"
+                        },
+                        "clear": {
+                            "type": "boolean",
+                            "description": "Remove the message's notes instead",
+                            "optional": true,
+                            "default": false
+                        }
+                    },
+                    "required": ["id"]
+                }),
+            },
+            Tool {
+                name: "list_bookmarks".to_string(),
+                description: "List bookmarked messages, newest first, with their tags, notes and a snippet.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tag": {
+                            "type": "string",
+                            "description": "Only bookmarks with this tag",
+                            "optional": true
+                        }
+                    }
+                }),
+            },
+            Tool {
+                name: "summarize_session".to_string(),
+                description: "Get the summary Claude Code wrote for a session, if any, plus Task tool instructions to summarize it with haiku. With a summarizer configured, or a client that supports sampling, the summary is written by that model and stored instead. Use for long sessions when you need an overview. local:true returns an extractive summary instead, for when no agent can be spawned.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "session_id": {
+                            "type": "string",
+                            "description": "Session ID or unique prefix to summarize"
+                        },
+                        "local": {
+                            "type": "boolean",
+                            "description": "Summarize here from the session's own text (key asks, decisions, final answers, files changed) instead of returning Task instructions",
+                            "optional": true,
+                            "default": false
+                        },
+                        "refresh": {
+                            "type": "boolean",
+                            "description": "When this server writes summaries (summarizer or sampling), write a fresh one even if one is stored",
+                            "optional": true,
+                            "default": false
+                        }
+                    },
+                    "required": ["session_id"]
+                }),
+            },
+            Tool {
+                name: "store_session_summary".to_string(),
+                description: "Keep a summary you wrote for a session (e.g. from the summarize_session Task) so later searches match it: it is indexed as the session's latest summary, found by summary: queries, shown by summarize_session and used for the session title. Storing again replaces it.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "session_id": {
+                            "type": "string",
+                            "description": "Session ID or unique prefix the summary is of"
+                        },
+                        "summary": {
+                            "type": "string",
+                            "description": "Summary text"
+                        },
+                        "source": {
+                            "type": "string",
+                            "description": "How it was made, kept with it",
+                            "optional": true,
+                            "default": "agent"
+                        }
+                    },
+                    "required": ["session_id", "summary"]
+                }),
+            },
+            Tool {
+                name: "get_messages".to_string(),
+                description: "Get full content of specific messages by UUID. Use after search to read complete message text.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "ids": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Message UUIDs or unique prefixes (from 💬 in search results)"
+                        },
+                        "expand_tool_results": {
+                            "type": "boolean",
+                            "description": "Replace `[result omitted: N chars]` with the tool output, read from the session JSONL",
+                            "optional": true,
+                            "default": false
+                        }
+                    },
+                    "required": ["ids"]
+                }),
+            },
+            Tool {
+                name: "respawn_server".to_string(),
+                description: "Respawn the MCP server to reload with latest changes".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {}
+                }),
+            },
+        ];
+
+        tools.push(Tool {
+            name: CONTINUE_OUTPUT_TOOL.to_string(),
+            description: "Next page of a tool output cut by max_output_tokens: pass the continuation from its +more: line. Outputs are kept for an hour.".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "continuation": {
+                        "type": "string",
+                        "description": "Token from the +more: continue_output(continuation=...) line ending the previous page"
+                    }
+                },
+                "required": ["continuation"]
+            }),
+        });
+
+        if self.analysis_tools_enabled() {
+            tools.push(Tool {
+                name: "get_stats".to_string(),
+                description: "Conversation statistics: message/session counts, projects, technologies, languages, monthly activity, and messages per subdirectory of the working directories.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "dir": {
+                            "type": "string",
+                            "description": "Only work in this directory or below it, broken down by its subdirectories (drill down by passing one of them next)",
+                            "optional": true
+                        }
+                    }
+                }),
+            });
+            tools.push(Tool {
+                name: "analyze_topics".to_string(),
+                description: "What conversations are about: the most mentioned technologies, programming languages and tools, and the busiest projects.".to_string(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "project": {
+                            "type": "string",
+                            "description": "Filter by project name, substring or glob",
+                            "optional": true
+                        },
+                        "dir": {
+                            "type": "string",
+                            "description": "Only work in this directory or below it",
+                            "optional": true
+                        },
+                        "after": {
+                            "type": "string",
+                            "description": "Messages after date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "before": {
+                            "type": "string",
+                            "description": "Messages before date (YYYY-MM-DD or ISO 8601)",
+                            "optional": true
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Entries per section",
+                            "optional": true,
+                            "default": 10
+                        }
+                    }
+                }),
+            });
+        }
+
+        tools
+    }
+
+    /// Tool definitions after applying the `tools:` config (disabled and renamed)
+    fn advertised_tool_definitions(&self) -> Vec<Tool> {
+        let tools_config = &get_config().tools;
+        self.tool_definitions()
+            .into_iter()
+            .filter_map(|mut tool| {
+                tool.name = tools_config.advertised_name(&tool.name)?.to_string();
+                add_budget_properties(&mut tool.input_schema);
+                Some(tool)
+            })
+            .collect()
+    }
+
+    /// Send `notifications/tools/list_changed` if the tool set differs from
+    /// what the client last listed. Needs a notification channel.
+    fn notify_if_tools_changed(&mut self) {
+        let (Some(advertised), Some(peer)) = (&self.advertised_tools, &self.peer) else {
+            return;
+        };
+        let current: Vec<String> = self
+            .advertised_tool_definitions()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        if &current == advertised {
+            return;
+        }
+
+        debug!("Tool set changed: {:?} -> {:?}", advertised, current);
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/tools/list_changed",
+        });
+        if peer.notifier().send(notification.to_string()).is_ok() {
+            self.advertised_tools = Some(current);
+        }
+    }
+
+    async fn handle_call_tool(&mut self, params: Value) -> Result<Value> {
+        let progress_token = params
+            .get("_meta")
+            .and_then(|m| m.get("progressToken"))
+            .cloned();
+        let request: CallToolRequest = serde_json::from_value(params)?;
+        debug!("Handling tool call: {}", request.name);
+        let max_output_tokens = match budget_args(request.arguments.as_ref()) {
+            Ok(budget) => budget,
+            Err(e) => return Ok(e.to_value()),
+        };
+        self.refresh()?;
+
+        // Disabled tools and original names of renamed tools are unknown
+        let config = get_config();
+        let tool_name = config.tools.resolve(&request.name).unwrap_or("");
+        // Pages come from the stored output, not from the budget below
+        if tool_name == CONTINUE_OUTPUT_TOOL {
+            return Ok(continue_output(
+                request.arguments.as_ref(),
+                max_output_tokens,
+                &self.continuations,
+            )
+            .unwrap_or_else(|e| e.to_value()));
+        }
+        let result = match tool_name {
+            "search_conversations" => self.tool_search_conversations(request.arguments).await,
+            "respawn_server" => self.tool_respawn().await,
+            "reindex" => self.tool_reindex(request.arguments, progress_token).await,
+            "get_session_messages" => self.tool_get_session_messages(request.arguments).await,
+            "summarize_session" => {
+                self.tool_summarize_session(request.arguments, max_output_tokens, progress_token)
+                    .await
+            }
+            "get_messages" => self.tool_get_messages(request.arguments).await,
+            "list_sessions" => self.tool_list_sessions(request.arguments).await,
+            "list_projects" => self.tool_list_projects(request.arguments).await,
+            "usage_report" => self.tool_usage_report(request.arguments).await,
+            "analyze_tool_usage" => self.tool_analyze_tool_usage(request.arguments).await,
+            "open_message" => self.tool_open_message(request.arguments).await,
+            "find_conversations_for_file" => {
+                self.tool_find_conversations_for_file(request.arguments)
+                    .await
+            }
+            "analyze_errors" => self.tool_analyze_errors(request.arguments).await,
+            "find_decisions" => self.tool_find_decisions(request.arguments).await,
+            "save_search" => self.tool_save_search(request.arguments).await,
+            "run_saved_search" => self.tool_run_saved_search(request.arguments).await,
+            "query_history" => self.tool_query_history(request.arguments).await,
+            "suggest_terms" => self.tool_suggest_terms(request.arguments).await,
+            "get_config" => self.tool_get_config(request.arguments).await,
+            "set_config" => self.tool_set_config(request.arguments).await,
+            "diff_sessions" => self.tool_diff_sessions(request.arguments).await,
+            "bookmark_message" => self.tool_bookmark_message(request.arguments).await,
+            "list_bookmarks" => self.tool_list_bookmarks(request.arguments).await,
+            "annotate_message" => self.tool_annotate_message(request.arguments).await,
+            "store_session_summary" => self.tool_store_session_summary(request.arguments).await,
+            "get_code_blocks" => self.tool_get_code_blocks(request.arguments).await,
+            "extract_commands" => self.tool_extract_commands(request.arguments).await,
+            "get_stats" if self.analysis_tools_enabled() => {
+                // Stats are read-only; a snapshot keeps the lock out of the await
+                let cache = CacheManager::new(&self.cache_dir)?;
+                handle_get_stats(Some(&self.search_engine), Some(&cache), request.arguments).await
+            }
+            "analyze_topics" if self.analysis_tools_enabled() => {
+                handle_analyze_topics(&self.search_engine, request.arguments).await
+            }
+            _ => {
+                return Ok(ToolError::new(
+                    "unknown_tool",
+                    format!("Unknown tool: {}", request.name),
+                )
+                .retry_with(serde_json::json!({ "action": "list_tools" }))
+                .to_value());
+            }
+        };
+
+        // Reindexing can change which tools are available
+        self.notify_if_tools_changed();
+
+        Ok(self.tool_result(result, max_output_tokens))
+    }
+
+    /// A tool's outcome as the call's result, cut to the output budget.
+    /// Tool failures are results with isError, not JSON-RPC errors.
+    fn tool_result(&mut self, result: Result<Value>, max_output_tokens: usize) -> Value {
+        let mut result = result.unwrap_or_else(|e| match e.downcast::<ToolError>() {
+            Ok(tool_error) => tool_error.to_value(),
+            Err(e) => match e.downcast::<AmbiguousId>() {
+                Ok(ambiguous) => ToolError::from(ambiguous).to_value(),
+                Err(e) => ToolError::new("internal_error", e.to_string()).to_value(),
+            },
+        });
+        apply_output_budget(&mut result, max_output_tokens, &mut self.continuations);
+        result
+    }
+
+    async fn tool_search_conversations(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let query_text = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("query"))?
+            .to_string();
+        check_query(&query_text)?;
+        let query_text_for_history = query_text.clone();
+        let (query_text, uuid_filter) = bookmark_filter(&query_text)?;
+
+        let debug_mode = args
+            .get("debug")
+            .and_then(|v| v.as_str())
+            .map(|s| s == "true")
+            .unwrap_or(false);
+
+        let mut project_filters = json_string_list(args.get("project"));
+        project_filters.extend(json_strings(args.get("include_projects")));
+
+        let session_filter = args
+            .get("session")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let host_filter = args
+            .get("host")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let branch_filter = args
+            .get("branch")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let source_filter = args
+            .get("source")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        // Parse grep-style context: -C (both), -B (before), -A (after)
+        let context_c = args.get("-C").and_then(|v| v.as_u64()).unwrap_or(2);
+        let context_before = args.get("-B").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
+        let context_after = args.get("-A").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
+
+        let exclude_projects = json_strings(args.get("exclude_projects"));
+
+        let exclude_patterns: Vec<String> = args
+            .get("exclude_patterns")
+            .map(|v| {
+                if let Some(arr) = v.as_array() {
+                    arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .map(|s| s.to_string())
+                        .collect()
+                } else if let Some(s) = v.as_str() {
+                    serde_json::from_str::<Vec<String>>(s).unwrap_or_default()
+                } else {
+                    Vec::new()
+                }
+            })
+            .unwrap_or_default();
+
+        let config = get_config();
+
+        // The caller's own session, which is being written to
+        let current_session_file = current_session_file();
+
+        let mut all_exclude_patterns = config.search.exclude_patterns.clone();
+        all_exclude_patterns.extend(exclude_patterns.clone());
+
+        let exclude_regexes: Vec<Regex> = all_exclude_patterns
+            .iter()
+            .filter_map(|p| Regex::new(p).ok())
+            .collect();
+
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+
+        let sort_by = match args
+            .get("sort_by")
+            .and_then(|v| v.as_str())
+            .unwrap_or("relevance")
+        {
+            "date_desc" => SortOrder::DateDesc,
+            "date_asc" => SortOrder::DateAsc,
+            _ => SortOrder::Relevance,
+        };
+        let recency_half_life_days = get_config()
+            .search
+            .recency_half_life(args.get("recency_boost").and_then(|v| v.as_f64()));
+
+        let after = date_arg(&args, "after")?;
+        let before = date_arg(&args, "before")?;
+        let window = args.get("session_active_between");
+        let window_date = |name: &str| {
+            window
+                .and_then(|w| w.get(name))
+                .and_then(|v| v.as_str())
+                .map(|s| {
+                    parse_date(s).map_err(|e| {
+                        ToolError::invalid_argument(format!(
+                            "session_active_between.{}: {}",
+                            name, e
+                        ))
+                        .retry_with(serde_json::json!({
+                            "action": "fix_argument",
+                            "argument": "session_active_between"
+                        }))
+                    })
+                })
+                .transpose()
+        };
+        let session_active_after = window_date("after")?;
+        let session_active_before = window_date("before")?;
+
+        // Parse include parameter, expanding named presets
+        let include = config
+            .search
+            .expand_include(&json_strings(args.get("include")))
+            .map_err(|e| {
+                ToolError::invalid_argument(e.to_string()).retry_with(
+                    serde_json::json!({ "action": "fix_argument", "argument": "include" }),
+                )
+            })?;
+
+        let truncate_length = args
+            .get("truncate_length")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(300) as usize;
+
+        let display_opts = DisplayOptions {
+            include_thinking: include.contains(&"thinking".to_string()),
+            include_tools: include.contains(&"tools".to_string()),
+            truncate_length,
+            snippet_length: args
+                .get("snippet_length")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize,
+            max_snippets: args
+                .get("max_snippets_per_message")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1) as usize,
+        };
+
+        let include_current_session = include.contains(&"current_session".to_string());
+
+        // Get current session ID from file detected earlier
+        let current_session_id: Option<String> = if !include_current_session {
+            current_session_file.as_ref().and_then(|p| {
+                p.file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_string())
+            })
+        } else {
+            None
+        };
+
+        let query = SearchQuery {
+            text: query_text,
+            project_filters,
+            session_filter,
+            limit: limit * 3,
+            sort_by,
+            after,
+            before,
+            host_filter,
+            branch_filter,
+            source_filter,
+            uuid_filter,
+            dir_filter: None,
+            session_active_after,
+            session_active_before,
+            recency_half_life_days,
+            type_boosts: get_config().search.type_boosts.clone(),
+        };
+
+        let search_engine = &self.search_engine;
+        let facets = search_engine.search_facets(&query)?;
+        let results_with_context =
+            search_engine.search_with_context(query.clone(), context_before, context_after)?;
+
+        let filtered: Vec<_> = results_with_context
+            .into_iter()
+            .filter(|r| {
+                let proj = &r.matched_message.project;
+                let path = &r.matched_message.project_path;
+                let session = &r.matched_message.session_id;
+
+                // Exclude current session unless explicitly included
+                if let Some(ref current) = current_session_id
+                    && session == current
+                {
+                    return false;
+                }
+
+                if exclude_projects.contains(proj) {
+                    return false;
+                }
+                !exclude_regexes
+                    .iter()
+                    .any(|regex| regex.is_match(proj) || regex.is_match(path))
+            })
+            .collect();
+        let dedupe_sessions = args
+            .get("dedupe_sessions")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let filtered = if dedupe_sessions {
+            dedupe_by_session(filtered)
+        } else {
+            filtered
+        };
+        let collapse_duplicates = args
+            .get("collapse_duplicates")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let mut filtered = if collapse_duplicates {
+            collapse_near_duplicates(filtered)
+        } else {
+            filtered
+        };
+        filtered.truncate(limit);
+        attach_notes(filtered.iter_mut().flat_map(|r| &mut r.context_messages))?;
+
+        let mut output = String::new();
+
+        if debug_mode {
+            output.push_str(&format!(
+                "DEBUG: query={:?}, -B={}, -A={}, limit={}, exclude_projects={:?}, patterns={:?}\n\n",
+                args.get("query"),
+                context_before,
+                context_after,
+                limit,
+                exclude_projects,
+                all_exclude_patterns
+            ));
+        }
+
+        if facets.hits >= FACET_MIN_HITS {
+            output.push_str(&format_search_facets(&facets));
+        }
+
+        if !exclude_projects.is_empty() || !all_exclude_patterns.is_empty() {
+            output.push_str(&format!(
+                "Excluding: {} projects, {} patterns\n",
+                exclude_projects.len(),
+                all_exclude_patterns.len()
+            ));
+        }
+
+        if filtered.is_empty() {
+            // Only an empty result pays for walking the history for staleness,
+            // skipping the current session (it's always being written to)
+            let current_session_name = current_session_file
+                .as_ref()
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str());
+            let files_for_stale_check: Vec<_> = discover_jsonl_files()?
+                .into_iter()
+                .filter(|f| f.file_name().and_then(|n| n.to_str()) != current_session_name)
+                .collect();
+            let (stale_count, new_count) = self.cache()?.quick_health_check(&files_for_stale_check);
+            if stale_count > 0 || new_count > 0 {
+                // No results but index is stale - return error prompting reindex
+                return Err(ToolError::new(
+                    "stale_index",
+                    format!(
+                        "No results found. Index is stale ({} modified, {} new files). Call reindex tool and retry search.",
+                        stale_count, new_count
+                    ),
+                )
+                .retry_with(serde_json::json!({ "action": "reindex", "then": "retry" }))
+                .into());
+            }
+            output.push_str("No results found.\n");
+            // Matches dropped by exclusions aren't a misspelled query
+            if facets.hits == 0
+                && let Ok(suggestions) = search_engine.suggest_queries(&query)
+            {
+                output.push_str(&format_query_suggestions(&suggestions));
+            }
+            if let Ok(history) = QueryHistory::open_default().and_then(|h| h.list()) {
+                output.push_str(&format_suggestions(&suggest_refinements(
+                    &history,
+                    &query_text_for_history,
+                )));
+            }
+        } else {
+            output.push_str(&format_results(&filtered, &display_opts, sort_by));
+            if filtered.len() == limit {
+                output.push_str(&format!("\n+more: limit={}\n", limit));
+            }
+        }
+
+        let record = QueryRecord::new(
+            &query_text_for_history,
+            history_filters(&args),
+            facets.hits,
+            "mcp",
+        );
+        if let Some(history_id) = record_query(&record)
+            && let Ok(mut last_search) = self.last_search.lock()
+        {
+            let shown = filtered
+                .iter()
+                .flat_map(|r| [&r.matched_message.uuid, &r.matched_message.session_id])
+                .cloned()
+                .collect();
+            *last_search = Some((history_id, shown));
+        }
+
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: output,
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_get_session_messages(&mut self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let session_id = args
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("session_id"))?;
+        self.note_chosen([session_id]);
+
+        let mut messages = self.search_engine.get_session_messages(session_id)?;
+
+        // Check if session source is stale and reindex if needed
+        if let Some(first) = messages.first() {
+            let (session_id, project_path) = (first.session_id.clone(), first.project_path.clone());
+            if self.ensure_session_fresh(&session_id, &project_path)? {
+                // Re-fetch after reindex
+                messages = self.search_engine.get_session_messages(&session_id)?;
+            }
+        }
+
+        if messages.is_empty() {
+            return Err(ToolError::not_found(format!(
+                "No messages found for session {}",
+                session_id
+            ))
+            .retry_with(serde_json::json!({ "action": "list_sessions" }))
+            .into());
+        }
+
+        let session_id = messages[0].session_id.clone();
+        let chain = if args
+            .get("follow_continuations")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        {
+            self.search_engine.conversation_chain(&session_id)?
+        } else {
+            vec![session_id.clone()]
+        };
+        for other in chain.iter().filter(|id| **id != session_id) {
+            messages.extend(self.search_engine.get_session_messages(other)?);
+        }
+        self.record_session_access(chain.iter().map(String::as_str));
+
+        // Sort by session in the chain, then sequence number, and filter
+        // displayable messages
+        messages.sort_by_key(|m| {
+            (
+                chain.iter().position(|id| *id == m.session_id),
+                m.sequence_num,
+            )
+        });
+        let mut messages: Vec<_> = messages
+            .into_iter()
+            .filter(|m| m.is_displayable())
+            .collect();
+        attach_notes(&mut messages)?;
+
+        // Long tool output is folded to one line unless asked for by id
+        let expand_ids = json_strings(args.get("expand_ids"));
+        let expanded =
+            |m: &SearchResult| expand_ids.iter().any(|id| m.uuid.starts_with(id.as_str()));
+        for message in messages.iter_mut().filter(|m| expanded(m)) {
+            expand_tool_results(&self.search_engine, std::slice::from_mut(message))?;
+        }
+        let positions: HashMap<&str, usize> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (m.uuid.as_str(), i))
+            .collect();
+        let folded: Vec<Option<String>> = messages
+            .iter()
+            .map(|m| {
+                if expanded(m) {
+                    return None;
+                }
+                let parent = m
+                    .parent_uuid
+                    .as_deref()
+                    .and_then(|uuid| positions.get(uuid))
+                    .map(|&i| &messages[i]);
+                folded_tool_result(m, parent)
+            })
+            .collect();
+
+        let total = messages.len();
+        let project = messages
+            .first()
+            .map(|m| m.project_path_display())
+            .unwrap_or_default();
+        let short_session = chain
+            .iter()
+            .map(|id| short_uuid(id))
+            .collect::<Vec<_>>()
+            .join(" → ");
+
+        if let Some(query) = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .filter(|q| !q.trim().is_empty())
+        {
+            check_query(query)?;
+            let (text, uuid_filter) = bookmark_filter(query)?;
+            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            let mut hits = Vec::new();
+            for id in &chain {
+                hits.extend(self.search_engine.search(SearchQuery {
+                    text: text.clone(),
+                    session_filter: Some(id.clone()),
+                    limit,
+                    uuid_filter: uuid_filter.clone(),
+                    ..Default::default()
+                })?);
+            }
+            hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+            hits.truncate(limit);
+            let mut found: Vec<(usize, &SearchResult)> = hits
+                .iter()
+                .filter_map(|hit| {
+                    let idx = messages.iter().position(|m| m.uuid == hit.uuid)?;
+                    Some((idx, hit))
+                })
+                .collect();
+            found.sort_by_key(|(idx, _)| *idx);
+
+            let mut output = format!(
+                "📁 {} 🗒️ {} ({} msgs) 🔍 {} matches for \"{}\"\n\n",
+                project,
+                short_session,
+                total,
+                found.len(),
+                query
+            );
+            for (idx, hit) in &found {
+                let content = match_snippets(&hit.content, &hit.matches, SESSION_HIT_CHARS, 1)
+                    .unwrap_or_else(|| truncate_content(&hit.content, SESSION_HIT_CHARS, true));
+                output.push_str(&format!(
+                    " [{}] {} {}: {} 💬 {}\n",
+                    idx,
+                    local_time(hit.timestamp).format("%H:%M"),
+                    hit.role_display(),
+                    content,
+                    short_uuid(&hit.uuid)
+                ));
+            }
+            if found.is_empty() {
+                output.push_str("No messages match in this session\n");
+            } else {
+                output.push_str("\nRead around a match with center_on=<💬 id> or offset=<index>\n");
+            }
+            return Ok(serde_json::to_value(CallToolResponse {
+                content: vec![ToolResult {
+                    result_type: "text".to_string(),
+                    text: output,
+                }],
+                is_error: None,
+            })?);
+        }
+
+        // Which messages: center_on/position mode vs offset/limit mode
+        let center = match (
+            args.get("center_on").and_then(|v| v.as_str()),
+            args.get("position").and_then(|v| v.as_str()),
+        ) {
+            // Find message by UUID (prefix match)
+            (Some(uuid), _) => Some(
+                messages
+                    .iter()
+                    .position(|m| m.uuid.starts_with(uuid))
+                    .unwrap_or(0),
+            ),
+            (None, Some(position)) => Some(session_position(&messages, position)?),
+            (None, None) => None,
+        };
+        let (start, end, center_idx) = if let Some(idx) = center {
+            // Parse -C/-B/-A
+            let context_c = args.get("-C").and_then(|v| v.as_u64()).unwrap_or(10);
+            let before = args.get("-B").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
+            let after = args.get("-A").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
+
+            let start = idx.saturating_sub(before);
+            let end = (idx + after + 1).min(total);
+            (start, end, Some(idx))
+        } else {
+            // From offset to the end, or limit messages; output over
+            // max_output_tokens is paged by continue_output
+            let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let limit = args.get("limit").and_then(|v| v.as_u64());
+            let start = offset.min(total);
+            let end = limit.map_or(total, |limit| (start + limit as usize).min(total));
+            (start, end, None)
+        };
+
+        let page_messages = &messages[start..end];
+
+        // Format header
+        let mut output = format!(
+            "📁 {} 🗒️ {} ({} msgs) [{}-{}/{}]\n\n",
+            project,
+            short_session,
+            total,
+            start,
+            end.saturating_sub(1),
+            total
+        );
+
+        // Format messages - full content, collapse redundant whitespace
+        for (i, msg) in page_messages.iter().enumerate() {
+            let idx = start + i;
+            let time = local_time(msg.timestamp).format("%H:%M");
+            let msg_type = msg.role_display();
+            // Mark centered message with »
+            let marker = if center_idx == Some(idx) { "»" } else { " " };
+            // Collapse whitespace but keep full content
+            let content = match &folded[idx] {
+                Some(line) => format!("{} 💬 {}", line, short_uuid(&msg.uuid)),
+                None => msg.content.split_whitespace().collect::<Vec<_>>().join(" "),
+            };
+            output.push_str(&format!(
+                "{}[{}] {} {}: {}\n",
+                marker, idx, time, msg_type, content
+            ));
+            output.push_str(&format_notes(&msg.notes, "   "));
+        }
+
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: output,
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_summarize_session(
+        &mut self,
+        args: Option<Value>,
+        max_output_tokens: usize,
+        progress_token: Option<Value>,
+    ) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let session_id = args
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("session_id"))?;
+
+        if args.get("local").and_then(|v| v.as_bool()).unwrap_or(false) {
+            let activity = self
+                .search_engine
+                .session_activity(session_id)?
+                .ok_or_else(|| {
+                    ToolError::not_found(format!("No messages found for session {}", session_id))
+                        .retry_with(serde_json::json!({ "action": "list_sessions" }))
+                })?;
+            self.record_session_access([activity.session_id.as_str()]);
+            let store = SessionStore::new(&self.cache_dir);
+            return Ok(serde_json::to_value(CallToolResponse {
+                content: vec![ToolResult {
+                    result_type: "text".to_string(),
+                    text: cached_extractive_summary(&store, &activity)?,
+                }],
+                is_error: None,
+            })?);
+        }
 
-        let before = if let Some(s) = args.get("before").and_then(|v| v.as_str()) {
-            match parse_date(s) {
-                Ok(dt) => Some(dt),
-                Err(e) => {
-                    return Ok(serde_json::to_value(CallToolResponse {
-                        content: vec![ToolResult {
-                            result_type: "text".to_string(),
-                            text: e,
-                        }],
-                        is_error: Some(true),
-                    })?);
-                }
-            }
-        } else {
-            None
+        // Get session stats for size estimation
+        let search_engine = &self.search_engine;
+        let messages = search_engine.get_session_messages(session_id)?;
+        let Some(first) = messages.first() else {
+            return Err(ToolError::not_found(format!(
+                "No messages found for session {}",
+                session_id
+            ))
+            .retry_with(serde_json::json!({ "action": "list_sessions" }))
+            .into());
         };
+        let session_id = first.session_id.as_str();
+        self.record_session_access([session_id]);
 
-        // Parse include parameter
-        let include = json_strings(args.get("include"));
+        // A configured local model, or else the client's model through
+        // sampling, writes the summary once the server is unlocked; if that
+        // fails the Task below still gets it written
+        let config = get_config();
+        let writer = match &config.summarizer {
+            Some(summarizer) => Some(SummaryWriter::Local(summarizer.clone())),
+            None => self
+                .peer
+                .clone()
+                .filter(Peer::supports_sampling)
+                .map(SummaryWriter::Sampling),
+        };
+        let Some(writer) = writer else {
+            return summary_task(&messages, "");
+        };
+        let refresh = args
+            .get("refresh")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if let Some(stored) = latest_summary(&messages).filter(|s| is_stored_summary(s))
+            && !refresh
+        {
+            return Ok(serde_json::to_value(CallToolResponse {
+                content: vec![ToolResult {
+                    result_type: "text".to_string(),
+                    text: format!(
+                        "Stored summary of {}:\n{}\n\n(refresh:true for a fresh one)",
+                        short_uuid(session_id),
+                        stored.content
+                    ),
+                }],
+                is_error: None,
+            })?);
+        }
+        let progress = self
+            .peer
+            .as_ref()
+            .map(|p| p.notifier().clone())
+            .zip(progress_token);
+        self.pending_summary = Some(PendingSummary::new(
+            messages,
+            writer,
+            max_output_tokens,
+            progress,
+        ));
+        Ok(Value::Null)
+    }
 
-        let truncate_length = args
-            .get("truncate_length")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(300) as usize;
+    /// The reply to a call that left `pending` to be written, once it was:
+    /// the summary stored, or the Task writing it if the model failed
+    fn finish_summary(
+        &mut self,
+        pending: PendingSummary,
+        written: Result<(String, String)>,
+    ) -> Result<String> {
+        let result = match written {
+            Ok((text, source)) => self.store_written_summary(&pending.messages, text, source),
+            Err(e) => summary_task(&pending.messages, &format!("\nSummarizer failed: {}", e)),
+        };
+        let result = self.tool_result(result, pending.max_output_tokens);
+        Ok(serde_json::to_string(&JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id: pending.request_id,
+            result: Some(result),
+            error: None,
+        })?)
+    }
 
-        let display_opts = DisplayOptions {
-            include_thinking: include.contains(&"thinking".to_string()),
-            include_tools: include.contains(&"tools".to_string()),
-            truncate_length,
+    fn store_written_summary(
+        &mut self,
+        messages: &[SearchResult],
+        text: String,
+        source: String,
+    ) -> Result<Value> {
+        let summary = StoredSummary {
+            text,
+            source,
+            message_count: messages.len(),
+            created_at: Utc::now(),
         };
+        {
+            let _writing = self.background_refresh.hold()?;
+            save_session_summary(&self.cache_dir, messages, &summary)?;
+        }
+        let counts = self.cache()?.get_session_counts().clone();
+        self.search_engine.reload(counts)?;
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format!(
+                    "{}\n{}",
+                    format_stored_summary(&messages[0].session_id, &summary),
+                    summary.text
+                ),
+            }],
+            is_error: None,
+        })?)
+    }
 
-        let include_current_session = include.contains(&"current_session".to_string());
+    async fn tool_store_session_summary(&mut self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let session_id = args
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("session_id"))?;
+        let text = args
+            .get("summary")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| ToolError::missing_argument("summary"))?;
+        let source = args
+            .get("source")
+            .and_then(|v| v.as_str())
+            .unwrap_or(AGENT_SUMMARY_SOURCE);
+
+        let messages = self.search_engine.get_session_messages(session_id)?;
+        let Some(first) = messages.first() else {
+            return Err(ToolError::not_found(format!(
+                "No messages found for session {}",
+                session_id
+            ))
+            .retry_with(serde_json::json!({ "action": "list_sessions" }))
+            .into());
+        };
+        let session_id = first.session_id.clone();
+        let summary = StoredSummary {
+            text: text.to_string(),
+            source: source.to_string(),
+            message_count: messages.len(),
+            created_at: Utc::now(),
+        };
+        {
+            let _writing = self.background_refresh.hold()?;
+            save_session_summary(&self.cache_dir, &messages, &summary)?;
+        }
+        let counts = self.cache()?.get_session_counts().clone();
+        self.search_engine.reload(counts)?;
 
-        // Get current session ID from file detected earlier
-        let current_session_id: Option<String> = if !include_current_session {
-            current_session_file.as_ref().and_then(|p| {
-                p.file_stem()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.to_string())
-            })
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_stored_summary(&session_id, &summary),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_get_messages(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let ids = json_strings(args.get("ids"));
+
+        if ids.is_empty() {
+            return Err(ToolError::missing_argument("ids").into());
+        }
+
+        let expand = args
+            .get("expand_tool_results")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        self.note_chosen(ids.iter().map(String::as_str));
+        let search_engine = &self.search_engine;
+        let mut messages = search_engine.get_messages_by_uuid(&ids)?;
+        if expand {
+            expand_tool_results(search_engine, &mut messages)?;
+        }
+        attach_notes(&mut messages)?;
+
+        if messages.is_empty() {
+            return Ok(serde_json::to_value(CallToolResponse {
+                content: vec![ToolResult {
+                    result_type: "text".to_string(),
+                    text: "No messages found for provided IDs".to_string(),
+                }],
+                is_error: None,
+            })?);
+        }
+
+        self.record_session_access(messages.iter().map(|m| m.session_id.as_str()));
+
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_messages(&messages),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_open_message(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let id = args
+            .get("id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("id"))?;
+
+        let Some(location) = locate_message(&self.search_engine, id)? else {
+            return Err(ToolError::not_found(format!(
+                "No message '{}' found in the index or its session file",
+                id
+            ))
+            .retry_with(serde_json::json!({ "action": "reindex", "then": "retry" }))
+            .into());
+        };
+
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_message_location(&location),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_list_sessions(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+        let sort = args
+            .get("sort_by")
+            .and_then(|v| v.as_str())
+            .and_then(SessionSort::parse)
+            .unwrap_or_default();
+
+        let mut sessions = indexed_sessions(&self.search_engine, &self.cache_dir, project_filter)?;
+        if args
+            .get("follow_continuations")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        {
+            let links = self.search_engine.session_continuations()?;
+            sessions = merge_continuations(sessions, &links);
+        }
+        let sessions = prepare_session_list(sessions, self.cache()?.get_session_access(), sort);
+
+        let output = if sessions.is_empty() {
+            "No sessions found.\n".to_string()
         } else {
-            None
+            format_session_list(&sessions, limit)
         };
 
-        let query = SearchQuery {
-            text: query_text,
-            project_filter,
-            session_filter,
-            limit: limit * 3,
-            sort_by,
-            after,
-            before,
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: output,
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_list_projects(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+
+        let projects = self.search_engine.list_projects(project_filter)?;
+        let output = if projects.is_empty() {
+            "No projects found.\n".to_string()
+        } else {
+            format_project_list(&projects, limit)
         };
 
-        let search_engine = &self.search_engine;
-        let results_with_context =
-            search_engine.search_with_context(query, context_before, context_after)?;
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: output,
+            }],
+            is_error: None,
+        })?)
+    }
 
-        // Filter and deduplicate
-        let mut session_seen = std::collections::HashSet::new();
-        let filtered: Vec<_> = results_with_context
-            .into_iter()
-            .filter(|r| {
-                let proj = &r.matched_message.project;
-                let path = &r.matched_message.project_path;
-                let session = &r.matched_message.session_id;
+    async fn tool_usage_report(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+        let days = args.get("days").and_then(|v| v.as_u64()).unwrap_or(14) as usize;
+        let after = date_arg(&args, "after")?;
+        let before = date_arg(&args, "before")?;
 
-                // Exclude current session unless explicitly included
-                if let Some(ref current) = current_session_id
-                    && session == current
-                {
-                    return false;
-                }
+        let report =
+            self.search_engine
+                .usage_report(project_filter, after, before, &get_config().usage)?;
 
-                if exclude_projects.contains(proj) {
-                    return false;
-                }
-                for regex in &exclude_regexes {
-                    if regex.is_match(proj) || regex.is_match(path) {
-                        return false;
-                    }
-                }
-                // Deduplicate by session
-                session_seen.insert(session.clone())
-            })
-            .take(limit)
-            .collect();
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_usage_report(&report, limit, days),
+            }],
+            is_error: None,
+        })?)
+    }
 
-        let mut output = String::new();
+    async fn tool_analyze_tool_usage(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(15) as usize;
+        let after = date_arg(&args, "after")?;
+        let before = date_arg(&args, "before")?;
 
-        if debug_mode {
-            output.push_str(&format!(
-                "DEBUG: query={:?}, -B={}, -A={}, limit={}, exclude_projects={:?}, patterns={:?}\n\n",
-                args.get("query"),
-                context_before,
-                context_after,
-                limit,
-                exclude_projects,
-                all_exclude_patterns
-            ));
-        }
+        let report = self
+            .search_engine
+            .tool_usage(project_filter, after, before)?;
 
-        if !exclude_projects.is_empty() || !all_exclude_patterns.is_empty() {
-            output.push_str(&format!(
-                "Excluding: {} projects, {} patterns\n",
-                exclude_projects.len(),
-                all_exclude_patterns.len()
-            ));
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_tool_usage_report(&report, limit),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_save_search(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let name = args
+            .get("name")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .ok_or_else(|| ToolError::missing_argument("name"))?;
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .ok_or_else(|| ToolError::missing_argument("query"))?;
+        check_query(query)?;
+        let string_arg = |key: &str| args.get(key).and_then(|v| v.as_str()).map(String::from);
+
+        // Validate now rather than when the search is replayed
+        date_arg(&args, "after")?;
+        date_arg(&args, "before")?;
+        let sort_by = string_arg("sort_by");
+        if let Some(sort) = &sort_by
+            && !["relevance", "date_desc", "date_asc"].contains(&sort.as_str())
+        {
+            return Err(ToolError::invalid_argument(format!(
+                "Unknown sort_by '{}': use relevance, date_desc or date_asc",
+                sort
+            ))
+            .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "sort_by" }))
+            .into());
         }
+        let include = json_strings(args.get("include"));
+        get_config().search.expand_include(&include).map_err(|e| {
+            ToolError::invalid_argument(e.to_string())
+                .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "include" }))
+        })?;
+
+        let search = SavedSearch {
+            query: query.to_string(),
+            project: string_arg("project"),
+            host: string_arg("host"),
+            branch: string_arg("branch"),
+            within_days: args
+                .get("within_days")
+                .and_then(|v| v.as_u64())
+                .map(|d| d as u32),
+            after: string_arg("after"),
+            before: string_arg("before"),
+            sort_by,
+            limit: args
+                .get("limit")
+                .and_then(|v| v.as_u64())
+                .map(|l| l as usize),
+            include,
+        };
+        let replaced = save_search(name, &search)?;
 
-        if filtered.is_empty() {
-            if stale_count > 0 || new_count > 0 {
-                // No results but index is stale - return error prompting reindex
-                return Ok(serde_json::to_value(CallToolResponse {
-                    content: vec![ToolResult {
-                        result_type: "text".to_string(),
-                        text: format!(
-                            "No results found. Index is stale ({} modified, {} new files). Call reindex tool and retry search.",
-                            stale_count, new_count
-                        ),
-                    }],
-                    is_error: Some(true),
-                })?);
-            }
-            output.push_str("No results found.\n");
-        } else {
-            for (i, result) in filtered.iter().enumerate() {
-                output.push_str(&result.format_compact_with_options(i, &display_opts));
-                if i < filtered.len() - 1 {
-                    output.push('\n');
-                }
-            }
-            if filtered.len() == limit {
-                output.push_str(&format!("\n+more: limit={}\n", limit));
-            }
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format!(
+                    "{} saved search '{}': {}\n",
+                    if replaced { "Updated" } else { "Added" },
+                    name,
+                    search.describe()
+                ),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_run_saved_search(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let searches = load_saved_searches()?;
+
+        let Some(name) = args.get("name").and_then(|v| v.as_str()) else {
+            return Ok(serde_json::to_value(CallToolResponse {
+                content: vec![ToolResult {
+                    result_type: "text".to_string(),
+                    text: format_saved_searches(&searches),
+                }],
+                is_error: None,
+            })?);
+        };
+        let search = searches.get(name).ok_or_else(|| {
+            ToolError::not_found(format!("No saved search named '{}'", name))
+                .retry_with(serde_json::json!({ "action": "run_saved_search" }))
+        })?;
+
+        let mut search_args = search.to_search_args();
+        if let Some(limit) = args.get("limit") {
+            search_args["limit"] = limit.clone();
         }
+        self.tool_search_conversations(Some(search_args)).await
+    }
+
+    async fn tool_query_history(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let view = match args.get("view").and_then(|v| v.as_str()) {
+            None => QueryHistoryView::default(),
+            Some(name) => QueryHistoryView::parse(name).ok_or_else(|| {
+                ToolError::invalid_argument(format!(
+                    "Unknown view '{}': use recent, frequent or failed",
+                    name
+                ))
+                .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "view" }))
+            })?,
+        };
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+        let records = QueryHistory::open_default()?.list()?;
+
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_query_history(&records, view, limit),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_suggest_terms(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let prefix = args
+            .get("prefix")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| ToolError::missing_argument("prefix"))?;
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+        let terms = self.search_engine.suggest_terms(prefix, limit)?;
 
         Ok(serde_json::to_value(CallToolResponse {
             content: vec![ToolResult {
                 result_type: "text".to_string(),
-                text: output,
+                text: format_term_suggestions(prefix, &terms),
             }],
             is_error: None,
         })?)
     }
 
-    async fn tool_get_session_messages(&mut self, args: Option<Value>) -> Result<Value> {
+    async fn tool_get_config(&self, args: Option<Value>) -> Result<Value> {
         let args = args.unwrap_or_default();
-        let session_id = args
-            .get("session_id")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing 'session_id' parameter"))?;
-
-        let mut messages = self.search_engine.get_session_messages(session_id)?;
+        let section = args.get("section").and_then(|v| v.as_str());
+        let text = format_config(&get_config(), section).map_err(|e| {
+            ToolError::invalid_argument(e.to_string())
+                .retry_with(serde_json::json!({ "action": "fix_argument", "argument": "section" }))
+        })?;
 
-        // Check if session source is stale and reindex if needed
-        if let Some(first) = messages.first()
-            && self.ensure_session_fresh(session_id, &first.project_path)?
-        {
-            // Re-fetch after reindex
-            messages = self.search_engine.get_session_messages(session_id)?;
-        }
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text,
+            }],
+            is_error: None,
+        })?)
+    }
 
-        if messages.is_empty() {
-            return Ok(serde_json::to_value(CallToolResponse {
-                content: vec![ToolResult {
-                    result_type: "text".to_string(),
-                    text: format!("No messages found for session {}", session_id),
-                }],
-                is_error: Some(true),
-            })?);
-        }
+    async fn tool_set_config(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let key = args
+            .get("key")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("key"))?;
+        let value = args
+            .get("value")
+            .ok_or_else(|| ToolError::missing_argument("value"))?;
+        let text = set_search_option(key, serde_yaml::to_value(value)?).map_err(|e| {
+            ToolError::invalid_argument(e.to_string())
+                .retry_with(serde_json::json!({ "action": "get_config", "section": "search" }))
+        })?;
 
-        // Sort by sequence number and filter displayable messages
-        messages.sort_by_key(|m| m.sequence_num);
-        let messages: Vec<_> = messages
-            .into_iter()
-            .filter(|m| m.is_displayable())
-            .collect();
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text,
+            }],
+            is_error: None,
+        })?)
+    }
 
-        let total = messages.len();
-        let project = messages
-            .first()
-            .map(|m| m.project_path_display())
-            .unwrap_or_default();
-        let short_session = short_uuid(session_id);
+    async fn tool_diff_sessions(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let mut sessions = Vec::new();
+        for key in ["session_a", "session_b"] {
+            let id = args
+                .get(key)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ToolError::missing_argument(key))?;
+            let activity = self.search_engine.session_activity(id)?.ok_or_else(|| {
+                ToolError::not_found(format!("No session '{}' in the index", id))
+                    .retry_with(serde_json::json!({ "action": "list_sessions" }))
+            })?;
+            sessions.push(activity);
+        }
+        self.record_session_access(sessions.iter().map(|s| s.session_id.as_str()));
 
-        // Determine pagination: center_on mode vs offset/limit mode
-        let center_on = args.get("center_on").and_then(|v| v.as_str());
-        let (start, end, center_idx) = if let Some(uuid) = center_on {
-            // Find message by UUID (prefix match)
-            let idx = messages
-                .iter()
-                .position(|m| m.uuid.starts_with(uuid))
-                .unwrap_or(0);
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_session_diff(&sessions[0], &sessions[1]),
+            }],
+            is_error: None,
+        })?)
+    }
 
-            // Parse -C/-B/-A
-            let context_c = args.get("-C").and_then(|v| v.as_u64()).unwrap_or(10);
-            let before = args.get("-B").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
-            let after = args.get("-A").and_then(|v| v.as_u64()).unwrap_or(context_c) as usize;
+    async fn tool_bookmark_message(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let id = args
+            .get("id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("id"))?;
+        let remove = args
+            .get("remove")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
-            let start = idx.saturating_sub(before);
-            let end = (idx + after + 1).min(total);
-            (start, end, Some(idx))
+        let store = BookmarkStore::open_default()?;
+        let text = if remove {
+            // Bookmarks of messages gone from the index can still be removed
+            let uuid = self
+                .search_engine
+                .resolve_message_id(id)?
+                .unwrap_or_else(|| id.to_string());
+            if store.remove(&uuid)? {
+                format!("Removed bookmark {}\n", short_uuid(&uuid))
+            } else {
+                format!("Message {} was not bookmarked\n", short_uuid(&uuid))
+            }
         } else {
-            // Standard offset/limit pagination
-            let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-            let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
-            let start = offset.min(total);
-            let end = (offset + limit).min(total);
-            (start, end, None)
+            let uuid = self.search_engine.resolve_message_id(id)?.ok_or_else(|| {
+                ToolError::not_found(format!("No message '{}' in the index", id))
+                    .retry_with(serde_json::json!({ "action": "search_conversations" }))
+            })?;
+            let tags = json_strings(args.get("tags"));
+            let note = args.get("note").and_then(|v| v.as_str());
+            format_bookmark(&store.bookmark(&uuid, &tags, note)?)
         };
 
-        let page_messages = &messages[start..end];
-        let has_more = end < total;
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text,
+            }],
+            is_error: None,
+        })?)
+    }
 
-        // Format header
-        let mut output = format!(
-            "📁 {} 🗒️ {} ({} msgs) [{}-{}/{}]\n\n",
-            project,
-            short_session,
-            total,
-            start,
-            end.saturating_sub(1),
-            total
-        );
+    async fn tool_annotate_message(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let id = args
+            .get("id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("id"))?;
+        let clear = args.get("clear").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let store = BookmarkStore::open_default()?;
+        let text = if clear {
+            // Notes on messages gone from the index can still be cleared
+            let uuid = self
+                .search_engine
+                .resolve_message_id(id)?
+                .unwrap_or_else(|| id.to_string());
+            let removed = store.clear_annotations(&uuid)?;
+            format!("Removed {} notes from {}\n", removed, short_uuid(&uuid))
+        } else {
+            let note = args
+                .get("note")
+                .and_then(|v| v.as_str())
+                .filter(|n| !n.trim().is_empty())
+                .ok_or_else(|| ToolError::missing_argument("note"))?;
+            let uuid = self.search_engine.resolve_message_id(id)?.ok_or_else(|| {
+                ToolError::not_found(format!("No message '{}' in the index", id))
+                    .retry_with(serde_json::json!({ "action": "search_conversations" }))
+            })?;
+            format_annotations(&uuid, &store.annotate(&uuid, note)?)
+        };
 
-        // Format messages - full content, collapse redundant whitespace
-        for (i, msg) in page_messages.iter().enumerate() {
-            let idx = start + i;
-            let time = msg.timestamp.format("%H:%M");
-            let msg_type = msg.role_display();
-            // Mark centered message with »
-            let marker = if center_idx == Some(idx) { "»" } else { " " };
-            // Collapse whitespace but keep full content
-            let content: String = msg.content.split_whitespace().collect::<Vec<_>>().join(" ");
-            output.push_str(&format!(
-                "{}[{}] {} {}: {}\n",
-                marker, idx, time, msg_type, content
-            ));
-        }
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text,
+            }],
+            is_error: None,
+        })?)
+    }
 
-        if has_more {
-            output.push_str(&format!("\n+more: offset={}\n", end));
-        }
+    async fn tool_list_bookmarks(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let tag = args.get("tag").and_then(|v| v.as_str());
+        let bookmarks = BookmarkStore::open_default()?.list(tag)?;
 
         Ok(serde_json::to_value(CallToolResponse {
             content: vec![ToolResult {
                 result_type: "text".to_string(),
-                text: output,
+                text: format_bookmark_list(&self.search_engine, &bookmarks)?,
             }],
             is_error: None,
         })?)
     }
 
-    async fn tool_summarize_session(&self, args: Option<Value>) -> Result<Value> {
+    async fn tool_get_code_blocks(&self, args: Option<Value>) -> Result<Value> {
         let args = args.unwrap_or_default();
-        let session_id = args
-            .get("session_id")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing 'session_id' parameter"))?;
-
-        // Get session stats for size estimation
-        let search_engine = &self.search_engine;
-        let messages = search_engine.get_session_messages(session_id)?;
-        let msg_count = messages.len();
-        let total_chars: usize = messages.iter().map(|m| m.content.len()).sum();
-        let approx_tokens = total_chars / 4; // rough estimate: ~4 chars per token
+        let session_id = args.get("session_id").and_then(|v| v.as_str());
+        let query_text = args.get("query").and_then(|v| v.as_str());
+        let language = args.get("language").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
 
-        let safe_limit = (HAIKU_CONTEXT_WINDOW as f64 * CONTEXT_SAFETY_MARGIN) as usize;
-        let size_note = if approx_tokens > safe_limit {
-            " (large - may need multiple agents)"
-        } else {
-            ""
+        let snippets = match (session_id, query_text) {
+            (Some(session_id), _) => {
+                session_code_snippets(&self.search_engine, session_id, language)?
+            }
+            (None, Some(text)) => {
+                check_query(text)?;
+                let query = SearchQuery {
+                    text: text.to_string(),
+                    project_filters: json_string_list(args.get("project")),
+                    limit,
+                    ..Default::default()
+                };
+                search_code_snippets(&self.search_engine, query, language)?
+            }
+            (None, None) => {
+                return Err(ToolError::invalid_argument("Provide 'session_id' or 'query'").into());
+            }
         };
+        if snippets.is_empty() {
+            let error = ToolError::not_found("No code blocks found");
+            return Err(match language {
+                Some(_) => error.retry_with(
+                    serde_json::json!({ "action": "fix_argument", "argument": "language" }),
+                ),
+                None => error,
+            }
+            .into());
+        }
 
-        let output = format!(
-            r#"Session {session_id}: {msg_count} messages, ~{approx_tokens} tokens{size_note}
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_code_snippets(&snippets, limit),
+            }],
+            is_error: None,
+        })?)
+    }
 
-Task(
-  subagent_type: "general-purpose",
-  model: "haiku",
-  prompt: "Summarize session {session_id}:
-1. Call get_session_messages(session_id=\"{session_id}\")
-2. If output ends with '+more: offset=N', call again with that offset
-3. Repeat until no '+more' appears
-4. Return a concise summary: topic, key decisions, outcome"
-)"#
-        );
+    async fn tool_analyze_errors(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let min_sessions = args
+            .get("min_sessions")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(1) as usize;
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(15) as usize;
+        let after = date_arg(&args, "after")?;
+        let before = date_arg(&args, "before")?;
+
+        let clusters =
+            self.search_engine
+                .error_clusters(project_filter, after, before, min_sessions)?;
+        if clusters.is_empty() {
+            return Err(ToolError::not_found("No matching tool errors found")
+                .retry_with(
+                    serde_json::json!({ "action": "fix_argument", "argument": "min_sessions" }),
+                )
+                .into());
+        }
 
         Ok(serde_json::to_value(CallToolResponse {
             content: vec![ToolResult {
                 result_type: "text".to_string(),
-                text: output,
+                text: format_error_clusters(&clusters, limit),
             }],
             is_error: None,
         })?)
     }
 
-    async fn tool_get_messages(&self, args: Option<Value>) -> Result<Value> {
+    async fn tool_find_decisions(&self, args: Option<Value>) -> Result<Value> {
         let args = args.unwrap_or_default();
-        let ids = json_strings(args.get("ids"));
-
-        if ids.is_empty() {
-            return Ok(serde_json::to_value(CallToolResponse {
-                content: vec![ToolResult {
-                    result_type: "text".to_string(),
-                    text: "No message IDs provided".to_string(),
-                }],
-                is_error: Some(true),
-            })?);
+        let session_filter = args.get("session").and_then(|v| v.as_str());
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(30) as usize;
+        let after = date_arg(&args, "after")?;
+        let before = date_arg(&args, "before")?;
+
+        let decisions =
+            self.search_engine
+                .decisions(session_filter, project_filter, after, before)?;
+        if decisions.is_empty() {
+            return Err(ToolError::not_found("No decisions found")
+                .retry_with(serde_json::json!({ "action": "summarize_session" }))
+                .into());
+        }
+        if session_filter.is_some() {
+            self.record_session_access([decisions[0].session_id.as_str()]);
         }
 
-        let search_engine = &self.search_engine;
-        let messages = search_engine.get_messages_by_uuid(&ids)?;
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_decisions(&decisions, limit),
+            }],
+            is_error: None,
+        })?)
+    }
 
-        if messages.is_empty() {
-            return Ok(serde_json::to_value(CallToolResponse {
-                content: vec![ToolResult {
-                    result_type: "text".to_string(),
-                    text: "No messages found for provided IDs".to_string(),
-                }],
-                is_error: None,
-            })?);
+    async fn tool_find_conversations_for_file(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let path = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ToolError::missing_argument("path"))?;
+        let modified_only = args
+            .get("modified_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+
+        let touches = self
+            .search_engine
+            .file_touches(path, project_filter, modified_only)?;
+        if touches.is_empty() {
+            return Err(
+                ToolError::not_found(format!("No sessions touched {}", path))
+                    .retry_with(serde_json::json!({ "action": "reindex", "then": "retry" }))
+                    .into(),
+            );
         }
 
-        let mut output = String::new();
-        for msg in &messages {
-            output.push_str(&format!(
-                "💬 {} 📅 {} [{}]\n{}\n\n",
-                &msg.uuid[..8.min(msg.uuid.len())],
-                msg.timestamp.format("%Y-%m-%d %H:%M"),
-                msg.message_type,
-                msg.content
-            ));
+        Ok(serde_json::to_value(CallToolResponse {
+            content: vec![ToolResult {
+                result_type: "text".to_string(),
+                text: format_file_history(&touches, limit),
+            }],
+            is_error: None,
+        })?)
+    }
+
+    async fn tool_extract_commands(&self, args: Option<Value>) -> Result<Value> {
+        let args = args.unwrap_or_default();
+        let project_filter = args.get("project").and_then(|v| v.as_str());
+        let session_filter = args.get("session").and_then(|v| v.as_str());
+        let pattern = args.get("pattern").and_then(|v| v.as_str());
+        let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(30) as usize;
+
+        let runs = self
+            .search_engine
+            .command_runs(project_filter, session_filter, pattern)?;
+        if runs.is_empty() {
+            return Err(ToolError::not_found("No matching Bash commands found")
+                .retry_with(serde_json::json!({ "action": "reindex", "then": "retry" }))
+                .into());
         }
 
         Ok(serde_json::to_value(CallToolResponse {
             content: vec![ToolResult {
                 result_type: "text".to_string(),
-                text: output,
+                text: format_command_history(&runs, limit),
             }],
             is_error: None,
         })?)
     }
 
-    #[cfg(unix)]
     async fn tool_respawn(&self) -> Result<Value> {
         // Try to find the release binary first, fallback to current_exe
         let current_dir = std::env::current_dir()
             .map_err(|e| anyhow::anyhow!("Failed to get current directory: {}", e))?;
 
-        let release_path = current_dir.join("target/release/claude-conversation-search");
+        let release_path = current_dir.join(format!(
+            "target/release/claude-conversation-search{}",
+            std::env::consts::EXE_SUFFIX
+        ));
         let exe_path = if release_path.exists() {
             release_path
         } else {
@@ -917,51 +2895,47 @@ Task(
         // Schedule respawn after a short delay to allow response to be sent
         tokio::spawn(async move {
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-            // Replace current process with new instance using exec
-            let args: Vec<String> = std::env::args().collect();
-            let err = exec::execvp(&exe_path, &args);
-            eprintln!("Failed to exec with {}: {}", exe_path.display(), err);
+            respawn(&exe_path);
         });
 
         Ok(serde_json::to_value(response)?)
     }
 
-    #[cfg(windows)]
-    async fn tool_respawn(&self) -> Result<Value> {
-        Ok(serde_json::to_value(CallToolResponse {
-            content: vec![ToolResult {
-                result_type: "text".to_string(),
-                text: "respawn_server is not supported on Windows".to_string(),
-            }],
-            is_error: Some(true),
-        })?)
-    }
-
-    async fn tool_reindex(&mut self, args: Option<Value>) -> Result<Value> {
+    async fn tool_reindex(
+        &mut self,
+        args: Option<Value>,
+        progress_token: Option<Value>,
+    ) -> Result<Value> {
         let args = args.unwrap_or_default();
         let full_rebuild = args.get("full").and_then(|v| v.as_bool()).unwrap_or(false);
         let all_files = discover_jsonl_files()?;
+        let on_progress = progress_reporter(
+            self.peer.as_ref().map(|p| p.notifier().clone()),
+            progress_token,
+        );
 
+        let _writing = self.background_refresh.hold()?;
+        // Other processes index and rebuild under the same lock
+        let _lock = ExclusiveIndexAccess::acquire()?;
         let result = if full_rebuild {
-            // Full rebuild - clear and recreate
-            if self.cache_dir.exists() {
-                std::fs::remove_dir_all(&self.cache_dir)?;
-            }
-            let mut indexer = crate::shared::SearchIndexer::new(&self.cache_dir)?;
-            let mut cache = crate::shared::CacheManager::new(&self.cache_dir)?;
-            cache.update_incremental(&mut indexer, all_files)?;
+            // Full rebuild - built beside the current index, then swapped in
+            let cache = crate::shared::rebuild_index(&self.cache_dir, all_files, on_progress)?;
             let counts = cache.get_session_counts().clone();
+            // The old index files are gone, so reopen rather than reload
             self.search_engine = crate::shared::SearchEngine::new(&self.cache_dir, counts)?;
+            *self.cache()? = cache;
             "Full rebuild complete".to_string()
         } else {
             // Incremental update
-            let mut indexer = crate::shared::SearchIndexer::open(&self.cache_dir)?;
-            let mut cache = crate::shared::CacheManager::new(&self.cache_dir)?;
-            let (stale, new) = cache.quick_health_check(&all_files);
-            cache.update_incremental(&mut indexer, all_files)?;
-            let counts = cache.get_session_counts().clone();
-            self.search_engine = crate::shared::SearchEngine::new(&self.cache_dir, counts)?;
+            let (stale, new, counts) = {
+                let mut indexer = crate::shared::SearchIndexer::open(&self.cache_dir)?;
+                let mut cache = self.cache()?;
+                cache.reload_if_changed()?;
+                let (stale, new) = cache.quick_health_check(&all_files);
+                cache.update_incremental_with_progress(&mut indexer, all_files, on_progress)?;
+                (stale, new, cache.get_session_counts().clone())
+            };
+            self.search_engine.reload(counts)?;
             format!(
                 "Incremental update: {} stale + {} new files reindexed",
                 stale, new
@@ -986,6 +2960,9 @@ Task(
             }
             _ => Err(anyhow::anyhow!("Unknown method: {}", request.method)),
         };
+        if let Some(pending) = &mut self.pending_summary {
+            pending.request_id = request.id.clone();
+        }
 
         match result {
             Ok(result) => JsonRpcResponse {
@@ -1008,39 +2985,136 @@ Task(
     }
 }
 
-pub async fn run_mcp_server() -> Result<()> {
-    // Initialize logging to stderr so it doesn't interfere with JSON-RPC
-    // Only show CRITICAL/ERROR level logs to avoid JSON parsing issues
+/// `summarize_session`'s answer when no model wrote the summary: a Task
+/// for a subagent to write and store it
+fn summary_task(messages: &[SearchResult], summarizer_note: &str) -> Result<Value> {
+    let session_id = messages[0].session_id.as_str();
+    let msg_count = messages.len();
+    let approx_tokens: usize = messages.iter().map(|m| m.token_count).sum();
+
+    let safe_limit = (HAIKU_CONTEXT_WINDOW as f64 * CONTEXT_SAFETY_MARGIN) as usize;
+    let size_note = if approx_tokens > safe_limit {
+        " (large - may need multiple agents)"
+    } else {
+        ""
+    };
+    // Claude Code's own summary often makes the Task unnecessary
+    let summary_note = latest_summary(messages)
+        .map(|s| {
+            let label = if is_stored_summary(s) {
+                "Stored summary"
+            } else {
+                "Claude Code summary"
+            };
+            format!(
+                "\n{}: {}\n\nOnly if more detail is needed:",
+                label, s.content
+            )
+        })
+        .unwrap_or_default();
+
+    let output = format!(
+        r#"Session {session_id}: {msg_count} messages, ~{approx_tokens} tokens{size_note}{summarizer_note}{summary_note}
+
+Task(
+  subagent_type: "general-purpose",
+  model: "haiku",
+  prompt: "Summarize session {session_id}:
+1. Call get_session_messages(session_id=\"{session_id}\", max_output_tokens={page_tokens})
+2. If output ends with '+more: continue_output(continuation=...)', call continue_output with that continuation and max_output_tokens={page_tokens}
+3. Repeat until no '+more' appears
+4. Return a concise summary: topic, key decisions, outcome"
+)
+
+Then store_session_summary(session_id="{session_id}", summary=...) so later searches find it."#,
+        page_tokens = SUMMARY_PAGE_TOKENS
+    );
+
+    Ok(serde_json::to_value(CallToolResponse {
+        content: vec![ToolResult {
+            result_type: "text".to_string(),
+            text: output,
+        }],
+        is_error: None,
+    })?)
+}
+
+/// Build an indexing progress callback that emits `notifications/progress`.
+/// No-op unless the client sent a progress token and can receive notifications.
+fn progress_reporter(
+    notifier: Option<Notifier>,
+    token: Option<Value>,
+) -> impl FnMut(IndexProgress) {
+    let mut last_sent: Option<std::time::Instant> = None;
+    move |p: IndexProgress| {
+        let (Some(notifier), Some(token)) = (&notifier, &token) else {
+            return;
+        };
+        let finished = p.files_done == p.files_total;
+        if !finished && last_sent.is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        last_sent = Some(std::time::Instant::now());
+
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": {
+                "progressToken": token,
+                "progress": p.files_done,
+                "total": p.files_total,
+                "message": format!(
+                    "{}/{} files, {} entries indexed",
+                    p.files_done, p.files_total, p.entries_indexed
+                ),
+            },
+        });
+        let _ = notifier.send(notification.to_string());
+    }
+}
+
+/// Initialize logging to stderr so it doesn't interfere with JSON-RPC.
+/// Only show CRITICAL/ERROR level logs to avoid JSON parsing issues.
+pub(crate) fn init_server_logging() {
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
         .with_env_filter("error")
         .init();
+}
 
-    let mut server = McpServer::new()?;
-    let stdin = tokio::io::stdin();
-    let mut stdout = tokio::io::stdout();
-    let mut reader = AsyncBufReader::new(stdin).lines();
-
-    while let Some(line) = reader.next_line().await? {
-        if line.trim().is_empty() {
-            continue;
+impl McpServer {
+    /// Handle one raw JSON-RPC message and return the serialized reply.
+    /// Notifications and requests raised while handling it go to `peer`, if
+    /// given. Returns None for notifications, which never get a response,
+    /// and for calls left waiting on a summary; see `serve_message`.
+    pub(crate) async fn handle_message(
+        &mut self,
+        line: &str,
+        peer: Option<&Peer>,
+    ) -> Result<Option<String>> {
+        self.peer = peer.cloned();
+        let reply = self.handle_message_inner(line).await;
+        self.peer = None;
+        match self.pending_summary {
+            Some(_) => reply.map(|_| None),
+            None => reply,
         }
+    }
 
+    async fn handle_message_inner(&mut self, line: &str) -> Result<Option<String>> {
         debug!("Received line: {}", line);
 
-        match serde_json::from_str::<JsonRpcRequest>(&line) {
+        let response = match serde_json::from_str::<JsonRpcRequest>(line) {
             Ok(request) => {
-                let response = server.handle_request(request).await;
-                let response_json = serde_json::to_string(&response)?;
-                debug!("Sending response: {}", response_json);
-
-                stdout.write_all(response_json.as_bytes()).await?;
-                stdout.write_all(b"\n").await?;
-                stdout.flush().await?;
+                if request.id.is_none() && request.method.starts_with("notifications/") {
+                    debug!("Received notification: {}", request.method);
+                    return Ok(None);
+                }
+                self.handle_request(request).await
             }
             Err(e) => {
                 error!("Failed to parse JSON-RPC request: {}", e);
-                let error_response = JsonRpcResponse {
+                JsonRpcResponse {
                     jsonrpc: "2.0".to_string(),
                     id: None,
                     result: None,
@@ -1049,14 +3123,139 @@ pub async fn run_mcp_server() -> Result<()> {
                         message: format!("Parse error: {e}"),
                         data: None,
                     }),
-                };
-                let response_json = serde_json::to_string(&error_response)?;
-                stdout.write_all(response_json.as_bytes()).await?;
-                stdout.write_all(b"\n").await?;
-                stdout.flush().await?;
+                }
+            }
+        };
+
+        let response_json = serde_json::to_string(&response)?;
+        debug!("Sending response: {}", response_json);
+        Ok(Some(response_json))
+    }
+}
+
+/// Replace the current process with a new instance using exec
+#[cfg(unix)]
+fn respawn(exe_path: &std::path::Path) {
+    let args: Vec<String> = std::env::args().collect();
+    let err = exec::execvp(exe_path, &args);
+    eprintln!("Failed to exec with {}: {}", exe_path.display(), err);
+}
+
+/// Windows has no exec: start a detached child on the same stdio handles,
+/// then exit so the client's pipes are served by the new instance only
+#[cfg(windows)]
+fn respawn(exe_path: &std::path::Path) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+    let child = std::process::Command::new(exe_path)
+        .args(std::env::args_os().skip(1))
+        .creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+        .spawn();
+    match child {
+        Ok(_) => std::process::exit(0),
+        Err(e) => eprintln!("Failed to spawn {}: {}", exe_path.display(), e),
+    }
+}
+
+pub async fn run_mcp_server() -> Result<()> {
+    // Thin client: relay to the shared daemon when one is running
+    if super::daemon::proxy_stdio().await? {
+        return Ok(());
+    }
+
+    init_server_logging();
+
+    let server = tokio::sync::Mutex::new(McpServer::new()?);
+
+    // Single writer so notifications and responses never interleave mid-line
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            stdout.write_all(message.as_bytes()).await?;
+            stdout.write_all(b"\n").await?;
+            stdout.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+
+    serve_connection(AsyncBufReader::new(tokio::io::stdin()), tx, &server).await?;
+    writer.await??;
+    Ok(())
+}
+
+/// Handle one raw message with a server shared between connections. The
+/// lock is held throughout, except while a summary the message asked for is
+/// written, which can take minutes.
+pub(crate) async fn serve_message(
+    server: &tokio::sync::Mutex<McpServer>,
+    line: &str,
+    peer: Option<&Peer>,
+) -> Result<Option<String>> {
+    let (reply, pending) = {
+        let mut server = server.lock().await;
+        let reply = server.handle_message(line, peer).await;
+        let pending = server.pending_summary.take();
+        (reply?, pending)
+    };
+    let Some(mut pending) = pending else {
+        return Ok(reply);
+    };
+    let written = pending.write().await;
+    let reply = server.lock().await.finish_summary(pending, written)?;
+    Ok(Some(reply))
+}
+
+/// Serve newline-delimited JSON-RPC from one client until it disconnects,
+/// replies going to `tx`. Messages are handled in order, but the client's
+/// responses to server requests (sampling) are read and delivered while a
+/// tool call waits on them.
+pub(crate) async fn serve_connection<R>(
+    reader: R,
+    tx: Notifier,
+    server: &tokio::sync::Mutex<McpServer>,
+) -> Result<()>
+where
+    R: tokio::io::AsyncBufRead + Unpin + Send + 'static,
+{
+    let peer = Peer::new(tx.clone());
+    let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let router_peer = peer.clone();
+    let router = tokio::spawn(async move {
+        let mut lines = reader.lines();
+        let read = async {
+            while let Some(line) = lines.next_line().await? {
+                if line.trim().is_empty() || router_peer.deliver(&line) {
+                    continue;
+                }
+                if line_tx.send(line).is_err() {
+                    break;
+                }
             }
+            Ok::<_, std::io::Error>(())
+        };
+        let result = read.await;
+        router_peer.close();
+        result
+    });
+
+    let mut refreshing = None;
+    while let Some(line) = line_rx.recv().await {
+        let reply = serve_message(server, &line, Some(&peer)).await?;
+        if let Some(response_json) = reply {
+            tx.send(response_json)?;
+        }
+        if let Some(handle) = server.lock().await.start_background_refresh() {
+            refreshing = Some(handle);
         }
     }
 
+    router.await??;
+    // Let a refresh finish writing before a stdio server exits
+    if let Some(handle) = refreshing {
+        handle.await?;
+    }
     Ok(())
 }
@@ -1,10 +1,10 @@
 use anyhow::Result;
 use serde_json::Value;
-use std::collections::HashMap;
 use tracing::debug;
 
 use super::server::{CallToolResponse, ToolResult};
-use crate::shared::{CacheManager, SearchEngine, SearchQuery, SortOrder};
+use super::tool_error::ToolError;
+use crate::shared::{CacheManager, SearchEngine, home_to_tilde, local_time, short_uuid};
 
 pub async fn handle_get_stats(
     search_engine: Option<&SearchEngine>,
@@ -16,134 +16,91 @@ pub async fn handle_get_stats(
         .get("project")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
+    let dir_filter = args.get("dir").and_then(|v| v.as_str());
 
-    debug!("Getting stats for project filter: {:?}", project_filter);
+    debug!(
+        "Getting stats for project filter: {:?}, dir: {:?}",
+        project_filter, dir_filter
+    );
 
     // Get cache info
     let cache_stats = if let Some(cache_mgr) = cache_manager {
         cache_mgr.get_stats()
     } else {
-        return Ok(serde_json::to_value(CallToolResponse {
-            content: vec![ToolResult {
-                result_type: "text".to_string(),
-                text: "Cache manager not initialized".to_string(),
-            }],
-            is_error: Some(true),
-        })?);
-    };
-
-    // Get search results for analysis
-    let query = SearchQuery {
-        text: "*".to_string(),
-        project_filter: project_filter.clone(),
-        session_filter: None,
-        limit: 1000, // Get a large sample for stats
-        sort_by: SortOrder::default(),
-        after: None,
-        before: None,
+        return Ok(ToolError::new("internal_error", "Cache manager not initialized").to_value());
     };
 
     let search_engine =
         search_engine.ok_or_else(|| anyhow::anyhow!("Search engine not initialized"))?;
-    let results = search_engine.search(query)?;
+    let stats = search_engine.aggregate_stats(project_filter.as_deref(), dir_filter)?;
+    let directories = search_engine.directory_breakdown(project_filter.as_deref(), dir_filter)?;
 
-    if results.is_empty() {
-        let msg = if let Some(ref proj) = project_filter {
+    if stats.messages == 0 {
+        let msg = if let Some(dir) = dir_filter {
+            format!("No conversations found under directory: {}", dir)
+        } else if let Some(ref proj) = project_filter {
             format!("No conversations found for project: {}", proj)
         } else {
             "No conversations found in index".to_string()
         };
 
-        return Ok(serde_json::to_value(CallToolResponse {
-            content: vec![ToolResult {
-                result_type: "text".to_string(),
-                text: msg,
-            }],
-            is_error: Some(true),
-        })?);
-    }
-
-    // Analyze conversation data
-    let mut session_count = std::collections::HashSet::new();
-    let mut project_counts = HashMap::new();
-    let mut tech_counts = HashMap::new();
-    let mut lang_counts = HashMap::new();
-    let mut monthly_counts = HashMap::new();
-    let mut has_code_count = 0;
-    let mut has_error_count = 0;
-    let mut total_chars = 0;
-
-    for result in &results {
-        session_count.insert(&result.session_id);
-        *project_counts.entry(&result.project).or_insert(0) += 1;
-        total_chars += result.content.len();
-
-        if result.has_code {
-            has_code_count += 1;
-        }
-        if result.has_error {
-            has_error_count += 1;
-        }
-
-        // Count technologies and languages
-        for tech in &result.technologies {
-            *tech_counts.entry(tech).or_insert(0) += 1;
-        }
-        for lang in &result.code_languages {
-            *lang_counts.entry(lang).or_insert(0) += 1;
-        }
-
-        // Count by month
-        let month_key = result.timestamp.format("%Y-%m").to_string();
-        *monthly_counts.entry(month_key).or_insert(0) += 1;
+        return Ok(ToolError::not_found(msg)
+            .retry_with(serde_json::json!({ "action": "reindex" }))
+            .to_value());
     }
+    let total = stats.messages as f32;
 
     // Sort projects by count
-    let mut project_stats: Vec<_> = project_counts.into_iter().collect();
-    project_stats.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut project_stats: Vec<_> = stats.projects.into_iter().collect();
+    project_stats.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     // Sort tech by count
-    let mut tech_stats: Vec<_> = tech_counts.into_iter().collect();
-    tech_stats.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut tech_stats: Vec<_> = stats.technologies.into_iter().collect();
+    tech_stats.sort_by_key(|b| std::cmp::Reverse(b.1));
     tech_stats.truncate(15); // Top 15
 
     // Sort languages by count
-    let mut lang_stats: Vec<_> = lang_counts.into_iter().collect();
-    lang_stats.sort_by(|a, b| b.1.cmp(&a.1));
+    let mut lang_stats: Vec<_> = stats.code_languages.into_iter().collect();
+    lang_stats.sort_by_key(|b| std::cmp::Reverse(b.1));
     lang_stats.truncate(10); // Top 10
 
     // Sort months chronologically
-    let mut monthly_stats: Vec<_> = monthly_counts.into_iter().collect();
+    let mut monthly_stats: Vec<_> = stats.months.into_iter().collect();
     monthly_stats.sort_by(|a, b| a.0.cmp(&b.0));
 
     let mut output = String::new();
 
     // Header
-    let title = if let Some(ref proj) = project_filter {
+    let mut title = if let Some(ref proj) = project_filter {
         format!("Conversation Statistics - Project: {}", proj)
     } else {
         "Conversation Statistics - All Projects".to_string()
     };
+    if let Some(dir) = dir_filter {
+        title.push_str(&format!(" - Directory: {}", dir));
+    }
     output.push_str(&format!("# {}\n\n", title));
 
     // Overall stats
     output.push_str("## Overview\n");
-    output.push_str(&format!("**Total Messages**: {}\n", results.len()));
-    output.push_str(&format!("**Unique Sessions**: {}\n", session_count.len()));
+    output.push_str(&format!("**Total Messages**: {}\n", stats.messages));
+    output.push_str(&format!("**Unique Sessions**: {}\n", stats.sessions.len()));
     output.push_str(&format!("**Projects**: {}\n", project_stats.len()));
     output.push_str(&format!(
         "**Messages with Code**: {} ({:.1}%)\n",
-        has_code_count,
-        (has_code_count as f32 / results.len() as f32) * 100.0
+        stats.with_code,
+        (stats.with_code as f32 / total) * 100.0
     ));
     output.push_str(&format!(
         "**Messages with Errors**: {} ({:.1}%)\n",
-        has_error_count,
-        (has_error_count as f32 / results.len() as f32) * 100.0
+        stats.with_error,
+        (stats.with_error as f32 / total) * 100.0
     ));
+    output.push_str(&format!("**Total Words**: {}\n", stats.words));
     output.push_str(&format!(
-        "**Total Content**: {:.1} MB\n\n",
-        total_chars as f32 / 1_048_576.0
+        "**Estimated Tokens**: {} (avg {} per session)\n\n",
+        stats.tokens,
+        stats.tokens / stats.sessions.len().max(1) as u64
     ));
 
     // Cache stats
@@ -152,10 +109,17 @@ pub async fn handle_get_stats(
         "**Index Size**: {:.1} MB\n",
         cache_stats.cache_size_mb
     ));
+    if cache_stats.jsonl_size_mb > 0.0 {
+        output.push_str(&format!(
+            "**JSONL Size**: {:.1} MB (segments {:.2}x)\n",
+            cache_stats.jsonl_size_mb,
+            cache_stats.index_size_mb / cache_stats.jsonl_size_mb
+        ));
+    }
     if let Some(last_updated) = cache_stats.last_updated {
         output.push_str(&format!(
             "**Last Updated**: {}\n",
-            last_updated.format("%Y-%m-%d %H:%M")
+            local_time(last_updated).format("%Y-%m-%d %H:%M")
         ));
     }
     output.push_str(&format!("**Total Files**: {}\n", cache_stats.total_files));
@@ -168,7 +132,7 @@ pub async fn handle_get_stats(
     if project_filter.is_none() && project_stats.len() > 1 {
         output.push_str("## Projects\n");
         for (project, count) in project_stats.iter().take(10) {
-            let percentage = (*count as f32 / results.len() as f32) * 100.0;
+            let percentage = (*count as f32 / total) * 100.0;
             output.push_str(&format!(
                 "**{}**: {} messages ({:.1}%)\n",
                 project, count, percentage
@@ -183,11 +147,76 @@ pub async fn handle_get_stats(
         output.push('\n');
     }
 
+    // Branch breakdown (messages outside a git repo have no branch)
+    let mut branch_stats: Vec<_> = stats.branches.into_iter().collect();
+    branch_stats.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    if !branch_stats.is_empty() {
+        output.push_str("## Branches\n");
+        for (branch, count) in branch_stats.iter().take(10) {
+            let percentage = (*count as f32 / total) * 100.0;
+            output.push_str(&format!(
+                "**{}**: {} messages ({:.1}%)\n",
+                branch, count, percentage
+            ));
+        }
+        if branch_stats.len() > 10 {
+            output.push_str(&format!(
+                "... and {} more branches\n",
+                branch_stats.len() - 10
+            ));
+        }
+        output.push('\n');
+    }
+
+    // Subdirectories of the working directories, to drill down with `dir`
+    if !directories.children.is_empty() {
+        output.push_str(&format!(
+            "## Directories under {}\n",
+            home_to_tilde(&directories.path)
+        ));
+        for (name, count) in directories.children.iter().take(10) {
+            let percentage = (*count as f32 / total) * 100.0;
+            output.push_str(&format!(
+                "**{}/**: {} messages ({:.1}%)\n",
+                name, count, percentage
+            ));
+        }
+        if directories.children.len() > 10 {
+            output.push_str(&format!(
+                "... and {} more directories\n",
+                directories.children.len() - 10
+            ));
+        }
+        if directories.here > 0 {
+            output.push_str(&format!(
+                "*{} messages in the directory itself*\n",
+                directories.here
+            ));
+        }
+        output.push('\n');
+    }
+
+    // Largest sessions by tokens (what a summarizer has to read)
+    let mut session_tokens: Vec<_> = stats.session_tokens.into_iter().collect();
+    session_tokens.sort_by_key(|b| std::cmp::Reverse(b.1));
+    if session_tokens.len() > 1 {
+        output.push_str("## Largest Sessions\n");
+        for (session_id, tokens) in session_tokens.iter().take(5) {
+            output.push_str(&format!(
+                "**{}**: ~{} tokens, {} messages\n",
+                short_uuid(session_id),
+                tokens,
+                stats.sessions.get(session_id).copied().unwrap_or(0)
+            ));
+        }
+        output.push('\n');
+    }
+
     // Technology usage
     if !tech_stats.is_empty() {
         output.push_str("## Top Technologies\n");
         for (tech, count) in &tech_stats {
-            let percentage = (*count as f32 / results.len() as f32) * 100.0;
+            let percentage = (*count as f32 / total) * 100.0;
             output.push_str(&format!(
                 "**{}**: {} mentions ({:.1}%)\n",
                 tech, count, percentage
@@ -200,7 +229,7 @@ pub async fn handle_get_stats(
     if !lang_stats.is_empty() {
         output.push_str("## Programming Languages\n");
         for (lang, count) in &lang_stats {
-            let percentage = (*count as f32 / results.len() as f32) * 100.0;
+            let percentage = (*count as f32 / total) * 100.0;
             output.push_str(&format!(
                 "**{}**: {} mentions ({:.1}%)\n",
                 lang, count, percentage
@@ -0,0 +1,95 @@
+//! Summaries written by a model for `summarize_session`. Writing one takes
+//! a request per chunk of the session, each up to minutes, so the tool call
+//! leaves it pending: the server lock is released while it is written and
+//! other connections are served meanwhile, then it is stored under the lock.
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+use super::sampling::{Peer, sample_summary};
+use super::server::Notifier;
+use crate::shared::{SearchResult, SummarizerConfig, summarize_messages_with_progress};
+
+/// What writes the summary
+pub(crate) enum SummaryWriter {
+    /// The configured local model
+    Local(SummarizerConfig),
+    /// The client's model, through sampling
+    Sampling(Peer),
+}
+
+/// A summary a tool call is waiting on, answered once written
+pub(crate) struct PendingSummary {
+    /// JSON-RPC id of the call to answer
+    pub(crate) request_id: Option<Value>,
+    pub(crate) max_output_tokens: usize,
+    pub(crate) messages: Vec<SearchResult>,
+    writer: SummaryWriter,
+    /// Where `notifications/progress` go, and their token
+    progress: Option<(Notifier, Value)>,
+}
+
+impl PendingSummary {
+    pub(crate) fn new(
+        messages: Vec<SearchResult>,
+        writer: SummaryWriter,
+        max_output_tokens: usize,
+        progress: Option<(Notifier, Value)>,
+    ) -> Self {
+        Self {
+            request_id: None,
+            max_output_tokens,
+            messages,
+            writer,
+            progress,
+        }
+    }
+
+    /// Write the summary, reporting progress after each request; returns it
+    /// with the model that wrote it. A local model is run on a blocking
+    /// thread, its client being synchronous.
+    pub(crate) async fn write(&mut self) -> Result<(String, String)> {
+        let on_progress = chunk_progress_reporter(self.progress.clone());
+        match &self.writer {
+            SummaryWriter::Local(config) => {
+                let config = config.clone();
+                let messages = std::mem::take(&mut self.messages);
+                let (messages, summary) = tokio::task::spawn_blocking(move || {
+                    let summary = summarize_messages_with_progress(&config, &messages, on_progress)
+                        .map(|text| (text, config.model.clone()));
+                    (messages, summary)
+                })
+                .await
+                .map_err(|e| anyhow!("Summarizer stopped: {}", e))?;
+                self.messages = messages;
+                summary
+            }
+            SummaryWriter::Sampling(peer) => {
+                sample_summary(peer, &self.messages, on_progress).await
+            }
+        }
+    }
+}
+
+/// Callback sending `notifications/progress` per summarized chunk. No-op
+/// unless the client sent a progress token and can receive notifications.
+fn chunk_progress_reporter(
+    progress: Option<(Notifier, Value)>,
+) -> impl FnMut(usize, usize) + Send + 'static {
+    move |done, total| {
+        let Some((notifier, token)) = &progress else {
+            return;
+        };
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": {
+                "progressToken": token,
+                "progress": done,
+                "total": total,
+                "message": format!("{}/{} summary requests done", done, total),
+            },
+        });
+        let _ = notifier.send(notification.to_string());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::shared::AmbiguousId;
+use serde_json::{Value, json};
+use std::fmt;
+
+/// Tool failure reported to the client as an `isError` result.
+///
+/// The text content reads naturally; `structuredContent.error` carries a stable
+/// `code` and an optional `retry_with` hint (e.g. `{"action":"reindex"}`) so
+/// agents can recover without parsing the message.
+#[derive(Debug)]
+pub struct ToolError {
+    pub code: &'static str,
+    pub message: String,
+    pub retry_with: Option<Value>,
+}
+
+impl ToolError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retry_with: None,
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new("invalid_argument", message)
+    }
+
+    pub fn missing_argument(name: &str) -> Self {
+        Self::invalid_argument(format!("Missing '{}' parameter", name))
+            .retry_with(json!({ "action": "add_argument", "argument": name }))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new("not_found", message)
+    }
+
+    pub fn retry_with(mut self, hint: Value) -> Self {
+        self.retry_with = Some(hint);
+        self
+    }
+
+    /// Serialize as a `tools/call` result
+    pub fn to_value(&self) -> Value {
+        let mut text = format!("{}\ncode={}", self.message, self.code);
+        if let Some(hint) = &self.retry_with {
+            text.push_str(&format!(" retry_with={}", hint));
+        }
+
+        json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": true,
+            "structuredContent": {
+                "error": {
+                    "code": self.code,
+                    "message": self.message,
+                    "retry_with": self.retry_with,
+                }
+            },
+        })
+    }
+}
+
+impl From<AmbiguousId> for ToolError {
+    fn from(ambiguous: AmbiguousId) -> Self {
+        Self::invalid_argument(ambiguous.to_string()).retry_with(json!({
+            "action": "use_longer_id",
+            "candidates": ambiguous.candidates,
+        }))
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_error_payload() {
+        let value = ToolError::new("stale_index", "Index is stale")
+            .retry_with(json!({ "action": "reindex" }))
+            .to_value();
+
+        assert_eq!(value["isError"], true);
+        assert_eq!(value["structuredContent"]["error"]["code"], "stale_index");
+        assert_eq!(
+            value["structuredContent"]["error"]["retry_with"]["action"],
+            "reindex"
+        );
+        let text = value["content"][0]["text"].as_str().unwrap();
+        assert!(text.starts_with("Index is stale\ncode=stale_index"));
+    }
+}
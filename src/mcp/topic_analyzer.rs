@@ -0,0 +1,39 @@
+use anyhow::Result;
+use serde_json::Value;
+use tracing::debug;
+
+use super::server::{CallToolResponse, ToolResult, date_arg};
+use super::tool_error::ToolError;
+use crate::shared::{SearchEngine, format_topic_report};
+
+pub async fn handle_analyze_topics(
+    search_engine: &SearchEngine,
+    args: Option<Value>,
+) -> Result<Value> {
+    let args = args.unwrap_or_default();
+    let project_filter = args.get("project").and_then(|v| v.as_str());
+    let dir_filter = args.get("dir").and_then(|v| v.as_str());
+    let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+    let after = date_arg(&args, "after")?;
+    let before = date_arg(&args, "before")?;
+
+    debug!(
+        "Analyzing topics for project filter: {:?}, dir: {:?}",
+        project_filter, dir_filter
+    );
+
+    let report = search_engine.topic_report(project_filter, dir_filter, after, before)?;
+    if report.messages == 0 {
+        return Ok(ToolError::not_found("No conversations found to analyze")
+            .retry_with(serde_json::json!({ "action": "reindex" }))
+            .to_value());
+    }
+
+    Ok(serde_json::to_value(CallToolResponse {
+        content: vec![ToolResult {
+            result_type: "text".to_string(),
+            text: format_topic_report(&report, project_filter, dir_filter, limit),
+        }],
+        is_error: None,
+    })?)
+}
@@ -0,0 +1,612 @@
+use super::config::get_config;
+use super::models::{ConversationEntry, MessageType, ShellCommand};
+use super::parser::{JsonlParser, read_text_file};
+use super::transcripts::{content_text, derived_id, new_entry, str_field, time_field};
+use super::utils::{file_mtime, truncate_content};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use strip_ansi_escapes::strip_str;
+use tracing::warn;
+
+/// `source` of entries from Codex CLI rollouts
+pub const CODEX_SOURCE: &str = "codex";
+/// `source` of entries from Gemini CLI chats
+pub const GEMINI_SOURCE: &str = "gemini";
+
+/// Codex tools whose input is a shell command
+const CODEX_SHELL_TOOLS: &[&str] = &["shell", "shell_command", "local_shell", "exec_command"];
+/// Gemini tools whose `command` argument is a shell command
+const GEMINI_SHELL_TOOLS: &[&str] = &["run_shell_command"];
+/// Gemini tools whose `file_path` argument is modified / only read
+const GEMINI_WRITE_TOOLS: &[&str] = &["write_file", "replace"];
+const GEMINI_READ_TOOLS: &[&str] = &["read_file"];
+
+/// User messages Codex writes itself at the start of a session
+const CODEX_INJECTED_PREFIXES: &[&str] = &[
+    "<environment_context>",
+    "<user_instructions>",
+    "# AGENTS.md instructions",
+];
+
+/// Text of a tool result the way the JSONL parser indexes one: errors are
+/// kept, successful output only when `index.skip_tool_results` is off
+fn tool_result_text(output: &str, is_error: bool) -> String {
+    if is_error {
+        let preview = truncate_content(output, get_config().limits.tool_result_max_chars, false);
+        format!("[error] {}", preview)
+    } else if get_config().index.skip_tool_results {
+        format!("[result omitted: {} chars]", output.chars().count())
+    } else {
+        let preview = truncate_content(output, get_config().limits.tool_result_max_chars, false);
+        format!("[result] {}", preview)
+    }
+}
+
+fn tool_input_text(name: &str, input: &str) -> String {
+    let preview = truncate_content(input, get_config().limits.tool_input_max_chars, false);
+    format!("[{}] {}", name, preview)
+}
+
+/// `Exit code: N` / `Exit Code: N` line of a shell tool's output
+fn exit_code_line(output: &str) -> Option<i32> {
+    output.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("Exit code:")
+            .or_else(|| line.trim().strip_prefix("Exit Code:"))?
+            .trim()
+            .parse()
+            .ok()
+    })
+}
+
+/// Session being read from a Codex rollout, updated as its lines go by
+struct CodexState {
+    session_id: String,
+    cwd: Option<String>,
+    git_branch: Option<String>,
+    model: Option<String>,
+    timestamp: DateTime<Utc>,
+    /// call_id -> tool name
+    tool_names: HashMap<String, String>,
+    /// Shell call_id -> (failed, exit code)
+    shell_results: HashMap<String, (bool, Option<i32>)>,
+}
+
+/// Codex CLI rollout files (`~/.codex/sessions/YYYY/MM/DD/rollout-*.jsonl`):
+/// one JSON line per event, wrapped in `{"type", "payload"}` since late 2025
+/// and bare before that
+#[derive(Default)]
+pub struct CodexParser;
+
+impl CodexParser {
+    pub fn parse_file(&self, path: &Path) -> Result<Vec<ConversationEntry>> {
+        let (content, _) = read_text_file(path)?;
+        // rollout-2025-10-01T09-00-00-<uuid>.jsonl
+        let file_session = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.get(s.len().saturating_sub(36)..))
+            .filter(|s| uuid::Uuid::parse_str(s).is_ok())
+            .map(str::to_string);
+        let mut state = CodexState {
+            session_id: file_session.unwrap_or_else(|| derived_id(&path.to_string_lossy())),
+            cwd: None,
+            git_branch: None,
+            model: None,
+            timestamp: file_mtime(path)?,
+            tool_names: HashMap::new(),
+            shell_results: HashMap::new(),
+        };
+
+        let mut entries: Vec<ConversationEntry> = Vec::new();
+        for (line_num, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = match serde_json::from_str(line) {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("Invalid JSON at {}:{}: {}", path.display(), line_num + 1, e);
+                    continue;
+                }
+            };
+            if let Some(timestamp) = time_field(&value, "timestamp") {
+                state.timestamp = timestamp;
+            }
+
+            let entry = match (
+                value.get("type").and_then(Value::as_str),
+                value.get("payload"),
+            ) {
+                (Some("session_meta"), Some(meta)) => {
+                    self.read_meta(meta, &mut state);
+                    None
+                }
+                (Some("turn_context"), Some(context)) => {
+                    state.cwd = str_field(context, "cwd").or(state.cwd.take());
+                    state.model = str_field(context, "model").or(state.model.take());
+                    None
+                }
+                (Some("response_item"), Some(item)) => self.read_item(item, &mut state),
+                // Old rollouts: a bare session header, then bare items
+                (None, None) if value.get("id").is_some() => {
+                    self.read_meta(&value, &mut state);
+                    None
+                }
+                (Some(_), None) => self.read_item(&value, &mut state),
+                _ => None,
+            };
+
+            if let Some(mut entry) = entry {
+                entry.uuid = derived_id(&format!("{}:{}", state.session_id, line_num));
+                entry.parent_uuid = entries.last().map(|e| e.uuid.clone());
+                entry.sequence_num = entries.len();
+                entries.push(entry);
+            }
+        }
+
+        for command in entries.iter_mut().flat_map(|e| e.shell_commands.iter_mut()) {
+            if let Some(id) = &command.tool_use_id
+                && let Some(&(failed, exit_code)) = state.shell_results.get(id)
+            {
+                command.failed = failed;
+                command.exit_code = exit_code;
+            }
+        }
+        let source_file = path.to_string_lossy().into_owned();
+        for entry in &mut entries {
+            entry.session_id = state.session_id.clone();
+            entry.source_file = Some(source_file.clone());
+        }
+        Ok(entries)
+    }
+
+    fn read_meta(&self, meta: &Value, state: &mut CodexState) {
+        if let Some(id) = str_field(meta, "id") {
+            state.session_id = id;
+        }
+        if let Some(timestamp) = time_field(meta, "timestamp") {
+            state.timestamp = timestamp;
+        }
+        state.cwd = str_field(meta, "cwd").or(state.cwd.take());
+        state.git_branch = meta
+            .get("git")
+            .and_then(|git| str_field(git, "branch"))
+            .or(state.git_branch.take());
+    }
+
+    fn read_item(&self, item: &Value, state: &mut CodexState) -> Option<ConversationEntry> {
+        let call_id = str_field(item, "call_id").or_else(|| str_field(item, "id"));
+        let mut entry = match item.get("type")?.as_str()? {
+            "message" => {
+                let message_type = match item.get("role")?.as_str()? {
+                    "user" => MessageType::User,
+                    "assistant" => MessageType::Assistant,
+                    _ => return None,
+                };
+                let text = content_text(item.get("content"));
+                if matches!(message_type, MessageType::User)
+                    && CODEX_INJECTED_PREFIXES
+                        .iter()
+                        .any(|prefix| text.trim_start().starts_with(prefix))
+                {
+                    return None;
+                }
+                self.entry(state, message_type, &text)?
+            }
+            "reasoning" => {
+                let summary = content_text(item.get("summary"));
+                if summary.trim().is_empty() {
+                    return None;
+                }
+                self.entry(
+                    state,
+                    MessageType::Assistant,
+                    &format!("[thinking] {}", summary),
+                )?
+            }
+            "function_call" | "custom_tool_call" | "local_shell_call" => {
+                let name = str_field(item, "name").unwrap_or_else(|| "local_shell".to_string());
+                let input = match item.get("arguments").or_else(|| item.get("input")) {
+                    Some(Value::String(input)) => input.clone(),
+                    Some(other) => other.to_string(),
+                    None => item.get("action").map(Value::to_string).unwrap_or_default(),
+                };
+                let command = CODEX_SHELL_TOOLS
+                    .contains(&name.as_str())
+                    .then(|| shell_command(item, &input))
+                    .flatten();
+                let mut entry = self.entry(
+                    state,
+                    MessageType::Assistant,
+                    &tool_input_text(&name, command.as_deref().unwrap_or(&input)),
+                )?;
+                if let Some(command) = command {
+                    entry.shell_commands.push(ShellCommand {
+                        tool_use_id: call_id.clone(),
+                        command,
+                        exit_code: None,
+                        failed: false,
+                    });
+                }
+                if name == "apply_patch" {
+                    for path in patched_files(&input) {
+                        entry.files_modified.push(path.clone());
+                        entry.files_touched.push(path);
+                    }
+                }
+                if let Some(id) = call_id {
+                    state.tool_names.insert(id, name.clone());
+                }
+                if !entry.tools_mentioned.contains(&name) {
+                    entry.tools_mentioned.push(name.clone());
+                }
+                entry.tool_calls.push(name);
+                entry
+            }
+            "function_call_output" | "custom_tool_call_output" => {
+                let raw = match item.get("output")? {
+                    Value::String(output) => output.clone(),
+                    other => other.to_string(),
+                };
+                // Older Codex wraps output as {"output", "metadata": {"exit_code"}}
+                let wrapped: Option<Value> = serde_json::from_str(&raw).ok();
+                let output = wrapped
+                    .as_ref()
+                    .and_then(|w| str_field(w, "output"))
+                    .unwrap_or_else(|| raw.clone());
+                let exit_code = wrapped
+                    .as_ref()
+                    .and_then(|w| w.pointer("/metadata/exit_code"))
+                    .and_then(Value::as_i64)
+                    .map(|code| code as i32)
+                    .or_else(|| exit_code_line(&output));
+                let is_error = exit_code.is_some_and(|code| code != 0);
+                let tool = call_id
+                    .as_ref()
+                    .and_then(|id| state.tool_names.get(id).cloned());
+                if let Some(id) = &call_id
+                    && tool
+                        .as_deref()
+                        .is_some_and(|name| CODEX_SHELL_TOOLS.contains(&name))
+                {
+                    state
+                        .shell_results
+                        .insert(id.clone(), (is_error, exit_code));
+                }
+                let mut entry = self.entry(
+                    state,
+                    MessageType::User,
+                    &tool_result_text(&strip_str(&output), is_error),
+                )?;
+                if is_error {
+                    entry.has_error = true;
+                    entry
+                        .tool_errors
+                        .push(tool.unwrap_or_else(|| "unknown".to_string()));
+                }
+                entry
+            }
+            _ => return None,
+        };
+        entry.cwd = state.cwd.clone();
+        entry.git_branch = state.git_branch.clone();
+        if matches!(entry.message_type, MessageType::Assistant) {
+            entry.model = state.model.clone();
+        }
+        Some(entry)
+    }
+
+    fn entry(
+        &self,
+        state: &CodexState,
+        message_type: MessageType,
+        text: &str,
+    ) -> Option<ConversationEntry> {
+        let content =
+            truncate_content(&strip_str(text), get_config().limits.entry_max_chars, false);
+        if content.trim().is_empty() {
+            return None;
+        }
+        let mut entry = new_entry(
+            String::new(),
+            &state.session_id,
+            CODEX_SOURCE,
+            state.timestamp,
+            message_type,
+            content,
+        );
+        if let Some(cwd) = &state.cwd {
+            entry.project_path = JsonlParser.extract_project_name_from_path(cwd);
+        }
+        Some(entry)
+    }
+}
+
+/// Command line of a Codex shell call: `["bash", "-lc", "cmd"]` is shown as
+/// `cmd`, other argv lists joined with spaces
+fn shell_command(item: &Value, input: &str) -> Option<String> {
+    let arguments: Value = serde_json::from_str(input).unwrap_or(Value::Null);
+    let command = arguments
+        .get("command")
+        .or_else(|| arguments.get("cmd"))
+        .or_else(|| item.pointer("/action/command"))?;
+    match command {
+        Value::String(command) => Some(command.clone()),
+        Value::Array(argv) => {
+            let argv: Vec<&str> = argv.iter().filter_map(Value::as_str).collect();
+            match argv.as_slice() {
+                [_, "-lc" | "-c", script] => Some(script.to_string()),
+                _ => Some(argv.join(" ")),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Files an `apply_patch` input adds, updates or deletes
+fn patched_files(patch: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for line in patch.lines() {
+        let path = ["*** Add File: ", "*** Update File: ", "*** Delete File: "]
+            .iter()
+            .find_map(|prefix| line.strip_prefix(prefix));
+        if let Some(path) = path
+            && !files.iter().any(|f| f == path.trim())
+        {
+            files.push(path.trim().to_string());
+        }
+    }
+    files
+}
+
+/// Gemini CLI chat files (`~/.gemini/tmp/<project hash>/chats/session-*.json`):
+/// one session with `messages` of type `user` or `gemini`
+#[derive(Default)]
+pub struct GeminiParser;
+
+impl GeminiParser {
+    pub fn parse_file(&self, path: &Path) -> Result<Vec<ConversationEntry>> {
+        let (content, _) = read_text_file(path)?;
+        let json: Value = serde_json::from_str(&content)?;
+        let session_id =
+            str_field(&json, "sessionId").unwrap_or_else(|| derived_id(&path.to_string_lossy()));
+        let mut timestamp = time_field(&json, "startTime").unwrap_or(file_mtime(path)?);
+
+        let mut entries: Vec<ConversationEntry> = Vec::new();
+        let messages = json.get("messages").and_then(Value::as_array);
+        for (index, message) in messages.into_iter().flatten().enumerate() {
+            let message_type = match message.get("type").and_then(Value::as_str) {
+                Some("user") => MessageType::User,
+                Some("gemini") => MessageType::Assistant,
+                _ => continue,
+            };
+            timestamp = time_field(message, "timestamp").unwrap_or(timestamp);
+            let uuid = str_field(message, "id")
+                .unwrap_or_else(|| derived_id(&format!("{session_id}:{index}")));
+
+            let mut parts = Vec::new();
+            for thought in message
+                .get("thoughts")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let subject = str_field(thought, "subject").unwrap_or_default();
+                let description = str_field(thought, "description").unwrap_or_default();
+                parts.push(format!("[thinking] {} {}", subject, description));
+            }
+            parts.push(content_text(message.get("content")));
+
+            let calls: Vec<&Value> = message
+                .get("toolCalls")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .collect();
+            let mut results = Vec::new();
+            let mut tool_calls = Vec::new();
+            let mut shell_commands = Vec::new();
+            let mut files_touched = Vec::new();
+            let mut files_modified = Vec::new();
+            for call in calls {
+                let Some(name) = str_field(call, "name") else {
+                    continue;
+                };
+                let args = call.get("args").cloned().unwrap_or(Value::Null);
+                parts.push(tool_input_text(&name, &args.to_string()));
+
+                let output = gemini_tool_output(call);
+                let exit_code = exit_code_line(&output);
+                let is_error = call.get("status").and_then(Value::as_str) == Some("error")
+                    || exit_code.is_some_and(|code| code != 0);
+                if GEMINI_SHELL_TOOLS.contains(&name.as_str())
+                    && let Some(command) = str_field(&args, "command")
+                {
+                    shell_commands.push(ShellCommand {
+                        tool_use_id: str_field(call, "id"),
+                        command,
+                        exit_code: exit_code.or((!is_error).then_some(0)),
+                        failed: is_error,
+                    });
+                }
+                let file =
+                    str_field(&args, "file_path").or_else(|| str_field(&args, "absolute_path"));
+                if let Some(file) = file {
+                    if GEMINI_WRITE_TOOLS.contains(&name.as_str()) {
+                        files_modified.push(file.clone());
+                        files_touched.push(file);
+                    } else if GEMINI_READ_TOOLS.contains(&name.as_str()) {
+                        files_touched.push(file);
+                    }
+                }
+                results.push((name.clone(), output, is_error));
+                tool_calls.push(name);
+            }
+
+            let content = truncate_content(
+                &strip_str(parts.join("\n")),
+                get_config().limits.entry_max_chars,
+                false,
+            );
+            if !content.trim().is_empty() {
+                let mut entry = new_entry(
+                    uuid.clone(),
+                    &session_id,
+                    GEMINI_SOURCE,
+                    timestamp,
+                    message_type,
+                    content,
+                );
+                if matches!(entry.message_type, MessageType::Assistant) {
+                    entry.model = str_field(message, "model");
+                }
+                for name in &tool_calls {
+                    if !entry.tools_mentioned.contains(name) {
+                        entry.tools_mentioned.push(name.clone());
+                    }
+                }
+                entry.tool_calls = tool_calls;
+                entry.shell_commands = shell_commands;
+                entry.files_touched = files_touched;
+                entry.files_modified = files_modified;
+                push_threaded(&mut entries, entry);
+            }
+
+            // Results are their own user turn, as in Claude Code
+            for (call_index, (name, output, is_error)) in results.into_iter().enumerate() {
+                let mut entry = new_entry(
+                    derived_id(&format!("{uuid}:result:{call_index}")),
+                    &session_id,
+                    GEMINI_SOURCE,
+                    timestamp,
+                    MessageType::User,
+                    tool_result_text(&strip_str(&output), is_error),
+                );
+                if is_error {
+                    entry.has_error = true;
+                    entry.tool_errors.push(name);
+                }
+                push_threaded(&mut entries, entry);
+            }
+        }
+
+        let source_file = path.to_string_lossy().into_owned();
+        for entry in &mut entries {
+            entry.source_file = Some(source_file.clone());
+        }
+        Ok(entries)
+    }
+}
+
+/// Output of a Gemini tool call: the function responses, else what the CLI showed
+fn gemini_tool_output(call: &Value) -> String {
+    let responses: Vec<String> = call
+        .get("result")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part.pointer("/functionResponse/response"))
+        .filter_map(|response| {
+            str_field(response, "output").or_else(|| str_field(response, "error"))
+        })
+        .collect();
+    if responses.is_empty() {
+        str_field(call, "resultDisplay").unwrap_or_default()
+    } else {
+        responses.join("\n")
+    }
+}
+
+fn push_threaded(entries: &mut Vec<ConversationEntry>, mut entry: ConversationEntry) {
+    entry.parent_uuid = entries.last().map(|e| e.uuid.clone());
+    entry.sequence_num = entries.len();
+    entries.push(entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codex_rollout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir
+            .path()
+            .join("rollout-2026-03-01T09-00-00-0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b.jsonl");
+        let lines = [
+            r#"{"timestamp":"2026-03-01T09:00:00Z","type":"session_meta","payload":{"id":"0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b","cwd":"/home/me/vault-rs","git":{"branch":"main"}}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:00Z","type":"turn_context","payload":{"cwd":"/home/me/vault-rs","model":"gpt-5-codex"}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:01Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"<environment_context>cwd</environment_context>"}]}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:02Z","type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"Why does cargo test fail?"}]}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:03Z","type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"bash\",\"-lc\",\"cargo test\"]}","call_id":"call_1"}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:09Z","type":"response_item","payload":{"type":"function_call_output","call_id":"call_1","output":"{\"output\":\"error[E0382]: borrow of moved value\",\"metadata\":{\"exit_code\":101}}"}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:10Z","type":"event_msg","payload":{"type":"agent_message","message":"duplicate"}}"#,
+            r#"{"timestamp":"2026-03-01T09:00:11Z","type":"response_item","payload":{"type":"message","role":"assistant","content":[{"type":"output_text","text":"The Vec is moved into the closure."}]}}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let entries = CodexParser.parse_file(&path).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|e| e.source == CODEX_SOURCE));
+        assert!(
+            entries
+                .iter()
+                .all(|e| e.session_id == "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b")
+        );
+        assert_eq!(entries[0].content, "Why does cargo test fail?");
+        assert_eq!(entries[0].project_path, "vault-rs");
+        assert_eq!(entries[0].git_branch.as_deref(), Some("main"));
+        assert_eq!(entries[1].content, "[shell] cargo test");
+        assert_eq!(entries[1].model.as_deref(), Some("gpt-5-codex"));
+        let command = &entries[1].shell_commands[0];
+        assert_eq!(command.command, "cargo test");
+        assert_eq!(command.exit_code, Some(101));
+        assert!(command.failed);
+        assert_eq!(entries[2].kind(), "tool_result");
+        assert_eq!(entries[2].tool_errors, vec!["shell"]);
+        assert_eq!(
+            entries[3].parent_uuid.as_deref(),
+            Some(entries[2].uuid.as_str())
+        );
+    }
+
+    #[test]
+    fn test_gemini_chat() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("session-2026-03-01T09-00-abcd.json");
+        std::fs::write(
+            &path,
+            r#"{"sessionId":"g-1","startTime":"2026-03-01T09:00:00Z","messages":[
+                {"id":"u1","timestamp":"2026-03-01T09:00:00Z","type":"user","content":"List the failing tests"},
+                {"id":"a1","timestamp":"2026-03-01T09:00:05Z","type":"gemini","model":"gemini-2.5-pro","content":"Running them now.",
+                 "thoughts":[{"subject":"Plan","description":"run the suite"}],
+                 "toolCalls":[{"id":"t1","name":"run_shell_command","args":{"command":"npm test"},"status":"error",
+                   "result":[{"functionResponse":{"id":"t1","name":"run_shell_command","response":{"output":"Exit Code: 1\n2 failing"}}}]}]},
+                {"id":"i1","type":"info","content":"Request cancelled."}
+            ]}"#,
+        )
+        .unwrap();
+
+        let entries = GeminiParser.parse_file(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(
+            entries
+                .iter()
+                .all(|e| e.source == GEMINI_SOURCE && e.session_id == "g-1")
+        );
+        assert!(
+            entries[1]
+                .content
+                .starts_with("[thinking] Plan run the suite")
+        );
+        assert!(entries[1].content.contains("[run_shell_command]"));
+        assert_eq!(entries[1].model.as_deref(), Some("gemini-2.5-pro"));
+        assert_eq!(entries[1].shell_commands[0].exit_code, Some(1));
+        assert!(entries[2].content.starts_with("[error] Exit Code: 1"));
+        assert_eq!(entries[2].parent_uuid.as_deref(), Some("a1"));
+    }
+}
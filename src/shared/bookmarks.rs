@@ -0,0 +1,370 @@
+use super::config::get_config;
+use super::models::SearchResult;
+use super::path_utils::short_uuid;
+use super::query_fields::split_bookmarked;
+use super::search::SearchEngine;
+use super::session_store::open_database;
+use super::timezone::local_time;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redb::{ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Message uuid → JSON-encoded `BookmarkRecord`
+const BOOKMARKS: TableDefinition<&str, &[u8]> = TableDefinition::new("bookmarks");
+/// Message uuid → JSON-encoded `Vec<Annotation>`, oldest first
+const ANNOTATIONS: TableDefinition<&str, &[u8]> = TableDefinition::new("annotations");
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BookmarkRecord {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// A message marked for later, with its tags and note
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Bookmark {
+    pub uuid: String,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Bookmark {
+    fn from_record(uuid: &str, record: BookmarkRecord) -> Self {
+        Self {
+            uuid: uuid.to_string(),
+            tags: record.tags,
+            note: record.note,
+            created_at: record.created_at,
+        }
+    }
+}
+
+/// A free-text note attached to a message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Annotation {
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Bookmarked and annotated messages, keyed by uuid, in the data dir so they outlive
+/// `cache clear` and rebuilds
+pub struct BookmarkStore {
+    path: PathBuf,
+}
+
+impl BookmarkStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join("bookmarks.redb"),
+        }
+    }
+
+    /// The store in the configured data dir
+    pub fn open_default() -> Result<Self> {
+        let data_dir = get_config().get_data_dir()?;
+        std::fs::create_dir_all(&data_dir)?;
+        Ok(Self::new(&data_dir))
+    }
+
+    /// Bookmark a message, or update its bookmark: tags are added to the
+    /// existing ones and a note replaces the old note
+    pub fn bookmark(&self, uuid: &str, tags: &[String], note: Option<&str>) -> Result<Bookmark> {
+        let db = open_database(&self.path)?;
+        let txn = db.begin_write()?;
+        let record = {
+            let mut table = txn.open_table(BOOKMARKS)?;
+            let mut record = match table.get(uuid)? {
+                Some(value) => serde_json::from_slice(value.value())?,
+                None => BookmarkRecord {
+                    created_at: Utc::now(),
+                    ..Default::default()
+                },
+            };
+            for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+                if !record.tags.iter().any(|t| t == tag) {
+                    record.tags.push(tag.to_string());
+                }
+            }
+            if let Some(note) = note {
+                record.note = Some(note.trim().to_string()).filter(|n| !n.is_empty());
+            }
+            table.insert(uuid, serde_json::to_vec(&record)?.as_slice())?;
+            record
+        };
+        txn.commit()?;
+        Ok(Bookmark::from_record(uuid, record))
+    }
+
+    /// Remove a bookmark; false when the message had none
+    pub fn remove(&self, uuid: &str) -> Result<bool> {
+        if !self.path.exists() {
+            return Ok(false);
+        }
+        let db = open_database(&self.path)?;
+        let txn = db.begin_write()?;
+        let removed = txn.open_table(BOOKMARKS)?.remove(uuid)?.is_some();
+        txn.commit()?;
+        Ok(removed)
+    }
+
+    /// Add a note to a message, after any it already has; returns them all
+    pub fn annotate(&self, uuid: &str, text: &str) -> Result<Vec<Annotation>> {
+        let text = text.trim();
+        if text.is_empty() {
+            anyhow::bail!("Annotation text is empty");
+        }
+        let db = open_database(&self.path)?;
+        let txn = db.begin_write()?;
+        let annotations = {
+            let mut table = txn.open_table(ANNOTATIONS)?;
+            let mut annotations: Vec<Annotation> = match table.get(uuid)? {
+                Some(value) => serde_json::from_slice(value.value())?,
+                None => Vec::new(),
+            };
+            annotations.push(Annotation {
+                text: text.to_string(),
+                created_at: Utc::now(),
+            });
+            table.insert(uuid, serde_json::to_vec(&annotations)?.as_slice())?;
+            annotations
+        };
+        txn.commit()?;
+        Ok(annotations)
+    }
+
+    /// Drop every note on a message; returns how many there were
+    pub fn clear_annotations(&self, uuid: &str) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let db = open_database(&self.path)?;
+        let txn = db.begin_write()?;
+        let removed = match txn.open_table(ANNOTATIONS)?.remove(uuid)? {
+            Some(value) => serde_json::from_slice::<Vec<Annotation>>(value.value())?.len(),
+            None => 0,
+        };
+        txn.commit()?;
+        Ok(removed)
+    }
+
+    /// Notes shown with each message: its bookmark note, then its
+    /// annotations oldest first
+    pub fn notes(&self) -> Result<HashMap<String, Vec<String>>> {
+        let mut notes: HashMap<String, Vec<String>> = HashMap::new();
+        for bookmark in self.list(None)? {
+            if let Some(note) = bookmark.note {
+                notes.entry(bookmark.uuid).or_default().push(note);
+            }
+        }
+        if !self.path.exists() {
+            return Ok(notes);
+        }
+        let db = open_database(&self.path)?;
+        let txn = db.begin_read()?;
+        let table = match txn.open_table(ANNOTATIONS) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(notes),
+            Err(e) => return Err(e.into()),
+        };
+        for row in table.iter()? {
+            let (uuid, value) = row?;
+            let annotations: Vec<Annotation> = serde_json::from_slice(value.value())?;
+            notes
+                .entry(uuid.value().to_string())
+                .or_default()
+                .extend(annotations.into_iter().map(|a| a.text));
+        }
+        Ok(notes)
+    }
+
+    /// Bookmarks, newest first, optionally only those with `tag`
+    pub fn list(&self, tag: Option<&str>) -> Result<Vec<Bookmark>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let db = open_database(&self.path)?;
+        let txn = db.begin_read()?;
+        let table = match txn.open_table(BOOKMARKS) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut bookmarks = Vec::new();
+        for row in table.iter()? {
+            let (uuid, value) = row?;
+            let record: BookmarkRecord = serde_json::from_slice(value.value())?;
+            if tag.is_none_or(|tag| record.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))) {
+                bookmarks.push(Bookmark::from_record(uuid.value(), record));
+            }
+        }
+        bookmarks.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(bookmarks)
+    }
+}
+
+/// Set `notes` on messages that have any in the default store
+pub fn attach_notes<'a>(messages: impl IntoIterator<Item = &'a mut SearchResult>) -> Result<()> {
+    let notes = BookmarkStore::open_default()?.notes()?;
+    if notes.is_empty() {
+        return Ok(());
+    }
+    for message in messages {
+        if let Some(texts) = notes.get(&message.uuid) {
+            message.notes = texts.clone();
+        }
+    }
+    Ok(())
+}
+
+/// Lines confirming a message's annotations:
+/// 📝 1a2b3c4d: 2 notes
+///    - first note
+pub fn format_annotations(uuid: &str, annotations: &[Annotation]) -> String {
+    let mut output = format!(
+        "📝 {}: {} note{}\n",
+        short_uuid(uuid),
+        annotations.len(),
+        if annotations.len() == 1 { "" } else { "s" }
+    );
+    for annotation in annotations {
+        output.push_str(&format!("   - {}\n", annotation.text));
+    }
+    output
+}
+
+/// Split `bookmarked:true` off a search query: the query to run, and the
+/// uuids of bookmarked messages to limit it to when the term was there
+pub fn bookmark_filter(query: &str) -> Result<(String, Option<Vec<String>>)> {
+    let (text, bookmarked) = split_bookmarked(query)?;
+    if !bookmarked {
+        return Ok((text, None));
+    }
+    let uuids = BookmarkStore::open_default()?
+        .list(None)?
+        .into_iter()
+        .map(|b| b.uuid)
+        .collect();
+    Ok((text, Some(uuids)))
+}
+
+/// One line confirming a bookmark:
+/// 🔖 1a2b3c4d [canonical, k8s] 📝 note
+pub fn format_bookmark(bookmark: &Bookmark) -> String {
+    let mut line = format!("🔖 {}", short_uuid(&bookmark.uuid));
+    if !bookmark.tags.is_empty() {
+        line.push_str(&format!(" [{}]", bookmark.tags.join(", ")));
+    }
+    if let Some(note) = &bookmark.note {
+        line.push_str(&format!(" 📝 {}", note));
+    }
+    line.push('\n');
+    line
+}
+
+/// Bookmarks with the message each one marks, looked up in the index:
+/// N. 🔖 [tags] 💬 msg 📁 project 🗒️ session 📅 date
+///    📝 note
+///    snippet
+pub fn format_bookmark_list(engine: &SearchEngine, bookmarks: &[Bookmark]) -> Result<String> {
+    if bookmarks.is_empty() {
+        return Ok("No bookmarks\n".to_string());
+    }
+    let uuids: Vec<String> = bookmarks.iter().map(|b| b.uuid.clone()).collect();
+    let messages: HashMap<String, SearchResult> = engine
+        .get_messages_by_uuid(&uuids)?
+        .into_iter()
+        .map(|m| (m.uuid.clone(), m))
+        .collect();
+
+    let mut output = String::new();
+    for (i, bookmark) in bookmarks.iter().enumerate() {
+        let tags = if bookmark.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", bookmark.tags.join(", "))
+        };
+        output.push_str(&format!(
+            "{}. 🔖{} 💬 {}",
+            i + 1,
+            tags,
+            short_uuid(&bookmark.uuid)
+        ));
+        match messages.get(&bookmark.uuid) {
+            Some(message) => output.push_str(&format!(
+                " 📁 {} 🗒️ {} 📅 {}\n",
+                message.project,
+                short_uuid(&message.session_id),
+                local_time(message.timestamp).format("%Y-%m-%d %H:%M")
+            )),
+            None => output.push_str(" (no longer in the index)\n"),
+        }
+        if let Some(note) = &bookmark.note {
+            output.push_str(&format!("   📝 {}\n", note));
+        }
+        if let Some(message) = messages.get(&bookmark.uuid) {
+            output.push_str(&format!("   {}\n", message.snippet));
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bookmark_merges_tags_and_replaces_note() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = BookmarkStore::new(temp_dir.path());
+        assert!(store.list(None).unwrap().is_empty());
+        assert!(!store.remove("m1").unwrap());
+
+        store
+            .bookmark("m1", &["canonical".to_string()], Some("use this"))
+            .unwrap();
+        let updated = store
+            .bookmark("m1", &["k8s".to_string(), "canonical".to_string()], None)
+            .unwrap();
+        assert_eq!(updated.tags, vec!["canonical", "k8s"]);
+        assert_eq!(updated.note.as_deref(), Some("use this"));
+        store.bookmark("m2", &[], Some("")).unwrap();
+
+        assert_eq!(store.list(None).unwrap().len(), 2);
+        let tagged = store.list(Some("K8S")).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].uuid, "m1");
+        assert_eq!(store.list(Some("m2")).unwrap(), vec![]);
+
+        assert!(store.remove("m1").unwrap());
+        assert_eq!(store.list(None).unwrap()[0].note, None);
+    }
+
+    #[test]
+    fn test_notes_combine_bookmark_note_and_annotations() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = BookmarkStore::new(temp_dir.path());
+        assert!(store.notes().unwrap().is_empty());
+        assert_eq!(store.clear_annotations("m1").unwrap(), 0);
+        assert!(store.annotate("m1", "  ").is_err());
+
+        store.annotate("m1", "first").unwrap();
+        assert_eq!(store.annotate("m1", "second").unwrap().len(), 2);
+        store.bookmark("m1", &[], Some("canonical")).unwrap();
+        store.annotate("m2", "other").unwrap();
+
+        let notes = store.notes().unwrap();
+        assert_eq!(notes["m1"], vec!["canonical", "first", "second"]);
+        assert_eq!(notes["m2"], vec!["other"]);
+
+        assert_eq!(store.clear_annotations("m1").unwrap(), 2);
+        assert_eq!(store.notes().unwrap()["m1"], vec!["canonical"]);
+    }
+}
@@ -1,23 +1,41 @@
-use super::indexer::SearchIndexer;
+use super::agents::{CODEX_SOURCE, CodexParser, GEMINI_SOURCE, GeminiParser};
+use super::config::get_config;
+use super::indexer::{SCHEMA_VERSION, SearchIndexer};
+use super::lock::ExclusiveIndexAccess;
+use super::metadata::metadata_config_digest;
 use super::parser::JsonlParser;
-use super::utils::file_mtime;
+use super::search::SearchEngine;
+use super::session_store::SessionStore;
+use super::timezone::local_time;
+use super::transcripts::{TRANSCRIPT_SOURCE, parse_transcript_file};
+use super::utils::{
+    file_mtime, history_source_of, is_excluded_from_index, source_host_for, source_root_for,
+};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tracing::{debug, info, warn};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CacheMetadata {
     pub indexed_files: HashMap<PathBuf, FileMetadata>,
     pub last_full_scan: Option<DateTime<Utc>>,
+    /// `SCHEMA_VERSION` of the last indexing run
     pub index_version: u32,
+    /// `metadata_config_digest` of the patterns and extractors it tagged messages with
+    #[serde(default)]
+    pub metadata_digest: String,
     pub total_entries: u64,
     /// Cached message counts per session (user + assistant messages only)
     #[serde(default)]
     pub session_counts: HashMap<String, usize>,
+    /// Last time each session was fetched by a retrieval tool
+    #[serde(default)]
+    pub session_access: HashMap<String, DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,16 +48,34 @@ pub struct FileMetadata {
     pub entry_count: usize,
 }
 
+/// Indexing progress: files walked (indexed or skipped) and entries added so far
+#[derive(Debug, Clone, Copy)]
+pub struct IndexProgress {
+    pub files_done: usize,
+    pub files_total: usize,
+    pub entries_indexed: usize,
+}
+
 pub struct CacheManager {
     cache_dir: PathBuf,
     metadata_file: PathBuf,
     metadata: CacheMetadata,
+    /// Modification time of the metadata file when last read or written
+    loaded_mtime: Option<SystemTime>,
+    /// Access times recorded while the index was locked, not yet saved
+    unsaved_access: HashMap<String, DateTime<Utc>>,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
 }
 
 impl CacheManager {
     pub fn new(cache_dir: &Path) -> Result<Self> {
         let metadata_file = cache_dir.join("cache-metadata.json");
 
+        // Taken before reading, so a concurrent write shows up as a change
+        let loaded_mtime = modified_time(&metadata_file);
         let metadata = if metadata_file.exists() {
             let content = fs::read_to_string(&metadata_file)?;
             serde_json::from_str(&content).unwrap_or_default()
@@ -51,9 +87,24 @@ impl CacheManager {
             cache_dir: cache_dir.to_path_buf(),
             metadata_file,
             metadata,
+            loaded_mtime,
+            unsaved_access: HashMap::new(),
         })
     }
 
+    /// Re-read the metadata if another process wrote it since it was loaded.
+    /// Returns true when it did.
+    pub fn reload_if_changed(&mut self) -> Result<bool> {
+        if modified_time(&self.metadata_file) == self.loaded_mtime {
+            return Ok(false);
+        }
+        let unsaved_access = std::mem::take(&mut self.unsaved_access);
+        *self = Self::new(&self.cache_dir)?;
+        self.metadata.session_access.extend(unsaved_access.clone());
+        self.unsaved_access = unsaved_access;
+        Ok(true)
+    }
+
     pub fn needs_indexing(&self, file_path: &Path) -> Result<bool> {
         let file_size = fs::metadata(file_path)?.len();
         let file_modified = file_mtime(file_path)?;
@@ -71,13 +122,35 @@ impl CacheManager {
         &mut self,
         indexer: &mut SearchIndexer,
         files: Vec<PathBuf>,
+    ) -> Result<()> {
+        self.update_incremental_with_progress(indexer, files, |_| {})
+    }
+
+    /// Like `update_incremental`, reporting progress before each file and once at the end
+    pub fn update_incremental_with_progress(
+        &mut self,
+        indexer: &mut SearchIndexer,
+        files: Vec<PathBuf>,
+        mut on_progress: impl FnMut(IndexProgress),
     ) -> Result<()> {
         use super::models::MessageType;
         let parser = JsonlParser;
         let mut files_processed = 0;
         let mut total_entries = 0;
+        let files_total = files.len();
+        let mut touched_sessions = HashSet::new();
+        // Deletes of files that now yield no entries, committed at the end
+        let mut uncommitted_deletes = false;
+
+        self.purge_excluded(indexer)?;
+
+        for (files_done, file_path) in files.into_iter().enumerate() {
+            on_progress(IndexProgress {
+                files_done,
+                files_total,
+                entries_indexed: total_entries,
+            });
 
-        for file_path in files {
             if !file_path.exists() {
                 // Remove from cache if file was deleted
                 if self.metadata.indexed_files.remove(&file_path).is_some() {
@@ -94,36 +167,60 @@ impl CacheManager {
             info!("Processing: {}", file_path.display());
 
             // Parse and index the file
-            match parser.parse_file(&file_path) {
-                Ok(entries) => {
+            let parsed = match history_source_of(&file_path) {
+                CODEX_SOURCE => CodexParser.parse_file(&file_path),
+                GEMINI_SOURCE => GeminiParser.parse_file(&file_path),
+                TRANSCRIPT_SOURCE => parse_transcript_file(&file_path),
+                _ => parser.parse_file(&file_path),
+            };
+            match parsed {
+                Ok(mut entries) => {
+                    let host = source_host_for(&file_path);
+                    let root =
+                        source_root_for(&file_path).map(|r| r.to_string_lossy().into_owned());
+                    for entry in &mut entries {
+                        entry.source_host = host.clone();
+                        entry.source_root = root.clone();
+                    }
+
                     let entry_count = entries.len();
-                    total_entries += entry_count;
 
+                    // Replace what this file contributed before
+                    indexer.delete_file(&file_path)?;
                     if entry_count > 0 {
-                        // Delete old documents for this session before re-indexing
-                        if let Some(first) = entries.first() {
-                            indexer.delete_session(&first.session_id)?;
-                            // Clear old session count before recount
-                            self.metadata.session_counts.remove(&first.session_id);
-                        }
-
-                        // Count user/assistant messages per session
+                        // Count user/assistant messages per session; an
+                        // export holds many sessions
+                        let mut counts: HashMap<String, usize> = HashMap::new();
                         for entry in &entries {
+                            let count = counts.entry(entry.session_id.clone()).or_insert(0);
                             if matches!(
                                 entry.message_type,
                                 MessageType::User | MessageType::Assistant
                             ) {
-                                *self
-                                    .metadata
-                                    .session_counts
-                                    .entry(entry.session_id.clone())
-                                    .or_insert(0) += 1;
+                                *count += 1;
                             }
                         }
 
-                        indexer.index_conversations(entries)?;
+                        // Left unrecorded, so the next run tries it again
+                        if let Err(e) = indexer.index_conversations(entries) {
+                            warn!("Failed to index {}: {}", file_path.display(), e);
+                            uncommitted_deletes = true;
+                            continue;
+                        }
                         info!("  Indexed {} entries", entry_count);
+                        uncommitted_deletes = false;
+                        for (session_id, count) in counts {
+                            touched_sessions.insert(session_id.clone());
+                            if count > 0 {
+                                self.metadata.session_counts.insert(session_id, count);
+                            } else {
+                                self.metadata.session_counts.remove(&session_id);
+                            }
+                        }
+                    } else {
+                        uncommitted_deletes = true;
                     }
+                    total_entries += entry_count;
 
                     // Update cache metadata
                     let file_size = fs::metadata(&file_path)?.len();
@@ -148,9 +245,21 @@ impl CacheManager {
             }
         }
 
+        if uncommitted_deletes {
+            indexer.commit()?;
+        }
         self.metadata.total_entries += total_entries as u64;
         self.metadata.last_full_scan = Some(Utc::now());
+        self.metadata.index_version = SCHEMA_VERSION;
+        self.metadata.metadata_digest = metadata_config_digest(&get_config().metadata);
         self.save_metadata()?;
+        let touched_sessions: Vec<String> = touched_sessions.into_iter().collect();
+        self.refresh_session_store(&touched_sessions);
+        on_progress(IndexProgress {
+            files_done: files_total,
+            files_total,
+            entries_indexed: total_entries,
+        });
 
         if files_processed > 0 {
             info!(
@@ -164,6 +273,117 @@ impl CacheManager {
         Ok(())
     }
 
+    /// Bring the session store in line with the index: the sessions just
+    /// reindexed, or all of them when there is no current store
+    fn refresh_session_store(&self, touched_sessions: &[String]) {
+        let store = SessionStore::new(&self.cache_dir);
+        if store.is_current() && touched_sessions.is_empty() {
+            return;
+        }
+        let result = SearchEngine::new(&self.cache_dir, HashMap::new()).and_then(|engine| {
+            let session_ids = store.is_current().then_some(touched_sessions);
+            store.refresh(&engine, session_ids)
+        });
+        if let Err(e) = result {
+            warn!("Failed to update session store: {}", e);
+            store.mark_stale();
+        }
+    }
+
+    fn forget_in_session_store(&self, session_ids: &[String]) {
+        let store = SessionStore::new(&self.cache_dir);
+        if let Err(e) = store.remove(session_ids) {
+            warn!("Failed to update session store: {}", e);
+            store.mark_stale();
+        }
+    }
+
+    /// Drop already-indexed files that privacy excludes now cover
+    fn purge_excluded(&mut self, indexer: &mut SearchIndexer) -> Result<()> {
+        self.purge_files(indexer, is_excluded_from_index)
+    }
+
+    /// Drop the indexed files matching `excluded` from the index and the
+    /// metadata. Every file's documents go by path, whatever it is named
+    /// (subagent, Codex, Gemini and transcript files aren't named after a
+    /// session); a main session file named after its session ID also takes
+    /// the session's stored summary with it.
+    fn purge_files(
+        &mut self,
+        indexer: &mut SearchIndexer,
+        excluded: impl Fn(&Path) -> bool,
+    ) -> Result<()> {
+        let excluded: Vec<PathBuf> = self
+            .metadata
+            .indexed_files
+            .keys()
+            .filter(|path| excluded(path))
+            .cloned()
+            .collect();
+        if excluded.is_empty() {
+            return Ok(());
+        }
+
+        let mut excluded_sessions = Vec::new();
+        for path in &excluded {
+            if let Some(entry) = self.metadata.indexed_files.remove(path) {
+                self.metadata.total_entries = self
+                    .metadata
+                    .total_entries
+                    .saturating_sub(entry.entry_count as u64);
+            }
+            indexer.delete_file(path)?;
+            if let Some(session_id) = path.file_stem().and_then(|s| s.to_str())
+                && uuid::Uuid::parse_str(session_id).is_ok()
+            {
+                indexer.delete_session(session_id)?;
+                self.metadata.session_counts.remove(session_id);
+                self.metadata.session_access.remove(session_id);
+                excluded_sessions.push(session_id.to_string());
+            }
+        }
+        indexer.commit()?;
+        self.forget_in_session_store(&excluded_sessions);
+        info!("Removed {} excluded files from the index", excluded.len());
+        Ok(())
+    }
+
+    /// Re-root indexed file paths (index moved from a machine with another
+    /// Claude dir). Returns the (old, new) paths rewritten; their documents
+    /// still name the old path until reindexed.
+    pub fn rebase_paths(&mut self, from: &Path, to: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut rewritten = Vec::new();
+        self.metadata.indexed_files = std::mem::take(&mut self.metadata.indexed_files)
+            .into_iter()
+            .map(|(path, meta)| match path.strip_prefix(from) {
+                Ok(relative) => {
+                    let new_path = to.join(relative);
+                    rewritten.push((path, new_path.clone()));
+                    (new_path, meta)
+                }
+                Err(_) => (path, meta),
+            })
+            .collect();
+        self.save_metadata()?;
+        Ok(rewritten)
+    }
+
+    /// Mark files as not yet indexed, so the next update reparses them.
+    /// Returns how many were known.
+    pub fn forget_files(&mut self, files: &[PathBuf]) -> usize {
+        let mut forgotten = 0;
+        for path in files {
+            if let Some(entry) = self.metadata.indexed_files.remove(path) {
+                self.metadata.total_entries = self
+                    .metadata
+                    .total_entries
+                    .saturating_sub(entry.entry_count as u64);
+                forgotten += 1;
+            }
+        }
+        forgotten
+    }
+
     pub fn clear_cache(&mut self) -> Result<()> {
         if self.cache_dir.exists() {
             fs::remove_dir_all(&self.cache_dir)?;
@@ -185,25 +405,148 @@ impl CacheManager {
         )
     }
 
+    /// Entries the last indexing of `path` produced, if it was indexed
+    pub fn indexed_entry_count(&self, path: &Path) -> Option<usize> {
+        self.metadata
+            .indexed_files
+            .get(path)
+            .map(|meta| meta.entry_count)
+    }
+
+    /// Schema version the index was last written with, 0 before versions were recorded
+    pub fn index_version(&self) -> u32 {
+        self.metadata.index_version
+    }
+
+    pub fn metadata_digest(&self) -> &str {
+        &self.metadata.metadata_digest
+    }
+
     /// Get cached session interaction counts
     pub fn get_session_counts(&self) -> &HashMap<String, usize> {
         &self.metadata.session_counts
     }
 
+    /// Get last-retrieved timestamps per session
+    pub fn get_session_access(&self) -> &HashMap<String, DateTime<Utc>> {
+        &self.metadata.session_access
+    }
+
+    /// Take over the access times of the index being rebuilt
+    pub fn restore_session_access(&mut self, session_access: HashMap<String, DateTime<Utc>>) {
+        self.metadata.session_access = session_access;
+    }
+
+    /// Record that sessions were retrieved now (for listing and prune
+    /// policies). Other processes write the metadata file too, so only the
+    /// access times are written, onto its latest copy and under the index
+    /// lock; while indexing holds the lock they wait for the next save.
+    pub fn record_session_access<'a>(
+        &mut self,
+        session_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<()> {
+        let now = Utc::now();
+        for session_id in session_ids {
+            self.metadata
+                .session_access
+                .insert(session_id.to_string(), now);
+            self.unsaved_access.insert(session_id.to_string(), now);
+        }
+        let Ok(_lock) = ExclusiveIndexAccess::acquire() else {
+            debug!("Index busy, session access saved later");
+            return Ok(());
+        };
+        let up_to_date = modified_time(&self.metadata_file) == self.loaded_mtime;
+        let mut latest = Self::new(&self.cache_dir)?;
+        latest
+            .metadata
+            .session_access
+            .extend(self.unsaved_access.clone());
+        latest.save_metadata()?;
+        self.unsaved_access.clear();
+        if up_to_date {
+            // Nothing else changed, so this copy matches the file again
+            self.loaded_mtime = latest.loaded_mtime;
+        }
+        Ok(())
+    }
+
+    /// Remove sessions from the index. File metadata is kept so unchanged
+    /// files are not picked up again by incremental indexing.
+    pub fn prune_sessions(
+        &mut self,
+        indexer: &mut SearchIndexer,
+        session_ids: &[String],
+    ) -> Result<()> {
+        for session_id in session_ids {
+            indexer.delete_session(session_id)?;
+            self.metadata.session_counts.remove(session_id);
+            self.metadata.session_access.remove(session_id);
+        }
+        indexer.commit()?;
+        self.forget_in_session_store(session_ids);
+        self.save_metadata()
+    }
+
     pub fn get_stats(&self) -> CacheStats {
+        let cache_size_mb = self.calculate_cache_size_mb();
+        let segments = self.get_segment_stats().unwrap_or_else(|e| {
+            debug!("Segment stats unavailable: {}", e);
+            Vec::new()
+        });
+        let index_bytes: u64 = segments.iter().map(|s| s.bytes).sum();
+        let mut projects = self.get_project_stats();
+        let project_entries: u64 = projects.iter().map(|p| p.entries).sum();
+        for project in &mut projects {
+            project.estimated_index_bytes = (index_bytes as f64 * project.entries as f64
+                / project_entries.max(1) as f64) as u64;
+        }
         CacheStats {
             total_files: self.metadata.indexed_files.len(),
             total_entries: self.metadata.total_entries,
             last_updated: self.metadata.last_full_scan,
-            cache_size_mb: self.calculate_cache_size_mb(),
-            projects: self.get_project_stats(),
+            cache_size_mb,
+            index_size_mb: index_bytes as f64 / (1024.0 * 1024.0),
+            jsonl_size_mb: self
+                .metadata
+                .indexed_files
+                .values()
+                .map(|f| f.size)
+                .sum::<u64>() as f64
+                / (1024.0 * 1024.0),
+            segments,
+            projects,
         }
     }
 
-    fn save_metadata(&self) -> Result<()> {
+    /// Live segments of the index with their document counts and file sizes
+    fn get_segment_stats(&self) -> Result<Vec<SegmentStats>> {
+        let index = tantivy::Index::open_in_dir(&self.cache_dir)?;
+        let mut segments: Vec<SegmentStats> = index
+            .searchable_segment_metas()?
+            .into_iter()
+            .map(|meta| SegmentStats {
+                id: meta.id().short_uuid_string(),
+                docs: meta.num_docs(),
+                deleted_docs: meta.num_deleted_docs(),
+                bytes: meta
+                    .list_files()
+                    .iter()
+                    .filter_map(|file| fs::metadata(self.cache_dir.join(file)).ok())
+                    .map(|metadata| metadata.len())
+                    .sum(),
+            })
+            .collect();
+        segments.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+        Ok(segments)
+    }
+
+    fn save_metadata(&mut self) -> Result<()> {
         fs::create_dir_all(&self.cache_dir)?;
         let content = serde_json::to_string_pretty(&self.metadata)?;
         fs::write(&self.metadata_file, content)?;
+        self.loaded_mtime = modified_time(&self.metadata_file);
+        self.unsaved_access.clear();
         Ok(())
     }
 
@@ -234,11 +577,14 @@ impl CacheManager {
                             name: project_name.to_string(),
                             files: 0,
                             entries: 0,
+                            jsonl_bytes: 0,
+                            estimated_index_bytes: 0,
                             last_updated: file_meta.indexed_at,
                         });
 
                 stats.files += 1;
                 stats.entries += file_meta.entry_count as u64;
+                stats.jsonl_bytes += file_meta.size;
                 if file_meta.indexed_at > stats.last_updated {
                     stats.last_updated = file_meta.indexed_at;
                 }
@@ -246,7 +592,7 @@ impl CacheManager {
         }
 
         let mut project_list: Vec<ProjectStats> = projects.into_values().collect();
-        project_list.sort_by(|a, b| b.last_updated.cmp(&a.last_updated));
+        project_list.sort_by_key(|b| std::cmp::Reverse(b.last_updated));
         project_list
     }
 }
@@ -257,14 +603,31 @@ pub struct CacheStats {
     pub total_entries: u64,
     pub last_updated: Option<DateTime<Utc>>,
     pub cache_size_mb: f64,
+    /// Segment files only, without the session store and metadata
+    pub index_size_mb: f64,
+    /// Size of the indexed JSONL files when they were last indexed
+    pub jsonl_size_mb: f64,
+    /// Largest first
+    pub segments: Vec<SegmentStats>,
     pub projects: Vec<ProjectStats>,
 }
 
+#[derive(Debug, Clone)]
+pub struct SegmentStats {
+    pub id: String,
+    pub docs: u32,
+    pub deleted_docs: u32,
+    pub bytes: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct ProjectStats {
     pub name: String,
     pub files: usize,
     pub entries: u64,
+    pub jsonl_bytes: u64,
+    /// Share of the segment bytes in proportion to this project's entries
+    pub estimated_index_bytes: u64,
     pub last_updated: DateTime<Utc>,
 }
 
@@ -289,21 +652,21 @@ pub enum IndexHealthStatus {
 
 impl CacheManager {
     /// Quick health check - just counts stale/new files without full scan
-    /// Returns (stale_count, new_count) for passive reporting
+    /// Returns (stale_count, new_count) of the given files for passive reporting
     pub fn quick_health_check(&self, all_jsonl_files: &[PathBuf]) -> (usize, usize) {
         let mut stale = 0;
         let mut new_files = 0;
-        for (path, meta) in &self.metadata.indexed_files {
-            if let Ok(current_mtime) = file_mtime(path) {
-                let current_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-                if current_size != meta.size || current_mtime != meta.modified {
-                    stale += 1;
-                }
-            }
-        }
         for path in all_jsonl_files {
-            if !self.metadata.indexed_files.contains_key(path) {
-                new_files += 1;
+            match self.metadata.indexed_files.get(path) {
+                Some(meta) => {
+                    if let Ok(current_mtime) = file_mtime(path) {
+                        let current_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                        if current_size != meta.size || current_mtime != meta.modified {
+                            stale += 1;
+                        }
+                    }
+                }
+                None => new_files += 1,
             }
         }
         (stale, new_files)
@@ -365,7 +728,11 @@ impl std::fmt::Display for IndexHealth {
             self.total_indexed_files, self.total_entries
         )?;
         if let Some(last) = self.last_indexed {
-            writeln!(f, "Last indexed: {}", last.format("%Y-%m-%d %H:%M:%S UTC"))?;
+            writeln!(
+                f,
+                "Last indexed: {}",
+                local_time(last).format("%Y-%m-%d %H:%M:%S %:z")
+            )?;
         }
         writeln!(
             f,
@@ -382,3 +749,80 @@ impl std::fmt::Display for IndexHealth {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::SearchQuery;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_session_access_keeps_what_others_wrote() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index");
+        let history = temp_dir
+            .path()
+            .join("aaaaaaaa-1111-2222-3333-444444444444.jsonl");
+        fs::write(
+            &history,
+            r#"{"uuid":"u1","sessionId":"aaaaaaaa-1111-2222-3333-444444444444","type":"user","timestamp":"2026-10-01T10:00:00Z","cwd":"/tmp/p","message":{"role":"user","content":"hello"}}"#,
+        )
+        .unwrap();
+
+        // Loaded before another process indexes
+        let mut server_cache = CacheManager::new(&index_path).unwrap();
+        let mut indexer = SearchIndexer::new(&index_path).unwrap();
+        CacheManager::new(&index_path)
+            .unwrap()
+            .update_incremental(&mut indexer, vec![history.clone()])
+            .unwrap();
+
+        server_cache.record_session_access(["opened"]).unwrap();
+        assert!(server_cache.get_session_access().contains_key("opened"));
+        let latest = CacheManager::new(&index_path).unwrap();
+        assert!(
+            !latest.needs_indexing(&history).unwrap(),
+            "The other process's indexing is kept"
+        );
+    }
+
+    #[test]
+    fn test_excluded_files_leave_the_index_whatever_their_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index");
+        let history = temp_dir.path().join("agent-1a2b3c.jsonl");
+        fs::write(
+            &history,
+            r#"{"uuid":"u1","sessionId":"aaaaaaaa-1111-2222-3333-444444444444","type":"user","timestamp":"2026-10-01T10:00:00Z","cwd":"/tmp/p","message":{"role":"user","content":"confidential merger plans"}}"#,
+        )
+        .unwrap();
+
+        let search = |cache: &CacheManager| {
+            let engine =
+                SearchEngine::new(&index_path, cache.get_session_counts().clone()).unwrap();
+            engine
+                .search(SearchQuery {
+                    text: "merger".to_string(),
+                    limit: 10,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+
+        let mut indexer = SearchIndexer::new(&index_path).unwrap();
+        let mut cache = CacheManager::new(&index_path).unwrap();
+        cache
+            .update_incremental(&mut indexer, vec![history.clone()])
+            .unwrap();
+        assert_eq!(search(&cache).len(), 1);
+
+        cache
+            .purge_files(&mut indexer, |path| path == history)
+            .unwrap();
+        assert!(
+            search(&cache).is_empty(),
+            "Excluded agent file still searchable"
+        );
+        assert_eq!(cache.indexed_entry_count(&history), None);
+    }
+}
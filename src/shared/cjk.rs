@@ -0,0 +1,128 @@
+use tantivy::tokenizer::{Token, TokenStream, Tokenizer};
+
+/// Han, kana and Hangul: scripts written without spaces between words
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'      // Hangul Jamo
+        | '\u{3040}'..='\u{30FF}'    // Hiragana, Katakana
+        | '\u{3130}'..='\u{318F}'    // Hangul Compatibility Jamo
+        | '\u{31F0}'..='\u{31FF}'    // Katakana Phonetic Extensions
+        | '\u{3400}'..='\u{4DBF}'    // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'    // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'    // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}'    // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}'    // Halfwidth Katakana
+        | '\u{20000}'..='\u{2A6DF}' // CJK Extension B
+    )
+}
+
+/// Splits alphanumeric runs like the default tokenizer, and CJK runs into
+/// overlapping character pairs ("日本語" -> "日本", "本語"), so any word of
+/// two characters or more is found without a dictionary. A lone CJK
+/// character is its own token.
+#[derive(Clone, Default)]
+pub struct CjkBigramTokenizer {
+    token: Token,
+}
+
+pub struct CjkBigramTokenStream<'a> {
+    tokens: std::vec::IntoIter<Token>,
+    token: &'a mut Token,
+}
+
+fn push_token(tokens: &mut Vec<Token>, text: &str, from: usize, to: usize) {
+    tokens.push(Token {
+        offset_from: from,
+        offset_to: to,
+        position: tokens.len(),
+        text: text[from..to].to_string(),
+        position_length: 1,
+    });
+}
+
+fn bigram_tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if is_cjk(c) {
+            let mut run = vec![(start, c.len_utf8())];
+            while let Some(&(i, next)) = chars.peek() {
+                if !is_cjk(next) {
+                    break;
+                }
+                run.push((i, next.len_utf8()));
+                chars.next();
+            }
+            if run.len() == 1 {
+                push_token(&mut tokens, text, start, start + c.len_utf8());
+            }
+            for pair in run.windows(2) {
+                push_token(&mut tokens, text, pair[0].0, pair[1].0 + pair[1].1);
+            }
+        } else if c.is_alphanumeric() {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, next)) = chars.peek() {
+                if !next.is_alphanumeric() || is_cjk(next) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            push_token(&mut tokens, text, start, end);
+        }
+    }
+    tokens
+}
+
+impl Tokenizer for CjkBigramTokenizer {
+    type TokenStream<'a> = CjkBigramTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CjkBigramTokenStream<'a> {
+        self.token.reset();
+        CjkBigramTokenStream {
+            tokens: bigram_tokens(text).into_iter(),
+            token: &mut self.token,
+        }
+    }
+}
+
+impl TokenStream for CjkBigramTokenStream<'_> {
+    fn advance(&mut self) -> bool {
+        match self.tokens.next() {
+            Some(token) => {
+                *self.token = token;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn token(&self) -> &Token {
+        self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cjk_runs_become_bigrams() {
+        let mut tokenizer = CjkBigramTokenizer::default();
+        let mut stream = tokenizer.token_stream("Rustで日本語を検索 v2 中");
+        let mut words = Vec::new();
+        while stream.advance() {
+            words.push(stream.token().text.clone());
+        }
+        assert_eq!(
+            words,
+            [
+                "Rust", "で日", "日本", "本語", "語を", "を検", "検索", "v2", "中"
+            ]
+        );
+    }
+}
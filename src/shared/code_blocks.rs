@@ -0,0 +1,211 @@
+use super::models::{SearchQuery, SearchResult};
+use super::path_utils::{session_jsonl_path, short_uuid};
+use super::search::SearchEngine;
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+use super::utils::truncate_content;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// Characters of the prompt shown above each block
+const PROMPT_MAX_CHARS: usize = 200;
+
+/// A fenced code block from message content
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// Info string after the opening fence, if any (e.g. "rust")
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// A code block with the message it came from and the prompt that led to it
+#[derive(Debug, Clone)]
+pub struct CodeSnippet {
+    pub message: SearchResult,
+    pub prompt: Option<String>,
+    pub block: CodeBlock,
+}
+
+/// Fenced blocks in order; an unclosed fence (truncated content) runs to the end
+pub fn extract_code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(Option<String>, Vec<&str>)> = None;
+    for line in content.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut current, fence) {
+            (None, Some(info)) => {
+                let language = info.split_whitespace().next().map(str::to_string);
+                current = Some((language, Vec::new()));
+            }
+            (Some(_), Some(rest)) if rest.trim().is_empty() => {
+                let (language, lines) = current.take().unwrap_or_default();
+                blocks.push(CodeBlock {
+                    language,
+                    code: lines.join("\n"),
+                });
+            }
+            (Some((_, lines)), _) => lines.push(line),
+            (None, None) => {}
+        }
+    }
+    if let Some((language, lines)) = current {
+        blocks.push(CodeBlock {
+            language,
+            code: lines.join("\n"),
+        });
+    }
+    blocks
+}
+
+fn language_matches(block: &CodeBlock, language: Option<&str>) -> bool {
+    match language {
+        Some(wanted) => block
+            .language
+            .as_deref()
+            .is_some_and(|lang| lang.eq_ignore_ascii_case(wanted)),
+        None => true,
+    }
+}
+
+/// Code blocks in a session's messages (ordered by sequence), each with the
+/// closest earlier prompt. `only` restricts to these message uuids.
+fn snippets_from_session(
+    messages: Vec<SearchResult>,
+    only: Option<&HashSet<String>>,
+    language: Option<&str>,
+) -> Vec<CodeSnippet> {
+    let mut snippets = Vec::new();
+    let mut prompt: Option<String> = None;
+    for message in messages {
+        if only.is_none_or(|uuids| uuids.contains(&message.uuid)) {
+            for block in extract_code_blocks(&message.content) {
+                if language_matches(&block, language) {
+                    snippets.push(CodeSnippet {
+                        message: message.clone(),
+                        prompt: prompt.clone(),
+                        block,
+                    });
+                }
+            }
+        }
+        if message.is_prompt() {
+            prompt = Some(message.content);
+        }
+    }
+    snippets
+}
+
+/// All code blocks written in a session
+pub fn session_code_snippets(
+    engine: &SearchEngine,
+    session_id: &str,
+    language: Option<&str>,
+) -> Result<Vec<CodeSnippet>> {
+    let messages = engine.get_session_messages(session_id)?;
+    Ok(snippets_from_session(messages, None, language))
+}
+
+/// Code blocks in messages matching `query`, in match order
+pub fn search_code_snippets(
+    engine: &SearchEngine,
+    mut query: SearchQuery,
+    language: Option<&str>,
+) -> Result<Vec<CodeSnippet>> {
+    // Only messages with code can contribute
+    let limit = query.limit;
+    query.limit = limit * 3;
+    let matches: Vec<_> = engine
+        .search(query)?
+        .into_iter()
+        .filter(|m| m.has_code)
+        .take(limit)
+        .collect();
+
+    let mut by_session: HashMap<&str, HashSet<String>> = HashMap::new();
+    for m in &matches {
+        by_session
+            .entry(m.session_id.as_str())
+            .or_default()
+            .insert(m.uuid.clone());
+    }
+
+    let mut by_uuid: HashMap<String, Vec<CodeSnippet>> = HashMap::new();
+    for (session_id, uuids) in &by_session {
+        let messages = engine.messages_in_session(session_id)?;
+        for snippet in snippets_from_session(messages, Some(uuids), language) {
+            by_uuid
+                .entry(snippet.message.uuid.clone())
+                .or_default()
+                .push(snippet);
+        }
+    }
+
+    Ok(matches
+        .iter()
+        .flat_map(|m| by_uuid.remove(&m.uuid).unwrap_or_default())
+        .collect())
+}
+
+/// Format snippets, shared by CLI and MCP:
+/// N. 🗒️ session 💬 msg 📅 date 🏷️ lang
+///    ❓ prompt
+/// ```lang
+/// code
+/// ```
+pub fn format_code_snippets(snippets: &[CodeSnippet], limit: usize) -> String {
+    let mut output = String::new();
+    for (i, snippet) in snippets.iter().take(limit).enumerate() {
+        let message = &snippet.message;
+        let jsonl_path =
+            session_jsonl_path(&message.project_path, &message.session_id).unwrap_or_default();
+        let language = snippet.block.language.as_deref().unwrap_or("");
+        output.push_str(&format!(
+            "{}. 🗒️ {} 💬 {} 📅 {}{}\n",
+            i + 1,
+            file_hyperlink(
+                &jsonl_path.to_string_lossy(),
+                short_uuid(&message.session_id)
+            ),
+            short_uuid(&message.uuid),
+            local_time(message.timestamp).format("%Y-%m-%d %H:%M"),
+            if language.is_empty() {
+                String::new()
+            } else {
+                format!(" 🏷️ {}", language)
+            },
+        ));
+        if let Some(prompt) = &snippet.prompt {
+            output.push_str(&format!(
+                "   ❓ {}\n",
+                truncate_content(prompt, PROMPT_MAX_CHARS, true)
+            ));
+        }
+        output.push_str(&format!("```{}\n{}\n```\n\n", language, snippet.block.code));
+    }
+
+    if snippets.len() > limit {
+        output.push_str(&format!(
+            "+more: {} code blocks total, limit={}\n",
+            snippets.len(),
+            limit
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_code_blocks() {
+        let content = "Try this:\n```rust\nfn main() {}\n```\nthen\n```\nls -la\n```\n```python title=x\nprint(1)";
+        let blocks = extract_code_blocks(content);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].code, "fn main() {}");
+        assert_eq!(blocks[1].language, None);
+        assert_eq!(blocks[2].language.as_deref(), Some("python"));
+        assert_eq!(blocks[2].code, "print(1)", "Unclosed fence runs to the end");
+    }
+}
@@ -1,12 +1,64 @@
+use super::models::{MESSAGE_KINDS, TokenUsage};
+use super::saved_searches::SavedSearch;
 use anyhow::{Result, anyhow};
+use arc_swap::ArcSwap;
+use once_cell::sync::{Lazy, OnceCell};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::{info, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WebServerConfig {
-    pub path: String,
-    pub url: String,
+/// Accept a single value where a list is expected
+pub fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+    Ok(match Option::<OneOrMany<T>>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(OneOrMany::One(value)) => vec![value],
+        Some(OneOrMany::Many(values)) => values,
+    })
+}
+
+/// Local model (Ollama) that `summarize_session` and `summary --generate`
+/// call instead of leaving the work to Claude
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummarizerConfig {
+    /// Ollama server; plain `http://` only
+    #[serde(default = "SummarizerConfig::default_endpoint")]
+    pub endpoint: String,
+    pub model: String,
+    /// Estimated tokens of conversation per request; longer sessions are
+    /// summarized a chunk at a time, then the chunk summaries combined
+    #[serde(default = "SummarizerConfig::default_chunk_tokens")]
+    pub chunk_tokens: usize,
+    /// Seconds to wait for each answer
+    #[serde(default = "SummarizerConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl SummarizerConfig {
+    fn default_endpoint() -> String {
+        "http://localhost:11434".to_string()
+    }
+
+    fn default_chunk_tokens() -> usize {
+        6000
+    }
+
+    fn default_timeout_secs() -> u64 {
+        300
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,8 +67,60 @@ pub struct IndexConfig {
     pub auto_index_on_startup: bool,
     #[serde(default = "IndexConfig::default_writer_heap_mb")]
     pub writer_heap_mb: u32,
+    /// Least seconds between the MCP server's reindexing of changed files
+    /// after a request; 0 turns it off
+    #[serde(default = "IndexConfig::default_background_refresh_secs")]
+    pub background_refresh_secs: u64,
+    /// Index the caller's own session once it has gone this many seconds
+    /// without a write, so it can be searched mid-session; 0 leaves it out
+    /// of refreshes
+    #[serde(default)]
+    pub current_session_debounce_secs: u64,
     pub cache_dir: Option<PathBuf>,
-    pub claude_dir: Option<PathBuf>,
+    /// Claude config dirs whose `projects/` are indexed: one path or a list
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub claude_dir: Vec<PathBuf>,
+    /// Where `import` stores other machines' history (one subdirectory per host)
+    pub imports_dir: Option<PathBuf>,
+    /// Where Claude Desktop and OpenAI-style chat exports (`*.json`) are indexed from
+    pub transcripts_dir: Option<PathBuf>,
+    /// Projects never parsed or indexed: a project path (`~/work/client`),
+    /// a Claude project dir name, or a bare trailing name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_projects: Vec<String>,
+    /// Globs matched against JSONL file paths; matching files are never indexed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_path_globs: Vec<String>,
+    /// Index successful tool output as a size marker only; errors are kept
+    #[serde(default = "IndexConfig::default_skip_tool_results")]
+    pub skip_tool_results: bool,
+    /// Keep message text out of the index and read it back from the JSONL
+    #[serde(default = "IndexConfig::default_lazy_content")]
+    pub lazy_content: bool,
+    /// How message text is split into search terms; changing it rebuilds the index
+    #[serde(default)]
+    pub analyzer: AnalyzerConfig,
+    /// Other coding agents whose history is indexed alongside Claude Code's
+    #[serde(default)]
+    pub agents: AgentsConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AgentsConfig {
+    /// Index Codex CLI rollouts from `<codex_dir>/sessions`
+    #[serde(default)]
+    pub codex: bool,
+    /// Defaults to $CODEX_HOME, then ~/.codex
+    pub codex_dir: Option<PathBuf>,
+    /// Index Gemini CLI chats from `<gemini_dir>/tmp/*/chats`
+    #[serde(default)]
+    pub gemini: bool,
+    /// Defaults to ~/.gemini
+    pub gemini_dir: Option<PathBuf>,
 }
 
 impl IndexConfig {
@@ -27,6 +131,18 @@ impl IndexConfig {
     fn default_writer_heap_mb() -> u32 {
         50
     }
+
+    fn default_background_refresh_secs() -> u64 {
+        30
+    }
+
+    fn default_skip_tool_results() -> bool {
+        true
+    }
+
+    fn default_lazy_content() -> bool {
+        true
+    }
 }
 
 impl Default for IndexConfig {
@@ -34,8 +150,57 @@ impl Default for IndexConfig {
         Self {
             auto_index_on_startup: true,
             writer_heap_mb: 50,
+            background_refresh_secs: 30,
+            current_session_debounce_secs: 0,
             cache_dir: None,
-            claude_dir: None,
+            claude_dir: Vec::new(),
+            imports_dir: None,
+            transcripts_dir: None,
+            exclude_projects: Vec::new(),
+            exclude_path_globs: Vec::new(),
+            skip_tool_results: true,
+            lazy_content: true,
+            analyzer: AnalyzerConfig::default(),
+            agents: AgentsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalyzerConfig {
+    /// Reduce words to their stem, so "deploying" also finds "deployed"
+    #[serde(default)]
+    pub stemming: bool,
+    /// Stemmer language: english, french, german, spanish, ...
+    #[serde(default = "AnalyzerConfig::default_language")]
+    pub language: String,
+    /// Words left out of the index and of queries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stopwords: Vec<String>,
+    /// Index Chinese, Japanese and Korean as character pairs; needs a build
+    /// with the `cjk` feature
+    #[serde(default)]
+    pub cjk: bool,
+}
+
+impl AnalyzerConfig {
+    fn default_language() -> String {
+        "english".to_string()
+    }
+
+    /// True when content is tokenized exactly like the other text fields
+    pub fn is_default(&self) -> bool {
+        !self.stemming && self.stopwords.is_empty() && !self.cjk
+    }
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            stemming: false,
+            language: Self::default_language(),
+            stopwords: Vec::new(),
+            cjk: false,
         }
     }
 }
@@ -68,6 +233,8 @@ pub struct LimitsConfig {
     pub per_file_chars: usize,
     #[serde(default = "LimitsConfig::default_tool_result_max_chars")]
     pub tool_result_max_chars: usize,
+    #[serde(default = "LimitsConfig::default_entry_max_chars")]
+    pub entry_max_chars: usize,
     #[serde(default = "LimitsConfig::default_tool_input_max_chars")]
     pub tool_input_max_chars: usize,
 }
@@ -79,6 +246,9 @@ impl LimitsConfig {
     fn default_tool_result_max_chars() -> usize {
         2000
     }
+    fn default_entry_max_chars() -> usize {
+        20_000
+    }
     fn default_tool_input_max_chars() -> usize {
         200
     }
@@ -89,20 +259,316 @@ impl Default for LimitsConfig {
         Self {
             per_file_chars: 150_000,
             tool_result_max_chars: 2000,
+            entry_max_chars: 20_000,
             tool_input_max_chars: 200,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Flags accepted by `include` (tool calls) and `--include` (CLI)
+pub const INCLUDE_FLAGS: &[&str] = &["thinking", "tools", "current_session"];
+
+/// Presets available without configuration; config entries with the same name win
+const BUILTIN_INCLUDE_PRESETS: &[(&str, &[&str])] = &[
+    ("everything", &["thinking", "tools", "current_session"]),
+    ("prose_only", &[]),
+    ("debugging", &["thinking", "tools"]),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SearchConfig {
     #[serde(default)]
     pub exclude_patterns: Vec<String>,
+    /// Named include flag combinations, e.g. `review: [thinking]`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub include_presets: HashMap<String, Vec<String>>,
+    /// Default `recency_boost`: half-life in days of a message's score
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recency_boost: Option<f64>,
+    /// Score multiplier per message kind (user, assistant, tool_result,
+    /// summary, system); unlisted kinds keep their BM25 score
+    #[serde(default = "SearchConfig::default_type_boosts")]
+    pub type_boosts: HashMap<String, f32>,
+    /// Keep a history of searches for `query_history` and zero-hit suggestions
+    #[serde(default = "SearchConfig::default_record_queries")]
+    pub record_queries: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            exclude_patterns: Vec::new(),
+            include_presets: HashMap::new(),
+            recency_boost: None,
+            type_boosts: Self::default_type_boosts(),
+            record_queries: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    fn default_record_queries() -> bool {
+        true
+    }
+
+    fn default_type_boosts() -> HashMap<String, f32> {
+        HashMap::from([
+            ("user".to_string(), 2.0),
+            ("assistant".to_string(), 1.5),
+            ("tool_result".to_string(), 0.3),
+        ])
+    }
+
+    /// Score half-life for a search, from its `recency_boost` or the
+    /// configured default; 0 turns it off
+    pub fn recency_half_life(&self, requested: Option<f64>) -> Option<f64> {
+        requested.or(self.recency_boost).filter(|days| *days > 0.0)
+    }
+
+    /// Expand preset names into include flags. Errors on unknown names.
+    pub fn expand_include(&self, names: &[String]) -> Result<Vec<String>> {
+        let mut flags: Vec<String> = Vec::new();
+        let mut push = |flag: &str| {
+            if !flags.iter().any(|f| f == flag) {
+                flags.push(flag.to_string());
+            }
+        };
+
+        for name in names {
+            if INCLUDE_FLAGS.contains(&name.as_str()) {
+                push(name);
+            } else if let Some(preset) = self.include_presets.get(name) {
+                for flag in preset {
+                    if !INCLUDE_FLAGS.contains(&flag.as_str()) {
+                        return Err(anyhow!(
+                            "Include preset '{}' contains unknown flag '{}'",
+                            name,
+                            flag
+                        ));
+                    }
+                    push(flag);
+                }
+            } else if let Some((_, preset)) =
+                BUILTIN_INCLUDE_PRESETS.iter().find(|(n, _)| n == name)
+            {
+                preset.iter().for_each(|flag| push(flag));
+            } else {
+                return Err(anyhow!(
+                    "Unknown include '{}': use {} or a preset ({})",
+                    name,
+                    INCLUDE_FLAGS.join(", "),
+                    self.include_preset_names().join(", ")
+                ));
+            }
+        }
+
+        Ok(flags)
+    }
+
+    /// Built-in and configured preset names, sorted
+    pub fn include_preset_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_INCLUDE_PRESETS
+            .iter()
+            .map(|(n, _)| n.to_string())
+            .chain(self.include_presets.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DisplayConfig {
+    /// Zone for shown times and bare `YYYY-MM-DD` filters: `local` (the
+    /// system zone, or $TZ), `utc`, or a fixed offset like `+02:00`
+    #[serde(default = "DisplayConfig::default_timezone")]
+    pub timezone: String,
+}
+
+impl DisplayConfig {
+    fn default_timezone() -> String {
+        "local".to_string()
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            timezone: Self::default_timezone(),
+        }
+    }
+}
+
+/// USD per million tokens. Cache prices default to 1.25x (write) and 0.1x (read) input.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+}
+
+impl ModelPrice {
+    const fn builtin(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            cache_write: None,
+            cache_read: None,
+        }
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let per_token = |price: f64, tokens: u64| price * tokens as f64 / 1_000_000.0;
+        per_token(self.input, usage.input_tokens)
+            + per_token(self.output, usage.output_tokens)
+            + per_token(
+                self.cache_write.unwrap_or(self.input * 1.25),
+                usage.cache_creation_tokens,
+            )
+            + per_token(
+                self.cache_read.unwrap_or(self.input * 0.1),
+                usage.cache_read_tokens,
+            )
+    }
+}
+
+/// List prices keyed by model-name substring; the longest matching key wins
+const BUILTIN_MODEL_PRICES: &[(&str, ModelPrice)] = &[
+    ("opus", ModelPrice::builtin(15.0, 75.0)),
+    ("opus-4-5", ModelPrice::builtin(5.0, 25.0)),
+    ("sonnet", ModelPrice::builtin(3.0, 15.0)),
+    ("haiku", ModelPrice::builtin(0.8, 4.0)),
+    ("haiku-4-5", ModelPrice::builtin(1.0, 5.0)),
+];
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct UsageConfig {
+    /// Model-name substring -> price, overriding built-ins with the same key
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+impl UsageConfig {
+    /// Price for a model id, or None when no key matches (e.g. `<synthetic>`)
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        self.prices
+            .iter()
+            .map(|(key, price)| (key.as_str(), *price))
+            .chain(
+                BUILTIN_MODEL_PRICES
+                    .iter()
+                    .filter(|(key, _)| !self.prices.contains_key(*key))
+                    .copied(),
+            )
+            .filter(|(key, _)| model.contains(key))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| price)
+    }
+}
+
+/// Metadata extracted from messages at index time
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct MetadataConfig {
+    #[serde(default)]
+    pub patterns: PatternsConfig,
+    /// Name -> extractor whose values are searchable as `meta:<name>=<value>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extractors: BTreeMap<String, ExtractorConfig>,
+}
+
+impl MetadataConfig {
+    pub fn is_empty(&self) -> bool {
+        self.patterns.technologies.is_empty()
+            && self.patterns.tools.is_empty()
+            && self.extractors.is_empty()
+    }
+}
+
+/// Either a `regex`, whose matches (or first group) are the values, or a
+/// `command` kept running that answers each `{"content": ...}` JSON line
+/// on stdin with a JSON array of values
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ExtractorConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+}
+
+/// Name -> regex layered over the built-in patterns: a name replaces the
+/// built-in one, an empty regex drops it
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PatternsConfig {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub technologies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, String>,
+}
+
+/// MCP tool exposure: hide tools or publish them under another name
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ToolsConfig {
+    /// Built-in tool names never listed or callable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
+    /// Built-in name -> advertised name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rename: HashMap<String, String>,
+    /// Estimated tokens of tool output returned per call before it is cut
+    /// with a continuation cursor (0 = no limit)
+    #[serde(
+        default = "ToolsConfig::default_max_output_tokens",
+        skip_serializing_if = "ToolsConfig::is_default_max_output_tokens"
+    )]
+    pub max_output_tokens: usize,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            disabled: Vec::new(),
+            rename: HashMap::new(),
+            max_output_tokens: Self::default_max_output_tokens(),
+        }
+    }
+}
+
+impl ToolsConfig {
+    fn default_max_output_tokens() -> usize {
+        20_000
+    }
+
+    fn is_default_max_output_tokens(max: &usize) -> bool {
+        *max == Self::default_max_output_tokens()
+    }
+
+    /// Name a built-in tool is advertised under, or None if disabled
+    pub fn advertised_name<'a>(&'a self, builtin: &'a str) -> Option<&'a str> {
+        if self.disabled.iter().any(|d| d == builtin) {
+            return None;
+        }
+        Some(self.rename.get(builtin).map_or(builtin, |s| s.as_str()))
+    }
+
+    /// Built-in tool a called name refers to, or None if hidden
+    pub fn resolve<'a>(&'a self, called: &'a str) -> Option<&'a str> {
+        let builtin = self
+            .rename
+            .iter()
+            .find(|(_, advertised)| *advertised == called)
+            .map_or(called, |(builtin, _)| builtin.as_str());
+        (self.advertised_name(builtin) == Some(called)).then_some(builtin)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Config {
-    pub web_server: Option<WebServerConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summarizer: Option<SummarizerConfig>,
     #[serde(default)]
     pub index: IndexConfig,
     #[serde(default)]
@@ -111,29 +577,99 @@ pub struct Config {
     pub limits: LimitsConfig,
     #[serde(default)]
     pub search: SearchConfig,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default, skip_serializing_if = "MetadataConfig::is_empty")]
+    pub metadata: MetadataConfig,
+    /// Named searches, also written by `save_search`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub saved_searches: HashMap<String, SavedSearch>,
+    /// Old project path -> new path, so a moved repo keeps one history;
+    /// also written by `project merge`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub project_aliases: HashMap<String, String>,
+}
+
+/// Environment variable naming the profile when `--profile` isn't given
+pub const PROFILE_ENV: &str = "CLAUDE_SEARCH_PROFILE";
+
+static PROFILE: OnceCell<String> = OnceCell::new();
+
+/// Select a profile for this process, before the config is first read.
+/// Names are letters, digits, `-` and `_`, as they become directory names.
+pub fn set_profile(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid profile name '{}': use letters, digits, '-' and '_'",
+            name
+        ));
+    }
+    if PROFILE.set(name.to_string()).is_err() && active_profile().as_deref() != Some(name) {
+        return Err(anyhow!("A profile was already selected"));
+    }
+    Ok(())
+}
+
+/// Profile in use: from `--profile`, else `CLAUDE_SEARCH_PROFILE`. Each
+/// profile has its own config file, cache and data dirs; None is the default.
+pub fn active_profile() -> Option<String> {
+    PROFILE
+        .get()
+        .cloned()
+        .or_else(|| std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()))
+}
+
+/// Directory name under the cache or data dir, suffixed with the profile
+fn profile_dir_name() -> String {
+    match active_profile() {
+        Some(profile) => format!("claude-conversation-search-{}", profile),
+        None => "claude-conversation-search".to_string(),
+    }
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
-        let config_dir = dirs::config_dir()
+    /// `config.yaml`, or `profiles/<name>.yaml` for a profile
+    pub fn config_path() -> Result<PathBuf> {
+        let dir = dirs::config_dir()
             .ok_or_else(|| anyhow!("Could not determine config directory"))?
             .join("claude-conversation-search-mcp");
+        Ok(match active_profile() {
+            Some(profile) => dir.join("profiles").join(format!("{}.yaml", profile)),
+            None => dir.join("config.yaml"),
+        })
+    }
 
-        let config_path = config_dir.join("config.yaml");
+    pub fn load() -> Result<Self> {
+        let config_path = Self::config_path()?;
+        let config_dir = config_path
+            .parent()
+            .ok_or_else(|| anyhow!("Config path has no parent directory"))?;
 
-        let config = if config_path.exists() {
+        let mut root: serde_yaml::Value = if config_path.exists() {
             let config_content = fs::read_to_string(&config_path)?;
             serde_yaml::from_str(&config_content)?
         } else {
             // Create default config if it doesn't exist
-            fs::create_dir_all(&config_dir)?;
+            fs::create_dir_all(config_dir)?;
             let default_config = Self::default();
             let config_content = serde_yaml::to_string(&default_config)?;
-            fs::write(&config_path, config_content)?;
-            default_config
+            fs::write(&config_path, &config_content)?;
+            serde_yaml::from_str(&config_content)?
         };
+        if root.is_null() {
+            root = serde_yaml::Value::Mapping(Default::default());
+        }
+        apply_env_overrides(&mut root, |name| std::env::var(name).ok())?;
 
-        Ok(config)
+        Ok(serde_yaml::from_value(root)?)
     }
 
     pub fn get_cache_dir(&self) -> Result<PathBuf> {
@@ -142,27 +678,74 @@ impl Config {
         }
 
         let cache = dirs::cache_dir().ok_or_else(|| anyhow!("Could not find cache directory"))?;
-        Ok(cache.join("claude-conversation-search"))
+        Ok(cache.join(profile_dir_name()))
     }
 
-    pub fn get_claude_dir(&self) -> Result<PathBuf> {
-        if let Some(claude_dir) = &self.index.claude_dir {
-            return Ok(claude_dir.clone());
+    /// Configured Claude dirs, or else whichever of `~/.claude` and
+    /// `~/.config/claude` exist, or `~/.claude` when neither does
+    pub fn get_claude_dirs(&self) -> Result<Vec<PathBuf>> {
+        if !self.index.claude_dir.is_empty() {
+            return Ok(self.index.claude_dir.clone());
         }
 
         let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-
         let claude_dir = home.join(".claude");
-        if claude_dir.exists() {
-            return Ok(claude_dir);
+        let found: Vec<_> = [claude_dir.clone(), home.join(".config").join("claude")]
+            .into_iter()
+            .filter(|dir| dir.exists())
+            .collect();
+
+        Ok(if found.is_empty() {
+            vec![claude_dir]
+        } else {
+            found
+        })
+    }
+
+    pub fn get_imports_dir(&self) -> Result<PathBuf> {
+        if let Some(imports_dir) = &self.index.imports_dir {
+            return Ok(imports_dir.clone());
+        }
+
+        Ok(self.get_data_dir()?.join("imports"))
+    }
+
+    /// Where user data lives: outside the cache dir so `cache clear` and
+    /// rebuilds keep imported history and bookmarks
+    pub fn get_data_dir(&self) -> Result<PathBuf> {
+        let data = dirs::data_dir().ok_or_else(|| anyhow!("Could not find data directory"))?;
+        Ok(data.join(profile_dir_name()))
+    }
+
+    pub fn get_transcripts_dir(&self) -> Result<PathBuf> {
+        if let Some(transcripts_dir) = &self.index.transcripts_dir {
+            return Ok(transcripts_dir.clone());
         }
 
-        let config_claude_dir = home.join(".config").join("claude");
-        if config_claude_dir.exists() {
-            return Ok(config_claude_dir);
+        Ok(self.get_data_dir()?.join("transcripts"))
+    }
+
+    /// Codex CLI home, whether or not `index.agents.codex` is on
+    pub fn get_codex_dir(&self) -> Result<PathBuf> {
+        if let Some(codex_dir) = &self.index.agents.codex_dir {
+            return Ok(codex_dir.clone());
+        }
+        if let Some(codex_home) = std::env::var_os("CODEX_HOME") {
+            return Ok(PathBuf::from(codex_home));
+        }
+
+        let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
+        Ok(home.join(".codex"))
+    }
+
+    /// Gemini CLI home, whether or not `index.agents.gemini` is on
+    pub fn get_gemini_dir(&self) -> Result<PathBuf> {
+        if let Some(gemini_dir) = &self.index.agents.gemini_dir {
+            return Ok(gemini_dir.clone());
         }
 
-        Ok(claude_dir) // Return default even if it doesn't exist
+        let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
+        Ok(home.join(".gemini"))
     }
 
     pub fn get_lock_file_path(&self) -> Result<PathBuf> {
@@ -179,10 +762,399 @@ impl Config {
     }
 }
 
-// Global config instance
-use once_cell::sync::OnceCell;
-static CONFIG: OnceCell<Config> = OnceCell::new();
+/// Options settable from the environment, with whether they take text.
+/// Text options use the value as is; others are read as YAML, so lists and
+/// maps are written `[a, b]` and `{key: value}`.
+const ENV_OPTIONS: &[(&str, bool)] = &[
+    ("summarizer.endpoint", true),
+    ("summarizer.model", true),
+    ("summarizer.chunk_tokens", false),
+    ("summarizer.timeout_secs", false),
+    ("index.auto_index_on_startup", false),
+    ("index.writer_heap_mb", false),
+    ("index.background_refresh_secs", false),
+    ("index.current_session_debounce_secs", false),
+    ("index.cache_dir", true),
+    ("index.claude_dir", false),
+    ("index.imports_dir", true),
+    ("index.transcripts_dir", true),
+    ("index.exclude_projects", false),
+    ("index.exclude_path_globs", false),
+    ("index.skip_tool_results", false),
+    ("index.lazy_content", false),
+    ("index.analyzer.stemming", false),
+    ("index.analyzer.language", true),
+    ("index.analyzer.stopwords", false),
+    ("index.analyzer.cjk", false),
+    ("index.agents.codex", false),
+    ("index.agents.codex_dir", true),
+    ("index.agents.gemini", false),
+    ("index.agents.gemini_dir", true),
+    ("locking.enabled", false),
+    ("locking.lock_file", true),
+    ("limits.per_file_chars", false),
+    ("limits.tool_result_max_chars", false),
+    ("limits.entry_max_chars", false),
+    ("limits.tool_input_max_chars", false),
+    ("search.exclude_patterns", false),
+    ("search.include_presets", false),
+    ("search.recency_boost", false),
+    ("search.type_boosts", false),
+    ("search.record_queries", false),
+    ("tools.disabled", false),
+    ("tools.rename", false),
+    ("tools.max_output_tokens", false),
+    ("usage.prices", false),
+    ("display.timezone", true),
+    ("saved_searches", false),
+    ("project_aliases", false),
+];
+
+/// Environment variable overriding a config option: `CLAUDE_SEARCH_` and the
+/// dotted path in capitals with `_` for `.`, leaving out `index.`
+/// (`index.writer_heap_mb` is `CLAUDE_SEARCH_WRITER_HEAP_MB`)
+pub fn env_var_name(path: &str) -> String {
+    let path = path.strip_prefix("index.").unwrap_or(path);
+    format!("CLAUDE_SEARCH_{}", path.replace('.', "_").to_uppercase())
+}
+
+/// Layer options set in the environment over the config file's contents
+fn apply_env_overrides(
+    root: &mut serde_yaml::Value,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    for (path, is_text) in ENV_OPTIONS {
+        let name = env_var_name(path);
+        let Some(raw) = var(&name) else {
+            continue;
+        };
+        let value = if *is_text {
+            serde_yaml::Value::String(raw)
+        } else {
+            serde_yaml::from_str(&raw).map_err(|e| anyhow!("Invalid {}: {}", name, e))?
+        };
+
+        let mut node = &mut *root;
+        for key in path.split('.') {
+            if !node.is_mapping() {
+                *node = serde_yaml::Value::Mapping(Default::default());
+            }
+            node = node
+                .as_mapping_mut()
+                .expect("node is a mapping")
+                .entry(key.into())
+                .or_insert(serde_yaml::Value::Null);
+        }
+        *node = value;
+    }
+    Ok(())
+}
+
+/// Set `key` under the top-level `section` mapping of the config file,
+/// leaving other settings as they are. Returns true when it replaced an
+/// existing entry.
+pub fn set_config_entry(section: &str, key: &str, value: serde_yaml::Value) -> Result<bool> {
+    let config_path = Config::config_path()?;
+    // Make sure the file exists before editing it
+    Config::load()?;
 
-pub fn get_config() -> &'static Config {
-    CONFIG.get_or_init(|| Config::load().unwrap_or_default())
+    let content = fs::read_to_string(&config_path)?;
+    let mut root: serde_yaml::Value = serde_yaml::from_str(&content)?;
+    if !root.is_mapping() {
+        root = serde_yaml::Value::Mapping(Default::default());
+    }
+    let entries = root
+        .as_mapping_mut()
+        .expect("root is a mapping")
+        .entry(section.into())
+        .or_insert_with(|| serde_yaml::Value::Mapping(Default::default()));
+    if !entries.is_mapping() {
+        *entries = serde_yaml::Value::Mapping(Default::default());
+    }
+    let replaced = entries
+        .as_mapping_mut()
+        .expect("section is a mapping")
+        .insert(key.into(), value)
+        .is_some();
+
+    fs::write(&config_path, serde_yaml::to_string(&root)?)?;
+    Ok(replaced)
+}
+
+/// Search defaults `set_config` may change at runtime
+pub const SETTABLE_SEARCH_OPTIONS: &[&str] = &[
+    "recency_boost",
+    "type_boosts",
+    "exclude_patterns",
+    "include_presets",
+];
+
+/// Effective config as YAML, or one top-level section of it
+pub fn format_config(config: &Config, section: Option<&str>) -> Result<String> {
+    let root = serde_yaml::to_value(config)?;
+    let (title, value) = match section {
+        None => ("config".to_string(), root),
+        Some(section) => {
+            let value = root.get(section).cloned().ok_or_else(|| {
+                let sections: Vec<&str> = root
+                    .as_mapping()
+                    .into_iter()
+                    .flat_map(|m| m.keys().filter_map(|k| k.as_str()))
+                    .collect();
+                anyhow!(
+                    "Unknown config section '{}': use {}",
+                    section,
+                    sections.join(", ")
+                )
+            })?;
+            (section.to_string(), value)
+        }
+    };
+    Ok(format!(
+        "⚙️ Effective {} (file: {}, with CLAUDE_SEARCH_* overrides)\n{}",
+        title,
+        Config::config_path()?.display(),
+        serde_yaml::to_string(&value)?
+    ))
+}
+
+/// Change one search default in the config file after checking it, and
+/// start using it. Returns a confirmation.
+pub fn set_search_option(key: &str, value: serde_yaml::Value) -> Result<String> {
+    if !SETTABLE_SEARCH_OPTIONS.contains(&key) {
+        return Err(anyhow!(
+            "Option '{}' can't be set at runtime: use {}",
+            key,
+            SETTABLE_SEARCH_OPTIONS.join(", ")
+        ));
+    }
+    let mut search = serde_yaml::to_value(&get_config().search)?;
+    search[key] = value.clone();
+    let search: SearchConfig = serde_yaml::from_value(search)
+        .map_err(|e| anyhow!("Invalid value for search.{}: {}", key, e))?;
+    if search.recency_boost.is_some_and(|days| days < 0.0) {
+        return Err(anyhow!("recency_boost is a half-life in days: 0 or more"));
+    }
+    if let Some((kind, _)) = search
+        .type_boosts
+        .iter()
+        .find(|(kind, boost)| !MESSAGE_KINDS.contains(&kind.as_str()) || **boost < 0.0)
+    {
+        return Err(anyhow!(
+            "type_boosts takes a multiplier of 0 or more per kind ({}), not '{}'",
+            MESSAGE_KINDS.join(", "),
+            kind
+        ));
+    }
+    for name in search.include_presets.keys() {
+        search.expand_include(std::slice::from_ref(name))?;
+    }
+
+    set_config_entry("search", key, value)?;
+    reload_config()?;
+    let mut confirmation = format!(
+        "⚙️ Set search.{} in {}\n",
+        key,
+        Config::config_path()?.display()
+    );
+    let env_name = env_var_name(&format!("search.{}", key));
+    if std::env::var_os(&env_name).is_some() {
+        confirmation.push_str(&format!(
+            "⚠️ {} is set and still overrides the file\n",
+            env_name
+        ));
+    }
+    Ok(confirmation)
+}
+
+// Global config instance, swapped whole when the file changes
+static CONFIG: Lazy<ArcSwap<Config>> = Lazy::new(|| {
+    *LOADED_MTIME.lock().unwrap_or_else(|e| e.into_inner()) = config_mtime();
+    ArcSwap::from_pointee(Config::load().unwrap_or_else(|e| {
+        warn!("Using the default config: {}", e);
+        Config::default()
+    }))
+});
+
+/// Modification time of the config file when it was last read
+static LOADED_MTIME: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+fn config_mtime() -> Option<SystemTime> {
+    fs::metadata(Config::config_path().ok()?)
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Current config. Hold the returned handle for one operation only, so a
+/// reload is picked up by the next one.
+pub fn get_config() -> Arc<Config> {
+    CONFIG.load_full()
+}
+
+/// Read the config file again. A file that doesn't parse leaves the
+/// current config in place.
+pub fn reload_config() -> Result<()> {
+    Lazy::force(&CONFIG);
+    *LOADED_MTIME.lock().unwrap_or_else(|e| e.into_inner()) = config_mtime();
+    CONFIG.store(Arc::new(Config::load()?));
+    Ok(())
+}
+
+/// Reload the config when its file changed since it was read, for
+/// long-running servers. Returns true when a new config is in use.
+pub fn reload_config_if_changed() -> bool {
+    Lazy::force(&CONFIG);
+    if config_mtime() == *LOADED_MTIME.lock().unwrap_or_else(|e| e.into_inner()) {
+        return false;
+    }
+    match reload_config() {
+        Ok(()) => {
+            info!(
+                "Reloaded {}",
+                Config::config_path().unwrap_or_default().display()
+            );
+            true
+        }
+        Err(e) => {
+            warn!("Keeping the previous config: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_overrides_layer_over_the_file() {
+        let mut root: serde_yaml::Value =
+            serde_yaml::from_str("index:\n  writer_heap_mb: 80\n  lazy_content: false\n").unwrap();
+        let env = HashMap::from([
+            ("CLAUDE_SEARCH_WRITER_HEAP_MB", "200"),
+            ("CLAUDE_SEARCH_CACHE_DIR", "/var/cache/search"),
+            ("CLAUDE_SEARCH_CLAUDE_DIR", "[/data/a, /data/b]"),
+            ("CLAUDE_SEARCH_TOOLS_DISABLED", "[respawn_server]"),
+            ("CLAUDE_SEARCH_DISPLAY_TIMEZONE", "+02:00"),
+        ]);
+        apply_env_overrides(&mut root, |name| env.get(name).map(|v| v.to_string())).unwrap();
+        let config: Config = serde_yaml::from_value(root).unwrap();
+
+        assert_eq!(config.index.writer_heap_mb, 200);
+        assert!(!config.index.lazy_content);
+        assert_eq!(
+            config.index.cache_dir.as_deref(),
+            Some(std::path::Path::new("/var/cache/search"))
+        );
+        assert_eq!(config.index.claude_dir.len(), 2);
+        assert_eq!(config.tools.disabled, vec!["respawn_server".to_string()]);
+        assert_eq!(config.display.timezone, "+02:00");
+
+        let mut root = serde_yaml::Value::Null;
+        let bad = |name: &str| (name == "CLAUDE_SEARCH_LAZY_CONTENT").then(|| "[".to_string());
+        assert!(apply_env_overrides(&mut root, bad).is_err());
+    }
+
+    #[test]
+    fn test_expand_include_presets() {
+        let mut search = SearchConfig::default();
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            search.expand_include(&names(&["everything"])).unwrap(),
+            names(&["thinking", "tools", "current_session"])
+        );
+        assert!(
+            search
+                .expand_include(&names(&["prose_only"]))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            search
+                .expand_include(&names(&["tools", "debugging"]))
+                .unwrap(),
+            names(&["tools", "thinking"])
+        );
+        assert!(search.expand_include(&names(&["bogus"])).is_err());
+
+        // Config presets override built-ins of the same name
+        search
+            .include_presets
+            .insert("debugging".to_string(), names(&["tools"]));
+        assert_eq!(
+            search.expand_include(&names(&["debugging"])).unwrap(),
+            names(&["tools"])
+        );
+        search
+            .include_presets
+            .insert("broken".to_string(), names(&["nope"]));
+        assert!(search.expand_include(&names(&["broken"])).is_err());
+    }
+
+    #[test]
+    fn test_model_price_lookup() {
+        let mut usage = UsageConfig::default();
+        assert_eq!(
+            usage.price_for("claude-opus-4-1-20250805").unwrap().input,
+            15.0
+        );
+        assert_eq!(
+            usage.price_for("claude-opus-4-5-20251101").unwrap().input,
+            5.0
+        );
+        assert_eq!(usage.price_for("<synthetic>"), None);
+
+        usage
+            .prices
+            .insert("opus".to_string(), ModelPrice::builtin(10.0, 50.0));
+        assert_eq!(usage.price_for("claude-opus-4-1").unwrap().input, 10.0);
+
+        let tokens = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 0,
+            cache_creation_tokens: 1_000_000,
+            cache_read_tokens: 1_000_000,
+        };
+        let cost = ModelPrice::builtin(3.0, 15.0).cost(&tokens);
+        assert!((cost - (3.0 + 3.75 + 0.3)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_claude_dir_takes_one_path_or_a_list() {
+        let index = |yaml: &str| serde_yaml::from_str::<IndexConfig>(yaml).unwrap();
+        assert_eq!(index("claude_dir: /a").claude_dir, [PathBuf::from("/a")]);
+        assert_eq!(
+            index("claude_dir: [/a, /b]").claude_dir,
+            [PathBuf::from("/a"), PathBuf::from("/b")]
+        );
+        assert!(index("claude_dir: null").claude_dir.is_empty());
+        assert!(index("writer_heap_mb: 50").claude_dir.is_empty());
+    }
+
+    #[test]
+    fn test_tools_disable_and_rename() {
+        let tools = ToolsConfig {
+            disabled: vec!["respawn_server".to_string()],
+            rename: HashMap::from([(
+                "search_conversations".to_string(),
+                "history_search".to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        assert_eq!(tools.advertised_name("respawn_server"), None);
+        assert_eq!(tools.resolve("respawn_server"), None);
+        assert_eq!(
+            tools.advertised_name("search_conversations"),
+            Some("history_search")
+        );
+        assert_eq!(
+            tools.resolve("history_search"),
+            Some("search_conversations")
+        );
+        // Original name of a renamed tool is hidden
+        assert_eq!(tools.resolve("search_conversations"), None);
+        assert_eq!(tools.resolve("reindex"), Some("reindex"));
+    }
 }
@@ -0,0 +1,225 @@
+use super::path_utils::{home_to_tilde, session_jsonl_path, short_uuid};
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+use super::utils::truncate_content;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Max characters kept of a decision
+const DECISION_MAX_CHARS: usize = 200;
+
+/// Phrases an assistant settles something with: "we'll go with", "let's go
+/// with", "decided to", "the decision is", "settled on", "opted for", ...
+static DECISION_PHRASE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\b(?:(?:we|i|let)(?:['’](?:ll|re|m|s))?(?:\s+(?:will|are|am))?\s+go(?:ing)?\s+with|decided\s+(?:to|on|that|against)|decision\s*(?:is|was|:)|settled\s+on|opted\s+(?:for|to)|chose\s+to|stick(?:ing)?\s+with)\b",
+    )
+    .unwrap()
+});
+/// Checklist item marked done: `- [x] item`, `1. [x] item`, `✅ item`
+static DONE_ITEM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:(?:[-*+]|\d+\.)\s+\[[xX]\]|(?:[-*+]\s+)?(?:✅|✔️|✔|☑️|☑))\s*(.+)$").unwrap()
+});
+/// Start of a non-prose content part (`[Bash] `, `[thinking] `, ...)
+static PART_PREFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\[[A-Za-z][\w.-]+\] ").unwrap());
+/// Parts that can span lines; tool calls are one line of JSON
+static MULTILINE_PART: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\[(?:thinking|result|error)\] ").unwrap());
+static MARKDOWN_LEAD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:[-*+]\s+|\d+\.\s+|#+\s+|>\s*)*").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecisionKind {
+    /// A sentence stating what was decided
+    Statement,
+    /// A checklist item marked done
+    Done,
+}
+
+/// One decision-like statement of an assistant message
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub uuid: String,
+    pub session_id: String,
+    pub project_path: String,
+    pub timestamp: DateTime<Utc>,
+    pub kind: DecisionKind,
+    pub text: String,
+}
+
+/// The sentence of `line` holding the match at `start..end`
+fn sentence_around(line: &str, start: usize, end: usize) -> &str {
+    let from = line[..start]
+        .rfind(". ")
+        .into_iter()
+        .chain(line[..start].rfind("! "))
+        .chain(line[..start].rfind("? "))
+        .max()
+        .map_or(0, |i| i + 2);
+    let to = line[end..]
+        .char_indices()
+        .find(|&(i, c)| {
+            matches!(c, '.' | '!' | '?')
+                && line[end + i + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        })
+        .map_or(line.len(), |(i, c)| end + i + c.len_utf8());
+    &line[from..to]
+}
+
+/// Decision-like statements and done checklist items of a message's prose,
+/// in order. Code blocks, thinking, tool calls and questions are skipped.
+/// Parts have no end marker, so thinking and tool results run until the
+/// next part.
+pub fn extract_decisions(content: &str) -> Vec<(DecisionKind, String)> {
+    let mut decisions = Vec::new();
+    let mut in_code = false;
+    let mut in_part = false;
+    for line in content.lines() {
+        if PART_PREFIX.is_match(line) {
+            in_part = MULTILINE_PART.is_match(line);
+            in_code = false;
+            continue;
+        }
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || in_part {
+            continue;
+        }
+        let found = if let Some(item) = DONE_ITEM.captures(line) {
+            Some((DecisionKind::Done, item[1].to_string()))
+        } else if let Some(phrase) = DECISION_PHRASE.find(line) {
+            let sentence = sentence_around(line, phrase.start(), phrase.end());
+            (!sentence.trim_end().ends_with('?'))
+                .then(|| (DecisionKind::Statement, sentence.to_string()))
+        } else {
+            None
+        };
+        if let Some((kind, text)) = found {
+            let text = MARKDOWN_LEAD.replace(text.trim(), "").replace("**", "");
+            let text = truncate_content(&text, DECISION_MAX_CHARS, true);
+            if !text.is_empty() && !decisions.iter().any(|(_, seen)| *seen == text) {
+                decisions.push((kind, text));
+            }
+        }
+    }
+    decisions
+}
+
+fn session_link(project_path: &str, session_id: &str) -> String {
+    let jsonl_path = session_jsonl_path(project_path, session_id).unwrap_or_default();
+    file_hyperlink(&jsonl_path.to_string_lossy(), short_uuid(session_id))
+}
+
+/// Format decisions, shared by CLI and MCP, grouped by session, most recent
+/// session first and each session's decisions in order:
+/// 🗒️ session 📁 project 📅 date
+///    ✔ statement 💬 msg
+///    ☑ done item 💬 msg
+pub fn format_decisions(decisions: &[Decision], limit: usize) -> String {
+    let mut sessions: Vec<Vec<&Decision>> = Vec::new();
+    for decision in decisions {
+        match sessions
+            .iter_mut()
+            .find(|s| s[0].session_id == decision.session_id)
+        {
+            Some(session) => session.push(decision),
+            None => sessions.push(vec![decision]),
+        }
+    }
+    for session in &mut sessions {
+        session.sort_by_key(|d| d.timestamp);
+    }
+    sessions.sort_by_key(|s| std::cmp::Reverse(s[s.len() - 1].timestamp));
+
+    let mut output = String::new();
+    let mut shown = 0;
+    for session in &sessions {
+        if shown == limit {
+            break;
+        }
+        let first = session[0];
+        output.push_str(&format!(
+            "🗒️ {} 📁 {} 📅 {}\n",
+            session_link(&first.project_path, &first.session_id),
+            home_to_tilde(&first.project_path),
+            local_time(first.timestamp).format("%Y-%m-%d %H:%M"),
+        ));
+        for decision in session.iter().take(limit - shown) {
+            let marker = match decision.kind {
+                DecisionKind::Statement => "✔",
+                DecisionKind::Done => "☑",
+            };
+            output.push_str(&format!(
+                "   {} {} 💬 {}\n",
+                marker,
+                decision.text,
+                short_uuid(&decision.uuid)
+            ));
+            shown += 1;
+        }
+    }
+    if decisions.len() > shown {
+        output.push_str(&format!(
+            "… {} more; narrow with session, project, after or before\n",
+            decisions.len() - shown
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decisions_are_pulled_from_prose() {
+        let content = "Looked at both options. We'll go with Postgres since it has JSONB. Redis is faster.\n\
+                       Should we go with a queue?\n\
+                       - [x] **Migrate** the schema\n\
+                       - [ ] Backfill old rows\n\
+                       ✅ Tests pass\n\
+                       ```\n\
+                       // decided to inline this\n\
+                       ```\n\
+                       [Bash] {\"command\":\"echo we decided to\"}\n\
+                       After some thought I decided against caching.";
+        let decisions = extract_decisions(content);
+        assert_eq!(
+            decisions,
+            vec![
+                (
+                    DecisionKind::Statement,
+                    "We'll go with Postgres since it has JSONB.".to_string()
+                ),
+                (DecisionKind::Done, "Migrate the schema".to_string()),
+                (DecisionKind::Done, "Tests pass".to_string()),
+                (
+                    DecisionKind::Statement,
+                    "After some thought I decided against caching.".to_string()
+                ),
+            ]
+        );
+        assert!(extract_decisions("Let's go with v2.0 for now").len() == 1);
+
+        let thinking = "[thinking] Weighing the options.\n\
+                        Keep conservative: \"go with\", \"decided to\".\n\
+                        \n\
+                        So we'll go with the short list.\n\
+                        [Edit] {\"file_path\":\"src/decisions.rs\"}\n\
+                        Settled on the short list.";
+        assert_eq!(
+            extract_decisions(thinking),
+            vec![(
+                DecisionKind::Statement,
+                "Settled on the short list.".to_string()
+            )]
+        );
+        assert!(extract_decisions("Going with the flow of the code here").is_empty());
+    }
+}
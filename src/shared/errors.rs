@@ -0,0 +1,244 @@
+use super::path_utils::{session_jsonl_path, short_uuid};
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// Max characters kept in a signature
+const SIGNATURE_MAX_CHARS: usize = 120;
+
+static EXIT_CODE_LINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^Exit code -?\d+$").unwrap());
+/// Start of another content part (`[result] `, `[Bash] `, ...)
+static PART_PREFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\[[\w.-]+\] ").unwrap());
+static TOOL_ERROR_TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"</?tool_use_error>").unwrap());
+static QUOTED: Lazy<Regex> = Lazy::new(|| Regex::new(r#"'[^']*'|"[^"]*"|`[^`]*`"#).unwrap());
+static PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:~|\.{1,2})?/[^\s:,;)\]]+").unwrap());
+static HEX_ID: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b(?:0x[0-9a-fA-F]+|[0-9a-fA-F]*\d[0-9a-fA-F]*(?:-[0-9a-fA-F]+){2,}|[0-9a-f]{12,})\b",
+    )
+    .unwrap()
+});
+static NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+").unwrap());
+static WHITESPACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
+
+/// One failed tool result, as indexed
+#[derive(Debug, Clone)]
+pub struct ErrorOccurrence {
+    pub uuid: String,
+    pub session_id: String,
+    pub project_path: String,
+    pub timestamp: DateTime<Utc>,
+    pub tool: String,
+    /// First meaningful line of the error output
+    pub message: String,
+}
+
+/// Later message whose call of the failing tool went through
+#[derive(Debug, Clone)]
+pub struct ErrorResolution {
+    pub uuid: String,
+    pub session_id: String,
+    pub project_path: String,
+}
+
+/// Failures of one tool sharing a normalized signature
+#[derive(Debug, Clone)]
+pub struct ErrorCluster {
+    pub tool: String,
+    pub signature: String,
+    /// Oldest first
+    pub occurrences: Vec<ErrorOccurrence>,
+    /// At most one per session
+    pub resolutions: Vec<ErrorResolution>,
+}
+
+impl ErrorCluster {
+    pub fn session_count(&self) -> usize {
+        self.occurrences
+            .iter()
+            .map(|o| o.session_id.as_str())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
+/// First meaningful line of each `[error]` part of indexed content, skipping
+/// the `Exit code N` header of failed Bash results. Parts with no text give an
+/// empty string, so the list lines up with the message's failed tools.
+pub fn extract_error_messages(content: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut pending = false;
+    for line in content.lines() {
+        let line = match line.strip_prefix("[error] ") {
+            Some(rest) => {
+                if pending {
+                    messages.push(String::new());
+                }
+                pending = true;
+                rest
+            }
+            None if pending && PART_PREFIX.is_match(line) => {
+                messages.push(String::new());
+                pending = false;
+                continue;
+            }
+            None if pending => line,
+            None => continue,
+        };
+        let line = TOOL_ERROR_TAGS.replace_all(line, "");
+        let line = line.trim();
+        if line.is_empty() || EXIT_CODE_LINE.is_match(line) {
+            continue;
+        }
+        messages.push(line.to_string());
+        pending = false;
+    }
+    if pending {
+        messages.push(String::new());
+    }
+    messages
+}
+
+/// Error text with the parts that vary between runs (quoted values, paths,
+/// ids, numbers) replaced by placeholders
+pub fn error_signature(message: &str) -> String {
+    let signature = QUOTED.replace_all(message, "<str>");
+    let signature = PATH.replace_all(&signature, "<path>");
+    let signature = HEX_ID.replace_all(&signature, "<id>");
+    let signature = NUMBER.replace_all(&signature, "<n>");
+    let signature = WHITESPACE.replace_all(signature.trim(), " ");
+    signature.chars().take(SIGNATURE_MAX_CHARS).collect()
+}
+
+/// Group occurrences by tool and signature, the ones spanning most sessions first
+pub fn cluster_errors(mut occurrences: Vec<ErrorOccurrence>) -> Vec<ErrorCluster> {
+    occurrences.sort_by_key(|o| o.timestamp);
+
+    let mut by_key: HashMap<(String, String), ErrorCluster> = HashMap::new();
+    for occurrence in occurrences {
+        let signature = error_signature(&occurrence.message);
+        by_key
+            .entry((occurrence.tool.clone(), signature.clone()))
+            .or_insert_with(|| ErrorCluster {
+                tool: occurrence.tool.clone(),
+                signature,
+                occurrences: Vec::new(),
+                resolutions: Vec::new(),
+            })
+            .occurrences
+            .push(occurrence);
+    }
+
+    let mut clusters: Vec<_> = by_key.into_values().collect();
+    clusters.sort_by(|a, b| {
+        b.session_count()
+            .cmp(&a.session_count())
+            .then(b.occurrences.len().cmp(&a.occurrences.len()))
+            .then(a.signature.cmp(&b.signature))
+    });
+    clusters
+}
+
+fn session_link(project_path: &str, session_id: &str) -> String {
+    let jsonl_path = session_jsonl_path(project_path, session_id).unwrap_or_default();
+    file_hyperlink(&jsonl_path.to_string_lossy(), short_uuid(session_id))
+}
+
+/// Format error clusters, shared by CLI and MCP:
+/// N. 🚨 tool ×count in N sessions 📅 first→last
+///    ⚠️ latest message
+///    ✅ 🗒️ session 💬 resolving msg, ...
+///    ❔ unresolved in N sessions
+pub fn format_error_clusters(clusters: &[ErrorCluster], limit: usize) -> String {
+    let mut output = String::new();
+    for (i, cluster) in clusters.iter().take(limit).enumerate() {
+        let (first, last) = (
+            &cluster.occurrences[0],
+            &cluster.occurrences[cluster.occurrences.len() - 1],
+        );
+        let sessions = cluster.session_count();
+        output.push_str(&format!(
+            "{}. 🚨 {} ×{} in {} session{} 📅 {}→{}\n",
+            i + 1,
+            cluster.tool,
+            cluster.occurrences.len(),
+            sessions,
+            if sessions == 1 { "" } else { "s" },
+            local_time(first.timestamp).format("%Y-%m-%d"),
+            local_time(last.timestamp).format("%Y-%m-%d"),
+        ));
+        let example: String = last.message.chars().take(160).collect();
+        output.push_str(&format!(
+            "   ⚠️ {} (🗒️ {} 💬 {})\n",
+            example,
+            session_link(&last.project_path, &last.session_id),
+            short_uuid(&last.uuid),
+        ));
+
+        if !cluster.resolutions.is_empty() {
+            let links: Vec<String> = cluster
+                .resolutions
+                .iter()
+                .rev()
+                .take(3)
+                .map(|r| {
+                    format!(
+                        "🗒️ {} 💬 {}",
+                        session_link(&r.project_path, &r.session_id),
+                        short_uuid(&r.uuid)
+                    )
+                })
+                .collect();
+            output.push_str(&format!("   ✅ {}\n", links.join(", ")));
+        }
+        let unresolved = sessions - cluster.resolutions.len();
+        if unresolved > 0 {
+            output.push_str(&format!(
+                "   ❔ unresolved in {} session{}\n",
+                unresolved,
+                if unresolved == 1 { "" } else { "s" }
+            ));
+        }
+    }
+
+    if clusters.len() > limit {
+        output.push_str(&format!(
+            "+more: {} error signatures total, limit={}\n",
+            clusters.len(),
+            limit
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_ignores_varying_parts() {
+        let a = error_signature("error: could not find `Cargo.toml` in `/home/a/x` or any parent");
+        let b = error_signature("error: could not find `Cargo.toml` in `/tmp/y` or any parent");
+        assert_eq!(a, b);
+        assert_eq!(
+            error_signature("File /src/main.rs line 42: unexpected token"),
+            "File <path> line <n>: unexpected token"
+        );
+    }
+
+    #[test]
+    fn test_extract_error_messages_skips_exit_code() {
+        let content = "[Bash] {\"command\":\"make\"}\n[error] Exit code 2\nmake: *** No rule to make target\n[error] Exit code 1\n[result] ok\n[error] <tool_use_error>File has not been read yet</tool_use_error>";
+        assert_eq!(
+            extract_error_messages(content),
+            vec![
+                "make: *** No rule to make target",
+                "",
+                "File has not been read yet"
+            ]
+        );
+    }
+}
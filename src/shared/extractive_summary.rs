@@ -0,0 +1,237 @@
+use super::models::{SearchResult, SessionActivity};
+use super::path_utils::short_uuid;
+use super::session_diff::format_header;
+use super::session_store::{SessionStore, StoredSummary};
+use super::utils::{is_near_duplicate, truncate_content};
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::HashSet;
+
+/// `StoredSummary::source` of summaries made here
+pub const EXTRACTIVE_SOURCE: &str = "extractive";
+
+/// Characters shown per extracted line
+const LINE_MAX_CHARS: usize = 160;
+/// Prompts shorter than this are replies ("yes", "continue"), not asks
+const MIN_ASK_WORDS: usize = 5;
+const MAX_ASKS: usize = 6;
+const MAX_DECISIONS: usize = 8;
+const MAX_ANSWERS: usize = 2;
+const MAX_FILES: usize = 15;
+
+/// Phrases that usually state a decision, matched in lowercase sentences
+const DECISION_CUES: &[&str] = &[
+    "decided",
+    "decision",
+    "let's",
+    "let us",
+    "we'll",
+    "i'll use",
+    "going with",
+    "go with",
+    "instead of",
+    "switch to",
+    "chose",
+    "root cause",
+    "the fix is",
+    "the problem is",
+    "should use",
+    "don't use",
+    "do not use",
+];
+
+/// Assistant text, leaving out `[thinking]`, `[tool]` and similar entries
+fn is_answer(message: &SearchResult) -> bool {
+    message.message_type == "Assistant" && !message.content.starts_with('[')
+}
+
+/// The first prompt, then the longest others, in session order; retyped
+/// prompts count once
+fn key_asks(messages: &[SearchResult]) -> Vec<&SearchResult> {
+    let prompts: Vec<&SearchResult> = messages
+        .iter()
+        .filter(|m| m.is_prompt() && m.is_displayable())
+        .collect();
+    let Some((first, rest)) = prompts.split_first() else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<&SearchResult> = rest
+        .iter()
+        .copied()
+        .filter(|m| m.content.split_whitespace().count() >= MIN_ASK_WORDS)
+        .collect();
+    candidates.sort_by_key(|m| std::cmp::Reverse(m.content.len()));
+
+    let mut asks = vec![*first];
+    for candidate in candidates {
+        if asks.len() == MAX_ASKS {
+            break;
+        }
+        if !asks
+            .iter()
+            .any(|a| is_near_duplicate(a.simhash, candidate.simhash))
+        {
+            asks.push(candidate);
+        }
+    }
+    asks.sort_by_key(|m| m.sequence_num);
+    asks
+}
+
+/// Sentences of prompts and answers that state a decision, first ones first
+fn decisions(messages: &[SearchResult]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for message in messages.iter().filter(|m| m.is_prompt() || is_answer(m)) {
+        for sentence in message
+            .content
+            .split_inclusive(['.', '!', '?', '\n'])
+            .map(str::trim)
+            .filter(|s| s.split_whitespace().count() >= 3)
+        {
+            let lower = sentence.to_lowercase();
+            if DECISION_CUES.iter().any(|cue| lower.contains(cue)) && seen.insert(lower) {
+                found.push(format!(
+                    "{} 💬 {}",
+                    truncate_content(sentence, LINE_MAX_CHARS, true),
+                    short_uuid(&message.uuid)
+                ));
+                if found.len() == MAX_DECISIONS {
+                    return found;
+                }
+            }
+        }
+    }
+    found
+}
+
+/// An offline summary of a session picked from its own text: the first and
+/// largest asks, sentences that read like decisions, the final answers and
+/// the files it modified
+pub fn extractive_summary(activity: &SessionActivity) -> String {
+    let messages = &activity.messages;
+    let mut output = format_header("📝", activity);
+
+    let asks = key_asks(messages);
+    if !asks.is_empty() {
+        output.push_str("\n🙋 Asks\n");
+        for ask in asks {
+            output.push_str(&format!(
+                "  • {} 💬 {}\n",
+                truncate_content(&ask.content, LINE_MAX_CHARS, true),
+                short_uuid(&ask.uuid)
+            ));
+        }
+    }
+
+    let decisions = decisions(messages);
+    if !decisions.is_empty() {
+        output.push_str("\n🧭 Decisions\n");
+        for decision in decisions {
+            output.push_str(&format!("  • {}\n", decision));
+        }
+    }
+
+    let mut answers: Vec<&SearchResult> = messages
+        .iter()
+        .rev()
+        .filter(|m| is_answer(m))
+        .take(MAX_ANSWERS)
+        .collect();
+    answers.reverse();
+    if !answers.is_empty() {
+        output.push_str("\n🏁 Outcome\n");
+        for answer in answers {
+            output.push_str(&format!(
+                "  • {} 💬 {}\n",
+                truncate_content(&answer.content, LINE_MAX_CHARS * 2, true),
+                short_uuid(&answer.uuid)
+            ));
+        }
+    }
+
+    if !activity.files_modified.is_empty() {
+        let files: Vec<&str> = activity.files_modified.iter().map(String::as_str).collect();
+        let mut line = files
+            .iter()
+            .take(MAX_FILES)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if files.len() > MAX_FILES {
+            line.push_str(&format!(" +{} more", files.len() - MAX_FILES));
+        }
+        output.push_str(&format!("\n📄 Files changed: {}\n", line));
+    }
+    output
+}
+
+/// The extractive summary of a session, kept in the session store until the
+/// session grows unless another summary is stored there
+pub fn cached_extractive_summary(
+    store: &SessionStore,
+    activity: &SessionActivity,
+) -> Result<String> {
+    let message_count = activity.messages.len();
+    let kept = store.summary(&activity.session_id)?;
+    if let Some(summary) = &kept
+        && summary.source == EXTRACTIVE_SOURCE
+        && summary.message_count == message_count
+    {
+        return Ok(summary.text.clone());
+    }
+
+    let text = extractive_summary(activity);
+    // A summary stored by an agent or user is worth more than this cache
+    if kept.is_some_and(|summary| summary.source != EXTRACTIVE_SOURCE) {
+        return Ok(text);
+    }
+    store.store_summary(
+        &activity.session_id,
+        &StoredSummary {
+            text: text.clone(),
+            source: EXTRACTIVE_SOURCE.to_string(),
+            message_count,
+            created_at: Utc::now(),
+        },
+    )?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::models::test_message;
+
+    #[test]
+    fn test_extractive_summary_picks_asks_decisions_and_outcome() {
+        let activity = SessionActivity {
+            session_id: "s".to_string(),
+            messages: vec![
+                test_message(0, "User", "fix the flaky login test"),
+                test_message(1, "Assistant", "[thinking] maybe a clock issue"),
+                test_message(2, "Assistant", "The root cause is the clock. Checking."),
+                test_message(3, "User", "yes"),
+                test_message(
+                    4,
+                    "User",
+                    "ok, let's mock the clock instead of sleeping in tests",
+                ),
+                test_message(5, "Assistant", "Done: the test now passes reliably."),
+            ],
+            files_modified: ["/work/tests/login.rs".to_string()].into(),
+            ..Default::default()
+        };
+        let summary = extractive_summary(&activity);
+
+        assert!(summary.contains("• fix the flaky login test"));
+        assert!(summary.contains("• ok, let's mock the clock"));
+        assert!(!summary.contains("• yes"), "Short replies are not asks");
+        assert!(summary.contains("• The root cause is the clock."));
+        assert!(!summary.contains("clock issue"), "Thinking is left out");
+        assert!(summary.contains("🏁 Outcome\n  • The root cause"));
+        assert!(summary.contains("  • Done: the test now passes reliably."));
+        assert!(summary.contains("📄 Files changed: /work/tests/login.rs"));
+    }
+}
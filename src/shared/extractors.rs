@@ -0,0 +1,291 @@
+use super::config::{ExtractorConfig, get_config};
+use super::utils::expand_tilde;
+use anyhow::{Result, anyhow, bail};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use tracing::warn;
+
+/// How long a command extractor may take to answer one message
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Adds values to each message at index time, searchable as
+/// `meta:<name>=<value>` (or `meta:<name>` for any value)
+pub trait MetadataExtractor: Send + Sync {
+    /// Name the values are indexed under
+    fn name(&self) -> &str;
+    fn extract(&self, content: &str) -> Result<Vec<String>>;
+}
+
+/// Every match of a regex, or of its first capture group when it has one
+pub struct RegexExtractor {
+    name: String,
+    regex: Regex,
+}
+
+impl RegexExtractor {
+    pub fn new(name: &str, pattern: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            regex: Regex::new(pattern)?,
+        })
+    }
+}
+
+impl MetadataExtractor for RegexExtractor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn extract(&self, content: &str) -> Result<Vec<String>> {
+        Ok(self
+            .regex
+            .captures_iter(content)
+            .filter_map(|captures| captures.get(1).or_else(|| captures.get(0)))
+            .map(|m| m.as_str().to_string())
+            .collect())
+    }
+}
+
+enum CommandState {
+    NotStarted,
+    /// Its stdin and stdout are served by threads of their own, so a
+    /// program that stops reading or answering can be timed out
+    Running {
+        child: Child,
+        requests: Sender<String>,
+        replies: Receiver<std::io::Result<String>>,
+    },
+}
+
+/// An external program started on first use and kept running: it reads one
+/// `{"content": "..."}` JSON line per message on stdin and answers each with
+/// a JSON array of strings on one line of stdout. One that fails is stopped
+/// and started again for the next message.
+pub struct CommandExtractor {
+    name: String,
+    command: Vec<String>,
+    timeout: Duration,
+    state: Mutex<CommandState>,
+}
+
+impl CommandExtractor {
+    pub fn new(name: &str, command: Vec<String>) -> Result<Self> {
+        if command.is_empty() {
+            bail!("empty command");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            command,
+            timeout: REPLY_TIMEOUT,
+            state: Mutex::new(CommandState::NotStarted),
+        })
+    }
+
+    fn spawn(&self) -> Result<CommandState> {
+        let mut child = Command::new(expand_tilde(&self.command[0]))
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("cannot run {}: {}", self.command[0], e))?;
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        let (requests, pending) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            for request in pending {
+                if writeln!(stdin, "{}", request)
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        let (answer, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            loop {
+                let mut line = String::new();
+                let read = stdout.read_line(&mut line);
+                let ended = !matches!(read, Ok(n) if n > 0);
+                if answer.send(read.map(|_| line)).is_err() || ended {
+                    break;
+                }
+            }
+        });
+        Ok(CommandState::Running {
+            child,
+            requests,
+            replies,
+        })
+    }
+}
+
+impl MetadataExtractor for CommandExtractor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn extract(&self, content: &str) -> Result<Vec<String>> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow!("extractor lock poisoned"))?;
+        if matches!(*state, CommandState::NotStarted) {
+            *state = self.spawn()?;
+        }
+        let CommandState::Running {
+            requests, replies, ..
+        } = &mut *state
+        else {
+            unreachable!("started above");
+        };
+        let exited = || anyhow!("{} exited", self.command[0]);
+        let request = serde_json::json!({ "content": content }).to_string();
+        let result = requests.send(request).map_err(|_| exited()).and_then(|_| {
+            match replies.recv_timeout(self.timeout) {
+                Ok(Ok(line)) if line.is_empty() => Err(exited()),
+                Ok(Ok(line)) => Ok(serde_json::from_str::<Vec<String>>(&line)?),
+                Ok(Err(e)) => Err(e.into()),
+                Err(RecvTimeoutError::Timeout) => Err(anyhow!(
+                    "{} gave no answer within {}s",
+                    self.command[0],
+                    self.timeout.as_secs()
+                )),
+                Err(RecvTimeoutError::Disconnected) => Err(exited()),
+            }
+        });
+        if result.is_err()
+            && let CommandState::Running { mut child, .. } =
+                std::mem::replace(&mut *state, CommandState::NotStarted)
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        result
+    }
+}
+
+/// The extractors run on every indexed message
+#[derive(Default)]
+pub struct ExtractorRegistry {
+    extractors: Vec<Box<dyn MetadataExtractor>>,
+}
+
+impl ExtractorRegistry {
+    pub fn register(&mut self, extractor: Box<dyn MetadataExtractor>) {
+        self.extractors.push(extractor);
+    }
+
+    /// Extractors from `metadata.extractors`; an invalid one is left out with
+    /// a warning
+    pub fn from_config(extractors: &BTreeMap<String, ExtractorConfig>) -> Self {
+        let mut registry = Self::default();
+        for (name, config) in extractors {
+            let extractor: Result<Box<dyn MetadataExtractor>> =
+                match (&config.regex, config.command.is_empty()) {
+                    (Some(pattern), true) => {
+                        RegexExtractor::new(name, pattern).map(|e| Box::new(e) as _)
+                    }
+                    (None, false) => CommandExtractor::new(name, config.command.clone())
+                        .map(|e| Box::new(e) as _),
+                    _ => Err(anyhow!("set one of regex or command")),
+                };
+            match extractor {
+                Ok(extractor) => registry.register(extractor),
+                Err(e) => warn!("Ignoring metadata.extractors.{}: {}", name, e),
+            }
+        }
+        registry
+    }
+
+    /// Terms indexed for a message, lowercased: `name=value` for each value
+    /// found, and `name` alone when there was any. Fails when an extractor
+    /// does, so the message isn't indexed without its values.
+    pub fn terms(&self, content: &str) -> Result<Vec<String>> {
+        let mut terms = BTreeSet::new();
+        for extractor in &self.extractors {
+            let name = extractor.name().to_lowercase();
+            let values = extractor
+                .extract(content)
+                .map_err(|e| anyhow!("metadata extractor {} failed: {}", name, e))?;
+            for value in values.iter().map(|v| v.trim()).filter(|v| !v.is_empty()) {
+                terms.insert(name.clone());
+                terms.insert(format!("{}={}", name, value.to_lowercase()));
+            }
+        }
+        Ok(terms.into_iter().collect())
+    }
+}
+
+static REGISTRY: Lazy<ExtractorRegistry> =
+    Lazy::new(|| ExtractorRegistry::from_config(&get_config().metadata.extractors));
+
+/// `ExtractorRegistry::terms` of the configured extractors
+pub fn custom_metadata_terms(content: &str) -> Result<Vec<String>> {
+    REGISTRY.terms(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_indexes_names_and_values() {
+        let extractors = BTreeMap::from([
+            (
+                "jira".to_string(),
+                ExtractorConfig {
+                    regex: Some(r"\b([A-Z]+-\d+)\b".to_string()),
+                    ..Default::default()
+                },
+            ),
+            ("neither".to_string(), ExtractorConfig::default()),
+        ]);
+        let registry = ExtractorRegistry::from_config(&extractors);
+        assert_eq!(
+            registry
+                .terms("Fixes OPS-12 and OPS-7, see OPS-12")
+                .unwrap(),
+            vec!["jira", "jira=ops-12", "jira=ops-7"]
+        );
+        assert!(registry.terms("nothing to see").unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_extractor_answers_per_line_and_restarts_after_failure() {
+        let script = r#"while read -r line; do
+            case "$line" in *acme*) echo '["Acme"]' ;; *quit*) exit 0 ;; *) echo '[]' ;; esac
+        done"#;
+        let extractor = CommandExtractor::new(
+            "customer",
+            vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+        )
+        .unwrap();
+        assert_eq!(extractor.extract("call with acme").unwrap(), vec!["Acme"]);
+        assert!(extractor.extract("internal").unwrap().is_empty());
+        assert!(extractor.extract("quit").is_err());
+        assert_eq!(extractor.extract("acme again").unwrap(), vec!["Acme"]);
+
+        let missing = CommandExtractor::new("x", vec!["/nonexistent/tagger".to_string()]).unwrap();
+        assert!(missing.extract("acme").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_extractor_times_out() {
+        let mut extractor =
+            CommandExtractor::new("slow", vec!["sleep".to_string(), "60".to_string()]).unwrap();
+        extractor.timeout = Duration::from_millis(200);
+        let started = std::time::Instant::now();
+        assert!(extractor.extract("anything").is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
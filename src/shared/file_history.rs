@@ -0,0 +1,78 @@
+use super::models::FileTouch;
+use super::path_utils::{home_to_tilde, session_jsonl_path, short_uuid};
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+
+/// Message ids listed per path before eliding
+const MAX_IDS_PER_PATH: usize = 8;
+
+/// Format file touches grouped by session, most recently touched session first:
+/// N. 📁 ~/path 🗒️ session 📅 first→last ✏️ edits 👀 reads
+///    📄 ~/path/file 💬 ids (✏️ marks modifying messages)
+pub fn format_file_history(touches: &[FileTouch], limit: usize) -> String {
+    // Group by session, keeping each group's touches in chronological order
+    let mut sessions: Vec<(&str, Vec<&FileTouch>)> = Vec::new();
+    for touch in touches {
+        match sessions.iter_mut().find(|(id, _)| *id == touch.session_id) {
+            Some((_, group)) => group.push(touch),
+            None => sessions.push((&touch.session_id, vec![touch])),
+        }
+    }
+    sessions.sort_by_key(|(_, group)| std::cmp::Reverse(group.last().map(|t| t.timestamp)));
+
+    let mut output = String::new();
+    for (i, (session_id, group)) in sessions.iter().take(limit).enumerate() {
+        let (first, last) = (group[0], group[group.len() - 1]);
+        let edits = group.iter().filter(|t| t.modified).count();
+        let jsonl_path = session_jsonl_path(&first.project_path, session_id).unwrap_or_default();
+
+        output.push_str(&format!(
+            "{}. 📁 {} 🗒️ {} 📅 {}→{} ✏️ {} 👀 {}\n",
+            i + 1,
+            file_hyperlink(&first.project_path, &home_to_tilde(&first.project_path)),
+            file_hyperlink(&jsonl_path.to_string_lossy(), short_uuid(session_id)),
+            local_time(first.timestamp).format("%Y-%m-%d %H:%M"),
+            local_time(last.timestamp).format("%Y-%m-%d %H:%M"),
+            edits,
+            group.len() - edits,
+        ));
+
+        let mut paths: Vec<&str> = group.iter().map(|t| t.path.as_str()).collect();
+        paths.sort();
+        paths.dedup();
+        for path in paths {
+            let ids: Vec<String> = group
+                .iter()
+                .filter(|t| t.path == path)
+                .map(|t| {
+                    let id = short_uuid(&t.uuid);
+                    if t.modified {
+                        format!("✏️{}", id)
+                    } else {
+                        id.to_string()
+                    }
+                })
+                .collect();
+            let more = ids.len().saturating_sub(MAX_IDS_PER_PATH);
+            output.push_str(&format!(
+                "   📄 {} 💬 {}{}\n",
+                home_to_tilde(path),
+                ids[more..].join(" "),
+                if more > 0 {
+                    format!(" (+{} earlier)", more)
+                } else {
+                    String::new()
+                }
+            ));
+        }
+    }
+
+    if sessions.len() > limit {
+        output.push_str(&format!(
+            "+more: {} sessions total, limit={}\n",
+            sessions.len(),
+            limit
+        ));
+    }
+    output
+}
@@ -1,12 +1,173 @@
-use super::config::get_config;
-use super::models::ConversationEntry;
-use anyhow::Result;
+use super::config::{AnalyzerConfig, get_config};
+use super::extractors::custom_metadata_terms;
+use super::metadata::extract_entities;
+use super::models::{ConversationEntry, MessageType, SearchResult};
+use super::parser::JsonlParser;
+use super::path_utils::{alias_project_path, cwd_facet};
+use super::transcripts::CLAUDE_CODE_SOURCE;
+use super::utils::{estimate_tokens, simhash, truncate_content};
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::path::Path;
-use tantivy::schema::{FAST, Field, INDEXED, STORED, Schema, SchemaBuilder, TEXT};
+use tantivy::schema::{
+    FAST, FacetOptions, Field, FieldType, INDEXED, IndexRecordOption, STORED, STRING, Schema,
+    SchemaBuilder, TEXT, TextFieldIndexing, TextOptions,
+};
+use tantivy::tokenizer::{
+    Language, LowerCaser, RegexTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer,
+    StopWordFilter, TextAnalyzer, TextAnalyzerBuilder,
+};
 use tantivy::{Index, IndexWriter, Term, doc};
 
 /// Current schema version - increment when schema changes to trigger rebuild
-pub const SCHEMA_VERSION: u32 = 2;
+pub const SCHEMA_VERSION: u32 = 26;
+
+/// Message id of the summary stored for a session, derived from the session
+/// id so storing another one replaces it
+pub fn stored_summary_uuid(session_id: &str) -> String {
+    // FNV-1a twice over: stable across builds, as it is stored
+    let hash = |seed: u64| {
+        session_id.bytes().fold(seed, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    };
+    uuid::Uuid::from_u64_pair(hash(0xcbf2_9ce4_8422_2325), hash(0x5355_4d4d_4152_5953)).to_string()
+}
+
+/// Characters of a message kept in the index under `index.lazy_content`,
+/// shown once its JSONL line is gone
+pub(crate) const CONTENT_PREVIEW_CHARS: usize = 500;
+
+/// Tokenizers named with this prefix are built from `index.analyzer`
+const CONTENT_TOKENIZER_PREFIX: &str = "conversation_";
+
+/// Tokenizer of the content and summary fields: Tantivy's default unless
+/// the analyzer is configured, then a name derived from its settings so an
+/// index built with other settings fails `validate_schema`
+pub fn content_tokenizer_name(analyzer: &AnalyzerConfig) -> String {
+    if analyzer.is_default() {
+        return "default".to_string();
+    }
+    let mut stopwords: Vec<String> = analyzer
+        .stopwords
+        .iter()
+        .map(|w| w.to_lowercase())
+        .collect();
+    stopwords.sort();
+    let digest = Sha256::digest(format!(
+        "{}|{}|{}|{}",
+        analyzer.stemming,
+        analyzer.language.to_lowercase(),
+        stopwords.join(","),
+        analyzer.cjk
+    ));
+    let hex: String = digest[..6].iter().map(|b| format!("{b:02x}")).collect();
+    format!("{CONTENT_TOKENIZER_PREFIX}{hex}")
+}
+
+fn stemmer_language(name: &str) -> Result<Language> {
+    Ok(match name.to_lowercase().as_str() {
+        "arabic" => Language::Arabic,
+        "danish" => Language::Danish,
+        "dutch" => Language::Dutch,
+        "english" => Language::English,
+        "finnish" => Language::Finnish,
+        "french" => Language::French,
+        "german" => Language::German,
+        "greek" => Language::Greek,
+        "hungarian" => Language::Hungarian,
+        "italian" => Language::Italian,
+        "norwegian" => Language::Norwegian,
+        "portuguese" => Language::Portuguese,
+        "romanian" => Language::Romanian,
+        "russian" => Language::Russian,
+        "spanish" => Language::Spanish,
+        "swedish" => Language::Swedish,
+        "tamil" => Language::Tamil,
+        "turkish" => Language::Turkish,
+        _ => return Err(anyhow!("Unknown index.analyzer.language '{}'", name)),
+    })
+}
+
+#[cfg(feature = "cjk")]
+fn base_tokenizer(analyzer: &AnalyzerConfig) -> Result<TextAnalyzerBuilder> {
+    Ok(if analyzer.cjk {
+        TextAnalyzer::builder(super::cjk::CjkBigramTokenizer::default()).dynamic()
+    } else {
+        TextAnalyzer::builder(SimpleTokenizer::default()).dynamic()
+    })
+}
+
+#[cfg(not(feature = "cjk"))]
+fn base_tokenizer(analyzer: &AnalyzerConfig) -> Result<TextAnalyzerBuilder> {
+    if analyzer.cjk {
+        return Err(anyhow!(
+            "index.analyzer.cjk needs a build with `--features cjk`"
+        ));
+    }
+    Ok(TextAnalyzer::builder(SimpleTokenizer::default()).dynamic())
+}
+
+/// The default tokenizer (or CJK bigrams) followed by the configured
+/// stopwords and stemmer
+fn content_analyzer(analyzer: &AnalyzerConfig) -> Result<TextAnalyzer> {
+    let mut builder = base_tokenizer(analyzer)?
+        .filter_dynamic(RemoveLongFilter::limit(40))
+        .filter_dynamic(LowerCaser);
+    if !analyzer.stopwords.is_empty() {
+        builder = builder.filter_dynamic(StopWordFilter::remove(
+            analyzer.stopwords.iter().map(|w| w.to_lowercase()),
+        ));
+    }
+    if analyzer.stemming {
+        builder = builder.filter_dynamic(Stemmer::new(stemmer_language(&analyzer.language)?));
+    }
+    Ok(builder.build())
+}
+
+/// Tokenizer of the cwd field: one lowercased token per directory name, so
+/// `path:infra/terraform` is a phrase over consecutive directories
+pub const PATH_TOKENIZER: &str = "path_segments";
+
+fn path_analyzer() -> Result<TextAnalyzer> {
+    Ok(TextAnalyzer::builder(RegexTokenizer::new(r"[^/\\]+")?)
+        .filter(LowerCaser)
+        .build())
+}
+
+/// Register the configured analyzer under every content tokenizer name the
+/// index uses. An index built with older settings still opens this way,
+/// analyzed with the current ones, until it is rebuilt.
+pub fn register_tokenizers(index: &Index) -> Result<()> {
+    index
+        .tokenizers()
+        .register(PATH_TOKENIZER, path_analyzer()?);
+    let analyzer = &get_config().index.analyzer;
+    let schema = index.schema();
+    for (_, entry) in schema.fields() {
+        if let FieldType::Str(options) = entry.field_type()
+            && let Some(indexing) = options.get_indexing_options()
+            && indexing.tokenizer().starts_with(CONTENT_TOKENIZER_PREFIX)
+        {
+            index
+                .tokenizers()
+                .register(indexing.tokenizer(), content_analyzer(analyzer)?);
+        }
+    }
+    Ok(())
+}
+
+/// Name of the tokenizer a text field is indexed with
+pub fn tokenizer_of(schema: &Schema, field_name: &str) -> Option<String> {
+    let field = schema.get_field(field_name).ok()?;
+    match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) => options
+            .get_indexing_options()
+            .map(|indexing| indexing.tokenizer().to_string()),
+        _ => None,
+    }
+}
 
 pub struct IndexFields {
     pub uuid_field: Field,
@@ -14,6 +175,7 @@ pub struct IndexFields {
     pub content_field: Field,
     pub project_field: Field,
     pub session_field: Field,
+    pub session_key_field: Field,
     pub timestamp_field: Field,
     pub message_type_field: Field,
     pub model_field: Field,
@@ -22,15 +184,45 @@ pub struct IndexFields {
     pub tools_mentioned_field: Field,
     pub has_code_field: Field,
     pub has_error_field: Field,
+    pub has_attachments_field: Field,
     pub cwd_field: Field,
+    pub cwd_tree_field: Field,
     pub sequence_num_field: Field,
     pub is_sidechain_field: Field,
     pub agent_id_field: Field,
+    pub source_host_field: Field,
+    pub word_count_field: Field,
+    pub token_count_field: Field,
+    pub simhash_field: Field,
+    pub input_tokens_field: Field,
+    pub output_tokens_field: Field,
+    pub cache_creation_tokens_field: Field,
+    pub cache_read_tokens_field: Field,
+    pub tool_name_field: Field,
+    pub tool_error_field: Field,
+    pub files_touched_field: Field,
+    pub files_modified_field: Field,
+    pub git_branch_field: Field,
+    pub shell_commands_field: Field,
+    pub summary_field: Field,
+    pub message_kind_field: Field,
+    pub stored_content_field: Field,
+    pub content_preview_field: Field,
+    pub source_file_field: Field,
+    pub source_root_field: Field,
+    pub source_field: Field,
+    pub source_offset_field: Field,
+    pub urls_field: Field,
+    pub file_mentions_field: Field,
+    pub tickets_field: Field,
+    pub custom_metadata_field: Field,
 }
 
 pub struct SearchIndexer {
     writer: IndexWriter,
     fields: IndexFields,
+    /// Moved project paths, applied to `cwd` as entries are indexed
+    project_aliases: HashMap<String, String>,
 }
 
 impl SearchIndexer {
@@ -38,13 +230,23 @@ impl SearchIndexer {
     pub fn build_schema() -> (Schema, IndexFields) {
         let mut schema_builder = SchemaBuilder::default();
 
-        // Primary key for deduplication
-        let uuid_field = schema_builder.add_text_field("uuid", TEXT | STORED | FAST);
+        // Primary key: exact, so re-indexing replaces a message by its uuid
+        let uuid_field = schema_builder.add_text_field("uuid", STRING | STORED | FAST);
         let parent_uuid_field = schema_builder.add_text_field("parent_uuid", TEXT | STORED | FAST);
 
-        let content_field = schema_builder.add_text_field("content", TEXT | STORED);
+        // Prose fields use index.analyzer
+        let prose_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(&content_tokenizer_name(&get_config().index.analyzer))
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        // Searched only; the text shown is stored_content or the JSONL line
+        let content_field = schema_builder.add_text_field("content", prose_options.clone());
         let project_field = schema_builder.add_text_field("project", TEXT | STORED | FAST);
         let session_field = schema_builder.add_text_field("session_id", TEXT | STORED | FAST);
+        // Untokenized session id for exact lookups and deletes; session_id
+        // stays tokenized so `session_id:` queries can match a segment
+        let session_key_field = schema_builder.add_text_field("session_key", STRING);
         let timestamp_field = schema_builder.add_date_field("timestamp", INDEXED | STORED | FAST);
         let message_type_field =
             schema_builder.add_text_field("message_type", TEXT | STORED | FAST);
@@ -57,12 +259,78 @@ impl SearchIndexer {
             schema_builder.add_text_field("tools_mentioned", TEXT | STORED | FAST);
         let has_code_field = schema_builder.add_bool_field("has_code", INDEXED | STORED | FAST);
         let has_error_field = schema_builder.add_bool_field("has_error", INDEXED | STORED | FAST);
-        let cwd_field = schema_builder.add_text_field("cwd", TEXT | STORED | FAST);
+        let has_attachments_field =
+            schema_builder.add_bool_field("has_attachments", INDEXED | STORED | FAST);
+        let cwd_field = schema_builder.add_text_field(
+            "cwd",
+            TextOptions::default()
+                .set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer(PATH_TOKENIZER)
+                        .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                )
+                .set_stored()
+                .set_fast(None),
+        );
+        // The cwd again as a facet, for messages per subdirectory
+        let cwd_tree_field = schema_builder.add_facet_field("cwd_tree", FacetOptions::default());
         let sequence_num_field =
             schema_builder.add_u64_field("sequence_num", INDEXED | STORED | FAST);
         let is_sidechain_field =
             schema_builder.add_bool_field("is_sidechain", INDEXED | STORED | FAST);
         let agent_id_field = schema_builder.add_text_field("agent_id", TEXT | STORED | FAST);
+        // Exact-match host of imported history, empty for local
+        let source_host_field =
+            schema_builder.add_text_field("source_host", STRING | STORED | FAST);
+        // Whitespace-separated words in content, summed by stats without reading docs
+        let word_count_field = schema_builder.add_u64_field("word_count", STORED | FAST);
+        let token_count_field = schema_builder.add_u64_field("token_count", STORED | FAST);
+        // Content fingerprint for collapsing near-duplicate results
+        let simhash_field = schema_builder.add_u64_field("simhash", STORED);
+        // API usage from the JSONL (zero except on the first entry of each response)
+        let input_tokens_field = schema_builder.add_u64_field("input_tokens", STORED | FAST);
+        let output_tokens_field = schema_builder.add_u64_field("output_tokens", STORED | FAST);
+        let cache_creation_tokens_field =
+            schema_builder.add_u64_field("cache_creation_tokens", STORED | FAST);
+        let cache_read_tokens_field =
+            schema_builder.add_u64_field("cache_read_tokens", STORED | FAST);
+        // Tools actually called (tool_use) and tools whose tool_result failed, space-joined
+        let tool_name_field = schema_builder.add_text_field("tool_name", TEXT | STORED | FAST);
+        let tool_error_field = schema_builder.add_text_field("tool_error", TEXT | STORED | FAST);
+        // Exact file paths from file tools, one value per path
+        let files_touched_field =
+            schema_builder.add_text_field("files_touched", STRING | STORED | FAST);
+        let files_modified_field =
+            schema_builder.add_text_field("files_modified", STRING | STORED | FAST);
+        // Exact-match git branch the session was on, empty outside a repo
+        let git_branch_field = schema_builder.add_text_field("git_branch", STRING | STORED | FAST);
+        // JSON list of Bash commands with exit status; found via tool_name:bash
+        let shell_commands_field = schema_builder.add_text_field("shell_commands", STORED);
+        // Text of Claude Code's own session summaries, empty on other messages;
+        // stored once, in stored_content
+        let summary_field = schema_builder.add_text_field("summary", prose_options);
+        // `ConversationEntry::kind`, read by search.type_boosts at query time
+        let message_kind_field = schema_builder.add_text_field("message_kind", STRING | FAST);
+        // Copy of content, empty under index.lazy_content
+        let stored_content_field = schema_builder.add_text_field("stored_content", STORED);
+        // Start of content when stored_content is empty, for deleted JSONL
+        let content_preview_field = schema_builder.add_text_field("content_preview", STORED);
+        // Where content is read back from when stored_content is empty, and
+        // what a changed file's old documents are deleted by
+        let source_file_field = schema_builder.add_text_field("source_file", STRING | STORED);
+        let source_offset_field = schema_builder.add_u64_field("source_offset", STORED);
+        // Claude dir or imports dir the source file was found under
+        let source_root_field = schema_builder.add_text_field("source_root", STRING | STORED);
+        // Format the entry was parsed from: claude-code or a transcript export
+        let source_field = schema_builder.add_text_field("source", STRING | STORED | FAST);
+        // URLs, file paths and ticket ids mentioned in content, one value each,
+        // tokenized so `url:`/`file:`/`ticket:` phrases match part of one
+        let urls_field = schema_builder.add_text_field("urls", TEXT | STORED);
+        let file_mentions_field = schema_builder.add_text_field("file_mentions", TEXT | STORED);
+        let tickets_field = schema_builder.add_text_field("tickets", TEXT | STORED);
+        // `name` and `name=value` terms from metadata.extractors, for `meta:`
+        let custom_metadata_field =
+            schema_builder.add_text_field("custom_metadata", STRING | STORED);
 
         let schema = schema_builder.build();
         let fields = IndexFields {
@@ -71,6 +339,7 @@ impl SearchIndexer {
             content_field,
             project_field,
             session_field,
+            session_key_field,
             timestamp_field,
             message_type_field,
             model_field,
@@ -79,10 +348,38 @@ impl SearchIndexer {
             tools_mentioned_field,
             has_code_field,
             has_error_field,
+            has_attachments_field,
             cwd_field,
+            cwd_tree_field,
             sequence_num_field,
             is_sidechain_field,
             agent_id_field,
+            source_host_field,
+            word_count_field,
+            token_count_field,
+            simhash_field,
+            input_tokens_field,
+            output_tokens_field,
+            cache_creation_tokens_field,
+            cache_read_tokens_field,
+            tool_name_field,
+            tool_error_field,
+            files_touched_field,
+            files_modified_field,
+            git_branch_field,
+            shell_commands_field,
+            summary_field,
+            message_kind_field,
+            stored_content_field,
+            content_preview_field,
+            source_file_field,
+            source_root_field,
+            source_field,
+            source_offset_field,
+            urls_field,
+            file_mentions_field,
+            tickets_field,
+            custom_metadata_field,
         };
 
         (schema, fields)
@@ -93,8 +390,34 @@ impl SearchIndexer {
         let index = Index::open_in_dir(index_path)?;
         let actual_schema = index.schema();
 
-        // Check required fields exist - uuid is required in v2 schema
+        // Check required fields exist - has_attachments is required in v19 schema
         let required_fields = [
+            "has_attachments",
+            "source",
+            "source_root",
+            "session_key",
+            "stored_content",
+            "content_preview",
+            "source_file",
+            "source_offset",
+            "message_kind",
+            "summary",
+            "shell_commands",
+            "git_branch",
+            "files_touched",
+            "files_modified",
+            "tool_name",
+            "tool_error",
+            "cache_read_tokens",
+            "token_count",
+            "simhash",
+            "word_count",
+            "source_host",
+            "cwd_tree",
+            "custom_metadata",
+            "urls",
+            "file_mentions",
+            "tickets",
             "uuid",
             "content",
             "project",
@@ -110,7 +433,14 @@ impl SearchIndexer {
             }
         }
 
-        Ok(true)
+        // Built before cwd was split into directory names
+        if tokenizer_of(&actual_schema, "cwd").as_deref() != Some(PATH_TOKENIZER) {
+            return Ok(false);
+        }
+
+        // Built with another index.analyzer
+        let expected = content_tokenizer_name(&get_config().index.analyzer);
+        Ok(tokenizer_of(&actual_schema, "content").as_deref() == Some(expected.as_str()))
     }
 
     pub fn new(index_path: &Path) -> Result<Self> {
@@ -118,14 +448,20 @@ impl SearchIndexer {
 
         std::fs::create_dir_all(index_path)?;
         let index = Index::create_in_dir(index_path, schema)?;
+        register_tokenizers(&index)?;
         let config = get_config();
         let writer = index.writer(config.get_writer_heap_size())?;
 
-        Ok(Self { writer, fields })
+        Ok(Self {
+            writer,
+            fields,
+            project_aliases: get_config().project_aliases.clone(),
+        })
     }
 
     pub fn open(index_path: &Path) -> Result<Self> {
         let index = Index::open_in_dir(index_path)?;
+        register_tokenizers(&index)?;
         let schema = index.schema();
 
         // Get fields from the existing schema
@@ -135,6 +471,7 @@ impl SearchIndexer {
             content_field: schema.get_field("content")?,
             project_field: schema.get_field("project")?,
             session_field: schema.get_field("session_id")?,
+            session_key_field: schema.get_field("session_key")?,
             timestamp_field: schema.get_field("timestamp")?,
             message_type_field: schema.get_field("message_type")?,
             model_field: schema.get_field("model")?,
@@ -143,35 +480,178 @@ impl SearchIndexer {
             tools_mentioned_field: schema.get_field("tools_mentioned")?,
             has_code_field: schema.get_field("has_code")?,
             has_error_field: schema.get_field("has_error")?,
+            has_attachments_field: schema.get_field("has_attachments")?,
             cwd_field: schema.get_field("cwd")?,
+            cwd_tree_field: schema.get_field("cwd_tree")?,
             sequence_num_field: schema.get_field("sequence_num")?,
             is_sidechain_field: schema.get_field("is_sidechain")?,
             agent_id_field: schema.get_field("agent_id")?,
+            source_host_field: schema.get_field("source_host")?,
+            word_count_field: schema.get_field("word_count")?,
+            token_count_field: schema.get_field("token_count")?,
+            simhash_field: schema.get_field("simhash")?,
+            input_tokens_field: schema.get_field("input_tokens")?,
+            output_tokens_field: schema.get_field("output_tokens")?,
+            cache_creation_tokens_field: schema.get_field("cache_creation_tokens")?,
+            cache_read_tokens_field: schema.get_field("cache_read_tokens")?,
+            tool_name_field: schema.get_field("tool_name")?,
+            tool_error_field: schema.get_field("tool_error")?,
+            files_touched_field: schema.get_field("files_touched")?,
+            files_modified_field: schema.get_field("files_modified")?,
+            git_branch_field: schema.get_field("git_branch")?,
+            shell_commands_field: schema.get_field("shell_commands")?,
+            summary_field: schema.get_field("summary")?,
+            message_kind_field: schema.get_field("message_kind")?,
+            stored_content_field: schema.get_field("stored_content")?,
+            content_preview_field: schema.get_field("content_preview")?,
+            source_file_field: schema.get_field("source_file")?,
+            source_root_field: schema.get_field("source_root")?,
+            source_field: schema.get_field("source")?,
+            source_offset_field: schema.get_field("source_offset")?,
+            urls_field: schema.get_field("urls")?,
+            file_mentions_field: schema.get_field("file_mentions")?,
+            tickets_field: schema.get_field("tickets")?,
+            custom_metadata_field: schema.get_field("custom_metadata")?,
         };
 
         let config = get_config();
         let writer = index.writer(config.get_writer_heap_size())?;
 
-        Ok(Self { writer, fields })
+        Ok(Self {
+            writer,
+            fields,
+            project_aliases: get_config().project_aliases.clone(),
+        })
     }
 
-    /// Delete all documents for a session before re-indexing
+    /// Delete all documents of a session
     pub fn delete_session(&mut self, session_id: &str) -> Result<()> {
-        // TEXT field tokenizes at hyphens, so use first segment for deletion
-        // UUID first segments are unique enough to avoid false matches
-        let first_segment = session_id.split('-').next().unwrap_or(session_id);
-        let term = Term::from_field_text(self.fields.session_field, first_segment);
+        let term = Term::from_field_text(self.fields.session_key_field, session_id);
+        self.writer.delete_term(term);
+        Ok(())
+    }
+
+    /// Delete the documents parsed from a JSONL file before re-indexing it
+    pub fn delete_file(&mut self, path: &Path) -> Result<()> {
+        let term = Term::from_field_text(self.fields.source_file_field, &path.to_string_lossy());
         self.writer.delete_term(term);
         Ok(())
     }
 
+    /// Commit pending deletes/adds
+    pub fn commit(&mut self) -> Result<()> {
+        self.writer.commit()?;
+        Ok(())
+    }
+
+    /// Index a summary stored for a session as its latest Summary message,
+    /// so `summary:` and plain searches match it. `messages` are the
+    /// session's, to place it after them.
+    pub fn index_session_summary(&mut self, messages: &[SearchResult], text: &str) -> Result<()> {
+        let (Some(first), Some(timestamp)) =
+            (messages.first(), messages.iter().map(|m| m.timestamp).max())
+        else {
+            return Err(anyhow!("No messages to summarize"));
+        };
+        let uuid = stored_summary_uuid(&first.session_id);
+        let sequence_num = messages
+            .iter()
+            .filter(|m| m.uuid != uuid)
+            .map(|m| m.sequence_num + 1)
+            .max()
+            .unwrap_or(0);
+        self.index_conversations(vec![ConversationEntry {
+            uuid,
+            parent_uuid: None,
+            session_id: first.session_id.clone(),
+            project_path: first.project.clone(),
+            timestamp,
+            message_type: MessageType::Summary,
+            content: text.to_string(),
+            model: None,
+            cwd: Some(first.project_path.clone()),
+            sequence_num,
+            is_sidechain: false,
+            agent_id: None,
+            source: first.source.clone(),
+            source_host: first.source_host.clone(),
+            source_root: None,
+            git_branch: None,
+            usage: None,
+            source_file: None,
+            source_offset: 0,
+            technologies: Vec::new(),
+            has_code: false,
+            code_languages: Vec::new(),
+            has_error: false,
+            has_attachments: false,
+            tools_mentioned: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_errors: Vec::new(),
+            files_touched: Vec::new(),
+            files_modified: Vec::new(),
+            shell_commands: Vec::new(),
+        }])
+    }
+
+    /// Replace the project aliases read from the global config, for callers
+    /// that changed them after startup
+    pub fn set_project_aliases(&mut self, aliases: HashMap<String, String>) {
+        self.project_aliases = aliases;
+    }
+
     pub fn index_conversations(&mut self, entries: Vec<ConversationEntry>) -> Result<()> {
-        for entry in entries {
-            let doc = doc!(
+        let lazy_content = get_config().index.lazy_content;
+        // Before anything is added: an extractor failing fails the batch
+        let custom_metadata = entries
+            .iter()
+            .map(|entry| custom_metadata_terms(&entry.content))
+            .collect::<Result<Vec<_>>>()?;
+        for (mut entry, custom_metadata) in entries.into_iter().zip(custom_metadata) {
+            if let Some(moved) = entry
+                .cwd
+                .as_deref()
+                .and_then(|cwd| alias_project_path(cwd, &self.project_aliases))
+            {
+                entry.project_path = JsonlParser.extract_project_name_from_path(&moved);
+                entry.cwd = Some(moved);
+            }
+            let word_count = entry.content.split_whitespace().count() as u64;
+            let kind = entry.kind();
+            let token_count = estimate_tokens(&entry.content) as u64;
+            let simhash = simhash(&entry.content);
+            let usage = entry.usage.unwrap_or_default();
+            let summary = if matches!(entry.message_type, MessageType::Summary) {
+                entry.content.clone()
+            } else {
+                String::new()
+            };
+            // Only JSONL lines can be read back; exports keep their copy
+            let (stored_content, content_preview) = if lazy_content
+                && entry.source_file.is_some()
+                && entry.source == CLAUDE_CODE_SOURCE
+            {
+                let preview = truncate_content(&entry.content, CONTENT_PREVIEW_CHARS, false);
+                (String::new(), preview)
+            } else {
+                (entry.content.clone(), String::new())
+            };
+            let shell_commands = if entry.shell_commands.is_empty() {
+                String::new()
+            } else {
+                serde_json::to_string(&entry.shell_commands)?
+            };
+            let cwd_tree = entry.cwd.as_deref().map(cwd_facet);
+            let entities = extract_entities(&entry.content);
+            // Upsert: drop any earlier copy of this message, from any file
+            self.writer
+                .delete_term(Term::from_field_text(self.fields.uuid_field, &entry.uuid));
+            let mut doc = doc!(
                 self.fields.uuid_field => entry.uuid,
                 self.fields.parent_uuid_field => entry.parent_uuid.unwrap_or_default(),
                 self.fields.content_field => entry.content,
                 self.fields.project_field => entry.project_path,
+                self.fields.session_key_field => entry.session_id.clone(),
                 self.fields.session_field => entry.session_id,
                 self.fields.timestamp_field => tantivy::DateTime::from_timestamp_millis(entry.timestamp.timestamp_millis()),
                 self.fields.message_type_field => format!("{:?}", entry.message_type),
@@ -181,12 +661,55 @@ impl SearchIndexer {
                 self.fields.tools_mentioned_field => entry.tools_mentioned.join(" "),
                 self.fields.has_code_field => entry.has_code,
                 self.fields.has_error_field => entry.has_error,
+                self.fields.has_attachments_field => entry.has_attachments,
                 self.fields.cwd_field => entry.cwd.unwrap_or_else(|| "unknown".to_string()),
                 self.fields.sequence_num_field => entry.sequence_num as u64,
                 self.fields.is_sidechain_field => entry.is_sidechain,
                 self.fields.agent_id_field => entry.agent_id.unwrap_or_default(),
+                self.fields.source_host_field => entry.source_host.unwrap_or_default(),
+                self.fields.git_branch_field => entry.git_branch.unwrap_or_default(),
+                self.fields.shell_commands_field => shell_commands,
+                self.fields.summary_field => summary,
+                self.fields.message_kind_field => kind,
+                self.fields.stored_content_field => stored_content,
+                self.fields.content_preview_field => content_preview,
+                self.fields.source_file_field => entry.source_file.unwrap_or_default(),
+                self.fields.source_root_field => entry.source_root.unwrap_or_default(),
+                self.fields.source_field => entry.source,
+                self.fields.source_offset_field => entry.source_offset,
+                self.fields.word_count_field => word_count,
+                self.fields.token_count_field => token_count,
+                self.fields.simhash_field => simhash,
+                self.fields.input_tokens_field => usage.input_tokens,
+                self.fields.output_tokens_field => usage.output_tokens,
+                self.fields.cache_creation_tokens_field => usage.cache_creation_tokens,
+                self.fields.cache_read_tokens_field => usage.cache_read_tokens,
+                self.fields.tool_name_field => entry.tool_calls.join(" "),
+                self.fields.tool_error_field => entry.tool_errors.join(" "),
             );
 
+            if let Some(facet) = cwd_tree {
+                doc.add_facet(self.fields.cwd_tree_field, facet);
+            }
+            for path in &entry.files_touched {
+                doc.add_text(self.fields.files_touched_field, path);
+            }
+            for path in &entry.files_modified {
+                doc.add_text(self.fields.files_modified_field, path);
+            }
+            for term in &custom_metadata {
+                doc.add_text(self.fields.custom_metadata_field, term);
+            }
+            for (field, values) in [
+                (self.fields.urls_field, &entities.urls),
+                (self.fields.file_mentions_field, &entities.files),
+                (self.fields.tickets_field, &entities.tickets),
+            ] {
+                for value in values {
+                    doc.add_text(field, value);
+                }
+            }
+
             self.writer.add_document(doc)?;
         }
 
@@ -194,3 +717,38 @@ impl SearchIndexer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_analyzer_settings() {
+        let mut config = AnalyzerConfig::default();
+        assert_eq!(content_tokenizer_name(&config), "default");
+
+        config.stemming = true;
+        config.stopwords = vec!["Kubectl".to_string()];
+        let name = content_tokenizer_name(&config);
+        assert!(name.starts_with(CONTENT_TOKENIZER_PREFIX));
+        config.language = "French".to_string();
+        assert_ne!(
+            content_tokenizer_name(&config),
+            name,
+            "Language is part of the name"
+        );
+        config.language = "english".to_string();
+        assert_eq!(content_tokenizer_name(&config), name);
+
+        let mut analyzer = content_analyzer(&config).unwrap();
+        let mut stream = analyzer.token_stream("Deploying kubectl pods");
+        let mut words = Vec::new();
+        while stream.advance() {
+            words.push(stream.token().text.clone());
+        }
+        assert_eq!(words, ["deploy", "pod"]);
+
+        config.language = "klingon".to_string();
+        assert!(content_analyzer(&config).is_err());
+    }
+}
@@ -1,8 +1,11 @@
+use super::config::{MetadataConfig, get_config};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::warn;
 
-static TECHNOLOGY_PATTERNS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
+fn builtin_technology_patterns() -> HashMap<&'static str, Regex> {
     let mut map = HashMap::new();
 
     // Programming languages
@@ -127,9 +130,9 @@ static TECHNOLOGY_PATTERNS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
     map.insert("lucene", Regex::new(r"(?i)\blucene\b").unwrap());
 
     map
-});
+}
 
-static TOOL_PATTERNS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
+fn builtin_tool_patterns() -> HashMap<&'static str, Regex> {
     let mut map = HashMap::new();
 
     // CLI tools and commands commonly used in Claude Code
@@ -161,8 +164,67 @@ static TOOL_PATTERNS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
     );
 
     map
+}
+
+static TECHNOLOGY_PATTERNS: Lazy<HashMap<String, Regex>> = Lazy::new(|| {
+    let config = get_config();
+    with_custom(
+        builtin_technology_patterns(),
+        &config.metadata.patterns.technologies,
+        "technologies",
+    )
 });
 
+static TOOL_PATTERNS: Lazy<HashMap<String, Regex>> = Lazy::new(|| {
+    let config = get_config();
+    with_custom(
+        builtin_tool_patterns(),
+        &config.metadata.patterns.tools,
+        "tools",
+    )
+});
+
+/// Built-in patterns with `metadata.patterns.<section>` layered over them.
+/// A regex that doesn't compile is left out with a warning.
+fn with_custom(
+    builtin: HashMap<&'static str, Regex>,
+    custom: &BTreeMap<String, String>,
+    section: &str,
+) -> HashMap<String, Regex> {
+    let mut patterns: HashMap<String, Regex> = builtin
+        .into_iter()
+        .map(|(name, regex)| (name.to_string(), regex))
+        .collect();
+    for (name, pattern) in custom {
+        if pattern.is_empty() {
+            patterns.remove(name);
+            continue;
+        }
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                patterns.insert(name.clone(), regex);
+            }
+            Err(e) => warn!("Ignoring metadata.patterns.{}.{}: {}", section, name, e),
+        }
+    }
+    patterns
+}
+
+/// Identifies the configured patterns and extractors, empty without any.
+/// Stored with the index so changing them rebuilds it, as messages are
+/// tagged at index time.
+pub fn metadata_config_digest(config: &MetadataConfig) -> String {
+    if config.is_empty() {
+        return String::new();
+    }
+    let patterns = &config.patterns;
+    let digest = Sha256::digest(format!(
+        "{:?}|{:?}|{:?}",
+        patterns.technologies, patterns.tools, config.extractors
+    ));
+    digest[..6].iter().map(|b| format!("{b:02x}")).collect()
+}
+
 static CODE_BLOCK_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"```(\w+)?\n").unwrap());
 
 static LANGUAGE_PATTERNS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
@@ -190,11 +252,71 @@ static ERROR_PATTERNS: Lazy<Regex> = Lazy::new(|| {
         .unwrap()
 });
 
+static URL_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\bhttps?://[^\s<>"'`()\[\]{}]+"#).unwrap());
+
+/// Paths with a directory and an extension (`src/main.rs`, `~/.config/x.yaml`),
+/// or bare names with a common source or config extension
+static FILE_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?:~|\.{1,2})?/?(?:[\w.-]+/)+[\w.-]*\w\.[A-Za-z0-9]{1,8}\b|\b[\w-]+\.(?:rs|py|js|jsx|ts|tsx|go|java|kt|c|h|cc|cpp|hpp|rb|php|cs|swift|sh|toml|yaml|yml|json|md|sql|tf|lock)\b",
+    )
+    .unwrap()
+});
+
+/// `PROJ-2291` style issue keys and `#456` issue numbers
+static TICKET_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b[A-Z][A-Z0-9]{1,9}-\d+\b|(?:^|[\s(\[])(#\d+)\b").unwrap());
+
+/// Prefixes of `XXX-123` words that name standards and algorithms, not tickets
+const NOT_TICKET_PREFIXES: &[&str] = &["UTF", "SHA", "ISO", "RFC", "AES", "TLS", "MD", "HTTP"];
+
+/// URLs, file paths and ticket ids mentioned in a message, each once, in
+/// order of appearance
+#[derive(Debug, Default, PartialEq)]
+pub struct Entities {
+    pub urls: Vec<String>,
+    pub files: Vec<String>,
+    pub tickets: Vec<String>,
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !values.iter().any(|v| v == value) {
+        values.push(value.to_string());
+    }
+}
+
+pub fn extract_entities(content: &str) -> Entities {
+    let mut entities = Entities::default();
+    for url in URL_PATTERN.find_iter(content) {
+        let url = url
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        push_unique(&mut entities.urls, url);
+    }
+    // Paths inside URLs aren't files on disk
+    let without_urls = URL_PATTERN.replace_all(content, " ");
+    for file in FILE_PATTERN.find_iter(&without_urls) {
+        push_unique(&mut entities.files, file.as_str());
+    }
+    for captures in TICKET_PATTERN.captures_iter(&without_urls) {
+        let ticket = captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map_or("", |m| m.as_str());
+        let prefix = ticket.split('-').next().unwrap_or_default();
+        if !NOT_TICKET_PREFIXES.contains(&prefix) {
+            push_unique(&mut entities.tickets, ticket);
+        }
+    }
+    entities
+}
+
 pub fn extract_technologies(content: &str) -> Vec<String> {
     let mut technologies = HashSet::new();
     for (tech, pattern) in TECHNOLOGY_PATTERNS.iter() {
         if pattern.is_match(content) {
-            technologies.insert(tech.to_string());
+            technologies.insert(tech.clone());
         }
     }
     technologies.into_iter().collect()
@@ -204,7 +326,7 @@ pub fn extract_tools_mentioned(content: &str) -> Vec<String> {
     let mut tools = HashSet::new();
     for (tool, pattern) in TOOL_PATTERNS.iter() {
         if pattern.is_match(content) {
-            tools.insert(tool.to_string());
+            tools.insert(tool.clone());
         }
     }
     tools.into_iter().collect()
@@ -250,6 +372,41 @@ mod tests {
         assert!(techs.contains(&"docker".to_string()));
     }
 
+    #[test]
+    fn test_custom_patterns_extend_and_override() {
+        let custom = BTreeMap::from([
+            ("elixir".to_string(), r"(?i)\b(elixir|iex)\b".to_string()),
+            ("tmux".to_string(), String::new()),
+            ("broken".to_string(), "(".to_string()),
+        ]);
+        let patterns = with_custom(builtin_tool_patterns(), &custom, "tools");
+        assert!(patterns["elixir"].is_match("started IEx"));
+        assert!(!patterns.contains_key("tmux"));
+        assert!(!patterns.contains_key("broken"));
+        assert!(patterns.contains_key("grep"));
+
+        let mut config = MetadataConfig::default();
+        assert_eq!(metadata_config_digest(&config), "");
+        config.patterns.tools = custom;
+        let digest = metadata_config_digest(&config);
+        config.patterns.tools.remove("broken");
+        assert_ne!(metadata_config_digest(&config), digest);
+    }
+
+    #[test]
+    fn test_entity_extraction() {
+        let entities = extract_entities(
+            "PROJ-2291 broke src/shared/search.rs and Cargo.toml (see #456, \
+             https://github.com/org/repo/issues/456.) UTF-8 only, issue#7 isn't one",
+        );
+        assert_eq!(
+            entities.urls,
+            vec!["https://github.com/org/repo/issues/456"]
+        );
+        assert_eq!(entities.files, vec!["src/shared/search.rs", "Cargo.toml"]);
+        assert_eq!(entities.tickets, vec!["PROJ-2291", "#456"]);
+    }
+
     #[test]
     fn test_code_detection() {
         let content_with_code = "Here's some code:\n```rust\nfn main() {}\n```";
@@ -1,20 +1,72 @@
+pub mod agents;
+pub mod bookmarks;
 pub mod cache;
+#[cfg(feature = "cjk")]
+pub mod cjk;
+pub mod code_blocks;
 pub mod config;
+pub mod decisions;
+pub mod errors;
+pub mod extractive_summary;
+pub mod extractors;
+pub mod file_history;
 pub mod indexer;
 pub mod lock;
 pub mod metadata;
 pub mod models;
 pub mod parser;
 pub mod path_utils;
+pub mod permalink;
+pub mod query_fields;
+pub mod query_history;
+pub mod rebuild;
+pub mod saved_searches;
 pub mod search;
+pub mod session_diff;
+pub mod session_store;
+pub mod sessions;
+pub mod shell_history;
+pub mod site;
+pub mod summarizer;
 pub mod terminal;
+pub mod timezone;
+pub mod tool_usage;
+pub mod topics;
+pub mod transcripts;
+pub mod usage;
 pub mod utils;
+pub mod worklog;
 
+pub use agents::*;
+pub use bookmarks::*;
 pub use cache::*;
+pub use code_blocks::*;
 pub use config::*;
+pub use decisions::*;
+pub use errors::*;
+pub use extractive_summary::*;
+pub use extractors::*;
+pub use file_history::*;
 pub use indexer::*;
 pub use lock::*;
 pub use models::*;
 pub use path_utils::*;
+pub use permalink::*;
+pub use query_fields::*;
+pub use query_history::*;
+pub use rebuild::*;
+pub use saved_searches::*;
 pub use search::*;
+pub use session_diff::*;
+pub use session_store::*;
+pub use sessions::*;
+pub use shell_history::*;
+pub use site::*;
+pub use summarizer::*;
+pub use timezone::*;
+pub use tool_usage::*;
+pub use topics::*;
+pub use transcripts::*;
+pub use usage::*;
 pub use utils::*;
+pub use worklog::*;
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Raw JSONL message structure for parsing Claude Code logs
 #[derive(Debug, Deserialize, Clone)]
@@ -15,16 +16,81 @@ pub struct RawJsonlMessage {
     pub message: Option<RawMessage>,
     pub is_sidechain: Option<bool>,
     pub agent_id: Option<String>,
+    pub git_branch: Option<String>,
     // Summary type fields
     pub summary: Option<String>,
     pub leaf_uuid: Option<String>,
+    // System and attachment record fields: compact boundaries, hooks, local commands
+    pub subtype: Option<String>,
+    pub content: Option<serde_json::Value>,
+    pub level: Option<String>,
+    pub compact_metadata: Option<RawCompactMetadata>,
+    pub attachment: Option<serde_json::Value>,
+    /// `stop_hook_summary` records: the hooks run and the errors they reported
+    pub hook_infos: Option<Vec<serde_json::Value>>,
+    pub hook_errors: Option<Vec<serde_json::Value>>,
+}
+
+/// What a `compact_boundary` record says about the compaction
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RawCompactMetadata {
+    /// `manual` for /compact, `auto` when the context filled up
+    pub trigger: Option<String>,
+    pub pre_tokens: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct RawMessage {
+    /// API message id; Claude Code writes one line per content block, all sharing it
+    pub id: Option<String>,
     pub role: Option<String>,
     pub content: Option<serde_json::Value>,
     pub model: Option<String>,
+    pub usage: Option<RawUsage>,
+}
+
+/// API usage block recorded on assistant messages
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RawUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+/// Billed tokens for one API response (or a sum of them)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_tokens += other.cache_creation_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_tokens + self.cache_read_tokens
+    }
+}
+
+impl From<RawUsage> for TokenUsage {
+    fn from(raw: RawUsage) -> Self {
+        Self {
+            input_tokens: raw.input_tokens,
+            output_tokens: raw.output_tokens,
+            cache_creation_tokens: raw.cache_creation_input_tokens,
+            cache_read_tokens: raw.cache_read_input_tokens,
+        }
+    }
 }
 
 /// Content block types in assistant messages
@@ -33,13 +99,25 @@ pub enum ContentBlock {
     Text(String),
     Thinking(String),
     ToolUse {
+        id: Option<String>,
         name: String,
         input_preview: String,
+        /// `file_path`/`notebook_path` input, for file tools
+        file_path: Option<String>,
+        /// Full `command` input, for shell tools
+        command: Option<String>,
     },
     ToolResult {
+        tool_use_id: Option<String>,
         content_preview: String,
         is_error: bool,
+        /// Length of output left out under `index.skip_tool_results`
+        omitted_chars: usize,
+        /// Output held images or documents, as placeholders in the preview
+        has_attachments: bool,
     },
+    /// `[image image/png 48,213 bytes]` standing in for an image or document
+    Attachment(String),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -56,13 +134,83 @@ pub struct ConversationEntry {
     pub sequence_num: usize,
     pub is_sidechain: bool,
     pub agent_id: Option<String>,
+    /// Format the entry was parsed from: "claude-code", "claude-desktop" or "openai"
+    pub source: String,
+    /// Machine an imported conversation came from; None for local history
+    pub source_host: Option<String>,
+    /// Claude dir or imports dir the JSONL file was found under
+    pub source_root: Option<String>,
+    /// Git branch checked out in `cwd` when the message was written
+    pub git_branch: Option<String>,
+    /// API usage, set on the first entry of each assistant response only
+    pub usage: Option<TokenUsage>,
+    /// File and, for JSONL, byte offset of the line this entry was parsed from
+    pub source_file: Option<String>,
+    pub source_offset: u64,
 
     // Enhanced metadata for better search and categorization
     pub technologies: Vec<String>,
     pub has_code: bool,
     pub code_languages: Vec<String>,
     pub has_error: bool,
+    /// Holds image or document blocks, kept as `[image ...]` placeholders
+    pub has_attachments: bool,
     pub tools_mentioned: Vec<String>,
+    /// Tool names from this message's tool_use blocks, one per call
+    pub tool_calls: Vec<String>,
+    /// Tool names whose tool_result in this message is an error
+    pub tool_errors: Vec<String>,
+    /// Paths passed to Read/Edit/Write tools; `files_modified` is the written subset
+    pub files_touched: Vec<String>,
+    pub files_modified: Vec<String>,
+    /// Bash commands run by this message, exit status filled in from later results
+    pub shell_commands: Vec<ShellCommand>,
+}
+
+/// Every `ConversationEntry::kind`, as the `type:` query field accepts them
+pub const MESSAGE_KINDS: &[&str] = &[
+    "user",
+    "assistant",
+    "tool_result",
+    "summary",
+    "system",
+    "command",
+    "hook",
+    "compact",
+];
+
+impl ConversationEntry {
+    /// What the message is, for ranking: `user`, `assistant`, `tool_result`
+    /// (a user turn that only carries tool output), `summary` or `system`
+    pub fn kind(&self) -> &'static str {
+        match self.message_type {
+            MessageType::User
+                if ["[result] ", "[result omitted: ", "[error] "]
+                    .iter()
+                    .any(|prefix| self.content.starts_with(prefix)) =>
+            {
+                "tool_result"
+            }
+            MessageType::User => "user",
+            MessageType::Assistant => "assistant",
+            MessageType::Summary => "summary",
+            MessageType::System => "system",
+            MessageType::Command => "command",
+            MessageType::Hook => "hook",
+            MessageType::Compact => "compact",
+        }
+    }
+}
+
+/// One Bash tool call and how it ended
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShellCommand {
+    pub tool_use_id: Option<String>,
+    pub command: String,
+    /// 0 for a successful result, the reported code for a failed one
+    pub exit_code: Option<i32>,
+    /// Result was an error (non-zero exit, rejected or interrupted)
+    pub failed: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -71,21 +219,30 @@ pub enum MessageType {
     Assistant,
     Summary,
     System,
+    /// Slash command typed in Claude Code, or the output it printed
+    Command,
+    /// Hook run, with `has_error` set when it failed
+    Hook,
+    /// Compact boundary: the conversation was summarized to free context
+    Compact,
 }
 
 impl MessageType {
-    /// Short display name for output (User, AI, Sum, Sys)
+    /// Short display name for output (User, AI, Sum, Sys, Cmd, Hook, Compact)
     pub fn short_name(&self) -> &'static str {
         match self {
             MessageType::User => "User",
             MessageType::Assistant => "AI",
             MessageType::Summary => "Sum",
             MessageType::System => "Sys",
+            MessageType::Command => "Cmd",
+            MessageType::Hook => "Hook",
+            MessageType::Compact => "Compact",
         }
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub enum SortOrder {
     #[default]
     Relevance,
@@ -93,18 +250,155 @@ pub enum SortOrder {
     DateAsc,
 }
 
+/// A message whose file tools read or modified a matching path
+#[derive(Debug, Clone)]
+pub struct FileTouch {
+    pub uuid: String,
+    pub session_id: String,
+    pub project_path: String,
+    pub timestamp: DateTime<Utc>,
+    pub path: String,
+    pub modified: bool,
+}
+
+/// A shell command run by a message, with where and when it ran
+#[derive(Debug, Clone)]
+pub struct CommandRun {
+    pub uuid: String,
+    pub session_id: String,
+    pub project_path: String,
+    pub timestamp: DateTime<Utc>,
+    pub command: ShellCommand,
+}
+
+/// What one session did, gathered from its messages for `diff_sessions`
+/// and extractive summaries
+#[derive(Debug, Clone, Default)]
+pub struct SessionActivity {
+    pub session_id: String,
+    /// Every message, in session order
+    pub messages: Vec<SearchResult>,
+    /// Calls per tool name
+    pub tool_calls: BTreeMap<String, usize>,
+    /// Failed results per tool name
+    pub tool_errors: BTreeMap<String, usize>,
+    pub files_read: BTreeSet<String>,
+    pub files_modified: BTreeSet<String>,
+    /// Shell commands in the order they ran
+    pub commands: Vec<ShellCommand>,
+}
+
+/// Exact message-level aggregates over the whole index (or one project)
+#[derive(Debug, Default)]
+pub struct IndexStats {
+    pub messages: usize,
+    pub with_code: usize,
+    pub with_error: usize,
+    pub words: u64,
+    pub tokens: u64,
+    /// Message counts keyed by session id, project path, git branch, technology, language and `YYYY-MM`
+    pub sessions: HashMap<String, usize>,
+    pub projects: HashMap<String, usize>,
+    pub branches: HashMap<String, usize>,
+    pub technologies: HashMap<String, usize>,
+    pub code_languages: HashMap<String, usize>,
+    pub months: HashMap<String, usize>,
+    /// Estimated tokens per session id
+    pub session_tokens: HashMap<String, u64>,
+}
+
+/// Messages per subdirectory of a working directory, from the cwd facet
+#[derive(Debug, Default, PartialEq)]
+pub struct DirectoryBreakdown {
+    /// The directory broken down: the one asked for, or else the deepest
+    /// one every message was written under
+    pub path: String,
+    /// Messages written in `path` itself
+    pub here: u64,
+    /// Subdirectory names with the messages in or below each, most first
+    pub children: Vec<(String, u64)>,
+}
+
+/// Match counts over every hit of a query, not just the returned page
+#[derive(Debug, Default)]
+pub struct SearchFacets {
+    pub hits: usize,
+    /// Keyed by `YYYY-MM`
+    pub months: HashMap<String, usize>,
+    /// Keyed by project name
+    pub projects: HashMap<String, usize>,
+    /// Keyed by `source`
+    pub sources: HashMap<String, usize>,
+}
+
+/// Per-project aggregate for the project listing
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectInfo {
+    /// Name in the `project` field, as filters match it
+    pub project: String,
+    pub message_count: usize,
+    pub session_count: usize,
+    pub last_timestamp: Option<DateTime<Utc>>,
+}
+
+/// Per-session aggregate used by session listings and retention
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub project_path: String,
+    pub first_timestamp: DateTime<Utc>,
+    pub last_timestamp: DateTime<Utc>,
+    /// User + assistant messages
+    pub message_count: usize,
+    /// Estimated tokens across all messages
+    pub tokens: u64,
+    /// Generated at index time; None when listed straight from the index
+    pub title: Option<String>,
+    /// Last time a retrieval tool fetched this session
+    pub last_accessed: Option<DateTime<Utc>>,
+    /// Sessions continuing this one, folded into it by `merge_continuations`
+    pub continued_in: Vec<String>,
+}
+
+impl SessionInfo {
+    /// Most recent of last message and last retrieval
+    pub fn last_touched(&self) -> DateTime<Utc> {
+        self.last_accessed
+            .map_or(self.last_timestamp, |a| a.max(self.last_timestamp))
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SearchQuery {
     pub text: String,
-    pub project_filter: Option<String>,
+    /// Projects to search, each a name, substring or glob; empty for all
+    pub project_filters: Vec<String>,
     pub session_filter: Option<String>,
     pub limit: usize,
     pub sort_by: SortOrder,
     pub after: Option<DateTime<Utc>>,
     pub before: Option<DateTime<Utc>>,
+    /// Source host of imported history; "local" matches this machine's own
+    pub host_filter: Option<String>,
+    /// Exact git branch name
+    pub branch_filter: Option<String>,
+    /// Tool the history came from: claude-code, codex, gemini, claude-desktop or openai
+    pub source_filter: Option<String>,
+    /// Only these message uuids, e.g. bookmarked ones
+    pub uuid_filter: Option<Vec<String>>,
+    /// Only messages written in this working directory or below it
+    pub dir_filter: Option<String>,
+    /// Only sessions whose first to last message overlaps this window,
+    /// searched whole: unlike `after`/`before`, their messages outside it match
+    pub session_active_after: Option<DateTime<Utc>>,
+    pub session_active_before: Option<DateTime<Utc>>,
+    /// Halve a message's score for every this many days of age
+    pub recency_half_life_days: Option<f64>,
+    /// Score multiplier per `ConversationEntry::kind`
+    pub type_boosts: HashMap<String, f32>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SearchResult {
     pub uuid: String,
     pub parent_uuid: Option<String>,
@@ -120,18 +414,58 @@ pub struct SearchResult {
     pub tools_mentioned: Vec<String>,
     pub has_code: bool,
     pub has_error: bool,
+    pub has_attachments: bool,
     pub interaction_count: usize,
     pub sequence_num: usize,
     pub is_sidechain: bool,
     pub agent_id: Option<String>,
     pub message_type: String,
+    pub source: String,
+    pub source_host: Option<String>,
+    pub source_root: Option<String>,
+    pub git_branch: Option<String>,
+    /// Estimated tokens in `content`, computed at index time
+    pub token_count: usize,
+    /// Content fingerprint from `simhash`, zero when unknown
+    pub simhash: u64,
+    /// Title of the session, when the session store has one
+    pub session_title: Option<String>,
+    /// Where the query's terms occur in `content`; empty without a query
+    pub matches: Vec<MatchRange>,
+    /// Bookmark note and annotations, when filled in by `attach_notes`
+    pub notes: Vec<String>,
+}
+
+/// One query term occurrence in message content, end exclusive
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct MatchRange {
+    /// Byte offsets, for slicing the UTF-8 content
+    pub start: usize,
+    pub end: usize,
+    /// Character offsets, for editors and terminals
+    pub char_start: usize,
+    pub char_end: usize,
 }
 
+/// `host_filter` value selecting this machine's own (non-imported) history
+pub const LOCAL_HOST: &str = "local";
+
 impl SearchResult {
+    /// Whether the message came from `host` ("local" for non-imported history)
+    pub fn is_from_host(&self, host: &str) -> bool {
+        match &self.source_host {
+            Some(source) => source == host,
+            None => host == LOCAL_HOST,
+        }
+    }
+
     /// Check if message should be displayed (filters noise like Warmup, tool_result dumps)
     pub fn is_displayable(&self) -> bool {
         // Filter by message type
-        if !matches!(self.message_type.as_str(), "User" | "Assistant" | "Summary") {
+        if !matches!(
+            self.message_type.as_str(),
+            "User" | "Assistant" | "Summary" | "Command" | "Hook" | "Compact"
+        ) {
             return false;
         }
         // Filter internal warmup messages
@@ -147,12 +481,38 @@ impl SearchResult {
     }
 
     /// Short display name for message type (User, AI, Sum, Sys)
+    /// Human prompt, as opposed to a user-role message carrying tool results
+    pub fn is_prompt(&self) -> bool {
+        self.message_type == "User"
+            && !["[result] ", "[result omitted: ", "[error] "]
+                .iter()
+                .any(|prefix| self.content.starts_with(prefix))
+    }
+
     pub fn role_display(&self) -> &'static str {
         match self.message_type.as_str() {
             "User" => "User",
             "Assistant" => "AI",
             "Summary" => "Sum",
+            "Command" => "Cmd",
+            "Hook" => "Hook",
+            "Compact" => "Compact",
             _ => "?",
         }
     }
 }
+
+/// Message `sequence_num` of session `s`, for tests
+#[cfg(test)]
+pub(crate) fn test_message(sequence_num: usize, message_type: &str, content: &str) -> SearchResult {
+    SearchResult {
+        uuid: format!("{:08}-0000-0000-0000-000000000000", sequence_num),
+        session_id: "s".to_string(),
+        message_type: message_type.to_string(),
+        content: content.to_string(),
+        sequence_num,
+        timestamp: Utc::now(),
+        simhash: super::utils::simhash(content),
+        ..Default::default()
+    }
+}
@@ -1,18 +1,26 @@
 use super::metadata;
-use super::models::{ContentBlock, ConversationEntry, MessageType, RawJsonlMessage};
-use super::utils::truncate_content;
+use super::models::{
+    ContentBlock, ConversationEntry, MessageType, RawJsonlMessage, ShellCommand, TokenUsage,
+};
+use super::transcripts::CLAUDE_CODE_SOURCE;
+use super::utils::{group_digits, truncate_content};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use std::io::BufReader;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::Mutex;
 use strip_ansi_escapes::strip_str;
 use tracing::warn;
 
 use super::config::get_config;
 
-/// Read text file, skipping UTF-8 BOM if present
-fn read_text_file(path: &Path) -> Result<String> {
-    use std::fs::File;
+/// Read text file, skipping UTF-8 BOM if present; returns the text and the
+/// number of bytes skipped
+pub(super) fn read_text_file(path: &Path) -> Result<(String, u64)> {
     use std::io::Read;
 
     let mut file = BufReader::new(File::open(path)?);
@@ -27,18 +35,307 @@ fn read_text_file(path: &Path) -> Result<String> {
             // No BOM, include these bytes
             let mut content = String::from_utf8(first3.to_vec())?;
             file.read_to_string(&mut content)?;
-            return Ok(content);
+            return Ok((content, 0));
         }
         Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
             // File shorter than 3 bytes
-            return Ok(String::from_utf8(first3[..].to_vec())?);
+            return Ok((String::from_utf8(first3[..].to_vec())?, 0));
         }
         Err(e) => return Err(e.into()),
     }
 
     let mut content = String::new();
     file.read_to_string(&mut content)?;
-    Ok(content)
+    Ok((content, 3))
+}
+
+/// Tools whose `file_path` input is modified / only read
+const FILE_WRITE_TOOLS: &[&str] = &["Edit", "MultiEdit", "Write", "NotebookEdit"];
+const FILE_READ_TOOLS: &[&str] = &["Read"];
+/// Tools whose `command` input is a shell command
+const SHELL_TOOLS: &[&str] = &["Bash"];
+
+/// Searchable text and structured tool activity from one message
+#[derive(Default)]
+struct ExtractedContent {
+    text: String,
+    has_error: bool,
+    has_attachments: bool,
+    tool_calls: Vec<String>,
+    tool_errors: Vec<String>,
+    files_touched: Vec<String>,
+    files_modified: Vec<String>,
+    shell_commands: Vec<ShellCommand>,
+}
+
+/// Tool calls seen so far in a file, so results can be matched to their call
+#[derive(Default)]
+struct ToolTracker {
+    /// tool_use id -> tool name
+    names: HashMap<String, String>,
+    /// Shell tool_use id -> (failed, exit code)
+    shell_results: HashMap<String, (bool, Option<i32>)>,
+}
+
+/// `Exit code N` header Claude Code puts on failed Bash results
+fn parse_exit_code(result: &str) -> Option<i32> {
+    result
+        .trim_start()
+        .strip_prefix("Exit code ")?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Hook events Claude Code names at the start of a hook's system message
+const HOOK_EVENTS: &[&str] = &[
+    "PreToolUse",
+    "PostToolUse",
+    "UserPromptSubmit",
+    "Notification",
+    "Stop",
+    "SubagentStop",
+    "PreCompact",
+    "SessionStart",
+    "SessionEnd",
+];
+
+/// `/compact keep the tests` from the `<command-name>` markup Claude Code
+/// logs for a slash command, `[output] ...` from its `<local-command-stdout>`;
+/// empty when the command printed nothing
+fn slash_command(text: &str) -> Option<String> {
+    let tag = |name: &str| {
+        let open = format!("<{}>", name);
+        let start = text.find(&open)? + open.len();
+        let end = start + text[start..].find(&format!("</{}>", name))?;
+        Some(text[start..end].trim())
+    };
+    let text_start = text.trim_start();
+    if text_start.starts_with("<command-") {
+        let name = tag("command-name")?;
+        let name = if name.starts_with('/') {
+            name.to_string()
+        } else {
+            format!("/{}", name)
+        };
+        return Some(match tag("command-args").filter(|args| !args.is_empty()) {
+            Some(args) => format!("{} {}", name, args),
+            None => name,
+        });
+    }
+    if text_start.starts_with("<local-command-stdout>") {
+        return Some(
+            tag("local-command-stdout")
+                .filter(|output| !output.is_empty())
+                .map(|output| format!("[output] {}", output))
+                .unwrap_or_default(),
+        );
+    }
+    None
+}
+
+/// `PostToolUse:Edit [cargo fmt] non blocking error (exit code 1): ...` for
+/// a `hook_*` attachment record, and whether the hook failed
+fn hook_attachment(attachment: &serde_json::Value) -> Option<(String, bool)> {
+    let outcome = attachment.get("type")?.as_str()?.strip_prefix("hook_")?;
+    let field = |name: &str| {
+        attachment
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    };
+    let exit_code = attachment
+        .get("exitCode")
+        .and_then(|v| v.as_i64())
+        .filter(|&code| code != 0);
+    let failed = outcome.contains("error") || outcome == "cancelled" || exit_code.is_some();
+
+    let mut text = field("hookName")
+        .or_else(|| field("hookEvent"))
+        .unwrap_or("hook")
+        .to_string();
+    if let Some(command) = field("command") {
+        text.push_str(&format!(" [{}]", command));
+    }
+    text.push_str(&format!(" {}", outcome.replace('_', " ")));
+    if let Some(code) = exit_code {
+        text.push_str(&format!(" (exit code {})", code));
+    }
+    if let Some(output) = field("stderr")
+        .or_else(|| field("stdout"))
+        .or_else(|| field("content"))
+    {
+        text.push_str(&format!(": {}", output));
+    }
+    Some((text, failed))
+}
+
+/// Compact boundaries, hook runs and local commands from `system` and
+/// `attachment` records; other records of those types are skipped
+fn meta_event(raw: &RawJsonlMessage) -> Option<(MessageType, ExtractedContent)> {
+    let event = |message_type, text: String, has_error| {
+        Some((
+            message_type,
+            ExtractedContent {
+                text,
+                has_error,
+                ..Default::default()
+            },
+        ))
+    };
+    if let Some(attachment) = &raw.attachment {
+        let (text, failed) = hook_attachment(attachment)?;
+        return event(MessageType::Hook, text, failed);
+    }
+
+    let text = raw
+        .content
+        .as_ref()
+        .and_then(|c| c.as_str())
+        .map(strip_str)
+        .unwrap_or_default();
+    match raw.subtype.as_deref() {
+        Some("compact_boundary") => {
+            let metadata = raw.compact_metadata.as_ref();
+            let mut details = Vec::new();
+            if let Some(trigger) = metadata.and_then(|m| m.trigger.as_deref()) {
+                details.push(trigger.to_string());
+            }
+            if let Some(tokens) = metadata.and_then(|m| m.pre_tokens) {
+                details.push(format!("{} tokens before", group_digits(tokens as usize)));
+            }
+            let mut text = "Conversation compacted".to_string();
+            if !details.is_empty() {
+                text.push_str(&format!(" ({})", details.join(", ")));
+            }
+            event(MessageType::Compact, text, false)
+        }
+        Some("stop_hook_summary") => {
+            let strings = |values: &Option<Vec<serde_json::Value>>, key: &str| -> Vec<String> {
+                values
+                    .iter()
+                    .flatten()
+                    .filter_map(|v| v.as_str().or_else(|| v.get(key)?.as_str()))
+                    .map(String::from)
+                    .collect()
+            };
+            let commands = strings(&raw.hook_infos, "command");
+            let errors = strings(&raw.hook_errors, "error");
+            let mut text = format!("Stop hooks [{}]", commands.join(", "));
+            if !errors.is_empty() {
+                text.push_str(&format!(" failed: {}", errors.join("; ")));
+            }
+            event(MessageType::Hook, text, !errors.is_empty())
+        }
+        _ => {
+            if let Some(command) = slash_command(&text) {
+                return event(MessageType::Command, command, false);
+            }
+            let event_name = text.split([':', ' ']).next().unwrap_or_default();
+            if !HOOK_EVENTS.contains(&event_name) {
+                return None;
+            }
+            let failed = matches!(raw.level.as_deref(), Some("warning" | "error"))
+                || text.contains("failed")
+                || text.contains("error");
+            event(MessageType::Hook, text, failed)
+        }
+    }
+}
+
+/// `[image image/png 48,213 bytes /tmp/shot.png]` for an image or document
+/// content block, with what its source tells; the data is not indexed
+fn attachment_placeholder(block: &serde_json::Value) -> Option<String> {
+    let kind = block.get("type")?.as_str()?;
+    if !matches!(kind, "image" | "document") {
+        return None;
+    }
+    let source = block.get("source");
+    let field = |name: &str| {
+        source
+            .and_then(|s| s.get(name))
+            .or_else(|| block.get(name))
+            .and_then(|v| v.as_str())
+    };
+    let mut placeholder = format!("[{}", kind);
+    if let Some(media_type) = field("media_type") {
+        placeholder.push_str(&format!(" {}", media_type));
+    }
+    if let Some(data) = field("data") {
+        let bytes = if field("type") == Some("base64") {
+            let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+            (data.len() / 4 * 3).saturating_sub(padding)
+        } else {
+            data.len()
+        };
+        placeholder.push_str(&format!(" {} bytes", group_digits(bytes)));
+    }
+    if let Some(path) = field("path")
+        .or_else(|| field("file_path"))
+        .or_else(|| field("url"))
+    {
+        placeholder.push_str(&format!(" {}", path));
+    }
+    placeholder.push(']');
+    Some(placeholder)
+}
+
+fn push_unique(list: &mut Vec<String>, value: &str) {
+    if !list.iter().any(|v| v == value) {
+        list.push(value.to_string());
+    }
+}
+
+/// Give summary entries the session, project and time of the message they
+/// end at. A leaf in another file falls back to the summary line's own
+/// session, then to this file's first message; with neither it is dropped.
+/// Summary lines carry no uuid of their own; derive a stable one from the
+/// leaf so re-indexing keeps the same id
+fn summary_uuid(leaf_uuid: &str) -> String {
+    let digest = Sha256::digest(format!("summary:{leaf_uuid}"));
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_random_bytes(bytes)
+        .into_uuid()
+        .to_string()
+}
+
+fn attach_summaries(entries: &mut Vec<ConversationEntry>) {
+    let is_summary = |e: &ConversationEntry| matches!(e.message_type, MessageType::Summary);
+    let first_message = entries.iter().position(|e| !is_summary(e));
+    let owners: Vec<Option<usize>> = entries
+        .iter()
+        .map(|entry| {
+            if !is_summary(entry) {
+                return None;
+            }
+            let leaf = entry.parent_uuid.as_deref();
+            entries
+                .iter()
+                .position(|e| !is_summary(e) && Some(e.uuid.as_str()) == leaf)
+                .or(if entry.session_id.is_empty() {
+                    first_message
+                } else {
+                    None
+                })
+        })
+        .collect();
+
+    for (index, owner) in owners.into_iter().enumerate() {
+        let Some(owner) = owner else {
+            continue;
+        };
+        let owner = entries[owner].clone();
+        let summary = &mut entries[index];
+        summary.session_id = owner.session_id;
+        summary.project_path = owner.project_path;
+        summary.cwd = owner.cwd;
+        summary.git_branch = owner.git_branch;
+        summary.timestamp = owner.timestamp;
+    }
+    entries.retain(|e| !e.session_id.is_empty());
 }
 
 #[derive(Default)]
@@ -46,7 +343,7 @@ pub struct JsonlParser;
 
 impl JsonlParser {
     pub fn parse_file(&self, path: &Path) -> Result<Vec<ConversationEntry>> {
-        let content = read_text_file(path)?;
+        let (content, mut offset) = read_text_file(path)?;
         let mut entries = Vec::new();
         let project_name = self.extract_project_name(path);
 
@@ -63,16 +360,41 @@ impl JsonlParser {
         };
 
         let mut sequence_counter = 0;
-        for (line_num, line) in content.lines().enumerate() {
+        let mut billed_message_ids = HashSet::new();
+        let mut tools = ToolTracker::default();
+        for (line_num, line) in content.split_inclusive('\n').enumerate() {
+            let line_offset = offset;
+            offset += line.len() as u64;
             if line.trim().is_empty() {
                 continue;
             }
 
             match serde_json::from_str::<RawJsonlMessage>(line) {
                 Ok(raw) => {
-                    if let Some(entry) =
-                        self.parse_raw_message(raw, &project_name, sequence_counter, &file_agent_id)
-                    {
+                    if raw.message_type.as_deref() == Some("summary") {
+                        if let Some(mut entry) = self.summary_entry(raw, sequence_counter) {
+                            entry.source_offset = line_offset;
+                            entries.push(entry);
+                            sequence_counter += 1;
+                        }
+                        continue;
+                    }
+                    let message_id = raw.message.as_ref().and_then(|m| m.id.clone());
+                    if let Some(mut entry) = self.parse_raw_message(
+                        raw,
+                        &project_name,
+                        sequence_counter,
+                        &file_agent_id,
+                        &mut tools,
+                    ) {
+                        // Every content-block line repeats the response's usage; bill it once
+                        if entry.usage.is_some()
+                            && let Some(id) = message_id
+                            && !billed_message_ids.insert(id)
+                        {
+                            entry.usage = None;
+                        }
+                        entry.source_offset = line_offset;
                         entries.push(entry);
                         sequence_counter += 1;
                     }
@@ -83,22 +405,110 @@ impl JsonlParser {
             }
         }
 
+        // Results arrive in later lines than their calls
+        for command in entries.iter_mut().flat_map(|e| e.shell_commands.iter_mut()) {
+            if let Some(id) = &command.tool_use_id
+                && let Some(&(failed, exit_code)) = tools.shell_results.get(id)
+            {
+                command.failed = failed;
+                command.exit_code = exit_code;
+            }
+        }
+        attach_summaries(&mut entries);
+        let source_file = path.to_string_lossy().into_owned();
+        for entry in &mut entries {
+            entry.source_file = Some(source_file.clone());
+        }
+
         Ok(entries)
     }
 
+    /// Content of the entry at byte `offset` of `path`, extracted the way
+    /// `parse_file` does. None when the line there is no longer `uuid`.
+    pub fn read_content(&self, path: &Path, offset: u64, uuid: &str) -> Result<Option<String>> {
+        let mut file = BufReader::new(File::open(path)?);
+        file.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        file.read_line(&mut line)?;
+        let raw: RawJsonlMessage = serde_json::from_str(&line)?;
+        let entry = if raw.message_type.as_deref() == Some("summary") {
+            self.summary_entry(raw, 0)
+        } else {
+            self.parse_raw_message(raw, "", 0, &None, &mut ToolTracker::default())
+        };
+        Ok(entry.filter(|e| e.uuid == uuid).map(|e| e.content))
+    }
+
+    /// Entry for a summary line. Claude Code usually writes these with only
+    /// the summary and the uuid of the last message it covers (the leaf);
+    /// `attach_summaries` fills in what the line leaves out.
+    fn summary_entry(
+        &self,
+        raw: RawJsonlMessage,
+        sequence_num: usize,
+    ) -> Option<ConversationEntry> {
+        let leaf_uuid = raw.leaf_uuid?;
+        let content = strip_str(raw.summary.as_deref()?);
+        if content.trim().is_empty() {
+            return None;
+        }
+        let project_path = raw
+            .cwd
+            .as_ref()
+            .map(|cwd| self.extract_project_name_from_path(cwd))
+            .unwrap_or_default();
+
+        Some(ConversationEntry {
+            uuid: raw.uuid.unwrap_or_else(|| summary_uuid(&leaf_uuid)),
+            parent_uuid: Some(leaf_uuid),
+            session_id: raw.session_id.unwrap_or_default(),
+            project_path,
+            timestamp: raw
+                .timestamp
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+            message_type: MessageType::Summary,
+            content,
+            model: None,
+            cwd: raw.cwd,
+            sequence_num,
+            is_sidechain: false,
+            agent_id: None,
+            source: CLAUDE_CODE_SOURCE.to_string(),
+            source_host: None,
+            source_root: None,
+            git_branch: None,
+            usage: None,
+            source_file: None,
+            source_offset: 0,
+            technologies: vec![],
+            has_code: false,
+            code_languages: vec![],
+            has_error: false,
+            has_attachments: false,
+            tools_mentioned: vec![],
+            tool_calls: vec![],
+            tool_errors: vec![],
+            files_touched: vec![],
+            files_modified: vec![],
+            shell_commands: vec![],
+        })
+    }
+
     fn parse_raw_message(
         &self,
         raw: RawJsonlMessage,
         fallback_project: &str,
         sequence_num: usize,
         file_agent_id: &Option<String>,
+        tools: &mut ToolTracker,
     ) -> Option<ConversationEntry> {
         let msg_type = raw.message_type.as_deref()?;
 
         // Filter out noise message types
         match msg_type {
             "file-history-snapshot" | "queue-operation" => return None,
-            "user" | "assistant" | "summary" => {}
+            "user" | "assistant" | "system" | "attachment" => {}
             _ => return None, // Skip unknown types
         }
 
@@ -108,21 +518,30 @@ impl JsonlParser {
         let timestamp_str = raw.timestamp.as_deref()?;
         let timestamp: DateTime<Utc> = timestamp_str.parse().ok()?;
 
-        // Determine message type
-        let message_type = match msg_type {
-            "user" => MessageType::User,
-            "assistant" => MessageType::Assistant,
-            "summary" => MessageType::Summary,
-            _ => MessageType::System,
+        // Determine message type and extract searchable content
+        let (message_type, extracted) = match msg_type {
+            "user" => {
+                let mut extracted = self.extract_searchable_content(&raw, tools);
+                if let Some(command) = slash_command(&extracted.text) {
+                    extracted.text = command;
+                    (MessageType::Command, extracted)
+                } else {
+                    (MessageType::User, extracted)
+                }
+            }
+            "assistant" => (
+                MessageType::Assistant,
+                self.extract_searchable_content(&raw, tools),
+            ),
+            _ => meta_event(&raw)?,
         };
 
-        // Extract searchable content, stripping ANSI escape sequences from tool output
-        let (content, has_error, tools_used) = if msg_type == "summary" {
-            (raw.summary.unwrap_or_default(), false, Vec::new())
-        } else {
-            self.extract_searchable_content(&raw)
-        };
-        let content = strip_str(&content);
+        // Strip ANSI escape sequences from tool output
+        let content = truncate_content(
+            &strip_str(&extracted.text),
+            get_config().limits.entry_max_chars,
+            false,
+        );
 
         // Skip empty content
         if content.trim().is_empty() {
@@ -136,8 +555,13 @@ impl JsonlParser {
             .map(|cwd| self.extract_project_name_from_path(cwd))
             .unwrap_or_else(|| fallback_project.to_string());
 
-        // Get model from message
+        // Get model and API usage from message
         let model = raw.message.as_ref().and_then(|m| m.model.clone());
+        let usage = raw
+            .message
+            .as_ref()
+            .and_then(|m| m.usage.clone())
+            .map(TokenUsage::from);
 
         // Use agent_id from message or from filename
         let agent_id = raw.agent_id.or_else(|| file_agent_id.clone());
@@ -148,9 +572,9 @@ impl JsonlParser {
 
         // Merge tools from content blocks with metadata extraction
         let mut all_tools = tools_mentioned;
-        for tool in tools_used {
-            if !all_tools.contains(&tool) {
-                all_tools.push(tool);
+        for tool in &extracted.tool_calls {
+            if !all_tools.contains(tool) {
+                all_tools.push(tool.clone());
             }
         }
 
@@ -167,41 +591,51 @@ impl JsonlParser {
             sequence_num,
             is_sidechain: raw.is_sidechain.unwrap_or(false),
             agent_id,
+            source: CLAUDE_CODE_SOURCE.to_string(),
+            source_host: None,
+            source_root: None,
+            git_branch: raw.git_branch.filter(|b| !b.is_empty()),
+            usage,
+            source_file: None,
+            source_offset: 0,
             technologies,
             has_code,
             code_languages,
-            has_error: has_error || content_has_error,
+            has_error: extracted.has_error || content_has_error,
+            has_attachments: extracted.has_attachments,
             tools_mentioned: all_tools,
+            tool_calls: extracted.tool_calls,
+            tool_errors: extracted.tool_errors,
+            files_touched: extracted.files_touched,
+            files_modified: extracted.files_modified,
+            shell_commands: extracted.shell_commands,
         })
     }
 
-    /// Extract searchable content from message, filtering noise
-    fn extract_searchable_content(&self, raw: &RawJsonlMessage) -> (String, bool, Vec<String>) {
-        let message = match &raw.message {
-            Some(m) => m,
-            None => return (String::new(), false, Vec::new()),
-        };
-
-        let content_value = match &message.content {
-            Some(c) => c,
-            None => return (String::new(), false, Vec::new()),
+    /// Extract searchable content from message, filtering noise, plus tool
+    /// activity. Tool results resolve to their call through the file's `tools`.
+    fn extract_searchable_content(
+        &self,
+        raw: &RawJsonlMessage,
+        tools: &mut ToolTracker,
+    ) -> ExtractedContent {
+        let mut extracted = ExtractedContent::default();
+        let Some(content_value) = raw.message.as_ref().and_then(|m| m.content.as_ref()) else {
+            return extracted;
         };
 
         // Handle string content (simple user messages)
         if let Some(text) = content_value.as_str() {
-            return (text.to_string(), false, Vec::new());
+            extracted.text = text.to_string();
+            return extracted;
         }
 
         // Handle array content (assistant messages with blocks)
-        let blocks = match content_value.as_array() {
-            Some(arr) => arr,
-            None => return (String::new(), false, Vec::new()),
+        let Some(blocks) = content_value.as_array() else {
+            return extracted;
         };
 
         let mut parts = Vec::new();
-        let mut has_error = false;
-        let mut tools_used = Vec::new();
-
         for block in blocks {
             if let Some(content_block) = self.parse_content_block(block) {
                 match content_block {
@@ -213,33 +647,86 @@ impl JsonlParser {
                         parts.push(format!("[thinking] {}", thinking));
                     }
                     ContentBlock::ToolUse {
+                        id,
                         name,
                         input_preview,
+                        file_path,
+                        command,
                     } => {
                         // Include tool name and truncated input
-                        tools_used.push(name.clone());
+                        if let Some(id) = &id {
+                            tools.names.insert(id.clone(), name.clone());
+                        }
+                        if let Some(command) = command
+                            && SHELL_TOOLS.contains(&name.as_str())
+                        {
+                            extracted.shell_commands.push(ShellCommand {
+                                tool_use_id: id,
+                                command,
+                                exit_code: None,
+                                failed: false,
+                            });
+                        }
+                        if let Some(path) = file_path {
+                            if FILE_WRITE_TOOLS.contains(&name.as_str()) {
+                                push_unique(&mut extracted.files_modified, &path);
+                                push_unique(&mut extracted.files_touched, &path);
+                            } else if FILE_READ_TOOLS.contains(&name.as_str()) {
+                                push_unique(&mut extracted.files_touched, &path);
+                            }
+                        }
                         if !input_preview.is_empty() {
                             parts.push(format!("[{}] {}", name, input_preview));
                         }
+                        extracted.tool_calls.push(name);
                     }
                     ContentBlock::ToolResult {
+                        tool_use_id,
                         content_preview,
                         is_error,
+                        omitted_chars,
+                        has_attachments,
                     } => {
+                        extracted.has_attachments |= has_attachments;
                         // Include truncated result and error flag
+                        let tool = tool_use_id
+                            .as_ref()
+                            .and_then(|id| tools.names.get(id).cloned());
+                        if let Some(id) = tool_use_id
+                            && tool
+                                .as_deref()
+                                .is_some_and(|name| SHELL_TOOLS.contains(&name))
+                        {
+                            let exit_code = if is_error {
+                                parse_exit_code(&content_preview)
+                            } else {
+                                Some(0)
+                            };
+                            tools.shell_results.insert(id, (is_error, exit_code));
+                        }
                         if is_error {
-                            has_error = true;
+                            extracted.has_error = true;
+                            extracted
+                                .tool_errors
+                                .push(tool.unwrap_or_else(|| "unknown".to_string()));
                             parts.push(format!("[error] {}", content_preview));
                         } else if !content_preview.trim().is_empty() {
                             // Only include non-empty, non-error results (truncated)
                             parts.push(format!("[result] {}", content_preview));
+                        } else if omitted_chars > 0 {
+                            parts.push(format!("[result omitted: {} chars]", omitted_chars));
                         }
                     }
+                    ContentBlock::Attachment(placeholder) => {
+                        extracted.has_attachments = true;
+                        parts.push(placeholder);
+                    }
                 }
             }
         }
 
-        (parts.join("\n"), has_error, tools_used)
+        extracted.text = parts.join("\n");
+        extracted
     }
 
     fn parse_content_block(&self, block: &serde_json::Value) -> Option<ContentBlock> {
@@ -250,6 +737,7 @@ impl JsonlParser {
                 let text = block.get("text")?.as_str()?;
                 Some(ContentBlock::Text(text.to_string()))
             }
+            "image" | "document" => attachment_placeholder(block).map(ContentBlock::Attachment),
             "thinking" => {
                 let thinking = block.get("thinking")?.as_str()?;
                 Some(ContentBlock::Thinking(thinking.to_string()))
@@ -266,9 +754,20 @@ impl JsonlParser {
                         )
                     })
                     .unwrap_or_default();
+                let file_path = input
+                    .and_then(|v| v.get("file_path").or_else(|| v.get("notebook_path")))
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+                let command = input
+                    .and_then(|v| v.get("command"))
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
                 Some(ContentBlock::ToolUse {
+                    id: block.get("id").and_then(|v| v.as_str()).map(String::from),
                     name,
                     input_preview,
+                    file_path,
+                    command,
                 })
             }
             "tool_result" => {
@@ -277,27 +776,53 @@ impl JsonlParser {
                     .and_then(|v| v.as_bool())
                     .unwrap_or(false);
                 let content = block.get("content");
-                let content_preview = content
+                let placeholders: Vec<String> = content
+                    .and_then(|v| v.as_array())
+                    .map(|arr| arr.iter().filter_map(attachment_placeholder).collect())
+                    .unwrap_or_default();
+                let text = content
                     .and_then(|v| {
                         // Handle both string and array content
                         if let Some(s) = v.as_str() {
                             Some(s.to_string())
                         } else if let Some(arr) = v.as_array() {
-                            // Extract text from array format
-                            let texts: Vec<&str> = arr
+                            // Extract text from array format, images as placeholders
+                            let texts: Vec<String> = arr
                                 .iter()
-                                .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+                                .filter_map(|item| {
+                                    item.get("text")
+                                        .and_then(|t| t.as_str())
+                                        .map(String::from)
+                                        .or_else(|| attachment_placeholder(item))
+                                })
                                 .collect();
                             Some(texts.join(" "))
                         } else {
                             None
                         }
                     })
-                    .map(|s| truncate_content(&s, get_config().limits.tool_result_max_chars, false))
                     .unwrap_or_default();
+                // Output stays in the JSONL; errors are kept for error analysis,
+                // attachment placeholders so multimodal results stay findable
+                let (content_preview, omitted_chars) = if get_config().index.skip_tool_results
+                    && !is_error
+                {
+                    (placeholders.join(" "), text.chars().count())
+                } else {
+                    (
+                        truncate_content(&text, get_config().limits.tool_result_max_chars, false),
+                        0,
+                    )
+                };
                 Some(ContentBlock::ToolResult {
+                    tool_use_id: block
+                        .get("tool_use_id")
+                        .and_then(|v| v.as_str())
+                        .map(String::from),
                     content_preview,
                     is_error,
+                    omitted_chars,
+                    has_attachments: !placeholders.is_empty(),
                 })
             }
             _ => None,
@@ -312,18 +837,25 @@ impl JsonlParser {
             .to_string()
     }
 
-    fn extract_project_name_from_path(&self, cwd_path: &str) -> String {
-        let path = Path::new(cwd_path);
-        let components: Vec<&str> = path
-            .components()
-            .filter_map(|c| c.as_os_str().to_str())
+    /// Project name for a working directory: the name of the git repo it is
+    /// in, else the last meaningful path component ("~" for the home directory)
+    pub(super) fn extract_project_name_from_path(&self, cwd_path: &str) -> String {
+        if let Some(name) = git_repo_name(cwd_path) {
+            return name;
+        }
+        if dirs::home_dir().is_some_and(|home| Path::new(cwd_path) == home) {
+            return "~".to_string();
+        }
+
+        let components: Vec<&str> = cwd_path
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty() && !c.ends_with(':'))
             .collect();
 
         // Look for meaningful project name, skip common dirs
-        for i in (0..components.len()).rev() {
-            let component = components[i];
+        for component in components.iter().rev() {
             if matches!(
-                component,
+                *component,
                 "src" | "lib" | "bin" | "target" | "node_modules" | ".git"
             ) {
                 continue;
@@ -333,13 +865,33 @@ impl JsonlParser {
             }
         }
 
-        path.file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string()
+        components
+            .last()
+            .map_or_else(|| "unknown".to_string(), |c| c.to_string())
     }
 }
 
+/// Repo names by working directory, as every message of a session repeats it
+static GIT_REPO_NAMES: Lazy<Mutex<HashMap<String, Option<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Name of the directory holding `.git` above `cwd`, when it still exists
+/// here. A repo at the home directory (dotfiles) or the root doesn't count.
+fn git_repo_name(cwd: &str) -> Option<String> {
+    let mut names = GIT_REPO_NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    names
+        .entry(cwd.to_string())
+        .or_insert_with(|| {
+            let home = dirs::home_dir();
+            Path::new(cwd)
+                .ancestors()
+                .take_while(|dir| dir.parent().is_some() && Some(*dir) != home.as_deref())
+                .find(|dir| dir.join(".git").exists())
+                .and_then(|dir| dir.file_name()?.to_str().map(str::to_string))
+        })
+        .clone()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -349,7 +901,9 @@ mod tests {
         let json = r#"{"uuid":"abc123","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:00Z","message":{"role":"user","content":"Hello world"}}"#;
         let raw: RawJsonlMessage = serde_json::from_str(json).unwrap();
         let parser = JsonlParser;
-        let entry = parser.parse_raw_message(raw, "test", 0, &None).unwrap();
+        let entry = parser
+            .parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default())
+            .unwrap();
 
         assert_eq!(entry.uuid, "abc123");
         assert_eq!(entry.content, "Hello world");
@@ -361,7 +915,7 @@ mod tests {
         let json = r#"{"type":"file-history-snapshot","messageId":"xyz"}"#;
         let raw: RawJsonlMessage = serde_json::from_str(json).unwrap();
         let parser = JsonlParser;
-        let entry = parser.parse_raw_message(raw, "test", 0, &None);
+        let entry = parser.parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default());
 
         assert!(entry.is_none());
     }
@@ -371,7 +925,9 @@ mod tests {
         let json = r#"{"uuid":"abc123","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:00Z","message":{"role":"assistant","content":[{"type":"text","text":"Here is my response"}]}}"#;
         let raw: RawJsonlMessage = serde_json::from_str(json).unwrap();
         let parser = JsonlParser;
-        let entry = parser.parse_raw_message(raw, "test", 0, &None).unwrap();
+        let entry = parser
+            .parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default())
+            .unwrap();
 
         assert_eq!(entry.content, "Here is my response");
         assert_eq!(entry.message_type, MessageType::Assistant);
@@ -382,25 +938,276 @@ mod tests {
         let json = r#"{"uuid":"abc123","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:00Z","message":{"role":"assistant","content":[{"type":"thinking","thinking":"Let me think about this..."}]}}"#;
         let raw: RawJsonlMessage = serde_json::from_str(json).unwrap();
         let parser = JsonlParser;
-        let entry = parser.parse_raw_message(raw, "test", 0, &None).unwrap();
+        let entry = parser
+            .parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default())
+            .unwrap();
 
         assert!(entry.content.contains("[thinking]"));
         assert!(entry.content.contains("Let me think about this"));
     }
 
     #[test]
-    fn test_tool_result_truncation() {
-        let long_content = "x".repeat(5000);
-        let json = format!(
-            r#"{{"uuid":"abc123","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:00Z","message":{{"role":"assistant","content":[{{"type":"tool_result","content":"{}"}}]}}}}"#,
-            long_content
+    fn test_attachments_become_placeholders() {
+        let json = r#"{"uuid":"abc123","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:00Z","message":{"role":"user","content":[{"type":"image","source":{"type":"base64","media_type":"image/png","data":"aGVsbG8="}},{"type":"document","source":{"type":"url","url":"https://example.com/spec.pdf"}},{"type":"text","text":"What is wrong here?"}]}}"#;
+        let raw: RawJsonlMessage = serde_json::from_str(json).unwrap();
+        let entry = JsonlParser
+            .parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default())
+            .unwrap();
+        assert!(entry.has_attachments);
+        assert_eq!(
+            entry.content,
+            "[image image/png 5 bytes]\n[document https://example.com/spec.pdf]\nWhat is wrong here?"
         );
-        let raw: RawJsonlMessage = serde_json::from_str(&json).unwrap();
+
+        // A screenshot read back by a tool is all the result holds
+        let json = r#"{"uuid":"def456","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:01Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":[{"type":"image","source":{"type":"base64","media_type":"image/jpeg","data":"aGk="}}]}]}}"#;
+        let raw: RawJsonlMessage = serde_json::from_str(json).unwrap();
+        let entry = JsonlParser
+            .parse_raw_message(raw, "test", 1, &None, &mut ToolTracker::default())
+            .unwrap();
+        assert!(entry.has_attachments);
+        assert_eq!(entry.content, "[result] [image image/jpeg 2 bytes]");
+    }
+
+    #[test]
+    fn test_usage_billed_once_per_message() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sess1.jsonl");
+        let line = |uuid: &str, block: &str| {
+            format!(
+                r#"{{"uuid":"{uuid}","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:00Z","message":{{"id":"msg_1","role":"assistant","model":"claude-sonnet-4-5","content":[{block}],"usage":{{"input_tokens":10,"output_tokens":20,"cache_read_input_tokens":300}}}}}}"#
+            )
+        };
+        std::fs::write(
+            &path,
+            [
+                line("a", r#"{"type":"thinking","thinking":"plan"}"#),
+                line("b", r#"{"type":"text","text":"answer"}"#),
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+
+        let entries = JsonlParser.parse_file(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        let usage = entries[0].usage.unwrap();
+        assert_eq!(usage.output_tokens, 20);
+        assert_eq!(usage.cache_read_tokens, 300);
+        assert_eq!(entries[1].usage, None);
+    }
+
+    #[test]
+    fn test_summaries_join_their_leaf_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sess1.jsonl");
+        let lines = [
+            r#"{"type":"summary","summary":"Older conversation","leafUuid":"elsewhere"}"#,
+            r#"{"uuid":"a","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:00Z","gitBranch":"main","message":{"role":"user","content":"hi there"}}"#,
+            r#"{"type":"summary","summary":"Fixing the release build","leafUuid":"a"}"#,
+            r#"{"type":"summary","summary":"  ","leafUuid":"a"}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let entries = JsonlParser.parse_file(&path).unwrap();
+        assert_eq!(entries.len(), 3, "Blank summaries are dropped");
+        let summaries: Vec<_> = entries
+            .iter()
+            .filter(|e| e.message_type == MessageType::Summary)
+            .collect();
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries.iter().all(|s| s.session_id == "sess1"));
+        assert_eq!(summaries[0].uuid, summary_uuid("elsewhere"));
+        assert_ne!(summaries[0].uuid, summaries[1].uuid);
+        assert_eq!(summaries[1].content, "Fixing the release build");
+        assert_eq!(summaries[1].parent_uuid.as_deref(), Some("a"));
+        assert_eq!(summaries[1].git_branch.as_deref(), Some("main"));
+        assert_eq!(summaries[1].timestamp, entries[1].timestamp);
+    }
+
+    #[test]
+    fn test_tool_errors_resolve_to_tool_name() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sess1.jsonl");
+        let lines = [
+            r#"{"uuid":"a","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:00Z","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"make"}},{"type":"tool_use","id":"toolu_2","name":"Read","input":{"file_path":"x"}}]}}"#,
+            r#"{"uuid":"b","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:01Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","is_error":true,"content":"make: *** No rule"},{"type":"tool_result","tool_use_id":"toolu_2","content":"ok"}]}}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let entries = JsonlParser.parse_file(&path).unwrap();
+        assert_eq!(entries[0].tool_calls, vec!["Bash", "Read"]);
+        assert!(entries[0].tool_errors.is_empty());
+        assert_eq!(entries[1].tool_errors, vec!["Bash"]);
+    }
+
+    #[test]
+    fn test_shell_commands_get_exit_status() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sess1.jsonl");
+        let lines = [
+            r#"{"uuid":"a","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:00Z","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"cargo test"}},{"type":"tool_use","id":"toolu_2","name":"Bash","input":{"command":"ls"}},{"type":"tool_use","id":"toolu_3","name":"Bash","input":{"command":"sleep 999"}}]}}"#,
+            r#"{"uuid":"b","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:01Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","is_error":true,"content":"Exit code 101\nerror: test failed"},{"type":"tool_result","tool_use_id":"toolu_2","content":""}]}}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let entries = JsonlParser.parse_file(&path).unwrap();
+        let commands = &entries[0].shell_commands;
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0].command, "cargo test");
+        assert!(commands[0].failed);
+        assert_eq!(commands[0].exit_code, Some(101));
+        assert!(!commands[1].failed);
+        assert_eq!(commands[1].exit_code, Some(0));
+        assert_eq!(commands[2].exit_code, None, "No result recorded");
+    }
+
+    #[test]
+    fn test_meta_events_get_their_own_types() {
+        let parse = |record: &str| {
+            let json = format!(
+                r#"{{"uuid":"abc123","sessionId":"sess1","timestamp":"2025-12-28T10:00:00Z",{record}}}"#
+            );
+            let raw: RawJsonlMessage = serde_json::from_str(&json).unwrap();
+            JsonlParser
+                .parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default())
+                .map(|e| (e.message_type, e.content, e.has_error))
+        };
+
+        assert_eq!(
+            parse(
+                r#""type":"user","message":{"role":"user","content":"<command-message>compact</command-message>\n<command-name>/compact</command-name>\n<command-args>keep the tests</command-args>"}"#
+            ),
+            Some((
+                MessageType::Command,
+                "/compact keep the tests".to_string(),
+                false
+            ))
+        );
+        assert_eq!(
+            parse(
+                r#""type":"user","message":{"role":"user","content":"<local-command-stdout>Compacted.</local-command-stdout>"}"#
+            ),
+            Some((
+                MessageType::Command,
+                "[output] Compacted.".to_string(),
+                false
+            ))
+        );
+        assert_eq!(
+            parse(
+                r#""type":"system","subtype":"compact_boundary","content":"Conversation compacted","compactMetadata":{"trigger":"manual","preTokens":52012}"#
+            ),
+            Some((
+                MessageType::Compact,
+                "Conversation compacted (manual, 52,012 tokens before)".to_string(),
+                false
+            ))
+        );
+        assert_eq!(
+            parse(
+                r#""type":"system","level":"warning","content":"\u001b[1mPostToolUse:Edit\u001b[22m [cargo fmt] failed with non-blocking status code 1: error""#
+            ),
+            Some((
+                MessageType::Hook,
+                "PostToolUse:Edit [cargo fmt] failed with non-blocking status code 1: error"
+                    .to_string(),
+                true
+            ))
+        );
+        assert_eq!(
+            parse(
+                r#""type":"attachment","attachment":{"type":"hook_success","hookName":"SessionStart:startup","command":"~/bin/motd","exitCode":0,"stdout":"hi"}"#
+            ),
+            Some((
+                MessageType::Hook,
+                "SessionStart:startup [~/bin/motd] success: hi".to_string(),
+                false
+            ))
+        );
+        assert_eq!(
+            parse(r#""type":"system","subtype":"api_error","content":"Overloaded""#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_project_name_from_git_root() {
         let parser = JsonlParser;
-        let entry = parser.parse_raw_message(raw, "test", 0, &None).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("orbit-tracker");
+        let nested = repo.join("crates").join("core");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        assert_eq!(
+            parser.extract_project_name_from_path(nested.to_str().unwrap()),
+            "orbit-tracker"
+        );
 
-        // Should be truncated to ~get_config().limits.tool_result_max_chars + "[result] " prefix + "…"
+        // Without a repo on disk, the last meaningful component
+        assert_eq!(
+            parser.extract_project_name_from_path("/nonexistent/widget/src"),
+            "widget"
+        );
+        assert_eq!(
+            parser.extract_project_name_from_path(r"D:\work\gadget"),
+            "gadget"
+        );
+        assert_eq!(parser.extract_project_name_from_path(r"D:\"), "unknown");
+    }
+
+    #[test]
+    fn test_tool_result_truncation() {
+        let long_content = "x".repeat(5000);
+        let parse = |is_error: bool| {
+            let json = format!(
+                r#"{{"uuid":"abc123","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:00Z","message":{{"role":"user","content":[{{"type":"tool_result","is_error":{},"content":"{}"}}]}}}}"#,
+                is_error, long_content
+            );
+            let raw: RawJsonlMessage = serde_json::from_str(&json).unwrap();
+            JsonlParser
+                .parse_raw_message(raw, "test", 0, &None, &mut ToolTracker::default())
+                .unwrap()
+        };
+
+        // Errors are truncated to ~get_config().limits.tool_result_max_chars + "[error] " prefix + "…"
+        let entry = parse(true);
         assert!(entry.content.len() < get_config().limits.tool_result_max_chars + 100);
         assert!(entry.content.ends_with('…'));
+
+        // Successful output is left in the JSONL by default
+        let entry = parse(false);
+        assert_eq!(entry.content, "[result omitted: 5000 chars]");
+        assert_eq!(entry.kind(), "tool_result");
+    }
+
+    #[test]
+    fn test_read_content_at_recorded_offset() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("sess1.jsonl");
+        let lines = [
+            r#"{"uuid":"a","sessionId":"sess1","type":"user","timestamp":"2025-12-28T10:00:00Z","message":{"role":"user","content":"héllo there"}}"#,
+            r#"{"uuid":"b","sessionId":"sess1","type":"assistant","timestamp":"2025-12-28T10:00:01Z","message":{"role":"assistant","content":[{"type":"text","text":"General Kenobi"}]}}"#,
+            r#"{"type":"summary","summary":"Greetings","leafUuid":"b"}"#,
+        ];
+        let mut text = "\u{feff}".to_string();
+        text.push_str(&lines.join("\r\n"));
+        std::fs::write(&path, text).unwrap();
+
+        let entries = JsonlParser.parse_file(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        for entry in &entries {
+            let file = Path::new(entry.source_file.as_deref().unwrap());
+            let content = JsonlParser
+                .read_content(file, entry.source_offset, &entry.uuid)
+                .unwrap();
+            assert_eq!(content.as_deref(), Some(entry.content.as_str()));
+        }
+        let moved = JsonlParser
+            .read_content(&path, entries[1].source_offset, "a")
+            .unwrap();
+        assert_eq!(
+            moved, None,
+            "A different entry at the offset is not returned"
+        );
     }
 }
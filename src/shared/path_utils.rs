@@ -1,6 +1,15 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tantivy::schema::Facet;
 
 use super::config::get_config;
+use super::utils::expand_tilde;
+
+/// A working directory as a facet, one level per directory name, so
+/// `/home/me/mono/api` counts under `/home/me/mono` and `/home/me`
+pub fn cwd_facet(cwd: &str) -> Facet {
+    Facet::from_path(cwd.split(['/', '\\']).filter(|part| !part.is_empty()))
+}
 
 /// Extract first 8 characters of a UUID for display
 pub fn short_uuid(uuid: &str) -> &str {
@@ -28,14 +37,93 @@ pub fn project_dir_name(path: &str) -> String {
     path.replace(['/', '\\', '.'], "-")
 }
 
-/// Construct path to a session's JSONL file
+/// JSONL of the most recently written session of the working directory,
+/// i.e. the one being written by the agent that started this process
+pub fn current_session_file() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let dir_name = project_dir_name(&cwd.to_string_lossy());
+    get_config()
+        .get_claude_dirs()
+        .ok()?
+        .iter()
+        .filter_map(|dir| {
+            let pattern = dir.join("projects").join(&dir_name).join("*.jsonl");
+            glob::glob(&pattern.to_string_lossy()).ok()
+        })
+        .flat_map(|paths| paths.flatten())
+        .max_by_key(|p| p.metadata().and_then(|m| m.modified()).ok())
+}
+
+/// Map a path under an aliased (moved) project to its new location, using
+/// the longest matching old path. None when no alias applies.
+pub fn alias_project_path(path: &str, aliases: &HashMap<String, String>) -> Option<String> {
+    aliases
+        .iter()
+        .filter_map(|(old, new)| {
+            let old = expand_tilde(old.trim_end_matches('/'));
+            let rest = path.strip_prefix(old.as_str())?;
+            (rest.is_empty() || rest.starts_with('/')).then_some((old.len(), new, rest))
+        })
+        .max_by_key(|(len, _, _)| *len)
+        .map(|(_, new, rest)| format!("{}{}", expand_tilde(new.trim_end_matches('/')), rest))
+}
+
+/// Construct path to a session's JSONL file, in the first Claude dir that
+/// has it (or the first Claude dir when none does). Sessions of a merged
+/// project are also looked up under the old project directory.
 pub fn session_jsonl_path(project_path: &str, session_id: &str) -> Option<PathBuf> {
-    let claude_dir = get_config().get_claude_dir().ok()?;
-    let dir_name = project_dir_name(project_path);
-    Some(
-        claude_dir
-            .join("projects")
-            .join(dir_name)
-            .join(format!("{}.jsonl", session_id)),
-    )
+    let claude_dirs = get_config().get_claude_dirs().ok()?;
+    let file_name = format!("{}.jsonl", session_id);
+    let mut candidates = vec![project_path.to_string()];
+    for (old, new) in &get_config().project_aliases {
+        let new = expand_tilde(new.trim_end_matches('/'));
+        if let Some(rest) = project_path.strip_prefix(new.as_str())
+            && (rest.is_empty() || rest.starts_with('/'))
+        {
+            candidates.push(format!(
+                "{}{}",
+                expand_tilde(old.trim_end_matches('/')),
+                rest
+            ));
+        }
+    }
+    let relatives: Vec<PathBuf> = candidates
+        .iter()
+        .map(|path| {
+            Path::new("projects")
+                .join(project_dir_name(path))
+                .join(&file_name)
+        })
+        .collect();
+    relatives
+        .iter()
+        .flat_map(|relative| claude_dirs.iter().map(move |dir| dir.join(relative)))
+        .find(|path| path.exists())
+        .or_else(|| Some(claude_dirs.first()?.join(&relatives[0])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_project_path_matches_on_path_boundaries() {
+        let aliases = HashMap::from([
+            ("/src/old".to_string(), "/src/new".to_string()),
+            ("/src/old/vendor/".to_string(), "/src/lib".to_string()),
+        ]);
+        assert_eq!(
+            alias_project_path("/src/old", &aliases).as_deref(),
+            Some("/src/new")
+        );
+        assert_eq!(
+            alias_project_path("/src/old/crates/a", &aliases).as_deref(),
+            Some("/src/new/crates/a")
+        );
+        assert_eq!(
+            alias_project_path("/src/old/vendor/x", &aliases).as_deref(),
+            Some("/src/lib/x")
+        );
+        assert_eq!(alias_project_path("/src/older", &aliases), None);
+    }
 }
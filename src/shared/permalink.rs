@@ -0,0 +1,322 @@
+use super::models::SearchResult;
+use super::path_utils::{home_to_tilde, session_jsonl_path, short_uuid};
+use super::search::{SearchEngine, format_notes};
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+use super::utils::{discover_jsonl_files, group_digits, truncate_content};
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Where a message was recorded on disk
+#[derive(Debug, Clone)]
+pub struct MessageLocation {
+    pub message: SearchResult,
+    pub file: PathBuf,
+    /// 1-based line number in `file`
+    pub line: usize,
+}
+
+/// Line holding the JSONL entry whose `key` field is `value`
+fn find_line(file: &Path, key: &str, value: &str) -> Option<usize> {
+    let reader = BufReader::new(File::open(file).ok()?);
+    reader
+        .lines()
+        .map_while(|line| line.ok())
+        .position(|line| {
+            line.contains(value)
+                && serde_json::from_str::<serde_json::Value>(&line)
+                    .is_ok_and(|entry| entry[key] == value)
+        })
+        .map(|index| index + 1)
+}
+
+/// Line holding the message; summary lines have no uuid of their own and
+/// are found by the leaf they summarize
+fn find_message_line(file: &Path, message: &SearchResult) -> Option<usize> {
+    find_line(file, "uuid", &message.uuid).or_else(|| {
+        let leaf = message.parent_uuid.as_deref()?;
+        (message.message_type == "Summary")
+            .then(|| find_line(file, "leafUuid", leaf))
+            .flatten()
+    })
+}
+
+/// JSONL files that can hold a session's messages: the session file in the
+/// local projects dir, any imported copy, then subagent transcripts next to them
+fn candidate_files(message: &SearchResult) -> Result<Vec<PathBuf>> {
+    let file_name = format!("{}.jsonl", message.session_id);
+    let mut files: Vec<PathBuf> = session_jsonl_path(&message.project_path, &message.session_id)
+        .filter(|path| path.exists())
+        .into_iter()
+        .collect();
+    let discovered = discover_jsonl_files()?;
+    files.extend(
+        discovered
+            .iter()
+            .filter(|f| f.file_name().is_some_and(|n| *n == *file_name))
+            .filter(|f| !files.contains(f))
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+    if message.is_sidechain {
+        let dirs: Vec<_> = files.iter().filter_map(|f| f.parent()).collect();
+        let agents: Vec<_> = discovered
+            .iter()
+            .filter(|f| {
+                f.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("agent-"))
+                    && f.parent().is_some_and(|dir| dirs.contains(&dir))
+            })
+            .cloned()
+            .collect();
+        files.extend(agents);
+    }
+    Ok(files)
+}
+
+/// Resolve a message uuid (or a prefix, as shown after 💬) to its JSONL file
+/// and line number. None when the index has no such message or its file is gone.
+pub fn locate_message(engine: &SearchEngine, uuid: &str) -> Result<Option<MessageLocation>> {
+    let Some(message) = engine
+        .get_messages_by_uuid(&[uuid.to_string()])?
+        .into_iter()
+        .next()
+    else {
+        return Ok(None);
+    };
+    for file in candidate_files(&message)? {
+        if let Some(line) = find_message_line(&file, &message) {
+            return Ok(Some(MessageLocation {
+                message,
+                file,
+                line,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Replace `[result omitted: N chars]` in each message with its tool
+/// output, read back from the session JSONL
+pub fn expand_tool_results(engine: &SearchEngine, messages: &mut [SearchResult]) -> Result<()> {
+    for msg in messages {
+        if !msg.content.contains("[result omitted: ") {
+            continue;
+        }
+        if let Some(location) = locate_message(engine, &msg.uuid)? {
+            msg.content = read_tool_results(&location.file, location.line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Tool output longer than this is folded to one line in session views
+pub const FOLD_TOOL_RESULT_CHARS: usize = 500;
+/// Characters of the call's target (path, command) in a folded result
+const FOLDED_TARGET_CHARS: usize = 60;
+
+/// `Read src/main.rs`: the first tool call in a message, as `[Name] {input}`
+/// lines hold it, with the input's path, command or pattern
+fn describe_tool_call(message: &SearchResult) -> Option<String> {
+    message.content.lines().find_map(|line| {
+        let (name, input) = line.strip_prefix('[')?.split_once("] ")?;
+        if name == "thinking" || name.contains(' ') {
+            return None;
+        }
+        let input: Option<serde_json::Value> = serde_json::from_str(input).ok();
+        let target = input.as_ref().and_then(|input| {
+            [
+                "file_path",
+                "notebook_path",
+                "command",
+                "pattern",
+                "path",
+                "url",
+                "query",
+            ]
+            .iter()
+            .find_map(|key| input.get(key)?.as_str())
+        });
+        Some(match target {
+            Some(target) => format!(
+                "{} {}",
+                name,
+                truncate_content(&home_to_tilde(target), FOLDED_TARGET_CHARS, true)
+            ),
+            None => name.to_string(),
+        })
+    })
+}
+
+/// One-line stand-in for a long tool result, `[tool_result: 14,213 chars,
+/// Read src/main.rs]`, naming the call from `parent`, the message that made
+/// it. None for other messages, short results and failures, which are
+/// worth reading in full.
+pub fn folded_tool_result(message: &SearchResult, parent: Option<&SearchResult>) -> Option<String> {
+    let content = &message.content;
+    if message.message_type != "User" || content.contains("[error] ") {
+        return None;
+    }
+    let size = if let Some(rest) = content.strip_prefix("[result omitted: ") {
+        group_digits(rest.split(' ').next()?.parse().ok()?)
+    } else if let Some(result) = content.strip_prefix("[result] ") {
+        let chars = result.chars().count();
+        if chars <= FOLD_TOOL_RESULT_CHARS {
+            return None;
+        }
+        // The index keeps a preview, cut with … at `tool_result_max_chars`
+        let cut = if result.ends_with('…') { "+" } else { "" };
+        format!("{}{}", group_digits(chars), cut)
+    } else {
+        return None;
+    };
+    Some(match parent.and_then(describe_tool_call) {
+        Some(call) => format!("[tool_result: {} chars, {}]", size, call),
+        None => format!("[tool_result: {} chars]", size),
+    })
+}
+
+/// Full text of messages fetched by id, each under a header:
+/// 💬 id 📅 date [type], and its notes after the text
+pub fn format_messages(messages: &[SearchResult]) -> String {
+    let mut output = String::new();
+    for msg in messages {
+        output.push_str(&format!(
+            "💬 {} 📅 {} [{}]\n{}\n",
+            &msg.uuid[..8.min(msg.uuid.len())],
+            local_time(msg.timestamp).format("%Y-%m-%d %H:%M"),
+            msg.message_type,
+            msg.content
+        ));
+        output.push_str(&format_notes(&msg.notes, ""));
+        output.push('\n');
+    }
+    output
+}
+
+/// Tool output of the entry on `line` of `file`, one `[result]`/`[error]`
+/// part per tool_result block as the index would hold it untruncated. The
+/// index keeps only a preview, or none under `index.skip_tool_results`.
+pub fn read_tool_results(file: &Path, line: usize) -> Result<String> {
+    let reader = BufReader::new(File::open(file)?);
+    let Some(text) = reader.lines().nth(line.saturating_sub(1)).transpose()? else {
+        return Ok(String::new());
+    };
+    let entry: serde_json::Value = serde_json::from_str(&text)?;
+    let parts: Vec<String> = entry["message"]["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|block| block["type"] == "tool_result")
+        .map(|block| {
+            let output = match &block["content"] {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Array(items) => items
+                    .iter()
+                    .filter_map(|item| item["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                _ => String::new(),
+            };
+            let tag = if block["is_error"] == true {
+                "error"
+            } else {
+                "result"
+            };
+            format!("[{}] {}", tag, strip_ansi_escapes::strip_str(&output))
+        })
+        .collect();
+    Ok(parts.join("\n"))
+}
+
+/// Format a message location, shared by CLI and MCP:
+/// 📄 ~/path/session.jsonl:line
+/// 💬 uuid 🗒️ session 📅 date [type]
+pub fn format_message_location(location: &MessageLocation) -> String {
+    let path = location.file.to_string_lossy();
+    let message = &location.message;
+    format!(
+        "📄 {}\n💬 {} 🗒️ {} 📅 {} [{}]\n",
+        file_hyperlink(
+            &path,
+            &format!("{}:{}", home_to_tilde(&path), location.line)
+        ),
+        message.uuid,
+        short_uuid(&message.session_id),
+        local_time(message.timestamp).format("%Y-%m-%d %H:%M"),
+        message.message_type,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("s.jsonl");
+        std::fs::write(
+            &file,
+            "{\"type\":\"summary\",\"leafUuid\":\"u-2\"}\n{\"uuid\":\"u-1\",\"parentUuid\":null}\n{\"uuid\": \"u-2\",\"parentUuid\":\"u-1\"}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_line(&file, "uuid", "u-2"),
+            Some(3),
+            "Mentions in other fields are skipped"
+        );
+        assert_eq!(find_line(&file, "uuid", "u-3"), None);
+        assert_eq!(find_line(&file, "leafUuid", "u-2"), Some(1));
+    }
+
+    #[test]
+    fn test_folded_tool_result_names_the_call() {
+        let message = |message_type: &str, content: String| SearchResult {
+            message_type: message_type.to_string(),
+            content,
+            ..Default::default()
+        };
+        let call = message(
+            "Assistant",
+            "Reading it\n[Read] {\"file_path\":\"/src/main.rs\"}".to_string(),
+        );
+        let long = message("User", format!("[result] {}", "x".repeat(14213)));
+        assert_eq!(
+            folded_tool_result(&long, Some(&call)).unwrap(),
+            "[tool_result: 14,213 chars, Read /src/main.rs]"
+        );
+        let omitted = message("User", "[result omitted: 5000 chars]".to_string());
+        assert_eq!(
+            folded_tool_result(&omitted, None).unwrap(),
+            "[tool_result: 5,000 chars]"
+        );
+
+        let short = message("User", "[result] ok".to_string());
+        let failed = message("User", format!("[error] {}", "x".repeat(900)));
+        let prompt = message("User", "y".repeat(900));
+        for kept in [short, failed, prompt] {
+            assert_eq!(folded_tool_result(&kept, Some(&call)), None);
+        }
+    }
+
+    #[test]
+    fn test_read_tool_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("s.jsonl");
+        std::fs::write(
+            &file,
+            "{\"uuid\":\"u-1\"}\n{\"uuid\":\"u-2\",\"message\":{\"content\":[{\"type\":\"tool_result\",\"content\":\"a.rs\\nb.rs\"},{\"type\":\"tool_result\",\"is_error\":true,\"content\":[{\"type\":\"text\",\"text\":\"denied\"}]}]}}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_tool_results(&file, 2).unwrap(),
+            "[result] a.rs\nb.rs\n[error] denied"
+        );
+        assert_eq!(read_tool_results(&file, 1).unwrap(), "");
+    }
+}
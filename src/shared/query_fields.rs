@@ -0,0 +1,502 @@
+use super::models::{MESSAGE_KINDS, MatchRange};
+use super::search::project_matches;
+use super::utils::expand_tilde;
+use anyhow::{Result, bail};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Longest token Tantivy's default tokenizer keeps, in bytes
+const MAX_TOKEN_BYTES: usize = 40;
+
+/// A `name:value` prefix accepted in search queries
+pub struct QueryField {
+    /// Name typed in the query
+    pub name: &'static str,
+    /// Index field it searches
+    pub field: &'static str,
+}
+
+/// Field prefixes accepted in search queries. Anything else before a `:`
+/// is rejected rather than handed to Tantivy.
+pub const QUERY_FIELDS: &[QueryField] = &[
+    QueryField {
+        name: "project",
+        field: "project",
+    },
+    QueryField {
+        name: "session_id",
+        field: "session_id",
+    },
+    QueryField {
+        name: "has_error",
+        field: "has_error",
+    },
+    QueryField {
+        name: "has_code",
+        field: "has_code",
+    },
+    QueryField {
+        name: "has_attachments",
+        field: "has_attachments",
+    },
+    QueryField {
+        name: "type",
+        field: "message_kind",
+    },
+    QueryField {
+        name: "lang",
+        field: "code_languages",
+    },
+    QueryField {
+        name: "tech",
+        field: "technologies",
+    },
+    QueryField {
+        name: "model",
+        field: "model",
+    },
+    QueryField {
+        name: "summary",
+        field: "summary",
+    },
+    QueryField {
+        name: "path",
+        field: "cwd",
+    },
+    QueryField {
+        name: "url",
+        field: "urls",
+    },
+    QueryField {
+        name: "file",
+        field: "file_mentions",
+    },
+    QueryField {
+        name: "ticket",
+        field: "tickets",
+    },
+    QueryField {
+        name: "meta",
+        field: "custom_metadata",
+    },
+];
+
+fn lookup(name: &str) -> Option<&'static QueryField> {
+    // Index field names keep working for queries written against them
+    QUERY_FIELDS
+        .iter()
+        .find(|f| f.name == name || f.field == name)
+}
+
+fn field_names() -> String {
+    QUERY_FIELDS
+        .iter()
+        .map(|f| format!("{}:", f.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Value as Tantivy should see it, checked against what the field holds
+fn rewrite_value(field: &QueryField, value: &str) -> Result<String> {
+    if value.is_empty() {
+        bail!("Missing value after '{}:'", field.name);
+    }
+    match field.field {
+        "has_error" | "has_code" | "has_attachments" => match value.to_lowercase().as_str() {
+            bool_value @ ("true" | "false") => Ok(bool_value.to_string()),
+            _ => bail!(
+                "Invalid value '{}' for '{}:': use true or false",
+                value,
+                field.name
+            ),
+        },
+        // Kinds are indexed untokenized, in lowercase
+        "message_kind" => match value.to_lowercase() {
+            kind if MESSAGE_KINDS.contains(&kind.as_str()) => Ok(kind),
+            _ => bail!(
+                "Invalid value '{}' for '{}:': use one of {}",
+                value,
+                field.name,
+                MESSAGE_KINDS.join(", ")
+            ),
+        },
+        // Directory names in order, anywhere in the working directory
+        "cwd" if !value.starts_with('"') => {
+            let path = expand_tilde(value);
+            let path = path.trim_matches(['/', '\\']);
+            if path.is_empty() {
+                bail!(
+                    "Invalid value '{}' for '{}:': name a directory",
+                    value,
+                    field.name
+                );
+            }
+            Ok(format!("\"{}\"", path))
+        }
+        // Words of a mentioned URL, path or ticket id in order; `#` isn't indexed
+        "urls" | "file_mentions" | "tickets" if !value.starts_with('"') => {
+            let value = value.trim_start_matches('#');
+            if value.is_empty() {
+                bail!("Missing value after '{}:'", field.name);
+            }
+            Ok(format!("\"{}\"", value))
+        }
+        // Extractor terms are indexed whole and in lowercase: `name` or `name=value`
+        "custom_metadata" => {
+            let term = value.trim_matches('"').trim().to_lowercase();
+            if term.is_empty() || term.starts_with('=') {
+                bail!(
+                    "Invalid value '{}' for '{}:': use <extractor> or <extractor>=<value>",
+                    value,
+                    field.name
+                );
+            }
+            Ok(format!("\"{}\"", term))
+        }
+        // Only the directory name is indexed as project terms
+        "project" if !value.starts_with('"') => Ok(Path::new(value)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(value)
+            .to_string()),
+        _ => Ok(value.to_string()),
+    }
+}
+
+/// `field:value` as Tantivy should see it. A project value naming indexed
+/// projects by substring or glob becomes a phrase per matching directory name.
+fn rewrite_term(field: &QueryField, value: &str, projects: &[String]) -> Result<String> {
+    if field.field == "project" && !value.is_empty() && !value.starts_with('"') {
+        let names: BTreeSet<_> = projects
+            .iter()
+            .filter(|name| project_matches(name, value))
+            .collect();
+        let terms: Vec<_> = names
+            .iter()
+            .map(|name| format!("{}:\"{}\"", field.field, name))
+            .collect();
+        match terms.len() {
+            0 if value.contains(['*', '?', '[']) => {
+                // Glob syntax means nothing to Tantivy; as a phrase it matches nothing
+                return Ok(format!("{}:\"{}\"", field.field, value));
+            }
+            0 => {}
+            1 => return Ok(terms[0].clone()),
+            _ => return Ok(format!("({})", terms.join(" OR "))),
+        }
+    }
+    Ok(format!("{}:{}", field.field, rewrite_value(field, value)?))
+}
+
+/// End of a value starting at `start`: a quoted phrase, or up to whitespace
+/// or a closing parenthesis
+fn value_end(chars: &[char], start: usize) -> usize {
+    if chars.get(start) == Some(&'"') {
+        return chars[start + 1..]
+            .iter()
+            .position(|&c| c == '"')
+            .map_or(chars.len(), |p| start + p + 2);
+    }
+    chars[start..]
+        .iter()
+        .position(|&c| c.is_whitespace() || c == ')')
+        .map_or(chars.len(), |p| start + p)
+}
+
+/// Check the `field:value` terms of a query and map them to index fields
+/// (`lang:rust` → `code_languages:rust`), leaving boolean operators,
+/// grouping and quoted phrases to Tantivy. Unknown fields are an error
+/// instead of a query that silently matches nothing.
+pub fn rewrite_field_query(query: &str) -> Result<String> {
+    rewrite_field_query_with(query, &[])
+}
+
+/// `rewrite_field_query`, resolving `project:` values against these
+/// indexed project names
+pub(crate) fn rewrite_field_query_with(query: &str, projects: &[String]) -> Result<String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut output = String::with_capacity(query.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' {
+            let end = value_end(&chars, i);
+            output.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        let at_term_start = i == 0 || matches!(chars[i - 1], '(' | '+' | '-' | ' ' | '\t' | '\n');
+        let name_len = chars[i..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+            .count();
+        if !at_term_start || name_len == 0 || chars.get(i + name_len) != Some(&':') {
+            output.push(c);
+            i += 1;
+            continue;
+        }
+
+        let name: String = chars[i..i + name_len].iter().collect();
+        let Some(field) = lookup(&name) else {
+            bail!(
+                "Unknown field '{}:' in query. Use one of {}, or quote the text to search for it literally",
+                name,
+                field_names()
+            );
+        };
+        let value_start = i + name_len + 1;
+        let end = value_end(&chars, value_start);
+        let value: String = chars[value_start..end].iter().collect();
+        output.push_str(&rewrite_term(field, &value, projects)?);
+        i = end;
+    }
+    Ok(output)
+}
+
+/// Take a `bookmarked:true` term out of a query. Bookmarks live outside the
+/// index, so callers turn it into a uuid filter; the rest of the query is
+/// returned untouched without one, and `*` when nothing else is left.
+pub fn split_bookmarked(query: &str) -> Result<(String, bool)> {
+    let mut bookmarked = false;
+    let mut in_phrase = false;
+    let mut rest = Vec::new();
+    for word in query.split_whitespace() {
+        let value = word
+            .split_once(':')
+            .filter(|(name, _)| !in_phrase && name.eq_ignore_ascii_case("bookmarked"))
+            .map(|(_, value)| value);
+        in_phrase ^= word.matches('"').count() % 2 == 1;
+        match value.map(str::to_lowercase).as_deref() {
+            Some("true") => bookmarked = true,
+            Some(other) => bail!(
+                "Invalid value '{}' for 'bookmarked:': only bookmarked:true is supported",
+                other
+            ),
+            None => rest.push(word),
+        }
+    }
+    let rest = if !bookmarked {
+        query.to_string()
+    } else if rest.is_empty() {
+        "*".to_string()
+    } else {
+        rest.join(" ")
+    };
+    Ok((rest, bookmarked))
+}
+
+/// Alphanumeric runs with their byte offsets, as the default tokenizer
+/// splits text before lowercasing it
+fn tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push((s, &text[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, &text[s..]));
+    }
+    tokens.retain(|(_, token)| token.len() < MAX_TOKEN_BYTES);
+    tokens
+}
+
+/// Content terms of a query, lowercased: its words and phrase words, leaving
+/// out `field:value` terms, boolean operators and excluded (`-`) terms
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query_words(query) {
+        for (_, token) in tokens(&word) {
+            let token = token.to_lowercase();
+            if !terms.contains(&token) {
+                terms.push(token);
+            }
+        }
+    }
+    terms
+}
+
+/// The words of a query `query_terms` takes its terms from, as written:
+/// quoted phrases keep their quotes and `tokio-uring` stays one word
+pub fn query_words(query: &str) -> Vec<String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    let mut depth = 0;
+    // Depths of open `-( ... )` groups
+    let mut excluded_groups: Vec<usize> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '(' => depth += 1,
+            ')' => {
+                if excluded_groups.last() == Some(&depth) {
+                    excluded_groups.pop();
+                }
+                depth -= usize::from(depth > 0);
+            }
+            '-' if chars.get(i + 1) == Some(&'(') => {
+                depth += 1;
+                excluded_groups.push(depth);
+                i += 1;
+            }
+            c if c.is_whitespace() || c == '+' => {}
+            c => {
+                let excluded = c == '-';
+                let start = i + usize::from(excluded);
+                let name_len = chars[start..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                let is_field = name_len > 0 && chars.get(start + name_len) == Some(&':');
+                let end = value_end(&chars, start + if is_field { name_len + 1 } else { 0 });
+                let word: String = chars[start..end].iter().collect();
+                if !excluded
+                    && !is_field
+                    && excluded_groups.is_empty()
+                    && !matches!(word.as_str(), "AND" | "OR" | "NOT")
+                {
+                    words.push(word);
+                }
+                i = end.max(i + 1);
+                continue;
+            }
+        }
+        i += 1;
+    }
+    words
+}
+
+/// Where `terms` (from `query_terms`) occur in `content`, as whole tokens
+pub fn match_ranges(content: &str, terms: &[String]) -> Vec<MatchRange> {
+    match_ranges_by(content, terms, str::to_lowercase)
+}
+
+/// `match_ranges` comparing words and terms once both went through
+/// `normalize`, such as the stemmer content was indexed with
+pub fn match_ranges_by(
+    content: &str,
+    terms: &[String],
+    mut normalize: impl FnMut(&str) -> String,
+) -> Vec<MatchRange> {
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let terms: Vec<String> = terms.iter().map(|term| normalize(term)).collect();
+    let mut ranges = Vec::new();
+    let mut chars_before = 0;
+    let mut counted_to = 0;
+    for (start, token) in tokens(content) {
+        let token_term = normalize(token);
+        if !terms.contains(&token_term) {
+            continue;
+        }
+        chars_before += content[counted_to..start].chars().count();
+        let char_len = token.chars().count();
+        ranges.push(MatchRange {
+            start,
+            end: start + token.len(),
+            char_start: chars_before,
+            char_end: chars_before + char_len,
+        });
+        chars_before += char_len;
+        counted_to = start + token.len();
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_field_query() {
+        assert_eq!(
+            rewrite_field_query("lang:rust AND (tech:docker OR has_error:TRUE) -model:opus")
+                .unwrap(),
+            "code_languages:rust AND (technologies:docker OR has_error:true) -model:opus"
+        );
+        assert_eq!(
+            rewrite_field_query("project:/home/me/vault-rs \"error: not: fields\"").unwrap(),
+            "project:vault-rs \"error: not: fields\""
+        );
+        assert_eq!(
+            rewrite_field_query("re-run tokio::spawn")
+                .unwrap_err()
+                .to_string(),
+            "Unknown field 'tokio:' in query. Use one of project:, session_id:, has_error:, \
+             has_code:, has_attachments:, type:, lang:, tech:, model:, summary:, path:, url:, \
+             file:, ticket:, meta:, or quote the text to search for it literally"
+        );
+        assert!(rewrite_field_query("has_error:maybe").is_err());
+        assert_eq!(
+            rewrite_field_query("type:Compact OR (type:hook AND has_error:true)").unwrap(),
+            "message_kind:compact OR (message_kind:hook AND has_error:true)"
+        );
+        assert!(rewrite_field_query("type:robot").is_err());
+        assert!(rewrite_field_query("docker lang:").is_err());
+        assert_eq!(
+            rewrite_field_query("plan path:infra/terraform/").unwrap(),
+            "plan cwd:\"infra/terraform\""
+        );
+        assert!(rewrite_field_query("path:/").is_err());
+        assert_eq!(
+            rewrite_field_query("meta:Jira=OPS-12 OR meta:\"customer=Acme Corp\"").unwrap(),
+            "custom_metadata:\"jira=ops-12\" OR custom_metadata:\"customer=acme corp\""
+        );
+        assert!(rewrite_field_query("meta:=ops-12").is_err());
+        assert_eq!(
+            rewrite_field_query("ticket:#456 OR ticket:PROJ-2291 url:github.com/org").unwrap(),
+            "tickets:\"456\" OR tickets:\"PROJ-2291\" urls:\"github.com/org\""
+        );
+    }
+
+    #[test]
+    fn test_split_bookmarked() {
+        assert_eq!(
+            split_bookmarked("docker bookmarked:TRUE lang:rust").unwrap(),
+            ("docker lang:rust".to_string(), true)
+        );
+        assert_eq!(
+            split_bookmarked("bookmarked:true").unwrap(),
+            ("*".to_string(), true)
+        );
+        assert_eq!(
+            split_bookmarked("\"see bookmarked:true here\"").unwrap(),
+            ("\"see bookmarked:true here\"".to_string(), false)
+        );
+        assert!(split_bookmarked("bookmarked:false").is_err());
+    }
+
+    #[test]
+    fn test_query_terms_skip_fields_operators_and_exclusions() {
+        assert_eq!(
+            query_terms(
+                "Docker AND \"compose up\" lang:rust -wasm -(foo OR bar) (Tokio OR async-std) project:\"my app\""
+            ),
+            vec!["docker", "compose", "up", "tokio", "async", "std"]
+        );
+        assert_eq!(
+            query_words("\"compose up\" tokio-uring NOT lang:rust"),
+            vec!["\"compose up\"", "tokio-uring"]
+        );
+    }
+
+    #[test]
+    fn test_match_ranges_give_byte_and_char_offsets() {
+        let content = "Café — DOCKER build, docker's dockerfile";
+        let ranges = match_ranges(content, &query_terms("docker"));
+        assert_eq!(ranges.len(), 2, "Whole tokens only, not dockerfile");
+        assert_eq!(&content[ranges[0].start..ranges[0].end], "DOCKER");
+        assert_eq!((ranges[0].char_start, ranges[0].char_end), (7, 13));
+        assert_eq!(&content[ranges[1].start..ranges[1].end], "docker");
+        assert_eq!(ranges[1].char_start, 21);
+        assert!(match_ranges(content, &[]).is_empty());
+    }
+}
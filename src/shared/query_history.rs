@@ -0,0 +1,358 @@
+use super::config::get_config;
+use super::session_store::open_database;
+use super::timezone::local_time;
+use super::utils::edit_distance;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redb::{ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Record id (microseconds since the epoch, so oldest first) → JSON-encoded `QueryRecord`
+const QUERIES: TableDefinition<u64, &[u8]> = TableDefinition::new("queries");
+
+/// Past queries a zero-hit search suggests at most
+const MAX_SUGGESTIONS: usize = 3;
+
+/// One search as it was run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueryRecord {
+    pub query: String,
+    /// Filters given with it, as `name=value`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+    /// Matching messages before dedupe and limit
+    pub hits: usize,
+    /// `cli` or `mcp`
+    pub source: String,
+    pub at: DateTime<Utc>,
+    /// Message and session ids opened from its results afterwards
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chosen: Vec<String>,
+}
+
+impl QueryRecord {
+    pub fn new(query: &str, filters: Vec<String>, hits: usize, source: &str) -> Self {
+        Self {
+            query: query.trim().to_string(),
+            filters,
+            hits,
+            source: source.to_string(),
+            at: Utc::now(),
+            chosen: Vec::new(),
+        }
+    }
+}
+
+/// Searches run from the CLI and MCP, in the data dir so they outlive
+/// `cache clear` and rebuilds
+pub struct QueryHistory {
+    path: PathBuf,
+}
+
+impl QueryHistory {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join("query_history.redb"),
+        }
+    }
+
+    /// The store in the configured data dir
+    pub fn open_default() -> Result<Self> {
+        let data_dir = get_config().get_data_dir()?;
+        std::fs::create_dir_all(&data_dir)?;
+        Ok(Self::new(&data_dir))
+    }
+
+    /// Add a search; returns its id for `add_chosen`
+    pub fn record(&self, record: &QueryRecord) -> Result<u64> {
+        let db = open_database(&self.path)?;
+        let txn = db.begin_write()?;
+        let id = {
+            let mut table = txn.open_table(QUERIES)?;
+            // Two searches in the same microsecond get consecutive ids
+            let mut id = record.at.timestamp_micros().max(0) as u64;
+            while table.get(id)?.is_some() {
+                id += 1;
+            }
+            table.insert(id, serde_json::to_vec(record)?.as_slice())?;
+            id
+        };
+        txn.commit()?;
+        Ok(id)
+    }
+
+    /// Note results of search `id` that were opened, once each
+    pub fn add_chosen(&self, id: u64, chosen: &[String]) -> Result<()> {
+        let db = open_database(&self.path)?;
+        let txn = db.begin_write()?;
+        {
+            let mut table = txn.open_table(QUERIES)?;
+            let record = table
+                .get(id)?
+                .map(|value| serde_json::from_slice::<QueryRecord>(value.value()))
+                .transpose()?;
+            if let Some(mut record) = record {
+                for id in chosen {
+                    if !record.chosen.contains(id) {
+                        record.chosen.push(id.clone());
+                    }
+                }
+                table.insert(id, serde_json::to_vec(&record)?.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Every recorded search, oldest first
+    pub fn list(&self) -> Result<Vec<QueryRecord>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let db = open_database(&self.path)?;
+        let txn = db.begin_read()?;
+        let table = match txn.open_table(QUERIES) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut records = Vec::new();
+        for row in table.iter()? {
+            let (_, value) = row?;
+            records.push(serde_json::from_slice(value.value())?);
+        }
+        Ok(records)
+    }
+}
+
+/// Record a search in the default store when `search.record_queries` is on.
+/// Returns its id; history is best effort, so failures are only logged.
+pub fn record_query(record: &QueryRecord) -> Option<u64> {
+    if !get_config().search.record_queries || record.query.is_empty() {
+        return None;
+    }
+    QueryHistory::open_default()
+        .and_then(|history| history.record(record))
+        .map_err(|e| tracing::debug!("Query not recorded: {}", e))
+        .ok()
+}
+
+/// Searches grouped by query text, case and spacing ignored
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStats {
+    pub query: String,
+    pub count: usize,
+    pub last_hits: usize,
+    pub last_at: DateTime<Utc>,
+    /// Results opened over all its runs
+    pub chosen: usize,
+}
+
+fn normalize(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Stats per distinct query, most often run first (latest breaks ties)
+pub fn query_frequencies(records: &[QueryRecord]) -> Vec<QueryStats> {
+    let mut stats: HashMap<String, QueryStats> = HashMap::new();
+    for record in records {
+        let entry = stats
+            .entry(normalize(&record.query))
+            .or_insert_with(|| QueryStats {
+                query: record.query.clone(),
+                count: 0,
+                last_hits: record.hits,
+                last_at: record.at,
+                chosen: 0,
+            });
+        entry.count += 1;
+        entry.chosen += record.chosen.len();
+        if record.at >= entry.last_at {
+            entry.query = record.query.clone();
+            entry.last_hits = record.hits;
+            entry.last_at = record.at;
+        }
+    }
+    let mut stats: Vec<QueryStats> = stats.into_values().collect();
+    stats.sort_by(|a, b| b.count.cmp(&a.count).then(b.last_at.cmp(&a.last_at)));
+    stats
+}
+
+/// Whether two query words are the same or one looks like a typo of the other
+fn words_match(a: &str, b: &str) -> bool {
+    a == b || (a.chars().count().min(b.chars().count()) >= 4 && edit_distance(a, b) <= 1)
+}
+
+/// Past queries that found something and share words with `query`, for a
+/// search that found nothing: most shared words first, then most used
+pub fn suggest_refinements(records: &[QueryRecord], query: &str) -> Vec<QueryStats> {
+    let normalized = normalize(query);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    let mut scored: Vec<(usize, QueryStats)> = query_frequencies(records)
+        .into_iter()
+        .filter(|stats| stats.last_hits > 0 && normalize(&stats.query) != normalized)
+        .filter_map(|stats| {
+            let candidate = normalize(&stats.query);
+            let shared = words
+                .iter()
+                .filter(|word| candidate.split_whitespace().any(|c| words_match(word, c)))
+                .count();
+            (shared > 0).then_some((shared, stats))
+        })
+        .collect();
+    scored.sort_by(|(a, a_stats), (b, b_stats)| b.cmp(a).then(b_stats.count.cmp(&a_stats.count)));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, stats)| stats)
+        .collect()
+}
+
+/// Suggestions appended to "No results found.", empty without any
+pub fn format_suggestions(suggestions: &[QueryStats]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let mut output = String::from("Similar past searches that found results:\n");
+    for stats in suggestions {
+        output.push_str(&format!(
+            "  🔎 \"{}\" ({} hits, run {}×)\n",
+            stats.query, stats.last_hits, stats.count
+        ));
+    }
+    output
+}
+
+/// Which searches `query_history` lists
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum QueryHistoryView {
+    /// Latest searches first
+    #[default]
+    Recent,
+    /// Distinct queries, most often run first
+    Frequent,
+    /// Distinct queries that found nothing the last time they ran
+    Failed,
+}
+
+impl QueryHistoryView {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "recent" => Some(Self::Recent),
+            "frequent" => Some(Self::Frequent),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Recorded searches for `query_history`, shared by CLI and MCP
+pub fn format_query_history(
+    records: &[QueryRecord],
+    view: QueryHistoryView,
+    limit: usize,
+) -> String {
+    if records.is_empty() {
+        return "No searches recorded yet.\n".to_string();
+    }
+    let mut output = String::new();
+    match view {
+        QueryHistoryView::Recent => {
+            for record in records.iter().rev().take(limit) {
+                output.push_str(&format!(
+                    "🔎 \"{}\" {} hits 📅 {} ({})",
+                    record.query,
+                    record.hits,
+                    local_time(record.at).format("%Y-%m-%d %H:%M"),
+                    record.source
+                ));
+                if !record.filters.is_empty() {
+                    output.push_str(&format!(" [{}]", record.filters.join(", ")));
+                }
+                if !record.chosen.is_empty() {
+                    output.push_str(&format!(" → opened {}", record.chosen.join(", ")));
+                }
+                output.push('\n');
+            }
+        }
+        QueryHistoryView::Frequent | QueryHistoryView::Failed => {
+            let stats = query_frequencies(records);
+            let stats: Vec<_> = stats
+                .iter()
+                .filter(|s| view == QueryHistoryView::Frequent || s.last_hits == 0)
+                .take(limit)
+                .collect();
+            if stats.is_empty() {
+                return "No searches without results.\n".to_string();
+            }
+            for s in stats {
+                output.push_str(&format!(
+                    "🔎 \"{}\" run {}×, {} hits last time, {} results opened 📅 {}\n",
+                    s.query,
+                    s.count,
+                    s.last_hits,
+                    s.chosen,
+                    local_time(s.last_at).format("%Y-%m-%d %H:%M")
+                ));
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_history_records_and_suggests_refinements() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = QueryHistory::new(temp_dir.path());
+        assert!(history.list().unwrap().is_empty());
+
+        let first = history
+            .record(&QueryRecord::new("kubectl rollout", Vec::new(), 12, "mcp"))
+            .unwrap();
+        history
+            .record(&QueryRecord::new("Kubectl  rollout", Vec::new(), 9, "cli"))
+            .unwrap();
+        history
+            .record(&QueryRecord::new(
+                "nginx proxy",
+                vec!["project=web".to_string()],
+                3,
+                "mcp",
+            ))
+            .unwrap();
+        history
+            .record(&QueryRecord::new("kubectl zeppelin", Vec::new(), 0, "mcp"))
+            .unwrap();
+        history.add_chosen(first, &["c1".to_string()]).unwrap();
+        history.add_chosen(first, &["c1".to_string()]).unwrap();
+
+        let records = history.list().unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].chosen, vec!["c1"]);
+
+        let frequent = query_frequencies(&records);
+        assert_eq!(frequent[0].query, "Kubectl  rollout");
+        assert_eq!((frequent[0].count, frequent[0].chosen), (2, 1));
+
+        // A typo still matches; queries that found nothing aren't suggested
+        let suggestions = suggest_refinements(&records, "kubectll restart");
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].last_hits, 9);
+        assert!(suggest_refinements(&records, "terraform").is_empty());
+
+        let failed = format_query_history(&records, QueryHistoryView::Failed, 10);
+        assert!(failed.contains("kubectl zeppelin"));
+        assert!(!failed.contains("nginx"));
+    }
+}
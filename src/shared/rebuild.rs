@@ -0,0 +1,197 @@
+use super::cache::{CacheManager, IndexProgress};
+use super::config::get_config;
+use super::extractive_summary::EXTRACTIVE_SOURCE;
+use super::indexer::{SCHEMA_VERSION, SearchIndexer};
+use super::metadata::metadata_config_digest;
+use super::search::SearchEngine;
+use super::session_store::{SessionStore, StoredSummary, save_session_summary};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Files in the cache dir that belong to running processes rather than to
+/// the index; a swap moves them into the new directory, open as they are
+const CARRIED_OVER: &[&str] = &["index.lock", "daemon.sock"];
+
+/// Whether the index at `index_path` was built by this schema version with
+/// the configured analyzer and metadata settings. Every schema change so far
+/// needs the JSONL re-parsed, so an outdated index is migrated with
+/// `rebuild_index`.
+pub fn index_is_current(index_path: &Path) -> Result<bool> {
+    let cache = CacheManager::new(index_path)?;
+    let version = cache.index_version();
+    if version != SCHEMA_VERSION {
+        info!(
+            "Index schema v{} is outdated (current: v{})",
+            version, SCHEMA_VERSION
+        );
+        return Ok(false);
+    }
+    if cache.metadata_digest() != metadata_config_digest(&get_config().metadata) {
+        info!("Metadata patterns or extractors changed since the index was built");
+        return Ok(false);
+    }
+    SearchIndexer::validate_schema(index_path)
+}
+
+/// Sibling directory a rebuild is written to: `<cache_dir>.new`
+fn sibling_dir(index_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = index_path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    index_path.with_file_name(name)
+}
+
+/// Index `files` from scratch into `<cache_dir>.new`, then swap it in for
+/// `index_path`. The old index answers searches until the swap, and a
+/// rebuild that fails or is interrupted leaves it in place.
+pub fn rebuild_index(
+    index_path: &Path,
+    files: Vec<PathBuf>,
+    on_progress: impl FnMut(IndexProgress),
+) -> Result<CacheManager> {
+    let staging = sibling_dir(index_path, ".new");
+    if staging.exists() {
+        warn!("Removing unfinished rebuild at {}", staging.display());
+        fs::remove_dir_all(&staging)?;
+    }
+    let summaries = stored_summaries(index_path);
+    // Neither can access times, which `cache prune --never-accessed` goes by
+    let session_access = CacheManager::new(index_path)?.get_session_access().clone();
+    {
+        let mut indexer = SearchIndexer::new(&staging)?;
+        let mut cache = CacheManager::new(&staging)?;
+        cache.restore_session_access(session_access);
+        cache.update_incremental_with_progress(&mut indexer, files, on_progress)?;
+    }
+    if let Err(e) = restore_summaries(&staging, summaries) {
+        warn!("Failed to carry stored summaries over: {}", e);
+    }
+    swap_into_place(&staging, index_path)?;
+    info!("Rebuilt index swapped into {}", index_path.display());
+    CacheManager::new(index_path)
+}
+
+/// Summaries stored for sessions, which JSONL can't give back: extractive
+/// ones are only a cache and are made again
+fn stored_summaries(index_path: &Path) -> Vec<(String, StoredSummary)> {
+    match SessionStore::new(index_path).summaries() {
+        Ok(summaries) => summaries
+            .into_iter()
+            .filter(|(_, summary)| summary.source != EXTRACTIVE_SOURCE)
+            .collect(),
+        Err(e) => {
+            warn!("Stored summaries unavailable: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Store and index summaries again in a rebuilt index, for the sessions
+/// still in it
+fn restore_summaries(index_path: &Path, summaries: Vec<(String, StoredSummary)>) -> Result<()> {
+    if summaries.is_empty() {
+        return Ok(());
+    }
+    let engine = SearchEngine::new(index_path, HashMap::new())?;
+    for (session_id, summary) in summaries {
+        let messages = engine.get_session_messages(&session_id)?;
+        if messages.first().is_some_and(|m| m.session_id == session_id) {
+            save_session_summary(index_path, &messages, &summary)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace `target` with `staging`. The two renames leave `target` missing
+/// only between them; the old directory is deleted after, or put back if
+/// the new one can't be moved in.
+pub fn swap_into_place(staging: &Path, target: &Path) -> Result<()> {
+    if !target.exists() {
+        fs::rename(staging, target)?;
+        return Ok(());
+    }
+    carry_over(target, staging)?;
+    let old = sibling_dir(target, ".old");
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    if let Err(e) = fs::rename(target, &old) {
+        carry_over(staging, target)?;
+        return Err(e.into());
+    }
+    if let Err(e) = fs::rename(staging, target) {
+        fs::rename(&old, target)?;
+        carry_over(staging, target)?;
+        return Err(e.into());
+    }
+    if let Err(e) = fs::remove_dir_all(&old) {
+        warn!("Failed to remove old index {}: {}", old.display(), e);
+    }
+    Ok(())
+}
+
+/// Move the files of running processes from one cache dir to the other
+fn carry_over(from: &Path, to: &Path) -> Result<()> {
+    for name in CARRIED_OVER {
+        let file = from.join(name);
+        if file.exists() {
+            fs::rename(&file, to.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_swap_keeps_carried_over_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("cache");
+        let staging = sibling_dir(&target, ".new");
+        fs::create_dir_all(&target).unwrap();
+        fs::create_dir_all(&staging).unwrap();
+        fs::write(target.join("old.idx"), "old").unwrap();
+        fs::write(target.join("index.lock"), "").unwrap();
+        fs::write(staging.join("meta.json"), "new").unwrap();
+
+        swap_into_place(&staging, &target).unwrap();
+        assert!(target.join("meta.json").exists());
+        assert!(target.join("index.lock").exists());
+        assert!(!target.join("old.idx").exists());
+        assert!(!staging.exists());
+        assert!(!sibling_dir(&target, ".old").exists());
+    }
+
+    #[test]
+    fn test_rebuild_keeps_session_access() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("cache");
+        let mut cache = CacheManager::new(&target).unwrap();
+        let mut indexer = SearchIndexer::new(&target).unwrap();
+        cache.restore_session_access(HashMap::from([("opened-session".to_string(), Utc::now())]));
+        cache.update_incremental(&mut indexer, Vec::new()).unwrap();
+        drop(indexer);
+
+        let cache = rebuild_index(&target, Vec::new(), |_| {}).unwrap();
+        assert!(cache.get_session_access().contains_key("opened-session"));
+    }
+
+    #[test]
+    fn test_failed_swap_puts_the_old_index_back() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("cache");
+        // Staged somewhere that can't be moved in, such as another device
+        let staging = sibling_dir(&target, ".gone");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("meta.json"), "old").unwrap();
+
+        assert!(swap_into_place(&staging, &target).is_err());
+        assert_eq!(fs::read_to_string(target.join("meta.json")).unwrap(), "old");
+        assert!(!sibling_dir(&target, ".old").exists());
+    }
+}
@@ -0,0 +1,123 @@
+use super::config::{Config, set_config_entry};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Search replayed by name with `run_saved_search`; fields mirror the
+/// `search_conversations` arguments
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SavedSearch {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Rolling window, resolved to `after` each time the search runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub within_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+}
+
+impl SavedSearch {
+    /// `search_conversations` arguments, with `within_days` turned into `after`
+    pub fn to_search_args(&self) -> serde_json::Value {
+        let after = match self.within_days {
+            Some(days) => Some((Utc::now() - Duration::days(days as i64)).to_rfc3339()),
+            None => self.after.clone(),
+        };
+        serde_json::json!({
+            "query": self.query,
+            "project": self.project,
+            "host": self.host,
+            "branch": self.branch,
+            "after": after,
+            "before": self.before,
+            "sort_by": self.sort_by.as_deref().unwrap_or("relevance"),
+            "limit": self.limit.unwrap_or(10),
+            "include": self.include,
+        })
+    }
+
+    /// One-line description for listings
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("\"{}\"", self.query)];
+        let filters = [
+            ("project", &self.project),
+            ("host", &self.host),
+            ("branch", &self.branch),
+            ("after", &self.after),
+            ("before", &self.before),
+            ("sort", &self.sort_by),
+        ];
+        for (name, value) in filters {
+            if let Some(value) = value {
+                parts.push(format!("{}={}", name, value));
+            }
+        }
+        if let Some(days) = self.within_days {
+            parts.push(format!("last {} days", days));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Saved searches as currently on disk; the global config is loaded once,
+/// so this picks up searches saved since startup
+pub fn load_saved_searches() -> Result<HashMap<String, SavedSearch>> {
+    Ok(Config::load()?.saved_searches)
+}
+
+/// Store a saved search in the config file, leaving other settings as they
+/// are. Returns true when it replaced one with the same name.
+pub fn save_search(name: &str, search: &SavedSearch) -> Result<bool> {
+    set_config_entry("saved_searches", name, serde_yaml::to_value(search)?)
+}
+
+/// List saved searches by name, shared by CLI and MCP
+pub fn format_saved_searches(searches: &HashMap<String, SavedSearch>) -> String {
+    if searches.is_empty() {
+        return "No saved searches. Add one with save_search or under saved_searches in config.\n"
+            .to_string();
+    }
+    let mut names: Vec<_> = searches.keys().collect();
+    names.sort();
+    let mut output = String::new();
+    for name in names {
+        output.push_str(&format!("🔖 {} {}\n", name, searches[name].describe()));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_days_resolves_to_after() {
+        let search: SavedSearch =
+            serde_yaml::from_str("query: deploy\nbranch: main\nwithin_days: 7\n").unwrap();
+        let args = search.to_search_args();
+        let after = chrono::DateTime::parse_from_rfc3339(args["after"].as_str().unwrap()).unwrap();
+        let age = Utc::now() - after.with_timezone(&Utc);
+        assert!((age - Duration::days(7)).num_seconds().abs() < 60);
+        assert_eq!(args["branch"], "main");
+        assert_eq!(args["sort_by"], "relevance");
+        assert_eq!(args["limit"], 10);
+
+        let yaml = serde_yaml::to_string(&search).unwrap();
+        assert!(!yaml.contains("project"), "Unset filters are not written");
+        assert_eq!(serde_yaml::from_str::<SavedSearch>(&yaml).unwrap(), search);
+    }
+}
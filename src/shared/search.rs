@@ -1,15 +1,132 @@
-use super::models::{SearchQuery, SearchResult, SortOrder};
-use super::path_utils::{session_jsonl_path, short_uuid};
+use super::config::UsageConfig;
+use super::decisions::{Decision, extract_decisions};
+use super::errors::{
+    ErrorCluster, ErrorOccurrence, ErrorResolution, cluster_errors, extract_error_messages,
+};
+use super::indexer::{register_tokenizers, tokenizer_of};
+use super::models::{
+    CommandRun, DirectoryBreakdown, FileTouch, IndexStats, LOCAL_HOST, MatchRange, ProjectInfo,
+    SearchFacets, SearchQuery, SearchResult, SessionActivity, SessionInfo, ShellCommand, SortOrder,
+    TokenUsage,
+};
+use super::parser::JsonlParser;
+use super::path_utils::{cwd_facet, session_jsonl_path, short_uuid};
+use super::query_fields::{
+    match_ranges, match_ranges_by, query_terms, query_words, rewrite_field_query_with,
+};
+use super::session_store::SessionStore;
+use super::sessions::{format_title, title_from_message, title_from_summary};
 use super::terminal::file_hyperlink;
-use super::utils::truncate_content;
+use super::timezone::local_time;
+use super::tool_usage::ToolUsageReport;
+use super::topics::TopicReport;
+use super::transcripts::CLAUDE_CODE_SOURCE;
+use super::usage::UsageReport;
+use super::utils::{edit_distance, expand_tilde, is_near_duplicate, truncate_content};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
-use std::path::Path;
-use tantivy::collector::TopDocs;
-use tantivy::query::{BooleanQuery, Occur, QueryParser, TermQuery};
-use tantivy::schema::{Field, IndexRecordOption, Value};
-use tantivy::{Index, IndexReader, ReloadPolicy, TantivyDocument, Term};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use tantivy::collector::{Count, DocSetCollector, FacetCollector, TopDocs};
+use tantivy::query::{
+    AllQuery, BooleanQuery, EmptyQuery, EnableScoring, Occur, QueryParser, RangeQuery, TermQuery,
+    TermSetQuery,
+};
+use tantivy::schema::{Facet, Field, IndexRecordOption, Value};
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::{
+    DocAddress, DocId, DocSet, Index, IndexReader, ReloadPolicy, Searcher, SegmentReader,
+    TERMINATED, TantivyDocument, Term,
+};
+use tracing::debug;
+
+/// Documents of a segment `weight` matches, or all of them without one
+fn segment_docs(
+    segment_reader: &SegmentReader,
+    weight: Option<&dyn tantivy::query::Weight>,
+) -> Result<Vec<DocId>> {
+    let Some(weight) = weight else {
+        return Ok((0..segment_reader.max_doc()).collect());
+    };
+    let mut scorer = weight.scorer(segment_reader, 1.0)?;
+    let mut docs = Vec::new();
+    while scorer.doc() != TERMINATED {
+        docs.push(scorer.doc());
+        scorer.advance();
+    }
+    Ok(docs)
+}
+
+/// Fold documents of one segment into per-session aggregates from fast fields
+fn aggregate_sessions(
+    segment_reader: &SegmentReader,
+    docs: impl Iterator<Item = DocId>,
+    project_filter: Option<&str>,
+    sessions: &mut HashMap<String, SessionInfo>,
+) -> Result<()> {
+    let fast_fields = segment_reader.fast_fields();
+    let (Some(session_col), Some(cwd_col), Some(type_col)) = (
+        fast_fields.str("session_id")?,
+        fast_fields.str("cwd")?,
+        fast_fields.str("message_type")?,
+    ) else {
+        return Ok(());
+    };
+    let timestamp_col = fast_fields.date("timestamp")?;
+    let token_count_col = fast_fields.u64("token_count")?;
+    let mut session_id = String::new();
+    let mut cwd = String::new();
+    let mut message_type = String::new();
+
+    for doc in docs {
+        let Some(ord) = session_col.term_ords(doc).next() else {
+            continue;
+        };
+        session_col.ord_to_str(ord, &mut session_id)?;
+
+        cwd.clear();
+        if let Some(ord) = cwd_col.term_ords(doc).next() {
+            cwd_col.ord_to_str(ord, &mut cwd)?;
+        }
+        if let Some(filter) = project_filter
+            && !project_matches(&cwd, filter)
+        {
+            continue;
+        }
+
+        message_type.clear();
+        if let Some(ord) = type_col.term_ords(doc).next() {
+            type_col.ord_to_str(ord, &mut message_type)?;
+        }
+        let timestamp = timestamp_col
+            .first(doc)
+            .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+            .unwrap_or_default();
+        let counts = matches!(message_type.as_str(), "User" | "Assistant");
+
+        let info = sessions
+            .entry(session_id.clone())
+            .or_insert_with(|| SessionInfo {
+                session_id: session_id.clone(),
+                project_path: cwd.clone(),
+                first_timestamp: timestamp,
+                last_timestamp: timestamp,
+                message_count: 0,
+                tokens: 0,
+                title: None,
+                last_accessed: None,
+                continued_in: Vec::new(),
+            });
+        info.first_timestamp = info.first_timestamp.min(timestamp);
+        info.last_timestamp = info.last_timestamp.max(timestamp);
+        if counts {
+            info.message_count += 1;
+        }
+        info.tokens += token_count_col.first(doc).unwrap_or(0);
+    }
+    Ok(())
+}
 
 /// Extract project name from a path and split into TEXT-tokenizer segments.
 /// Tantivy's default TEXT tokenizer splits on non-alphanumeric characters,
@@ -38,7 +155,10 @@ fn build_project_query(project_field: Field, filter: &str) -> Box<dyn tantivy::q
     Box::new(BooleanQuery::new(segment_queries))
 }
 
-fn project_matches(project_path: &str, filter: &str) -> bool {
+/// Whether the project at `project_path` (or named so) is one `filter`
+/// names: the same directory name, a case-insensitive part of it, or a glob.
+/// A path filter is reduced to its last component.
+pub(crate) fn project_matches(project_path: &str, filter: &str) -> bool {
     let filter_name = Path::new(filter)
         .file_name()
         .and_then(|n| n.to_str())
@@ -47,7 +167,127 @@ fn project_matches(project_path: &str, filter: &str) -> bool {
         .file_name()
         .and_then(|n| n.to_str())
         .unwrap_or(project_path);
+    if filter_name.contains(['*', '?', '[']) {
+        return glob::Pattern::new(filter_name).is_ok_and(|pattern| pattern.matches(result_name));
+    }
     result_name == filter_name
+        || (!filter_name.is_empty()
+            && result_name
+                .to_lowercase()
+                .contains(&filter_name.to_lowercase()))
+}
+
+/// Score tweak multiplying BM25 by the boost of the message's kind and by
+/// `0.5^(age / half_life)`, so that of two equally relevant messages the
+/// user's own words and the recent one rank first. Messages without a
+/// timestamp, or from the future, aren't decayed.
+fn score_tweak(
+    half_life_days: Option<f64>,
+    type_boosts: HashMap<String, f32>,
+) -> impl Fn(&SegmentReader) -> Box<dyn Fn(DocId, f32) -> f32> + Send + Sync {
+    let now_millis = Utc::now().timestamp_millis();
+    let half_life_millis = half_life_days.map(|days| days * 86_400_000.0);
+    move |segment_reader: &SegmentReader| {
+        let fast_fields = segment_reader.fast_fields();
+        let timestamp_col = half_life_millis.and_then(|_| fast_fields.date("timestamp").ok());
+        // Boost per term ordinal of this segment's kind column
+        let kind_boosts = fast_fields
+            .str("message_kind")
+            .ok()
+            .flatten()
+            .map(|kind_col| {
+                let mut boosts = vec![1.0; kind_col.num_terms()];
+                let mut kind = String::new();
+                for (ord, boost) in boosts.iter_mut().enumerate() {
+                    if kind_col.ord_to_str(ord as u64, &mut kind).is_ok()
+                        && let Some(&value) = type_boosts.get(&kind)
+                    {
+                        *boost = value;
+                    }
+                }
+                (kind_col, boosts)
+            });
+
+        Box::new(move |doc, mut score| {
+            if let Some((kind_col, boosts)) = &kind_boosts
+                && let Some(ord) = kind_col.term_ords(doc).next()
+            {
+                score *= boosts[ord as usize];
+            }
+            if let (Some(timestamp_col), Some(half_life_millis)) =
+                (&timestamp_col, half_life_millis)
+                && let Some(timestamp) = timestamp_col.first(doc)
+            {
+                let age_millis = (now_millis - timestamp.into_timestamp_millis()).max(0) as f64;
+                score *= 0.5f64.powf(age_millis / half_life_millis) as f32;
+            }
+            score
+        })
+    }
+}
+
+/// Sort key ranking documents by timestamp in `order`, with the tweaked
+/// relevance score breaking ties. Documents without a timestamp come last.
+fn date_sort_key(
+    order: SortOrder,
+    tweak: impl Fn(&SegmentReader) -> Box<dyn Fn(DocId, f32) -> f32> + Send + Sync,
+) -> impl Fn(&SegmentReader) -> Box<dyn Fn(DocId, f32) -> (i64, f32)> + Send + Sync {
+    move |segment_reader: &SegmentReader| {
+        let timestamp_col = segment_reader.fast_fields().date("timestamp").ok();
+        let score = tweak(segment_reader);
+        let descending = order != SortOrder::DateAsc;
+        Box::new(move |doc, relevance| {
+            let millis = timestamp_col
+                .as_ref()
+                .and_then(|col| col.first(doc))
+                .map(|timestamp| timestamp.into_timestamp_millis());
+            let key = match millis {
+                Some(millis) if descending => millis,
+                Some(millis) => -millis,
+                None => i64::MIN,
+            };
+            (key, score(doc, relevance))
+        })
+    }
+}
+
+/// Range over the timestamp field for `after`/`before`, both inclusive.
+/// Indexed dates are truncated to the second, so each bound is floored to
+/// the second too and the exact check is left to the post-filter.
+fn date_range_query(
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Option<Box<dyn tantivy::query::Query>> {
+    if after.is_none() && before.is_none() {
+        return None;
+    }
+    let bound = |date: Option<DateTime<Utc>>| match date {
+        Some(date) => Bound::Included(tantivy::DateTime::from_timestamp_secs(date.timestamp())),
+        None => Bound::Unbounded,
+    };
+    Some(Box::new(RangeQuery::new_date_bounds(
+        "timestamp".to_string(),
+        bound(after),
+        bound(before),
+    )))
+}
+
+/// Whether a project name matches any of several `project_matches` filters
+fn matches_any_project(project: &str, filters: &[String]) -> bool {
+    filters
+        .iter()
+        .any(|filter| project_matches(project, filter))
+}
+
+/// Whether an indexed absolute path is `query`: the same path, or a
+/// relative query (`src/main.rs`) matching whole trailing components
+fn file_path_matches(path: &str, query: &str) -> bool {
+    let query = query.trim_start_matches("./");
+    path == query
+        || (!query.starts_with('/')
+            && path
+                .strip_suffix(query)
+                .is_some_and(|prefix| prefix.ends_with('/')))
 }
 
 /// Maximum messages to retrieve per session.
@@ -55,14 +295,74 @@ fn project_matches(project_path: &str, filter: &str) -> bool {
 /// runaway queries while covering all realistic session sizes.
 const MAX_SESSION_MESSAGES: usize = 5000;
 
+/// Hits from which search output starts with a facet summary
+pub const FACET_MIN_HITS: usize = 100;
+
+/// Months and projects listed in a facet summary
+const FACET_ENTRIES: usize = 5;
+
+/// Length of `SearchResult::snippet`, in characters
+const SNIPPET_CHARS: usize = 150;
+
+/// tool_name words of the tools whose calls carry shell commands
+const SHELL_TOOL_TERMS: &[&str] = &["bash", "shell", "exec"];
+
+/// Full ids listed when a short id is ambiguous
+const AMBIGUOUS_ID_CANDIDATES: usize = 5;
+
+/// Rewrites offered at most for a query that found nothing
+const MAX_QUERY_SUGGESTIONS: usize = 3;
+
+/// Indexed terms tried in place of each word the index doesn't have
+const SIMILAR_TERMS: usize = 2;
+
+/// A rewrite of a query that found nothing, with what it finds instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuerySuggestion {
+    pub query: String,
+    pub hits: usize,
+}
+
+/// A short session or message id that matches more than one indexed id
+#[derive(Debug, Clone)]
+pub struct AmbiguousId {
+    /// "session" or "message"
+    pub kind: &'static str,
+    pub prefix: String,
+    /// The first matching ids, sorted
+    pub candidates: Vec<String>,
+    /// More ids match than are listed
+    pub more: bool,
+}
+
+impl std::fmt::Display for AmbiguousId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Ambiguous {} id '{}', matches {}{}; use more characters",
+            self.kind,
+            self.prefix,
+            self.candidates.join(", "),
+            if self.more { ", ..." } else { "" }
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousId {}
+
 pub struct SearchEngine {
     index: Index,
     reader: IndexReader,
     uuid_field: Field,
     parent_uuid_field: Field,
     content_field: Field,
+    stored_content_field: Field,
+    content_preview_field: Field,
+    source_file_field: Field,
+    source_offset_field: Field,
     project_field: Field,
     session_field: Field,
+    session_key_field: Field,
     timestamp_field: Field,
     message_type_field: Field,
     technologies_field: Field,
@@ -70,16 +370,45 @@ pub struct SearchEngine {
     tools_mentioned_field: Field,
     has_code_field: Field,
     has_error_field: Field,
+    has_attachments_field: Field,
     cwd_field: Field,
+    cwd_tree_field: Field,
     sequence_num_field: Field,
     is_sidechain_field: Field,
     agent_id_field: Field,
+    source_host_field: Field,
+    source_root_field: Field,
+    source_field: Field,
+    git_branch_field: Field,
+    tool_name_field: Field,
+    tool_error_field: Field,
+    shell_commands_field: Field,
+    files_touched_field: Field,
+    files_modified_field: Field,
+    token_count_field: Field,
+    simhash_field: Field,
+    /// Analyzer of the content field when it is not the default one, to
+    /// highlight words that matched through their stem
+    content_analyzer: Option<TextAnalyzer>,
     interaction_counts: HashMap<String, usize>,
+    session_store: SessionStore,
+    session_titles: HashMap<String, String>,
+}
+
+/// User messages tried per session before giving up on a title
+const TITLE_CANDIDATES: usize = 10;
+
+fn load_titles(store: &SessionStore) -> HashMap<String, String> {
+    store.titles().unwrap_or_else(|e| {
+        debug!("Session titles unavailable: {}", e);
+        HashMap::new()
+    })
 }
 
 impl SearchEngine {
     pub fn new(index_path: &Path, session_counts: HashMap<String, usize>) -> Result<Self> {
         let index = Index::open_in_dir(index_path)?;
+        register_tokenizers(&index)?;
         let reader = index
             .reader_builder()
             .reload_policy(ReloadPolicy::OnCommitWithDelay)
@@ -89,8 +418,13 @@ impl SearchEngine {
         let uuid_field = schema.get_field("uuid")?;
         let parent_uuid_field = schema.get_field("parent_uuid")?;
         let content_field = schema.get_field("content")?;
+        let stored_content_field = schema.get_field("stored_content")?;
+        let content_preview_field = schema.get_field("content_preview")?;
+        let source_file_field = schema.get_field("source_file")?;
+        let source_offset_field = schema.get_field("source_offset")?;
         let project_field = schema.get_field("project")?;
         let session_field = schema.get_field("session_id")?;
+        let session_key_field = schema.get_field("session_key")?;
         let timestamp_field = schema.get_field("timestamp")?;
         let message_type_field = schema.get_field("message_type")?;
         let technologies_field = schema.get_field("technologies")?;
@@ -98,10 +432,28 @@ impl SearchEngine {
         let tools_mentioned_field = schema.get_field("tools_mentioned")?;
         let has_code_field = schema.get_field("has_code")?;
         let has_error_field = schema.get_field("has_error")?;
+        let has_attachments_field = schema.get_field("has_attachments")?;
         let cwd_field = schema.get_field("cwd")?;
+        let cwd_tree_field = schema.get_field("cwd_tree")?;
         let sequence_num_field = schema.get_field("sequence_num")?;
         let is_sidechain_field = schema.get_field("is_sidechain")?;
         let agent_id_field = schema.get_field("agent_id")?;
+        let source_host_field = schema.get_field("source_host")?;
+        let source_root_field = schema.get_field("source_root")?;
+        let source_field = schema.get_field("source")?;
+        let git_branch_field = schema.get_field("git_branch")?;
+        let tool_name_field = schema.get_field("tool_name")?;
+        let tool_error_field = schema.get_field("tool_error")?;
+        let shell_commands_field = schema.get_field("shell_commands")?;
+        let token_count_field = schema.get_field("token_count")?;
+        let simhash_field = schema.get_field("simhash")?;
+        let files_touched_field = schema.get_field("files_touched")?;
+        let files_modified_field = schema.get_field("files_modified")?;
+        let content_analyzer = tokenizer_of(&schema, "content")
+            .filter(|name| name != "default")
+            .and_then(|name| index.tokenizers().get(&name));
+        let session_store = SessionStore::new(index_path);
+        let session_titles = load_titles(&session_store);
 
         Ok(Self {
             index,
@@ -109,8 +461,13 @@ impl SearchEngine {
             uuid_field,
             parent_uuid_field,
             content_field,
+            stored_content_field,
+            content_preview_field,
+            source_file_field,
+            source_offset_field,
             project_field,
             session_field,
+            session_key_field,
             timestamp_field,
             message_type_field,
             technologies_field,
@@ -118,93 +475,358 @@ impl SearchEngine {
             tools_mentioned_field,
             has_code_field,
             has_error_field,
+            has_attachments_field,
             cwd_field,
+            cwd_tree_field,
             sequence_num_field,
             is_sidechain_field,
             agent_id_field,
+            source_host_field,
+            source_root_field,
+            source_field,
+            git_branch_field,
+            tool_name_field,
+            tool_error_field,
+            shell_commands_field,
+            files_touched_field,
+            files_modified_field,
+            token_count_field,
+            simhash_field,
+            content_analyzer,
             interaction_counts: session_counts,
+            session_store,
+            session_titles,
         })
     }
 
-    pub fn search(&self, query: SearchQuery) -> Result<Vec<SearchResult>> {
+    /// Pick up new commits and session counts without reopening the index
+    pub fn reload(&mut self, session_counts: HashMap<String, usize>) -> Result<()> {
+        self.reader.reload()?;
+        self.interaction_counts = session_counts;
+        self.session_titles = load_titles(&self.session_store);
+        Ok(())
+    }
+
+    /// Number of live documents in the index
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Every indexed project name, from the fast field dictionaries
+    fn project_names(&self) -> Result<Vec<String>> {
+        let mut paths = BTreeSet::new();
+        for segment_reader in self.reader.searcher().segment_readers() {
+            let Some(project_col) = segment_reader.fast_fields().str("project")? else {
+                continue;
+            };
+            let mut stream = project_col.dictionary().stream()?;
+            while stream.advance() {
+                paths.insert(String::from_utf8_lossy(stream.key()).into_owned());
+            }
+        }
+        Ok(paths.into_iter().collect())
+    }
+
+    /// Query for the documents of every project one of `filters` matches,
+    /// resolved against the indexed names since terms can't express
+    /// substrings or globs
+    fn project_query(&self, filters: &[String]) -> Result<Box<dyn tantivy::query::Query>> {
+        let matches: Vec<_> = self
+            .project_names()?
+            .into_iter()
+            .filter(|name| matches_any_project(name, filters))
+            .map(|name| {
+                (
+                    Occur::Should,
+                    build_project_query(self.project_field, &name),
+                )
+            })
+            .collect();
+        Ok(if matches.is_empty() {
+            Box::new(EmptyQuery)
+        } else {
+            Box::new(BooleanQuery::new(matches))
+        })
+    }
+
+    /// Messages, sessions and last activity per project, most recent first
+    pub fn list_projects(&self, filter: Option<&str>) -> Result<Vec<ProjectInfo>> {
         let searcher = self.reader.searcher();
+        let mut projects: HashMap<String, (ProjectInfo, HashSet<String>)> = HashMap::new();
+        let mut project = String::new();
+        let mut session_id = String::new();
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(project_col), Some(session_col)) =
+                (fast_fields.str("project")?, fast_fields.str("session_id")?)
+            else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in 0..segment_reader.max_doc() {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                let Some(ord) = project_col.term_ords(doc).next() else {
+                    continue;
+                };
+                project_col.ord_to_str(ord, &mut project)?;
+                if filter.is_some_and(|filter| !project_matches(&project, filter)) {
+                    continue;
+                }
+
+                let (info, sessions) = projects.entry(project.clone()).or_insert_with(|| {
+                    (
+                        ProjectInfo {
+                            project: project.clone(),
+                            message_count: 0,
+                            session_count: 0,
+                            last_timestamp: None,
+                        },
+                        HashSet::new(),
+                    )
+                });
+                info.message_count += 1;
+                if let Some(timestamp) = timestamp_col
+                    .first(doc)
+                    .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+                {
+                    info.last_timestamp = info.last_timestamp.max(Some(timestamp));
+                }
+                if let Some(ord) = session_col.term_ords(doc).next() {
+                    session_col.ord_to_str(ord, &mut session_id)?;
+                    if !sessions.contains(&session_id) {
+                        sessions.insert(session_id.clone());
+                    }
+                }
+            }
+        }
+
+        let mut projects: Vec<_> = projects
+            .into_values()
+            .map(|(mut info, sessions)| {
+                info.session_count = sessions.len();
+                info
+            })
+            .collect();
+        projects.sort_by(|a, b| {
+            b.last_timestamp
+                .cmp(&a.last_timestamp)
+                .then_with(|| a.project.cmp(&b.project))
+        });
+        Ok(projects)
+    }
 
+    /// Tantivy query for the text and the filters that can be expressed as terms
+    fn build_query(&self, query: &SearchQuery) -> Result<Box<dyn tantivy::query::Query>> {
         let query_parser = QueryParser::for_index(
             &self.index,
             vec![self.content_field, self.session_field, self.project_field],
         );
-        let text_query = query_parser.parse_query(&query.text)?;
+        let projects = self.project_names()?;
+        let text_query =
+            query_parser.parse_query(&rewrite_field_query_with(&query.text, &projects)?)?;
 
         let mut final_query_parts = vec![(
             Occur::Must,
             Box::new(text_query) as Box<dyn tantivy::query::Query>,
         )];
 
-        if let Some(ref project_filter) = query.project_filter {
-            let project_query = build_project_query(self.project_field, project_filter);
-            final_query_parts.push((Occur::Must, project_query));
+        if !query.project_filters.is_empty() {
+            final_query_parts.push((Occur::Must, self.project_query(&query.project_filters)?));
         }
 
         if let Some(ref session_filter) = query.session_filter {
-            // Split on hyphens like get_session_messages - TEXT fields tokenize at hyphens
-            let segments: Vec<_> = session_filter.split('-').collect();
-            let segment_queries: Vec<_> = segments
+            // A short id matches through the full one; an unknown one matches nothing
+            let session_filter = self
+                .resolve_session_id(session_filter)?
+                .unwrap_or_else(|| session_filter.clone());
+            final_query_parts.push((Occur::Must, Box::new(self.session_query(&session_filter))));
+        }
+
+        // Local history has no host term, so "local" is only post-filtered
+        if let Some(ref host_filter) = query.host_filter
+            && host_filter != LOCAL_HOST
+        {
+            let term = Term::from_field_text(self.source_host_field, host_filter);
+            final_query_parts.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+
+        if let Some(ref branch_filter) = query.branch_filter {
+            let term = Term::from_field_text(self.git_branch_field, branch_filter);
+            final_query_parts.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+
+        if let Some(ref source_filter) = query.source_filter {
+            let term = Term::from_field_text(self.source_field, source_filter);
+            final_query_parts.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+
+        if let Some(ref uuids) = query.uuid_filter {
+            let terms = uuids
                 .iter()
-                .map(|seg| {
-                    let term = Term::from_field_text(self.session_field, seg);
-                    (
-                        Occur::Must,
-                        Box::new(TermQuery::new(term, IndexRecordOption::Basic))
-                            as Box<dyn tantivy::query::Query>,
-                    )
-                })
-                .collect();
-            let session_query = BooleanQuery::new(segment_queries);
-            final_query_parts.push((Occur::Must, Box::new(session_query)));
+                .map(|uuid| Term::from_field_text(self.uuid_field, uuid));
+            final_query_parts.push((Occur::Must, Box::new(TermSetQuery::new(terms))));
+        }
+
+        if query.session_active_after.is_some() || query.session_active_before.is_some() {
+            let terms = self
+                .sessions_active_between(query.session_active_after, query.session_active_before)?
+                .into_iter()
+                .map(|id| Term::from_field_text(self.session_key_field, &id));
+            final_query_parts.push((Occur::Must, Box::new(TermSetQuery::new(terms))));
+        }
+
+        if let Some(ref dir_filter) = query.dir_filter {
+            final_query_parts.push((Occur::Must, self.dir_query(dir_filter)));
+        }
+
+        if let Some(range) = date_range_query(query.after, query.before) {
+            final_query_parts.push((Occur::Must, range));
         }
 
-        let final_query = if final_query_parts.len() > 1 {
-            Box::new(BooleanQuery::new(final_query_parts)) as Box<dyn tantivy::query::Query>
+        Ok(if final_query_parts.len() > 1 {
+            Box::new(BooleanQuery::new(final_query_parts))
         } else {
             final_query_parts.into_iter().next().unwrap().1
-        };
+        })
+    }
+
+    /// Messages written in `dir` or any directory below it
+    fn dir_query(&self, dir: &str) -> Box<dyn tantivy::query::Query> {
+        let facet = cwd_facet(&expand_tilde(dir));
+        Box::new(TermQuery::new(
+            Term::from_facet(self.cwd_tree_field, &facet),
+            IndexRecordOption::Basic,
+        ))
+    }
+
+    /// Filters Tantivy can only approximate, checked on the result
+    fn passes_post_filters(query: &SearchQuery, result: &SearchResult) -> bool {
+        // Session prefix (Tantivy matches segments, but we need prefix precision)
+        if let Some(ref session_filter) = query.session_filter
+            && !result.session_id.starts_with(session_filter.as_str())
+        {
+            return false;
+        }
 
-        let top_docs = searcher.search(&*final_query, &TopDocs::with_limit(query.limit))?;
+        // Full project name (Tantivy matches segments)
+        if !query.project_filters.is_empty()
+            && !matches_any_project(&result.project, &query.project_filters)
+        {
+            return false;
+        }
+
+        if let Some(ref host_filter) = query.host_filter
+            && !result.is_from_host(host_filter)
+        {
+            return false;
+        }
+
+        // The index range is whole seconds wide at each end
+        query.after.is_none_or(|after| result.timestamp >= after)
+            && query.before.is_none_or(|before| result.timestamp <= before)
+    }
+
+    pub fn search(&self, query: SearchQuery) -> Result<Vec<SearchResult>> {
+        let searcher = self.reader.searcher();
+        let final_query = self.build_query(&query)?;
+        let top_docs = if query.sort_by != SortOrder::Relevance {
+            let key = date_sort_key(
+                query.sort_by,
+                score_tweak(query.recency_half_life_days, query.type_boosts.clone()),
+            );
+            searcher
+                .search(
+                    &*final_query,
+                    &TopDocs::with_limit(query.limit).tweak_score(key),
+                )?
+                .into_iter()
+                .map(|((_, score), doc_address)| (score, doc_address))
+                .collect()
+        } else if query.recency_half_life_days.is_some() || !query.type_boosts.is_empty() {
+            searcher.search(
+                &*final_query,
+                &TopDocs::with_limit(query.limit).tweak_score(score_tweak(
+                    query.recency_half_life_days,
+                    query.type_boosts.clone(),
+                )),
+            )?
+        } else {
+            searcher.search(&*final_query, &TopDocs::with_limit(query.limit))?
+        };
 
         let mut results = Vec::new();
         for (score, doc_address) in top_docs {
             let result = self.doc_to_result(&searcher.doc(doc_address)?, score, &query.text)?;
-
-            // Apply session prefix filter (Tantivy matches segments, but we need prefix precision)
-            if let Some(ref session_filter) = query.session_filter
-                && !result.session_id.starts_with(session_filter.as_str())
-            {
-                continue;
+            if Self::passes_post_filters(&query, &result) {
+                results.push(result);
             }
+        }
 
-            // Apply project post-filter (Tantivy matches segments, verify full project name)
-            if let Some(ref project_filter) = query.project_filter
-                && !project_matches(&result.project_path, project_filter)
-            {
-                continue;
-            }
+        Ok(results)
+    }
 
-            // Apply date range filters
-            if let Some(after) = query.after
-                && result.timestamp < after
-            {
-                continue;
-            }
-            if let Some(before) = query.before
-                && result.timestamp > before
-            {
-                continue;
+    /// Like `search_with_context`, but hands each match to `on_match` as soon
+    /// as it is scored, in index order rather than by score or date, and
+    /// without a limit. Stops early when `on_match` returns false.
+    pub fn search_each_with_context(
+        &self,
+        query: SearchQuery,
+        context_before: usize,
+        context_after: usize,
+        mut on_match: impl FnMut(SearchResultWithContext) -> Result<bool>,
+    ) -> Result<()> {
+        let searcher = self.reader.searcher();
+        let final_query = self.build_query(&query)?;
+        let weight = final_query.weight(EnableScoring::enabled_from_searcher(&searcher))?;
+
+        // Matches of one session are mostly adjacent in the index
+        let mut session: Option<(String, Vec<SearchResult>)> = None;
+        for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+            let alive = segment_reader.alive_bitset();
+            let mut scorer = weight.scorer(segment_reader, 1.0)?;
+            let mut doc = scorer.doc();
+            while doc != TERMINATED {
+                if alive.is_none_or(|bitset| bitset.is_alive(doc)) {
+                    let address = DocAddress::new(segment_ord as u32, doc);
+                    let result =
+                        self.doc_to_result(&searcher.doc(address)?, scorer.score(), &query.text)?;
+                    if Self::passes_post_filters(&query, &result) {
+                        if session
+                            .as_ref()
+                            .is_none_or(|(id, _)| *id != result.session_id)
+                        {
+                            let messages = self.messages_in_session(&result.session_id)?;
+                            session = Some((result.session_id.clone(), messages));
+                        }
+                        let messages = session.as_ref().map_or(&[][..], |(_, m)| m);
+                        if !on_match(with_context(
+                            result,
+                            messages,
+                            context_before,
+                            context_after,
+                        ))? {
+                            return Ok(());
+                        }
+                    }
+                }
+                doc = scorer.advance();
             }
-
-            results.push(result);
         }
-
-        Ok(results)
+        Ok(())
     }
 
     /// Search with context - returns matches with surrounding messages (grep -C style)
@@ -214,143 +836,41 @@ impl SearchEngine {
         context_before: usize,
         context_after: usize,
     ) -> Result<Vec<SearchResultWithContext>> {
-        // Save sort order before consuming query
-        let sort_by = query.sort_by.clone();
-
-        // First, get the matching messages
+        // Matches come ranked by the query's sort order
         let matches = self.search(query)?;
 
         let mut results_with_context = Vec::new();
-
         for match_result in matches {
-            let session_messages = self.get_session_messages(&match_result.session_id)?;
-
-            // If we can't get session messages, still return the match with just itself as context
-            if session_messages.is_empty() {
-                results_with_context.push(SearchResultWithContext {
-                    matched_message: match_result.clone(),
-                    context_messages: vec![match_result],
-                    match_index: 0,
-                    total_session_messages: 1,
-                });
-                continue;
-            }
-
-            // Sort by sequence number
-            let mut session_messages = session_messages;
-            session_messages.sort_by_key(|m| m.sequence_num);
-
-            // Count only displayable messages (consistent with get_session_messages)
-            let total_session_messages = session_messages
-                .iter()
-                .filter(|m| m.is_displayable())
-                .count();
-
-            // Find the matching message index by UUID or by content/timestamp as fallback
-            let match_idx = session_messages
-                .iter()
-                .position(|m| m.uuid == match_result.uuid)
-                .or_else(|| {
-                    // Fallback: find by sequence number
-                    session_messages
-                        .iter()
-                        .position(|m| m.sequence_num == match_result.sequence_num)
-                });
-
-            if let Some(idx) = match_idx {
-                // Get context window around the match
-                let start = idx.saturating_sub(context_before);
-                let end = (idx + context_after + 1).min(session_messages.len());
-
-                // Filter to displayable messages only, track new match index
-                let mut context_messages = Vec::new();
-                let mut new_match_idx = 0;
-                for (i, msg) in session_messages[start..end].iter().enumerate() {
-                    if msg.is_displayable() {
-                        if start + i == idx {
-                            new_match_idx = context_messages.len();
-                        }
-                        context_messages.push(msg.clone());
-                    }
-                }
-
-                // If no context found (e.g., all filtered out), use match as its own context
-                if context_messages.is_empty() {
-                    context_messages.push(match_result.clone());
-                    new_match_idx = 0;
-                }
-
-                results_with_context.push(SearchResultWithContext {
-                    matched_message: match_result,
-                    context_messages,
-                    match_index: new_match_idx,
-                    total_session_messages,
-                });
-            } else {
-                // UUID/sequence not found in session, return match with itself as context
-                results_with_context.push(SearchResultWithContext {
-                    matched_message: match_result.clone(),
-                    context_messages: vec![match_result],
-                    match_index: 0,
-                    total_session_messages,
-                });
-            }
-        }
-
-        // Apply sorting based on sort_by
-        match sort_by {
-            SortOrder::DateDesc => {
-                results_with_context.sort_by(|a, b| {
-                    b.matched_message
-                        .timestamp
-                        .cmp(&a.matched_message.timestamp)
-                });
-            }
-            SortOrder::DateAsc => {
-                results_with_context.sort_by(|a, b| {
-                    a.matched_message
-                        .timestamp
-                        .cmp(&b.matched_message.timestamp)
-                });
-            }
-            SortOrder::Relevance => {
-                // Already sorted by BM25 score from Tantivy
-            }
+            let session_messages = self.messages_in_session(&match_result.session_id)?;
+            results_with_context.push(with_context(
+                match_result,
+                &session_messages,
+                context_before,
+                context_after,
+            ));
         }
 
         Ok(results_with_context)
     }
 
-    /// Get all messages for a session
+    /// Get all messages for a session, given its id or a unique prefix of it
     pub fn get_session_messages(&self, session_id: &str) -> Result<Vec<SearchResult>> {
-        let searcher = self.reader.searcher();
+        match self.resolve_session_id(session_id)? {
+            Some(session_id) => self.messages_in_session(&session_id),
+            None => Ok(Vec::new()),
+        }
+    }
 
-        // Use TermQuery on each UUID segment for exact matching
-        // Session IDs are UUIDs like "9e1e6a58-cd5a-4651-a9fd-c24c04cb8809"
-        // TEXT field tokenizes at hyphens, so we match all segments with AND
-        let segments: Vec<_> = session_id.split('-').collect();
-        let segment_queries: Vec<_> = segments
-            .iter()
-            .map(|seg| {
-                let term = Term::from_field_text(self.session_field, seg);
-                (
-                    Occur::Must,
-                    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
-                        as Box<dyn tantivy::query::Query>,
-                )
-            })
-            .collect();
-        let query = BooleanQuery::new(segment_queries);
+    /// All messages of the session with exactly this id
+    pub(crate) fn messages_in_session(&self, session_id: &str) -> Result<Vec<SearchResult>> {
+        let searcher = self.reader.searcher();
 
+        let query = self.session_query(session_id);
         let top_docs = searcher.search(&query, &TopDocs::with_limit(MAX_SESSION_MESSAGES))?;
 
         let mut results = Vec::new();
         for (score, doc_address) in top_docs {
-            let result = self.doc_to_result(&searcher.doc(doc_address)?, score, "")?;
-            // Filter to session_id match - support prefix matching for short IDs
-            if result.session_id == session_id || result.session_id.starts_with(session_id) {
-                results.push(result);
-            }
+            results.push(self.doc_to_result(&searcher.doc(doc_address)?, score, "")?);
         }
 
         // Sort by sequence number
@@ -359,33 +879,26 @@ impl SearchEngine {
         Ok(results)
     }
 
-    /// Get specific messages by their UUIDs
+    /// Get specific messages by their UUIDs or unique prefixes of them;
+    /// ids matching nothing are skipped
     pub fn get_messages_by_uuid(&self, uuids: &[String]) -> Result<Vec<SearchResult>> {
         let searcher = self.reader.searcher();
         let mut results = Vec::new();
 
         for uuid in uuids {
-            // UUID is stored as TEXT, tokenized at hyphens
-            let segments: Vec<_> = uuid.split('-').collect();
-            let segment_queries: Vec<_> = segments
-                .iter()
-                .map(|seg| {
-                    let term = Term::from_field_text(self.uuid_field, seg);
-                    (
-                        Occur::Must,
-                        Box::new(TermQuery::new(term, IndexRecordOption::Basic))
-                            as Box<dyn tantivy::query::Query>,
-                    )
-                })
-                .collect();
-            let query = BooleanQuery::new(segment_queries);
+            let Some(uuid) = self.resolve_message_id(uuid)? else {
+                continue;
+            };
+            let query = TermQuery::new(
+                Term::from_field_text(self.uuid_field, &uuid),
+                IndexRecordOption::Basic,
+            );
 
             let top_docs = searcher.search(&query, &TopDocs::with_limit(10))?;
 
             for (score, doc_address) in top_docs {
                 let result = self.doc_to_result(&searcher.doc(doc_address)?, score, "")?;
-                // Exact match or prefix match
-                if result.uuid == *uuid || result.uuid.starts_with(uuid) {
+                if result.uuid == uuid {
                     results.push(result);
                     break;
                 }
@@ -395,6 +908,106 @@ impl SearchEngine {
         Ok(results)
     }
 
+    /// Documents of the session with exactly this id
+    fn session_query(&self, session_id: &str) -> TermQuery {
+        TermQuery::new(
+            Term::from_field_text(self.session_key_field, session_id),
+            IndexRecordOption::Basic,
+        )
+    }
+
+    /// Full session id for an id or a unique prefix of it, such as the
+    /// 8-character ids shown in output. Errors with `AmbiguousId` when
+    /// several sessions match.
+    pub fn resolve_session_id(&self, prefix: &str) -> Result<Option<String>> {
+        self.resolve_id("session", "session_id", prefix)
+    }
+
+    /// Full message uuid for a uuid or a unique prefix of it
+    pub fn resolve_message_id(&self, prefix: &str) -> Result<Option<String>> {
+        self.resolve_id("message", "uuid", prefix)
+    }
+
+    fn resolve_id(&self, kind: &'static str, field: &str, prefix: &str) -> Result<Option<String>> {
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Ok(None);
+        }
+        let mut candidates = self.ids_with_prefix(field, prefix)?;
+        if candidates.iter().any(|id| id == prefix) {
+            return Ok(Some(prefix.to_string()));
+        }
+        match candidates.len() {
+            0 => Ok(None),
+            1 => Ok(candidates.pop()),
+            _ => {
+                let more = candidates.len() > AMBIGUOUS_ID_CANDIDATES;
+                candidates.truncate(AMBIGUOUS_ID_CANDIDATES);
+                Err(AmbiguousId {
+                    kind,
+                    prefix: prefix.to_string(),
+                    candidates,
+                    more,
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Ids in a string fast field starting with `prefix`, sorted, at most one
+    /// more than are listed in an ambiguity error. The term dictionaries keep
+    /// ids of deleted documents until segments merge, so when several match,
+    /// ids without a live document are dropped.
+    fn ids_with_prefix(&self, field: &str, prefix: &str) -> Result<Vec<String>> {
+        let searcher = self.reader.searcher();
+        let mut ids: Vec<String> = Vec::new();
+        let mut by_segment = Vec::new();
+        for segment_reader in searcher.segment_readers() {
+            let Some(column) = segment_reader.fast_fields().str(field)? else {
+                continue;
+            };
+            let mut matching = Vec::new();
+            let mut stream = column
+                .dictionary()
+                .range()
+                .ge(prefix.as_bytes())
+                .into_stream()?;
+            while stream.advance() && stream.key().starts_with(prefix.as_bytes()) {
+                matching.push((
+                    stream.term_ord(),
+                    String::from_utf8_lossy(stream.key()).into_owned(),
+                ));
+            }
+            ids.extend(matching.iter().map(|(_, id)| id.clone()));
+            by_segment.push((segment_reader, column, matching));
+        }
+        ids.sort();
+        ids.dedup();
+        if ids.len() <= 1 {
+            return Ok(ids);
+        }
+
+        let mut live = Vec::new();
+        for (segment_reader, column, mut matching) in by_segment {
+            if let Some(alive) = segment_reader.alive_bitset() {
+                let ords: HashSet<u64> = matching.iter().map(|(ord, _)| *ord).collect();
+                let mut live_ords = HashSet::new();
+                for doc in alive.iter_alive() {
+                    live_ords.extend(column.term_ords(doc).filter(|ord| ords.contains(ord)));
+                    if live_ords.len() == ords.len() {
+                        break;
+                    }
+                }
+                matching.retain(|(ord, _)| live_ords.contains(ord));
+            }
+            live.extend(matching.into_iter().map(|(_, id)| id));
+        }
+        live.sort();
+        live.dedup();
+        live.truncate(AMBIGUOUS_ID_CANDIDATES + 1);
+        Ok(live)
+    }
+
     fn doc_to_result(
         &self,
         doc: &TantivyDocument,
@@ -413,11 +1026,7 @@ impl SearchEngine {
             .filter(|s| !s.is_empty())
             .map(|s| s.to_string());
 
-        let content = doc
-            .get_first(self.content_field)
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+        let content = self.doc_content(doc, &uuid);
 
         let project = doc
             .get_first(self.project_field)
@@ -425,9 +1034,11 @@ impl SearchEngine {
             .unwrap_or("")
             .to_string();
 
+        // Entries without a cwd (transcript exports) are indexed as "unknown"
         let project_path = doc
             .get_first(self.cwd_field)
             .and_then(|v| v.as_str())
+            .filter(|cwd| *cwd != "unknown")
             .unwrap_or(&project)
             .to_string();
 
@@ -451,11 +1062,20 @@ impl SearchEngine {
             .unwrap_or("Unknown")
             .to_string();
 
-        let snippet = if query_text.is_empty() {
-            truncate_content(&content, 150, false)
-        } else {
-            self.generate_snippet(&content, query_text)
+        let terms = query_terms(query_text);
+        let matches = match self.content_analyzer.clone() {
+            Some(mut analyzer) => match_ranges_by(&content, &terms, |word| {
+                let mut stream = analyzer.token_stream(word);
+                if stream.advance() {
+                    stream.token().text.clone()
+                } else {
+                    word.to_lowercase()
+                }
+            }),
+            None => match_ranges(&content, &terms),
         };
+        let snippet = match_snippets(&content, &matches, SNIPPET_CHARS, 1)
+            .unwrap_or_else(|| truncate_content(&content, SNIPPET_CHARS, false));
 
         let technologies = doc
             .get_first(self.technologies_field)
@@ -485,10 +1105,19 @@ impl SearchEngine {
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
+        let has_attachments = doc
+            .get_first(self.has_attachments_field)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
         let sequence_num = doc
             .get_first(self.sequence_num_field)
             .and_then(|v| v.as_u64())
             .unwrap_or(0) as usize;
+        let simhash = doc
+            .get_first(self.simhash_field)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
 
         let is_sidechain = doc
             .get_first(self.is_sidechain_field)
@@ -501,7 +1130,37 @@ impl SearchEngine {
             .filter(|s| !s.is_empty())
             .map(|s| s.to_string());
 
-        let interaction_count = self.get_interaction_count(&session_id);
+        let source_host = doc
+            .get_first(self.source_host_field)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+
+        let source_root = doc
+            .get_first(self.source_root_field)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+
+        let source = doc
+            .get_first(self.source_field)
+            .and_then(|v| v.as_str())
+            .unwrap_or(CLAUDE_CODE_SOURCE)
+            .to_string();
+
+        let git_branch = doc
+            .get_first(self.git_branch_field)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+
+        let token_count = doc
+            .get_first(self.token_count_field)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize;
+
+        let interaction_count = self.get_interaction_count(&session_id);
+        let session_title = self.session_titles.get(&session_id).cloned();
 
         Ok(SearchResult {
             uuid,
@@ -518,662 +1177,4126 @@ impl SearchEngine {
             tools_mentioned,
             has_code,
             has_error,
+            has_attachments,
             interaction_count,
             sequence_num,
             is_sidechain,
             agent_id,
             message_type,
+            source,
+            source_host,
+            source_root,
+            git_branch,
+            token_count,
+            simhash,
+            session_title,
+            matches,
+            notes: Vec::new(),
         })
     }
 
-    fn generate_snippet(&self, content: &str, query: &str) -> String {
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let query_words: Vec<&str> = query.split_whitespace().collect();
+    /// Message text: the stored copy, or under `index.lazy_content` the
+    /// JSONL line the document was indexed from, falling back to the stored
+    /// preview once that line is gone
+    fn doc_content(&self, doc: &TantivyDocument, uuid: &str) -> String {
+        let stored = doc
+            .get_first(self.stored_content_field)
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        if !stored.is_empty() {
+            return stored.to_string();
+        }
+        let preview = || {
+            doc.get_first(self.content_preview_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        let Some(file) = doc
+            .get_first(self.source_file_field)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+        else {
+            return preview();
+        };
+        let offset = doc
+            .get_first(self.source_offset_field)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        JsonlParser
+            .read_content(Path::new(file), offset, uuid)
+            .unwrap_or_else(|e| {
+                debug!("Content of {} unavailable from {}: {}", uuid, file, e);
+                None
+            })
+            .unwrap_or_else(preview)
+    }
+
+    fn get_interaction_count(&self, session_id: &str) -> usize {
+        self.interaction_counts
+            .get(session_id)
+            .copied()
+            .unwrap_or(0)
+    }
 
-        if words.len() <= 30 {
-            return content.to_string();
+    /// Aggregate per-session info from fast fields (exact, no stored-doc reads).
+    /// `last_accessed` is left empty for the caller to fill from the cache.
+    pub fn list_sessions(&self, project_filter: Option<&str>) -> Result<Vec<SessionInfo>> {
+        let searcher = self.reader.searcher();
+        let mut sessions = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let alive = segment_reader.alive_bitset();
+            let docs = (0..segment_reader.max_doc())
+                .filter(|&doc| alive.is_none_or(|bitset| bitset.is_alive(doc)));
+            aggregate_sessions(segment_reader, docs, project_filter, &mut sessions)?;
         }
+        Ok(sessions.into_values().collect())
+    }
 
-        let mut best_start = 0;
-        let mut best_score = 0;
+    /// Ids of the sessions with a message in the window, or whose first and
+    /// last messages are on either side of it
+    pub fn sessions_active_between(
+        &self,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<String>> {
+        let sessions = match self.session_store.is_current() {
+            true => self.session_store.sessions(None).or_else(|e| {
+                debug!("Session store unavailable, scanning the index: {}", e);
+                self.list_sessions(None)
+            })?,
+            false => self.list_sessions(None)?,
+        };
+        Ok(sessions
+            .into_iter()
+            .filter(|s| {
+                after.is_none_or(|after| s.last_timestamp >= after)
+                    && before.is_none_or(|before| s.first_timestamp <= before)
+            })
+            .map(|s| s.session_id)
+            .collect())
+    }
 
-        for (i, window) in words.windows(30).enumerate() {
-            let window_text = window.join(" ");
-            let mut score = 0;
+    /// `list_sessions` for just these sessions, reaching their documents
+    /// through session_id terms instead of visiting every document
+    pub fn sessions_by_id(&self, session_ids: &[String]) -> Result<Vec<SessionInfo>> {
+        let searcher = self.reader.searcher();
+        let mut sessions = HashMap::new();
+        for (segment_ord, docs) in self.session_docs(&searcher, Some(session_ids))? {
+            aggregate_sessions(
+                searcher.segment_reader(segment_ord),
+                docs.into_iter(),
+                None,
+                &mut sessions,
+            )?;
+        }
+        sessions.retain(|id, _| session_ids.contains(id));
+        Ok(sessions.into_values().collect())
+    }
 
-            for query_word in &query_words {
-                if window_text
-                    .to_lowercase()
-                    .contains(&query_word.to_lowercase())
+    /// Files these sessions were indexed from, for reindexing them
+    pub fn source_files(&self, session_ids: &[String]) -> Result<BTreeSet<PathBuf>> {
+        let searcher = self.reader.searcher();
+        let mut files = BTreeSet::new();
+        for (segment_ord, docs) in self.session_docs(&searcher, Some(session_ids))? {
+            for doc_id in docs {
+                let doc: TantivyDocument = searcher.doc(DocAddress::new(segment_ord, doc_id))?;
+                let session = doc
+                    .get_first(self.session_field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                if let Some(file) = doc
+                    .get_first(self.source_file_field)
+                    .and_then(|v| v.as_str())
+                    .filter(|file| !file.is_empty() && session_ids.iter().any(|s| s == session))
                 {
-                    score += 1;
+                    files.insert(PathBuf::from(file));
                 }
             }
+        }
+        Ok(files)
+    }
+
+    /// Sessions that continue another as `(continuation, continued)` pairs.
+    /// A session picked up in a new file after compaction starts with
+    /// summaries whose leaf message is in the session it continues.
+    pub fn session_continuations(&self) -> Result<Vec<(String, String)>> {
+        let searcher = self.reader.searcher();
+        let text = |doc: &TantivyDocument, field: Field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let timestamp = |doc: &TantivyDocument| {
+            doc.get_first(self.timestamp_field)
+                .and_then(|v| v.as_datetime())
+                .map(|dt| dt.into_timestamp_millis())
+                .unwrap_or_default()
+        };
 
-            if score > best_score {
-                best_score = score;
-                best_start = i;
+        let summaries = TermQuery::new(
+            Term::from_field_text(self.message_type_field, "summary"),
+            IndexRecordOption::Basic,
+        );
+        // Leaf uuid -> (session, timestamp) of the message, None when not indexed
+        let mut leaves: HashMap<String, Option<(String, i64)>> = HashMap::new();
+        let mut links = BTreeSet::new();
+        for address in searcher.search(&summaries, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let leaf = text(&doc, self.parent_uuid_field);
+            if leaf.is_empty() {
+                continue;
+            }
+            if !leaves.contains_key(&leaf) {
+                let query = TermQuery::new(
+                    Term::from_field_text(self.uuid_field, &leaf),
+                    IndexRecordOption::Basic,
+                );
+                let found = match searcher.search(&query, &TopDocs::with_limit(1))?.first() {
+                    Some((_, address)) => {
+                        let leaf_doc: TantivyDocument = searcher.doc(*address)?;
+                        Some((text(&leaf_doc, self.session_field), timestamp(&leaf_doc)))
+                    }
+                    None => None,
+                };
+                leaves.insert(leaf.clone(), found);
+            }
+            let session_id = text(&doc, self.session_field);
+            // A continuation starts once what it continues has ended
+            if let Some(Some((continued, leaf_time))) = leaves.get(&leaf)
+                && *continued != session_id
+                && *leaf_time <= timestamp(&doc)
+            {
+                links.insert((session_id, continued.clone()));
             }
         }
+        Ok(links.into_iter().collect())
+    }
 
-        let snippet_words = &words[best_start..std::cmp::min(best_start + 30, words.len())];
-        let mut snippet = snippet_words.join(" ");
-
-        if best_start > 0 {
-            snippet = format!("...{snippet}");
+    /// The sessions of the logical conversation `session_id` belongs to,
+    /// following continuations both ways, oldest first
+    pub fn conversation_chain(&self, session_id: &str) -> Result<Vec<String>> {
+        let links = self.session_continuations()?;
+        let mut chain = vec![session_id.to_string()];
+        let mut next = 0;
+        while next < chain.len() {
+            let current = chain[next].clone();
+            for (continuation, continued) in &links {
+                for (from, to) in [(continuation, continued), (continued, continuation)] {
+                    if *from == current && !chain.contains(to) {
+                        chain.push(to.clone());
+                    }
+                }
+            }
+            next += 1;
         }
-        if best_start + 30 < words.len() {
-            snippet = format!("{snippet}...");
+        if chain.len() > 1 {
+            let mut sessions = self.sessions_by_id(&chain)?;
+            sessions.sort_by_key(|s| s.first_timestamp);
+            chain = sessions.into_iter().map(|s| s.session_id).collect();
         }
-
-        snippet
+        Ok(chain)
     }
 
-    fn get_interaction_count(&self, session_id: &str) -> usize {
-        self.interaction_counts
-            .get(session_id)
-            .copied()
-            .unwrap_or(0)
+    /// Live documents per segment, of these sessions (found through their
+    /// session_id terms, so possibly a few more) or of every session
+    fn session_docs(
+        &self,
+        searcher: &tantivy::Searcher,
+        session_ids: Option<&[String]>,
+    ) -> Result<HashMap<u32, Vec<DocId>>> {
+        let mut docs_by_segment: HashMap<u32, Vec<DocId>> = HashMap::new();
+        let Some(session_ids) = session_ids else {
+            for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+                let alive = segment_reader.alive_bitset();
+                docs_by_segment.insert(
+                    segment_ord as u32,
+                    (0..segment_reader.max_doc())
+                        .filter(|&doc| alive.is_none_or(|bitset| bitset.is_alive(doc)))
+                        .collect(),
+                );
+            }
+            return Ok(docs_by_segment);
+        };
+        if session_ids.is_empty() {
+            return Ok(docs_by_segment);
+        }
+
+        let query = BooleanQuery::new(
+            session_ids
+                .iter()
+                .map(|id| {
+                    let term: Box<dyn tantivy::query::Query> = Box::new(self.session_query(id));
+                    (Occur::Should, term)
+                })
+                .collect(),
+        );
+        for doc_address in searcher.search(&query, &DocSetCollector)? {
+            docs_by_segment
+                .entry(doc_address.segment_ord)
+                .or_default()
+                .push(doc_address.doc_id);
+        }
+        Ok(docs_by_segment)
     }
 
-    pub fn get_all_documents(
+    /// Titles of these sessions, or of every session: the latest summary
+    /// Claude Code wrote, else the first substantive main-thread user message.
+    /// Sessions with neither are left out.
+    pub fn session_titles(
         &self,
-        project_filter: Option<String>,
-        limit: usize,
-    ) -> Result<Vec<SearchResult>> {
+        session_ids: Option<&[String]>,
+    ) -> Result<HashMap<String, String>> {
         let searcher = self.reader.searcher();
+        let mut user_messages: HashMap<String, Vec<(i64, DocAddress)>> = HashMap::new();
+        let mut summaries: HashMap<String, ((i64, u64), DocAddress)> = HashMap::new();
+        let mut session_id = String::new();
+        let mut message_type = String::new();
+
+        for (segment_ord, docs) in self.session_docs(&searcher, session_ids)? {
+            let fast_fields = searcher.segment_reader(segment_ord).fast_fields();
+            let (Some(session_col), Some(type_col)) = (
+                fast_fields.str("session_id")?,
+                fast_fields.str("message_type")?,
+            ) else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let sidechain_col = fast_fields.bool("is_sidechain")?;
+            let sequence_col = fast_fields.u64("sequence_num")?;
 
-        let query: Box<dyn tantivy::query::Query> = if let Some(ref project_filter) = project_filter
-        {
-            build_project_query(self.project_field, project_filter)
-        } else {
-            Box::new(tantivy::query::AllQuery)
-        };
-
-        let top_docs = searcher.search(&*query, &TopDocs::with_limit(limit))?;
-
-        let mut results = Vec::new();
-        for (_score, doc_address) in top_docs {
-            let result = self.doc_to_result(&searcher.doc(doc_address)?, 1.0, "")?;
+            for doc in docs {
+                if sidechain_col.first(doc).unwrap_or(false) {
+                    continue;
+                }
+                message_type.clear();
+                if let Some(ord) = type_col.term_ords(doc).next() {
+                    type_col.ord_to_str(ord, &mut message_type)?;
+                }
+                if !matches!(message_type.as_str(), "User" | "Summary") {
+                    continue;
+                }
+                let Some(ord) = session_col.term_ords(doc).next() else {
+                    continue;
+                };
+                session_col.ord_to_str(ord, &mut session_id)?;
+                if session_ids.is_some_and(|ids| !ids.contains(&session_id)) {
+                    continue;
+                }
+                let timestamp = timestamp_col
+                    .first(doc)
+                    .map_or(0, |dt| dt.into_timestamp_millis());
+                let doc_address = DocAddress::new(segment_ord, doc);
+                if message_type == "Summary" {
+                    let order = (timestamp, sequence_col.first(doc).unwrap_or(0));
+                    let latest = summaries
+                        .entry(session_id.clone())
+                        .or_insert((order, doc_address));
+                    if order > latest.0 {
+                        *latest = (order, doc_address);
+                    }
+                } else {
+                    user_messages
+                        .entry(session_id.clone())
+                        .or_default()
+                        .push((timestamp, doc_address));
+                }
+            }
+        }
 
-            if let Some(ref project_filter) = project_filter
-                && !project_matches(&result.project_path, project_filter)
-            {
+        let mut titles = HashMap::new();
+        for (session_id, (_, doc_address)) in summaries {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let uuid = doc
+                .get_first(self.uuid_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            let summary = self.doc_content(&doc, uuid);
+            if !summary.is_empty() {
+                titles.insert(session_id, title_from_summary(&summary));
+            }
+        }
+        for (session_id, mut messages) in user_messages {
+            if titles.contains_key(&session_id) {
                 continue;
             }
-
-            results.push(result);
+            messages.sort_by_key(|(timestamp, _)| *timestamp);
+            for (_, doc_address) in messages.into_iter().take(TITLE_CANDIDATES) {
+                let doc: TantivyDocument = searcher.doc(doc_address)?;
+                let uuid = doc
+                    .get_first(self.uuid_field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                if let Some(title) = title_from_message(&self.doc_content(&doc, uuid)) {
+                    titles.insert(session_id, title);
+                    break;
+                }
+            }
         }
-
-        Ok(results)
+        Ok(titles)
     }
-}
 
-/// Search result with surrounding context messages
-#[derive(Debug, Clone)]
-pub struct SearchResultWithContext {
-    pub matched_message: SearchResult,
-    pub context_messages: Vec<SearchResult>,
-    pub match_index: usize,
-    pub total_session_messages: usize,
-}
+    /// Hits per month and project over every match of `query`, with the same
+    /// filters as `search` but read from fast fields instead of documents
+    pub fn search_facets(&self, query: &SearchQuery) -> Result<SearchFacets> {
+        let searcher = self.reader.searcher();
+        let final_query = self.build_query(query)?;
+        let weight = final_query.weight(EnableScoring::disabled_from_searcher(&searcher))?;
+        let mut facets = SearchFacets::default();
+        let mut project = String::new();
+        let mut value = String::new();
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(project_col), Some(session_col), Some(host_col), Some(source_col)) = (
+                fast_fields.str("project")?,
+                fast_fields.str("session_id")?,
+                fast_fields.str("source_host")?,
+                fast_fields.str("source")?,
+            ) else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let alive = segment_reader.alive_bitset();
+            let mut scorer = weight.scorer(segment_reader, 1.0)?;
+            let mut doc = scorer.doc();
+            while doc != TERMINATED {
+                let current = doc;
+                doc = scorer.advance();
+                if alive.is_some_and(|bitset| !bitset.is_alive(current)) {
+                    continue;
+                }
 
-/// Options for what to include in search result display
-#[derive(Debug, Clone)]
-pub struct DisplayOptions {
-    pub include_thinking: bool,
-    pub include_tools: bool,
-    /// Characters shown per message around match (0 = full content)
-    pub truncate_length: usize,
-}
+                project.clear();
+                if let Some(ord) = project_col.term_ords(current).next() {
+                    project_col.ord_to_str(ord, &mut project)?;
+                }
+                if !query.project_filters.is_empty()
+                    && !matches_any_project(&project, &query.project_filters)
+                {
+                    continue;
+                }
+                if let Some(filter) = &query.session_filter {
+                    value.clear();
+                    if let Some(ord) = session_col.term_ords(current).next() {
+                        session_col.ord_to_str(ord, &mut value)?;
+                    }
+                    if !value.starts_with(filter.as_str()) {
+                        continue;
+                    }
+                }
+                if let Some(filter) = &query.host_filter {
+                    value.clear();
+                    if let Some(ord) = host_col.term_ords(current).next() {
+                        host_col.ord_to_str(ord, &mut value)?;
+                    }
+                    let host = if value.is_empty() { LOCAL_HOST } else { &value };
+                    if host != filter {
+                        continue;
+                    }
+                }
+                let timestamp = timestamp_col
+                    .first(current)
+                    .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()));
+                if let Some(timestamp) = timestamp
+                    && (query.after.is_some_and(|after| timestamp < after)
+                        || query.before.is_some_and(|before| timestamp > before))
+                {
+                    continue;
+                }
 
-impl Default for DisplayOptions {
-    fn default() -> Self {
-        Self {
-            include_thinking: false,
-            include_tools: false,
-            truncate_length: 300,
+                facets.hits += 1;
+                *facets.projects.entry(project.clone()).or_default() += 1;
+                value.clear();
+                if let Some(ord) = source_col.term_ords(current).next() {
+                    source_col.ord_to_str(ord, &mut value)?;
+                }
+                *facets.sources.entry(value.clone()).or_default() += 1;
+                if let Some(timestamp) = timestamp {
+                    *facets
+                        .months
+                        .entry(local_time(timestamp).format("%Y-%m").to_string())
+                        .or_default() += 1;
+                }
+            }
         }
+        Ok(facets)
     }
-}
 
-/// Filter content based on display options
-fn filter_content(s: &str, opts: &DisplayOptions) -> Option<String> {
-    // Check if content should be hidden
-    if !opts.include_thinking && s.starts_with("[thinking]") {
-        return None;
-    }
-    if !opts.include_tools
-        && (s.starts_with('[') && s.contains(']') && !s.starts_with("[result]"))
-        && !s.starts_with("[thinking]")
-    {
-        // Looks like a tool call [ToolName] {...}
-        if let Some(bracket_end) = s.find(']') {
-            let prefix = &s[1..bracket_end];
-            // Tool names are typically CamelCase or contain underscores/colons
-            if prefix
-                .chars()
-                .any(|c| c.is_uppercase() || c == '_' || c == ':')
-                && !prefix.contains(' ')
-            {
-                return None;
+    /// Rewrites of a query that found nothing which do find something, with
+    /// the same filters, most hits first. A word the index doesn't have is
+    /// swapped for indexed terms a typo away; a compound word such as
+    /// `tokio-uring` is also tried as its separate parts, since it is
+    /// searched as a phrase.
+    pub fn suggest_queries(&self, query: &SearchQuery) -> Result<Vec<QuerySuggestion>> {
+        let searcher = self.reader.searcher();
+        let mut analyzer = self.index.tokenizer_for_field(self.content_field)?;
+        let mut candidates: Vec<String> = Vec::new();
+        for word in query_words(&query.text) {
+            if word.starts_with('"') {
+                continue;
+            }
+            let mut tokens = Vec::new();
+            let mut stream = analyzer.token_stream(&word);
+            while stream.advance() {
+                tokens.push(stream.token().text.clone());
+            }
+
+            let mut variants = Vec::new();
+            let mut known = Vec::new();
+            for (i, token) in tokens.iter().enumerate() {
+                if searcher.doc_freq(&Term::from_field_text(self.content_field, token))? > 0 {
+                    known.push(token.as_str());
+                    continue;
+                }
+                for term in self.similar_terms(&searcher, token)? {
+                    let mut replaced = tokens.clone();
+                    replaced[i] = term;
+                    variants.push(replaced.join(" "));
+                }
+            }
+            if tokens.len() > 1 && !known.is_empty() {
+                variants.push(known.join(" "));
+            }
+            for variant in variants {
+                let text = query.text.replacen(&word, &variant, 1);
+                if !candidates.contains(&text) {
+                    candidates.push(text);
+                }
             }
         }
-    }
-    Some(s.to_string())
-}
 
-impl SearchResultWithContext {
-    /// Format as grep -C style output - compact and dense
-    /// Format: N. 📁 ~/path 🗒️ session (M msgs) 💬 msg_uuid
-    ///            User: content preview...
-    ///         »  AI: matched content...
-    ///            User: content...
-    pub fn format_compact(&self, index: usize) -> String {
-        self.format_compact_with_options(index, &DisplayOptions::default())
+        let mut suggestions = Vec::new();
+        for text in candidates {
+            let rewritten = SearchQuery {
+                text,
+                ..query.clone()
+            };
+            let hits = self.search_facets(&rewritten)?.hits;
+            if hits > 0 {
+                suggestions.push(QuerySuggestion {
+                    query: rewritten.text,
+                    hits,
+                });
+            }
+        }
+        suggestions.sort_by_key(|s| std::cmp::Reverse(s.hits));
+        suggestions.truncate(MAX_QUERY_SUGGESTIONS);
+        Ok(suggestions)
     }
 
-    /// Format with display options
-    pub fn format_compact_with_options(&self, index: usize, opts: &DisplayOptions) -> String {
-        let mut output = String::new();
-
-        let project_path_full = &self.matched_message.project_path;
-        let project_path_display = self.matched_message.project_path_display();
-        let session_id = &self.matched_message.session_id;
-
-        let jsonl_path = session_jsonl_path(project_path_full, session_id).unwrap_or_default();
-        let jsonl_path_str = jsonl_path.to_string_lossy();
+    /// Indexed content terms one edit from `token`, or two for words over
+    /// five letters: closest first, then those in the most messages
+    fn similar_terms(&self, searcher: &Searcher, token: &str) -> Result<Vec<String>> {
+        let len = token.chars().count();
+        if len < 3 {
+            return Ok(Vec::new());
+        }
+        let max_distance = if len <= 5 { 1 } else { 2 };
+        let mut found: HashMap<String, usize> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(self.content_field)?;
+            let mut terms = inverted_index.terms().stream()?;
+            while terms.advance() {
+                let Ok(term) = std::str::from_utf8(terms.key()) else {
+                    continue;
+                };
+                if term.chars().count().abs_diff(len) > max_distance || found.contains_key(term) {
+                    continue;
+                }
+                let distance = edit_distance(token, term);
+                if distance <= max_distance {
+                    found.insert(term.to_string(), distance);
+                }
+            }
+        }
 
-        let short_session = short_uuid(session_id);
-        let short_msg = short_uuid(&self.matched_message.uuid);
+        let mut similar = Vec::new();
+        for (term, distance) in found {
+            let doc_freq = searcher.doc_freq(&Term::from_field_text(self.content_field, &term))?;
+            similar.push((distance, doc_freq, term));
+        }
+        similar.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+        Ok(similar
+            .into_iter()
+            .take(SIMILAR_TERMS)
+            .map(|(_, _, term)| term)
+            .collect())
+    }
 
-        let path_link = file_hyperlink(project_path_full, &project_path_display);
-        let session_link = file_hyperlink(&jsonl_path_str, short_session);
+    /// Indexed content terms starting with `prefix`, with how many messages
+    /// each is in, most frequent first. Counts come from the term
+    /// dictionaries, so messages replaced by a reindex may still count
+    /// until their segment is merged.
+    pub fn suggest_terms(&self, prefix: &str, limit: usize) -> Result<Vec<(String, u32)>> {
+        let prefix = prefix.to_lowercase();
+        let searcher = self.reader.searcher();
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(self.content_field)?;
+            let mut terms = inverted_index
+                .terms()
+                .range()
+                .ge(prefix.as_bytes())
+                .into_stream()?;
+            while terms.advance() {
+                if !terms.key().starts_with(prefix.as_bytes()) {
+                    break;
+                }
+                if let Ok(term) = std::str::from_utf8(terms.key()) {
+                    *counts.entry(term.to_string()).or_default() += terms.value().doc_freq;
+                }
+            }
+        }
 
-        output.push_str(&format!(
-            "{}. 📁 {} 🗒️ {} ({} msgs) 💬 {} 📅 {}\n",
-            index + 1,
-            path_link,
-            session_link,
-            self.total_session_messages,
-            short_msg,
-            self.matched_message.timestamp.format("%Y-%m-%d %H:%M"),
-        ));
+        let mut terms: Vec<(String, u32)> = counts.into_iter().collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(limit);
+        Ok(terms)
+    }
 
-        let mut tags = Vec::new();
-        tags.extend(self.matched_message.technologies.iter().take(3).cloned());
-        tags.extend(self.matched_message.code_languages.iter().take(2).cloned());
-        if self.matched_message.has_error {
-            tags.push("error".to_string());
+    /// Messages per subdirectory of `dir`, or of the deepest directory all
+    /// matching messages share, counted on the cwd facet
+    pub fn directory_breakdown(
+        &self,
+        project_filter: Option<&str>,
+        dir: Option<&str>,
+    ) -> Result<DirectoryBreakdown> {
+        let searcher = self.reader.searcher();
+        let mut parts: Vec<(Occur, Box<dyn tantivy::query::Query>)> = Vec::new();
+        if let Some(filter) = project_filter {
+            parts.push((Occur::Must, self.project_query(&[filter.to_string()])?));
         }
-        if !tags.is_empty() {
-            output.push_str(&format!("🎟️{}\n", tags.join(",")));
+        if let Some(dir) = dir {
+            parts.push((Occur::Must, self.dir_query(dir)));
         }
+        let query: Box<dyn tantivy::query::Query> = if parts.is_empty() {
+            Box::new(AllQuery)
+        } else {
+            Box::new(BooleanQuery::new(parts))
+        };
 
-        self.format_context_messages(&mut output, opts);
-        output
-    }
-
-    fn format_context_messages(&self, output: &mut String, opts: &DisplayOptions) {
-        for (i, msg) in self.context_messages.iter().enumerate() {
-            // Filter content based on options
-            if filter_content(&msg.content, opts).is_none() {
+        let mut facet = dir.map_or_else(Facet::root, |dir| cwd_facet(&expand_tilde(dir)));
+        // Messages in or below `facet`; at the root, those with a cwd at all
+        let mut total = match dir {
+            Some(_) => Some(searcher.search(&query, &Count)? as u64),
+            None => None,
+        };
+        loop {
+            let mut collector = FacetCollector::for_field("cwd_tree");
+            collector.add_facet(facet.clone());
+            let counts = searcher.search(&query, &collector)?;
+            let mut children: Vec<(Facet, u64)> = counts
+                .get(facet.clone())
+                .map(|(child, count)| (child.clone(), count))
+                .collect();
+            let in_children: u64 = children.iter().map(|(_, count)| count).sum();
+            let total = *total.get_or_insert(in_children);
+            // Walk down while a single directory holds everything
+            if dir.is_none() && children.len() == 1 && in_children == total {
+                facet = children.remove(0).0;
                 continue;
             }
 
-            let prefix = if i == self.match_index { "»  " } else { "   " };
-            let content = if opts.truncate_length == 0 {
-                msg.content.split_whitespace().collect::<Vec<_>>().join(" ")
-            } else {
-                truncate_content(&msg.content, opts.truncate_length, true)
+            children.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            return Ok(DirectoryBreakdown {
+                path: if facet.is_root() {
+                    "/".to_string()
+                } else {
+                    facet.to_path_string()
+                },
+                here: total.saturating_sub(in_children),
+                children: children
+                    .into_iter()
+                    .map(|(child, count)| {
+                        let name = child.to_path().last().copied().unwrap_or_default();
+                        (name.to_string(), count)
+                    })
+                    .collect(),
+            });
+        }
+    }
+
+    /// Technologies, languages, tools and projects counted over every message
+    /// in the date range from fast fields, however large the history
+    pub fn topic_report(
+        &self,
+        project_filter: Option<&str>,
+        dir_filter: Option<&str>,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<TopicReport> {
+        let searcher = self.reader.searcher();
+        let dir_weight = dir_filter
+            .map(|dir| {
+                self.dir_query(dir)
+                    .weight(EnableScoring::disabled_from_searcher(&searcher))
+            })
+            .transpose()?;
+        let mut report = TopicReport::default();
+        let mut cwd = String::new();
+        let mut values = [String::new(), String::new(), String::new(), String::new()];
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(cwd_col), Some(project_col), Some(tech_col), Some(lang_col), Some(tool_col)) = (
+                fast_fields.str("cwd")?,
+                fast_fields.str("project")?,
+                fast_fields.str("technologies")?,
+                fast_fields.str("code_languages")?,
+                fast_fields.str("tools_mentioned")?,
+            ) else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in segment_docs(segment_reader, dir_weight.as_deref())? {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                if after.is_some() || before.is_some() {
+                    let timestamp = timestamp_col
+                        .first(doc)
+                        .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+                        .unwrap_or_default();
+                    if after.is_some_and(|after| timestamp < after)
+                        || before.is_some_and(|before| timestamp > before)
+                    {
+                        continue;
+                    }
+                }
+                cwd.clear();
+                if let Some(ord) = cwd_col.term_ords(doc).next() {
+                    cwd_col.ord_to_str(ord, &mut cwd)?;
+                }
+                if let Some(filter) = project_filter
+                    && !project_matches(&cwd, filter)
+                {
+                    continue;
+                }
+
+                // Multi-valued metadata is indexed as one space-joined value
+                for (col, value) in [&project_col, &tech_col, &lang_col, &tool_col]
+                    .into_iter()
+                    .zip(values.iter_mut())
+                {
+                    value.clear();
+                    if let Some(ord) = col.term_ords(doc).next() {
+                        col.ord_to_str(ord, value)?;
+                    }
+                }
+                let [project, technologies, languages, tools] = &values;
+                report.record(project, technologies, languages, tools);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Exact message statistics from fast fields, visiting every live doc in
+    /// every segment rather than a capped search
+    pub fn aggregate_stats(
+        &self,
+        project_filter: Option<&str>,
+        dir_filter: Option<&str>,
+    ) -> Result<IndexStats> {
+        let searcher = self.reader.searcher();
+        let dir_weight = dir_filter
+            .map(|dir| {
+                self.dir_query(dir)
+                    .weight(EnableScoring::disabled_from_searcher(&searcher))
+            })
+            .transpose()?;
+        let mut stats = IndexStats::default();
+        let mut session_id = String::new();
+        let mut cwd = String::new();
+        let mut words = String::new();
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(session_col), Some(cwd_col), Some(tech_col), Some(lang_col)) = (
+                fast_fields.str("session_id")?,
+                fast_fields.str("cwd")?,
+                fast_fields.str("technologies")?,
+                fast_fields.str("code_languages")?,
+            ) else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let has_code_col = fast_fields.bool("has_code")?;
+            let has_error_col = fast_fields.bool("has_error")?;
+            let word_count_col = fast_fields.u64("word_count")?;
+            let token_count_col = fast_fields.u64("token_count")?;
+            let branch_col = fast_fields.str("git_branch")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in segment_docs(segment_reader, dir_weight.as_deref())? {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                cwd.clear();
+                if let Some(ord) = cwd_col.term_ords(doc).next() {
+                    cwd_col.ord_to_str(ord, &mut cwd)?;
+                }
+                if let Some(filter) = project_filter
+                    && !project_matches(&cwd, filter)
+                {
+                    continue;
+                }
+
+                stats.messages += 1;
+                *stats.projects.entry(cwd.clone()).or_default() += 1;
+                let tokens = token_count_col.first(doc).unwrap_or(0);
+                stats.tokens += tokens;
+                if let Some(ord) = session_col.term_ords(doc).next() {
+                    session_col.ord_to_str(ord, &mut session_id)?;
+                    *stats.sessions.entry(session_id.clone()).or_default() += 1;
+                    *stats.session_tokens.entry(session_id.clone()).or_default() += tokens;
+                }
+                if has_code_col.first(doc).unwrap_or(false) {
+                    stats.with_code += 1;
+                }
+                if has_error_col.first(doc).unwrap_or(false) {
+                    stats.with_error += 1;
+                }
+                stats.words += word_count_col.first(doc).unwrap_or(0);
+                if let Some(col) = &branch_col
+                    && let Some(ord) = col.term_ords(doc).next()
+                {
+                    col.ord_to_str(ord, &mut words)?;
+                    if !words.is_empty() {
+                        *stats.branches.entry(words.clone()).or_default() += 1;
+                    }
+                }
+
+                // Multi-valued metadata is indexed as one space-joined value
+                for (col, counts) in [
+                    (&tech_col, &mut stats.technologies),
+                    (&lang_col, &mut stats.code_languages),
+                ] {
+                    if let Some(ord) = col.term_ords(doc).next() {
+                        col.ord_to_str(ord, &mut words)?;
+                        for word in words.split_whitespace() {
+                            *counts.entry(word.to_string()).or_default() += 1;
+                        }
+                    }
+                }
+
+                if let Some(timestamp) = timestamp_col
+                    .first(doc)
+                    .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+                {
+                    *stats
+                        .months
+                        .entry(local_time(timestamp).format("%Y-%m").to_string())
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Sum recorded API usage per project, model and day from fast fields,
+    /// pricing each response by its model
+    pub fn usage_report(
+        &self,
+        project_filter: Option<&str>,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        prices: &UsageConfig,
+    ) -> Result<UsageReport> {
+        let searcher = self.reader.searcher();
+        let mut report = UsageReport::default();
+        let mut price_cache = HashMap::new();
+        let mut cwd = String::new();
+        let mut model = String::new();
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(cwd_col), Some(model_col)) =
+                (fast_fields.str("cwd")?, fast_fields.str("model")?)
+            else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let input_col = fast_fields.u64("input_tokens")?;
+            let output_col = fast_fields.u64("output_tokens")?;
+            let cache_creation_col = fast_fields.u64("cache_creation_tokens")?;
+            let cache_read_col = fast_fields.u64("cache_read_tokens")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in 0..segment_reader.max_doc() {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                let usage = TokenUsage {
+                    input_tokens: input_col.first(doc).unwrap_or(0),
+                    output_tokens: output_col.first(doc).unwrap_or(0),
+                    cache_creation_tokens: cache_creation_col.first(doc).unwrap_or(0),
+                    cache_read_tokens: cache_read_col.first(doc).unwrap_or(0),
+                };
+                if usage.total() == 0 {
+                    continue;
+                }
+
+                let timestamp = timestamp_col
+                    .first(doc)
+                    .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+                    .unwrap_or_default();
+                if after.is_some_and(|after| timestamp < after)
+                    || before.is_some_and(|before| timestamp > before)
+                {
+                    continue;
+                }
+
+                cwd.clear();
+                if let Some(ord) = cwd_col.term_ords(doc).next() {
+                    cwd_col.ord_to_str(ord, &mut cwd)?;
+                }
+                if let Some(filter) = project_filter
+                    && !project_matches(&cwd, filter)
+                {
+                    continue;
+                }
+
+                model.clear();
+                if let Some(ord) = model_col.term_ords(doc).next() {
+                    model_col.ord_to_str(ord, &mut model)?;
+                }
+                let price = *price_cache
+                    .entry(model.clone())
+                    .or_insert_with(|| prices.price_for(&model));
+                let cost = price.map(|p| p.cost(&usage));
+
+                report.total.record(&usage, cost);
+                for (groups, key) in [
+                    (&mut report.by_project, cwd.clone()),
+                    (&mut report.by_model, model.clone()),
+                    (
+                        &mut report.by_day,
+                        local_time(timestamp).format("%Y-%m-%d").to_string(),
+                    ),
+                ] {
+                    groups.entry(key).or_default().record(&usage, cost);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Messages whose Read/Edit/Write tools touched a path matching `path_query`,
+    /// oldest first, from fast fields
+    pub fn file_touches(
+        &self,
+        path_query: &str,
+        project_filter: Option<&str>,
+        modified_only: bool,
+    ) -> Result<Vec<FileTouch>> {
+        let searcher = self.reader.searcher();
+        let mut touches = Vec::new();
+        let mut path = String::new();
+        let mut modified = String::new();
+        let mut cwd = String::new();
+        let mut value = String::new();
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (
+                Some(touched_col),
+                Some(modified_col),
+                Some(cwd_col),
+                Some(session_col),
+                Some(uuid_col),
+            ) = (
+                fast_fields.str("files_touched")?,
+                fast_fields.str("files_modified")?,
+                fast_fields.str("cwd")?,
+                fast_fields.str("session_id")?,
+                fast_fields.str("uuid")?,
+            )
+            else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in 0..segment_reader.max_doc() {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                for ord in touched_col.term_ords(doc) {
+                    touched_col.ord_to_str(ord, &mut path)?;
+                    if !file_path_matches(&path, path_query) {
+                        continue;
+                    }
+                    let mut is_modified = false;
+                    for ord in modified_col.term_ords(doc) {
+                        modified_col.ord_to_str(ord, &mut modified)?;
+                        is_modified |= modified == path;
+                    }
+                    if modified_only && !is_modified {
+                        continue;
+                    }
+
+                    cwd.clear();
+                    if let Some(ord) = cwd_col.term_ords(doc).next() {
+                        cwd_col.ord_to_str(ord, &mut cwd)?;
+                    }
+                    if let Some(filter) = project_filter
+                        && !project_matches(&cwd, filter)
+                    {
+                        continue;
+                    }
+
+                    let mut read_str = |col: &tantivy::columnar::StrColumn| -> Result<String> {
+                        value.clear();
+                        if let Some(ord) = col.term_ords(doc).next() {
+                            col.ord_to_str(ord, &mut value)?;
+                        }
+                        Ok(value.clone())
+                    };
+                    touches.push(FileTouch {
+                        uuid: read_str(&uuid_col)?,
+                        session_id: read_str(&session_col)?,
+                        project_path: cwd.clone(),
+                        timestamp: timestamp_col
+                            .first(doc)
+                            .and_then(|dt| {
+                                DateTime::from_timestamp_millis(dt.into_timestamp_millis())
+                            })
+                            .unwrap_or_default(),
+                        path: path.clone(),
+                        modified: is_modified,
+                    });
+                }
+            }
+        }
+
+        touches.sort_by_key(|t| t.timestamp);
+        Ok(touches)
+    }
+
+    /// Count tool calls and failed tool results per tool and project from fast fields
+    pub fn tool_usage(
+        &self,
+        project_filter: Option<&str>,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<ToolUsageReport> {
+        let searcher = self.reader.searcher();
+        let mut report = ToolUsageReport::default();
+        let mut cwd = String::new();
+        let mut tools = String::new();
+
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(cwd_col), Some(calls_col), Some(errors_col)) = (
+                fast_fields.str("cwd")?,
+                fast_fields.str("tool_name")?,
+                fast_fields.str("tool_error")?,
+            ) else {
+                continue;
+            };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in 0..segment_reader.max_doc() {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                let timestamp = timestamp_col
+                    .first(doc)
+                    .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+                    .unwrap_or_default();
+                if after.is_some_and(|after| timestamp < after)
+                    || before.is_some_and(|before| timestamp > before)
+                {
+                    continue;
+                }
+
+                cwd.clear();
+                if let Some(ord) = cwd_col.term_ords(doc).next() {
+                    cwd_col.ord_to_str(ord, &mut cwd)?;
+                }
+                if let Some(filter) = project_filter
+                    && !project_matches(&cwd, filter)
+                {
+                    continue;
+                }
+
+                for (col, is_error) in [(&calls_col, false), (&errors_col, true)] {
+                    let Some(ord) = col.term_ords(doc).next() else {
+                        continue;
+                    };
+                    col.ord_to_str(ord, &mut tools)?;
+                    for tool in tools.split_whitespace() {
+                        let (calls, errors) = if is_error { (0, 1) } else { (1, 0) };
+                        report.record(&cwd, tool, calls, errors);
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Shell commands run in matching messages, oldest first. `pattern` is a
+    /// case-insensitive substring of the command.
+    pub fn command_runs(
+        &self,
+        project_filter: Option<&str>,
+        session_filter: Option<&str>,
+        pattern: Option<&str>,
+    ) -> Result<Vec<CommandRun>> {
+        let searcher = self.reader.searcher();
+        let session_filter = match session_filter {
+            Some(filter) => Some(
+                self.resolve_session_id(filter)?
+                    .unwrap_or(filter.to_string()),
+            ),
+            None => None,
+        };
+
+        // tool_name is tokenized, so terms are lowercase words: Claude Code's
+        // Bash, Codex's shell / exec_command and Gemini's run_shell_command
+        let shell_tools: Vec<(Occur, Box<dyn tantivy::query::Query>)> = SHELL_TOOL_TERMS
+            .iter()
+            .map(|term| {
+                (
+                    Occur::Should,
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(self.tool_name_field, term),
+                        IndexRecordOption::Basic,
+                    )) as Box<dyn tantivy::query::Query>,
+                )
+            })
+            .collect();
+        let mut query_parts: Vec<(Occur, Box<dyn tantivy::query::Query>)> =
+            vec![(Occur::Must, Box::new(BooleanQuery::new(shell_tools)))];
+        if let Some(project_filter) = project_filter {
+            query_parts.push((
+                Occur::Must,
+                self.project_query(&[project_filter.to_string()])?,
+            ));
+        }
+        let query = BooleanQuery::new(query_parts);
+        let pattern = pattern.map(str::to_lowercase);
+
+        let mut runs = Vec::new();
+        for doc_address in searcher.search(&query, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let Some(json) = doc
+                .get_first(self.shell_commands_field)
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+            else {
+                continue;
+            };
+            let commands: Vec<ShellCommand> = serde_json::from_str(json)?;
+
+            let result = self.doc_to_result(&doc, 0.0, "")?;
+            if let Some(filter) = project_filter
+                && !project_matches(&result.project, filter)
+            {
+                continue;
+            }
+            if let Some(filter) = &session_filter
+                && result.session_id != *filter
+            {
+                continue;
+            }
+
+            for command in commands {
+                if let Some(pattern) = &pattern
+                    && !command.command.to_lowercase().contains(pattern.as_str())
+                {
+                    continue;
+                }
+                runs.push(CommandRun {
+                    uuid: result.uuid.clone(),
+                    session_id: result.session_id.clone(),
+                    project_path: result.project_path.clone(),
+                    timestamp: result.timestamp,
+                    command,
+                });
+            }
+        }
+
+        runs.sort_by_key(|r| r.timestamp);
+        Ok(runs)
+    }
+
+    /// Messages of a session with the tools it called, the files it read
+    /// and modified and the shell commands it ran; None for unknown ids
+    pub fn session_activity(&self, session_id: &str) -> Result<Option<SessionActivity>> {
+        let Some(session_id) = self.resolve_session_id(session_id)? else {
+            return Ok(None);
+        };
+        let searcher = self.reader.searcher();
+        let query = self.session_query(&session_id);
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(MAX_SESSION_MESSAGES))?;
+
+        let mut activity = SessionActivity {
+            session_id,
+            ..Default::default()
+        };
+        let mut touched = BTreeSet::new();
+        let mut commands = Vec::new();
+        for (score, doc_address) in top_docs {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            // Tool names are joined with spaces, paths stored one per value
+            let values = |field: Field| {
+                doc.get_all(field)
+                    .filter_map(|v| v.as_str())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            };
+            for (field, counts) in [
+                (self.tool_name_field, &mut activity.tool_calls),
+                (self.tool_error_field, &mut activity.tool_errors),
+            ] {
+                for tool in values(field).iter().flat_map(|s| s.split_whitespace()) {
+                    *counts.entry(tool.to_string()).or_default() += 1;
+                }
+            }
+            touched.extend(values(self.files_touched_field));
+            activity
+                .files_modified
+                .extend(values(self.files_modified_field));
+
+            let result = self.doc_to_result(&doc, score, "")?;
+            if let Some(json) = doc
+                .get_first(self.shell_commands_field)
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+            {
+                let ran: Vec<ShellCommand> = serde_json::from_str(json)?;
+                commands.push((result.sequence_num, ran));
+            }
+            activity.messages.push(result);
+        }
+
+        activity.messages.sort_by_key(|m| m.sequence_num);
+        commands.sort_by_key(|(seq, _)| *seq);
+        activity.commands = commands.into_iter().flat_map(|(_, ran)| ran).collect();
+        activity.files_read = touched
+            .difference(&activity.files_modified)
+            .cloned()
+            .collect();
+        Ok(Some(activity))
+    }
+
+    /// Decision-like statements of assistant messages, oldest first, from
+    /// one session (id or unique prefix) or all sessions matching the
+    /// project filter and date range
+    pub fn decisions(
+        &self,
+        session_filter: Option<&str>,
+        project_filter: Option<&str>,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Decision>> {
+        let searcher = self.reader.searcher();
+        let mut clauses: Vec<(Occur, Box<dyn tantivy::query::Query>)> = vec![(
+            Occur::Must,
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.message_type_field, "assistant"),
+                IndexRecordOption::Basic,
+            )),
+        )];
+        if let Some(prefix) = session_filter {
+            let Some(session_id) = self.resolve_session_id(prefix)? else {
+                return Ok(Vec::new());
+            };
+            clauses.push((Occur::Must, Box::new(self.session_query(&session_id))));
+        }
+        if let Some(project_filter) = project_filter {
+            clauses.push((
+                Occur::Must,
+                self.project_query(&[project_filter.to_string()])?,
+            ));
+        }
+        if let Some(range) = date_range_query(after, before) {
+            clauses.push((Occur::Must, range));
+        }
+
+        let mut decisions = Vec::new();
+        for doc_address in searcher.search(&BooleanQuery::new(clauses), &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let result = self.doc_to_result(&doc, 0.0, "")?;
+            for (kind, text) in extract_decisions(&result.content) {
+                decisions.push(Decision {
+                    uuid: result.uuid.clone(),
+                    session_id: result.session_id.clone(),
+                    project_path: result.project_path.clone(),
+                    timestamp: result.timestamp,
+                    kind,
+                    text,
+                });
+            }
+        }
+        decisions.sort_by_key(|d| d.timestamp);
+        Ok(decisions)
+    }
+
+    /// Failed tool results clustered by error signature, each cluster with the
+    /// messages where the failing tool later went through in the same session.
+    /// Clusters seen in fewer than `min_sessions` sessions are dropped.
+    pub fn error_clusters(
+        &self,
+        project_filter: Option<&str>,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        min_sessions: usize,
+    ) -> Result<Vec<ErrorCluster>> {
+        let searcher = self.reader.searcher();
+        let query = TermQuery::new(
+            Term::from_field_bool(self.has_error_field, true),
+            IndexRecordOption::Basic,
+        );
+
+        let mut occurrences = Vec::new();
+        for doc_address in searcher.search(&query, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let result = self.doc_to_result(&doc, 0.0, "")?;
+            if project_filter.is_some_and(|filter| !project_matches(&result.project, filter))
+                || after.is_some_and(|after| result.timestamp < after)
+                || before.is_some_and(|before| result.timestamp > before)
+            {
+                continue;
+            }
+
+            // Failed tools and [error] parts are both in block order
+            let tools = doc
+                .get_first(self.tool_error_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            let mut tools = tools.split_whitespace();
+            for message in extract_error_messages(&result.content) {
+                let tool = tools.next().unwrap_or("unknown");
+                if message.is_empty() {
+                    continue;
+                }
+                occurrences.push(ErrorOccurrence {
+                    uuid: result.uuid.clone(),
+                    session_id: result.session_id.clone(),
+                    project_path: result.project_path.clone(),
+                    timestamp: result.timestamp,
+                    tool: tool.to_string(),
+                    message,
+                });
+            }
+        }
+
+        let mut clusters = cluster_errors(occurrences);
+        clusters.retain(|c| c.session_count() >= min_sessions);
+        self.find_resolutions(&mut clusters)?;
+        Ok(clusters)
+    }
+
+    /// For each session of each cluster, the first call of the failing tool
+    /// after its last occurrence that isn't followed by another failure of it
+    fn find_resolutions(&self, clusters: &mut [ErrorCluster]) -> Result<()> {
+        let wanted: std::collections::HashSet<&str> = clusters
+            .iter()
+            .flat_map(|c| c.occurrences.iter().map(|o| o.session_id.as_str()))
+            .collect();
+
+        // Session id -> (timestamp, sequence, uuid, called tools, failed tools)
+        type Step = (DateTime<Utc>, u64, String, String, String);
+        let mut steps: HashMap<String, Vec<Step>> = HashMap::new();
+        let searcher = self.reader.searcher();
+        let mut session_id = String::new();
+        let mut value = String::new();
+        for segment_reader in searcher.segment_readers() {
+            let fast_fields = segment_reader.fast_fields();
+            let (Some(session_col), Some(uuid_col), Some(calls_col), Some(errors_col)) = (
+                fast_fields.str("session_id")?,
+                fast_fields.str("uuid")?,
+                fast_fields.str("tool_name")?,
+                fast_fields.str("tool_error")?,
+            ) else {
+                continue;
             };
+            let timestamp_col = fast_fields.date("timestamp")?;
+            let sequence_col = fast_fields.u64("sequence_num")?;
+            let alive = segment_reader.alive_bitset();
+
+            for doc in 0..segment_reader.max_doc() {
+                if alive.is_some_and(|bitset| !bitset.is_alive(doc)) {
+                    continue;
+                }
+                session_id.clear();
+                if let Some(ord) = session_col.term_ords(doc).next() {
+                    session_col.ord_to_str(ord, &mut session_id)?;
+                }
+                if !wanted.contains(session_id.as_str()) {
+                    continue;
+                }
+                let mut read_str = |col: &tantivy::columnar::StrColumn| -> Result<String> {
+                    value.clear();
+                    if let Some(ord) = col.term_ords(doc).next() {
+                        col.ord_to_str(ord, &mut value)?;
+                    }
+                    Ok(value.clone())
+                };
+                let step = (
+                    timestamp_col
+                        .first(doc)
+                        .and_then(|dt| DateTime::from_timestamp_millis(dt.into_timestamp_millis()))
+                        .unwrap_or_default(),
+                    sequence_col.first(doc).unwrap_or(0),
+                    read_str(&uuid_col)?,
+                    read_str(&calls_col)?,
+                    read_str(&errors_col)?,
+                );
+                steps.entry(session_id.clone()).or_default().push(step);
+            }
+        }
+        for session_steps in steps.values_mut() {
+            session_steps.sort_by_key(|(timestamp, sequence, ..)| (*timestamp, *sequence));
+        }
+
+        for cluster in clusters.iter_mut() {
+            let mut last_per_session: Vec<&ErrorOccurrence> = Vec::new();
+            for occurrence in cluster.occurrences.iter().rev() {
+                if !last_per_session
+                    .iter()
+                    .any(|o| o.session_id == occurrence.session_id)
+                {
+                    last_per_session.push(occurrence);
+                }
+            }
+
+            let mut resolutions = Vec::new();
+            for occurrence in last_per_session {
+                let Some(session_steps) = steps.get(&occurrence.session_id) else {
+                    continue;
+                };
+                let Some(start) = session_steps
+                    .iter()
+                    .position(|(_, _, uuid, ..)| *uuid == occurrence.uuid)
+                else {
+                    continue;
+                };
+
+                let mut candidate = None;
+                for (_, _, uuid, calls, errors) in &session_steps[start + 1..] {
+                    if candidate.is_some() && errors.split_whitespace().any(|t| t == cluster.tool) {
+                        candidate = None;
+                    }
+                    if calls.split_whitespace().any(|t| t == cluster.tool) {
+                        if candidate.is_some() {
+                            break;
+                        }
+                        candidate = Some(uuid);
+                    }
+                }
+                if let Some(uuid) = candidate {
+                    resolutions.push(ErrorResolution {
+                        uuid: uuid.clone(),
+                        session_id: occurrence.session_id.clone(),
+                        project_path: occurrence.project_path.clone(),
+                    });
+                }
+            }
+            // Oldest first, like occurrences
+            resolutions.reverse();
+            cluster.resolutions = resolutions;
+        }
+        Ok(())
+    }
+
+    pub fn get_all_documents(
+        &self,
+        project_filter: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let searcher = self.reader.searcher();
+
+        let query: Box<dyn tantivy::query::Query> = if let Some(ref project_filter) = project_filter
+        {
+            self.project_query(std::slice::from_ref(project_filter))?
+        } else {
+            Box::new(tantivy::query::AllQuery)
+        };
+
+        let top_docs = searcher.search(&*query, &TopDocs::with_limit(limit))?;
+
+        let mut results = Vec::new();
+        for (_score, doc_address) in top_docs {
+            let result = self.doc_to_result(&searcher.doc(doc_address)?, 1.0, "")?;
+
+            if let Some(ref project_filter) = project_filter
+                && !project_matches(&result.project, project_filter)
+            {
+                continue;
+            }
+
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+}
+
+/// Surround a match with the displayable messages near it in its session
+/// (`session_messages` ordered by sequence)
+fn with_context(
+    match_result: SearchResult,
+    session_messages: &[SearchResult],
+    context_before: usize,
+    context_after: usize,
+) -> SearchResultWithContext {
+    // If we can't get session messages, still return the match with just itself as context
+    if session_messages.is_empty() {
+        return SearchResultWithContext {
+            context_messages: vec![match_result.clone()],
+            matched_message: match_result,
+            match_index: 0,
+            total_session_messages: 1,
+            more_in_session: Vec::new(),
+            near_duplicates: Vec::new(),
+        };
+    }
+
+    // Count only displayable messages (consistent with get_session_messages)
+    let total_session_messages = session_messages
+        .iter()
+        .filter(|m| m.is_displayable())
+        .count();
+
+    // Find the matching message index by UUID or by sequence number as fallback
+    let match_idx = session_messages
+        .iter()
+        .position(|m| m.uuid == match_result.uuid)
+        .or_else(|| {
+            session_messages
+                .iter()
+                .position(|m| m.sequence_num == match_result.sequence_num)
+        });
+
+    let Some(idx) = match_idx else {
+        // UUID/sequence not found in session, return match with itself as context
+        return SearchResultWithContext {
+            context_messages: vec![match_result.clone()],
+            matched_message: match_result,
+            match_index: 0,
+            total_session_messages,
+            more_in_session: Vec::new(),
+            near_duplicates: Vec::new(),
+        };
+    };
+
+    // Get context window around the match
+    let start = idx.saturating_sub(context_before);
+    let end = (idx + context_after + 1).min(session_messages.len());
+
+    // Filter to displayable messages only, track new match index
+    let mut context_messages = Vec::new();
+    let mut new_match_idx = 0;
+    for (i, msg) in session_messages[start..end].iter().enumerate() {
+        if msg.is_displayable() {
+            if start + i == idx {
+                new_match_idx = context_messages.len();
+            }
+            context_messages.push(msg.clone());
+        }
+    }
+
+    // If no context found (e.g., all filtered out), use match as its own context
+    if context_messages.is_empty() {
+        context_messages.push(match_result.clone());
+        new_match_idx = 0;
+    }
+
+    SearchResultWithContext {
+        matched_message: match_result,
+        context_messages,
+        match_index: new_match_idx,
+        total_session_messages,
+        more_in_session: Vec::new(),
+        near_duplicates: Vec::new(),
+    }
+}
+
+/// One line summing up where a query's hits are, newest months first and
+/// busiest projects first, plus the tools they came from when there are several:
+/// 📊 342 hits 📅 2025-12 120, 2025-11 98, +3 earlier 📁 vault-rs 200, +2 more 🤖 claude-code 300, codex 42
+pub fn format_search_facets(facets: &SearchFacets) -> String {
+    fn list(entries: Vec<(String, usize)>, rest: &str) -> String {
+        let mut parts: Vec<_> = entries
+            .iter()
+            .take(FACET_ENTRIES)
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect();
+        if entries.len() > FACET_ENTRIES {
+            parts.push(format!("+{} {}", entries.len() - FACET_ENTRIES, rest));
+        }
+        parts.join(", ")
+    }
+
+    let mut months: Vec<_> = facets.months.clone().into_iter().collect();
+    months.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut projects: Vec<_> = facets.projects.clone().into_iter().collect();
+    projects.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let sources = if facets.sources.len() > 1 {
+        let mut sources: Vec<_> = facets.sources.clone().into_iter().collect();
+        sources.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        format!(" 🤖 {}", list(sources, "more"))
+    } else {
+        String::new()
+    };
+
+    format!(
+        "📊 {} hits 📅 {} 📁 {}{}\n",
+        facets.hits,
+        list(months, "earlier"),
+        list(projects, "more"),
+        sources
+    )
+}
+
+/// `suggest_terms` output, one term and its message count per line
+pub fn format_term_suggestions(prefix: &str, terms: &[(String, u32)]) -> String {
+    if terms.is_empty() {
+        return format!("No indexed terms start with '{}'.\n", prefix);
+    }
+    let width = terms
+        .iter()
+        .map(|(term, _)| term.chars().count())
+        .max()
+        .unwrap_or(0);
+    terms
+        .iter()
+        .map(|(term, count)| format!("{:<width$} {} msgs\n", term, count, width = width))
+        .collect()
+}
+
+/// "Did you mean" lines after "No results found.", empty without suggestions
+pub fn format_query_suggestions(suggestions: &[QuerySuggestion]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let mut output = String::from("Did you mean:\n");
+    for suggestion in suggestions {
+        output.push_str(&format!(
+            "  🔎 \"{}\" ({} hits)\n",
+            suggestion.query, suggestion.hits
+        ));
+    }
+    output
+}
+
+/// Numbered results separated by blank lines. Date-sorted results read like a
+/// journal: each day's run starts with a "── 2025-06-11 (3 results) ──" header.
+pub fn format_results(
+    results: &[SearchResultWithContext],
+    opts: &DisplayOptions,
+    sort_by: SortOrder,
+) -> String {
+    let day = |result: &SearchResultWithContext| {
+        local_time(result.matched_message.timestamp)
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let mut output = String::new();
+    for (i, result) in results.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        if sort_by != SortOrder::Relevance && (i == 0 || day(&results[i - 1]) != day(result)) {
+            let count = results[i..]
+                .iter()
+                .take_while(|other| day(other) == day(result))
+                .count();
+            let noun = if count == 1 { "result" } else { "results" };
+            output.push_str(&format!("── {} ({} {}) ──\n", day(result), count, noun));
+        }
+        output.push_str(&result.format_compact_with_options(i, opts));
+    }
+    output
+}
+
+/// Keep the first match of each session, in order, noting the uuids of the
+/// session's other matches on it
+pub fn dedupe_by_session(results: Vec<SearchResultWithContext>) -> Vec<SearchResultWithContext> {
+    let mut kept: Vec<SearchResultWithContext> = Vec::new();
+    let mut index_by_session: HashMap<String, usize> = HashMap::new();
+    for result in results {
+        match index_by_session.get(&result.matched_message.session_id) {
+            Some(&index) => kept[index]
+                .more_in_session
+                .push(result.matched_message.uuid),
+            None => {
+                index_by_session.insert(result.matched_message.session_id.clone(), kept.len());
+                kept.push(result);
+            }
+        }
+    }
+    kept
+}
+
+/// Keep the first of each group of matches with nearly the same content
+/// (retries, re-pasted answers), noting the uuids of the others on it
+pub fn collapse_near_duplicates(
+    results: Vec<SearchResultWithContext>,
+) -> Vec<SearchResultWithContext> {
+    let mut kept: Vec<SearchResultWithContext> = Vec::new();
+    for result in results {
+        let simhash = result.matched_message.simhash;
+        match kept
+            .iter_mut()
+            .find(|k| is_near_duplicate(k.matched_message.simhash, simhash))
+        {
+            Some(first) => first.near_duplicates.push(result.matched_message.uuid),
+            None => kept.push(result),
+        }
+    }
+    kept
+}
+
+/// Search result with surrounding context messages
+#[derive(Debug, Clone)]
+pub struct SearchResultWithContext {
+    pub matched_message: SearchResult,
+    pub context_messages: Vec<SearchResult>,
+    pub match_index: usize,
+    pub total_session_messages: usize,
+    /// Uuids of other matches in the session, when results were deduplicated
+    pub more_in_session: Vec<String>,
+    /// Uuids of matches with nearly the same content, when they were collapsed
+    pub near_duplicates: Vec<String>,
+}
+
+/// Options for what to include in search result display
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    pub include_thinking: bool,
+    pub include_tools: bool,
+    /// Characters shown per message (0 = full content)
+    pub truncate_length: usize,
+    /// Characters per snippet of the matched message (0 = `truncate_length`)
+    pub snippet_length: usize,
+    /// Separate regions of matches shown from the matched message
+    pub max_snippets: usize,
+}
+
+impl DisplayOptions {
+    fn snippet_chars(&self) -> usize {
+        if self.snippet_length == 0 {
+            self.truncate_length
+        } else {
+            self.snippet_length
+        }
+    }
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            include_thinking: false,
+            include_tools: false,
+            truncate_length: 300,
+            snippet_length: 0,
+            max_snippets: 1,
+        }
+    }
+}
+
+/// Up to `max` windows of `length` characters around separate regions of
+/// `matches`, whitespace collapsed, with … where content was left out. None
+/// without matches or when the content fits in one window.
+pub fn match_snippets(
+    content: &str,
+    matches: &[MatchRange],
+    length: usize,
+    max: usize,
+) -> Option<String> {
+    let chars: Vec<char> = content.chars().collect();
+    if matches.is_empty() || max == 0 || length == 0 || chars.len() <= length {
+        return None;
+    }
+
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    for range in matches {
+        if windows
+            .last()
+            .is_some_and(|&(_, end)| range.char_end <= end)
+        {
+            continue;
+        }
+        if windows.len() == max {
+            break;
+        }
+        // Center the match, without running past the end or into the last window
+        let lead = length.saturating_sub(range.char_end - range.char_start) / 2;
+        let start = range
+            .char_start
+            .saturating_sub(lead)
+            .min(chars.len() - length)
+            .max(windows.last().map_or(0, |&(_, end)| end));
+        windows.push((start, (start + length).min(chars.len())));
+    }
+
+    let mut snippet = String::new();
+    let mut shown_to = 0;
+    for (start, end) in windows {
+        if start > shown_to {
+            snippet.push_str(if snippet.is_empty() { "…" } else { " … " });
+        }
+        let text: String = chars[start..end].iter().collect();
+        snippet.push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+        shown_to = end;
+    }
+    if shown_to < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+/// Notes on a message, one per line under it, marked apart from the
+/// conversation: `{indent}📝 Note: text`
+pub fn format_notes(notes: &[String], indent: &str) -> String {
+    notes
+        .iter()
+        .map(|note| format!("{}📝 Note: {}\n", indent, note))
+        .collect()
+}
+
+/// Filter content based on display options
+fn filter_content(s: &str, opts: &DisplayOptions) -> Option<String> {
+    // Check if content should be hidden
+    if !opts.include_thinking && s.starts_with("[thinking]") {
+        return None;
+    }
+    if !opts.include_tools
+        && (s.starts_with('[') && s.contains(']') && !s.starts_with("[result]"))
+        && !s.starts_with("[thinking]")
+    {
+        // Looks like a tool call [ToolName] {...}
+        if let Some(bracket_end) = s.find(']') {
+            let prefix = &s[1..bracket_end];
+            // Tool names are typically CamelCase or contain underscores/colons
+            if prefix
+                .chars()
+                .any(|c| c.is_uppercase() || c == '_' || c == ':')
+                && !prefix.contains(' ')
+            {
+                return None;
+            }
+        }
+    }
+    Some(s.to_string())
+}
+
+impl SearchResultWithContext {
+    /// Format as grep -C style output - compact and dense
+    /// Format: N. 📁 ~/path 🗒️ session (M msgs) 💬 msg_uuid
+    ///            User: content preview...
+    ///         »  AI: matched content...
+    ///            User: content...
+    pub fn format_compact(&self, index: usize) -> String {
+        self.format_compact_with_options(index, &DisplayOptions::default())
+    }
+
+    /// Format with display options
+    pub fn format_compact_with_options(&self, index: usize, opts: &DisplayOptions) -> String {
+        let mut output = String::new();
+
+        let project_path_full = &self.matched_message.project_path;
+        let project_path_display = self.matched_message.project_path_display();
+        let session_id = &self.matched_message.session_id;
+
+        let jsonl_path = session_jsonl_path(project_path_full, session_id).unwrap_or_default();
+        let jsonl_path_str = jsonl_path.to_string_lossy();
+
+        let short_session = short_uuid(session_id);
+        let short_msg = short_uuid(&self.matched_message.uuid);
+
+        let path_link = file_hyperlink(project_path_full, &project_path_display);
+        let session_link = file_hyperlink(&jsonl_path_str, short_session);
+
+        let host = self
+            .matched_message
+            .source_host
+            .as_ref()
+            .map(|h| format!(" 🖥️ {}", h))
+            .unwrap_or_default();
+        let branch = self
+            .matched_message
+            .git_branch
+            .as_ref()
+            .map(|b| format!(" 🌿 {}", b))
+            .unwrap_or_default();
+
+        output.push_str(&format!(
+            "{}. 📁 {}{}{} 🗒️ {}{} ({} msgs) 💬 {} 📅 {}\n",
+            index + 1,
+            path_link,
+            host,
+            branch,
+            session_link,
+            format_title(self.matched_message.session_title.as_deref()),
+            self.total_session_messages,
+            short_msg,
+            local_time(self.matched_message.timestamp).format("%Y-%m-%d %H:%M"),
+        ));
+
+        let mut tags = Vec::new();
+        tags.extend(self.matched_message.technologies.iter().take(3).cloned());
+        tags.extend(self.matched_message.code_languages.iter().take(2).cloned());
+        if self.matched_message.has_error {
+            tags.push("error".to_string());
+        }
+        if self.matched_message.has_attachments {
+            tags.push("attachments".to_string());
+        }
+        if !tags.is_empty() {
+            output.push_str(&format!("🎟️{}\n", tags.join(",")));
+        }
+
+        self.format_context_messages(&mut output, opts);
+        if !self.more_in_session.is_empty() {
+            let ids: Vec<_> = self.more_in_session.iter().map(|u| short_uuid(u)).collect();
+            output.push_str(&format!(
+                "   +{} more matches in this session: 💬 {}\n",
+                ids.len(),
+                ids.join(" ")
+            ));
+        }
+        if !self.near_duplicates.is_empty() {
+            let ids: Vec<_> = self.near_duplicates.iter().map(|u| short_uuid(u)).collect();
+            output.push_str(&format!(
+                "   +{} near-duplicates: 💬 {}\n",
+                ids.len(),
+                ids.join(" ")
+            ));
+        }
+        output
+    }
+
+    /// One object per result for `--format jsonl`: the match's metadata and its
+    /// context messages, filtered and truncated like the text output
+    pub fn to_json(&self, opts: &DisplayOptions) -> serde_json::Value {
+        let matched = &self.matched_message;
+        let context: Vec<_> = self
+            .context_messages
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| filter_content(&msg.content, opts).is_some())
+            .map(|(i, msg)| {
+                let content = if opts.truncate_length == 0 {
+                    msg.content.clone()
+                } else {
+                    self.shown_content(i, msg, opts)
+                };
+                serde_json::json!({
+                    "uuid": msg.uuid,
+                    "message_type": msg.message_type,
+                    "timestamp": msg.timestamp,
+                    "is_match": i == self.match_index,
+                    "content": content,
+                    "notes": msg.notes,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "uuid": matched.uuid,
+            "session_id": matched.session_id,
+            "session_title": matched.session_title,
+            "project": matched.project,
+            "project_path": matched.project_path,
+            "timestamp": matched.timestamp,
+            "message_type": matched.message_type,
+            "score": matched.score,
+            "source": matched.source,
+            "source_host": matched.source_host,
+            "source_root": matched.source_root,
+            "git_branch": matched.git_branch,
+            "technologies": matched.technologies,
+            "code_languages": matched.code_languages,
+            "has_error": matched.has_error,
+            "has_attachments": matched.has_attachments,
+            "session_messages": self.total_session_messages,
+            "matches": matched.matches,
+            "more_in_session": self.more_in_session,
+            "near_duplicates": self.near_duplicates,
+            "context": context,
+        })
+    }
+
+    /// A context message cut to size: snippets around the matches for the
+    /// matched message, otherwise its start
+    fn shown_content(&self, index: usize, msg: &SearchResult, opts: &DisplayOptions) -> String {
+        let matches = &self.matched_message.matches;
+        (index == self.match_index)
+            .then(|| {
+                match_snippets(
+                    &msg.content,
+                    matches,
+                    opts.snippet_chars(),
+                    opts.max_snippets,
+                )
+            })
+            .flatten()
+            .unwrap_or_else(|| truncate_content(&msg.content, opts.truncate_length, true))
+    }
+
+    fn format_context_messages(&self, output: &mut String, opts: &DisplayOptions) {
+        for (i, msg) in self.context_messages.iter().enumerate() {
+            // Filter content based on options
+            if filter_content(&msg.content, opts).is_none() {
+                continue;
+            }
+
+            let prefix = if i == self.match_index { "»  " } else { "   " };
+            let content = if opts.truncate_length == 0 {
+                msg.content.split_whitespace().collect::<Vec<_>>().join(" ")
+            } else {
+                self.shown_content(i, msg, opts)
+            };
+
+            output.push_str(&format!("{}{}: {}\n", prefix, msg.role_display(), content));
+            output.push_str(&format_notes(&msg.notes, "   "));
+        }
+    }
+
+    /// Format with more detail for verbose output
+    pub fn format_verbose(&self, index: usize) -> String {
+        let mut output = String::new();
+
+        output.push_str(&format!(
+            "{}. [{}] {} | {} | score: {:.2}\n",
+            index + 1,
+            self.matched_message.project,
+            local_time(self.matched_message.timestamp).format("%Y-%m-%d %H:%M"),
+            short_uuid(&self.matched_message.session_id),
+            self.matched_message.score,
+        ));
+        output.push_str(&format!(
+            "   {} msgs in session | uuid: {}\n",
+            self.total_session_messages,
+            short_uuid(&self.matched_message.uuid),
+        ));
+
+        // Metadata tags on one line
+        let mut tags = Vec::new();
+        if !self.matched_message.technologies.is_empty() {
+            tags.push(self.matched_message.technologies.join(","));
+        }
+        if !self.matched_message.code_languages.is_empty() {
+            tags.push(self.matched_message.code_languages.join(","));
+        }
+        if self.matched_message.has_code {
+            tags.push("code".to_string());
+        }
+        if self.matched_message.has_error {
+            tags.push("error".to_string());
+        }
+        if self.matched_message.has_attachments {
+            tags.push("attachments".to_string());
+        }
+        if !tags.is_empty() {
+            output.push_str(&format!("   tags: {}\n", tags.join(" ")));
+        }
+
+        // Context messages
+        let default_opts = DisplayOptions::default();
+        for (i, msg) in self.context_messages.iter().enumerate() {
+            let prefix = if i == self.match_index { ">> " } else { "   " };
+            let content = truncate_content(&msg.content, default_opts.truncate_length, true);
+            output.push_str(&format!("{}{}: {}\n", prefix, msg.role_display(), content));
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::indexer::{CONTENT_PREVIEW_CHARS, SearchIndexer};
+    use crate::shared::models::{ConversationEntry, MessageType};
+    use crate::shared::sessions::merge_continuations;
+    use crate::shared::utils::estimate_tokens;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn make_entry(
+        uuid: &str,
+        session_id: &str,
+        msg_type: MessageType,
+        content: &str,
+        seq: usize,
+    ) -> ConversationEntry {
+        ConversationEntry {
+            uuid: uuid.to_string(),
+            parent_uuid: None,
+            session_id: session_id.to_string(),
+            project_path: "/test/project".to_string(),
+            timestamp: Utc::now(),
+            message_type: msg_type,
+            content: content.to_string(),
+            model: None,
+            cwd: None,
+            sequence_num: seq,
+            is_sidechain: false,
+            agent_id: None,
+            source: CLAUDE_CODE_SOURCE.to_string(),
+            source_host: None,
+            source_root: None,
+            git_branch: None,
+            usage: None,
+            source_file: None,
+            source_offset: 0,
+            technologies: vec![],
+            has_code: false,
+            code_languages: vec![],
+            has_error: false,
+            has_attachments: false,
+            tools_mentioned: vec![],
+            tool_calls: vec![],
+            tool_errors: vec![],
+            files_touched: vec![],
+            files_modified: vec![],
+            shell_commands: vec![],
+        }
+    }
+
+    #[test]
+    fn test_get_session_messages_returns_all_indexed() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // Create 100 messages for a session
+        let session_id = "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee";
+        let entries: Vec<_> = (0..100)
+            .map(|i| {
+                let msg_type = if i % 2 == 0 {
+                    MessageType::User
+                } else {
+                    MessageType::Assistant
+                };
+                make_entry(
+                    &format!("uuid-{:04}", i),
+                    session_id,
+                    msg_type,
+                    &format!("Message {}", i),
+                    i,
+                )
+            })
+            .collect();
+
+        // Index them
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        // Retrieve with SearchEngine
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let messages = engine.get_session_messages(session_id).unwrap();
+
+        assert_eq!(
+            messages.len(),
+            100,
+            "Should retrieve all 100 indexed messages"
+        );
+    }
+
+    #[test]
+    fn test_get_session_messages_with_short_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_id = "12345678-abcd-efgh-ijkl-mnopqrstuvwx";
+        let entries = vec![
+            make_entry("uuid-1", session_id, MessageType::User, "Hello", 0),
+            make_entry("uuid-2", session_id, MessageType::Assistant, "Hi there", 1),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+
+        // Test with short ID (first 8 chars)
+        let messages = engine.get_session_messages("12345678").unwrap();
+        assert_eq!(
+            messages.len(),
+            2,
+            "Should find messages with short session ID"
+        );
+    }
+
+    fn make_entry_with_project(
+        uuid: &str,
+        session_id: &str,
+        msg_type: MessageType,
+        content: &str,
+        seq: usize,
+        project_name: &str,
+        cwd: &str,
+    ) -> ConversationEntry {
+        ConversationEntry {
+            uuid: uuid.to_string(),
+            parent_uuid: None,
+            session_id: session_id.to_string(),
+            project_path: project_name.to_string(),
+            timestamp: Utc::now(),
+            message_type: msg_type,
+            content: content.to_string(),
+            model: None,
+            cwd: Some(cwd.to_string()),
+            sequence_num: seq,
+            is_sidechain: false,
+            agent_id: None,
+            source: CLAUDE_CODE_SOURCE.to_string(),
+            source_host: None,
+            source_root: None,
+            git_branch: None,
+            usage: None,
+            source_file: None,
+            source_offset: 0,
+            technologies: vec![],
+            has_code: false,
+            code_languages: vec![],
+            has_error: false,
+            has_attachments: false,
+            tools_mentioned: vec![],
+            tool_calls: vec![],
+            tool_errors: vec![],
+            files_touched: vec![],
+            files_modified: vec![],
+            shell_commands: vec![],
+        }
+    }
+
+    #[test]
+    fn test_project_filter_with_full_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_id = "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee";
+        let entries = vec![
+            make_entry_with_project(
+                "uuid-1",
+                session_id,
+                MessageType::User,
+                "hello world",
+                0,
+                "my-cool-project",
+                "/home/user/GIT/my-cool-project",
+            ),
+            make_entry_with_project(
+                "uuid-2",
+                session_id,
+                MessageType::Assistant,
+                "hi there",
+                1,
+                "my-cool-project",
+                "/home/user/GIT/my-cool-project",
+            ),
+            make_entry_with_project(
+                "uuid-3",
+                session_id,
+                MessageType::User,
+                "other stuff",
+                2,
+                "other-project",
+                "/home/user/GIT/other-project",
+            ),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+
+        // Filter by full path (how users pass --project)
+        let results = engine
+            .search(SearchQuery {
+                text: "hello".to_string(),
+                limit: 10,
+                project_filters: vec!["/home/user/GIT/my-cool-project".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            results.len(),
+            1,
+            "Should find 1 result with full path project filter"
+        );
+        assert_eq!(results[0].uuid, "uuid-1");
+
+        // Filter by short project name
+        let results = engine
+            .search(SearchQuery {
+                text: "hello".to_string(),
+                limit: 10,
+                project_filters: vec!["my-cool-project".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            results.len(),
+            1,
+            "Should find 1 result with short project name filter"
+        );
+
+        // Filter should exclude non-matching projects
+        let results = engine
+            .search(SearchQuery {
+                text: "hello".to_string(),
+                limit: 10,
+                project_filters: vec!["other-project".to_string()],
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(results.len(), 0, "Should find 0 results for wrong project");
+    }
+
+    #[test]
+    fn test_project_substring_and_glob_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let entries: Vec<_> = [
+            ("uuid-1", "aaaaaaaa-1", "client-acme"),
+            ("uuid-2", "aaaaaaaa-1", "client-acme"),
+            ("uuid-3", "bbbbbbbb-2", "client-zeta"),
+            ("uuid-4", "cccccccc-3", "web-app"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (uuid, session, project))| {
+            let mut entry = make_entry(uuid, session, MessageType::User, "deploy script", i);
+            entry.project_path = project.to_string();
+            entry.cwd = Some(format!("/home/u/{}/src", project));
+            entry
+        })
+        .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let projects = engine.list_projects(None).unwrap();
+        assert_eq!(projects.len(), 3);
+        let acme = projects
+            .iter()
+            .find(|p| p.project == "client-acme")
+            .unwrap();
+        assert_eq!((acme.message_count, acme.session_count), (2, 1));
+        assert_eq!(engine.list_projects(Some("client-*")).unwrap().len(), 2);
+        assert_eq!(engine.list_projects(Some("ACME")).unwrap().len(), 1);
+
+        let search = |text: &str, project: Option<&str>| {
+            engine
+                .search(SearchQuery {
+                    text: text.to_string(),
+                    project_filters: project.into_iter().map(str::to_string).collect(),
+                    limit: 10,
+                    ..Default::default()
+                })
+                .unwrap()
+                .len()
+        };
+        assert_eq!(search("deploy", Some("client")), 3);
+        assert_eq!(search("deploy", Some("*-zeta")), 1);
+        assert_eq!(search("deploy", Some("nothing-*")), 0);
+        let several = engine
+            .search(SearchQuery {
+                text: "deploy".to_string(),
+                project_filters: vec!["zeta".to_string(), "web-*".to_string()],
+                limit: 10,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(several.len(), 2);
+        assert_eq!(search("project:client-*", None), 3);
+        assert_eq!(search("project:web", None), 1);
+        assert_eq!(search("deploy -project:acme", None), 2);
+        assert_eq!(search("project:nothing-*", None), 0);
+    }
+
+    #[test]
+    fn test_project_filter_get_all_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_id = "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee";
+        let entries = vec![
+            make_entry_with_project(
+                "uuid-1",
+                session_id,
+                MessageType::User,
+                "hello",
+                0,
+                "freeswitch-database_utils",
+                "/mnt/bcachefs/@home/user/GIT/freeswitch-database_utils",
+            ),
+            make_entry_with_project(
+                "uuid-2",
+                session_id,
+                MessageType::User,
+                "world",
+                1,
+                "claude-conversation-search-mcp",
+                "/mnt/bcachefs/@home/user/GIT/claude-conversation-search-mcp",
+            ),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+
+        let results = engine
+            .get_all_documents(
+                Some("/mnt/bcachefs/@home/user/GIT/freeswitch-database_utils".to_string()),
+                10,
+            )
+            .unwrap();
+
+        assert_eq!(
+            results.len(),
+            1,
+            "Should find 1 document for freeswitch-database_utils"
+        );
+    }
+
+    #[test]
+    fn test_session_filter_with_full_uuid() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_a = "aaaaaaaa-1111-2222-3333-444444444444";
+        let session_b = "bbbbbbbb-5555-6666-7777-888888888888";
+        // Same hyphen segments as session A, in another order
+        let session_c = "aaaaaaaa-2222-1111-3333-444444444444";
+        let entries = vec![
+            make_entry("uuid-1", session_a, MessageType::User, "hello world", 0),
+            make_entry("uuid-2", session_b, MessageType::User, "hello world", 0),
+            make_entry("uuid-3", session_c, MessageType::User, "hello world", 0),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+
+        // Full session ID
+        let results = engine
+            .search(SearchQuery {
+                text: "hello".to_string(),
+                limit: 10,
+                session_filter: Some(session_a.to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(results.len(), 1, "Should find 1 result for session A");
+        assert_eq!(results[0].uuid, "uuid-1");
+
+        // Short prefix shared by sessions A and C
+        let error = engine
+            .search(SearchQuery {
+                text: "hello".to_string(),
+                limit: 10,
+                session_filter: Some("aaaaaaaa".to_string()),
+                ..Default::default()
+            })
+            .unwrap_err();
+        let ambiguous = error
+            .downcast::<AmbiguousId>()
+            .expect("Ambiguous prefix should be refused");
+        assert_eq!(ambiguous.candidates, vec![session_a, session_c]);
+        let tool_error = crate::mcp::tool_error::ToolError::from(ambiguous);
+        assert_eq!(tool_error.code, "invalid_argument");
+        assert_eq!(
+            tool_error.retry_with.unwrap()["candidates"],
+            serde_json::json!([session_a, session_c])
+        );
+
+        // Short prefix of one session
+        let results = engine
+            .search(SearchQuery {
+                text: "hello".to_string(),
+                limit: 10,
+                session_filter: Some("bbbbbbbb".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            results.len(),
+            1,
+            "Should find 1 result with short session prefix"
+        );
+        assert_eq!(results[0].uuid, "uuid-2");
+    }
+
+    #[test]
+    fn test_get_session_messages_by_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_id = "aabbccdd-1122-3344-5566-778899001122";
+        let entries = vec![
+            make_entry("uuid-1", session_id, MessageType::User, "first", 0),
+            make_entry("uuid-2", session_id, MessageType::Assistant, "second", 1),
+            make_entry("uuid-3", session_id, MessageType::User, "third", 2),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+
+        // Full ID
+        let messages = engine.get_session_messages(session_id).unwrap();
+        assert_eq!(messages.len(), 3);
+
+        // Short prefix
+        let messages = engine.get_session_messages("aabbccdd").unwrap();
+        assert_eq!(
+            messages.len(),
+            3,
+            "Should find all messages with short prefix"
+        );
+
+        // Non-matching prefix
+        let messages = engine.get_session_messages("xxxxxxxx").unwrap();
+        assert_eq!(
+            messages.len(),
+            0,
+            "Should find no messages for wrong prefix"
+        );
+    }
+
+    #[test]
+    fn test_resolve_ids_by_unique_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_a = "abcd1111-1122-3344-5566-778899001122";
+        let session_b = "abcd2222-1122-3344-5566-778899001122";
+        let session_c = "abce3333-1122-3344-5566-778899001122";
+        let entries = vec![
+            make_entry("f00d0001-aaaa", session_a, MessageType::User, "one", 0),
+            make_entry("f00d0002-bbbb", session_b, MessageType::User, "two", 0),
+            make_entry("beef0003-cccc", session_c, MessageType::User, "three", 0),
+        ];
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        assert_eq!(
+            engine.resolve_session_id("abcd2").unwrap().as_deref(),
+            Some(session_b),
+            "Prefixes shorter than a uuid segment resolve"
+        );
+        assert_eq!(engine.get_session_messages("abce").unwrap().len(), 1);
+        assert_eq!(engine.resolve_session_id("ffff").unwrap(), None);
+
+        let error = engine.get_session_messages("abcd").unwrap_err();
+        let ambiguous = error.downcast_ref::<AmbiguousId>().unwrap();
+        assert_eq!(ambiguous.candidates, vec![session_a, session_b]);
+        assert!(error.to_string().contains("Ambiguous session id 'abcd'"));
+
+        assert!(engine.get_messages_by_uuid(&["f00d".to_string()]).is_err());
+        let messages = engine
+            .get_messages_by_uuid(&["f00d0002".to_string(), "beef".to_string()])
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].uuid, "beef0003-cccc");
+
+        // A deleted session no longer makes its prefix ambiguous
+        indexer.delete_session(session_a).unwrap();
+        indexer.commit().unwrap();
+        drop(indexer);
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        assert_eq!(
+            engine.resolve_session_id("abcd").unwrap().as_deref(),
+            Some(session_b)
+        );
+    }
+
+    #[test]
+    fn test_reindexing_upserts_by_uuid() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // Same first uuid segment, different sessions
+        let session_a = "abcd1111-1122-3344-5566-778899001122";
+        let session_b = "abcd1111-9999-3344-5566-778899001122";
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer
+            .index_conversations(vec![
+                make_entry("msg-a", session_a, MessageType::User, "first draft", 0),
+                make_entry("msg-b", session_b, MessageType::User, "other session", 0),
+            ])
+            .unwrap();
+        indexer
+            .index_conversations(vec![make_entry(
+                "msg-a",
+                session_a,
+                MessageType::User,
+                "second draft",
+                0,
+            )])
+            .unwrap();
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        assert_eq!(
+            engine.reader.searcher().num_docs(),
+            2,
+            "No duplicate of msg-a"
+        );
+        let messages = engine.get_messages_by_uuid(&["msg-a".to_string()]).unwrap();
+        assert_eq!(messages[0].content, "second draft");
+
+        indexer.delete_session(session_a).unwrap();
+        indexer.commit().unwrap();
+        drop(indexer);
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        assert!(engine.get_session_messages(session_a).unwrap().is_empty());
+        assert_eq!(engine.get_session_messages(session_b).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_deleted_jsonl_falls_back_to_the_preview() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index");
+        let content = "word ".repeat(200);
+        let mut entry = make_entry("msg-a", "s", MessageType::User, &content, 0);
+        entry.source_file = Some(temp_dir.path().join("gone.jsonl").display().to_string());
+        let mut indexer = SearchIndexer::new(&index_path).unwrap();
+        indexer.index_conversations(vec![entry]).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(&index_path, HashMap::new()).unwrap();
+        let messages = engine.get_messages_by_uuid(&["msg-a".to_string()]).unwrap();
+        assert_eq!(messages[0].content.chars().count(), CONTENT_PREVIEW_CHARS);
+        assert!(messages[0].content.starts_with("word word"));
+        assert!(messages[0].content.ends_with('…'));
+    }
+
+    #[test]
+    fn test_match_snippets_show_separate_regions() {
+        let filler = "lorem ipsum ".repeat(20);
+        let content = format!("alpha {filler}beta {filler}gamma {filler}end");
+        let matches =
+            crate::shared::match_ranges(&content, &crate::shared::query_terms("alpha beta gamma"));
+
+        let snippet = match_snippets(&content, &matches, 40, 3).unwrap();
+        assert_eq!(snippet.matches(" … ").count(), 2, "{snippet}");
+        assert!(snippet.starts_with("alpha") && snippet.ends_with('…'));
+        assert!(snippet.contains("beta") && snippet.contains("gamma"));
+
+        let snippet = match_snippets(&content, &matches, 40, 2).unwrap();
+        assert!(!snippet.contains("gamma"), "Limited to two regions");
+        assert_eq!(match_snippets("short alpha", &matches[..1], 40, 3), None);
+        assert_eq!(match_snippets(&content, &[], 40, 3), None);
+    }
+
+    #[test]
+    fn test_date_sorted_results_group_by_day() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // Noon UTC stays on the same day in any display timezone
+        let entries: Vec<_> = ["2025-06-10", "2025-06-11", "2025-06-11"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, day)| {
+                let mut entry = make_entry(
+                    &format!("uuid-{}", i),
+                    &format!("session-{}", i),
+                    MessageType::User,
+                    "deploy",
+                    0,
+                );
+                entry.timestamp = format!("{}T12:00:00Z", day).parse().unwrap();
+                entry
+            })
+            .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let search = |sort_by| {
+            let query = SearchQuery {
+                text: "deploy".to_string(),
+                limit: 10,
+                sort_by,
+                ..Default::default()
+            };
+            let results = engine.search_with_context(query, 0, 0).unwrap();
+            format_results(&results, &DisplayOptions::default(), sort_by)
+        };
+        let journal = search(SortOrder::DateDesc);
+        let newer = journal.find("── 2025-06-11 (2 results) ──").unwrap();
+        let older = journal.find("── 2025-06-10 (1 result) ──").unwrap();
+        assert!(newer < older);
+        assert_eq!(journal.matches("──").count(), 4);
+        assert!(!search(SortOrder::Relevance).contains("──"));
+    }
+
+    #[test]
+    fn test_dedupe_by_session_lists_other_matches() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_a = "aaaaaaaa-1111-2222-3333-444444444444";
+        let session_b = "bbbbbbbb-5555-6666-7777-888888888888";
+        let entries = vec![
+            make_entry("uuid-1", session_a, MessageType::User, "deploy the api", 0),
+            make_entry("uuid-2", session_a, MessageType::User, "deploy failed", 1),
+            make_entry("uuid-3", session_b, MessageType::User, "deploy docs", 0),
+            make_entry("uuid-4", session_a, MessageType::User, "deploy again", 2),
+        ];
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let query = SearchQuery {
+            text: "deploy".to_string(),
+            limit: 10,
+            sort_by: SortOrder::DateAsc,
+            ..Default::default()
+        };
+        let results = engine.search_with_context(query, 0, 0).unwrap();
+        assert_eq!(results.len(), 4);
+
+        let deduped = dedupe_by_session(results);
+        assert_eq!(deduped.len(), 2);
+        let first_a = deduped
+            .iter()
+            .find(|r| r.matched_message.session_id == session_a)
+            .unwrap();
+        assert_eq!(first_a.more_in_session.len(), 2);
+        assert!(
+            first_a
+                .format_compact(0)
+                .contains("+2 more matches in this session")
+        );
+        let only_b = deduped
+            .iter()
+            .find(|r| r.matched_message.session_id == session_b)
+            .unwrap();
+        assert!(only_b.more_in_session.is_empty());
+    }
+
+    #[test]
+    fn test_collapse_near_duplicates_lists_the_others() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let answer = "Raise proxy_read_timeout in the nginx location block to 300 seconds \
+                      and reload nginx so the slow upstream has time to answer";
+        let entries = vec![
+            make_entry("uuid-1", "session-a", MessageType::Assistant, answer, 0),
+            make_entry("uuid-2", "session-b", MessageType::Assistant, answer, 0),
+            make_entry(
+                "uuid-3",
+                "session-c",
+                MessageType::Assistant,
+                &format!("{}.", answer.replace("and reload", "and then reload")),
+                0,
+            ),
+            make_entry(
+                "uuid-4",
+                "session-d",
+                MessageType::Assistant,
+                "nginx returns 502 because the upstream socket path is wrong",
+                0,
+            ),
+        ];
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let query = SearchQuery {
+            text: "nginx".to_string(),
+            limit: 10,
+            sort_by: SortOrder::DateAsc,
+            ..Default::default()
+        };
+        let results = engine.search_with_context(query, 0, 0).unwrap();
+        assert_eq!(results.len(), 4);
+
+        let collapsed = collapse_near_duplicates(results);
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed[0].matched_message.uuid, "uuid-1");
+        assert_eq!(collapsed[0].near_duplicates, vec!["uuid-2", "uuid-3"]);
+        assert!(
+            collapsed[0]
+                .format_compact(0)
+                .contains("+2 near-duplicates")
+        );
+        assert!(collapsed[1].near_duplicates.is_empty());
+    }
+
+    #[test]
+    fn test_search_facets_count_every_filtered_hit() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let entries: Vec<_> = [
+            ("uuid-1", "vault-rs", "2025-11-03T10:00:00Z"),
+            ("uuid-2", "vault-rs", "2025-12-01T10:00:00Z"),
+            ("uuid-3", "vault-rs", "2025-12-20T10:00:00Z"),
+            ("uuid-4", "web-app", "2025-12-21T10:00:00Z"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (uuid, project, timestamp))| {
+            let mut entry = make_entry(uuid, "aaaaaaaa-1", MessageType::User, "retry logic", i);
+            entry.project_path = project.to_string();
+            entry.timestamp = timestamp.parse().unwrap();
+            entry
+        })
+        .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let query = SearchQuery {
+            text: "retry".to_string(),
+            limit: 1,
+            ..Default::default()
+        };
+        let facets = engine.search_facets(&query).unwrap();
+        assert_eq!(facets.hits, 4);
+        assert_eq!(facets.months["2025-12"], 3);
+        assert_eq!(facets.months["2025-11"], 1);
+        assert_eq!(facets.projects["vault-rs"], 3);
+        assert_eq!(
+            format_search_facets(&facets),
+            "📊 4 hits 📅 2025-12 3, 2025-11 1 📁 vault-rs 3, web-app 1\n"
+        );
+
+        let filtered = engine
+            .search_facets(&SearchQuery {
+                project_filters: vec!["web-app".to_string()],
+                after: Some("2025-12-01T00:00:00Z".parse().unwrap()),
+                ..query
+            })
+            .unwrap();
+        assert_eq!(filtered.hits, 1);
+        assert_eq!(filtered.projects["web-app"], 1);
+    }
+
+    #[test]
+    fn test_suggest_queries_and_terms_from_the_vocabulary() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let entries: Vec<_> = [
+            "configure the nginx reverse proxy",
+            "nginx keeps returning 502",
+            "switch the runtime to io_uring",
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, content)| {
+            make_entry(
+                &format!("uuid-{}", i),
+                "aaaaaaaa-1",
+                MessageType::User,
+                content,
+                i,
+            )
+        })
+        .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let suggest = |text: &str| {
+            engine
+                .suggest_queries(&SearchQuery {
+                    text: text.to_string(),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+
+        // A swapped pair of letters is one edit
+        let typo = suggest("ngnix");
+        assert_eq!(typo[0].query, "nginx");
+        assert_eq!(typo[0].hits, 2);
+        assert_eq!(
+            format_query_suggestions(&typo),
+            "Did you mean:\n  🔎 \"nginx\" (2 hits)\n"
+        );
+
+        // The phrase misses, its known part alone doesn't
+        let compound = suggest("tokio-uring");
+        assert_eq!(compound.len(), 1);
+        assert_eq!(compound[0].query, "uring");
+        assert!(suggest("zeppelin").is_empty());
+
+        let terms = engine.suggest_terms("R", 10).unwrap();
+        assert_eq!(
+            terms,
+            vec![
+                ("returning".to_string(), 1),
+                ("reverse".to_string(), 1),
+                ("runtime".to_string(), 1)
+            ]
+        );
+        assert_eq!(engine.suggest_terms("ngi", 1).unwrap()[0].1, 2);
+        assert_eq!(
+            format_term_suggestions("ngi", &engine.suggest_terms("ngi", 1).unwrap()),
+            "nginx 2 msgs\n"
+        );
+        assert!(engine.suggest_terms("zz", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_recency_boost_ranks_recent_matches_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let mut old = make_entry(
+            "uuid-old",
+            "aaaaaaaa-1",
+            MessageType::User,
+            "flaky test flaky test flaky test",
+            0,
+        );
+        old.timestamp = Utc::now() - chrono::Duration::days(365);
+        let recent = make_entry(
+            "uuid-new",
+            "bbbbbbbb-2",
+            MessageType::User,
+            "flaky test in the login suite, maybe a timing issue",
+            0,
+        );
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(vec![old, recent]).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let search = |half_life: Option<f64>| {
+            engine
+                .search(SearchQuery {
+                    text: "flaky".to_string(),
+                    limit: 10,
+                    recency_half_life_days: half_life,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        assert_eq!(search(None)[0].uuid, "uuid-old");
+        let boosted = search(Some(30.0));
+        assert_eq!(boosted[0].uuid, "uuid-new");
+        assert!(boosted[1].score < search(None)[0].score / 1000.0);
+    }
+
+    #[test]
+    fn test_date_sort_ranks_every_match_not_just_the_most_relevant() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // The most relevant matches are the oldest
+        let entries = (0..20)
+            .map(|day| {
+                let text = if day < 10 {
+                    "cache cache cache"
+                } else {
+                    "a cache miss"
+                };
+                let mut entry = make_entry(
+                    &format!("uuid-{day:02}"),
+                    "aaaaaaaa-1",
+                    MessageType::User,
+                    text,
+                    day,
+                );
+                entry.timestamp = Utc::now() - chrono::Duration::days(20 - day as i64);
+                entry
+            })
+            .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let search = |sort_by: SortOrder| -> Vec<String> {
+            engine
+                .search(SearchQuery {
+                    text: "cache".to_string(),
+                    limit: 3,
+                    sort_by,
+                    ..Default::default()
+                })
+                .unwrap()
+                .into_iter()
+                .map(|r| r.uuid)
+                .collect()
+        };
+        assert_eq!(
+            search(SortOrder::DateDesc),
+            ["uuid-19", "uuid-18", "uuid-17"]
+        );
+        assert_eq!(
+            search(SortOrder::DateAsc),
+            ["uuid-00", "uuid-01", "uuid-02"]
+        );
+    }
+
+    #[test]
+    fn test_date_range_filters_before_the_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // Whole seconds, as stored timestamps have no sub-millisecond part
+        let start = DateTime::from_timestamp(Utc::now().timestamp() - 30 * 86_400, 0).unwrap();
+        let entries = (0..20)
+            .map(|day| {
+                // Older messages match better
+                let text = if day < 15 {
+                    "deploy deploy deploy"
+                } else {
+                    "one deploy"
+                };
+                let mut entry = make_entry(
+                    &format!("uuid-{day:02}"),
+                    "aaaaaaaa-1",
+                    MessageType::User,
+                    text,
+                    day,
+                );
+                entry.timestamp = start + chrono::Duration::days(day as i64);
+                entry
+            })
+            .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let results = engine
+            .search(SearchQuery {
+                text: "deploy".to_string(),
+                limit: 3,
+                after: Some(start + chrono::Duration::days(15)),
+                before: Some(start + chrono::Duration::days(17)),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut uuids: Vec<_> = results.into_iter().map(|r| r.uuid).collect();
+        uuids.sort();
+        assert_eq!(uuids, ["uuid-15", "uuid-16", "uuid-17"]);
+    }
+
+    #[test]
+    fn test_decisions_filter_by_project_and_date() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let start = DateTime::from_timestamp(Utc::now().timestamp() - 10 * 86_400, 0).unwrap();
+        let entry = |uuid: &str, project: &str, day: i64| {
+            let mut entry = make_entry_with_project(
+                uuid,
+                &format!("{project}-session"),
+                MessageType::Assistant,
+                "We'll go with the queue.",
+                day as usize,
+                project,
+                &format!("/work/{project}"),
+            );
+            entry.timestamp = start + chrono::Duration::days(day);
+            entry
+        };
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer
+            .index_conversations(vec![
+                entry("uuid-old", "alpha", 0),
+                entry("uuid-new", "alpha", 5),
+                entry("uuid-other", "beta", 5),
+            ])
+            .unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let decisions = engine
+            .decisions(
+                None,
+                Some("alpha"),
+                Some(start + chrono::Duration::days(1)),
+                None,
+            )
+            .unwrap();
+        let uuids: Vec<_> = decisions.iter().map(|d| d.uuid.as_str()).collect();
+        assert_eq!(uuids, ["uuid-new"]);
+        assert_eq!(engine.decisions(None, None, None, None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_type_boosts_demote_tool_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let dump = make_entry(
+            "uuid-dump",
+            "aaaaaaaa-1",
+            MessageType::User,
+            "[result] src/a.rs: timeout timeout\nsrc/b.rs: timeout",
+            0,
+        );
+        let question = make_entry(
+            "uuid-user",
+            "aaaaaaaa-1",
+            MessageType::User,
+            "why does the request timeout after a minute when the proxy is on",
+            1,
+        );
+        assert_eq!(dump.kind(), "tool_result");
+        assert_eq!(question.kind(), "user");
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(vec![dump, question]).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let search = |type_boosts: HashMap<String, f32>| {
+            engine
+                .search(SearchQuery {
+                    text: "timeout".to_string(),
+                    limit: 10,
+                    type_boosts,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        assert_eq!(search(HashMap::new())[0].uuid, "uuid-dump");
+        let boosts = HashMap::from([("user".to_string(), 2.0), ("tool_result".to_string(), 0.3)]);
+        assert_eq!(search(boosts)[0].uuid, "uuid-user");
+    }
+
+    #[test]
+    fn test_displayable_count_matches_retrieval() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_id = "testtest-1234-5678-abcd-ef0123456789";
+        let entries = vec![
+            make_entry("uuid-1", session_id, MessageType::User, "User message", 0),
+            make_entry(
+                "uuid-2",
+                session_id,
+                MessageType::Assistant,
+                "Assistant message",
+                1,
+            ),
+            make_entry(
+                "uuid-3",
+                session_id,
+                MessageType::System,
+                "System message",
+                2,
+            ),
+            make_entry("uuid-4", session_id, MessageType::Summary, "Summary", 3),
+            make_entry("uuid-5", session_id, MessageType::User, "Warmup", 4), // Should be filtered
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let messages = engine.get_session_messages(session_id).unwrap();
+
+        // Count displayable
+        let displayable_count = messages.iter().filter(|m| m.is_displayable()).count();
+        // User, Assistant, Summary are displayable; System is not; "Warmup" content filtered
+        assert_eq!(
+            displayable_count, 3,
+            "Should have 3 displayable messages (User, Assistant, Summary)"
+        );
+    }
+
+    #[test]
+    fn test_continuations_link_sessions_through_summary_leaves() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+        let start = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 3, 2, 9, 0, 0).unwrap();
+        let at = |entry: ConversationEntry, hours: i64| ConversationEntry {
+            timestamp: start + chrono::TimeDelta::hours(hours),
+            ..entry
+        };
+        // b picks up a after compaction, c picks up b; d is unrelated
+        let summary = |uuid: &str, session_id: &str, leaf: &str, hours: i64| {
+            let mut entry = make_entry(uuid, session_id, MessageType::Summary, "Earlier work", 0);
+            entry.parent_uuid = Some(leaf.to_string());
+            at(entry, hours)
+        };
+        let entries = vec![
+            at(
+                make_entry("a-1", "aaaa", MessageType::User, "start here", 0),
+                0,
+            ),
+            summary("b-0", "bbbb", "a-1", 1),
+            at(make_entry("b-1", "bbbb", MessageType::User, "go on", 1), 1),
+            summary("c-0", "cccc", "b-1", 2),
+            at(make_entry("c-1", "cccc", MessageType::User, "finish", 1), 2),
+            at(make_entry("d-1", "dddd", MessageType::User, "other", 0), 0),
+            // A summary of a session that had not ended yet is no continuation
+            summary("d-0", "dddd", "c-1", 0),
+        ];
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let links = engine.session_continuations().unwrap();
+        assert_eq!(
+            links,
+            [
+                ("bbbb".to_string(), "aaaa".to_string()),
+                ("cccc".to_string(), "bbbb".to_string())
+            ]
+        );
+        assert_eq!(
+            engine.conversation_chain("bbbb").unwrap(),
+            ["aaaa", "bbbb", "cccc"]
+        );
+        assert_eq!(engine.conversation_chain("dddd").unwrap(), ["dddd"]);
+
+        let merged = merge_continuations(engine.list_sessions(None).unwrap(), &links);
+        assert_eq!(merged.len(), 2);
+        let chain = merged.iter().find(|s| s.session_id == "aaaa").unwrap();
+        assert_eq!(chain.continued_in, ["bbbb", "cccc"]);
+        assert_eq!(chain.message_count, 3);
+        assert_eq!(chain.last_timestamp, start + chrono::TimeDelta::hours(2));
+    }
+
+    #[test]
+    fn test_session_titles_prefer_summaries_then_substantive_messages() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_a = "aaaaaaaa-1111-2222-3333-444444444444";
+        let session_b = "bbbbbbbb-5555-6666-7777-888888888888";
+        let session_c = "cccccccc-9999-0000-1111-222222222222";
+        let entries = vec![
+            make_entry("uuid-1", session_a, MessageType::User, "[result] ok", 0),
+            make_entry("uuid-2", session_a, MessageType::User, "yes", 1),
+            make_entry(
+                "uuid-3",
+                session_a,
+                MessageType::Assistant,
+                "Happy to help with anything",
+                2,
+            ),
+            make_entry(
+                "uuid-4",
+                session_a,
+                MessageType::User,
+                "Why does the deploy pipeline\n  fail on main?",
+                3,
+            ),
+            make_entry("uuid-5", session_b, MessageType::User, "continue", 0),
+            make_entry(
+                "uuid-6",
+                session_c,
+                MessageType::User,
+                "Please look at the flaky login test",
+                0,
+            ),
+            make_entry("uuid-7", session_c, MessageType::Summary, "Login test", 1),
+            make_entry(
+                "uuid-8",
+                session_c,
+                MessageType::Summary,
+                "Flaky login test race",
+                2,
+            ),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let titles = engine.session_titles(None).unwrap();
+        assert_eq!(
+            titles.get(session_c).map(String::as_str),
+            Some("Flaky login test race"),
+            "The latest summary wins over user messages"
+        );
+        assert_eq!(
+            titles.get(session_a).map(String::as_str),
+            Some("Why does the deploy pipeline fail on main?")
+        );
+        assert!(!titles.contains_key(session_b));
+        assert_eq!(
+            engine
+                .session_titles(Some(&[session_b.to_string()]))
+                .unwrap()
+                .len(),
+            0
+        );
+    }
 
-            output.push_str(&format!("{}{}: {}\n", prefix, msg.role_display(), content));
-        }
+    #[test]
+    fn test_list_sessions_aggregates_from_fast_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let session_a = "aaaaaaaa-1111-2222-3333-444444444444";
+        let session_b = "bbbbbbbb-5555-6666-7777-888888888888";
+        let entries = vec![
+            make_entry_with_project(
+                "uuid-1",
+                session_a,
+                MessageType::User,
+                "one",
+                0,
+                "alpha",
+                "/work/alpha",
+            ),
+            make_entry_with_project(
+                "uuid-2",
+                session_a,
+                MessageType::Assistant,
+                "two",
+                1,
+                "alpha",
+                "/work/alpha",
+            ),
+            make_entry_with_project(
+                "uuid-3",
+                session_a,
+                MessageType::System,
+                "sys",
+                2,
+                "alpha",
+                "/work/alpha",
+            ),
+            make_entry_with_project(
+                "uuid-4",
+                session_b,
+                MessageType::User,
+                "three",
+                0,
+                "beta",
+                "/work/beta",
+            ),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let mut sessions = engine.list_sessions(None).unwrap();
+        sessions.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, session_a);
+        assert_eq!(sessions[0].project_path, "/work/alpha");
+        assert_eq!(sessions[0].message_count, 2, "System messages not counted");
+
+        let filtered = engine.list_sessions(Some("beta")).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].session_id, session_b);
+
+        let by_id = engine
+            .sessions_by_id(&[session_a.to_string(), "cccccccc-0000".to_string()])
+            .unwrap();
+        assert_eq!(by_id.len(), 1);
+        assert_eq!(by_id[0].message_count, sessions[0].message_count);
+        assert_eq!(by_id[0].tokens, sessions[0].tokens);
+        assert!(by_id[0].tokens > 0);
     }
 
-    /// Format with more detail for verbose output
-    pub fn format_verbose(&self, index: usize) -> String {
-        let mut output = String::new();
+    #[test]
+    fn test_usage_report_groups_and_prices() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
 
-        output.push_str(&format!(
-            "{}. [{}] {} | {} | score: {:.2}\n",
-            index + 1,
-            self.matched_message.project,
-            self.matched_message.timestamp.format("%Y-%m-%d %H:%M"),
-            short_uuid(&self.matched_message.session_id),
-            self.matched_message.score,
-        ));
-        output.push_str(&format!(
-            "   {} msgs in session | uuid: {}\n",
-            self.total_session_messages,
-            short_uuid(&self.matched_message.uuid),
-        ));
+        let response = |uuid: &str, model: &str, cwd: &str, output_tokens: u64| {
+            let mut entry = make_entry_with_project(
+                uuid,
+                "aaaaaaaa-1",
+                MessageType::Assistant,
+                "done",
+                0,
+                "p",
+                cwd,
+            );
+            entry.model = Some(model.to_string());
+            entry.usage = Some(TokenUsage {
+                output_tokens,
+                ..Default::default()
+            });
+            entry
+        };
+        let mut unbilled = response("uuid-4", "claude-sonnet-4-5", "/work/alpha", 0);
+        unbilled.usage = None;
+        let entries = vec![
+            response("uuid-1", "claude-sonnet-4-5", "/work/alpha", 1_000_000),
+            response("uuid-2", "claude-sonnet-4-5", "/work/beta", 1_000_000),
+            response("uuid-3", "<synthetic>", "/work/alpha", 500),
+            unbilled,
+        ];
 
-        // Metadata tags on one line
-        let mut tags = Vec::new();
-        if !self.matched_message.technologies.is_empty() {
-            tags.push(self.matched_message.technologies.join(","));
-        }
-        if !self.matched_message.code_languages.is_empty() {
-            tags.push(self.matched_message.code_languages.join(","));
-        }
-        if self.matched_message.has_code {
-            tags.push("code".to_string());
-        }
-        if self.matched_message.has_error {
-            tags.push("error".to_string());
-        }
-        if !tags.is_empty() {
-            output.push_str(&format!("   tags: {}\n", tags.join(" ")));
-        }
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
 
-        // Context messages
-        let default_opts = DisplayOptions::default();
-        for (i, msg) in self.context_messages.iter().enumerate() {
-            let prefix = if i == self.match_index { ">> " } else { "   " };
-            let content = truncate_content(&msg.content, default_opts.truncate_length, true);
-            output.push_str(&format!("{}{}: {}\n", prefix, msg.role_display(), content));
-        }
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let prices = UsageConfig::default();
+        let report = engine.usage_report(None, None, None, &prices).unwrap();
+        assert_eq!(report.total.responses, 3);
+        assert_eq!(report.total.unpriced, 1);
+        assert!((report.total.cost_usd - 30.0).abs() < 1e-9);
+        assert_eq!(report.by_model["claude-sonnet-4-5"].responses, 2);
+        assert_eq!(
+            report.by_project["/work/alpha"].usage.output_tokens,
+            1_000_500
+        );
+        assert_eq!(report.by_day.len(), 1);
 
-        output
+        let beta = engine
+            .usage_report(Some("beta"), None, None, &prices)
+            .unwrap();
+        assert_eq!(beta.total.responses, 1);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::shared::indexer::SearchIndexer;
-    use crate::shared::models::{ConversationEntry, MessageType};
-    use chrono::Utc;
-    use tempfile::TempDir;
+    #[test]
+    fn test_tool_usage_counts_calls_and_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
 
-    fn make_entry(
-        uuid: &str,
-        session_id: &str,
-        msg_type: MessageType,
-        content: &str,
-        seq: usize,
-    ) -> ConversationEntry {
-        ConversationEntry {
-            uuid: uuid.to_string(),
-            parent_uuid: None,
-            session_id: session_id.to_string(),
-            project_path: "/test/project".to_string(),
-            timestamp: Utc::now(),
-            message_type: msg_type,
-            content: content.to_string(),
-            model: None,
-            cwd: None,
-            sequence_num: seq,
-            is_sidechain: false,
-            agent_id: None,
-            technologies: vec![],
-            has_code: false,
-            code_languages: vec![],
-            has_error: false,
-            tools_mentioned: vec![],
-        }
+        let entry = |uuid: &str, cwd: &str, calls: &[&str], errors: &[&str]| {
+            let mut entry = make_entry_with_project(
+                uuid,
+                "aaaaaaaa-1",
+                MessageType::Assistant,
+                "work",
+                0,
+                "p",
+                cwd,
+            );
+            entry.tool_calls = calls.iter().map(|s| s.to_string()).collect();
+            entry.tool_errors = errors.iter().map(|s| s.to_string()).collect();
+            entry
+        };
+        let entries = vec![
+            entry("uuid-1", "/work/alpha", &["Bash", "Bash", "Read"], &[]),
+            entry("uuid-2", "/work/alpha", &[], &["Bash"]),
+            entry("uuid-3", "/work/beta", &["Edit"], &[]),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let report = engine.tool_usage(None, None, None).unwrap();
+        assert_eq!(report.total.calls, 4);
+        assert_eq!(report.total.errors, 1);
+        assert_eq!(report.by_tool["Bash"].calls, 2);
+        assert_eq!(report.by_tool["Bash"].failure_rate(), 50.0);
+        assert_eq!(report.by_project["/work/beta"]["Edit"].calls, 1);
+
+        let alpha = engine.tool_usage(Some("alpha"), None, None).unwrap();
+        assert!(!alpha.by_tool.contains_key("Edit"));
     }
 
     #[test]
-    fn test_get_session_messages_returns_all_indexed() {
+    fn test_file_path_matches() {
+        let path = "/home/me/app/src/shared/search.rs";
+        assert!(file_path_matches(path, path));
+        assert!(file_path_matches(path, "src/shared/search.rs"));
+        assert!(file_path_matches(path, "./search.rs"));
+        assert!(!file_path_matches(path, "arch.rs"));
+        assert!(!file_path_matches(path, "/other/src/shared/search.rs"));
+    }
+
+    #[test]
+    fn test_file_touches_finds_modifying_sessions() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        // Create 100 messages for a session
-        let session_id = "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee";
-        let entries: Vec<_> = (0..100)
+        let touch = |uuid: &str, session: &str, touched: &[&str], modified: &[&str]| {
+            let mut entry = make_entry_with_project(
+                uuid,
+                session,
+                MessageType::Assistant,
+                "working on it",
+                0,
+                "app",
+                "/home/me/app",
+            );
+            entry.files_touched = touched.iter().map(|s| s.to_string()).collect();
+            entry.files_modified = modified.iter().map(|s| s.to_string()).collect();
+            entry
+        };
+        let target = "/home/me/app/src/shared/search.rs";
+        let entries = vec![
+            touch("uuid-1", "aaaaaaaa-1", &[target], &[]),
+            touch(
+                "uuid-2",
+                "bbbbbbbb-2",
+                &["/home/me/app/README.md", target],
+                &[target],
+            ),
+            touch("uuid-3", "cccccccc-3", &["/home/me/app/src/main.rs"], &[]),
+        ];
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let all = engine
+            .file_touches("src/shared/search.rs", None, false)
+            .unwrap();
+        assert_eq!(all.len(), 2);
+
+        let modified = engine
+            .file_touches("src/shared/search.rs", None, true)
+            .unwrap();
+        assert_eq!(modified.len(), 1);
+        assert_eq!(modified[0].session_id, "bbbbbbbb-2");
+        assert_eq!(modified[0].uuid, "uuid-2");
+        assert_eq!(modified[0].path, target);
+    }
+
+    #[test]
+    fn test_aggregate_stats_counts_every_document() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // More docs than any search limit the old sampled stats used
+        let entries: Vec<_> = (0..2500)
             .map(|i| {
-                let msg_type = if i % 2 == 0 {
-                    MessageType::User
-                } else {
-                    MessageType::Assistant
-                };
-                make_entry(
-                    &format!("uuid-{:04}", i),
-                    session_id,
-                    msg_type,
-                    &format!("Message {}", i),
+                let mut entry = make_entry_with_project(
+                    &format!("uuid-{}", i),
+                    if i % 2 == 0 {
+                        "aaaaaaaa-1"
+                    } else {
+                        "bbbbbbbb-2"
+                    },
+                    MessageType::User,
+                    "fix the rust build",
                     i,
-                )
+                    "alpha",
+                    if i < 2000 {
+                        "/work/alpha"
+                    } else {
+                        "/work/beta"
+                    },
+                );
+                entry.has_code = i % 5 == 0;
+                entry.technologies = vec!["rust".to_string(), "cargo".to_string()];
+                entry
             })
             .collect();
 
-        // Index them
         let mut indexer = SearchIndexer::new(index_path).unwrap();
         indexer.index_conversations(entries).unwrap();
         drop(indexer);
 
-        // Retrieve with SearchEngine
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
-        let messages = engine.get_session_messages(session_id).unwrap();
-
+        let stats = engine.aggregate_stats(None, None).unwrap();
+        assert_eq!(stats.messages, 2500);
+        assert_eq!(stats.sessions.len(), 2);
+        assert_eq!(stats.with_code, 500);
+        assert_eq!(stats.words, 2500 * 4);
         assert_eq!(
-            messages.len(),
-            100,
-            "Should retrieve all 100 indexed messages"
+            stats.tokens,
+            2500 * estimate_tokens("fix the rust build") as u64
+        );
+        assert_eq!(
+            stats.session_tokens.values().sum::<u64>(),
+            stats.tokens,
+            "Session totals cover every message"
         );
+        assert_eq!(stats.technologies.get("cargo"), Some(&2500));
+        assert_eq!(stats.projects.get("/work/beta"), Some(&500));
+
+        let beta = engine.aggregate_stats(Some("beta"), None).unwrap();
+        assert_eq!(beta.messages, 500);
     }
 
     #[test]
-    fn test_get_session_messages_with_short_id() {
+    fn test_topic_report_counts_every_message_in_range() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        let session_id = "12345678-abcd-efgh-ijkl-mnopqrstuvwx";
-        let entries = vec![
-            make_entry("uuid-1", session_id, MessageType::User, "Hello", 0),
-            make_entry("uuid-2", session_id, MessageType::Assistant, "Hi there", 1),
-        ];
-
+        let entries: Vec<_> = (0..30)
+            .map(|i| {
+                let mut entry = make_entry(
+                    &format!("uuid-{}", i),
+                    "session",
+                    MessageType::User,
+                    "hi",
+                    i,
+                );
+                entry.timestamp = format!("2025-06-{:02}T12:00:00Z", i % 30 + 1)
+                    .parse()
+                    .unwrap();
+                entry.technologies = match i % 3 {
+                    0 => vec!["docker".to_string(), "nginx".to_string()],
+                    _ => vec!["docker".to_string()],
+                };
+                entry.tools_mentioned = vec!["kubectl".to_string()];
+                entry
+            })
+            .collect();
         let mut indexer = SearchIndexer::new(index_path).unwrap();
         indexer.index_conversations(entries).unwrap();
         drop(indexer);
 
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let report = engine.topic_report(None, None, None, None).unwrap();
+        assert_eq!(report.messages, 30);
+        assert_eq!(report.technologies["docker"], 30);
+        assert_eq!(report.technologies["nginx"], 10);
+        assert_eq!(report.tools["kubectl"], 30);
+
+        // June 1st to 10th holds messages 0 to 9
+        let day = |d: &str| Some(format!("2025-06-{}T12:00:00Z", d).parse().unwrap());
+        let report = engine
+            .topic_report(None, None, day("01"), day("10"))
+            .unwrap();
+        assert_eq!(report.messages, 10);
+        assert_eq!(report.technologies["nginx"], 4);
+        assert!(
+            engine
+                .topic_report(Some("elsewhere"), None, None, None)
+                .unwrap()
+                .projects
+                .is_empty()
+        );
+    }
 
-        // Test with short ID (first 8 chars)
-        let messages = engine.get_session_messages("12345678").unwrap();
+    #[test]
+    fn test_directory_breakdown_drills_into_subdirectories() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let entries: Vec<_> = [
+            "/home/me/mono",
+            "/home/me/mono",
+            "/home/me/mono/api",
+            "/home/me/mono/api",
+            "/home/me/mono/api",
+            "/home/me/mono/api/v2",
+            "/home/me/mono/web",
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, cwd)| {
+            make_entry_with_project(
+                &format!("uuid-{}", i),
+                "aaaaaaaa-1",
+                MessageType::User,
+                "deploy",
+                i,
+                "mono",
+                cwd,
+            )
+        })
+        .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        // Starts at the deepest directory every message shares
         assert_eq!(
-            messages.len(),
-            2,
-            "Should find messages with short session ID"
+            engine.directory_breakdown(None, None).unwrap(),
+            DirectoryBreakdown {
+                path: "/home/me/mono".to_string(),
+                here: 2,
+                children: vec![("api".to_string(), 4), ("web".to_string(), 1)],
+            }
         );
-    }
 
-    fn make_entry_with_project(
-        uuid: &str,
-        session_id: &str,
-        msg_type: MessageType,
-        content: &str,
-        seq: usize,
-        project_name: &str,
-        cwd: &str,
-    ) -> ConversationEntry {
-        ConversationEntry {
-            uuid: uuid.to_string(),
-            parent_uuid: None,
-            session_id: session_id.to_string(),
-            project_path: project_name.to_string(),
-            timestamp: Utc::now(),
-            message_type: msg_type,
-            content: content.to_string(),
-            model: None,
-            cwd: Some(cwd.to_string()),
-            sequence_num: seq,
-            is_sidechain: false,
-            agent_id: None,
-            technologies: vec![],
-            has_code: false,
-            code_languages: vec![],
-            has_error: false,
-            tools_mentioned: vec![],
-        }
+        let api = engine
+            .directory_breakdown(None, Some("/home/me/mono/api/"))
+            .unwrap();
+        assert_eq!((api.here, api.children), (3, vec![("v2".to_string(), 1)]));
+        let stats = engine
+            .aggregate_stats(None, Some("/home/me/mono/api"))
+            .unwrap();
+        assert_eq!(stats.messages, 4);
+        let results = engine
+            .search(SearchQuery {
+                text: "deploy".to_string(),
+                limit: 10,
+                dir_filter: Some("/home/me/mono/web".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(results.len(), 1);
     }
 
     #[test]
-    fn test_project_filter_with_full_path() {
+    fn test_session_active_between_searches_whole_sessions() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        let session_id = "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee";
-        let entries = vec![
-            make_entry_with_project(
-                "uuid-1",
-                session_id,
-                MessageType::User,
-                "hello world",
-                0,
-                "my-cool-project",
-                "/home/user/GIT/my-cool-project",
-            ),
-            make_entry_with_project(
-                "uuid-2",
-                session_id,
-                MessageType::Assistant,
-                "hi there",
-                1,
-                "my-cool-project",
-                "/home/user/GIT/my-cool-project",
-            ),
-            make_entry_with_project(
-                "uuid-3",
-                session_id,
-                MessageType::User,
-                "other stuff",
-                2,
-                "other-project",
-                "/home/user/GIT/other-project",
-            ),
-        ];
-
+        // The index keeps milliseconds
+        let now = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+        let day = |d: i64| now - chrono::Duration::days(30 - d);
+        // "sprint" spans days 1-5 with nothing on days 3-4; "later" is days 10-11
+        let entries: Vec<_> = [("sprint", 1), ("sprint", 5), ("later", 10), ("later", 11)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (session, d))| {
+                let mut entry = make_entry(
+                    &format!("uuid-{}", i),
+                    session,
+                    MessageType::User,
+                    "deploy",
+                    i,
+                );
+                entry.timestamp = day(d);
+                entry
+            })
+            .collect();
         let mut indexer = SearchIndexer::new(index_path).unwrap();
         indexer.index_conversations(entries).unwrap();
         drop(indexer);
 
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
-
-        // Filter by full path (how users pass --project)
-        let results = engine
+        let search = |after, before| {
+            engine
+                .search(SearchQuery {
+                    text: "deploy".to_string(),
+                    limit: 10,
+                    session_active_after: after,
+                    session_active_before: before,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        let results = search(Some(day(3)), Some(day(4)));
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.session_id == "sprint"));
+        assert_eq!(search(Some(day(5)), None).len(), 4);
+        assert!(search(Some(day(20)), None).is_empty());
+        // Per-message dates over the same window find nothing
+        let dated = engine
             .search(SearchQuery {
-                text: "hello".to_string(),
+                text: "deploy".to_string(),
                 limit: 10,
-                project_filter: Some("/home/user/GIT/my-cool-project".to_string()),
+                after: Some(day(3)),
+                before: Some(day(4)),
                 ..Default::default()
             })
             .unwrap();
+        assert!(dated.is_empty());
+    }
 
-        assert_eq!(
-            results.len(),
-            1,
-            "Should find 1 result with full path project filter"
-        );
-        assert_eq!(results[0].uuid, "uuid-1");
+    #[test]
+    fn test_branch_filter_and_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
 
-        // Filter by short project name
+        let entries: Vec<_> = [
+            ("uuid-1", Some("main")),
+            ("uuid-2", Some("feature/login")),
+            ("uuid-3", Some("feature/login")),
+            ("uuid-4", None),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (uuid, branch))| {
+            let mut entry = make_entry(uuid, "aaaaaaaa-1", MessageType::User, "oauth token", i);
+            entry.git_branch = branch.map(str::to_string);
+            entry
+        })
+        .collect();
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
         let results = engine
             .search(SearchQuery {
-                text: "hello".to_string(),
+                text: "oauth".to_string(),
                 limit: 10,
-                project_filter: Some("my-cool-project".to_string()),
+                branch_filter: Some("feature/login".to_string()),
                 ..Default::default()
             })
             .unwrap();
+        assert_eq!(results.len(), 2, "Slashes in branch names match exactly");
+        assert!(
+            results
+                .iter()
+                .all(|r| r.git_branch.as_deref() == Some("feature/login"))
+        );
 
+        let stats = engine.aggregate_stats(None, None).unwrap();
+        assert_eq!(stats.branches.get("feature/login"), Some(&2));
+        assert_eq!(stats.branches.get("main"), Some(&1));
         assert_eq!(
-            results.len(),
-            1,
-            "Should find 1 result with short project name filter"
+            stats.branches.len(),
+            2,
+            "Entries without a branch are not counted"
         );
+    }
 
-        // Filter should exclude non-matching projects
-        let results = engine
-            .search(SearchQuery {
-                text: "hello".to_string(),
-                limit: 10,
-                project_filter: Some("other-project".to_string()),
-                ..Default::default()
+    #[test]
+    fn test_search_each_streams_every_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        let entries: Vec<_> = (0..25)
+            .map(|i| {
+                let session = format!("aaaaaaaa-{}", i % 3);
+                make_entry(
+                    &format!("uuid-{i}"),
+                    &session,
+                    MessageType::User,
+                    "deploy",
+                    i,
+                )
+            })
+            .collect();
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let query = || SearchQuery {
+            text: "deploy".to_string(),
+            limit: 0,
+            session_filter: Some("aaaaaaaa-1".to_string()),
+            ..Default::default()
+        };
+        let mut streamed = Vec::new();
+        engine
+            .search_each_with_context(query(), 1, 1, |result| {
+                assert_eq!(result.matched_message.session_id, "aaaaaaaa-1");
+                assert!(result.context_messages.len() <= 3);
+                streamed.push(result.matched_message.uuid);
+                Ok(true)
             })
             .unwrap();
+        assert_eq!(streamed.len(), 8, "No limit applies, post-filters do");
 
-        assert_eq!(results.len(), 0, "Should find 0 results for wrong project");
+        let mut calls = 0;
+        engine
+            .search_each_with_context(query(), 0, 0, |_| {
+                calls += 1;
+                Ok(calls < 2)
+            })
+            .unwrap();
+        assert_eq!(calls, 2, "Returning false stops the scan");
     }
 
     #[test]
-    fn test_project_filter_get_all_documents() {
+    fn test_entity_fields_find_mentions() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        let session_id = "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee";
         let entries = vec![
-            make_entry_with_project(
+            make_entry(
                 "uuid-1",
-                session_id,
+                "aaaaaaaa-1",
                 MessageType::User,
-                "hello",
+                "PROJ-2291 fails in src/shared/search.rs, see https://github.com/org/repo/pull/12",
                 0,
-                "freeswitch-database_utils",
-                "/mnt/bcachefs/@home/user/GIT/freeswitch-database_utils",
             ),
-            make_entry_with_project(
+            make_entry(
                 "uuid-2",
-                session_id,
+                "aaaaaaaa-1",
                 MessageType::User,
-                "world",
+                "PROJ-229 and #456 mention search",
                 1,
-                "claude-conversation-search-mcp",
-                "/mnt/bcachefs/@home/user/GIT/claude-conversation-search-mcp",
             ),
         ];
-
         let mut indexer = SearchIndexer::new(index_path).unwrap();
         indexer.index_conversations(entries).unwrap();
         drop(indexer);
 
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
-
-        let results = engine
-            .get_all_documents(
-                Some("/mnt/bcachefs/@home/user/GIT/freeswitch-database_utils".to_string()),
-                10,
-            )
-            .unwrap();
-
-        assert_eq!(
-            results.len(),
-            1,
-            "Should find 1 document for freeswitch-database_utils"
-        );
+        let uuids = |text: &str| -> Vec<String> {
+            let query = SearchQuery {
+                text: text.to_string(),
+                limit: 10,
+                ..Default::default()
+            };
+            engine
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.uuid)
+                .collect()
+        };
+        assert_eq!(uuids("ticket:PROJ-2291"), vec!["uuid-1"]);
+        assert_eq!(uuids("ticket:#456"), vec!["uuid-2"]);
+        assert_eq!(uuids("url:github.com/org"), vec!["uuid-1"]);
+        assert_eq!(uuids("file:search.rs"), vec!["uuid-1"]);
+        assert!(uuids("url:search").is_empty());
     }
 
     #[test]
-    fn test_session_filter_with_full_uuid() {
+    fn test_field_scoped_query() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        let session_a = "aaaaaaaa-1111-2222-3333-444444444444";
-        let session_b = "bbbbbbbb-5555-6666-7777-888888888888";
-        let entries = vec![
-            make_entry("uuid-1", session_a, MessageType::User, "hello world", 0),
-            make_entry("uuid-2", session_b, MessageType::User, "hello world", 0),
-        ];
-
+        let mut rust_fix = make_entry("uuid-1", "aaaaaaaa-1", MessageType::Assistant, "fix", 0);
+        rust_fix.code_languages = vec!["rust".to_string()];
+        rust_fix.technologies = vec!["docker".to_string()];
+        rust_fix.cwd = Some("/home/me/work/infra/terraform/modules".to_string());
+        let mut failed = make_entry("uuid-2", "aaaaaaaa-1", MessageType::User, "fix", 1);
+        failed.has_error = true;
+        failed.cwd = Some("/home/me/work/infra-tools".to_string());
         let mut indexer = SearchIndexer::new(index_path).unwrap();
-        indexer.index_conversations(entries).unwrap();
+        indexer.index_conversations(vec![rust_fix, failed]).unwrap();
         drop(indexer);
 
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
-
-        // Full session ID
-        let results = engine
-            .search(SearchQuery {
-                text: "hello".to_string(),
-                limit: 10,
-                session_filter: Some(session_a.to_string()),
-                ..Default::default()
-            })
-            .unwrap();
-
-        assert_eq!(results.len(), 1, "Should find 1 result for session A");
-        assert_eq!(results[0].uuid, "uuid-1");
-
-        // Short prefix
-        let results = engine
-            .search(SearchQuery {
-                text: "hello".to_string(),
+        let uuids = |text: &str| -> Vec<String> {
+            let query = SearchQuery {
+                text: text.to_string(),
                 limit: 10,
-                session_filter: Some("aaaaaaaa".to_string()),
                 ..Default::default()
-            })
-            .unwrap();
-
-        assert_eq!(
-            results.len(),
-            1,
-            "Should find 1 result with short session prefix"
+            };
+            engine
+                .search(query)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.uuid)
+                .collect()
+        };
+        assert_eq!(uuids("fix AND lang:rust AND tech:docker"), vec!["uuid-1"]);
+        assert_eq!(uuids("fix AND has_error:true"), vec!["uuid-2"]);
+        // Whole directory names in order, not words inside them
+        assert_eq!(uuids("fix AND path:Infra/terraform"), vec!["uuid-1"]);
+        assert_eq!(uuids("fix AND path:infra-tools"), vec!["uuid-2"]);
+        assert!(uuids("fix AND path:tools").is_empty());
+        assert!(
+            engine
+                .search(SearchQuery {
+                    text: "language:rust".to_string(),
+                    limit: 10,
+                    ..Default::default()
+                })
+                .is_err(),
+            "Unknown fields are rejected"
         );
-        assert_eq!(results[0].uuid, "uuid-1");
     }
 
     #[test]
-    fn test_get_session_messages_by_prefix() {
+    fn test_command_runs_filters_and_orders() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        let session_id = "aabbccdd-1122-3344-5566-778899001122";
-        let entries = vec![
-            make_entry("uuid-1", session_id, MessageType::User, "first", 0),
-            make_entry("uuid-2", session_id, MessageType::Assistant, "second", 1),
-            make_entry("uuid-3", session_id, MessageType::User, "third", 2),
-        ];
+        let entries: Vec<_> = [
+            ("uuid-1", "kubectl get pods -n prod", Some(0)),
+            ("uuid-2", "ffmpeg -i in.mkv out.mp4", Some(1)),
+            ("uuid-3", "kubectl rollout restart deploy/api", None),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (uuid, command, exit_code))| {
+            let mut entry = make_entry(uuid, "aaaaaaaa-1", MessageType::Assistant, command, i);
+            entry.timestamp = Utc::now() + chrono::Duration::seconds(i as i64);
+            entry.tool_calls = vec!["Bash".to_string()];
+            entry.shell_commands = vec![ShellCommand {
+                tool_use_id: None,
+                command: command.to_string(),
+                exit_code,
+                failed: exit_code != Some(0),
+            }];
+            entry
+        })
+        .collect();
 
         let mut indexer = SearchIndexer::new(index_path).unwrap();
         indexer.index_conversations(entries).unwrap();
         drop(indexer);
 
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let runs = engine.command_runs(None, None, None).unwrap();
+        let uuids: Vec<_> = runs.iter().map(|r| r.uuid.as_str()).collect();
+        assert_eq!(uuids, vec!["uuid-1", "uuid-2", "uuid-3"], "Oldest first");
+        assert_eq!(runs[1].command.exit_code, Some(1));
 
-        // Full ID
-        let messages = engine.get_session_messages(session_id).unwrap();
-        assert_eq!(messages.len(), 3);
-
-        // Short prefix
-        let messages = engine.get_session_messages("aabbccdd").unwrap();
-        assert_eq!(
-            messages.len(),
-            3,
-            "Should find all messages with short prefix"
-        );
+        let kubectl = engine.command_runs(None, None, Some("KUBECTL")).unwrap();
+        assert_eq!(kubectl.len(), 2);
 
-        // Non-matching prefix
-        let messages = engine.get_session_messages("xxxxxxxx").unwrap();
-        assert_eq!(
-            messages.len(),
-            0,
-            "Should find no messages for wrong prefix"
-        );
+        let other_session = engine.command_runs(None, Some("bbbbbbbb"), None).unwrap();
+        assert!(other_session.is_empty());
     }
 
     #[test]
-    fn test_displayable_count_matches_retrieval() {
+    fn test_session_activity_collects_tools_files_and_commands() {
         let temp_dir = TempDir::new().unwrap();
         let index_path = temp_dir.path();
 
-        let session_id = "testtest-1234-5678-abcd-ef0123456789";
+        let session_a = "aaaaaaaa-1111-2222-3333-444444444444";
+        let session_b = "bbbbbbbb-5555-6666-7777-888888888888";
+        let mut edit = make_entry("a-2", session_a, MessageType::Assistant, "[Edit] {}", 1);
+        edit.tool_calls = vec!["Read".to_string(), "Edit".to_string()];
+        edit.files_touched = vec!["/src/my app.rs".to_string(), "/src/lib.rs".to_string()];
+        edit.files_modified = vec!["/src/my app.rs".to_string()];
+        let mut run = make_entry("a-3", session_a, MessageType::Assistant, "[Bash] {}", 2);
+        run.tool_calls = vec!["Bash".to_string()];
+        run.shell_commands = vec![ShellCommand {
+            tool_use_id: None,
+            command: "cargo test".to_string(),
+            exit_code: Some(101),
+            failed: true,
+        }];
+        let mut failure = make_entry("a-4", session_a, MessageType::User, "[error] exit 101", 3);
+        failure.tool_errors = vec!["Bash".to_string()];
+        failure.has_error = true;
         let entries = vec![
-            make_entry("uuid-1", session_id, MessageType::User, "User message", 0),
             make_entry(
-                "uuid-2",
-                session_id,
-                MessageType::Assistant,
-                "Assistant message",
-                1,
+                "a-1",
+                session_a,
+                MessageType::User,
+                "fix the flaky login test",
+                0,
             ),
+            edit,
+            run,
+            failure,
             make_entry(
-                "uuid-3",
-                session_id,
-                MessageType::System,
-                "System message",
-                2,
+                "b-1",
+                session_b,
+                MessageType::User,
+                "fix the flaky login test",
+                0,
             ),
-            make_entry("uuid-4", session_id, MessageType::Summary, "Summary", 3),
-            make_entry("uuid-5", session_id, MessageType::User, "Warmup", 4), // Should be filtered
+            make_entry(
+                "b-2",
+                session_b,
+                MessageType::User,
+                "pin the clock instead",
+                1,
+            ),
+            make_entry("b-3", session_b, MessageType::Assistant, "Pinned it.", 2),
         ];
-
         let mut indexer = SearchIndexer::new(index_path).unwrap();
         indexer.index_conversations(entries).unwrap();
         drop(indexer);
 
         let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
-        let messages = engine.get_session_messages(session_id).unwrap();
+        assert!(engine.session_activity("cccccccc").unwrap().is_none());
+        let a = engine.session_activity("aaaaaaaa").unwrap().unwrap();
+        assert_eq!(a.session_id, session_a);
+        assert_eq!(a.messages.len(), 4);
+        assert_eq!(a.tool_calls["Bash"], 1);
+        assert_eq!(a.tool_errors["Bash"], 1);
+        assert!(a.files_modified.contains("/src/my app.rs"));
+        assert_eq!(a.files_read.iter().collect::<Vec<_>>(), vec!["/src/lib.rs"]);
+        assert_eq!(a.commands[0].command, "cargo test");
+
+        let b = engine.session_activity(session_b).unwrap().unwrap();
+        let diff = crate::shared::format_session_diff(&a, &b);
+        assert!(diff.contains("1 shared, 0 only in A, 1 only in B"));
+        assert!(diff.contains("  B pin the clock instead"));
+        assert!(diff.contains("A only: cargo test ✗"));
+        assert!(diff.contains("A only: /src/my app.rs"));
+        assert!(diff.contains("⚠️ last tool result failed"));
+        assert!(diff.contains("Pinned it."));
+    }
 
-        // Count displayable
-        let displayable_count = messages.iter().filter(|m| m.is_displayable()).count();
-        // User, Assistant, Summary are displayable; System is not; "Warmup" content filtered
+    #[test]
+    fn test_error_clusters_group_and_resolve() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path();
+
+        // (uuid, session, content, called tools, failed tools)
+        let steps = [
+            ("a1", "aaaaaaaa-1", "[Bash] make", "Bash", ""),
+            (
+                "a2",
+                "aaaaaaaa-1",
+                "[error] Exit code 2\nmake: No rule to make target 'x.o'",
+                "",
+                "Bash",
+            ),
+            ("a3", "aaaaaaaa-1", "[Bash] make all", "Bash", ""),
+            ("a4", "aaaaaaaa-1", "[result] done", "", ""),
+            ("b1", "bbbbbbbb-2", "[Bash] make", "Bash", ""),
+            (
+                "b2",
+                "bbbbbbbb-2",
+                "[error] Exit code 2\nmake: No rule to make target 'y.o'",
+                "",
+                "Bash",
+            ),
+            ("b3", "bbbbbbbb-2", "[Bash] make", "Bash", ""),
+            (
+                "b4",
+                "bbbbbbbb-2",
+                "[error] Exit code 1\nmake: permission denied",
+                "",
+                "Bash",
+            ),
+        ];
+        let entries: Vec<_> = steps
+            .iter()
+            .enumerate()
+            .map(|(i, (uuid, session, content, calls, errors))| {
+                let mut entry = make_entry(uuid, session, MessageType::Assistant, content, i);
+                entry.timestamp = Utc::now() + chrono::Duration::seconds(i as i64);
+                entry.has_error = !errors.is_empty();
+                entry.tool_calls = calls.split_whitespace().map(String::from).collect();
+                entry.tool_errors = errors.split_whitespace().map(String::from).collect();
+                entry
+            })
+            .collect();
+
+        let mut indexer = SearchIndexer::new(index_path).unwrap();
+        indexer.index_conversations(entries).unwrap();
+        drop(indexer);
+
+        let engine = SearchEngine::new(index_path, HashMap::new()).unwrap();
+        let clusters = engine.error_clusters(None, None, None, 1).unwrap();
+        assert_eq!(clusters.len(), 2);
+        let recurring = &clusters[0];
+        assert_eq!(recurring.tool, "Bash");
         assert_eq!(
-            displayable_count, 3,
-            "Should have 3 displayable messages (User, Assistant, Summary)"
+            recurring.session_count(),
+            2,
+            "Quoted targets normalized away"
         );
+        // a3 went through; b3 failed again with another error
+        let resolved: Vec<_> = recurring
+            .resolutions
+            .iter()
+            .map(|r| r.uuid.as_str())
+            .collect();
+        assert_eq!(resolved, vec!["a3"]);
+
+        let recurring_only = engine.error_clusters(None, None, None, 2).unwrap();
+        assert_eq!(recurring_only.len(), 1);
     }
 }
@@ -0,0 +1,309 @@
+use super::models::{SearchResult, SessionActivity, ShellCommand};
+use super::path_utils::short_uuid;
+use super::sessions::{format_title, latest_summary, title_from_message, title_from_summary};
+use super::timezone::local_time;
+use super::utils::{is_near_duplicate, truncate_content};
+use std::collections::BTreeSet;
+
+/// Characters shown per prompt and final answer
+const TEXT_MAX_CHARS: usize = 120;
+/// Commands and files listed per side before eliding
+const MAX_LISTED: usize = 10;
+/// Tools listed per side, most called first
+const MAX_TOOLS: usize = 8;
+
+/// One step of an alignment of two sequences
+#[derive(Debug, PartialEq)]
+enum Step {
+    Both(usize, usize),
+    OnlyA(usize),
+    OnlyB(usize),
+}
+
+/// Longest common subsequence alignment of `a` and `b`, in order
+fn align<T>(a: &[T], b: &[T], same: impl Fn(&T, &T) -> bool) -> Vec<Step> {
+    // lengths[i][j]: longest common subsequence of a[i..] and b[j..]
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if same(&a[i], &b[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut steps = Vec::new();
+    while i < a.len() && j < b.len() {
+        if same(&a[i], &b[j]) {
+            steps.push(Step::Both(i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            steps.push(Step::OnlyA(i));
+            i += 1;
+        } else {
+            steps.push(Step::OnlyB(j));
+            j += 1;
+        }
+    }
+    steps.extend((i..a.len()).map(Step::OnlyA));
+    steps.extend((j..b.len()).map(Step::OnlyB));
+    steps
+}
+
+/// The same prompt asked again, retyped or lightly edited
+fn same_prompt(a: &&SearchResult, b: &&SearchResult) -> bool {
+    let words = |m: &SearchResult| {
+        m.content
+            .to_lowercase()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    is_near_duplicate(a.simhash, b.simhash) || words(a) == words(b)
+}
+
+fn prompts(activity: &SessionActivity) -> Vec<&SearchResult> {
+    activity
+        .messages
+        .iter()
+        .filter(|m| m.is_prompt() && m.is_displayable())
+        .collect()
+}
+
+/// `{side} 🗒️ session "title" 📁 project 📅 first→last (N msgs)`
+pub(super) fn format_header(side: &str, activity: &SessionActivity) -> String {
+    let messages = &activity.messages;
+    let title = latest_summary(messages)
+        .map(|s| title_from_summary(&s.content))
+        .or_else(|| {
+            messages
+                .iter()
+                .filter(|m| m.is_prompt())
+                .find_map(|m| title_from_message(&m.content))
+        });
+    let (Some(first), Some(last)) = (messages.first(), messages.last()) else {
+        return format!("{} 🗒️ {} (empty)\n", side, short_uuid(&activity.session_id));
+    };
+    format!(
+        "{} 🗒️ {}{} 📁 {} 📅 {}→{} ({} msgs)\n",
+        side,
+        short_uuid(&activity.session_id),
+        format_title(title.as_deref()),
+        first.project_path_display(),
+        local_time(first.timestamp).format("%Y-%m-%d %H:%M"),
+        local_time(last.timestamp).format("%Y-%m-%d %H:%M"),
+        messages.iter().filter(|m| m.is_displayable()).count(),
+    )
+}
+
+/// `Bash×12 Edit×5 (4 failed)`, most called first
+fn format_tools(activity: &SessionActivity) -> String {
+    let mut tools: Vec<(&String, &usize)> = activity.tool_calls.iter().collect();
+    tools.sort_by_key(|(name, calls)| (std::cmp::Reverse(**calls), *name));
+    let mut line: Vec<String> = tools
+        .iter()
+        .take(MAX_TOOLS)
+        .map(|(name, calls)| format!("{}×{}", name, calls))
+        .collect();
+    if tools.len() > MAX_TOOLS {
+        line.push(format!("+{} more", tools.len() - MAX_TOOLS));
+    }
+    if line.is_empty() {
+        line.push("none".to_string());
+    }
+    let failed: usize = activity.tool_errors.values().sum();
+    format!("{} ({} failed)", line.join(" "), failed)
+}
+
+/// Up to `MAX_LISTED` items joined with `, `, then how many were left out
+fn format_list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    let items: Vec<&str> = items.collect();
+    let mut line = items
+        .iter()
+        .take(MAX_LISTED)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if items.len() > MAX_LISTED {
+        line.push_str(&format!(" +{} more", items.len() - MAX_LISTED));
+    }
+    line
+}
+
+/// Commands only one side ran, each once, ✗ when it failed there
+fn commands_only_in(commands: &[ShellCommand], other: &[ShellCommand]) -> Vec<String> {
+    let other: BTreeSet<&str> = other.iter().map(|c| c.command.as_str()).collect();
+    let mut seen = BTreeSet::new();
+    let mut only = Vec::new();
+    for command in commands {
+        let text = command.command.as_str();
+        if other.contains(text) || !seen.insert(text) {
+            continue;
+        }
+        let text = truncate_content(text, TEXT_MAX_CHARS, true);
+        only.push(if command.failed {
+            format!("{} ✗", text)
+        } else {
+            text
+        });
+    }
+    only
+}
+
+/// How the session ended: its last answer, and whether the last tool
+/// result before it had failed
+fn format_outcome(side: &str, activity: &SessionActivity) -> String {
+    let answer = activity
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.message_type == "Assistant" && !m.content.starts_with('['));
+    let last_result_failed = activity
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.message_type == "User" && !m.is_prompt())
+        .is_some_and(|m| m.has_error);
+    let warning = if last_result_failed {
+        " ⚠️ last tool result failed"
+    } else {
+        ""
+    };
+    match answer {
+        Some(answer) => format!(
+            "  {} 💬 {} 📅 {}: {}{}\n",
+            side,
+            short_uuid(&answer.uuid),
+            local_time(answer.timestamp).format("%Y-%m-%d %H:%M"),
+            truncate_content(&answer.content, TEXT_MAX_CHARS, true),
+            warning
+        ),
+        None => format!("  {} no answer{}\n", side, warning),
+    }
+}
+
+/// Compare two sessions, such as two attempts at the same task: prompts
+/// aligned in order (= both, A/B only one), tools and shell commands used,
+/// files modified and how each ended
+pub fn format_session_diff(a: &SessionActivity, b: &SessionActivity) -> String {
+    let mut output = String::new();
+    output.push_str(&format_header("🔀 A", a));
+    output.push_str(&format_header("   B", b));
+
+    let (prompts_a, prompts_b) = (prompts(a), prompts(b));
+    let steps = align(&prompts_a, &prompts_b, same_prompt);
+    let shared = steps.iter().filter(|s| matches!(s, Step::Both(..))).count();
+    output.push_str(&format!(
+        "\n💬 Prompts: {} shared, {} only in A, {} only in B\n",
+        shared,
+        prompts_a.len() - shared,
+        prompts_b.len() - shared
+    ));
+    for step in &steps {
+        let (side, prompt) = match *step {
+            Step::Both(i, _) => ("=", prompts_a[i]),
+            Step::OnlyA(i) => ("A", prompts_a[i]),
+            Step::OnlyB(j) => ("B", prompts_b[j]),
+        };
+        output.push_str(&format!(
+            "  {} {}\n",
+            side,
+            truncate_content(&prompt.content, TEXT_MAX_CHARS, true)
+        ));
+    }
+
+    output.push_str("\n🔧 Tools\n");
+    output.push_str(&format!("  A: {}\n", format_tools(a)));
+    output.push_str(&format!("  B: {}\n", format_tools(b)));
+
+    let failed = |commands: &[ShellCommand]| commands.iter().filter(|c| c.failed).count();
+    output.push_str(&format!(
+        "\n💻 Commands: A ran {} ({} failed), B ran {} ({} failed)\n",
+        a.commands.len(),
+        failed(&a.commands),
+        b.commands.len(),
+        failed(&b.commands)
+    ));
+    for (side, only) in [
+        ("A", commands_only_in(&a.commands, &b.commands)),
+        ("B", commands_only_in(&b.commands, &a.commands)),
+    ] {
+        if !only.is_empty() {
+            output.push_str(&format!(
+                "  {} only: {}\n",
+                side,
+                format_list(only.iter().map(String::as_str))
+            ));
+        }
+    }
+
+    output.push_str(&format!(
+        "\n📄 Files modified (also read: A {}, B {})\n",
+        a.files_read.len(),
+        b.files_read.len()
+    ));
+    let sides: [(&str, Vec<&str>); 3] = [
+        (
+            "both",
+            a.files_modified
+                .intersection(&b.files_modified)
+                .map(String::as_str)
+                .collect(),
+        ),
+        (
+            "A only",
+            a.files_modified
+                .difference(&b.files_modified)
+                .map(String::as_str)
+                .collect(),
+        ),
+        (
+            "B only",
+            b.files_modified
+                .difference(&a.files_modified)
+                .map(String::as_str)
+                .collect(),
+        ),
+    ];
+    if sides.iter().all(|(_, files)| files.is_empty()) {
+        output.push_str("  none\n");
+    }
+    for (side, files) in sides.iter().filter(|(_, files)| !files.is_empty()) {
+        output.push_str(&format!(
+            "  {}: {}\n",
+            side,
+            format_list(files.iter().copied())
+        ));
+    }
+
+    output.push_str("\n🏁 Outcome\n");
+    output.push_str(&format_outcome("A", a));
+    output.push_str(&format_outcome("B", b));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_keeps_shared_steps_in_order() {
+        let a = ["fix login", "mock the clock", "run tests"];
+        let b = ["fix login", "use a fixed time", "run tests", "commit"];
+        assert_eq!(
+            align(&a, &b, |x, y| x == y),
+            vec![
+                Step::Both(0, 0),
+                Step::OnlyA(1),
+                Step::OnlyB(1),
+                Step::Both(2, 2),
+                Step::OnlyB(3),
+            ]
+        );
+        assert_eq!(align(&a, &[], |x, y| x == y).len(), 3);
+    }
+}
@@ -0,0 +1,487 @@
+use super::indexer::SearchIndexer;
+use super::models::{SearchResult, SessionInfo};
+use super::path_utils::short_uuid;
+use super::search::{SearchEngine, project_matches};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redb::{Database, DatabaseError, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::debug;
+
+/// Session id → JSON-encoded `SessionRecord`
+const SESSIONS: TableDefinition<&str, &[u8]> = TableDefinition::new("sessions");
+/// Session id → JSON-encoded `StoredSummary`
+const SUMMARIES: TableDefinition<&str, &[u8]> = TableDefinition::new("summaries");
+
+/// The database is locked while open, so other processes wait their turn
+const OPEN_ATTEMPTS: u32 = 50;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionRecord {
+    project_path: String,
+    first_timestamp: DateTime<Utc>,
+    last_timestamp: DateTime<Utc>,
+    message_count: usize,
+    #[serde(default)]
+    tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+}
+
+impl SessionRecord {
+    fn from_info(info: &SessionInfo) -> Self {
+        Self {
+            project_path: info.project_path.clone(),
+            first_timestamp: info.first_timestamp,
+            last_timestamp: info.last_timestamp,
+            message_count: info.message_count,
+            tokens: info.tokens,
+            title: info.title.clone(),
+        }
+    }
+
+    fn into_info(self, session_id: &str) -> SessionInfo {
+        SessionInfo {
+            session_id: session_id.to_string(),
+            project_path: self.project_path,
+            first_timestamp: self.first_timestamp,
+            last_timestamp: self.last_timestamp,
+            message_count: self.message_count,
+            tokens: self.tokens,
+            title: self.title,
+            last_accessed: None,
+            continued_in: Vec::new(),
+        }
+    }
+}
+
+/// A summary kept for a session, stale once the session has grown past the
+/// `message_count` it was made from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSummary {
+    pub text: String,
+    /// How it was made, such as `extractive`
+    pub source: String,
+    pub message_count: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `StoredSummary::source` of summaries handed in without one
+pub const AGENT_SUMMARY_SOURCE: &str = "agent";
+
+/// Open or create a redb database, waiting while another process has it open
+pub(super) fn open_database(path: &Path) -> Result<Database> {
+    let mut attempts = 1;
+    loop {
+        match Database::create(path) {
+            Err(DatabaseError::DatabaseAlreadyOpen) if attempts < OPEN_ATTEMPTS => {
+                attempts += 1;
+                std::thread::sleep(OPEN_RETRY_DELAY);
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Per-session metadata in a small database next to the index, kept current
+/// by indexing so session listings don't have to aggregate the whole index
+pub struct SessionStore {
+    path: PathBuf,
+    /// Present while the stored sessions are behind the index
+    stale_marker: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(cache_dir: &Path) -> Self {
+        Self {
+            path: cache_dir.join("sessions.redb"),
+            stale_marker: cache_dir.join("sessions.stale"),
+        }
+    }
+
+    /// A store only exists once it was filled from the full index
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Whether listings can use the stored sessions: not after an update
+    /// failed, until the next full refresh
+    pub fn is_current(&self) -> bool {
+        self.exists() && !self.stale_marker.exists()
+    }
+
+    /// Whether another process or handle has the database open right now
+    pub fn is_busy(&self) -> bool {
+        self.exists()
+            && matches!(
+                Database::create(&self.path),
+                Err(DatabaseError::DatabaseAlreadyOpen)
+            )
+    }
+
+    fn open(&self) -> Result<Database> {
+        open_database(&self.path)
+    }
+
+    /// Every stored session, optionally limited to a project
+    pub fn sessions(&self, project_filter: Option<&str>) -> Result<Vec<SessionInfo>> {
+        self.read_records(|session_id, record| {
+            project_filter
+                .is_none_or(|filter| project_matches(&record.project_path, filter))
+                .then(|| record.into_info(session_id))
+        })
+    }
+
+    /// Title per session, for sessions that have one
+    pub fn titles(&self) -> Result<HashMap<String, String>> {
+        if !self.is_current() {
+            return Ok(HashMap::new());
+        }
+        Ok(self
+            .read_records(|session_id, record| Some((session_id.to_string(), record.title?)))?
+            .into_iter()
+            .collect())
+    }
+
+    fn read_records<T>(
+        &self,
+        mut keep: impl FnMut(&str, SessionRecord) -> Option<T>,
+    ) -> Result<Vec<T>> {
+        let db = self.open()?;
+        let txn = db.begin_read()?;
+        let table = match txn.open_table(SESSIONS) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut kept = Vec::new();
+        for row in table.iter()? {
+            let (session_id, value) = row?;
+            let record: SessionRecord = serde_json::from_slice(value.value())?;
+            kept.extend(keep(session_id.value(), record));
+        }
+        Ok(kept)
+    }
+
+    /// Replace the listed sessions with their current aggregates; listed
+    /// sessions missing from `sessions` are removed
+    pub fn update(&self, session_ids: &[String], sessions: &[SessionInfo]) -> Result<()> {
+        self.write_sessions(false, session_ids, sessions)
+    }
+
+    /// `update`, dropping every stored session first when `replace_all`
+    fn write_sessions(
+        &self,
+        replace_all: bool,
+        session_ids: &[String],
+        sessions: &[SessionInfo],
+    ) -> Result<()> {
+        let db = self.open()?;
+        let txn = db.begin_write()?;
+        if replace_all {
+            txn.delete_table(SESSIONS)?;
+        }
+        {
+            let mut table = txn.open_table(SESSIONS)?;
+            for session_id in session_ids {
+                table.remove(session_id.as_str())?;
+            }
+            for info in sessions {
+                let record = serde_json::to_vec(&SessionRecord::from_info(info))?;
+                table.insert(info.session_id.as_str(), record.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Re-aggregate these sessions from the index, titles included, or
+    /// every session to fill a new or stale store
+    pub fn refresh(&self, engine: &SearchEngine, session_ids: Option<&[String]>) -> Result<()> {
+        let mut sessions = match session_ids {
+            Some(ids) => engine.sessions_by_id(ids)?,
+            None => engine.list_sessions(None)?,
+        };
+        let mut titles = engine.session_titles(session_ids)?;
+        for session in &mut sessions {
+            session.title = titles.remove(&session.session_id);
+        }
+
+        match session_ids {
+            Some(ids) => self.update(ids, &sessions),
+            None => {
+                self.write_sessions(true, &[], &sessions)?;
+                if self.stale_marker.exists() {
+                    fs::remove_file(&self.stale_marker)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove sessions and their summaries, if there is a store
+    pub fn remove(&self, session_ids: &[String]) -> Result<()> {
+        if !self.exists() {
+            return Ok(());
+        }
+        self.update(session_ids, &[])?;
+        let db = self.open()?;
+        let txn = db.begin_write()?;
+        {
+            let mut table = txn.open_table(SUMMARIES)?;
+            for session_id in session_ids {
+                table.remove(session_id.as_str())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// The summary kept for a session, if any
+    pub fn summary(&self, session_id: &str) -> Result<Option<StoredSummary>> {
+        if !self.exists() {
+            return Ok(None);
+        }
+        let db = self.open()?;
+        let txn = db.begin_read()?;
+        let table = match txn.open_table(SUMMARIES) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        match table.get(session_id)? {
+            Some(value) => Ok(Some(serde_json::from_slice(value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Every kept summary, by session id
+    pub fn summaries(&self) -> Result<Vec<(String, StoredSummary)>> {
+        if !self.exists() {
+            return Ok(Vec::new());
+        }
+        let db = self.open()?;
+        let txn = db.begin_read()?;
+        let table = match txn.open_table(SUMMARIES) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut summaries = Vec::new();
+        for row in table.iter()? {
+            let (session_id, value) = row?;
+            summaries.push((
+                session_id.value().to_string(),
+                serde_json::from_slice(value.value())?,
+            ));
+        }
+        Ok(summaries)
+    }
+
+    /// Keep a summary for a session, replacing any earlier one. Without a
+    /// store there is nowhere to keep it: creating one here would hide
+    /// every session from listings until the next full refresh.
+    pub fn store_summary(&self, session_id: &str, summary: &StoredSummary) -> Result<bool> {
+        if !self.exists() {
+            return Ok(false);
+        }
+        let db = self.open()?;
+        let txn = db.begin_write()?;
+        {
+            let mut table = txn.open_table(SUMMARIES)?;
+            let record = serde_json::to_vec(summary)?;
+            table.insert(session_id, record.as_slice())?;
+        }
+        txn.commit()?;
+        Ok(true)
+    }
+
+    /// Mark the sessions of a store that could not be kept current as
+    /// stale, so the next indexing run refills them instead of listings
+    /// showing stale sessions. Summaries can't be rebuilt and are kept.
+    pub fn mark_stale(&self) {
+        if let Err(e) = fs::write(&self.stale_marker, b"") {
+            debug!("Could not write {}: {}", self.stale_marker.display(), e);
+        }
+    }
+}
+
+/// Keep a summary for the session of `messages` and index it as the
+/// session's latest Summary message, replacing an earlier stored one.
+/// Its title is refreshed, as titles come from summaries first.
+pub fn save_session_summary(
+    index_path: &Path,
+    messages: &[SearchResult],
+    summary: &StoredSummary,
+) -> Result<()> {
+    let Some(first) = messages.first() else {
+        return Ok(());
+    };
+    let session_ids = [first.session_id.clone()];
+    {
+        let mut indexer = SearchIndexer::open(index_path)?;
+        indexer.index_session_summary(messages, &summary.text)?;
+    }
+    let store = SessionStore::new(index_path);
+    if store.store_summary(&first.session_id, summary)? {
+        let engine = SearchEngine::new(index_path, HashMap::new())?;
+        store.refresh(&engine, Some(&session_ids))?;
+    }
+    Ok(())
+}
+
+/// Confirmation of a summary just stored
+pub fn format_stored_summary(session_id: &str, summary: &StoredSummary) -> String {
+    format!(
+        "📝 Stored {} summary of 🗒️ {} ({} words), searchable with summary:<word>\n",
+        summary.source,
+        short_uuid(session_id),
+        summary.text.split_whitespace().count()
+    )
+}
+
+/// Sessions in the index, from the session store when there is one and
+/// otherwise by aggregating the index. `last_accessed` is left empty.
+pub fn indexed_sessions(
+    engine: &SearchEngine,
+    cache_dir: &Path,
+    project_filter: Option<&str>,
+) -> Result<Vec<SessionInfo>> {
+    let store = SessionStore::new(cache_dir);
+    if store.is_current() {
+        match store.sessions(project_filter) {
+            Ok(sessions) => return Ok(sessions),
+            Err(e) => debug!("Session store unavailable, scanning the index: {}", e),
+        }
+    }
+    engine.list_sessions(project_filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn session(session_id: &str, project_path: &str, message_count: usize) -> SessionInfo {
+        SessionInfo {
+            session_id: session_id.to_string(),
+            project_path: project_path.to_string(),
+            first_timestamp: Utc::now(),
+            last_timestamp: Utc::now(),
+            message_count,
+            tokens: 10,
+            title: None,
+            last_accessed: None,
+            continued_in: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_update_replaces_listed_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        store.remove(&["a".to_string()]).unwrap();
+        assert!(!store.exists(), "Removing from no store creates none");
+
+        store
+            .update(
+                &["a".to_string(), "b".to_string()],
+                &[
+                    session("a", "/work/alpha", 2),
+                    session("b", "/work/beta", 1),
+                ],
+            )
+            .unwrap();
+        store
+            .update(&["a".to_string()], &[session("a", "/work/alpha", 5)])
+            .unwrap();
+        store.remove(&["b".to_string()]).unwrap();
+
+        let sessions = store.sessions(None).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session_id, "a");
+        assert_eq!(sessions[0].message_count, 5);
+        assert_eq!(sessions[0].tokens, 10);
+        assert!(store.sessions(Some("beta")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_summaries_are_kept_until_their_session_is_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        let summary = |text: &str| StoredSummary {
+            text: text.to_string(),
+            source: AGENT_SUMMARY_SOURCE.to_string(),
+            message_count: 2,
+            created_at: Utc::now(),
+        };
+        assert!(!store.store_summary("a", &summary("first")).unwrap());
+        assert!(!store.exists(), "Storing a summary creates no store");
+
+        store
+            .update(&["a".to_string()], &[session("a", "/work/alpha", 2)])
+            .unwrap();
+        assert!(store.store_summary("a", &summary("first")).unwrap());
+        assert!(store.store_summary("a", &summary("second")).unwrap());
+        store
+            .update(&["a".to_string()], &[session("a", "/work/alpha", 3)])
+            .unwrap();
+        assert_eq!(store.summary("a").unwrap().unwrap().text, "second");
+        assert_eq!(store.summaries().unwrap().len(), 1);
+
+        store.remove(&["a".to_string()]).unwrap();
+        assert!(store.summary("a").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_stale_store_keeps_its_summaries() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        store
+            .update(
+                &["a".to_string(), "b".to_string()],
+                &[
+                    session("a", "/work/alpha", 2),
+                    session("b", "/work/beta", 1),
+                ],
+            )
+            .unwrap();
+        let summary = StoredSummary {
+            text: "kept".to_string(),
+            source: AGENT_SUMMARY_SOURCE.to_string(),
+            message_count: 2,
+            created_at: Utc::now(),
+        };
+        assert!(store.store_summary("a", &summary).unwrap());
+        assert!(store.is_current());
+
+        store.mark_stale();
+        assert!(!store.is_current(), "Listings scan the index instead");
+        assert_eq!(store.summary("a").unwrap().unwrap().text, "kept");
+
+        store
+            .write_sessions(true, &[], &[session("a", "/work/alpha", 3)])
+            .unwrap();
+        let sessions = store.sessions(None).unwrap();
+        assert_eq!(sessions.len(), 1, "A full refill drops gone sessions");
+        assert_eq!(sessions[0].message_count, 3);
+        assert_eq!(store.summary("a").unwrap().unwrap().text, "kept");
+    }
+
+    #[test]
+    fn test_open_store_is_busy() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        assert!(!store.is_busy(), "No store to wait for");
+        store.update(&[], &[]).unwrap();
+        let db = store.open().unwrap();
+        assert!(store.is_busy());
+        drop(db);
+        assert!(!store.is_busy());
+    }
+}
@@ -0,0 +1,254 @@
+use super::indexer::stored_summary_uuid;
+use super::models::{ProjectInfo, SearchResult, SessionInfo};
+use super::path_utils::{home_to_tilde, session_jsonl_path, short_uuid};
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+use super::utils::truncate_content;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// Longest generated session title, in characters
+const TITLE_MAX_CHARS: usize = 60;
+/// Fewer words than this is a reply ("yes", "go on"), not a topic
+const TITLE_MIN_WORDS: usize = 3;
+
+/// Session title from a user message, or None for messages that don't say
+/// what the session is about: tool results, slash command output, short replies
+pub fn title_from_message(content: &str) -> Option<String> {
+    let text = content.trim_start();
+    if text.starts_with('[') || text.starts_with('<') || text.starts_with("Caveat:") {
+        return None;
+    }
+    if text.split_whitespace().count() < TITLE_MIN_WORDS {
+        return None;
+    }
+    Some(truncate_content(text, TITLE_MAX_CHARS, true))
+}
+
+/// Session title from a summary Claude Code wrote for it
+pub fn title_from_summary(summary: &str) -> String {
+    truncate_content(summary, TITLE_MAX_CHARS, true)
+}
+
+/// The most recent summary Claude Code wrote for a session, from its messages
+pub fn latest_summary(messages: &[SearchResult]) -> Option<&SearchResult> {
+    messages
+        .iter()
+        .filter(|m| m.message_type == "Summary")
+        .max_by_key(|m| (m.timestamp, m.sequence_num))
+}
+
+/// Whether a Summary message is one stored with `save_session_summary`
+/// rather than written by Claude Code
+pub fn is_stored_summary(summary: &SearchResult) -> bool {
+    summary.uuid == stored_summary_uuid(&summary.session_id)
+}
+
+/// ` "title"` to follow a session id, empty for untitled sessions
+pub fn format_title(title: Option<&str>) -> String {
+    title.map(|t| format!(" \"{}\"", t)).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SessionSort {
+    /// Most recent message first
+    #[default]
+    LastActive,
+    /// Most recently retrieved first, never-accessed last
+    LastAccessed,
+    /// Oldest first message first
+    Oldest,
+    /// Most messages first
+    Messages,
+}
+
+impl SessionSort {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "last_active" => Some(Self::LastActive),
+            "last_accessed" => Some(Self::LastAccessed),
+            "oldest" => Some(Self::Oldest),
+            "messages" => Some(Self::Messages),
+            _ => None,
+        }
+    }
+}
+
+/// Fill access timestamps from the cache and sort
+pub fn prepare_session_list(
+    mut sessions: Vec<SessionInfo>,
+    access: &HashMap<String, DateTime<Utc>>,
+    sort: SessionSort,
+) -> Vec<SessionInfo> {
+    for session in &mut sessions {
+        session.last_accessed = access.get(&session.session_id).copied();
+    }
+    match sort {
+        SessionSort::LastActive => {
+            sessions.sort_by_key(|s| std::cmp::Reverse(s.last_timestamp));
+        }
+        SessionSort::LastAccessed => {
+            sessions.sort_by_key(|s| std::cmp::Reverse((s.last_accessed, s.last_timestamp)));
+        }
+        SessionSort::Oldest => sessions.sort_by_key(|s| s.first_timestamp),
+        SessionSort::Messages => {
+            sessions.sort_by_key(|s| std::cmp::Reverse(s.message_count));
+        }
+    }
+    sessions
+}
+
+/// Fold sessions continuing another (`(continuation, continued)` links)
+/// into the first listed session of their chain, so a conversation
+/// compacted across files lists as one: counts and tokens add up, the span
+/// covers them all and `continued_in` names the folded sessions
+pub fn merge_continuations(
+    mut sessions: Vec<SessionInfo>,
+    links: &[(String, String)],
+) -> Vec<SessionInfo> {
+    let continues: HashMap<&str, &str> = links
+        .iter()
+        .map(|(continuation, continued)| (continuation.as_str(), continued.as_str()))
+        .collect();
+    let listed: HashSet<String> = sessions.iter().map(|s| s.session_id.clone()).collect();
+    let root_of = |session_id: &str| {
+        let mut root = session_id;
+        let mut seen = HashSet::from([root]);
+        while let Some(&continued) = continues.get(root)
+            && listed.contains(continued)
+            && seen.insert(continued)
+        {
+            root = continued;
+        }
+        root.to_string()
+    };
+
+    sessions.sort_by_key(|s| s.first_timestamp);
+    let mut merged: Vec<SessionInfo> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut folded = Vec::new();
+    for session in sessions {
+        let root = root_of(&session.session_id);
+        if root == session.session_id {
+            positions.insert(root, merged.len());
+            merged.push(session);
+        } else {
+            folded.push((root, session));
+        }
+    }
+    for (root, session) in folded {
+        let Some(&position) = positions.get(&root) else {
+            continue;
+        };
+        let target = &mut merged[position];
+        target.first_timestamp = target.first_timestamp.min(session.first_timestamp);
+        target.last_timestamp = target.last_timestamp.max(session.last_timestamp);
+        target.message_count += session.message_count;
+        target.tokens += session.tokens;
+        if target.title.is_none() {
+            target.title = session.title;
+        }
+        target.continued_in.push(session.session_id);
+    }
+    merged
+}
+
+/// Sessions with no activity or retrieval since `cutoff`.
+/// With `never_accessed`, only sessions that were never retrieved qualify.
+pub fn prune_candidates(
+    sessions: &[SessionInfo],
+    cutoff: DateTime<Utc>,
+    never_accessed: bool,
+) -> Vec<&SessionInfo> {
+    sessions
+        .iter()
+        .filter(|s| s.last_touched() < cutoff)
+        .filter(|s| !never_accessed || s.last_accessed.is_none())
+        .collect()
+}
+
+/// Format one session as a dense listing line:
+/// N. 📁 ~/path 🗒️ session "title" (M msgs) 📅 first→last 👁️ accessed
+pub fn format_session_line(index: usize, session: &SessionInfo) -> String {
+    let path_display = home_to_tilde(&session.project_path);
+    let path_link = file_hyperlink(&session.project_path, &path_display);
+    let jsonl_path =
+        session_jsonl_path(&session.project_path, &session.session_id).unwrap_or_default();
+    let session_link = file_hyperlink(
+        &jsonl_path.to_string_lossy(),
+        short_uuid(&session.session_id),
+    );
+
+    let mut line = format!(
+        "{}. 📁 {} 🗒️ {}{} ({} msgs) 📅 {}→{}",
+        index + 1,
+        path_link,
+        session_link,
+        format_title(session.title.as_deref()),
+        session.message_count,
+        local_time(session.first_timestamp).format("%Y-%m-%d %H:%M"),
+        local_time(session.last_timestamp).format("%Y-%m-%d %H:%M"),
+    );
+    if !session.continued_in.is_empty() {
+        let ids: Vec<&str> = session
+            .continued_in
+            .iter()
+            .map(|id| short_uuid(id))
+            .collect();
+        line.push_str(&format!(" 🔗 continued in {}", ids.join(", ")));
+    }
+    if let Some(accessed) = session.last_accessed {
+        line.push_str(&format!(
+            " 👁️ {}",
+            local_time(accessed).format("%Y-%m-%d %H:%M")
+        ));
+    }
+    line.push('\n');
+    line
+}
+
+/// Format a session listing, shared by CLI and MCP
+pub fn format_session_list(sessions: &[SessionInfo], limit: usize) -> String {
+    let mut output = String::new();
+    for (i, session) in sessions.iter().take(limit).enumerate() {
+        output.push_str(&format_session_line(i, session));
+    }
+    if sessions.len() > limit {
+        output.push_str(&format!(
+            "+more: {} sessions total, limit={}\n",
+            sessions.len(),
+            limit
+        ));
+    }
+    output
+}
+
+/// Format a project listing, shared by CLI and MCP:
+/// N. 📁 name (M msgs, S sessions) 📅 last
+pub fn format_project_list(projects: &[ProjectInfo], limit: usize) -> String {
+    let mut output = String::new();
+    for (i, project) in projects.iter().take(limit).enumerate() {
+        output.push_str(&format!(
+            "{}. 📁 {} ({} msgs, {} sessions)",
+            i + 1,
+            project.project,
+            project.message_count,
+            project.session_count,
+        ));
+        if let Some(last) = project.last_timestamp {
+            output.push_str(&format!(
+                " 📅 {}",
+                local_time(last).format("%Y-%m-%d %H:%M")
+            ));
+        }
+        output.push('\n');
+    }
+    if projects.len() > limit {
+        output.push_str(&format!(
+            "+more: {} projects total, limit={}\n",
+            projects.len(),
+            limit
+        ));
+    }
+    output
+}
@@ -0,0 +1,66 @@
+use super::models::{CommandRun, ShellCommand};
+use super::path_utils::{home_to_tilde, session_jsonl_path, short_uuid};
+use super::terminal::file_hyperlink;
+use super::timezone::local_time;
+
+fn status_icon(command: &ShellCommand) -> String {
+    match (command.failed, command.exit_code) {
+        (false, Some(_)) => "✅".to_string(),
+        (true, Some(code)) => format!("❌ {}", code),
+        (true, None) => "❌".to_string(),
+        // No result recorded (interrupted session or still running)
+        (false, None) => "❔".to_string(),
+    }
+}
+
+/// Format commands deduplicated by text, ordered by their latest run and
+/// keeping the most recent `limit`:
+/// N. ✅ 📅 last run 🔁 runs 📁 ~/path 🗒️ session 💬 msg
+///    $ command
+pub fn format_command_history(runs: &[CommandRun], limit: usize) -> String {
+    // Latest run and run count per distinct command, runs are oldest first
+    let mut unique: Vec<(&CommandRun, usize)> = Vec::new();
+    for run in runs {
+        let text = run.command.command.trim();
+        match unique
+            .iter()
+            .position(|(seen, _)| seen.command.command.trim() == text)
+        {
+            Some(pos) => {
+                let (_, count) = unique.remove(pos);
+                unique.push((run, count + 1));
+            }
+            None => unique.push((run, 1)),
+        }
+    }
+
+    let mut output = String::new();
+    let skipped = unique.len().saturating_sub(limit);
+    if skipped > 0 {
+        output.push_str(&format!(
+            "+more: {} earlier commands, {} total, limit={}\n",
+            skipped,
+            unique.len(),
+            limit
+        ));
+    }
+
+    for (i, (run, count)) in unique[skipped..].iter().enumerate() {
+        let jsonl_path = session_jsonl_path(&run.project_path, &run.session_id).unwrap_or_default();
+        output.push_str(&format!(
+            "{}. {} 📅 {} 🔁 {} 📁 {} 🗒️ {} 💬 {}\n",
+            skipped + i + 1,
+            status_icon(&run.command),
+            local_time(run.timestamp).format("%Y-%m-%d %H:%M"),
+            count,
+            file_hyperlink(&run.project_path, &home_to_tilde(&run.project_path)),
+            file_hyperlink(&jsonl_path.to_string_lossy(), short_uuid(&run.session_id)),
+            short_uuid(&run.uuid),
+        ));
+        for (n, line) in run.command.command.trim().lines().enumerate() {
+            let prompt = if n == 0 { "$" } else { " " };
+            output.push_str(&format!("   {} {}\n", prompt, line));
+        }
+    }
+    output
+}
@@ -0,0 +1,416 @@
+use super::models::{SearchResult, SessionInfo};
+use super::path_utils::{home_to_tilde, project_dir_name, short_uuid};
+use super::sessions::title_from_message;
+use super::timezone::local_time;
+use super::utils::truncate_content;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Characters of each message kept in the client-side search index
+const SEARCH_TEXT_MAX_CHARS: usize = 2000;
+
+/// A session and its messages, ordered by sequence, as exported
+pub struct SiteSession {
+    pub info: SessionInfo,
+    pub messages: Vec<SearchResult>,
+}
+
+/// What `write_site` wrote
+#[derive(Debug, Default, PartialEq)]
+pub struct SiteSummary {
+    pub projects: usize,
+    pub sessions: usize,
+    pub messages: usize,
+}
+
+const STYLE: &str = r#"body { font-family: system-ui, sans-serif; max-width: 60rem; margin: 0 auto; padding: 1rem; color: #222; }
+a { color: #0550ae; }
+nav { margin-bottom: 1rem; }
+.meta { color: #666; font-size: 0.9em; }
+.message { border-left: 4px solid #ccc; margin: 1rem 0; padding: 0.25rem 0.75rem; }
+.message.user { border-color: #0969da; }
+.message.assistant { border-color: #1a7f37; }
+.message.summary { border-color: #9a6700; }
+.text { white-space: pre-wrap; overflow-wrap: anywhere; }
+pre { background: #f6f8fa; padding: 0.75rem; overflow-x: auto; }
+details summary { cursor: pointer; color: #666; }
+#search { width: 100%; font-size: 1.1em; padding: 0.4rem; box-sizing: border-box; }
+#results li { margin: 0.5rem 0; }
+.tok-comment { color: #6e7781; font-style: italic; }
+.tok-string { color: #0a3069; }
+.tok-number { color: #0550ae; }
+.tok-keyword { color: #cf222e; font-weight: 600; }
+"#;
+
+/// Client-side search over `SEARCH_INDEX` and a small highlighter for code
+/// blocks; both run without a server so the site works from `file://`
+const SCRIPT: &str = r##"(function () {
+  const KEYWORDS = new Set(("as async await break case catch class const continue def default do elif else enum "
+    + "export extends false fn for from func function if impl import in interface let loop match mod mut "
+    + "new nil None null package pub return self Self static struct switch this throw trait true True False "
+    + "try type use var where while with yield").split(" "));
+  const TOKEN = /(\/\/[^\n]*|#[^\n]*|\/\*[\s\S]*?\*\/)|("(?:[^"\\\n]|\\.)*"|'(?:[^'\\\n]|\\.)*'|`[^`]*`)|(\b\d+(?:\.\d+)?\b)|([A-Za-z_]\w*)/g;
+  const HASH_COMMENTS = new Set(["sh", "bash", "zsh", "shell", "python", "py", "ruby", "rb", "yaml", "yml", "toml", "perl", "r", "dockerfile", "makefile"]);
+
+  function highlight(code) {
+    const lang = (code.className.match(/language-(\S+)/) || [])[1] || "";
+    const text = code.textContent;
+    const out = document.createDocumentFragment();
+    let last = 0;
+    for (const m of text.matchAll(TOKEN)) {
+      let cls = null;
+      if (m[1] && (!m[1].startsWith("#") || HASH_COMMENTS.has(lang))) cls = "comment";
+      else if (m[2]) cls = "string";
+      else if (m[3]) cls = "number";
+      else if (m[4] && KEYWORDS.has(m[4])) cls = "keyword";
+      if (!cls) continue;
+      out.append(text.slice(last, m.index));
+      const span = document.createElement("span");
+      span.className = "tok-" + cls;
+      span.textContent = m[0];
+      out.append(span);
+      last = m.index + m[0].length;
+    }
+    out.append(text.slice(last));
+    code.replaceChildren(out);
+  }
+  document.querySelectorAll("pre code").forEach(highlight);
+
+  const input = document.getElementById("search");
+  const results = document.getElementById("results");
+  if (!input || typeof SEARCH_INDEX === "undefined") return;
+  const entries = SEARCH_INDEX.map((e) => Object.assign({ lower: e.text.toLowerCase() }, e));
+  input.addEventListener("input", function () {
+    const terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    results.replaceChildren();
+    if (!terms.length) return;
+    const hits = entries.filter((e) => terms.every((t) => e.lower.includes(t)));
+    for (const e of hits.slice(0, 100)) {
+      const li = document.createElement("li");
+      const a = document.createElement("a");
+      a.href = e.href;
+      a.textContent = e.title;
+      const pos = Math.max(0, e.lower.indexOf(terms[0]) - 60);
+      const snippet = document.createElement("div");
+      snippet.className = "meta";
+      snippet.textContent = e.date + " " + e.project + " — " + (pos ? "…" : "") + e.text.slice(pos, pos + 200);
+      li.append(a, snippet);
+      results.append(li);
+    }
+    const count = document.createElement("li");
+    count.className = "meta";
+    count.textContent = hits.length + " messages" + (hits.length > 100 ? ", first 100 shown" : "");
+    results.prepend(count);
+  });
+})();
+"##;
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A name safe to use as a file name, from a project path or session id
+fn slug(name: &str) -> String {
+    let slug: String = project_dir_name(name)
+        .trim_matches('-')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if slug.is_empty() {
+        "unknown".to_string()
+    } else {
+        slug
+    }
+}
+
+fn format_time(timestamp: DateTime<Utc>) -> String {
+    local_time(timestamp).format("%Y-%m-%d %H:%M").to_string()
+}
+
+fn session_title(session: &SiteSession) -> String {
+    session
+        .info
+        .title
+        .clone()
+        .or_else(|| {
+            session
+                .messages
+                .iter()
+                .filter(|m| m.is_prompt())
+                .find_map(|m| title_from_message(&m.content))
+        })
+        .unwrap_or_else(|| format!("Session {}", short_uuid(&session.info.session_id)))
+}
+
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n\
+         <body>\n{body}<script src=\"{root}site.js\"></script>\n</body>\n</html>\n",
+        title = escape(title),
+    )
+}
+
+/// Message content as HTML: text kept as written, fenced blocks as
+/// `<pre><code class="language-x">` for the highlighter
+fn render_content(content: &str) -> String {
+    let mut html = String::new();
+    let mut text: Vec<&str> = Vec::new();
+    let mut code: Option<(Option<&str>, Vec<&str>)> = None;
+    let flush_text = |html: &mut String, text: &mut Vec<&str>| {
+        let joined = text.join("\n");
+        if !joined.trim().is_empty() {
+            html.push_str(&format!(
+                "<div class=\"text\">{}</div>\n",
+                escape(joined.trim())
+            ));
+        }
+        text.clear();
+    };
+    let push_code = |html: &mut String, language: Option<&str>, lines: &[&str]| {
+        let class = language
+            .map(|l| format!(" class=\"language-{}\"", escape(&l.to_lowercase())))
+            .unwrap_or_default();
+        html.push_str(&format!(
+            "<pre><code{}>{}</code></pre>\n",
+            class,
+            escape(&lines.join("\n"))
+        ));
+    };
+    for line in content.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut code, fence) {
+            (None, Some(info)) => {
+                flush_text(&mut html, &mut text);
+                code = Some((info.split_whitespace().next(), Vec::new()));
+            }
+            (Some(_), Some(rest)) if rest.trim().is_empty() => {
+                let (language, lines) = code.take().unwrap_or_default();
+                push_code(&mut html, language, &lines);
+            }
+            (Some((_, lines)), _) => lines.push(line),
+            (None, None) => text.push(line),
+        }
+    }
+    match code {
+        Some((language, lines)) => push_code(&mut html, language, &lines),
+        None => flush_text(&mut html, &mut text),
+    }
+    html
+}
+
+fn render_message(message: &SearchResult) -> String {
+    let content = render_content(&message.content);
+    // Tool results and hook output are long and rarely what a reader wants
+    let content = if (message.message_type == "User" && !message.is_prompt())
+        || matches!(
+            message.message_type.as_str(),
+            "Hook" | "Command" | "Compact"
+        ) {
+        format!(
+            "<details><summary>{}</summary>\n{}</details>\n",
+            escape(&truncate_content(&message.content, 80, true)),
+            content
+        )
+    } else {
+        content
+    };
+    format!(
+        "<div class=\"message {class}\" id=\"m-{id}\">\n<div class=\"meta\">{role} · \
+         <a href=\"#m-{id}\">{time}</a></div>\n{content}</div>\n",
+        class = message.message_type.to_lowercase(),
+        id = escape(short_uuid(&message.uuid)),
+        role = message.role_display(),
+        time = format_time(message.timestamp),
+    )
+}
+
+/// Render sessions into a static site under `dir`: an index with search, a
+/// page per project listing its sessions and a page per session. Existing
+/// files of the same name are overwritten.
+pub fn write_site(dir: &Path, sessions: &[SiteSession]) -> Result<SiteSummary> {
+    fs::create_dir_all(dir.join("projects"))?;
+    fs::create_dir_all(dir.join("sessions"))?;
+    fs::write(dir.join("style.css"), STYLE)?;
+    fs::write(dir.join("site.js"), SCRIPT)?;
+
+    let mut projects: BTreeMap<&str, Vec<&SiteSession>> = BTreeMap::new();
+    for session in sessions {
+        projects
+            .entry(session.info.project_path.as_str())
+            .or_default()
+            .push(session);
+    }
+
+    let mut summary = SiteSummary::default();
+    let mut search_index = Vec::new();
+    let mut project_rows = String::new();
+    for (project, mut project_sessions) in projects {
+        project_sessions.sort_by_key(|s| std::cmp::Reverse(s.info.last_timestamp));
+        let project_name = home_to_tilde(project);
+        let project_page = format!("{}.html", slug(project));
+        let mut session_rows = String::new();
+        for session in &project_sessions {
+            let title = session_title(session);
+            let session_page = format!("{}.html", slug(&session.info.session_id));
+            let messages: Vec<&SearchResult> = session
+                .messages
+                .iter()
+                .filter(|m| !m.content.trim().is_empty())
+                .collect();
+            let body = format!(
+                "<nav><a href=\"../index.html\">All projects</a> › \
+                 <a href=\"../projects/{}\">{}</a></nav>\n<h1>{}</h1>\n\
+                 <p class=\"meta\">{} to {} · {} messages · {}</p>\n{}",
+                project_page,
+                escape(&project_name),
+                escape(&title),
+                format_time(session.info.first_timestamp),
+                format_time(session.info.last_timestamp),
+                messages.len(),
+                escape(&session.info.session_id),
+                messages
+                    .iter()
+                    .map(|m| render_message(m))
+                    .collect::<String>()
+            );
+            fs::write(
+                dir.join("sessions").join(&session_page),
+                page(&title, "../", &body),
+            )?;
+            session_rows.push_str(&format!(
+                "<li><a href=\"../sessions/{}\">{}</a> <span class=\"meta\">{} · {} messages</span></li>\n",
+                session_page,
+                escape(&title),
+                format_time(session.info.last_timestamp),
+                messages.len()
+            ));
+            for message in &messages {
+                search_index.push(serde_json::json!({
+                    "href": format!("sessions/{}#m-{}", session_page, short_uuid(&message.uuid)),
+                    "title": title,
+                    "project": project_name,
+                    "date": format_time(message.timestamp),
+                    "text": truncate_content(&message.content, SEARCH_TEXT_MAX_CHARS, false),
+                }));
+            }
+            summary.sessions += 1;
+            summary.messages += messages.len();
+        }
+        let body = format!(
+            "<nav><a href=\"../index.html\">All projects</a></nav>\n<h1>{}</h1>\n<ul>\n{}</ul>\n",
+            escape(&project_name),
+            session_rows
+        );
+        fs::write(
+            dir.join("projects").join(&project_page),
+            page(&project_name, "../", &body),
+        )?;
+        project_rows.push_str(&format!(
+            "<li><a href=\"projects/{}\">{}</a> <span class=\"meta\">{} sessions</span></li>\n",
+            project_page,
+            escape(&project_name),
+            project_sessions.len()
+        ));
+        summary.projects += 1;
+    }
+
+    // A script rather than JSON: browsers refuse to fetch() from file://
+    fs::write(
+        dir.join("search-index.js"),
+        format!(
+            "const SEARCH_INDEX = {};\n",
+            serde_json::Value::Array(search_index)
+        ),
+    )?;
+    let body = format!(
+        "<h1>Conversation history</h1>\n<p class=\"meta\">{} projects · {} sessions · {} messages</p>\n\
+         <input id=\"search\" type=\"search\" placeholder=\"Search all messages\" autofocus>\n\
+         <ul id=\"results\"></ul>\n<h2>Projects</h2>\n<ul>\n{}</ul>\n\
+         <script src=\"search-index.js\"></script>\n",
+        summary.projects, summary.sessions, summary.messages, project_rows
+    );
+    fs::write(
+        dir.join("index.html"),
+        page("Conversation history", "", &body),
+    )?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::models::test_message;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_site_has_index_project_and_session_pages() {
+        let dir = TempDir::new().unwrap();
+        let session = SiteSession {
+            info: SessionInfo {
+                session_id: "abc/123".to_string(),
+                project_path: "/work/api".to_string(),
+                first_timestamp: Utc::now(),
+                last_timestamp: Utc::now(),
+                message_count: 3,
+                tokens: 0,
+                title: None,
+                last_accessed: None,
+                continued_in: Vec::new(),
+            },
+            messages: vec![
+                test_message(0, "User", "Why does <Vec> & friends panic here?"),
+                test_message(
+                    1,
+                    "Assistant",
+                    "Bounds check:\n```rust\nlet x = v[3];\n```\nDone.",
+                ),
+                test_message(2, "User", "[result] huge tool output"),
+            ],
+        };
+        let summary = write_site(dir.path(), &[session]).unwrap();
+        assert_eq!(
+            summary,
+            SiteSummary {
+                projects: 1,
+                sessions: 1,
+                messages: 3
+            }
+        );
+
+        let session_page = fs::read_to_string(dir.path().join("sessions/abc-123.html")).unwrap();
+        assert!(session_page.contains("<h1>Why does &lt;Vec&gt; &amp; friends panic here?</h1>"));
+        assert!(session_page.contains(
+            "<div class=\"text\">Bounds check:</div>\n<pre><code class=\"language-rust\">let x = v[3];</code></pre>\n<div class=\"text\">Done.</div>"
+        ));
+        assert!(session_page.contains("<details><summary>[result] huge tool output</summary>"));
+        assert!(session_page.contains("href=\"../projects/work-api.html\""));
+
+        let project_page = fs::read_to_string(dir.path().join("projects/work-api.html")).unwrap();
+        assert!(project_page.contains("href=\"../sessions/abc-123.html\""));
+        let index = fs::read_to_string(dir.path().join("search-index.js")).unwrap();
+        assert!(index.contains("\"href\":\"sessions/abc-123.html#m-00000001\""));
+        assert!(dir.path().join("index.html").exists());
+    }
+}
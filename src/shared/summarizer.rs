@@ -0,0 +1,352 @@
+use super::config::SummarizerConfig;
+use super::models::SearchResult;
+use super::utils::{estimate_tokens, truncate_content};
+use anyhow::{Result, anyhow, bail};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Context tokens asked of the model beyond a chunk, for the prompt and answer
+const CONTEXT_HEADROOM_TOKENS: usize = 2048;
+
+const SUMMARY_PROMPT: &str =
+    "Summarize this conversation concisely. Include: topic, key decisions, outcome.";
+
+/// Conversation text of the displayable messages, one `Role: text` line per
+/// message, split so each chunk stays within `chunk_tokens`. A message larger
+/// than a chunk is cut to fit.
+pub fn conversation_chunks(messages: &[SearchResult], chunk_tokens: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+    for message in messages.iter().filter(|m| m.is_displayable()) {
+        let content = message.content.split_whitespace().collect::<Vec<_>>();
+        let mut line = format!("{}: {}\n", message.role_display(), content.join(" "));
+        let mut tokens = estimate_tokens(&line);
+        if tokens > chunk_tokens {
+            // Tokens run about three characters at worst
+            line = format!("{}\n", truncate_content(&line, chunk_tokens * 3, true));
+            tokens = estimate_tokens(&line);
+        }
+        if current_tokens + tokens > chunk_tokens && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+        current.push_str(&line);
+        current_tokens += tokens;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Prompt summarizing a conversation that fits in one request
+pub fn summary_prompt(conversation: &str) -> String {
+    format!("{}\n\n{}", SUMMARY_PROMPT, conversation)
+}
+
+/// Prompt summarizing chunk `index` (from 0) of a conversation in `total`
+pub fn chunk_prompt(index: usize, total: usize, chunk: &str) -> String {
+    format!(
+        "This is part {} of {} of a conversation. Summarize what was asked, \
+         decided and done in this part.\n\n{}",
+        index + 1,
+        total,
+        chunk
+    )
+}
+
+/// Prompt combining the chunk summaries, in order, into one
+pub fn combine_prompt(parts: &[String]) -> String {
+    let parts: String = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| format!("Part {}:\n{}\n\n", i + 1, part))
+        .collect();
+    format!(
+        "These are summaries of consecutive parts of one conversation. {}\n\n{}",
+        SUMMARY_PROMPT, parts
+    )
+}
+
+/// Summary of a session's messages from the configured model: in one request
+/// when the conversation fits a chunk, otherwise each chunk summarized in
+/// turn and the parts combined
+pub fn summarize_messages(config: &SummarizerConfig, messages: &[SearchResult]) -> Result<String> {
+    summarize_messages_with_progress(config, messages, |_, _| {})
+}
+
+/// Like `summarize_messages`, reporting the requests done and to do after
+/// each one
+pub fn summarize_messages_with_progress(
+    config: &SummarizerConfig,
+    messages: &[SearchResult],
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<String> {
+    let chunks = conversation_chunks(messages, config.chunk_tokens);
+    match chunks.as_slice() {
+        [] => bail!("no messages to summarize"),
+        [conversation] => {
+            let summary = generate(config, &summary_prompt(conversation))?;
+            on_progress(1, 1);
+            Ok(summary)
+        }
+        _ => {
+            let total = chunks.len() + 1;
+            let mut parts = Vec::with_capacity(chunks.len());
+            for (i, chunk) in chunks.iter().enumerate() {
+                parts.push(generate(config, &chunk_prompt(i, chunks.len(), chunk))?);
+                on_progress(i + 1, total);
+            }
+            let summary = generate(config, &combine_prompt(&parts))?;
+            on_progress(total, total);
+            Ok(summary)
+        }
+    }
+}
+
+/// Split `http://host:port/prefix` into the address to connect to, the
+/// `Host` header and the path prefix
+fn parse_endpoint(endpoint: &str) -> Result<(String, String, String)> {
+    let rest = endpoint.strip_prefix("http://").ok_or_else(|| {
+        anyhow!(
+            "summarizer.endpoint must start with http:// (got {})",
+            endpoint
+        )
+    })?;
+    let (host, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if host.is_empty() {
+        bail!("summarizer.endpoint has no host: {}", endpoint);
+    }
+    let address = if host
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()))
+    {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    let prefix = prefix.trim_end_matches('/');
+    let prefix = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", prefix)
+    };
+    Ok((address, host.to_string(), prefix))
+}
+
+/// Body of a `Transfer-Encoding: chunked` response
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| anyhow!("truncated chunked response"))?;
+        let size_line = String::from_utf8_lossy(&body[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| anyhow!("bad chunk size {:?}", size_hex))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        if body.len() < size {
+            bail!("truncated chunked response");
+        }
+        decoded.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// POST a JSON body and read the whole JSON answer; the status is returned
+/// with it so Ollama's `{"error": ...}` bodies can be reported
+fn post_json(config: &SummarizerConfig, path: &str, body: &Value) -> Result<(u16, Value)> {
+    let (address, host, prefix) = parse_endpoint(&config.endpoint)?;
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("cannot resolve {}", address))?;
+    let mut stream = TcpStream::connect_timeout(&socket, timeout)
+        .map_err(|e| anyhow!("cannot reach summarizer at {}: {}", config.endpoint, e))?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let body = body.to_string();
+    write!(
+        stream,
+        "POST {}{} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        prefix,
+        path,
+        host,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("malformed HTTP response from {}", config.endpoint))?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("malformed HTTP status from {}", config.endpoint))?;
+    let chunked = head.lines().skip(1).any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+    let payload = &response[header_end + 4..];
+    let payload = if chunked {
+        dechunk(payload)?
+    } else {
+        payload.to_vec()
+    };
+    let json = serde_json::from_slice(&payload).unwrap_or(Value::Null);
+    Ok((status, json))
+}
+
+/// One completion from Ollama's `/api/generate`
+fn generate(config: &SummarizerConfig, prompt: &str) -> Result<String> {
+    let request = serde_json::json!({
+        "model": config.model,
+        "prompt": prompt,
+        "stream": false,
+        // Ollama's default context is smaller than a chunk and would drop
+        // the start of the conversation
+        "options": { "num_ctx": config.chunk_tokens + CONTEXT_HEADROOM_TOKENS },
+    });
+    let (status, answer) = post_json(config, "/api/generate", &request)?;
+    if status != 200 {
+        let reason = answer
+            .get("error")
+            .and_then(Value::as_str)
+            .unwrap_or("no error message");
+        bail!("summarizer returned HTTP {}: {}", status, reason);
+    }
+    answer
+        .get("response")
+        .and_then(Value::as_str)
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .ok_or_else(|| anyhow!("summarizer returned no text"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::models::test_message;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    /// Answers each request with the number of requests so far, the last
+    /// one chunked, and returns the prompts it received
+    fn fake_ollama(requests: usize) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut prompts = Vec::new();
+            for n in 1..=requests {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                prompts.push(request["prompt"].as_str().unwrap().to_string());
+
+                let answer = serde_json::json!({ "response": format!(" summary {} ", n) });
+                let answer = answer.to_string();
+                let response = if n == requests {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                        answer.len(),
+                        answer
+                    )
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        answer.len(),
+                        answer
+                    )
+                };
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            prompts
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn test_long_sessions_are_summarized_in_chunks_then_combined() {
+        let messages = vec![
+            test_message(0, "User", "first question about the deploy pipeline"),
+            test_message(1, "Tool", "not displayable"),
+            test_message(2, "Assistant", &"word ".repeat(40)),
+            test_message(3, "User", "and a follow-up about rolling back the release"),
+        ];
+        let chunks = conversation_chunks(&messages, 30);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("User: first question"));
+        assert!(
+            estimate_tokens(&chunks[1]) <= 30 + 1,
+            "Oversized message cut"
+        );
+        assert_eq!(conversation_chunks(&messages, 10_000).len(), 1);
+
+        let (endpoint, server) = fake_ollama(4);
+        let config = SummarizerConfig {
+            endpoint,
+            model: "llama3".to_string(),
+            chunk_tokens: 30,
+            timeout_secs: 10,
+        };
+        let mut progress = Vec::new();
+        let summary = summarize_messages_with_progress(&config, &messages, |done, total| {
+            progress.push((done, total))
+        });
+        assert_eq!(summary.unwrap(), "summary 4");
+        assert_eq!(progress, [(1, 4), (2, 4), (3, 4), (4, 4)]);
+        let prompts = server.join().unwrap();
+        assert!(prompts[0].starts_with("This is part 1 of 3"));
+        assert!(prompts[3].contains("Part 1:\nsummary 1\n\nPart 2:\nsummary 2"));
+    }
+
+    #[test]
+    fn test_endpoint_parsing() {
+        assert_eq!(
+            parse_endpoint("http://gpu-box:11434/ollama/").unwrap(),
+            (
+                "gpu-box:11434".to_string(),
+                "gpu-box:11434".to_string(),
+                "/ollama".to_string()
+            )
+        );
+        assert_eq!(
+            parse_endpoint("http://ollama.lan").unwrap().0,
+            "ollama.lan:80"
+        );
+        assert!(parse_endpoint("https://ollama.lan").is_err());
+    }
+}
@@ -1,6 +1,64 @@
+use std::borrow::Cow;
 use std::io::IsTerminal;
 use std::sync::OnceLock;
 
+/// When to emit escape sequences (OSC 8 hyperlinks)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorChoice {
+    /// Honor HYPERLINKS, NO_COLOR and CLICOLOR, then probe the terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How output is decorated; the CLI sets it from `--color` / `--no-emoji`,
+/// everything else gets the environment defaults
+#[derive(Debug, Clone, Copy)]
+pub struct OutputStyle {
+    pub hyperlinks: bool,
+    pub emoji: bool,
+}
+
+static STYLE_FLAGS: OnceLock<(ColorChoice, bool)> = OnceLock::new();
+
+/// Set the process-wide output style. Only the first call takes effect.
+pub fn init_output_style(color: ColorChoice, emoji: bool) {
+    let _ = STYLE_FLAGS.set((color, emoji));
+}
+
+pub fn output_style() -> OutputStyle {
+    let (color, emoji) = STYLE_FLAGS
+        .get()
+        .copied()
+        .unwrap_or((ColorChoice::Auto, true));
+    OutputStyle {
+        // Resolved on first use so commands that print no links never probe
+        hyperlinks: match color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => supports_hyperlinks(),
+        },
+        emoji,
+    }
+}
+
+/// NO_COLOR (any non-empty value), CLICOLOR_FORCE and CLICOLOR=0, per
+/// no-color.org and bixense.com/clicolors. None when none of them is set.
+fn color_from_env() -> Option<bool> {
+    let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+    if var("NO_COLOR").is_some() {
+        return Some(false);
+    }
+    if var("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+        return Some(true);
+    }
+    if var("CLICOLOR").is_some_and(|v| v == "0") {
+        return Some(false);
+    }
+    None
+}
+
 /// Check if terminal supports OSC 8 hyperlinks by querying it
 pub fn supports_hyperlinks() -> bool {
     static SUPPORTS: OnceLock<bool> = OnceLock::new();
@@ -10,13 +68,23 @@ pub fn supports_hyperlinks() -> bool {
             return val != "0" && val.to_lowercase() != "false";
         }
 
+        if let Some(color) = color_from_env() {
+            return color;
+        }
+
         // Must be a TTY
         if !std::io::stdout().is_terminal() || !std::io::stdin().is_terminal() {
             return false;
         }
 
-        // Query terminal with DA1 (Primary Device Attributes)
-        query_terminal_da1()
+        #[cfg(unix)]
+        {
+            query_terminal_da1()
+        }
+        #[cfg(windows)]
+        {
+            windows_terminal_hyperlinks()
+        }
     })
 }
 
@@ -71,15 +139,23 @@ fn query_terminal_da1() -> bool {
     result.unwrap_or(false)
 }
 
-/// Windows: terminal hyperlink detection not supported
+/// Windows consoles don't answer DA1 queries reliably, so recognize the
+/// terminals known to render OSC 8 from the variables they set
 #[cfg(windows)]
-fn query_terminal_da1() -> bool {
-    false
+fn windows_terminal_hyperlinks() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    // Windows Terminal
+    !var("WT_SESSION").is_empty()
+        || var("ConEmuANSI") == "ON"
+        || matches!(
+            var("TERM_PROGRAM").as_str(),
+            "vscode" | "WezTerm" | "mintty"
+        )
 }
 
-/// Create OSC 8 hyperlink if terminal supports it, otherwise plain text
+/// Create OSC 8 hyperlink if the output style allows it, otherwise plain text
 pub fn hyperlink(url: &str, text: &str) -> String {
-    if supports_hyperlinks() {
+    if output_style().hyperlinks {
         format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
     } else {
         text.to_string()
@@ -88,5 +164,85 @@ pub fn hyperlink(url: &str, text: &str) -> String {
 
 /// Create file:/// hyperlink (RFC 8089)
 pub fn file_hyperlink(path: &str, text: &str) -> String {
+    // C:\Users\me becomes file:///C:/Users/me
+    #[cfg(windows)]
+    let path = path.replace('\\', "/");
     hyperlink(&format!("file:///{}", path), text)
 }
+
+/// ASCII stand-ins for the emoji used in output. Field markers become labels,
+/// decorations on headings are dropped.
+const EMOJI_LABELS: &[(&str, &str)] = &[
+    ("📁 ", "dir: "),
+    ("🗒️ ", "session: "),
+    ("💬 ", "msg: "),
+    ("📅 ", "date: "),
+    ("📄 ", "file: "),
+    ("🌿 ", "branch: "),
+    ("🖥️ ", "host: "),
+    ("🎟️", "tags: "),
+    ("🏷️ ", "lang: "),
+    ("🔁 ", "runs: "),
+    ("👁️ ", "seen: "),
+    ("✏️", "edit:"),
+    ("👀 ", "read: "),
+    ("⏱️ ", "took: "),
+    ("🤖 ", "model: "),
+    ("🔖 ", "saved: "),
+    ("❓ ", "Q: "),
+    ("✅", "[ok]"),
+    ("❌", "[fail]"),
+    ("❔", "[?]"),
+    ("🚨", "[error]"),
+    ("⚠️", "[warn]"),
+    ("✗", "err"),
+    ("→", "->"),
+];
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x2190..=0x2BFF | 0x1F000..=0x1FAFF | 0xFE0F)
+}
+
+/// Emoji replaced by ASCII labels; unmapped ones (heading decorations) are
+/// removed along with the space after them
+fn to_ascii(text: &str) -> String {
+    let mut plain = text.to_string();
+    for (emoji, label) in EMOJI_LABELS {
+        plain = plain.replace(emoji, label);
+    }
+    let mut output = String::with_capacity(plain.len());
+    let mut chars = plain.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_emoji(c) {
+            while chars.next_if(|&c| is_emoji(c)).is_some() {}
+            chars.next_if_eq(&' ');
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// Text as the output style wants it: unchanged unless emoji are turned off
+pub fn styled(text: &str) -> Cow<'_, str> {
+    if output_style().emoji || !text.chars().any(is_emoji) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(to_ascii(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(
+            to_ascii("1. 📁 ~/src/app 🗒️ 9e1e6a58 📅 2026-01-02→2026-01-03\n   ✅ 💬 c12"),
+            "1. dir: ~/src/app session: 9e1e6a58 date: 2026-01-02->2026-01-03\n   [ok] msg: c12"
+        );
+        assert_eq!(to_ascii("📊 Overall Statistics"), "Overall Statistics");
+        assert_eq!(to_ascii("🎟️rust,docker"), "tags: rust,docker");
+    }
+}
@@ -0,0 +1,129 @@
+use super::config::get_config;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use std::str::FromStr;
+use tracing::warn;
+
+/// Zone timestamps are shown in and bare dates are read in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayZone {
+    /// The system zone, which follows $TZ
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+}
+
+impl FromStr for DisplayZone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "utc" | "z" => Ok(Self::Utc),
+            _ => s.parse::<FixedOffset>().map(Self::Fixed).map_err(|_| {
+                anyhow!(
+                    "Invalid timezone '{}': use local, utc or an offset like +02:00",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl DisplayZone {
+    /// Wall-clock time of `timestamp` in this zone
+    pub fn local(&self, timestamp: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Self::Local => timestamp.with_timezone(&Local).fixed_offset(),
+            Self::Utc => timestamp.fixed_offset(),
+            Self::Fixed(offset) => timestamp.with_timezone(offset),
+        }
+    }
+
+    /// First instant of `date` in this zone. A local midnight skipped by a
+    /// DST change is read as UTC midnight.
+    pub fn start_of_day(&self, date: NaiveDate) -> DateTime<Utc> {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        let start = match self {
+            Self::Local => Local
+                .from_local_datetime(&midnight)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+            Self::Utc => Some(Utc.from_utc_datetime(&midnight)),
+            Self::Fixed(offset) => offset
+                .from_local_datetime(&midnight)
+                .single()
+                .map(|dt| dt.with_timezone(&Utc)),
+        };
+        start.unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+    }
+}
+
+static DISPLAY_ZONE: Lazy<DisplayZone> = Lazy::new(|| {
+    let setting = &get_config().display.timezone;
+    setting.parse().unwrap_or_else(|e| {
+        warn!("{}; showing local time", e);
+        DisplayZone::Local
+    })
+});
+
+/// The `display.timezone` zone; an invalid setting falls back to local time
+pub fn display_zone() -> DisplayZone {
+    *DISPLAY_ZONE
+}
+
+/// `timestamp` in the display zone, for formatting
+pub fn local_time(timestamp: DateTime<Utc>) -> DateTime<FixedOffset> {
+    display_zone().local(timestamp)
+}
+
+/// Parse a filter date: YYYY-MM-DD as the start of that day in the display
+/// zone, or a full ISO 8601 timestamp
+pub fn parse_date(s: &str) -> Result<DateTime<Utc>> {
+    parse_date_in(s, display_zone())
+}
+
+fn parse_date_in(s: &str, zone: DisplayZone) -> Result<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(zone.start_of_day(date));
+    }
+    anyhow::bail!("Invalid date '{}': use YYYY-MM-DD or ISO 8601", s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_dates_start_in_the_display_zone() {
+        let paris: DisplayZone = "+02:00".parse().unwrap();
+        assert_eq!(
+            parse_date_in("2026-10-17", paris).unwrap().to_rfc3339(),
+            "2026-10-16T22:00:00+00:00"
+        );
+        assert_eq!(
+            parse_date_in("2026-10-17", DisplayZone::Utc)
+                .unwrap()
+                .to_rfc3339(),
+            "2026-10-17T00:00:00+00:00"
+        );
+        // Explicit offsets win over the zone
+        assert_eq!(
+            parse_date_in("2026-10-17T08:00:00-04:00", paris)
+                .unwrap()
+                .to_rfc3339(),
+            "2026-10-17T12:00:00+00:00"
+        );
+
+        let evening = parse_date_in("2026-10-16T19:30:00Z", paris).unwrap();
+        assert_eq!(
+            paris.local(evening).format("%Y-%m-%d %H:%M").to_string(),
+            "2026-10-16 21:30"
+        );
+        assert!("Europe/Paris".parse::<DisplayZone>().is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use super::path_utils::home_to_tilde;
+use std::collections::HashMap;
+
+/// Calls and failed results for one tool (or a group of tools)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ToolStats {
+    pub calls: usize,
+    pub errors: usize,
+}
+
+impl ToolStats {
+    pub fn failure_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.calls as f64 * 100.0
+        }
+    }
+}
+
+/// Tool calls from tool_use blocks and failures from tool_result errors
+#[derive(Debug, Default)]
+pub struct ToolUsageReport {
+    pub total: ToolStats,
+    pub by_tool: HashMap<String, ToolStats>,
+    /// Project path -> tool name -> stats
+    pub by_project: HashMap<String, HashMap<String, ToolStats>>,
+}
+
+impl ToolUsageReport {
+    pub fn record(&mut self, project: &str, tool: &str, calls: usize, errors: usize) {
+        let groups = [
+            &mut self.total,
+            self.by_tool.entry(tool.to_string()).or_default(),
+            self.by_project
+                .entry(project.to_string())
+                .or_default()
+                .entry(tool.to_string())
+                .or_default(),
+        ];
+        for stats in groups {
+            stats.calls += calls;
+            stats.errors += errors;
+        }
+    }
+}
+
+/// Most-called first
+fn sorted_by_calls(tools: &HashMap<String, ToolStats>) -> Vec<(&String, &ToolStats)> {
+    let mut rows: Vec<_> = tools.iter().collect();
+    rows.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then(a.0.cmp(b.0)));
+    rows
+}
+
+fn format_stats(stats: &ToolStats) -> String {
+    format!(
+        "{} calls, {} failed ({:.1}%)",
+        stats.calls,
+        stats.errors,
+        stats.failure_rate()
+    )
+}
+
+/// Format a tool usage report, shared by CLI and MCP
+pub fn format_tool_usage_report(report: &ToolUsageReport, limit: usize) -> String {
+    if report.total.calls == 0 && report.total.errors == 0 {
+        return "No tool calls recorded.\n".to_string();
+    }
+
+    let mut output = format!("🔧 Total: {}\n\nBy tool:\n", format_stats(&report.total));
+    let tools = sorted_by_calls(&report.by_tool);
+    for (tool, stats) in tools.iter().take(limit) {
+        output.push_str(&format!("  {} {}\n", tool, format_stats(stats)));
+    }
+    if tools.len() > limit {
+        output.push_str(&format!(
+            "+more: {} tools total, limit={}\n",
+            tools.len(),
+            limit
+        ));
+    }
+
+    // Projects by total calls, each with its top tools inline
+    let mut projects: Vec<_> = report
+        .by_project
+        .iter()
+        .map(|(project, tools)| {
+            let total = tools
+                .values()
+                .fold(ToolStats::default(), |acc, s| ToolStats {
+                    calls: acc.calls + s.calls,
+                    errors: acc.errors + s.errors,
+                });
+            (project, total, tools)
+        })
+        .collect();
+    projects.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then(a.0.cmp(b.0)));
+
+    output.push_str("\nBy project:\n");
+    for (project, total, tools) in projects.iter().take(limit) {
+        let top: Vec<String> = sorted_by_calls(tools)
+            .iter()
+            .take(5)
+            .map(|(tool, stats)| match stats.errors {
+                0 => format!("{} {}", tool, stats.calls),
+                errors => format!("{} {} ({} ✗)", tool, stats.calls, errors),
+            })
+            .collect();
+        output.push_str(&format!(
+            "  📁 {} {}: {}\n",
+            home_to_tilde(project),
+            format_stats(total),
+            top.join(", ")
+        ));
+    }
+    if projects.len() > limit {
+        output.push_str(&format!(
+            "+more: {} projects total, limit={}\n",
+            projects.len(),
+            limit
+        ));
+    }
+
+    output
+}
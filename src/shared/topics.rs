@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+/// What the analyzed messages are about, counted per message
+#[derive(Debug, Default)]
+pub struct TopicReport {
+    pub messages: usize,
+    pub technologies: HashMap<String, usize>,
+    pub languages: HashMap<String, usize>,
+    pub tools: HashMap<String, usize>,
+    pub projects: HashMap<String, usize>,
+}
+
+impl TopicReport {
+    /// Count one message; the lists are space-separated, as indexed
+    pub fn record(&mut self, project: &str, technologies: &str, languages: &str, tools: &str) {
+        self.messages += 1;
+        *self.projects.entry(project.to_string()).or_default() += 1;
+        for (counts, names) in [
+            (&mut self.technologies, technologies),
+            (&mut self.languages, languages),
+            (&mut self.tools, tools),
+        ] {
+            for name in names.split_whitespace() {
+                *counts.entry(name.to_string()).or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Most mentioned first, names breaking ties
+fn top(counts: &HashMap<String, usize>, limit: usize) -> Vec<(&String, &usize)> {
+    let mut rows: Vec<_> = counts.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    rows.truncate(limit);
+    rows
+}
+
+/// `topics` output, shared by the CLI and `analyze_topics`
+pub fn format_topic_report(
+    report: &TopicReport,
+    project_filter: Option<&str>,
+    dir_filter: Option<&str>,
+    limit: usize,
+) -> String {
+    let mut output = format!(
+        "Topic Analysis - {} conversations analyzed\n\n",
+        report.messages
+    );
+    if let Some(project) = project_filter {
+        output.push_str(&format!("Filtered by project: {}\n\n", project));
+    }
+    if let Some(dir) = dir_filter {
+        output.push_str(&format!("Filtered by directory: {}\n\n", dir));
+    }
+
+    let sections = [
+        ("🔧 Top Technologies:", &report.technologies),
+        ("💻 Top Programming Languages:", &report.languages),
+        ("🔨 Top Tools Mentioned:", &report.tools),
+    ];
+    for (title, counts) in sections {
+        if counts.is_empty() {
+            continue;
+        }
+        output.push_str(title);
+        output.push('\n');
+        for (name, count) in top(counts, limit) {
+            output.push_str(&format!("   {} ({})\n", name, count));
+        }
+        output.push('\n');
+    }
+
+    // A single project's breakdown would be itself
+    if project_filter.is_none() && !report.projects.is_empty() {
+        output.push_str("📂 Project Activity:\n");
+        for (project, count) in top(&report.projects, limit) {
+            output.push_str(&format!("   {} ({} conversations)\n", project, count));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_report_counts_and_ranks() {
+        let mut report = TopicReport::default();
+        report.record("web", "docker nginx", "", "");
+        report.record("web", "docker", "", "");
+        report.record("api", "rust", "", "");
+
+        let output = format_topic_report(&report, None, None, 2);
+        assert!(output.starts_with("Topic Analysis - 3 conversations analyzed"));
+        assert!(output.contains("   docker (2)\n   nginx (1)\n\n"));
+        assert!(!output.contains("rust"));
+        assert!(output.contains("   web (2 conversations)"));
+        assert!(!output.contains("Languages"));
+        assert!(!format_topic_report(&report, Some("web"), None, 2).contains("📂"));
+    }
+}
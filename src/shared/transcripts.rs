@@ -0,0 +1,410 @@
+use super::config::get_config;
+use super::metadata;
+use super::models::{ConversationEntry, MessageType};
+use super::parser::read_text_file;
+use super::utils::{file_mtime, truncate_content};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use strip_ansi_escapes::strip_str;
+
+/// `source` of entries parsed from Claude Code JSONL
+pub const CLAUDE_CODE_SOURCE: &str = "claude-code";
+/// `history_source_of` a transcript export; its entries carry their format's source
+pub const TRANSCRIPT_SOURCE: &str = "transcript";
+
+/// One conversation read out of a transcript file, before normalization
+#[derive(Debug, Default)]
+pub struct Transcript {
+    /// Conversation id, used as the session id; derived from the file when absent
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub model: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub messages: Vec<TranscriptMessage>,
+}
+
+#[derive(Debug)]
+pub struct TranscriptMessage {
+    pub id: Option<String>,
+    pub message_type: MessageType,
+    pub text: String,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// A chat export layout the indexer can read from the transcripts dir
+pub trait TranscriptFormat: Sync {
+    /// Value of the `source` field on entries from this format
+    fn source(&self) -> &'static str;
+    /// Whether a whole file's JSON is in this format
+    fn detect(&self, json: &Value) -> bool;
+    fn conversations(&self, json: &Value) -> Vec<Transcript>;
+}
+
+/// Formats tried in order on each transcript file; the first match parses it
+pub static TRANSCRIPT_FORMATS: &[&dyn TranscriptFormat] = &[&ClaudeDesktop, &OpenAiChat];
+
+/// Claude Desktop / claude.ai `conversations.json` export: one conversation
+/// object or a list of them, each with `chat_messages`
+pub struct ClaudeDesktop;
+
+impl TranscriptFormat for ClaudeDesktop {
+    fn source(&self) -> &'static str {
+        "claude-desktop"
+    }
+
+    fn detect(&self, json: &Value) -> bool {
+        objects(json).any(|c| c.get("chat_messages").is_some_and(Value::is_array))
+    }
+
+    fn conversations(&self, json: &Value) -> Vec<Transcript> {
+        objects(json)
+            .map(|conversation| Transcript {
+                id: str_field(conversation, "uuid"),
+                title: str_field(conversation, "name"),
+                model: str_field(conversation, "model"),
+                created: time_field(conversation, "created_at"),
+                messages: list(conversation, "chat_messages")
+                    .filter_map(|message| {
+                        let message_type = match message.get("sender")?.as_str()? {
+                            "human" => MessageType::User,
+                            "assistant" => MessageType::Assistant,
+                            _ => return None,
+                        };
+                        // Newer exports split the text into content blocks
+                        let blocks = content_text(message.get("content"));
+                        Some(TranscriptMessage {
+                            id: str_field(message, "uuid"),
+                            message_type,
+                            text: if blocks.trim().is_empty() {
+                                str_field(message, "text").unwrap_or_default()
+                            } else {
+                                blocks
+                            },
+                            timestamp: time_field(message, "created_at"),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Chat-completions style dump: `{"messages": [{"role", "content"}]}`, one
+/// conversation or a list of them. System and tool messages are skipped.
+pub struct OpenAiChat;
+
+impl TranscriptFormat for OpenAiChat {
+    fn source(&self) -> &'static str {
+        "openai"
+    }
+
+    fn detect(&self, json: &Value) -> bool {
+        objects(json).any(|c| {
+            c.get("messages")
+                .and_then(Value::as_array)
+                .is_some_and(|messages| messages.iter().any(|m| m.get("role").is_some()))
+        })
+    }
+
+    fn conversations(&self, json: &Value) -> Vec<Transcript> {
+        objects(json)
+            .map(|conversation| Transcript {
+                id: str_field(conversation, "id")
+                    .or_else(|| str_field(conversation, "conversation_id")),
+                title: str_field(conversation, "title"),
+                model: str_field(conversation, "model"),
+                created: time_field(conversation, "created")
+                    .or_else(|| time_field(conversation, "created_at")),
+                messages: list(conversation, "messages")
+                    .filter_map(|message| {
+                        let message_type = match message.get("role")?.as_str()? {
+                            "user" => MessageType::User,
+                            "assistant" => MessageType::Assistant,
+                            _ => return None,
+                        };
+                        Some(TranscriptMessage {
+                            id: str_field(message, "id"),
+                            message_type,
+                            text: content_text(message.get("content")),
+                            timestamp: time_field(message, "created")
+                                .or_else(|| time_field(message, "timestamp")),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Whether `path` is a transcript export rather than Claude Code JSONL
+pub fn is_transcript_file(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("json")
+}
+
+/// Parse a transcript export with the first format that recognizes it
+pub fn parse_transcript_file(path: &Path) -> Result<Vec<ConversationEntry>> {
+    let (content, _) = read_text_file(path)?;
+    let json: Value = serde_json::from_str(&content)?;
+    let format = TRANSCRIPT_FORMATS
+        .iter()
+        .find(|format| format.detect(&json))
+        .ok_or_else(|| anyhow!("Unrecognized transcript format: {}", path.display()))?;
+
+    let fallback_time = file_mtime(path)?;
+    let source_file = path.to_string_lossy().into_owned();
+    let mut entries = Vec::new();
+    for (index, transcript) in format.conversations(&json).into_iter().enumerate() {
+        let session_id = transcript
+            .id
+            .clone()
+            .unwrap_or_else(|| derived_id(&format!("{source_file}#{index}")));
+        entries.extend(normalize(
+            transcript,
+            &session_id,
+            format.source(),
+            fallback_time,
+        ));
+    }
+    for entry in &mut entries {
+        entry.source_file = Some(source_file.clone());
+    }
+    Ok(entries)
+}
+
+/// Turn a transcript into entries the way the JSONL parser builds them:
+/// messages without their own time inherit the previous one's, and the
+/// title becomes a summary ending at the last message
+fn normalize(
+    transcript: Transcript,
+    session_id: &str,
+    source: &str,
+    fallback_time: DateTime<Utc>,
+) -> Vec<ConversationEntry> {
+    let mut entries: Vec<ConversationEntry> = Vec::new();
+    let mut timestamp = transcript.created.unwrap_or(fallback_time);
+    for (index, message) in transcript.messages.into_iter().enumerate() {
+        let content = truncate_content(
+            &strip_str(&message.text),
+            get_config().limits.entry_max_chars,
+            false,
+        );
+        if content.trim().is_empty() {
+            continue;
+        }
+        timestamp = message.timestamp.unwrap_or(timestamp);
+        let model = match message.message_type {
+            MessageType::Assistant => transcript.model.clone(),
+            _ => None,
+        };
+        let uuid = message
+            .id
+            .unwrap_or_else(|| derived_id(&format!("{session_id}:{index}")));
+        let mut entry = new_entry(
+            uuid,
+            session_id,
+            source,
+            timestamp,
+            message.message_type,
+            content,
+        );
+        entry.parent_uuid = entries.last().map(|e| e.uuid.clone());
+        entry.model = model;
+        entry.sequence_num = entries.len();
+        entries.push(entry);
+    }
+
+    if let Some(title) = transcript.title.filter(|t| !t.trim().is_empty())
+        && let Some(last) = entries.last()
+    {
+        let mut summary = new_entry(
+            derived_id(&format!("summary:{session_id}")),
+            session_id,
+            source,
+            last.timestamp,
+            MessageType::Summary,
+            strip_str(&title),
+        );
+        summary.parent_uuid = Some(last.uuid.clone());
+        summary.sequence_num = entries.len();
+        entries.push(summary);
+    }
+    entries
+}
+
+/// Entry from another tool's history with content metadata filled in; the
+/// caller sets threading, model and tool activity
+pub(super) fn new_entry(
+    uuid: String,
+    session_id: &str,
+    source: &str,
+    timestamp: DateTime<Utc>,
+    message_type: MessageType,
+    content: String,
+) -> ConversationEntry {
+    let (technologies, tools_mentioned, code_languages, has_code, has_error) =
+        metadata::extract_all_metadata(&content);
+    ConversationEntry {
+        uuid,
+        parent_uuid: None,
+        session_id: session_id.to_string(),
+        // Without a working directory, group them by where they came from
+        project_path: source.to_string(),
+        timestamp,
+        message_type,
+        content,
+        model: None,
+        cwd: None,
+        sequence_num: 0,
+        is_sidechain: false,
+        agent_id: None,
+        source: source.to_string(),
+        source_host: None,
+        source_root: None,
+        git_branch: None,
+        usage: None,
+        source_file: None,
+        source_offset: 0,
+        technologies,
+        has_code,
+        code_languages,
+        has_error,
+        has_attachments: false,
+        tools_mentioned,
+        tool_calls: vec![],
+        tool_errors: vec![],
+        files_touched: vec![],
+        files_modified: vec![],
+        shell_commands: vec![],
+    }
+}
+
+/// Stable uuid for something the export gives no id, so re-indexing keeps it
+pub(super) fn derived_id(key: &str) -> String {
+    let digest = Sha256::digest(key);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_random_bytes(bytes)
+        .into_uuid()
+        .to_string()
+}
+
+/// The top-level object, or each object of a top-level list
+fn objects(json: &Value) -> Box<dyn Iterator<Item = &Value> + '_> {
+    match json {
+        Value::Array(items) => Box::new(items.iter().filter(|v| v.is_object())),
+        Value::Object(_) => Box::new(std::iter::once(json)),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+fn list<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+pub(super) fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// RFC 3339 string or Unix seconds
+pub(super) fn time_field(value: &Value, key: &str) -> Option<DateTime<Utc>> {
+    match value.get(key)? {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => {
+            let secs = n.as_f64()?;
+            DateTime::from_timestamp_millis((secs * 1000.0) as i64)
+        }
+        _ => None,
+    }
+}
+
+/// Text of a message's content: a plain string or the text of its blocks
+pub(super) fn content_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                Value::String(text) => Some(text.as_str()),
+                _ => block.get("text").and_then(Value::as_str),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Vec<ConversationEntry> {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("export.json");
+        std::fs::write(&path, json).unwrap();
+        parse_transcript_file(&path).unwrap()
+    }
+
+    #[test]
+    fn test_claude_desktop_export() {
+        let entries = parse(
+            r#"[{"uuid":"conv-1","name":"Borrow checker woes","created_at":"2026-03-01T09:00:00Z",
+                "chat_messages":[
+                  {"uuid":"m1","sender":"human","text":"Why does this not compile?","created_at":"2026-03-01T09:00:00Z"},
+                  {"uuid":"m2","sender":"assistant","text":"","content":[{"type":"text","text":"The Vec is moved into the closure."}],"created_at":"2026-03-01T09:00:05Z"}
+                ]}]"#,
+        );
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|e| e.source == "claude-desktop"));
+        assert!(entries.iter().all(|e| e.session_id == "conv-1"));
+        assert_eq!(entries[0].uuid, "m1");
+        assert!(matches!(entries[0].message_type, MessageType::User));
+        assert_eq!(entries[1].content, "The Vec is moved into the closure.");
+        assert_eq!(entries[1].parent_uuid.as_deref(), Some("m1"));
+        assert!(matches!(entries[2].message_type, MessageType::Summary));
+        assert_eq!(entries[2].content, "Borrow checker woes");
+        assert_eq!(entries[2].parent_uuid.as_deref(), Some("m2"));
+    }
+
+    #[test]
+    fn test_openai_chat_dump() {
+        let json = r#"{"model":"gpt-4o","created":1772355600,"messages":[
+              {"role":"system","content":"You are helpful."},
+              {"role":"user","content":"Write a python script that parses CSV"},
+              {"role":"assistant","content":[{"type":"text","text":"Use the csv module."}]}
+            ]}"#;
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("chat.json");
+        std::fs::write(&path, json).unwrap();
+        let entries = parse_transcript_file(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.source == "openai"));
+        assert!(matches!(entries[0].message_type, MessageType::User));
+        assert_eq!(entries[0].model, None);
+        assert_eq!(entries[1].model.as_deref(), Some("gpt-4o"));
+        assert_eq!(entries[1].content, "Use the csv module.");
+        assert_eq!(
+            entries[1].timestamp.to_rfc3339(),
+            "2026-03-01T09:00:00+00:00"
+        );
+        // No ids in the dump: derived ones are stable across parses
+        assert_eq!(entries[0].session_id, entries[1].session_id);
+        assert_eq!(
+            parse_transcript_file(&path).unwrap()[1].uuid,
+            entries[1].uuid
+        );
+
+        std::fs::write(&path, r#"{"rows":[]}"#).unwrap();
+        assert!(parse_transcript_file(&path).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use super::models::TokenUsage;
+use super::path_utils::home_to_tilde;
+use std::collections::HashMap;
+
+/// Token and cost totals for one group of API responses
+#[derive(Debug, Clone, Default)]
+pub struct UsageTotals {
+    pub responses: usize,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+    /// Responses from models without a known price, left out of `cost_usd`
+    pub unpriced: usize,
+}
+
+impl UsageTotals {
+    pub fn record(&mut self, usage: &TokenUsage, cost: Option<f64>) {
+        self.responses += 1;
+        self.usage.add(usage);
+        match cost {
+            Some(cost) => self.cost_usd += cost,
+            None => self.unpriced += 1,
+        }
+    }
+}
+
+/// Usage grouped by project path, model id and `YYYY-MM-DD` in the display zone
+#[derive(Debug, Default)]
+pub struct UsageReport {
+    pub total: UsageTotals,
+    pub by_project: HashMap<String, UsageTotals>,
+    pub by_model: HashMap<String, UsageTotals>,
+    pub by_day: HashMap<String, UsageTotals>,
+}
+
+/// 950, 12.3k, 4.5M
+fn compact_count(n: u64) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", n as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1}M", n as f64 / 1e6),
+        _ => format!("{:.1}B", n as f64 / 1e9),
+    }
+}
+
+fn format_totals(totals: &UsageTotals) -> String {
+    let usage = &totals.usage;
+    let mut line = format!(
+        "{} resp in {} out {} cache-write {} cache-read {} ${:.2}",
+        totals.responses,
+        compact_count(usage.input_tokens),
+        compact_count(usage.output_tokens),
+        compact_count(usage.cache_creation_tokens),
+        compact_count(usage.cache_read_tokens),
+        totals.cost_usd,
+    );
+    if totals.unpriced > 0 {
+        line.push_str(&format!(" ({} unpriced)", totals.unpriced));
+    }
+    line
+}
+
+/// Groups by cost, then by tokens for unpriced models
+fn sorted_by_cost(groups: &HashMap<String, UsageTotals>) -> Vec<(&String, &UsageTotals)> {
+    let mut rows: Vec<_> = groups.iter().collect();
+    rows.sort_by(|a, b| {
+        b.1.cost_usd
+            .total_cmp(&a.1.cost_usd)
+            .then(b.1.usage.total().cmp(&a.1.usage.total()))
+    });
+    rows
+}
+
+/// Format a usage report, shared by CLI and MCP. Costs are list-price estimates.
+pub fn format_usage_report(report: &UsageReport, limit: usize, days: usize) -> String {
+    if report.total.responses == 0 {
+        return "No API usage recorded.\n".to_string();
+    }
+
+    let mut output = format!("💰 Total: {}\n", format_totals(&report.total));
+
+    output.push_str("\nBy model:\n");
+    for (model, totals) in sorted_by_cost(&report.by_model) {
+        output.push_str(&format!("  🤖 {} {}\n", model, format_totals(totals)));
+    }
+
+    output.push_str("\nBy project:\n");
+    let projects = sorted_by_cost(&report.by_project);
+    for (project, totals) in projects.iter().take(limit) {
+        output.push_str(&format!(
+            "  📁 {} {}\n",
+            home_to_tilde(project),
+            format_totals(totals)
+        ));
+    }
+    if projects.len() > limit {
+        output.push_str(&format!(
+            "+more: {} projects total, limit={}\n",
+            projects.len(),
+            limit
+        ));
+    }
+
+    output.push_str("\nBy day:\n");
+    let mut day_rows: Vec<_> = report.by_day.iter().collect();
+    day_rows.sort_by(|a, b| b.0.cmp(a.0));
+    for (day, totals) in day_rows.iter().take(days) {
+        output.push_str(&format!("  📅 {} {}\n", day, format_totals(totals)));
+    }
+    if day_rows.len() > days {
+        output.push_str(&format!(
+            "+more: {} days total, days={}\n",
+            day_rows.len(),
+            days
+        ));
+    }
+
+    output
+}
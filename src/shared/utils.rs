@@ -1,30 +1,184 @@
+use super::agents::{CODEX_SOURCE, GEMINI_SOURCE};
 use super::cache::CacheManager;
 use super::config::get_config;
 use super::indexer::SearchIndexer;
 use super::lock::ExclusiveIndexAccess;
+use super::path_utils::project_dir_name;
+use super::rebuild::{index_is_current, rebuild_index};
+use super::transcripts::{CLAUDE_CODE_SOURCE, TRANSCRIPT_SOURCE, is_transcript_file};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use glob::glob;
+use std::collections::HashSet;
 use std::fs::{self};
 use std::path::{Path, PathBuf};
 use tracing::{info, warn};
 
-pub fn get_claude_dir() -> Result<PathBuf> {
-    get_config().get_claude_dir()
+pub fn get_claude_dirs() -> Result<Vec<PathBuf>> {
+    get_config().get_claude_dirs()
 }
 
 pub fn get_cache_dir() -> Result<PathBuf> {
     get_config().get_cache_dir()
 }
 
-/// Discover all JSONL files in Claude projects directory
+pub fn get_imports_dir() -> Result<PathBuf> {
+    get_config().get_imports_dir()
+}
+
+pub fn get_transcripts_dir() -> Result<PathBuf> {
+    get_config().get_transcripts_dir()
+}
+
+/// History dirs of the other coding agents enabled under `index.agents`,
+/// with the glob of their session files inside
+fn agent_history_dirs() -> Result<Vec<(PathBuf, &'static str)>> {
+    let config = get_config();
+    let mut dirs = Vec::new();
+    if config.index.agents.codex {
+        dirs.push((config.get_codex_dir()?.join("sessions"), "**/*.jsonl"));
+    }
+    if config.index.agents.gemini {
+        dirs.push((config.get_gemini_dir()?.join("tmp"), "*/chats/*.json"));
+    }
+    Ok(dirs)
+}
+
+/// Which parser reads a history file: Claude Code JSONL unless it sits in
+/// an enabled agent's history dir or is a transcript export
+pub fn history_source_of(file: &Path) -> &'static str {
+    let config = get_config();
+    let under = |dir: Result<PathBuf>| dir.is_ok_and(|dir| file.starts_with(dir));
+    if config.index.agents.codex && under(config.get_codex_dir()) {
+        CODEX_SOURCE
+    } else if config.index.agents.gemini && under(config.get_gemini_dir()) {
+        GEMINI_SOURCE
+    } else if is_transcript_file(file) {
+        TRANSCRIPT_SOURCE
+    } else {
+        CLAUDE_CODE_SOURCE
+    }
+}
+
+/// Discover all JSONL files in the projects directory of every Claude dir
+/// and in imported history, plus transcript exports and enabled agents'
+/// history, minus privacy excludes
 pub fn discover_jsonl_files() -> Result<Vec<PathBuf>> {
-    let claude_dir = get_claude_dir()?;
-    let pattern = claude_dir.join("projects/**/*.jsonl");
-    let files: Vec<PathBuf> = glob(&pattern.to_string_lossy())?.flatten().collect();
+    let mut patterns: Vec<_> = get_claude_dirs()?
+        .iter()
+        .map(|dir| dir.join("projects/**/*.jsonl"))
+        .collect();
+    patterns.push(get_imports_dir()?.join("*/**/*.jsonl"));
+    patterns.push(get_transcripts_dir()?.join("**/*.json"));
+    for (dir, files) in agent_history_dirs()? {
+        patterns.push(dir.join(files));
+    }
+
+    // Listed dirs may overlap, e.g. one mounted inside another
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for pattern in patterns {
+        files.extend(
+            glob(&pattern.to_string_lossy())?
+                .flatten()
+                .filter(|f| !is_excluded_from_index(f))
+                .filter(|f| seen.insert(f.clone())),
+        );
+    }
     Ok(files)
 }
 
+/// Claude dir, imports dir, transcripts dir or agent history dir a file
+/// was found under
+pub fn source_root_for(file: &Path) -> Option<PathBuf> {
+    let mut roots = get_claude_dirs().ok()?;
+    roots.push(get_imports_dir().ok()?);
+    roots.push(get_transcripts_dir().ok()?);
+    roots.extend(agent_history_dirs().ok()?.into_iter().map(|(dir, _)| dir));
+    // The deepest root wins when one is nested in another
+    roots
+        .into_iter()
+        .filter(|root| file.starts_with(root))
+        .max_by_key(|root| root.components().count())
+}
+
+/// Host an imported JSONL file came from (its directory under the imports dir)
+pub fn source_host_for(file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(get_imports_dir().ok()?).ok()?;
+    Some(
+        relative
+            .components()
+            .next()?
+            .as_os_str()
+            .to_str()?
+            .to_string(),
+    )
+}
+
+/// Whether `index.exclude_projects` / `index.exclude_path_globs` cover this JSONL file
+pub fn is_excluded_from_index(file: &Path) -> bool {
+    let index_config = &get_config().index;
+
+    if index_config
+        .exclude_path_globs
+        .iter()
+        .filter_map(|g| glob::Pattern::new(&expand_tilde(g)).ok())
+        .any(|p| p.matches_path(file))
+    {
+        return true;
+    }
+
+    let Some(dir_name) = project_dir_of(file) else {
+        return false;
+    };
+
+    index_config
+        .exclude_projects
+        .iter()
+        .any(|entry| project_matches(&dir_name, entry))
+}
+
+/// Claude project dir a JSONL file belongs to: the first component under
+/// projects/, or under the host directory for imported history
+fn project_dir_of(file: &Path) -> Option<String> {
+    let relative: PathBuf = match get_claude_dirs().ok().and_then(|dirs| {
+        dirs.iter()
+            .find_map(|dir| file.strip_prefix(dir.join("projects")).ok())
+    }) {
+        Some(local) => local.to_path_buf(),
+        None => file
+            .strip_prefix(get_imports_dir().ok()?)
+            .ok()?
+            .components()
+            .skip(1)
+            .collect(),
+    };
+    Some(
+        relative
+            .components()
+            .next()?
+            .as_os_str()
+            .to_str()?
+            .to_string(),
+    )
+}
+
+/// Match a Claude project dir name (`-home-me-work-client`) against an exclude entry.
+/// Dir names lose the distinction between `/`, `.` and `-`, so bare names can over-match.
+fn project_matches(dir_name: &str, entry: &str) -> bool {
+    let encoded = project_dir_name(&expand_tilde(entry));
+    dir_name == entry
+        || dir_name == encoded
+        || (!entry.contains(['/', '\\']) && dir_name.ends_with(&format!("-{}", encoded)))
+}
+
+pub fn expand_tilde(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
 /// Get file modification time as DateTime<Utc>
 pub fn file_mtime(path: &Path) -> Result<DateTime<Utc>> {
     let metadata = fs::metadata(path)?;
@@ -35,6 +189,19 @@ pub fn file_mtime(path: &Path) -> Result<DateTime<Utc>> {
     Ok(DateTime::from_timestamp(mtime, 0).unwrap_or_else(Utc::now))
 }
 
+/// `14,213`
+pub fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
 /// Truncate string at UTF-8 character boundary, optionally collapsing whitespace
 pub fn truncate_content(s: &str, max_chars: usize, collapse_whitespace: bool) -> String {
     let processed = if collapse_whitespace {
@@ -51,6 +218,111 @@ pub fn truncate_content(s: &str, max_chars: usize, collapse_whitespace: bool) ->
     }
 }
 
+/// Approximate BPE token count (cl100k-style) without a vocabulary: short words
+/// are one token and longer ones split every ~6 letters, digits group in threes,
+/// punctuation and non-ASCII characters count individually, and a single space
+/// merges into the word after it.
+pub fn estimate_tokens(text: &str) -> usize {
+    fn run_len(chars: &mut std::iter::Peekable<std::str::Chars>, pred: fn(&char) -> bool) -> usize {
+        let mut len = 1;
+        while chars.next_if(pred).is_some() {
+            len += 1;
+        }
+        len
+    }
+
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens += if c.is_ascii_alphabetic() {
+            run_len(&mut chars, char::is_ascii_alphabetic).div_ceil(6)
+        } else if c.is_ascii_digit() {
+            run_len(&mut chars, char::is_ascii_digit).div_ceil(3)
+        } else if c == ' ' && chars.peek().is_some_and(|next| !next.is_whitespace()) {
+            0
+        } else if c.is_whitespace() {
+            run_len(&mut chars, |c| c.is_whitespace());
+            1
+        } else {
+            1
+        };
+    }
+    tokens
+}
+
+/// Messages whose simhashes differ in at most this many bits are near-duplicates
+///
+/// Message-sized texts sharing most of their words land within 5 bits, while
+/// unrelated ones are 20+ apart and mixes of the same vocabulary 9+.
+pub const NEAR_DUPLICATE_BITS: u32 = 6;
+
+/// 64-bit simhash of the lowercased words of `text`, so retries and lightly
+/// edited copies land a few bits apart. Word shingles would keep word
+/// order, but one changed word then moves too many bits in a message this
+/// short. Zero for text without words, which is never a near-duplicate.
+pub fn simhash(text: &str) -> u64 {
+    // FNV-1a: stable across builds, unlike std's hasher, as it is stored
+    fn fnv1a(word: &str) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in word.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
+    }
+
+    let mut weights = [0i32; 64];
+    let mut words = 0;
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        words += 1;
+        let hash = fnv1a(&word.to_lowercase());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    if words == 0 {
+        return 0;
+    }
+    let hash = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |hash, (bit, _)| hash | 1 << bit);
+    // Keep zero meaning "no words"
+    hash.max(1)
+}
+
+/// Whether two simhashes are close enough to call the messages the same
+pub fn is_near_duplicate(a: u64, b: u64) -> bool {
+    a != 0 && b != 0 && (a ^ b).count_ones() <= NEAR_DUPLICATE_BITS
+}
+
+/// Edit distance between two words, in characters, a swap of two adjacent
+/// letters (`ngnix`) counting as one edit like in most typos
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (previous[j - 1] + cost)
+                .min(previous[j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, row);
+    }
+    previous[b.len()]
+}
+
 pub fn auto_index(index_path: &Path) -> Result<()> {
     let config = get_config();
 
@@ -69,38 +341,24 @@ pub fn auto_index(index_path: &Path) -> Result<()> {
         }
     };
 
-    let mut cache_manager = CacheManager::new(index_path)?;
-
     let mut indexer = if index_path.join("meta.json").exists() {
         // Check if existing index has correct schema
-        match SearchIndexer::validate_schema(index_path) {
+        match index_is_current(index_path) {
             Ok(true) => {
                 // Schema is valid, open existing index
                 SearchIndexer::open(index_path)?
             }
             Ok(false) => {
-                // Schema mismatch, rebuild
+                // Outdated schema, migrate by rebuilding next to the old index
                 info!("Index schema mismatch detected. Rebuilding index...");
-
-                // Remove the old index
-                if let Err(rm_err) = std::fs::remove_dir_all(index_path) {
-                    warn!("Failed to remove old index: {}", rm_err);
-                }
-
-                // Create new index
-                SearchIndexer::new(index_path)?
+                rebuild_index(index_path, discover_jsonl_files()?, |_| {})?;
+                return Ok(());
             }
             Err(e) => {
                 // Failed to validate (corrupted index), rebuild
                 warn!("Failed to validate index: {}. Rebuilding...", e);
-
-                // Remove the corrupted index
-                if let Err(rm_err) = std::fs::remove_dir_all(index_path) {
-                    warn!("Failed to remove corrupted index: {}", rm_err);
-                }
-
-                // Create new index
-                SearchIndexer::new(index_path)?
+                rebuild_index(index_path, discover_jsonl_files()?, |_| {})?;
+                return Ok(());
             }
         }
     } else {
@@ -108,7 +366,61 @@ pub fn auto_index(index_path: &Path) -> Result<()> {
         SearchIndexer::new(index_path)?
     };
 
+    // Load metadata after a possible rebuild wiped it with the old index
+    let mut cache_manager = CacheManager::new(index_path)?;
     let all_files = discover_jsonl_files()?;
     cache_manager.update_incremental(&mut indexer, all_files)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello, world!"), 4);
+        assert_eq!(estimate_tokens("use proxy_pass"), 4);
+        assert_eq!(estimate_tokens("1234567"), 3);
+        assert_eq!(estimate_tokens("a\n\n  b"), 3);
+        // Long identifiers cost more than one token, CJK roughly one per char
+        assert_eq!(estimate_tokens("internationalization"), 4);
+        assert_eq!(estimate_tokens("日本語"), 3);
+    }
+
+    #[test]
+    fn test_simhash_near_duplicates() {
+        let answer = "To fix the timeout, raise proxy_read_timeout in the nginx location block \
+                      to 300 seconds and reload nginx so the upstream has time to answer";
+        let retry = "To fix the timeout, raise proxy_read_timeout in the nginx location block \
+                     to 300 seconds and then reload nginx so the upstream has time to answer.";
+        let other = "The migration failed because the users table already has a unique \
+                     index on email, drop it before running the migration again";
+        assert_eq!(simhash(answer), simhash(&answer.to_uppercase()));
+        assert!(is_near_duplicate(simhash(answer), simhash(retry)));
+        assert!(!is_near_duplicate(simhash(answer), simhash(other)));
+        assert_eq!(simhash(" ... "), 0);
+        assert!(!is_near_duplicate(0, 0));
+    }
+
+    #[test]
+    fn test_edit_distance_counts_swaps_as_one_edit() {
+        assert_eq!(edit_distance("nginx", "nginx"), 0);
+        assert_eq!(edit_distance("ngnix", "nginx"), 1);
+        assert_eq!(edit_distance("kubectll", "kubectl"), 1);
+        assert_eq!(edit_distance("tokio", "io"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_project_matches() {
+        let dir = "-home-me-work-client-acme";
+        assert!(project_matches(dir, dir));
+        assert!(project_matches(dir, "/home/me/work/client-acme"));
+        assert!(project_matches(dir, "client-acme"));
+        assert!(project_matches(dir, "acme"));
+        assert!(!project_matches(dir, "/home/me/work"));
+        assert!(!project_matches(dir, "cme"));
+    }
+}
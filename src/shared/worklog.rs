@@ -0,0 +1,209 @@
+use super::models::SessionInfo;
+use super::path_utils::home_to_tilde;
+use super::timezone::local_time;
+use chrono::{DateTime, Datelike, TimeDelta, Utc};
+use std::collections::BTreeMap;
+
+/// A session resumed days later would otherwise count the whole gap as work
+const MAX_SESSION_DURATION: TimeDelta = TimeDelta::hours(8);
+/// Session titles listed per row, from the session with most messages
+const MAX_TOPICS: usize = 5;
+
+/// How worklog rows are bucketed in time, by the display zone's calendar
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WorklogPeriod {
+    #[default]
+    Day,
+    /// ISO week, labelled `2026-W42`
+    Week,
+    Month,
+}
+
+impl WorklogPeriod {
+    fn label(self, timestamp: DateTime<Utc>) -> String {
+        let local = local_time(timestamp);
+        match self {
+            WorklogPeriod::Day => local.format("%Y-%m-%d").to_string(),
+            WorklogPeriod::Week => {
+                let week = local.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            WorklogPeriod::Month => local.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// Work on one project in one period
+#[derive(Debug, Default, PartialEq)]
+pub struct WorklogRow {
+    pub period: String,
+    pub project: String,
+    pub sessions: usize,
+    pub messages: usize,
+    /// First to last message of each session, capped per session
+    pub duration: TimeDelta,
+    /// Titles of the sessions, most messages first
+    pub topics: Vec<String>,
+}
+
+/// Sessions started within `[after, before)` summed per period and project,
+/// oldest period first, busiest project first within it
+pub fn build_worklog(
+    sessions: &[SessionInfo],
+    period: WorklogPeriod,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Vec<WorklogRow> {
+    let mut groups: BTreeMap<(String, String), Vec<&SessionInfo>> = BTreeMap::new();
+    for session in sessions.iter().filter(|s| {
+        after.is_none_or(|after| s.first_timestamp >= after)
+            && before.is_none_or(|before| s.first_timestamp < before)
+    }) {
+        groups
+            .entry((
+                period.label(session.first_timestamp),
+                session.project_path.clone(),
+            ))
+            .or_default()
+            .push(session);
+    }
+
+    let mut rows: Vec<WorklogRow> = groups
+        .into_iter()
+        .map(|((period, project), mut sessions)| {
+            sessions.sort_by_key(|s| std::cmp::Reverse(s.message_count));
+            let mut topics: Vec<String> = Vec::new();
+            for title in sessions.iter().filter_map(|s| s.title.as_ref()) {
+                if topics.len() < MAX_TOPICS && !topics.contains(title) {
+                    topics.push(title.clone());
+                }
+            }
+            WorklogRow {
+                period,
+                project,
+                sessions: sessions.len(),
+                messages: sessions.iter().map(|s| s.message_count).sum(),
+                duration: sessions
+                    .iter()
+                    .map(|s| (s.last_timestamp - s.first_timestamp).min(MAX_SESSION_DURATION))
+                    .sum(),
+                topics,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        a.period
+            .cmp(&b.period)
+            .then(b.duration.cmp(&a.duration))
+            .then(a.project.cmp(&b.project))
+    });
+    rows
+}
+
+/// `2h 05m`, or `12m` under an hour
+fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Worklog as a Markdown table, then the totals
+pub fn format_worklog_markdown(rows: &[WorklogRow]) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut output =
+        String::from("| Period | Project | Sessions | Messages | Duration | Topics |\n");
+    output.push_str("|---|---|--:|--:|--:|---|\n");
+    for row in rows {
+        output.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            row.period,
+            cell(&home_to_tilde(&row.project)),
+            row.sessions,
+            row.messages,
+            format_duration(row.duration),
+            cell(&row.topics.join("; "))
+        ));
+    }
+    output.push_str(&format!(
+        "\n**Total**: {} sessions, {} messages, {}\n",
+        rows.iter().map(|r| r.sessions).sum::<usize>(),
+        rows.iter().map(|r| r.messages).sum::<usize>(),
+        format_duration(rows.iter().map(|r| r.duration).sum())
+    ));
+    output
+}
+
+/// Worklog as CSV with a header row, hours as a decimal for spreadsheets
+pub fn format_worklog_csv(rows: &[WorklogRow]) -> String {
+    let field = |text: &str| {
+        if text.contains([',', '"', '\n']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    };
+    let mut output = String::from("period,project,sessions,messages,hours,topics\n");
+    for row in rows {
+        output.push_str(&format!(
+            "{},{},{},{},{:.2},{}\n",
+            row.period,
+            field(&row.project),
+            row.sessions,
+            row.messages,
+            row.duration.num_minutes() as f64 / 60.0,
+            field(&row.topics.join("; "))
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn session(project: &str, start_hour: u32, minutes: i64, title: &str) -> SessionInfo {
+        let first = Utc.with_ymd_and_hms(2026, 3, 2, start_hour, 0, 0).unwrap();
+        SessionInfo {
+            session_id: format!("{}-{}", project, start_hour),
+            project_path: project.to_string(),
+            first_timestamp: first,
+            last_timestamp: first + TimeDelta::minutes(minutes),
+            message_count: minutes as usize,
+            tokens: 0,
+            title: Some(title.to_string()),
+            last_accessed: None,
+            continued_in: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_worklog_groups_sessions_by_period_and_project() {
+        let sessions = [
+            session("/work/api", 9, 30, "fix login"),
+            session("/work/api", 11, 90, "add rate limits, per user"),
+            session("/work/web", 12, 20000, "resumed much later"),
+        ];
+        let rows = build_worklog(&sessions, WorklogPeriod::Month, None, None);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].project, "/work/web", "Longest first");
+        assert_eq!(rows[0].duration, MAX_SESSION_DURATION);
+        assert_eq!(rows[1].sessions, 2);
+        assert_eq!(rows[1].duration, TimeDelta::minutes(120));
+        assert_eq!(rows[1].topics, ["add rate limits, per user", "fix login"]);
+
+        let after = Utc.with_ymd_and_hms(2026, 3, 2, 10, 0, 0).unwrap();
+        let rows = build_worklog(&sessions, WorklogPeriod::Week, Some(after), None);
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|r| r.sessions == 1));
+
+        let csv = format_worklog_csv(&rows[1..]);
+        assert!(csv.ends_with(",/work/api,1,90,1.50,\"add rate limits, per user\"\n"));
+        let markdown = format_worklog_markdown(&rows);
+        assert!(markdown.contains("| 1h 30m | add rate limits, per user |"));
+        assert!(markdown.contains("**Total**: 2 sessions, 20090 messages, 9h 30m"));
+    }
+}